@@ -0,0 +1,626 @@
+//! The Agent Client Protocol (ACP), so editors that host agents as subprocesses (e.g. Zed)
+//! can run this agent directly.
+//!
+//! `openhands-agent-server-rs acp` speaks newline-delimited JSON-RPC on stdin/stdout. Each
+//! `session/new` creates a conversation rooted at the editor's `cwd`, with the MCP servers the
+//! editor passes. During `session/prompt` the agent reports its messages, tool calls and their
+//! results (with diffs of edited files) as `session/update` notifications, and asks with
+//! `session/request_permission` before running tools that execute commands or edit files.
+
+use crate::permissions::PermissionProfile;
+use async_trait::async_trait;
+use openhands_sdk_rs::agent::tools::{default_tools, Tool};
+use openhands_sdk_rs::agent::{AgentConfig, StepObserver};
+use openhands_sdk_rs::events::{ActionEvent, Event, MessageEvent, ObservationEvent};
+use openhands_sdk_rs::mcp::{McpServerConfig, McpToolProvider};
+use openhands_sdk_rs::session::{Conversation, ConversationManager};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::AbortHandle;
+
+pub const PROTOCOL_VERSION: u64 = 1;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// A JSON-RPC error: code and message.
+type RpcError = (i64, String);
+
+/// The JSON-RPC connection to the editor.
+struct Connection {
+    out: mpsc::UnboundedSender<Value>,
+    /// Requests sent to the editor, by id, waiting for their response.
+    pending: Mutex<HashMap<u64, oneshot::Sender<Result<Value, Value>>>>,
+    next_id: AtomicU64,
+}
+
+impl Connection {
+    fn notify(&self, method: &str, params: Value) {
+        let _ = self
+            .out
+            .send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        let _ = self
+            .out
+            .send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        match rx.await {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(error)) => Err(error.to_string()),
+            Err(_) => Err("The editor disconnected".to_string()),
+        }
+    }
+
+    fn respond(&self, id: Value, result: Result<Value, RpcError>) {
+        let message = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        let _ = self.out.send(message);
+    }
+
+    /// Route the editor's response to one of our requests.
+    fn resolve(&self, message: &Value) {
+        let Some(id) = message["id"].as_u64() else {
+            return;
+        };
+        if let Some(tx) = self.pending.lock().unwrap().remove(&id) {
+            let _ = tx.send(match message.get("error") {
+                Some(error) => Err(error.clone()),
+                None => Ok(message["result"].clone()),
+            });
+        }
+    }
+}
+
+struct Session {
+    conversation: Conversation,
+    /// Keeps the session's MCP servers connected.
+    _mcp: Option<Arc<McpToolProvider>>,
+    /// Tools the user allowed for the rest of the session.
+    always_allowed: Mutex<HashSet<String>>,
+    /// The prompt being run, so `session/cancel` can stop it.
+    running: Mutex<Option<AbortHandle>>,
+}
+
+struct AcpAgent {
+    connection: Arc<Connection>,
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    /// The server's `[agent]` configuration.
+    agent_config: AgentConfig,
+    /// Decides which tools sessions' agents may use.
+    profile: PermissionProfile,
+}
+
+/// Serve ACP on stdin/stdout until the editor closes stdin. Sessions' agents run as
+/// `agent_config` says, without the tools `profile` does not allow.
+pub async fn serve_acp(
+    agent_config: AgentConfig,
+    profile: PermissionProfile,
+) -> Result<(), String> {
+    let (out, mut outgoing) = mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = outgoing.recv().await {
+            let line = format!("{}\n", message);
+            if stdout.write_all(line.as_bytes()).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let agent = Arc::new(AcpAgent {
+        connection: Arc::new(Connection {
+            out,
+            pending: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        }),
+        sessions: Mutex::new(HashMap::new()),
+        agent_config,
+        profile,
+    });
+    tracing::info!("Serving ACP over stdio");
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await.map_err(|e| e.to_string())? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::warn!("Ignoring malformed ACP message: {}", e);
+                continue;
+            }
+        };
+        if message.get("method").is_none() {
+            agent.connection.resolve(&message);
+            continue;
+        }
+        // Prompts run for a while and wait on the editor, so requests are handled
+        // concurrently.
+        let agent = agent.clone();
+        tokio::spawn(async move { agent.handle(message).await });
+    }
+
+    // The editor is gone: stop running prompts and fail requests waiting for it.
+    for session in agent.sessions.lock().unwrap().values() {
+        if let Some(running) = session.running.lock().unwrap().take() {
+            running.abort();
+        }
+    }
+    agent.connection.pending.lock().unwrap().clear();
+    drop(agent);
+    let _ = writer.await;
+    Ok(())
+}
+
+impl AcpAgent {
+    async fn handle(self: Arc<Self>, message: Value) {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => Ok(initialize()),
+            "authenticate" => Ok(json!({})),
+            "session/new" => self.new_session(&params).await,
+            "session/prompt" => self.prompt(&params).await,
+            "session/cancel" => {
+                self.cancel(&params);
+                Ok(Value::Null)
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+        };
+        // Notifications have no id and get no response.
+        if let Some(id) = message.get("id") {
+            self.connection.respond(id.clone(), result);
+        }
+    }
+
+    async fn new_session(&self, params: &Value) -> Result<Value, RpcError> {
+        let cwd = params["cwd"]
+            .as_str()
+            .map(PathBuf::from)
+            .filter(|cwd| cwd.is_absolute())
+            .ok_or((INVALID_PARAMS, "'cwd' must be an absolute path".to_string()))?;
+
+        let mut manager = ConversationManager::new().with_agent_config(self.agent_config.clone());
+        let mcp_servers = mcp_servers(&params["mcpServers"]);
+        let mcp = if mcp_servers.is_empty() {
+            None
+        } else {
+            let mcp = Arc::new(McpToolProvider::connect(&mcp_servers).await);
+            manager = manager.with_mcp(mcp.clone());
+            Some(mcp)
+        };
+        let config = self.profile.agent_config(&manager);
+        let conversation =
+            manager.create_conversation_configured("acp", String::new(), cwd, config);
+
+        let session_id = conversation.id.clone();
+        self.sessions.lock().unwrap().insert(
+            session_id.clone(),
+            Arc::new(Session {
+                conversation,
+                _mcp: mcp,
+                always_allowed: Mutex::new(HashSet::new()),
+                running: Mutex::new(None),
+            }),
+        );
+        Ok(json!({ "sessionId": session_id }))
+    }
+
+    fn session(&self, params: &Value) -> Result<(String, Arc<Session>), RpcError> {
+        let session_id = params["sessionId"].as_str().unwrap_or_default();
+        let session = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .cloned()
+            .ok_or((INVALID_PARAMS, format!("No session {}", session_id)))?;
+        Ok((session_id.to_string(), session))
+    }
+
+    async fn prompt(&self, params: &Value) -> Result<Value, RpcError> {
+        let (session_id, session) = self.session(params)?;
+        let content = prompt_text(&params["prompt"]);
+        let observer = AcpObserver {
+            connection: self.connection.clone(),
+            session_id,
+            session: session.clone(),
+            tools: default_tools(session.conversation.workspace_dir.clone()),
+            snapshots: Mutex::new(HashMap::new()),
+        };
+
+        let task = tokio::spawn(async move {
+            let conversation = observer.session.conversation.clone();
+            let mut history = conversation.history.write().await;
+            history.push(Event::Message(MessageEvent {
+                source: "user".to_string(),
+                content,
+                file_refs: Vec::new(),
+            }));
+            let mut runtime = conversation.runtime.write().await;
+            let events = conversation
+                .agent
+                .step_events_observed(&history, runtime.as_mut(), &observer)
+                .await
+                .map_err(|e| e.to_string())?;
+            history.extend(events);
+            Ok::<(), String>(())
+        });
+        *session.running.lock().unwrap() = Some(task.abort_handle());
+        let result = task.await;
+        session.running.lock().unwrap().take();
+
+        match result {
+            Ok(Ok(())) => Ok(json!({ "stopReason": "end_turn" })),
+            Ok(Err(e)) => Err((INTERNAL_ERROR, e)),
+            Err(e) if e.is_cancelled() => Ok(json!({ "stopReason": "cancelled" })),
+            Err(e) => Err((INTERNAL_ERROR, e.to_string())),
+        }
+    }
+
+    fn cancel(&self, params: &Value) {
+        if let Ok((_, session)) = self.session(params) {
+            if let Some(running) = session.running.lock().unwrap().take() {
+                running.abort();
+            }
+        }
+    }
+}
+
+fn initialize() -> Value {
+    json!({
+        "protocolVersion": PROTOCOL_VERSION,
+        "agentCapabilities": {
+            "loadSession": false,
+            "promptCapabilities": { "image": false, "audio": false, "embeddedContext": true },
+            "mcpCapabilities": { "http": true, "sse": false },
+        },
+        "authMethods": [],
+        "agentInfo": { "name": "openhands-rs", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+/// The MCP servers of `session/new`: stdio servers with `command`, HTTP servers with `url`.
+fn mcp_servers(servers: &Value) -> BTreeMap<String, McpServerConfig> {
+    let Some(servers) = servers.as_array() else {
+        return BTreeMap::new();
+    };
+    servers
+        .iter()
+        .filter_map(|server| {
+            let name = server["name"].as_str()?.to_string();
+            let strings = |key: &str| -> Vec<&Value> {
+                server[key]
+                    .as_array()
+                    .map(|a| a.iter().collect())
+                    .unwrap_or_default()
+            };
+            let config = McpServerConfig {
+                command: server["command"].as_str().map(str::to_string),
+                args: strings("args")
+                    .into_iter()
+                    .filter_map(|a| a.as_str().map(str::to_string))
+                    .collect(),
+                env: strings("env")
+                    .into_iter()
+                    .filter_map(|v| {
+                        Some((
+                            v["name"].as_str()?.to_string(),
+                            v["value"].as_str()?.to_string(),
+                        ))
+                    })
+                    .collect(),
+                url: server["url"].as_str().map(str::to_string),
+            };
+            Some((name, config))
+        })
+        .collect()
+}
+
+/// The user message for a prompt's content blocks. Linked and embedded resources are
+/// referenced by URI, with embedded text included.
+fn prompt_text(blocks: &Value) -> String {
+    let Some(blocks) = blocks.as_array() else {
+        return String::new();
+    };
+    blocks
+        .iter()
+        .filter_map(|block| match block["type"].as_str()? {
+            "text" => Some(block["text"].as_str()?.to_string()),
+            "resource_link" => {
+                let uri = block["uri"].as_str()?;
+                Some(format!(
+                    "[@{}]({})",
+                    block["name"].as_str().unwrap_or(uri),
+                    uri
+                ))
+            }
+            "resource" => {
+                let resource = &block["resource"];
+                let uri = resource["uri"].as_str()?;
+                Some(match resource["text"].as_str() {
+                    Some(text) => format!("<context ref=\"{}\">\n{}\n</context>", uri, text),
+                    None => format!("[@{}]({})", uri, uri),
+                })
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The ACP tool kind of an action, which decides its icon in the editor and whether it
+/// needs permission.
+fn tool_kind(action: &ActionEvent) -> &'static str {
+    match action.tool_name.as_str() {
+        "cmd" | "terminal" | "execute_bash" => "execute",
+        "file_editor" if action.arguments["command"] == "view" => "read",
+        "file_editor" | "apply_patch" | "write_file" => "edit",
+        "read_file" | "recall" => "read",
+        "glob" | "grep" | "semantic_search" => "search",
+        _ => "other",
+    }
+}
+
+/// Reading and searching is always allowed; everything else, including tools of MCP
+/// servers and plugins, is up to the user.
+fn needs_permission(kind: &str) -> bool {
+    !matches!(kind, "read" | "search")
+}
+
+fn tool_call(action: &ActionEvent) -> Value {
+    json!({
+        "toolCallId": action.tool_call_id,
+        "title": action.tool_name,
+        "kind": tool_kind(action),
+        "status": "pending",
+        "rawInput": action.arguments,
+    })
+}
+
+/// Reports a prompt's step to the editor.
+struct AcpObserver {
+    connection: Arc<Connection>,
+    session_id: String,
+    session: Arc<Session>,
+    /// For the files an action may change, see [`Tool::affected_paths`].
+    tools: Vec<Box<dyn Tool>>,
+    /// Content of those files before each action, by tool call id.
+    snapshots: Mutex<HashMap<String, HashMap<String, Option<String>>>>,
+}
+
+impl AcpObserver {
+    fn update(&self, update: Value) {
+        self.connection.notify(
+            "session/update",
+            json!({ "sessionId": self.session_id, "update": update }),
+        );
+    }
+
+    fn message_chunk(&self, text: &str) {
+        self.update(json!({
+            "sessionUpdate": "agent_message_chunk",
+            "content": { "type": "text", "text": text },
+        }));
+    }
+
+    /// The result of an action: its output, and a diff per edited file.
+    fn tool_call_update(&self, observation: &ObservationEvent) -> Value {
+        let before = self
+            .snapshots
+            .lock()
+            .unwrap()
+            .remove(&observation.tool_call_id)
+            .unwrap_or_default();
+        let mut content = vec![json!({
+            "type": "content",
+            "content": { "type": "text", "text": observation.content },
+        })];
+        for change in &observation.file_changes {
+            content.push(json!({
+                "type": "diff",
+                "path": change.path,
+                "oldText": before.get(&change.path).cloned().flatten(),
+                "newText": std::fs::read_to_string(&change.path).unwrap_or_default(),
+            }));
+        }
+        let failed = observation.content.starts_with("Error:");
+        json!({
+            "sessionUpdate": "tool_call_update",
+            "toolCallId": observation.tool_call_id,
+            "status": if failed { "failed" } else { "completed" },
+            "content": content,
+        })
+    }
+}
+
+#[async_trait]
+impl StepObserver for AcpObserver {
+    async fn on_event(&self, event: &Event) {
+        match event {
+            Event::Action(action) => {
+                if let Some(thought) = &action.thought {
+                    self.message_chunk(thought);
+                }
+                let paths = self
+                    .tools
+                    .iter()
+                    .find(|t| t.name() == action.tool_name)
+                    .map(|t| t.affected_paths(&action.arguments))
+                    .unwrap_or_default();
+                let snapshot = paths
+                    .iter()
+                    .map(|path| {
+                        (
+                            path.to_string_lossy().to_string(),
+                            std::fs::read_to_string(path).ok(),
+                        )
+                    })
+                    .collect();
+                self.snapshots
+                    .lock()
+                    .unwrap()
+                    .insert(action.tool_call_id.clone(), snapshot);
+
+                let mut update = tool_call(action);
+                update["sessionUpdate"] = json!("tool_call");
+                self.update(update);
+            }
+            Event::Observation(observation) => self.update(self.tool_call_update(observation)),
+            Event::Message(message) => self.message_chunk(&message.content),
+        }
+    }
+
+    async fn approve(&self, action: &ActionEvent) -> Result<(), String> {
+        let allowed = !needs_permission(tool_kind(action))
+            || self
+                .session
+                .always_allowed
+                .lock()
+                .unwrap()
+                .contains(&action.tool_name)
+            || {
+                let response = self
+                    .connection
+                    .request(
+                        "session/request_permission",
+                        json!({
+                            "sessionId": self.session_id,
+                            "toolCall": tool_call(action),
+                            "options": [
+                                { "optionId": "allow_once", "name": "Allow", "kind": "allow_once" },
+                                { "optionId": "allow_always", "name": "Always allow", "kind": "allow_always" },
+                                { "optionId": "reject_once", "name": "Reject", "kind": "reject_once" },
+                            ],
+                        }),
+                    )
+                    .await;
+                match response {
+                    Ok(response) => match response["outcome"]["optionId"].as_str() {
+                        Some("allow_once") => true,
+                        Some("allow_always") => {
+                            self.session
+                                .always_allowed
+                                .lock()
+                                .unwrap()
+                                .insert(action.tool_name.clone());
+                            true
+                        }
+                        _ => false,
+                    },
+                    Err(e) => {
+                        tracing::warn!("Permission request failed: {}", e);
+                        false
+                    }
+                }
+            };
+        let status = if allowed { "in_progress" } else { "failed" };
+        self.update(json!({
+            "sessionUpdate": "tool_call_update",
+            "toolCallId": action.tool_call_id,
+            "status": status,
+        }));
+        if allowed {
+            Ok(())
+        } else {
+            Err("The user did not allow this action".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(tool_name: &str, arguments: Value) -> ActionEvent {
+        ActionEvent {
+            source: "agent".to_string(),
+            tool_name: tool_name.to_string(),
+            tool_call_id: "call_1".to_string(),
+            arguments,
+            thought: None,
+            security_risk: None,
+            reasoning: None,
+        }
+    }
+
+    #[test]
+    fn test_prompt_and_tool_mapping() {
+        let prompt = json!([
+            { "type": "text", "text": "Explain" },
+            { "type": "resource_link", "uri": "file:///src/main.rs", "name": "main.rs" },
+            { "type": "resource", "resource": { "uri": "file:///a.txt", "text": "hello" } },
+            { "type": "image", "data": "aGk=", "mimeType": "image/png" },
+        ]);
+        assert_eq!(
+            prompt_text(&prompt),
+            "Explain\n[@main.rs](file:///src/main.rs)\n<context ref=\"file:///a.txt\">\nhello\n</context>"
+        );
+
+        let view = action("file_editor", json!({ "command": "view", "path": "a" }));
+        let edit = action("file_editor", json!({ "command": "create", "path": "a" }));
+        assert_eq!(tool_kind(&view), "read");
+        assert_eq!(tool_kind(&edit), "edit");
+        assert!(!needs_permission(tool_kind(&view)));
+        assert!(needs_permission(tool_kind(&action("cmd", json!({})))));
+        assert!(needs_permission(tool_kind(&action(
+            "search__fetch",
+            json!({})
+        ))));
+
+        let servers = mcp_servers(&json!([
+            { "name": "fs", "command": "npx", "args": ["server"], "env": [{ "name": "A", "value": "1" }] },
+            { "type": "http", "name": "web", "url": "http://localhost:8931/mcp", "headers": [] },
+        ]));
+        assert_eq!(servers["fs"].command.as_deref(), Some("npx"));
+        assert_eq!(servers["fs"].env["A"], "1");
+        assert_eq!(
+            servers["web"].url.as_deref(),
+            Some("http://localhost:8931/mcp")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connection_routes_responses() {
+        let (out, mut outgoing) = mpsc::unbounded_channel();
+        let connection = Arc::new(Connection {
+            out,
+            pending: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        });
+        let request = tokio::spawn({
+            let connection = connection.clone();
+            async move {
+                connection
+                    .request("session/request_permission", json!({}))
+                    .await
+            }
+        });
+        let sent = outgoing.recv().await.unwrap();
+        assert_eq!(sent["method"], "session/request_permission");
+        connection.resolve(
+            &json!({ "id": sent["id"], "result": { "outcome": { "outcome": "cancelled" } } }),
+        );
+        assert_eq!(
+            request.await.unwrap().unwrap()["outcome"]["outcome"],
+            "cancelled"
+        );
+    }
+}
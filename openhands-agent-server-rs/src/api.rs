@@ -0,0 +1,214 @@
+use crate::audit::AuditLog;
+use crate::auth::{User, UserStore};
+use crate::display::DisplayService;
+use crate::events::EventHub;
+use crate::permissions::{PermissionProfile, Profiles, API_RESOLVE_ISSUE};
+use crate::quotas::QuotaTracker;
+use crate::scheduler::Scheduler;
+use crate::tasks::TaskQueue;
+use crate::webhooks::WebhookState;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use openhands_sdk_rs::llm::LLMConfig;
+use openhands_sdk_rs::notifications::Notifier;
+use openhands_sdk_rs::plugins::PluginRegistry;
+use openhands_sdk_rs::repo_host::host_for_url;
+use openhands_sdk_rs::resolver::{IssueResolver, ResolveIssueRequest, ResolveIssueResult};
+use openhands_sdk_rs::runtime::bash::BashEventService;
+use openhands_sdk_rs::session::ConversationManager;
+use openhands_sdk_rs::setup::SetupConfig;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub workspace_dir: PathBuf,
+    pub display: Option<Arc<DisplayService>>,
+    pub webhooks: Arc<WebhookState>,
+    pub scheduler: Arc<Scheduler>,
+    pub tasks: Arc<TaskQueue>,
+    pub users: Arc<UserStore>,
+    pub profiles: Arc<Profiles>,
+    pub quotas: Arc<QuotaTracker>,
+    pub audit: Arc<AuditLog>,
+    pub conversations: Arc<Mutex<ConversationManager>>,
+    /// Server-wide `[setup]` for new conversations.
+    pub setup: Arc<SetupConfig>,
+    /// Issue resolutions started by `POST /api/resolve-issue`.
+    pub resolve_runs: Arc<ResolveRuns>,
+    pub plugins: Arc<PluginRegistry>,
+    pub events: Arc<EventHub>,
+    /// The commands run by the MCP tools.
+    pub bash: Arc<BashEventService>,
+}
+
+/// Build an issue resolver for the host of `repo_url`, with credentials (optional for public
+/// repositories) and notification targets from the environment.
+pub fn issue_resolver(workspace_dir: PathBuf, repo_url: &str) -> Result<IssueResolver, String> {
+    let (host, _) = host_for_url(repo_url)?;
+    Ok(
+        IssueResolver::new(host, LLMConfig::from_env(), workspace_dir)
+            .with_notifier(Notifier::from_env()),
+    )
+}
+
+/// Finished resolutions kept for `GET /api/resolve-issue/{id}`; the oldest go first.
+const MAX_FINISHED_RESOLVE_RUNS: usize = 100;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolveRunState {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// An issue resolution started by `POST /api/resolve-issue`.
+#[derive(Serialize, Debug, Clone)]
+pub struct ResolveRun {
+    pub id: String,
+    pub repo_url: String,
+    pub issue_number: u64,
+    pub state: ResolveRunState,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub result: Option<ResolveIssueResult>,
+    pub error: Option<String>,
+    #[serde(skip)]
+    owner: String,
+}
+
+/// Resolutions in progress and the most recent finished ones.
+#[derive(Default)]
+pub struct ResolveRuns {
+    runs: Mutex<HashMap<String, ResolveRun>>,
+}
+
+impl ResolveRuns {
+    async fn start(&self, owner: &str, request: &ResolveIssueRequest) -> ResolveRun {
+        let run = ResolveRun {
+            id: uuid::Uuid::new_v4().to_string(),
+            repo_url: request.repo_url.clone(),
+            issue_number: request.issue_number,
+            state: ResolveRunState::Running,
+            started_at: Utc::now(),
+            finished_at: None,
+            result: None,
+            error: None,
+            owner: owner.to_string(),
+        };
+        self.runs.lock().await.insert(run.id.clone(), run.clone());
+        run
+    }
+
+    async fn finish(&self, id: &str, result: Result<ResolveIssueResult, String>) {
+        let mut runs = self.runs.lock().await;
+        if let Some(run) = runs.get_mut(id) {
+            run.finished_at = Some(Utc::now());
+            match result {
+                Ok(result) => {
+                    run.state = ResolveRunState::Succeeded;
+                    run.result = Some(result);
+                }
+                Err(e) => {
+                    run.state = ResolveRunState::Failed;
+                    run.error = Some(e);
+                }
+            }
+        }
+        let mut finished: Vec<(DateTime<Utc>, String)> = runs
+            .values()
+            .filter_map(|run| run.finished_at.map(|at| (at, run.id.clone())))
+            .collect();
+        if finished.len() > MAX_FINISHED_RESOLVE_RUNS {
+            finished.sort();
+            for (_, id) in &finished[..finished.len() - MAX_FINISHED_RESOLVE_RUNS] {
+                runs.remove(id);
+            }
+        }
+    }
+
+    /// The run `id` if `user` started it.
+    pub async fn get(&self, user: &User, id: &str) -> Option<ResolveRun> {
+        self.runs
+            .lock()
+            .await
+            .get(id)
+            .filter(|run| run.owner == user.id)
+            .cloned()
+    }
+}
+
+/// Start resolving an issue in the background and return the run, to be polled with
+/// `GET /api/resolve-issue/{id}`.
+pub async fn resolve_issue(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Json(request): Json<ResolveIssueRequest>,
+) -> Result<(StatusCode, Json<ResolveRun>), Response> {
+    profile
+        .require_api(API_RESOLVE_ISSUE)
+        .map_err(IntoResponse::into_response)?;
+    let limits = state.quotas.limits(&profile);
+    state
+        .quotas
+        .check_spend(&user.id, &limits, Utc::now())
+        .await
+        .map_err(IntoResponse::into_response)?;
+    for target in &request.notify {
+        target
+            .validate()
+            .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    }
+    let resolver = issue_resolver(
+        profile.workspace_dir(&state.workspace_dir, &user.id),
+        &request.repo_url,
+    )
+    .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    state
+        .audit
+        .record(
+            &user,
+            "issue.resolve",
+            &format!("{}#{}", request.repo_url, request.issue_number),
+        )
+        .await;
+
+    let run = state.resolve_runs.start(&user.id, &request).await;
+    let id = run.id.clone();
+    tokio::spawn(async move {
+        let result = resolver.resolve(&request).await;
+        state
+            .quotas
+            .record_usage(&user.id, &resolver.usage(), Utc::now())
+            .await;
+        state.resolve_runs.finish(&id, result).await;
+    });
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
+
+pub async fn get_resolve_run(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Path(id): Path<String>,
+) -> Result<Json<ResolveRun>, Response> {
+    profile
+        .require_api(API_RESOLVE_ISSUE)
+        .map_err(IntoResponse::into_response)?;
+    state
+        .resolve_runs
+        .get(&user, &id)
+        .await
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Resolve run not found").into_response())
+}
@@ -0,0 +1,80 @@
+//! Append-only audit log of actions taken through the HTTP API, one JSON object per line.
+
+use crate::api::AppState;
+use crate::auth::User;
+use axum::{extract::State, Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub user_id: String,
+    pub action: String,
+    /// Id of the conversation, schedule, user, ... the action applied to.
+    pub target: String,
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Append an entry. Failures are logged and never fail the request.
+    pub async fn record(&self, user: &User, action: &str, target: &str) {
+        let entry = AuditEntry {
+            at: Utc::now(),
+            user_id: user.id.clone(),
+            action: action.to_string(),
+            target: target.to_string(),
+        };
+        let _guard = self.lock.lock().await;
+        let result = serde_json::to_string(&entry)
+            .map_err(|e| e.to_string())
+            .and_then(|line| {
+                if let Some(parent) = self.path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                    .map_err(|e| e.to_string())?;
+                writeln!(file, "{}", line).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            tracing::error!("Failed to write audit log: {}", e);
+        }
+    }
+
+    /// Entries of `user_id`, or of everyone if `None`, oldest first.
+    pub async fn entries(&self, user_id: Option<&str>) -> Vec<AuditEntry> {
+        let _guard = self.lock.lock().await;
+        let content = std::fs::read_to_string(&self.path).unwrap_or_default();
+        content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+            .filter(|e| user_id.is_none_or(|id| e.user_id == id))
+            .collect()
+    }
+}
+
+/// Admins see the whole log, other users only their own entries.
+pub async fn list_audit(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Json<Vec<AuditEntry>> {
+    let filter = (!user.admin).then_some(user.id.as_str());
+    Json(state.audit.entries(filter).await)
+}
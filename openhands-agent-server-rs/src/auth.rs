@@ -0,0 +1,339 @@
+//! Users and per-user API keys.
+//!
+//! Users and the SHA-256 hashes of their keys are stored in a JSON file. Authentication is
+//! enabled as soon as `OPENHANDS_ADMIN_KEY` is set or a user exists; until then every
+//! request runs as the built-in `local` user, which keeps single-user setups working
+//! without configuration. Requests authenticate with `Authorization: Bearer <key>`.
+//! Because the first user switches authentication on, it can only be created from the
+//! server host itself while authentication is still disabled.
+
+use crate::api::AppState;
+use crate::permissions::PermissionProfile;
+use axum::{
+    extract::{ConnectInfo, Path, Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::path::{Path as FsPath, PathBuf};
+use tokio::sync::Mutex;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct User {
+    pub id: String,
+    pub name: String,
+    /// Admins manage users and see every user's schedules and audit entries.
+    #[serde(default)]
+    pub admin: bool,
+    /// Permission profile name; defaults to `admin` for admins and `developer` otherwise.
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl User {
+    /// The implicit user when authentication is disabled.
+    fn local() -> Self {
+        Self {
+            id: "local".to_string(),
+            name: "local".to_string(),
+            admin: true,
+            profile: None,
+            created_at: DateTime::UNIX_EPOCH,
+        }
+    }
+
+    /// The user behind `OPENHANDS_ADMIN_KEY`.
+    fn bootstrap_admin() -> Self {
+        Self {
+            id: "admin".to_string(),
+            name: "admin".to_string(),
+            admin: true,
+            profile: None,
+            created_at: DateTime::UNIX_EPOCH,
+        }
+    }
+
+    pub fn profile_name(&self) -> &str {
+        match &self.profile {
+            Some(profile) => profile,
+            None if self.admin => "admin",
+            None => "developer",
+        }
+    }
+}
+
+/// Directory holding a user's workspaces.
+pub fn user_workspace_dir(workspace_root: &FsPath, user_id: &str) -> PathBuf {
+    workspace_root.join("users").join(user_id)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ApiKey {
+    user_id: String,
+    /// Hex-encoded SHA-256 of the key; the key itself is only shown once.
+    key_hash: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct UserFile {
+    users: Vec<User>,
+    keys: Vec<ApiKey>,
+}
+
+pub struct UserStore {
+    path: PathBuf,
+    admin_key: Option<String>,
+    data: Mutex<UserFile>,
+}
+
+impl UserStore {
+    pub fn load(path: PathBuf, admin_key: Option<String>) -> Result<Self, String> {
+        let data = if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?
+        } else {
+            UserFile::default()
+        };
+        Ok(Self {
+            path,
+            admin_key: admin_key.filter(|k| !k.is_empty()),
+            data: Mutex::new(data),
+        })
+    }
+
+    fn save(&self, data: &UserFile) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let content = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, content)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+
+    pub async fn enabled(&self) -> bool {
+        self.admin_key.is_some() || !self.data.lock().await.users.is_empty()
+    }
+
+    /// Create a user together with its first API key.
+    pub async fn create_user(
+        &self,
+        name: &str,
+        admin: bool,
+        profile: Option<String>,
+    ) -> Result<(User, String), String> {
+        let user = User {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            admin,
+            profile,
+            created_at: Utc::now(),
+        };
+        let mut data = self.data.lock().await;
+        data.users.push(user.clone());
+        let key = new_key(&mut data, &user.id);
+        self.save(&data)?;
+        Ok((user, key))
+    }
+
+    /// Issue an additional key for an existing user.
+    pub async fn create_key(&self, user_id: &str) -> Result<Option<String>, String> {
+        let mut data = self.data.lock().await;
+        if !data.users.iter().any(|u| u.id == user_id) {
+            return Ok(None);
+        }
+        let key = new_key(&mut data, user_id);
+        self.save(&data)?;
+        Ok(Some(key))
+    }
+
+    pub async fn authenticate(&self, key: &str) -> Option<User> {
+        if let Some(admin_key) = &self.admin_key {
+            if crate::webhooks::constant_time_eq(key.as_bytes(), admin_key.as_bytes()) {
+                return Some(User::bootstrap_admin());
+            }
+        }
+        let hash = hash_key(key);
+        let data = self.data.lock().await;
+        let key = data.keys.iter().find(|k| k.key_hash == hash)?;
+        data.users.iter().find(|u| u.id == key.user_id).cloned()
+    }
+}
+
+fn new_key(data: &mut UserFile, user_id: &str) -> String {
+    let key = format!("oh_{}", uuid::Uuid::new_v4().simple());
+    data.keys.push(ApiKey {
+        user_id: user_id.to_string(),
+        key_hash: hash_key(&key),
+        created_at: Utc::now(),
+    });
+    key
+}
+
+fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Middleware resolving the caller into [`User`] and [`PermissionProfile`] request
+/// extensions.
+pub async fn authenticate(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let user = if state.users.enabled().await {
+        let key = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        match key {
+            Some(key) => match state.users.authenticate(key.trim()).await {
+                Some(user) => user,
+                None => return (StatusCode::UNAUTHORIZED, "Invalid API key").into_response(),
+            },
+            None => return (StatusCode::UNAUTHORIZED, "Missing API key").into_response(),
+        }
+    } else {
+        User::local()
+    };
+
+    let profile = if state.users.enabled().await {
+        match state.profiles.get(user.profile_name()) {
+            Some(profile) => profile.clone(),
+            None => {
+                return (
+                    StatusCode::FORBIDDEN,
+                    format!("Unknown permission profile '{}'", user.profile_name()),
+                )
+                    .into_response()
+            }
+        }
+    } else {
+        PermissionProfile::unrestricted()
+    };
+    request.extensions_mut().insert(user);
+    request.extensions_mut().insert(profile);
+    next.run(request).await
+}
+
+#[derive(Deserialize)]
+pub struct CreateUserRequest {
+    pub name: String,
+    #[serde(default)]
+    pub admin: bool,
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreateUserResponse {
+    pub user: User,
+    pub api_key: String,
+}
+
+pub async fn me(Extension(user): Extension<User>) -> Json<User> {
+    Json(user)
+}
+
+pub async fn create_user(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    peer: Option<Extension<ConnectInfo<SocketAddr>>>,
+    Json(request): Json<CreateUserRequest>,
+) -> Result<(StatusCode, Json<CreateUserResponse>), (StatusCode, String)> {
+    if !user.admin {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only admins can create users".to_string(),
+        ));
+    }
+    // Without authentication every caller is the `local` admin, so a remote caller could
+    // otherwise claim the server by creating the first user.
+    let loopback = peer.is_some_and(|Extension(ConnectInfo(addr))| addr.ip().is_loopback());
+    if !state.users.enabled().await && !loopback {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Set OPENHANDS_ADMIN_KEY or create the first user from the server host".to_string(),
+        ));
+    }
+    if let Some(profile) = &request.profile {
+        if state.profiles.get(profile).is_none() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Unknown permission profile '{}'", profile),
+            ));
+        }
+    }
+    let (created, api_key) = state
+        .users
+        .create_user(&request.name, request.admin, request.profile)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state.audit.record(&user, "user.create", &created.id).await;
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateUserResponse {
+            user: created,
+            api_key,
+        }),
+    ))
+}
+
+/// Issue a new key for `id`. Users may create keys for themselves; admins for anyone.
+pub async fn create_key(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, String), (StatusCode, String)> {
+    if !user.admin && user.id != id {
+        return Err((StatusCode::NOT_FOUND, format!("No user {}", id)));
+    }
+    match state.users.create_key(&id).await {
+        Ok(Some(key)) => {
+            state.audit.record(&user, "api_key.create", &id).await;
+            Ok((StatusCode::CREATED, key))
+        }
+        Ok(None) => Err((StatusCode::NOT_FOUND, format!("No user {}", id))),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_user_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("users.json");
+        let store = UserStore::load(path.clone(), None).unwrap();
+        assert!(!store.enabled().await);
+
+        let (alice, key) = store.create_user("alice", false, None).await.unwrap();
+        assert!(store.enabled().await);
+        assert_eq!(store.authenticate(&key).await, Some(alice.clone()));
+        assert_eq!(store.authenticate("oh_wrong").await, None);
+
+        // Keys survive a restart and only their hashes are stored.
+        let store = UserStore::load(path.clone(), Some("bootstrap".to_string())).unwrap();
+        let second = store.create_key(&alice.id).await.unwrap().unwrap();
+        assert_eq!(store.authenticate(&second).await, Some(alice.clone()));
+        assert!(store.authenticate("bootstrap").await.unwrap().admin);
+        assert!(store.create_key("nobody").await.unwrap().is_none());
+        assert!(!std::fs::read_to_string(path).unwrap().contains(&key));
+    }
+}
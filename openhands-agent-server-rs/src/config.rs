@@ -0,0 +1,184 @@
+//! Server configuration file (`openhands.toml`).
+//!
+//! The file is optional; a missing file yields the defaults. Its location can be overridden
+//! with `OPENHANDS_CONFIG`.
+
+use crate::limits::ToolLimits;
+use crate::permissions::PermissionProfile;
+use crate::quotas::{LlmPricing, Quotas};
+use openhands_sdk_rs::agent::format::OutputFormat;
+use openhands_sdk_rs::agent::AgentConfig;
+use openhands_sdk_rs::injection::LlmInjectionClassifier;
+use openhands_sdk_rs::llm::{LLMConfig, LLM};
+use openhands_sdk_rs::mcp::McpServerConfig;
+use openhands_sdk_rs::redact::RedactionConfig;
+use openhands_sdk_rs::retention::RetentionPolicy;
+use openhands_sdk_rs::runtime::env::EnvPolicy;
+use openhands_sdk_rs::runtime::network::NetworkPolicy;
+use openhands_sdk_rs::security::{LlmSecurityAnalyzer, SecurityMode};
+use openhands_sdk_rs::session::DEFAULT_CONFIRMATION_TIMEOUT;
+use openhands_sdk_rs::setup::SetupConfig;
+use openhands_sdk_rs::shell_output::ShellOutputConfig;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct ServerConfig {
+    /// Permission profiles by name, added to (or replacing) the built-in
+    /// `viewer`, `developer` and `admin` profiles.
+    #[serde(default)]
+    pub profiles: HashMap<String, PermissionProfile>,
+    /// Default per-user limits.
+    #[serde(default)]
+    pub quotas: Quotas,
+    /// Token prices used to estimate LLM spend.
+    #[serde(default)]
+    pub llm_pricing: LlmPricing,
+    /// Commands run in every new conversation's workspace before the agent's first step.
+    #[serde(default)]
+    pub setup: SetupConfig,
+    /// External MCP servers whose tools conversations may use, by name.
+    #[serde(default)]
+    pub mcp_servers: BTreeMap<String, McpServerConfig>,
+    /// Extra secret patterns and variables to redact from events, logs and prompts.
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    /// Which server environment variables commands run by tools inherit.
+    #[serde(default)]
+    pub tool_env: EnvPolicy,
+    /// Where commands run by tools may connect to.
+    #[serde(default)]
+    pub network: NetworkPolicy,
+    /// What conversations do with high risk commands.
+    #[serde(default)]
+    pub security: SecurityConfig,
+    /// Postgres/MySQL URLs the `database` tool may query read-only, by name.
+    #[serde(default)]
+    pub databases: BTreeMap<String, String>,
+    /// How tool results are presented to agents: `markdown`, `plain` or `json`.
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    /// Replacements for the MCP file tools' messages, by key.
+    #[serde(default)]
+    pub messages: BTreeMap<String, String>,
+    /// How many MCP tool calls run at once.
+    #[serde(default)]
+    pub tool_limits: ToolLimits,
+    /// Commands of `execute_bash` and `terminal` running at once; 32 by default. Further
+    /// commands wait for one to finish.
+    #[serde(default)]
+    pub max_running_commands: Option<usize>,
+    /// How many bash events stay as files before they are archived.
+    #[serde(default)]
+    pub event_retention: RetentionPolicy,
+    /// Flush each bash event to disk before it counts as written, so events survive a
+    /// power loss. Off by default; writes are atomic either way.
+    #[serde(default)]
+    pub fsync_events: bool,
+    /// How command output is cleaned before agents read it.
+    #[serde(default)]
+    pub shell_output: ShellOutputConfig,
+    /// How conversations' agents run their steps.
+    #[serde(default)]
+    pub agent: AgentConfig,
+    /// Permission profile whose tools ACP sessions may use; all tools when unset.
+    #[serde(default)]
+    pub acp_profile: Option<String>,
+}
+
+/// `[security]`: the default security mode of conversations.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SecurityConfig {
+    #[serde(default)]
+    pub mode: SecurityMode,
+    /// Seconds a high risk action waits for confirmation before it is rejected.
+    #[serde(default = "default_confirmation_timeout_secs")]
+    pub confirmation_timeout_secs: u64,
+    /// An LLM reviewing every action on top of the built-in rules.
+    #[serde(default)]
+    pub analyzer: Option<AnalyzerConfig>,
+    /// An LLM checking tool results for prompt injection on top of the built-in patterns.
+    #[serde(default)]
+    pub injection_classifier: Option<InjectionClassifierConfig>,
+}
+
+/// `[security.analyzer]`. The API key is the agent's (`OPENAI_API_KEY`).
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AnalyzerConfig {
+    /// Defaults to `LLM_MODEL`; a small, fast model is usually enough.
+    pub model: Option<String>,
+    /// What to flag or deny, replacing the built-in policy.
+    pub policy: Option<String>,
+}
+
+impl AnalyzerConfig {
+    pub fn analyzer(&self) -> LlmSecurityAnalyzer {
+        let mut llm = LLMConfig::from_env();
+        if let Some(model) = &self.model {
+            llm.model = model.clone();
+        }
+        let analyzer = LlmSecurityAnalyzer::new(LLM::new(llm));
+        match &self.policy {
+            Some(policy) => analyzer.with_policy(policy),
+            None => analyzer,
+        }
+    }
+}
+
+/// `[security.injection_classifier]`. The API key is the agent's (`OPENAI_API_KEY`).
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct InjectionClassifierConfig {
+    /// Defaults to `LLM_MODEL`; a small, fast model is usually enough.
+    pub model: Option<String>,
+    /// What counts as an injection, replacing the built-in prompt.
+    pub prompt: Option<String>,
+}
+
+impl InjectionClassifierConfig {
+    pub fn classifier(&self) -> LlmInjectionClassifier {
+        let mut llm = LLMConfig::from_env();
+        if let Some(model) = &self.model {
+            llm.model = model.clone();
+        }
+        let classifier = LlmInjectionClassifier::new(LLM::new(llm));
+        match &self.prompt {
+            Some(prompt) => classifier.with_prompt(prompt),
+            None => classifier,
+        }
+    }
+}
+
+fn default_confirmation_timeout_secs() -> u64 {
+    DEFAULT_CONFIRMATION_TIMEOUT.as_secs()
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            mode: SecurityMode::default(),
+            confirmation_timeout_secs: default_confirmation_timeout_secs(),
+            analyzer: None,
+            injection_classifier: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Read `OPENHANDS_CONFIG`, or `openhands.toml` in `cwd`.
+    pub fn from_env(cwd: &Path) -> Result<Self, String> {
+        let path = std::env::var("OPENHANDS_CONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| cwd.join("openhands.toml"));
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,641 @@
+//! Per-user conversations over HTTP.
+//!
+//! Each conversation belongs to the user that created it and works in a workspace under
+//! the directory their permission profile allows. Other users' conversations are reported
+//! as not found.
+//!
+//! The server-wide `[setup]` of `openhands.toml`, followed by the request's own `setup`, runs
+//! in the conversation's runtime right after creation. Its output becomes the first history
+//! event, and the first message waits for it to finish. A request's own setup commands are
+//! shell commands, so only profiles allowing the `cmd` tool may send them.
+//!
+//! In `confirm` security mode a high risk action holds up its step until it is approved or
+//! rejected through `pending_actions`; clients learn about it from the `agent.action` event,
+//! which carries its `security_risk`.
+
+use crate::api::AppState;
+use crate::auth::User;
+use crate::permissions::{PermissionProfile, API_CONVERSATIONS};
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::Utc;
+use openhands_sdk_rs::activity::{ActivityReport, ConversationActivity};
+use openhands_sdk_rs::agent::StepObserver;
+use openhands_sdk_rs::bus::BusObserver;
+use openhands_sdk_rs::events::{ActionEvent, Event, LLMDelta, MessageEvent};
+use openhands_sdk_rs::export::ExportFormat;
+use openhands_sdk_rs::file_refs::{FileRef, MAX_FILE_REFS};
+use openhands_sdk_rs::models::{
+    ActionDecision, ConversationPrompt, ConversationResponse, InitConversationRequest,
+    MessageRequest, MessageResponse, PlanExecution, PlanResponse, PromptUpdate, UndoRequest,
+    UndoResponse,
+};
+use openhands_sdk_rs::outcome::TaskResult;
+use openhands_sdk_rs::profile::ProfileReport;
+use openhands_sdk_rs::runtime::baseline::FileDiff;
+use openhands_sdk_rs::runtime::ports::ExposedPort;
+use openhands_sdk_rs::security::Confirmations;
+use openhands_sdk_rs::session::Conversation;
+use serde::Deserialize;
+
+pub const DEFAULT_SYSTEM_MESSAGE: &str = "You are a helpful software engineering agent.";
+
+pub async fn create_conversation(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Json(request): Json<InitConversationRequest>,
+) -> Result<(StatusCode, Json<ConversationResponse>), Response> {
+    profile
+        .require_api(API_CONVERSATIONS)
+        .map_err(IntoResponse::into_response)?;
+    if request
+        .setup
+        .as_ref()
+        .is_some_and(|setup| !setup.is_empty())
+        && !profile.allows_tool("cmd")
+    {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "API key is not permitted to run setup commands".to_string(),
+        )
+            .into_response());
+    }
+    let mut conversations = state.conversations.lock().await;
+    state
+        .quotas
+        .check_conversations(
+            conversations.list_conversations(&user.id).len(),
+            &state.quotas.limits(&profile),
+        )
+        .map_err(IntoResponse::into_response)?;
+    // The id is only known after creation, so the workspace is named by a fresh uuid.
+    let workspace_dir = profile
+        .workspace_dir(&state.workspace_dir, &user.id)
+        .join("conversations")
+        .join(uuid::Uuid::new_v4().to_string());
+    std::fs::create_dir_all(&workspace_dir).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to create workspace: {}", e),
+        )
+            .into_response()
+    })?;
+
+    let setup = state.setup.extend(&request.setup.unwrap_or_default());
+    let config = profile.agent_config(&conversations);
+    let conversation = conversations.create_conversation_configured(
+        &user.id,
+        request
+            .system_message
+            .unwrap_or_else(|| DEFAULT_SYSTEM_MESSAGE.to_string()),
+        workspace_dir,
+        config,
+    );
+    for (name, secret) in &request.secrets {
+        conversation.add_secret(name, &secret.value, secret.expose_to_tools);
+    }
+    if let Some(mode) = request.security_mode {
+        conversation.confirmations.set_mode(mode);
+    }
+    conversation.dry_run.set_enabled(request.dry_run);
+    drop(conversations);
+    if let Err(e) = conversation.connect().await {
+        state
+            .conversations
+            .lock()
+            .await
+            .remove_conversation(&user.id, &conversation.id);
+        let _ = conversation.close().await;
+        return Err((
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to start the runtime: {}", e),
+        )
+            .into_response());
+    }
+    if !setup.is_empty() {
+        // `send_message` needs the history lock, so holding it defers the first step.
+        let mut history = conversation.history.clone().write_owned().await;
+        let workspace_dir = conversation.workspace_dir.clone();
+        let runtime = conversation.runtime.clone();
+        tokio::spawn(async move {
+            let runtime = runtime.read().await;
+            let outcome = setup.run_in(&workspace_dir, runtime.as_ref()).await;
+            history.push(Event::Observation(outcome));
+        });
+    }
+    state
+        .audit
+        .record(&user, "conversation.create", &conversation.id)
+        .await;
+    Ok((
+        StatusCode::CREATED,
+        Json(ConversationResponse {
+            id: conversation.id,
+            status: "created".to_string(),
+        }),
+    ))
+}
+
+pub async fn list_conversations(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Json<Vec<ConversationResponse>> {
+    let conversations = state.conversations.lock().await;
+    Json(
+        conversations
+            .list_conversations(&user.id)
+            .into_iter()
+            .map(|c| ConversationResponse {
+                id: c.id.clone(),
+                status: "active".to_string(),
+            })
+            .collect(),
+    )
+}
+
+/// Publishes a step's events on the bus, follows them in the conversation's activity and
+/// gates its actions on the conversation's security mode. Actions of a paused conversation
+/// wait until it is resumed.
+struct ConversationObserver<'a> {
+    events: BusObserver,
+    confirmations: &'a Confirmations,
+    activity: &'a ConversationActivity,
+}
+
+#[async_trait]
+impl StepObserver for ConversationObserver<'_> {
+    async fn on_event(&self, event: &Event) {
+        self.activity.observe(event);
+        self.events.on_event(event).await;
+    }
+
+    fn on_delta(&self, delta: &LLMDelta) {
+        self.events.on_delta(delta);
+    }
+
+    async fn approve(&self, action: &ActionEvent) -> Result<(), String> {
+        self.activity.wait_while_paused().await;
+        self.confirmations.check(action).await
+    }
+}
+
+/// The error of a turn whose step was stopped through the API.
+pub const STEP_STOPPED: &str = "The step was stopped";
+
+/// Send a user message and run the agent until it replies.
+pub async fn send_message(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Path(id): Path<String>,
+    Json(request): Json<MessageRequest>,
+) -> Result<Json<MessageResponse>, Response> {
+    profile
+        .require_api(API_CONVERSATIONS)
+        .map_err(IntoResponse::into_response)?;
+    state
+        .quotas
+        .check_spend(&user.id, &state.quotas.limits(&profile), Utc::now())
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let conversation = state
+        .conversations
+        .lock()
+        .await
+        .get_owned_conversation(&user.id, &id)
+        .cloned()
+        .ok_or_else(|| {
+            (StatusCode::NOT_FOUND, format!("No conversation {}", id)).into_response()
+        })?;
+    let file_refs = read_file_refs(&conversation, &request.file_refs)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    state.audit.record(&user, "conversation.message", &id).await;
+
+    let response = run_turn(&state, &user.id, &conversation, request.content, file_refs)
+        .await
+        .map_err(|e| {
+            let status = if e == STEP_STOPPED {
+                StatusCode::CONFLICT
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+            (status, e).into_response()
+        })?;
+    Ok(Json(MessageResponse { response }))
+}
+
+/// The workspace files a message refers to, each once.
+fn read_file_refs(conversation: &Conversation, paths: &[String]) -> Result<Vec<FileRef>, String> {
+    if paths.len() > MAX_FILE_REFS {
+        return Err(format!(
+            "A message can refer to at most {} files, not {}",
+            MAX_FILE_REFS,
+            paths.len()
+        ));
+    }
+    let mut file_refs: Vec<FileRef> = Vec::new();
+    for path in paths {
+        if file_refs.iter().all(|f| &f.path != path) {
+            file_refs.push(FileRef::read(&conversation.workspace_dir, path)?);
+        }
+    }
+    Ok(file_refs)
+}
+
+/// Add a user message to `conversation` and run the agent until it replies, publishing the
+/// step's events, charging its usage to `user_id` and recording the outcome if the agent
+/// called `finish`. A step stopped through the API fails with [`STEP_STOPPED`] and leaves
+/// only the message in the history.
+pub(crate) async fn run_turn(
+    state: &AppState,
+    user_id: &str,
+    conversation: &Conversation,
+    content: String,
+    file_refs: Vec<FileRef>,
+) -> Result<String, String> {
+    let id = &conversation.id;
+    let mut history = conversation.history.write().await;
+    let message = Event::Message(MessageEvent {
+        source: "user".to_string(),
+        content,
+        file_refs,
+    });
+    state.events.bus.publish_agent_event(id, &message);
+    history.push(message);
+    let mut runtime = conversation.runtime.write().await;
+    let usage_before = conversation.agent.usage();
+    let observer = ConversationObserver {
+        events: state.events.bus.observer(id),
+        confirmations: &conversation.confirmations,
+        activity: &conversation.activity,
+    };
+    let mut stop = conversation.activity.begin_step();
+    let result = tokio::select! {
+        result = conversation
+            .agent
+            .step_events_observed(&history, runtime.as_mut(), &observer) => {
+            result.map_err(|e| e.to_string())
+        }
+        _ = stop.requested() => Err(STEP_STOPPED.to_string()),
+    };
+    conversation.activity.end_step();
+    state
+        .quotas
+        .record_usage(
+            user_id,
+            &conversation.agent.usage().since(&usage_before),
+            Utc::now(),
+        )
+        .await;
+    match &result {
+        Err(e) if e != STEP_STOPPED => conversation.activity.record_error(e),
+        _ => {}
+    }
+    let events = result?;
+
+    let response = match events.last() {
+        Some(Event::Message(m)) => m.content.clone(),
+        _ => String::new(),
+    };
+    let start = history.len();
+    history.extend(events);
+    conversation.record_result(&history, &history[start..]);
+    Ok(response)
+}
+
+async fn owned_conversation(
+    state: &AppState,
+    user: &User,
+    id: &str,
+) -> Result<Conversation, (StatusCode, String)> {
+    state
+        .conversations
+        .lock()
+        .await
+        .get_owned_conversation(&user.id, id)
+        .cloned()
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("No conversation {}", id)))
+}
+
+/// High risk actions of the conversation awaiting confirmation.
+pub async fn list_pending_actions(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<ActionEvent>>, (StatusCode, String)> {
+    let conversation = owned_conversation(&state, &user, &id).await?;
+    Ok(Json(conversation.confirmations.pending()))
+}
+
+/// Approve or reject a pending action; the step waiting on it continues either way.
+pub async fn decide_action(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Path((id, tool_call_id)): Path<(String, String)>,
+    Json(decision): Json<ActionDecision>,
+) -> Result<StatusCode, Response> {
+    profile
+        .require_api(API_CONVERSATIONS)
+        .map_err(IntoResponse::into_response)?;
+    let conversation = owned_conversation(&state, &user, &id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    if !conversation
+        .confirmations
+        .resolve(&tool_call_id, decision.approve)
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("No pending action {}", tool_call_id),
+        )
+            .into_response());
+    }
+    let action = if decision.approve {
+        "action.approve"
+    } else {
+        "action.reject"
+    };
+    state
+        .audit
+        .record(&user, action, &format!("{}/{}", id, tool_call_id))
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// What the user's conversations are doing: their running steps, the tool calls they wait
+/// on, their token spend and their recent errors.
+pub async fn list_activity(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Json<Vec<ActivityReport>> {
+    let pricing = state.quotas.pricing();
+    let conversations = state.conversations.lock().await;
+    Json(
+        conversations
+            .list_conversations(&user.id)
+            .into_iter()
+            .map(|c| {
+                let usage = c.agent.usage();
+                c.activity.report(&c.id, usage, pricing.cost_usd(&usage))
+            })
+            .collect(),
+    )
+}
+
+/// Pause or resume the conversation, or stop its running step.
+async fn control_conversation(
+    state: &AppState,
+    user: &User,
+    profile: &PermissionProfile,
+    id: &str,
+    control: &str,
+) -> Result<StatusCode, Response> {
+    profile
+        .require_api(API_CONVERSATIONS)
+        .map_err(IntoResponse::into_response)?;
+    let conversation = owned_conversation(state, user, id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    match control {
+        "pause" => conversation.activity.pause(),
+        "resume" => conversation.activity.resume(),
+        _ => {
+            if !conversation.activity.stop() {
+                return Err((
+                    StatusCode::CONFLICT,
+                    format!("Conversation {} is not running a step", id),
+                )
+                    .into_response());
+            }
+        }
+    }
+    state
+        .audit
+        .record(user, &format!("conversation.{}", control), id)
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Let the running step finish its tool call and wait before the next one, until resumed.
+pub async fn pause_conversation(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, Response> {
+    control_conversation(&state, &user, &profile, &id, "pause").await
+}
+
+pub async fn resume_conversation(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, Response> {
+    control_conversation(&state, &user, &profile, &id, "resume").await
+}
+
+/// End the running step; its message gets a 409 reply.
+pub async fn stop_conversation(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, Response> {
+    control_conversation(&state, &user, &profile, &id, "stop").await
+}
+
+/// The outcome the agent reported with `finish`, or `null` until it has.
+pub async fn get_result(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<Json<Option<TaskResult>>, (StatusCode, String)> {
+    let conversation = owned_conversation(&state, &user, &id).await?;
+    Ok(Json(conversation.task_result()))
+}
+
+/// The files the conversation changed, with unified diffs against their state before it
+/// changed them.
+pub async fn get_changes(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<FileDiff>>, (StatusCode, String)> {
+    let conversation = owned_conversation(&state, &user, &id).await?;
+    Ok(Json(
+        conversation.baseline.changes(&conversation.workspace_dir),
+    ))
+}
+
+/// Where the conversation's time and tokens went, with the estimated LLM spend.
+pub async fn get_profile(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<Json<ProfileReport>, (StatusCode, String)> {
+    let conversation = owned_conversation(&state, &user, &id).await?;
+    let mut report = conversation.agent.profile();
+    report.cost_usd = Some(state.quotas.pricing().cost_usd(&report.llm.usage));
+    Ok(Json(report))
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// The conversation as a Markdown or HTML report.
+pub async fn export_conversation(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let conversation = owned_conversation(&state, &user, &id).await?;
+    let report = conversation.report().await;
+    Ok((
+        [(header::CONTENT_TYPE, query.format.content_type())],
+        report.render(query.format),
+    )
+        .into_response())
+}
+
+/// Whether the conversation is in dry-run mode, and the actions the agent proposed.
+pub async fn get_plan(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<Json<PlanResponse>, (StatusCode, String)> {
+    let conversation = owned_conversation(&state, &user, &id).await?;
+    Ok(Json(PlanResponse {
+        dry_run: conversation.dry_run.is_enabled(),
+        actions: conversation.dry_run.proposed(),
+    }))
+}
+
+/// Approve the proposed actions: leave dry-run mode and execute them in order.
+pub async fn execute_plan(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Path(id): Path<String>,
+) -> Result<Json<PlanExecution>, Response> {
+    profile
+        .require_api(API_CONVERSATIONS)
+        .map_err(IntoResponse::into_response)?;
+    let conversation = owned_conversation(&state, &user, &id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    state.audit.record(&user, "plan.execute", &id).await;
+    let (events, remaining) = conversation.execute_plan().await;
+    for event in &events {
+        state.events.bus.publish_agent_event(&id, event);
+    }
+    Ok(Json(PlanExecution { events, remaining }))
+}
+
+/// The system message and extensions the conversation's agent builds its prompt from.
+pub async fn get_prompt(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<Json<ConversationPrompt>, (StatusCode, String)> {
+    let conversation = owned_conversation(&state, &user, &id).await?;
+    Ok(Json(conversation.agent.prompt()))
+}
+
+/// Change the conversation's system message or extensions; the agent uses them from its
+/// next step on.
+pub async fn update_prompt(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Path(id): Path<String>,
+    Json(update): Json<PromptUpdate>,
+) -> Result<Json<ConversationPrompt>, Response> {
+    profile
+        .require_api(API_CONVERSATIONS)
+        .map_err(IntoResponse::into_response)?;
+    let conversation = owned_conversation(&state, &user, &id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    state.audit.record(&user, "prompt.update", &id).await;
+    Ok(Json(conversation.agent.update_prompt(update)))
+}
+
+/// Undo the conversation's last file changes, whichever tools made them.
+pub async fn undo_changes(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Path(id): Path<String>,
+    Json(request): Json<UndoRequest>,
+) -> Result<Json<UndoResponse>, Response> {
+    profile
+        .require_api(API_CONVERSATIONS)
+        .map_err(IntoResponse::into_response)?;
+    let conversation = owned_conversation(&state, &user, &id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    state.audit.record(&user, "changes.undo", &id).await;
+    let (undone, events) = conversation
+        .undo(request.count)
+        .await
+        .map_err(|e| (StatusCode::CONFLICT, e).into_response())?;
+    for event in &events {
+        state.events.bus.publish_agent_event(&id, event);
+    }
+    Ok(Json(UndoResponse { undone }))
+}
+
+/// Discard the proposed actions, staying in dry-run mode.
+pub async fn discard_plan(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let conversation = owned_conversation(&state, &user, &id).await?;
+    conversation.dry_run.take();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Ports the conversation's sandbox forwards to the host, with their preview URLs.
+pub async fn list_ports(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<ExposedPort>>, (StatusCode, String)> {
+    let conversation = owned_conversation(&state, &user, &id).await?;
+    let ports = conversation
+        .ports
+        .map(|ports| ports.lock().unwrap().clone())
+        .unwrap_or_default();
+    Ok(Json(ports))
+}
+
+pub async fn delete_conversation(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let conversation = state
+        .conversations
+        .lock()
+        .await
+        .remove_conversation(&user.id, &id)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("No conversation {}", id)))?;
+    if let Err(e) = conversation.close().await {
+        tracing::warn!("Failed to close the runtime of {}: {}", id, e);
+    }
+    state.audit.record(&user, "conversation.delete", &id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
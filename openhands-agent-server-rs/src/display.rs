@@ -0,0 +1,176 @@
+//! Optional virtual display for GUI tasks.
+//!
+//! With `OH_ENABLE_VNC=true` the server starts an X server (`Xvfb`) on `DISPLAY`, exposes it
+//! over VNC (`x11vnc`, on loopback only) and, if the noVNC web client is installed, in the
+//! browser through `websockify` on `NOVNC_LISTEN:NOVNC_PORT` (loopback by default). Neither
+//! asks for a password, so put an authenticating proxy in front before listening elsewhere.
+//! Programs the agent launches from the shell get `DISPLAY` and draw on it, and the
+//! `screenshot_display` tool lets the agent look at the result. The processes are killed
+//! with the server.
+
+use openhands_sdk_rs::runtime::env;
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayConfig {
+    /// X display, e.g. `:1`.
+    pub display: String,
+    /// Screen size, e.g. `1280x800`.
+    pub geometry: String,
+    pub vnc_port: u16,
+    /// Address websockify listens on, e.g. `127.0.0.1`.
+    pub novnc_listen: String,
+    pub novnc_port: u16,
+    /// noVNC web client files served by websockify.
+    pub novnc_web: String,
+}
+
+impl DisplayConfig {
+    /// `None` unless `OH_ENABLE_VNC` is `true`.
+    pub fn from_env() -> Option<Self> {
+        if std::env::var("OH_ENABLE_VNC").unwrap_or_default() != "true" {
+            return None;
+        }
+        let var = |name: &str, default: &str| std::env::var(name).unwrap_or(default.to_string());
+        Some(Self {
+            display: var("DISPLAY", ":1"),
+            geometry: var("VNC_GEOMETRY", "1280x800"),
+            vnc_port: var("VNC_PORT", "5901").parse().unwrap_or(5901),
+            novnc_listen: var("NOVNC_LISTEN", "127.0.0.1"),
+            novnc_port: var("NOVNC_PORT", "8002").parse().unwrap_or(8002),
+            novnc_web: var("NOVNC_WEB", "/usr/share/novnc"),
+        })
+    }
+
+    /// Path of the X server's socket, present once it accepts connections.
+    fn socket_path(&self) -> String {
+        format!("/tmp/.X11-unix/X{}", self.display.trim_start_matches(':'))
+    }
+}
+
+pub struct DisplayService {
+    pub config: DisplayConfig,
+    processes: Mutex<Vec<Child>>,
+}
+
+impl DisplayService {
+    /// Start the X server and the VNC/noVNC servers in front of it.
+    pub async fn start(config: DisplayConfig) -> Result<Self, String> {
+        let xvfb = spawn(Command::new("Xvfb").args([
+            config.display.as_str(),
+            "-screen",
+            "0",
+            &format!("{}x24", config.geometry),
+            "-nolisten",
+            "tcp",
+        ]))?;
+        let service = Self {
+            processes: Mutex::new(vec![xvfb]),
+            config,
+        };
+
+        let socket = service.config.socket_path();
+        let started = tokio::time::timeout(STARTUP_TIMEOUT, async {
+            while !Path::new(&socket).exists() {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        });
+        if started.await.is_err() {
+            return Err(format!(
+                "X server did not start on {}",
+                service.config.display
+            ));
+        }
+
+        let mut processes = service.processes.lock().await;
+        processes.push(spawn(Command::new("x11vnc").args([
+            "-display",
+            service.config.display.as_str(),
+            "-rfbport",
+            &service.config.vnc_port.to_string(),
+            "-localhost",
+            "-forever",
+            "-shared",
+            "-nopw",
+            "-quiet",
+        ]))?);
+        if Path::new(&service.config.novnc_web).exists() {
+            processes.push(spawn(Command::new("websockify").args([
+                "--web",
+                service.config.novnc_web.as_str(),
+                &format!(
+                    "{}:{}",
+                    service.config.novnc_listen, service.config.novnc_port
+                ),
+                &format!("localhost:{}", service.config.vnc_port),
+            ]))?);
+        } else {
+            tracing::warn!(
+                "noVNC not found at {}, serving plain VNC only",
+                service.config.novnc_web
+            );
+        }
+        drop(processes);
+        env::expose_to_all("DISPLAY", &service.config.display);
+
+        tracing::info!(
+            "Virtual display {} started (VNC port {}, noVNC port {})",
+            service.config.display,
+            service.config.vnc_port,
+            service.config.novnc_port
+        );
+        Ok(service)
+    }
+
+    /// Capture the whole screen as PNG.
+    pub async fn screenshot(&self) -> Result<Vec<u8>, String> {
+        let output = Command::new("import")
+            .args(["-display", &self.config.display, "-window", "root", "png:-"])
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run import (ImageMagick): {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Screenshot failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(output.stdout)
+    }
+}
+
+fn spawn(command: &mut Command) -> Result<Child, String> {
+    let program = command
+        .as_std()
+        .get_program()
+        .to_string_lossy()
+        .into_owned();
+    command
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", program, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_socket_path() {
+        let config = DisplayConfig {
+            display: ":1".to_string(),
+            geometry: "1280x800".to_string(),
+            vnc_port: 5901,
+            novnc_listen: "127.0.0.1".to_string(),
+            novnc_port: 8002,
+            novnc_web: "/usr/share/novnc".to_string(),
+        };
+        assert_eq!(config.socket_path(), "/tmp/.X11-unix/X1");
+    }
+}
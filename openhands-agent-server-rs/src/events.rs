@@ -0,0 +1,164 @@
+//! The server's event bus and the HTTP endpoints reading from it.
+//!
+//! Conversations publish their agent events and file changes, and the bash service its
+//! commands and output. Events are always broadcast to live subscribers and counted;
+//! `OPENHANDS_EVENT_LOG` also appends them to a JSONL file and `OPENHANDS_EVENT_WEBHOOK_URL`
+//! posts each one to a URL.
+//!
+//! Each live subscriber may fall `OPENHANDS_EVENT_BUFFER` events (default 1024) behind. A
+//! subscriber that falls further behind misses the oldest events and gets a `gap` event in
+//! their place, or with `?on_lag=disconnect` has its stream closed.
+
+use crate::api::AppState;
+use crate::auth::User;
+use crate::permissions::{PermissionProfile, API_BASH_EVENTS};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
+    Extension, Json,
+};
+use futures::{future, stream, Stream, StreamExt};
+use openhands_sdk_rs::bus::{
+    BroadcastSink, BusPayload, DropPolicy, EventBus, JsonlSink, MetricsSink, WebhookSink,
+};
+use openhands_sdk_rs::models::{BashEventPage, BashEventQuery};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const DEFAULT_BUFFER: usize = 1024;
+
+pub struct EventHub {
+    pub bus: Arc<EventBus>,
+    pub stream: Arc<BroadcastSink>,
+    pub metrics: Arc<MetricsSink>,
+}
+
+impl EventHub {
+    pub fn from_env() -> Result<Self, String> {
+        let buffer = match env::var("OPENHANDS_EVENT_BUFFER") {
+            Ok(buffer) => buffer
+                .parse::<usize>()
+                .ok()
+                .filter(|buffer| *buffer > 0)
+                .ok_or_else(|| format!("Invalid OPENHANDS_EVENT_BUFFER: {}", buffer))?,
+            Err(_) => DEFAULT_BUFFER,
+        };
+        let bus = EventBus::new();
+        let stream = Arc::new(BroadcastSink::new(buffer));
+        let metrics = Arc::new(MetricsSink::default());
+        bus.add_sink(stream.clone());
+        bus.add_sink(metrics.clone());
+        if let Ok(path) = env::var("OPENHANDS_EVENT_LOG") {
+            bus.add_sink(Arc::new(JsonlSink::new(PathBuf::from(path))));
+        }
+        if let Ok(url) = env::var("OPENHANDS_EVENT_WEBHOOK_URL") {
+            bus.add_sink(Arc::new(WebhookSink::new(&url)));
+        }
+        Ok(Self {
+            bus,
+            stream,
+            metrics,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    #[serde(default)]
+    pub on_lag: DropPolicy,
+}
+
+/// Events of a conversation as server-sent events, from the time of the request on.
+pub async fn stream_events(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+    Query(query): Query<StreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, (StatusCode, String)> {
+    if state
+        .conversations
+        .lock()
+        .await
+        .get_owned_conversation(&user.id, &id)
+        .is_none()
+    {
+        return Err((StatusCode::NOT_FOUND, format!("No conversation {}", id)));
+    }
+    let subscription = state.events.stream.subscribe(query.on_lag);
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.recv().await?;
+        Some((event, subscription))
+    })
+    .filter(move |event| {
+        let ours = event.conversation_id.as_deref() == Some(id.as_str());
+        // Gaps may hide this conversation's events, so every subscriber gets them.
+        future::ready(ours || matches!(event.payload, BusPayload::Gap { .. }))
+    })
+    .map(|event| {
+        Ok(SseEvent::default()
+            .event(event.kind())
+            .json_data(&event)
+            .unwrap_or_default())
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Serialize)]
+pub struct EventMetrics {
+    /// Events published since the server started, by kind.
+    pub published: BTreeMap<&'static str, u64>,
+    /// Events live subscribers missed because they fell behind.
+    pub dropped: u64,
+    pub subscribers: usize,
+}
+
+/// The server's bash commands and their output matching the query, oldest first.
+pub async fn search_bash_events(
+    State(state): State<AppState>,
+    Extension(profile): Extension<PermissionProfile>,
+    Query(query): Query<BashEventQuery>,
+) -> Result<Json<BashEventPage>, (StatusCode, String)> {
+    profile.require_api(API_BASH_EVENTS)?;
+    let bash = state.bash.clone();
+    tokio::task::spawn_blocking(move || bash.search_bash_events(&query))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+pub async fn event_metrics(State(state): State<AppState>) -> Json<EventMetrics> {
+    Json(EventMetrics {
+        published: state.events.metrics.counts(),
+        dropped: state.events.stream.dropped(),
+        subscribers: state.events.stream.subscribers(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openhands_sdk_rs::models::BashEventKind;
+
+    #[test]
+    fn test_bash_event_query_from_url() {
+        let uri = "http://localhost/api/bash/events?failed=true&within_secs=600&kind=BashOutput&since=2025-06-01T12:00:00Z&command=cargo"
+            .parse()
+            .unwrap();
+        let Query(query) = Query::<BashEventQuery>::try_from_uri(&uri).unwrap();
+        assert!(query.failed);
+        assert_eq!(query.within_secs, Some(600));
+        assert_eq!(query.kind, Some(BashEventKind::BashOutput));
+        assert_eq!(
+            query.since.unwrap().to_rfc3339(),
+            "2025-06-01T12:00:00+00:00"
+        );
+        assert_eq!(query.command.as_deref(), Some("cargo"));
+        assert_eq!(query.command_id, None);
+    }
+}
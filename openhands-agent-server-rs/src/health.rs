@@ -0,0 +1,131 @@
+//! Readiness checks.
+//!
+//! `/health` only reports that the process is up. `/readyz` actively checks the
+//! dependencies an agent run needs and answers 503 with per-component details if any of
+//! them is unavailable.
+
+use crate::api::AppState;
+use axum::{extract::State, http::StatusCode, Json};
+use openhands_sdk_rs::llm::{LLMConfig, LLM};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+const DOCKER_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentStatus {
+    Ok,
+    Error,
+    /// Not needed with the current configuration.
+    Skipped,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ComponentHealth {
+    pub status: ComponentStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl ComponentHealth {
+    fn from_result(result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Self {
+                status: ComponentStatus::Ok,
+                detail: None,
+            },
+            Err(e) => Self {
+                status: ComponentStatus::Error,
+                detail: Some(e),
+            },
+        }
+    }
+
+    fn skipped(reason: &str) -> Self {
+        Self {
+            status: ComponentStatus::Skipped,
+            detail: Some(reason.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Readiness {
+    pub ready: bool,
+    pub components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+pub async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let (llm, workspace, docker) = tokio::join!(
+        async { ComponentHealth::from_result(check_llm().await) },
+        async { ComponentHealth::from_result(check_workspace(&state.workspace_dir)) },
+        check_docker(),
+    );
+    let components = BTreeMap::from([("llm", llm), ("workspace", workspace), ("docker", docker)]);
+    let ready = components
+        .values()
+        .all(|c| c.status != ComponentStatus::Error);
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(Readiness { ready, components }))
+}
+
+async fn check_llm() -> Result<(), String> {
+    LLM::new(LLMConfig::from_env()).check_connectivity().await
+}
+
+/// Create and remove a file in the workspace.
+pub fn check_workspace(workspace_dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(workspace_dir)
+        .map_err(|e| format!("Cannot create {}: {}", workspace_dir.display(), e))?;
+    let probe = workspace_dir.join(format!(".readyz-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&probe, b"ok")
+        .map_err(|e| format!("{} is not writable: {}", workspace_dir.display(), e))?;
+    std::fs::remove_file(&probe).map_err(|e| format!("Cannot remove {}: {}", probe.display(), e))
+}
+
+/// The Docker daemon is only required when conversations run in `DockerRuntime`.
+async fn check_docker() -> ComponentHealth {
+    if std::env::var("RUNTIME_ENV").unwrap_or_default() != "docker" {
+        return ComponentHealth::skipped("RUNTIME_ENV is not docker");
+    }
+    let info = tokio::process::Command::new("docker")
+        .args(["info", "--format", "{{.ServerVersion}}"])
+        .kill_on_drop(true)
+        .output();
+    let result = match tokio::time::timeout(DOCKER_TIMEOUT, info).await {
+        Err(_) => Err("Timed out waiting for the Docker daemon".to_string()),
+        Ok(Err(e)) => Err(format!("Failed to run docker: {}", e)),
+        Ok(Ok(output)) if !output.status.success() => Err(format!(
+            "Docker daemon unavailable: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+        Ok(Ok(_)) => Ok(()),
+    };
+    ComponentHealth::from_result(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path().join("workspace");
+        assert!(check_workspace(&workspace).is_ok());
+        assert_eq!(std::fs::read_dir(&workspace).unwrap().count(), 0);
+
+        let file = temp_dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(check_workspace(&file.join("workspace")).is_err());
+    }
+}
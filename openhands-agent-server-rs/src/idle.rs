@@ -0,0 +1,63 @@
+//! Activity tracking and optional shutdown after a period of idleness.
+//!
+//! Every API, MCP and webhook request counts as activity (health checks do not), as does
+//! every agent step, including those of background tasks, scheduled runs and issue
+//! resolutions, for as long as it runs. With `OPENHANDS_IDLE_SHUTDOWN_SECS` set, the server shuts down
+//! gracefully once it has been idle that long, which lets cloud sandboxes stop billing.
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use openhands_sdk_rs::system;
+use std::time::Duration;
+
+/// Middleware recording the request as activity; the server is never idle while one is
+/// being handled.
+pub async fn track_activity(request: Request, next: Next) -> Response {
+    let _busy = system::busy();
+    next.run(request).await
+}
+
+/// Idle timeout from `OPENHANDS_IDLE_SHUTDOWN_SECS`, if set.
+pub fn timeout_from_env() -> Result<Option<Duration>, String> {
+    match std::env::var("OPENHANDS_IDLE_SHUTDOWN_SECS") {
+        Ok(secs) => secs
+            .parse()
+            .map(|secs| Some(Duration::from_secs(secs)))
+            .map_err(|_| format!("Invalid OPENHANDS_IDLE_SHUTDOWN_SECS: {}", secs)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Resolve once nothing has happened for `timeout` and nothing is running.
+pub async fn wait_until_idle(timeout: Duration) {
+    loop {
+        let idle = Duration::from_secs_f64(system::idle_time());
+        if idle >= timeout && !system::is_busy() {
+            tracing::info!("Idle for {:?}, shutting down", idle);
+            return;
+        }
+        tokio::time::sleep(timeout.saturating_sub(idle).max(Duration::from_secs(1))).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_running_work_is_not_idle() {
+        let request = system::busy();
+        let wait = tokio::time::timeout(
+            Duration::from_millis(1500),
+            wait_until_idle(Duration::from_millis(10)),
+        );
+        assert!(wait.await.is_err());
+
+        drop(request);
+        tokio::time::timeout(
+            Duration::from_secs(3),
+            wait_until_idle(Duration::from_millis(10)),
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+//! `GET /server_info`: what this server offers, so clients can negotiate capabilities
+//! instead of assuming them.
+
+use crate::api::AppState;
+use crate::permissions::PermissionProfile;
+use crate::service::OpenHandsService;
+use crate::tools::descriptions::describe_tools;
+use axum::{extract::State, Extension, Json};
+use openhands_sdk_rs::models::{ServerInfo, ToolInfo};
+use openhands_sdk_rs::system::{get_system_info, SystemInfo};
+use std::path::Path;
+
+const TRANSPORTS: &[&str] = &["http", "mcp"];
+
+pub async fn server_info(
+    State(state): State<AppState>,
+    Extension(profile): Extension<PermissionProfile>,
+) -> Json<ServerInfo> {
+    let system = get_system_info().await;
+    Json(build_server_info(
+        &state.workspace_dir,
+        state.display.is_some(),
+        &profile,
+        system,
+    ))
+}
+
+/// The server description as seen by a caller with `profile`: only tools it may call are
+/// listed.
+pub fn build_server_info(
+    workspace_dir: &Path,
+    display_enabled: bool,
+    profile: &PermissionProfile,
+    system: SystemInfo,
+) -> ServerInfo {
+    let runtime = match std::env::var("RUNTIME_ENV").as_deref() {
+        Ok("docker") => "docker",
+        _ => "local",
+    };
+    ServerInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        transports: TRANSPORTS.iter().map(|t| t.to_string()).collect(),
+        runtime: runtime.to_string(),
+        workspace_dir: workspace_dir.display().to_string(),
+        tools: describe_tools(
+            OpenHandsService::tool_definitions(display_enabled),
+            workspace_dir,
+        )
+        .into_iter()
+        .filter(|tool| profile.allows_tool(&tool.name))
+        .map(|tool| ToolInfo {
+            name: tool.name.to_string(),
+            description: tool.description.map(|d| d.to_string()),
+            input_schema: serde_json::Value::Object(tool.input_schema.as_ref().clone()),
+        })
+        .collect(),
+        system,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permissions::Profiles;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_server_info_lists_permitted_tools() {
+        let system = get_system_info().await;
+        let info = build_server_info(
+            Path::new("/ws"),
+            false,
+            &PermissionProfile::unrestricted(),
+            system.clone(),
+        );
+        assert_eq!(info.workspace_dir, "/ws");
+        let bash = info
+            .tools
+            .iter()
+            .find(|t| t.name == "execute_bash")
+            .unwrap();
+        assert!(bash.input_schema["properties"]["command"].is_object());
+        assert!(!info.tools.iter().any(|t| t.name == "screenshot_display"));
+
+        let profiles = Profiles::new(HashMap::new());
+        let info = build_server_info(
+            Path::new("/ws"),
+            true,
+            profiles.get("viewer").unwrap(),
+            system,
+        );
+        let mut names: Vec<_> = info.tools.iter().map(|t| t.name.as_str()).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "environment_info",
+                "glob",
+                "grep",
+                "list_files",
+                "query_data",
+                "read_file",
+                "screenshot_display",
+                "stat_file"
+            ]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! How many MCP tool calls run at once.
+//!
+//! Every call takes a slot of its tool, if `[tool_limits.per_tool]` limits it, and one of
+//! the server's, if `max_concurrent` is set. A call that finds no free slot waits for one,
+//! in order, for up to `queue_timeout_secs`, and then fails with a "server busy" error
+//! instead of piling more work on the sandbox.
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+/// `[tool_limits]`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ToolLimits {
+    /// Calls of any tool at once; unlimited without it.
+    #[serde(default)]
+    pub max_concurrent: Option<usize>,
+    /// Calls of a tool at once, by tool name. `terminal` counts as `execute_bash`.
+    #[serde(default)]
+    pub per_tool: BTreeMap<String, usize>,
+    /// Seconds a call waits for a slot before it fails.
+    #[serde(default = "default_queue_timeout_secs")]
+    pub queue_timeout_secs: u64,
+}
+
+fn default_queue_timeout_secs() -> u64 {
+    30
+}
+
+impl Default for ToolLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent: None,
+            per_tool: BTreeMap::new(),
+            queue_timeout_secs: default_queue_timeout_secs(),
+        }
+    }
+}
+
+/// `terminal` runs its commands through `execute_bash`, so they share a limit.
+fn limit_key(tool: &str) -> &str {
+    match tool {
+        "terminal" => "execute_bash",
+        _ => tool,
+    }
+}
+
+struct Slots {
+    semaphore: Arc<Semaphore>,
+    size: usize,
+}
+
+impl Slots {
+    fn new(size: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(size)),
+            size,
+        }
+    }
+}
+
+/// The slots of [`ToolLimits`].
+pub struct ToolLimiter {
+    global: Option<Slots>,
+    per_tool: HashMap<String, Slots>,
+    timeout: Duration,
+}
+
+/// A call's slots, given back when dropped.
+pub struct ToolPermit {
+    _tool: Option<OwnedSemaphorePermit>,
+    _global: Option<OwnedSemaphorePermit>,
+}
+
+impl ToolLimiter {
+    pub fn new(limits: &ToolLimits) -> Self {
+        Self {
+            global: limits.max_concurrent.map(Slots::new),
+            per_tool: limits
+                .per_tool
+                .iter()
+                .map(|(tool, size)| (limit_key(tool).to_string(), Slots::new(*size)))
+                .collect(),
+            timeout: Duration::from_secs(limits.queue_timeout_secs),
+        }
+    }
+
+    /// Wait for the slots a call of `tool` needs. Fails if they are not free within the
+    /// queue timeout.
+    pub async fn acquire(&self, tool: &str) -> Result<ToolPermit, String> {
+        let deadline = Instant::now() + self.timeout;
+        // The tool's slot first, so a queued call does not hold one of the server's.
+        let tool_permit = match self.per_tool.get(limit_key(tool)) {
+            Some(slots) => Some(self.wait(slots, deadline).await.map_err(|_| {
+                format!(
+                    "Server busy: {} calls of '{}' are already running, its limit, and none finished within {}s. Try again later.",
+                    slots.size,
+                    tool,
+                    self.timeout.as_secs()
+                )
+            })?),
+            None => None,
+        };
+        let global_permit = match &self.global {
+            Some(slots) => Some(self.wait(slots, deadline).await.map_err(|_| {
+                format!(
+                    "Server busy: {} tool calls are already running, the server's limit, and none finished within {}s. Try again later.",
+                    slots.size,
+                    self.timeout.as_secs()
+                )
+            })?),
+            None => None,
+        };
+        Ok(ToolPermit {
+            _tool: tool_permit,
+            _global: global_permit,
+        })
+    }
+
+    async fn wait(&self, slots: &Slots, deadline: Instant) -> Result<OwnedSemaphorePermit, ()> {
+        match tokio::time::timeout_at(deadline, slots.semaphore.clone().acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(permit),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Default for ToolLimiter {
+    fn default() -> Self {
+        Self::new(&ToolLimits::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tool_limits() {
+        let limits: ToolLimits = toml::from_str(
+            "max_concurrent = 2\nqueue_timeout_secs = 0\n[per_tool]\nexecute_bash = 1\n",
+        )
+        .unwrap();
+        let limiter = ToolLimiter::new(&limits);
+
+        let bash = limiter.acquire("execute_bash").await.unwrap();
+        // `terminal` shares the limit of `execute_bash`.
+        let error = limiter.acquire("terminal").await.err().unwrap();
+        assert!(error.starts_with("Server busy: 1 calls of 'terminal'"));
+
+        let grep = limiter.acquire("grep").await.unwrap();
+        let error = limiter.acquire("glob").await.err().unwrap();
+        assert!(error.contains("2 tool calls are already running"));
+
+        drop(bash);
+        drop(grep);
+        limiter.acquire("terminal").await.unwrap();
+        // Without limits every call goes through.
+        let unlimited = ToolLimiter::default();
+        let _permits = [
+            unlimited.acquire("execute_bash").await.unwrap(),
+            unlimited.acquire("execute_bash").await.unwrap(),
+        ];
+    }
+}
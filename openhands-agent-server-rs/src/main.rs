@@ -1,38 +1,552 @@
+mod acp;
+mod api;
+mod audit;
+mod auth;
+mod config;
+mod conversations;
+mod display;
+mod events;
+mod health;
+mod idle;
+mod info;
+mod limits;
+mod openai;
+mod permissions;
+mod plugins;
+mod project;
+mod quotas;
+mod scheduler;
 mod service;
+mod tasks;
+#[cfg(test)]
+mod testing;
 mod tools;
+mod top;
+mod ui;
+mod webhooks;
 
-use axum::Router;
+use api::AppState;
+use audit::AuditLog;
+use auth::UserStore;
+use axum::{middleware, Router};
+use clap::{Args, Parser, Subcommand};
+use config::ServerConfig;
+use display::{DisplayConfig, DisplayService};
+use events::EventHub;
+use openhands_sdk_rs::client::AgentServerClient;
+use openhands_sdk_rs::export::ExportFormat;
+use openhands_sdk_rs::llm::LLMConfig;
+use openhands_sdk_rs::mcp::McpToolProvider;
+use openhands_sdk_rs::notifications::Notifier;
+use openhands_sdk_rs::plugins::PluginRegistry;
+use openhands_sdk_rs::profile::ProfileReport;
+use openhands_sdk_rs::redact::{self, Redactor};
+use openhands_sdk_rs::resolver::ResolveIssueRequest;
 use openhands_sdk_rs::runtime::bash::BashEventService;
+use openhands_sdk_rs::runtime::env as tool_env;
 use openhands_sdk_rs::runtime::file::FileService;
+use openhands_sdk_rs::runtime::network::{self, Egress, EgressProxy, NetworkMode, NetworkPolicy};
+use openhands_sdk_rs::runtime::pool::SandboxPool;
+use openhands_sdk_rs::runtime::sandbox::{sandbox_image, SandboxImageSpec};
+use openhands_sdk_rs::session::ConversationManager;
+use openhands_sdk_rs::shell_output;
+use permissions::{PermissionProfile, Profiles};
+use quotas::QuotaTracker;
 use rmcp::transport::{
     streamable_http_server::{session::local::LocalSessionManager, tower::StreamableHttpService},
     StreamableHttpServerConfig,
 };
+use rmcp::ServiceExt;
+use scheduler::Scheduler;
 use service::OpenHandsService;
 use std::env;
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tasks::TaskQueue;
 use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tools::messages::{self, MessageCatalog};
+use webhooks::{WebhookConfig, WebhookState};
+
+#[derive(Parser)]
+#[command(
+    about = "OpenHands agent server",
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Transport options of the default `serve` command
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(Args, Clone, Copy, Default)]
+#[group(multiple = false)]
+struct ServeArgs {
+    /// Serve MCP over stdin/stdout instead of HTTP
+    #[arg(long)]
+    mcp_stdio: bool,
+    /// Serve the HTTP API, with MCP at /mcp (default)
+    #[arg(long)]
+    http: bool,
+    /// Serve MCP over stdin/stdout and the HTTP API, sharing one service
+    #[arg(long)]
+    both: bool,
+}
+
+impl ServeArgs {
+    fn stdio(&self) -> bool {
+        self.mcp_stdio || self.both
+    }
+
+    fn http(&self) -> bool {
+        !self.mcp_stdio
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP/MCP server (default)
+    Serve(ServeArgs),
+    /// Clone a GitHub/GitLab/Bitbucket repository, let the agent resolve an issue, and print the diff
+    ResolveIssue {
+        /// Repository URL, e.g. https://github.com/owner/repo or https://gitlab.com/group/repo
+        repo_url: String,
+        issue_number: u64,
+    },
+    /// Run the agent for an editor over the Agent Client Protocol on stdin/stdout
+    Acp,
+    /// Show where a conversation's time and tokens went, from a running server
+    Profile {
+        conversation_id: String,
+        /// URL of the server
+        #[arg(long, default_value = "http://localhost:3000")]
+        server: String,
+        /// API key of the conversation's owner (defaults to OPENHANDS_API_KEY)
+        #[arg(long)]
+        api_key: Option<String>,
+    },
+    /// Watch a running server's conversations, and pause them or stop their steps
+    Top {
+        /// URL of the server
+        #[arg(long, default_value = "http://localhost:3000")]
+        server: String,
+        /// API key of the conversations' owner (defaults to OPENHANDS_API_KEY)
+        #[arg(long)]
+        api_key: Option<String>,
+    },
+    /// Write a conversation of a running server as a Markdown or HTML report
+    Export {
+        conversation_id: String,
+        /// markdown or html
+        #[arg(long, default_value = "markdown")]
+        format: ExportFormat,
+        /// File to write the report to (defaults to stdout)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// URL of the server
+        #[arg(long, default_value = "http://localhost:3000")]
+        server: String,
+        /// API key of the conversation's owner (defaults to OPENHANDS_API_KEY)
+        #[arg(long)]
+        api_key: Option<String>,
+    },
+    /// Manage sandbox images for the Docker runtime
+    Sandbox {
+        #[command(subcommand)]
+        command: SandboxCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum SandboxCommand {
+    /// Build a sandbox image from a base image and this server binary, and print its tag
+    Build {
+        /// Debian/Ubuntu based image with the project's runtimes, e.g. python:3.12-bookworm
+        base_image: String,
+        /// Additional apt packages to install (repeatable)
+        #[arg(long = "package")]
+        packages: Vec<String>,
+        /// Server binary to include (defaults to this executable)
+        #[arg(long)]
+        binary: Option<PathBuf>,
+    },
+}
 
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve(cli.serve));
 
-    // Set up tracing using the SDK's logger
-    openhands_sdk_rs::logger::init_logging();
+    // Set up tracing using the SDK's logger; stdout belongs to the protocol when serving over
+    // stdio.
+    match command {
+        Command::Serve(args) if args.stdio() => openhands_sdk_rs::logger::init_stderr_logging(),
+        Command::Acp => openhands_sdk_rs::logger::init_stderr_logging(),
+        // Logs would draw over the dashboard.
+        Command::Top { .. } => {}
+        _ => openhands_sdk_rs::logger::init_logging(),
+    }
 
     let cwd = env::current_dir().unwrap();
 
-    let bash_service = BashEventService::new(cwd.join("bash_events"));
-
     // Use WORKSPACE_DIR env var if set, otherwise default to current_dir/workspace
     let workspace_path = env::var("WORKSPACE_DIR")
         .map(PathBuf::from)
         .unwrap_or_else(|_| cwd.join("workspace"));
-    let file_service = FileService::new(workspace_path);
+
+    match command {
+        Command::Serve(args) => serve(cwd, workspace_path, args).await,
+        Command::ResolveIssue {
+            repo_url,
+            issue_number,
+        } => {
+            let request = ResolveIssueRequest {
+                repo_url,
+                issue_number,
+                instructions: None,
+                notify: Vec::new(),
+            };
+            let result = match api::issue_resolver(workspace_path, &request.repo_url) {
+                Ok(resolver) => resolver.resolve(&request).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(result) => {
+                    println!("Issue: {} ({})", result.issue_title, result.issue_url);
+                    println!("Repository: {}", result.repo_dir.display());
+                    println!("\n{}\n", result.summary);
+                    print!("{}", result.diff);
+                }
+                Err(e) => {
+                    eprintln!("Failed to resolve issue: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Acp => {
+            let config = match ServerConfig::from_env(&cwd) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Failed to load configuration: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let profile = match &config.acp_profile {
+                Some(name) => match Profiles::new(config.profiles.clone()).get(name) {
+                    Some(profile) => profile.clone(),
+                    None => {
+                        eprintln!("Unknown ACP profile {}", name);
+                        std::process::exit(1);
+                    }
+                },
+                None => PermissionProfile::unrestricted(),
+            };
+            if let Err(e) = acp::serve_acp(config.agent, profile).await {
+                eprintln!("ACP transport failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Profile {
+            conversation_id,
+            server,
+            api_key,
+        } => {
+            let mut client = AgentServerClient::new(&server);
+            if let Some(key) = api_key.or_else(|| env::var("OPENHANDS_API_KEY").ok()) {
+                client = client.with_api_key(&key);
+            }
+            match client.profile(&conversation_id).await {
+                Ok(report) => print_profile(&report),
+                Err(e) => {
+                    eprintln!("Failed to get the profile: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Top { server, api_key } => {
+            let mut client = AgentServerClient::new(&server);
+            if let Some(key) = api_key.or_else(|| env::var("OPENHANDS_API_KEY").ok()) {
+                client = client.with_api_key(&key);
+            }
+            if let Err(e) = top::run(client, &server).await {
+                eprintln!("top failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Export {
+            conversation_id,
+            format,
+            output,
+            server,
+            api_key,
+        } => {
+            let mut client = AgentServerClient::new(&server);
+            if let Some(key) = api_key.or_else(|| env::var("OPENHANDS_API_KEY").ok()) {
+                client = client.with_api_key(&key);
+            }
+            let written = match client.export(&conversation_id, format).await {
+                Ok(report) => match &output {
+                    Some(path) => std::fs::write(path, report).map_err(|e| e.to_string()),
+                    None => {
+                        print!("{}", report);
+                        Ok(())
+                    }
+                },
+                Err(e) => Err(e),
+            };
+            if let Err(e) = written {
+                eprintln!("Failed to export the conversation: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Sandbox {
+            command:
+                SandboxCommand::Build {
+                    base_image,
+                    packages,
+                    binary,
+                },
+        } => {
+            let binary = binary.unwrap_or_else(|| env::current_exe().unwrap());
+            match SandboxImageSpec::new(&base_image, binary)
+                .with_packages(packages)
+                .build()
+            {
+                Ok(tag) => println!("{}", tag),
+                Err(e) => {
+                    eprintln!("Failed to build sandbox image: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+fn print_profile(report: &ProfileReport) {
+    let secs = |ms: u64| format!("{:.1}s", ms as f64 / 1000.0);
+    let llm = &report.llm;
+    println!("{} steps in {}", report.steps.len(), secs(report.total_ms));
+    println!(
+        "LLM: {} calls ({} failed), {} total, {} max, {} prompt + {} completion tokens",
+        llm.calls,
+        llm.errors,
+        secs(llm.total_ms),
+        secs(llm.max_ms),
+        llm.usage.prompt_tokens,
+        llm.usage.completion_tokens
+    );
+    if let Some(cost) = report.cost_usd {
+        println!("Estimated cost: ${:.4}", cost);
+    }
+    if !report.tools.is_empty() {
+        println!("\nTools, slowest first:");
+        for tool in &report.tools {
+            println!(
+                "  {:<24} {:>4} calls {:>3} failed {:>3} retried {:>8} total {:>8} max",
+                tool.name,
+                tool.calls,
+                tool.errors,
+                tool.retries,
+                secs(tool.total_ms),
+                secs(tool.max_ms)
+            );
+        }
+    }
+    if !report.steps.is_empty() {
+        println!("\nSteps:");
+        for (i, step) in report.steps.iter().enumerate() {
+            println!(
+                "  {:>3}  {}  {:>8}  LLM {:>2} calls {:>8}  tools {:>2} calls {:>8}  {} tokens",
+                i + 1,
+                step.started_at.format("%Y-%m-%d %H:%M:%S"),
+                secs(step.duration_ms),
+                step.llm_calls,
+                secs(step.llm_ms),
+                step.tool_calls,
+                secs(step.tool_ms),
+                step.usage.prompt_tokens + step.usage.completion_tokens
+            );
+        }
+    }
+}
+
+async fn serve(cwd: PathBuf, workspace_path: PathBuf, args: ServeArgs) {
+    let config = match ServerConfig::from_env(&cwd) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    match Redactor::new(&config.redaction) {
+        Ok(redactor) => redact::set_redactor(redactor),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    match MessageCatalog::with_overrides(&config.messages) {
+        Ok(catalog) => messages::set_catalog(catalog),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = config.tool_env.validate() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    // Before any tool is created, so every command runs under the configured policy.
+    tool_env::set_policy(config.tool_env.clone());
+    shell_output::set_config(config.shell_output.clone());
+    // Held for the life of the server.
+    let _egress_proxy = match start_egress(&config.network).await {
+        Ok(proxy) => proxy,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let events = match EventHub::from_env() {
+        Ok(events) => Arc::new(events),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut bash_service = match BashEventService::new(cwd.join("bash_events")) {
+        Ok(service) => service
+            .with_bus(events.bus.clone())
+            .with_fsync(config.fsync_events),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(max_running) = config.max_running_commands {
+        bash_service = bash_service.with_max_running(max_running);
+    }
+    if !config.event_retention.is_unlimited() {
+        tokio::spawn(
+            bash_service
+                .clone()
+                .run_retention(config.event_retention.clone()),
+        );
+    }
+
+    let file_service = FileService::new(workspace_path.clone());
+
+    let display = match DisplayConfig::from_env() {
+        Some(config) => match DisplayService::start(config).await {
+            Ok(display) => Some(Arc::new(display)),
+            Err(e) => {
+                eprintln!("Failed to start virtual display: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
 
     // Create the MCP service
-    let openhands_service = OpenHandsService::new(bash_service, file_service);
+    let mut openhands_service = OpenHandsService::new(bash_service.clone(), file_service)
+        .with_events(events.stream.clone())
+        .with_databases(config.databases.clone())
+        .with_limits(&config.tool_limits);
+    if let Some(display) = &display {
+        openhands_service = openhands_service.with_display(display.clone());
+    }
+
+    if !args.http() {
+        if let Err(e) = serve_stdio(openhands_service).await {
+            eprintln!("MCP stdio transport failed: {}", e);
+            std::process::exit(1);
+        }
+        bash_service.shutdown(COMMAND_SHUTDOWN_GRACE).await;
+        return;
+    }
+
+    // With both transports, the process ends when the stdio client disconnects.
+    let stdio_closed: Pin<Box<dyn Future<Output = ()> + Send>> = if args.stdio() {
+        let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+        let service = openhands_service.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_stdio(service).await {
+                tracing::error!("MCP stdio transport failed: {}", e);
+            }
+            let _ = closed_tx.send(());
+        });
+        Box::pin(async move {
+            let _ = closed_rx.await;
+        })
+    } else {
+        Box::pin(std::future::pending())
+    };
 
+    serve_http(
+        cwd,
+        config,
+        workspace_path,
+        display,
+        events,
+        openhands_service,
+        stdio_closed,
+    )
+    .await;
+    bash_service.shutdown(COMMAND_SHUTDOWN_GRACE).await;
+}
+
+/// How long shutdown waits for running commands before cancelling them.
+const COMMAND_SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// Apply the `[network]` policy to commands, starting the egress proxy in `allowlist` mode.
+/// Sandbox containers reach the proxy through their network's gateway, so with the Docker
+/// runtime it listens on all interfaces (and still only accepts private addresses).
+async fn start_egress(policy: &NetworkPolicy) -> Result<Option<EgressProxy>, String> {
+    let proxy = if policy.mode == NetworkMode::Allowlist {
+        let bind = if env::var("RUNTIME_ENV").unwrap_or_default() == "docker" {
+            Ipv4Addr::UNSPECIFIED
+        } else {
+            Ipv4Addr::LOCALHOST
+        };
+        let proxy = EgressProxy::start(bind.into(), policy.clone()).await?;
+        tracing::info!(
+            "Egress proxy on port {} allows {}",
+            proxy.port(),
+            policy.hosts.join(", ")
+        );
+        Some(proxy)
+    } else {
+        None
+    };
+    network::set_egress(Egress {
+        policy: policy.clone(),
+        proxy_port: proxy.as_ref().map(EgressProxy::port),
+    });
+    Ok(proxy)
+}
+
+/// Serve MCP over stdin/stdout until the client disconnects. There is no authentication,
+/// so every tool is available on the shared workspace.
+async fn serve_stdio(service: OpenHandsService) -> Result<(), String> {
+    let running = service
+        .serve(rmcp::transport::stdio())
+        .await
+        .map_err(|e| e.to_string())?;
+    tracing::info!("Serving MCP over stdio");
+    running.waiting().await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The HTTP API, with MCP at `/mcp`.
+fn router(state: AppState, openhands_service: OpenHandsService) -> Router {
     // Wrap it in StreamableHttpService
     let mcp_service: StreamableHttpService<OpenHandsService, LocalSessionManager> =
         StreamableHttpService::new(
@@ -41,13 +555,330 @@ async fn main() {
             StreamableHttpServerConfig::default(),
         );
 
-    // Build our application with a route
-    let app = Router::new()
+    // Everything except health checks and (signed) webhooks requires an API key once
+    // authentication is enabled.
+    let authenticated = Router::new()
+        .route("/server_info", axum::routing::get(info::server_info))
+        .route("/api/me", axum::routing::get(auth::me))
+        .route("/api/users", axum::routing::post(auth::create_user))
+        .route(
+            "/api/users/{id}/keys",
+            axum::routing::post(auth::create_key),
+        )
+        .route("/api/audit", axum::routing::get(audit::list_audit))
+        .route(
+            "/api/conversations",
+            axum::routing::get(conversations::list_conversations)
+                .post(conversations::create_conversation),
+        )
+        .route(
+            "/api/conversations/{id}",
+            axum::routing::delete(conversations::delete_conversation),
+        )
+        .route(
+            "/api/conversations/{id}/messages",
+            axum::routing::post(conversations::send_message),
+        )
+        .route(
+            "/api/conversations/{id}/events",
+            axum::routing::get(events::stream_events),
+        )
+        .route(
+            "/api/bash/events",
+            axum::routing::get(events::search_bash_events),
+        )
+        .route(
+            "/api/events/metrics",
+            axum::routing::get(events::event_metrics),
+        )
+        .route(
+            "/api/conversations/{id}/pending_actions",
+            axum::routing::get(conversations::list_pending_actions),
+        )
+        .route(
+            "/api/conversations/{id}/pending_actions/{tool_call_id}",
+            axum::routing::post(conversations::decide_action),
+        )
+        .route(
+            "/api/conversations/{id}/result",
+            axum::routing::get(conversations::get_result),
+        )
+        .route(
+            "/api/conversations/{id}/changes",
+            axum::routing::get(conversations::get_changes),
+        )
+        .route(
+            "/api/conversations/{id}/prompt",
+            axum::routing::get(conversations::get_prompt).patch(conversations::update_prompt),
+        )
+        .route(
+            "/api/conversations/{id}/undo",
+            axum::routing::post(conversations::undo_changes),
+        )
+        .route(
+            "/api/conversations/{id}/profile",
+            axum::routing::get(conversations::get_profile),
+        )
+        .route(
+            "/api/conversations/{id}/pause",
+            axum::routing::post(conversations::pause_conversation),
+        )
+        .route(
+            "/api/conversations/{id}/resume",
+            axum::routing::post(conversations::resume_conversation),
+        )
+        .route(
+            "/api/conversations/{id}/stop",
+            axum::routing::post(conversations::stop_conversation),
+        )
+        .route(
+            "/api/activity",
+            axum::routing::get(conversations::list_activity),
+        )
+        .route(
+            "/api/conversations/{id}/export",
+            axum::routing::get(conversations::export_conversation),
+        )
+        .route(
+            "/api/conversations/{id}/plan",
+            axum::routing::get(conversations::get_plan).delete(conversations::discard_plan),
+        )
+        .route(
+            "/api/conversations/{id}/plan/execute",
+            axum::routing::post(conversations::execute_plan),
+        )
+        .route(
+            "/api/conversations/{id}/ports",
+            axum::routing::get(conversations::list_ports),
+        )
+        .route(
+            "/v1/chat/completions",
+            axum::routing::post(openai::chat_completions),
+        )
+        .route(
+            "/api/tools",
+            axum::routing::get(plugins::list_tools).post(plugins::register_tool),
+        )
+        .route(
+            "/api/tools/{name}",
+            axum::routing::delete(plugins::deregister_tool),
+        )
+        .route(
+            "/api/resolve-issue",
+            axum::routing::post(api::resolve_issue),
+        )
+        .route(
+            "/api/resolve-issue/{id}",
+            axum::routing::get(api::get_resolve_run),
+        )
+        .route(
+            "/api/tasks",
+            axum::routing::get(tasks::list_tasks).post(tasks::create_task),
+        )
+        .route("/api/tasks/{id}", axum::routing::get(tasks::get_task))
+        .route(
+            "/api/tasks/{id}/result",
+            axum::routing::get(tasks::get_task_result),
+        )
+        .route(
+            "/api/schedules",
+            axum::routing::get(scheduler::list_schedules).post(scheduler::create_schedule),
+        )
+        .route(
+            "/api/schedules/{id}",
+            axum::routing::delete(scheduler::delete_schedule),
+        )
+        .route(
+            "/api/schedules/{id}/runs",
+            axum::routing::get(scheduler::list_runs),
+        )
+        .nest_service("/mcp", mcp_service)
+        // Layers run outermost-last: authenticate, rate limit, then track activity.
+        .layer(middleware::from_fn(idle::track_activity))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            quotas::rate_limit,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::authenticate,
+        ));
+
+    Router::new()
         .route("/health", axum::routing::get(|| async { "OK" }))
-        .nest_service("/mcp", mcp_service);
+        .route("/readyz", axum::routing::get(health::readyz))
+        .route("/ui", axum::routing::get(ui::index))
+        .route("/ui/", axum::routing::get(ui::index))
+        .route("/ui/{*path}", axum::routing::get(ui::serve_asset))
+        .route(
+            "/webhooks/{provider}",
+            axum::routing::post(webhooks::handle_webhook)
+                .layer(middleware::from_fn(idle::track_activity)),
+        )
+        .merge(authenticated)
+        .with_state(state)
+}
+
+/// Serve the HTTP API and MCP at `/mcp` until idle shutdown or `stop` completes.
+async fn serve_http(
+    cwd: PathBuf,
+    config: ServerConfig,
+    workspace_path: PathBuf,
+    display: Option<Arc<DisplayService>>,
+    events: Arc<EventHub>,
+    openhands_service: OpenHandsService,
+    stop: impl Future<Output = ()> + Send + 'static,
+) {
+    let quotas =
+        match QuotaTracker::load(cwd.join("quotas.json"), config.quotas, config.llm_pricing) {
+            Ok(quotas) => Arc::new(quotas),
+            Err(e) => {
+                eprintln!("Failed to load quota counters: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+    let scheduler = match Scheduler::load(cwd.join("schedules.json"), workspace_path.clone()) {
+        Ok(scheduler) => Arc::new(
+            scheduler
+                .with_quotas(quotas.clone())
+                .with_agent_config(config.agent.clone()),
+        ),
+        Err(e) => {
+            eprintln!("Failed to load schedules: {}", e);
+            std::process::exit(1);
+        }
+    };
+    tokio::spawn(scheduler.clone().run());
+
+    let tasks = match TaskQueue::from_env(cwd.join("tasks")) {
+        Ok(tasks) => Arc::new(tasks),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let users = match UserStore::load(cwd.join("users.json"), env::var("OPENHANDS_ADMIN_KEY").ok())
+    {
+        Ok(users) => Arc::new(users),
+        Err(e) => {
+            eprintln!("Failed to load users: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let idle_timeout = match idle::timeout_from_env() {
+        Ok(timeout) => timeout,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Warm sandboxes for conversations running in Docker.
+    let sandbox_pool = if env::var("RUNTIME_ENV").unwrap_or_default() == "docker" {
+        match SandboxPool::from_env(&sandbox_image()) {
+            Ok(pool) => pool,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    let plugin_health_interval = match plugins::health_interval_from_env() {
+        Ok(interval) => interval,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let plugin_registry = PluginRegistry::new();
+    tokio::spawn(
+        plugin_registry
+            .clone()
+            .run_health_checks(plugin_health_interval),
+    );
+
+    let mcp = Arc::new(McpToolProvider::connect(&config.mcp_servers).await);
+
+    let mut conversations = ConversationManager::new()
+        .with_plugins(plugin_registry.clone())
+        .with_mcp(mcp)
+        .with_output_formatter(Arc::new(config.output_format))
+        .with_agent_config(config.agent.clone())
+        .with_notifier(Notifier::from_env())
+        .with_security(
+            config.security.mode,
+            Duration::from_secs(config.security.confirmation_timeout_secs),
+        );
+    if let Some(analyzer) = &config.security.analyzer {
+        conversations = conversations.with_security_analyzer(Arc::new(analyzer.analyzer()));
+    }
+    if let Some(classifier) = &config.security.injection_classifier {
+        conversations = conversations.with_injection_classifier(Arc::new(classifier.classifier()));
+    }
+    if let Ok(dir) = env::var("OPENHANDS_LLM_LOG_DIR") {
+        conversations = conversations.with_llm_recording(PathBuf::from(dir));
+    }
+    if let Err(e) = conversations.check_tool_schemas(&LLMConfig::from_env()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    if let Some(pool) = &sandbox_pool {
+        tracing::info!("Keeping {} warm sandboxes of {}", pool.size(), pool.image());
+        pool.fill();
+        conversations = conversations.with_pool(pool.clone());
+    }
+
+    let state = AppState {
+        workspace_dir: workspace_path,
+        display,
+        webhooks: Arc::new(WebhookState::new(WebhookConfig::from_env())),
+        scheduler,
+        tasks,
+        users,
+        profiles: Arc::new(Profiles::new(config.profiles)),
+        quotas,
+        audit: Arc::new(AuditLog::new(cwd.join("audit.log"))),
+        conversations: Arc::new(Mutex::new(conversations)),
+        setup: Arc::new(config.setup),
+        resolve_runs: Arc::default(),
+        plugins: plugin_registry,
+        events,
+        bash: openhands_service.bash_events(),
+    };
+    tasks::resume(&state);
+    let conversations = state.conversations.clone();
+
+    let app = router(state, openhands_service);
 
     // Run it
     let listener = TcpListener::bind("0.0.0.0:3000").await.unwrap();
     tracing::info!("Listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        let idle = async {
+            match idle_timeout {
+                Some(timeout) => idle::wait_until_idle(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = idle => {}
+            _ = stop => {}
+        }
+    })
+    .await
+    .unwrap();
+
+    conversations.lock().await.close_all().await;
+    if let Some(pool) = sandbox_pool {
+        pool.drain();
+    }
 }
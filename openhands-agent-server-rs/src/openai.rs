@@ -0,0 +1,355 @@
+//! An OpenAI-compatible `POST /v1/chat/completions`, so apps built on OpenAI clients can
+//! drive the agent without a custom integration.
+//!
+//! Each request runs in a transient conversation: system messages become the system
+//! message, user and assistant messages the history, and the agent answers the last user
+//! message using its tools server-side in a fresh workspace that is removed afterwards. With
+//! `"stream": true` the agent's messages are sent as `chat.completion.chunk` deltas over SSE
+//! as they are produced. The server's LLM answers whatever `model` the request names; the
+//! name is only echoed back.
+
+use crate::api::AppState;
+use crate::auth::User;
+use crate::conversations::DEFAULT_SYSTEM_MESSAGE;
+use crate::permissions::{PermissionProfile, API_CONVERSATIONS};
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Extension, Json,
+};
+use chrono::Utc;
+use openhands_sdk_rs::agent::StepObserver;
+use openhands_sdk_rs::events::{Event, MessageEvent};
+use openhands_sdk_rs::llm::{LLMConfig, TokenUsage};
+use openhands_sdk_rs::session::Conversation;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::StreamExt;
+
+#[derive(Deserialize)]
+pub struct ChatCompletionRequest {
+    #[serde(default)]
+    pub model: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default)]
+    pub stream_options: Option<StreamOptions>,
+}
+
+#[derive(Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    /// A string, or an array of content parts of which the text parts are used.
+    #[serde(default)]
+    pub content: Value,
+}
+
+#[derive(Deserialize, Default)]
+pub struct StreamOptions {
+    #[serde(default)]
+    pub include_usage: bool,
+}
+
+fn message_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter(|part| part["type"] == "text")
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// The system message and history for a chat. The last message must be the user's.
+fn chat_history(messages: &[ChatMessage]) -> Result<(String, Vec<Event>), String> {
+    if messages.last().map(|m| m.role.as_str()) != Some("user") {
+        return Err("The last message must have role 'user'".to_string());
+    }
+    let system: Vec<String> = messages
+        .iter()
+        .filter(|m| m.role == "system" || m.role == "developer")
+        .map(|m| message_text(&m.content))
+        .collect();
+    let history = messages
+        .iter()
+        .filter_map(|m| {
+            let source = match m.role.as_str() {
+                "user" => "user",
+                "assistant" => "agent",
+                _ => return None,
+            };
+            Some(Event::Message(MessageEvent {
+                source: source.to_string(),
+                content: message_text(&m.content),
+                file_refs: Vec::new(),
+            }))
+        })
+        .collect();
+    let system = if system.is_empty() {
+        DEFAULT_SYSTEM_MESSAGE.to_string()
+    } else {
+        system.join("\n\n")
+    };
+    Ok((system, history))
+}
+
+fn usage_json(usage: &TokenUsage) -> Value {
+    json!({
+        "prompt_tokens": usage.prompt_tokens,
+        "completion_tokens": usage.completion_tokens,
+        "total_tokens": usage.prompt_tokens + usage.completion_tokens,
+    })
+}
+
+/// A `chat.completion.chunk` with one choice.
+fn chunk(id: &str, model: &str, created: i64, delta: Value, finish_reason: Option<&str>) -> Value {
+    json!({
+        "id": id,
+        "object": "chat.completion.chunk",
+        "created": created,
+        "model": model,
+        "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+    })
+}
+
+/// Streams the agent's text as it is produced: the thoughts accompanying tool calls and the
+/// final message.
+struct DeltaObserver {
+    chunks: mpsc::UnboundedSender<String>,
+    id: String,
+    model: String,
+    created: i64,
+}
+
+impl DeltaObserver {
+    fn send(&self, delta: Value, finish_reason: Option<&str>) {
+        let chunk = chunk(&self.id, &self.model, self.created, delta, finish_reason);
+        let _ = self.chunks.send(chunk.to_string());
+    }
+}
+
+#[async_trait]
+impl StepObserver for DeltaObserver {
+    async fn on_event(&self, event: &Event) {
+        let text = match event {
+            Event::Action(action) => action.thought.as_ref().map(|t| format!("{}\n\n", t)),
+            Event::Message(message) => Some(message.content.clone()),
+            Event::Observation(_) => None,
+        };
+        if let Some(text) = text {
+            self.send(json!({ "content": text }), None);
+        }
+    }
+}
+
+/// Run the agent on the conversation's history, then record the usage and remove the
+/// conversation's workspace. Returns the reply.
+async fn run(
+    state: &AppState,
+    user: &User,
+    conversation: Conversation,
+    observer: &dyn StepObserver,
+) -> Result<(String, TokenUsage), String> {
+    let history = conversation.history.read().await.clone();
+    let mut runtime = conversation.runtime.write().await;
+    let result = conversation
+        .agent
+        .step_events_observed(&history, runtime.as_mut(), observer)
+        .await;
+    let usage = conversation.agent.usage();
+    state
+        .quotas
+        .record_usage(&user.id, &usage, Utc::now())
+        .await;
+    drop(runtime);
+    let _ = std::fs::remove_dir_all(&conversation.workspace_dir);
+
+    let events = result.map_err(|e| e.to_string())?;
+    let reply = match events.last() {
+        Some(Event::Message(m)) => m.content.clone(),
+        _ => String::new(),
+    };
+    Ok((reply, usage))
+}
+
+pub async fn chat_completions(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Json(request): Json<ChatCompletionRequest>,
+) -> Result<Response, Response> {
+    profile
+        .require_api(API_CONVERSATIONS)
+        .map_err(IntoResponse::into_response)?;
+    state
+        .quotas
+        .check_spend(&user.id, &state.quotas.limits(&profile), Utc::now())
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let (system_message, history) = chat_history(&request.messages)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+
+    let workspace_dir = profile
+        .workspace_dir(&state.workspace_dir, &user.id)
+        .join("chat")
+        .join(uuid::Uuid::new_v4().to_string());
+    std::fs::create_dir_all(&workspace_dir).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to create workspace: {}", e),
+        )
+            .into_response()
+    })?;
+    // Transient: the conversation is not listed, and ends with the request.
+    let conversation = {
+        let mut conversations = state.conversations.lock().await;
+        let config = profile.agent_config(&conversations);
+        let conversation = conversations.create_conversation_configured(
+            &user.id,
+            system_message,
+            workspace_dir,
+            config,
+        );
+        conversations.remove_conversation(&user.id, &conversation.id);
+        conversation
+    };
+    if !state.setup.is_empty() {
+        let runtime = conversation.runtime.read().await;
+        let setup = state
+            .setup
+            .run_in(&conversation.workspace_dir, runtime.as_ref())
+            .await;
+        drop(runtime);
+        conversation
+            .history
+            .write()
+            .await
+            .push(Event::Observation(setup));
+    }
+    conversation.history.write().await.extend(history);
+    state
+        .audit
+        .record(&user, "chat.completion", &conversation.id)
+        .await;
+
+    let id = format!("chatcmpl-{}", conversation.id);
+    let model = request
+        .model
+        .clone()
+        .unwrap_or_else(|| LLMConfig::from_env().model);
+    let created = Utc::now().timestamp();
+
+    if !request.stream {
+        let (reply, usage) = run(&state, &user, conversation, &())
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e).into_response())?;
+        return Ok(Json(json!({
+            "id": id,
+            "object": "chat.completion",
+            "created": created,
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": reply },
+                "finish_reason": "stop",
+            }],
+            "usage": usage_json(&usage),
+        }))
+        .into_response());
+    }
+
+    let include_usage = request.stream_options.unwrap_or_default().include_usage;
+    let (chunks, stream) = mpsc::unbounded_channel();
+    let observer = DeltaObserver {
+        chunks: chunks.clone(),
+        id,
+        model,
+        created,
+    };
+    observer.send(json!({ "role": "assistant" }), None);
+    tokio::spawn(async move {
+        match run(&state, &user, conversation, &observer).await {
+            Ok((_, usage)) => {
+                observer.send(json!({}), Some("stop"));
+                if include_usage {
+                    let mut last = chunk(&observer.id, &observer.model, created, json!({}), None);
+                    last["choices"] = json!([]);
+                    last["usage"] = usage_json(&usage);
+                    let _ = chunks.send(last.to_string());
+                }
+            }
+            Err(e) => {
+                let error = json!({ "error": { "message": e, "type": "server_error" } });
+                let _ = chunks.send(error.to_string());
+            }
+        }
+        let _ = chunks.send("[DONE]".to_string());
+    });
+
+    let events = UnboundedReceiverStream::new(stream)
+        .map(|data| Ok::<_, Infallible>(SseEvent::default().data(data)));
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: Value) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content,
+        }
+    }
+
+    #[test]
+    fn test_chat_history() {
+        let messages = vec![
+            message("system", json!("Answer in French.")),
+            message("user", json!("Hi")),
+            message("assistant", json!("Bonjour")),
+            message(
+                "user",
+                json!([
+                    { "type": "text", "text": "List the files" },
+                    { "type": "image_url", "image_url": { "url": "data:..." } },
+                ]),
+            ),
+        ];
+        let (system, history) = chat_history(&messages).unwrap();
+        assert_eq!(system, "Answer in French.");
+        let turns: Vec<(String, String)> = history
+            .into_iter()
+            .map(|e| match e {
+                Event::Message(m) => (m.source, m.content),
+                _ => panic!("unexpected event"),
+            })
+            .collect();
+        assert_eq!(
+            turns,
+            vec![
+                ("user".to_string(), "Hi".to_string()),
+                ("agent".to_string(), "Bonjour".to_string()),
+                ("user".to_string(), "List the files".to_string()),
+            ]
+        );
+
+        assert!(chat_history(&messages[..3]).is_err());
+        let (system, _) = chat_history(&messages[1..2]).unwrap();
+        assert_eq!(system, DEFAULT_SYSTEM_MESSAGE);
+    }
+}
@@ -0,0 +1,249 @@
+//! Permission profiles: which tools and HTTP features an API key may use, and which part of
+//! the workspace its tools operate on. The tools a profile leaves out are hidden from MCP
+//! clients and disabled in the agents of the user's conversations and tasks.
+//!
+//! Users are assigned a profile by name. The built-in `viewer`, `developer` and `admin`
+//! profiles can be overridden, and new ones added, under `[profiles.<name>]` in
+//! `openhands.toml`:
+//!
+//! ```toml
+//! [profiles.reviewer]
+//! tools = ["glob", "grep", "read_file", "list_files"]
+//! api = ["conversations"]
+//! workspace = "user"
+//!
+//! [profiles.reviewer.quotas]
+//! requests_per_minute = 30
+//! ```
+
+use crate::quotas::Quotas;
+use axum::http::StatusCode;
+use openhands_sdk_rs::agent::AgentConfig;
+use openhands_sdk_rs::session::ConversationManager;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// HTTP features gated by [`PermissionProfile::api`].
+pub const API_CONVERSATIONS: &str = "conversations";
+pub const API_RESOLVE_ISSUE: &str = "resolve_issue";
+pub const API_SCHEDULES: &str = "schedules";
+pub const API_TASKS: &str = "tasks";
+pub const API_PLUGINS: &str = "plugins";
+pub const API_BASH_EVENTS: &str = "bash_events";
+
+const READ_ONLY_TOOLS: &[&str] = &[
+    "glob",
+    "grep",
+    "read_file",
+    "list_files",
+    "stat_file",
+    "query_data",
+    "environment_info",
+    "screenshot_display",
+];
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceScope {
+    /// File tools operate on the user's own directory, `<workspace>/users/<id>`. Shell
+    /// tools are not confined; leave them out of profiles that must not escape it.
+    #[default]
+    User,
+    /// File tools operate on the whole server workspace.
+    Shared,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct PermissionProfile {
+    /// MCP and agent tool names; `"*"` allows every tool.
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// HTTP features (`conversations`, `resolve_issue`, `schedules`, `plugins`, `bash_events`);
+    /// `"*"` allows all.
+    #[serde(default)]
+    pub api: Vec<String>,
+    #[serde(default)]
+    pub workspace: WorkspaceScope,
+    /// Limits replacing those of the server-wide `[quotas]` for this profile; limits left
+    /// unset keep the server's.
+    #[serde(default)]
+    pub quotas: Option<Quotas>,
+}
+
+impl PermissionProfile {
+    /// Everything, on the shared workspace. Used when authentication is disabled.
+    pub fn unrestricted() -> Self {
+        Self {
+            tools: vec!["*".to_string()],
+            api: vec!["*".to_string()],
+            workspace: WorkspaceScope::Shared,
+            quotas: None,
+        }
+    }
+
+    pub fn allows_tool(&self, name: &str) -> bool {
+        self.tools.iter().any(|t| t == "*" || t == name)
+    }
+
+    pub fn allows_api(&self, feature: &str) -> bool {
+        self.api.iter().any(|a| a == "*" || a == feature)
+    }
+
+    /// Reject the request with 403 unless the profile allows the HTTP `feature`.
+    pub fn require_api(&self, feature: &str) -> Result<(), (StatusCode, String)> {
+        if self.allows_api(feature) {
+            Ok(())
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                format!("API key is not permitted to use {}", feature),
+            ))
+        }
+    }
+
+    /// The tools among `tool_names` the profile does not allow.
+    pub fn disabled_tools(&self, tool_names: &[String]) -> Vec<String> {
+        tool_names
+            .iter()
+            .filter(|name| !self.allows_tool(name))
+            .cloned()
+            .collect()
+    }
+
+    /// The agent configuration of `conversations` with the tools the profile does not allow
+    /// disabled too, so the agent cannot call them either.
+    pub fn agent_config(&self, conversations: &ConversationManager) -> AgentConfig {
+        let mut config = conversations.agent_config().clone();
+        for tool in self.disabled_tools(&conversations.tool_names()) {
+            if !config.disabled_tools.contains(&tool) {
+                config.disabled_tools.push(tool);
+            }
+        }
+        config
+    }
+
+    /// Root directory for the tools of `user_id`.
+    pub fn workspace_dir(&self, workspace_root: &Path, user_id: &str) -> PathBuf {
+        match self.workspace {
+            WorkspaceScope::User => crate::auth::user_workspace_dir(workspace_root, user_id),
+            WorkspaceScope::Shared => workspace_root.to_path_buf(),
+        }
+    }
+}
+
+pub struct Profiles {
+    profiles: HashMap<String, PermissionProfile>,
+}
+
+impl Profiles {
+    /// The built-in profiles, overridden/extended by `configured`.
+    pub fn new(configured: HashMap<String, PermissionProfile>) -> Self {
+        let mut profiles = HashMap::from([
+            (
+                "viewer".to_string(),
+                PermissionProfile {
+                    tools: READ_ONLY_TOOLS.iter().map(|t| t.to_string()).collect(),
+                    api: Vec::new(),
+                    workspace: WorkspaceScope::User,
+                    quotas: None,
+                },
+            ),
+            (
+                "developer".to_string(),
+                PermissionProfile {
+                    tools: vec!["*".to_string()],
+                    api: vec![
+                        API_CONVERSATIONS.to_string(),
+                        API_RESOLVE_ISSUE.to_string(),
+                        API_SCHEDULES.to_string(),
+                        API_TASKS.to_string(),
+                    ],
+                    workspace: WorkspaceScope::User,
+                    quotas: None,
+                },
+            ),
+            ("admin".to_string(), PermissionProfile::unrestricted()),
+        ]);
+        profiles.extend(configured);
+        Self { profiles }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PermissionProfile> {
+        self.profiles.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+
+    #[test]
+    fn test_profiles_from_config() {
+        let config = ServerConfig::parse(
+            r#"
+            [profiles.viewer]
+            tools = ["read_file"]
+
+            [profiles.ops]
+            tools = ["execute_bash", "terminal"]
+            api = ["schedules"]
+            workspace = "shared"
+            "#,
+        )
+        .unwrap();
+        let profiles = Profiles::new(config.profiles);
+
+        let viewer = profiles.get("viewer").unwrap();
+        assert!(viewer.allows_tool("read_file"));
+        assert!(!viewer.allows_tool("grep"));
+        assert!(viewer.require_api(API_CONVERSATIONS).is_err());
+
+        let ops = profiles.get("ops").unwrap();
+        assert!(ops.allows_tool("terminal"));
+        assert!(!ops.allows_tool("write_file"));
+        assert!(ops.allows_api(API_SCHEDULES));
+        assert_eq!(
+            ops.workspace_dir(Path::new("/ws"), "alice"),
+            PathBuf::from("/ws")
+        );
+
+        let developer = profiles.get("developer").unwrap();
+        assert!(developer.allows_tool("write_file"));
+        assert_eq!(
+            developer.workspace_dir(Path::new("/ws"), "alice"),
+            PathBuf::from("/ws/users/alice")
+        );
+        assert!(profiles.get("admin").unwrap().allows_api(API_RESOLVE_ISSUE));
+        assert!(ServerConfig::parse("[profiles.bad]\nworkspace = \"everywhere\"").is_err());
+    }
+
+    #[test]
+    fn test_profile_disables_agent_tools() {
+        let conversations = ConversationManager::new().with_agent_config(AgentConfig {
+            disabled_tools: vec!["github".to_string()],
+            ..Default::default()
+        });
+        let profiles = Profiles::new(HashMap::new());
+
+        let config = profiles.get("viewer").unwrap().agent_config(&conversations);
+        for tool in ["cmd", "terminal", "file_editor", "apply_patch", "github"] {
+            assert!(!config.tool_enabled(tool), "{}", tool);
+        }
+        for tool in ["read_file", "grep", "finish"] {
+            assert!(config.tool_enabled(tool), "{}", tool);
+        }
+        assert_eq!(
+            config
+                .disabled_tools
+                .iter()
+                .filter(|t| *t == "github")
+                .count(),
+            1
+        );
+
+        let config = profiles.get("admin").unwrap().agent_config(&conversations);
+        assert_eq!(config.disabled_tools, ["github"]);
+    }
+}
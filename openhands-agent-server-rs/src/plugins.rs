@@ -0,0 +1,66 @@
+//! Registration of plugin tools over HTTP (see `openhands_sdk_rs::plugins`).
+//!
+//! Registered tools are offered to conversations created afterwards. Registering and
+//! removing tools requires the `plugins` API permission; any user may list them.
+
+use crate::api::AppState;
+use crate::auth::User;
+use crate::permissions::{PermissionProfile, API_PLUGINS};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use openhands_sdk_rs::plugins::{RegisteredTool, ToolRegistration};
+use std::time::Duration;
+
+pub const DEFAULT_HEALTH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// `OPENHANDS_PLUGIN_HEALTH_SECS`, the interval of plugin health checks.
+pub fn health_interval_from_env() -> Result<Duration, String> {
+    match std::env::var("OPENHANDS_PLUGIN_HEALTH_SECS") {
+        Ok(secs) => secs
+            .parse::<u64>()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .ok_or_else(|| format!("Invalid OPENHANDS_PLUGIN_HEALTH_SECS: {}", secs)),
+        Err(_) => Ok(DEFAULT_HEALTH_INTERVAL),
+    }
+}
+
+pub async fn list_tools(State(state): State<AppState>) -> Json<Vec<RegisteredTool>> {
+    Json(state.plugins.list())
+}
+
+pub async fn register_tool(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Json(registration): Json<ToolRegistration>,
+) -> Result<(StatusCode, Json<RegisteredTool>), (StatusCode, String)> {
+    profile.require_api(API_PLUGINS)?;
+    let tool = state
+        .plugins
+        .register(registration)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    state
+        .audit
+        .record(&user, "plugin.register", &tool.registration.name)
+        .await;
+    Ok((StatusCode::CREATED, Json(tool)))
+}
+
+pub async fn deregister_tool(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    profile.require_api(API_PLUGINS)?;
+    if !state.plugins.deregister(&name) {
+        return Err((StatusCode::NOT_FOUND, format!("No plugin tool {}", name)));
+    }
+    state.audit.record(&user, "plugin.deregister", &name).await;
+    Ok(StatusCode::NO_CONTENT)
+}
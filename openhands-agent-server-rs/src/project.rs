@@ -0,0 +1,175 @@
+//! Project configuration: `.openhands/config.toml` at the root of a repository.
+//!
+//! A repository can tell the agent how to work on it: setup commands to run before the
+//! agent starts, its test and lint commands, tools the agent should not use, and prompt
+//! snippets. The server reads the file from the root of the repository a task clones and
+//! merges it with its own configuration: the project's setup runs after the server's
+//! `[setup]`, and its disabled tools are added to those of `[agent]`. A project can only
+//! take tools away, never grant them. Its prompt snippets reach the model as `external`
+//! content (see [`openhands_sdk_rs::provenance`]), and its setup commands only run for
+//! owners who may run shell commands.
+//!
+//! ```toml
+//! test_command = "cargo test --workspace"
+//! lint_command = "cargo clippy --workspace -- -D warnings"
+//! disabled_tools = ["browse"]
+//! prompts = ["Public functions need doc comments."]
+//!
+//! [setup]
+//! commands = ["cargo fetch"]
+//! ```
+
+use openhands_sdk_rs::agent::AgentConfig;
+use openhands_sdk_rs::provenance::{self, Provenance};
+use openhands_sdk_rs::setup::SetupConfig;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Location of the project configuration, relative to the project root.
+pub const PROJECT_CONFIG_PATH: &str = ".openhands/config.toml";
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ProjectConfig {
+    /// Run in the workspace after the server's `[setup]`.
+    #[serde(default)]
+    pub setup: SetupConfig,
+    /// How the project's tests are run.
+    #[serde(default)]
+    pub test_command: Option<String>,
+    /// How the project is linted.
+    #[serde(default)]
+    pub lint_command: Option<String>,
+    /// Tools, by name, the agent may not use in the project.
+    #[serde(default)]
+    pub disabled_tools: Vec<String>,
+    /// Added to the agent's system prompt.
+    #[serde(default)]
+    pub prompts: Vec<String>,
+}
+
+impl ProjectConfig {
+    /// The configuration of the project at `root`, or `None` if it has none.
+    pub fn discover(root: &Path) -> Result<Option<Self>, String> {
+        let path = root.join(PROJECT_CONFIG_PATH);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", PROJECT_CONFIG_PATH, e))?;
+        Self::parse(&content)
+            .map(Some)
+            .map_err(|e| format!("Invalid {}: {}", PROJECT_CONFIG_PATH, e))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| e.to_string())
+    }
+
+    /// `server`'s setup followed by the project's.
+    pub fn setup(&self, server: &SetupConfig) -> SetupConfig {
+        server.extend(&self.setup)
+    }
+
+    /// `server` with the project's tools disabled too.
+    pub fn agent_config(&self, server: &AgentConfig) -> AgentConfig {
+        let mut config = server.clone();
+        for tool in &self.disabled_tools {
+            if !config.disabled_tools.contains(tool) {
+                config.disabled_tools.push(tool.clone());
+            }
+        }
+        config
+    }
+
+    /// What the project adds to the agent's system prompt: its commands, then its snippets,
+    /// each marked as external content from the repository.
+    pub fn prompt_extensions(&self) -> Vec<String> {
+        let commands: Vec<String> = [
+            ("Run the tests", &self.test_command),
+            ("Run the linter", &self.lint_command),
+        ]
+        .into_iter()
+        .filter_map(|(what, command)| {
+            command
+                .as_ref()
+                .map(|command| format!("* {} with `{}`.", what, command))
+        })
+        .collect();
+        let mut extensions = Vec::new();
+        if !commands.is_empty() {
+            extensions.push(format!(
+                "<PROJECT_COMMANDS>\n{}\nRun them to verify your changes before you \
+                 finish.\n</PROJECT_COMMANDS>",
+                commands.join("\n")
+            ));
+        }
+        extensions.extend(self.prompts.iter().cloned());
+        extensions
+            .iter()
+            .map(|extension| provenance::tag(Provenance::External, PROJECT_CONFIG_PATH, extension))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_config() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        assert_eq!(ProjectConfig::discover(temp_dir.path()).unwrap(), None);
+
+        let path = temp_dir.path().join(PROJECT_CONFIG_PATH);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            r#"
+test_command = "cargo test"
+disabled_tools = ["browse", "execute_bash"]
+prompts = ["Keep the changelog up to date."]
+
+[setup]
+commands = ["cargo fetch"]
+"#,
+        )
+        .unwrap();
+        let project = ProjectConfig::discover(temp_dir.path()).unwrap().unwrap();
+
+        let server_setup = SetupConfig {
+            commands: vec!["apt-get install -y jq".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            project.setup(&server_setup).commands,
+            ["apt-get install -y jq", "cargo fetch"]
+        );
+        let server_agent = AgentConfig {
+            disabled_tools: vec!["execute_bash".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            project.agent_config(&server_agent).disabled_tools,
+            ["execute_bash", "browse"]
+        );
+        assert_eq!(
+            project.prompt_extensions(),
+            [
+                "<content provenance=\"external\" source=\".openhands/config.toml\">\n\
+                 <PROJECT_COMMANDS>\n* Run the tests with `cargo test`.\n\
+                 Run them to verify your changes before you finish.\n</PROJECT_COMMANDS>\n\
+                 </content>",
+                "<content provenance=\"external\" source=\".openhands/config.toml\">\n\
+                 Keep the changelog up to date.\n</content>"
+            ]
+        );
+
+        std::fs::write(&path, "disabled_tools = \"browse\"").unwrap();
+        let error = ProjectConfig::discover(temp_dir.path()).unwrap_err();
+        assert!(
+            error.starts_with("Invalid .openhands/config.toml"),
+            "{}",
+            error
+        );
+    }
+}
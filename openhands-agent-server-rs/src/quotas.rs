@@ -0,0 +1,362 @@
+//! Per-user request rate limits and usage quotas.
+//!
+//! Limits come from `[quotas]` in `openhands.toml`, overridable per permission profile with
+//! `[profiles.<name>.quotas]`; a limit a profile leaves unset keeps the server's. Counters
+//! are kept per user rather than per API key, so every key of a user shares them, and are
+//! persisted to a JSON file, so restarting the server does not reset a window. Exceeded
+//! limits are answered with `429 Too Many Requests` and a `Retry-After` header.
+//!
+//! LLM spend is estimated from token usage with `[llm_pricing]` and counted for
+//! conversations, tasks, issue resolution and scheduled runs, whether they succeed or not.
+//! Scheduled runs are skipped rather than started while their owner is over the limit.
+
+use crate::api::AppState;
+use crate::auth::User;
+use crate::permissions::PermissionProfile;
+use axum::{
+    extract::{Request, State},
+    http::{header::RETRY_AFTER, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Duration, DurationRound, NaiveDate, Utc};
+use openhands_sdk_rs::llm::TokenUsage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::Mutex;
+
+/// Suggested wait when a limit is not tied to a time window (open conversations).
+const RETRY_AFTER_CONVERSATIONS: u64 = 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Quotas {
+    pub requests_per_minute: Option<u32>,
+    /// Conversations a user may have open at once.
+    pub max_conversations: Option<usize>,
+    /// Estimated LLM spend per UTC day, in USD.
+    pub daily_llm_spend_usd: Option<f64>,
+}
+
+impl Quotas {
+    /// These limits, with those left unset taken from `defaults`.
+    pub fn or(self, defaults: Quotas) -> Quotas {
+        Quotas {
+            requests_per_minute: self.requests_per_minute.or(defaults.requests_per_minute),
+            max_conversations: self.max_conversations.or(defaults.max_conversations),
+            daily_llm_spend_usd: self.daily_llm_spend_usd.or(defaults.daily_llm_spend_usd),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct LlmPricing {
+    pub input_usd_per_mtok: f64,
+    pub output_usd_per_mtok: f64,
+}
+
+impl Default for LlmPricing {
+    /// `gpt-5-nano`, the default model.
+    fn default() -> Self {
+        Self {
+            input_usd_per_mtok: 0.05,
+            output_usd_per_mtok: 0.40,
+        }
+    }
+}
+
+impl LlmPricing {
+    pub fn cost_usd(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_usd_per_mtok
+            + usage.completion_tokens as f64 * self.output_usd_per_mtok)
+            / 1_000_000.0
+    }
+}
+
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    pub message: String,
+    /// Seconds until the request may succeed.
+    pub retry_after: u64,
+}
+
+impl IntoResponse for QuotaExceeded {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, self.retry_after.to_string())],
+            self.message,
+        )
+            .into_response()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct Counters {
+    window_start: Option<DateTime<Utc>>,
+    requests: u32,
+    spend_day: Option<NaiveDate>,
+    spend_usd: f64,
+}
+
+pub struct QuotaTracker {
+    path: PathBuf,
+    defaults: Quotas,
+    pricing: LlmPricing,
+    counters: Mutex<HashMap<String, Counters>>,
+    /// Bumped with every change of the counters.
+    version: AtomicU64,
+    /// The version last written to `path`, held while writing so writes do not interleave.
+    saved: Mutex<u64>,
+}
+
+impl QuotaTracker {
+    pub fn load(path: PathBuf, defaults: Quotas, pricing: LlmPricing) -> Result<Self, String> {
+        let counters = if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            path,
+            defaults,
+            pricing,
+            counters: Mutex::new(counters),
+            version: AtomicU64::new(0),
+            saved: Mutex::new(0),
+        })
+    }
+
+    /// Snapshot of `counters` to save once their lock is released; see [`Self::save`].
+    fn snapshot(&self, counters: &HashMap<String, Counters>) -> (u64, String) {
+        let version = self.version.fetch_add(1, Ordering::SeqCst) + 1;
+        // Plain strings and numbers always serialize.
+        let content = serde_json::to_string(counters).unwrap_or_default();
+        (version, content)
+    }
+
+    /// Write a snapshot, unless a newer one has been written already.
+    async fn save(&self, (version, content): (u64, String)) {
+        let mut saved = self.saved.lock().await;
+        if *saved >= version {
+            return;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        let result = match tokio::fs::write(&tmp, content).await {
+            Ok(()) => tokio::fs::rename(&tmp, &self.path).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => *saved = version,
+            Err(e) => tracing::error!("Failed to save quota counters: {}", e),
+        }
+    }
+
+    pub fn limits(&self, profile: &PermissionProfile) -> Quotas {
+        self.limits_with(profile.quotas)
+    }
+
+    /// The server's limits with a profile's `overrides` applied.
+    pub fn limits_with(&self, overrides: Option<Quotas>) -> Quotas {
+        match overrides {
+            Some(quotas) => quotas.or(self.defaults),
+            None => self.defaults,
+        }
+    }
+
+    /// The token prices spend is estimated with.
+    pub fn pricing(&self) -> LlmPricing {
+        self.pricing
+    }
+
+    /// Count a request against the per-minute limit.
+    pub async fn hit(
+        &self,
+        user_id: &str,
+        limits: &Quotas,
+        now: DateTime<Utc>,
+    ) -> Result<(), QuotaExceeded> {
+        let Some(limit) = limits.requests_per_minute else {
+            return Ok(());
+        };
+        let minute = now.duration_trunc(Duration::minutes(1)).unwrap_or(now);
+        let mut counters = self.counters.lock().await;
+        let entry = counters.entry(user_id.to_string()).or_default();
+        if entry.window_start != Some(minute) {
+            entry.window_start = Some(minute);
+            entry.requests = 0;
+        }
+        if entry.requests >= limit {
+            return Err(QuotaExceeded {
+                message: format!("Rate limit of {} requests per minute exceeded", limit),
+                retry_after: (minute + Duration::minutes(1) - now).num_seconds().max(1) as u64,
+            });
+        }
+        entry.requests += 1;
+        let snapshot = self.snapshot(&counters);
+        drop(counters);
+        self.save(snapshot).await;
+        Ok(())
+    }
+
+    /// Fail if the user already reached today's LLM spend.
+    pub async fn check_spend(
+        &self,
+        user_id: &str,
+        limits: &Quotas,
+        now: DateTime<Utc>,
+    ) -> Result<(), QuotaExceeded> {
+        let Some(limit) = limits.daily_llm_spend_usd else {
+            return Ok(());
+        };
+        let counters = self.counters.lock().await;
+        let spent = counters
+            .get(user_id)
+            .filter(|c| c.spend_day == Some(now.date_naive()))
+            .map_or(0.0, |c| c.spend_usd);
+        if spent >= limit {
+            let tomorrow = (now.date_naive() + Duration::days(1))
+                .and_hms_opt(0, 0, 0)
+                .expect("midnight is a valid time")
+                .and_utc();
+            return Err(QuotaExceeded {
+                message: format!("Daily LLM spend limit of ${:.2} reached", limit),
+                retry_after: (tomorrow - now).num_seconds().max(1) as u64,
+            });
+        }
+        Ok(())
+    }
+
+    pub async fn record_usage(&self, user_id: &str, usage: &TokenUsage, now: DateTime<Utc>) {
+        let mut counters = self.counters.lock().await;
+        let entry = counters.entry(user_id.to_string()).or_default();
+        if entry.spend_day != Some(now.date_naive()) {
+            entry.spend_day = Some(now.date_naive());
+            entry.spend_usd = 0.0;
+        }
+        entry.spend_usd += self.pricing.cost_usd(usage);
+        let snapshot = self.snapshot(&counters);
+        drop(counters);
+        self.save(snapshot).await;
+    }
+
+    pub fn check_conversations(&self, open: usize, limits: &Quotas) -> Result<(), QuotaExceeded> {
+        match limits.max_conversations {
+            Some(max) if open >= max => Err(QuotaExceeded {
+                message: format!("Limit of {} open conversations reached", max),
+                retry_after: RETRY_AFTER_CONVERSATIONS,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Middleware enforcing the per-minute request limit. Runs after [`crate::auth::authenticate`].
+pub async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let extensions = request.extensions();
+    if let (Some(user), Some(profile)) = (
+        extensions.get::<User>(),
+        extensions.get::<PermissionProfile>(),
+    ) {
+        let limits = state.quotas.limits(profile);
+        if let Err(e) = state.quotas.hit(&user.id, &limits, Utc::now()).await {
+            return e.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_quotas_survive_restart() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("quotas.json");
+        let limits = Quotas {
+            requests_per_minute: Some(2),
+            max_conversations: Some(1),
+            daily_llm_spend_usd: Some(0.01),
+        };
+        let at = |h, mi, s| Utc.with_ymd_and_hms(2026, 10, 12, h, mi, s).unwrap();
+
+        let tracker = QuotaTracker::load(path.clone(), limits, LlmPricing::default()).unwrap();
+        tracker.hit("alice", &limits, at(9, 0, 10)).await.unwrap();
+        tracker.hit("alice", &limits, at(9, 0, 20)).await.unwrap();
+        tracker
+            .record_usage(
+                "alice",
+                &TokenUsage {
+                    prompt_tokens: 100_000,
+                    completion_tokens: 20_000,
+                },
+                at(9, 0, 30),
+            )
+            .await;
+
+        let tracker = QuotaTracker::load(path, limits, LlmPricing::default()).unwrap();
+        let err = tracker
+            .hit("alice", &limits, at(9, 0, 45))
+            .await
+            .unwrap_err();
+        assert_eq!(err.retry_after, 15);
+        tracker.hit("bob", &limits, at(9, 0, 45)).await.unwrap();
+        tracker.hit("alice", &limits, at(9, 1, 0)).await.unwrap();
+
+        // $0.005 + $0.008 is over the $0.01 budget until midnight.
+        let err = tracker
+            .check_spend("alice", &limits, at(23, 0, 0))
+            .await
+            .unwrap_err();
+        assert_eq!(err.retry_after, 3600);
+        assert!(tracker
+            .check_spend(
+                "alice",
+                &limits,
+                Utc.with_ymd_and_hms(2026, 10, 13, 0, 0, 0).unwrap()
+            )
+            .await
+            .is_ok());
+
+        assert!(tracker.check_conversations(0, &limits).is_ok());
+        assert!(tracker.check_conversations(1, &limits).is_err());
+    }
+
+    #[test]
+    fn test_profile_quotas_fall_back_to_defaults() {
+        let temp_dir = TempDir::new().unwrap();
+        let defaults = Quotas {
+            requests_per_minute: Some(60),
+            max_conversations: Some(5),
+            daily_llm_spend_usd: Some(1.0),
+        };
+        let tracker = QuotaTracker::load(
+            temp_dir.path().join("quotas.json"),
+            defaults,
+            LlmPricing::default(),
+        )
+        .unwrap();
+        let mut profile = PermissionProfile::unrestricted();
+        assert_eq!(tracker.limits(&profile), defaults);
+
+        profile.quotas = Some(Quotas {
+            requests_per_minute: Some(10),
+            ..Default::default()
+        });
+        assert_eq!(
+            tracker.limits(&profile),
+            Quotas {
+                requests_per_minute: Some(10),
+                ..defaults
+            }
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+/// A standard five-field cron expression (`minute hour day-of-month month day-of-week`),
+/// evaluated in UTC. Fields accept `*`, numbers, ranges (`1-5`), lists (`1,15`) and steps
+/// (`*/10`, `0-30/5`). The aliases `@hourly`, `@daily`, `@weekly` and `@monthly` are
+/// also accepted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days_of_month: Vec<bool>,
+    months: Vec<bool>,
+    days_of_week: Vec<bool>,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "Invalid cron expression '{}': expected 5 fields",
+                expr
+            ));
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        // Both 0 and 7 mean Sunday.
+        if days_of_week[7] {
+            days_of_week[0] = true;
+        }
+        days_of_week.truncate(7);
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            dom_restricted: fields[2] != "*",
+            dow_restricted: fields[4] != "*",
+        })
+    }
+
+    pub fn matches(&self, time: &DateTime<Utc>) -> bool {
+        let dom = self.days_of_month[time.day() as usize];
+        let dow = self.days_of_week[time.weekday().num_days_from_sunday() as usize];
+        // As in cron, a restricted day-of-month and day-of-week match if either does.
+        let day = match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        };
+
+        day && self.minutes[time.minute() as usize]
+            && self.hours[time.hour() as usize]
+            && self.months[time.month() as usize]
+    }
+}
+
+/// Parse one field into a lookup table indexed by value.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>, String> {
+    let mut allowed = vec![false; max as usize + 1];
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("Invalid step in cron field '{}'", field))?,
+            ),
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, field)?, parse_value(b, field)?)
+        } else {
+            let value = parse_value(range, field)?;
+            // `5/10` means "from 5, every 10".
+            (value, if part.contains('/') { max } else { value })
+        };
+
+        if start < min || end > max || start > end {
+            return Err(format!(
+                "Cron field '{}' is out of range {}-{}",
+                field, min, max
+            ));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            allowed[value as usize] = true;
+        }
+    }
+    Ok(allowed)
+}
+
+fn parse_value(value: &str, field: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value '{}' in cron field '{}'", value, field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn test_cron_matches() {
+        // Mondays at 09:00 (2026-10-12 is a Monday).
+        let weekly = CronSchedule::parse("0 9 * * 1").unwrap();
+        assert!(weekly.matches(&at(2026, 10, 12, 9, 0)));
+        assert!(!weekly.matches(&at(2026, 10, 13, 9, 0)));
+        assert!(!weekly.matches(&at(2026, 10, 12, 9, 1)));
+
+        let every_15 = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert!(every_15.matches(&at(2026, 1, 1, 3, 45)));
+        assert!(!every_15.matches(&at(2026, 1, 1, 3, 50)));
+
+        let sunday = CronSchedule::parse("@weekly").unwrap();
+        assert!(sunday.matches(&at(2026, 10, 11, 0, 0)));
+        assert_eq!(
+            CronSchedule::parse("0 0 * * 7").unwrap(),
+            CronSchedule::parse("0 0 * * 0").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cron_rejects_invalid() {
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("a * * * *").is_err());
+    }
+}
@@ -0,0 +1,672 @@
+//! Recurring agent tasks.
+//!
+//! Tasks pair a cron expression with a prompt and are persisted, together with their run
+//! history, in a JSON file. The scheduler wakes up once a minute and starts every task whose
+//! schedule matches; a task that is still running when it comes due again is skipped (and
+//! the skip recorded) rather than started a second time.
+
+pub mod cron;
+
+use crate::api::AppState;
+use crate::auth::{user_workspace_dir, User};
+use crate::permissions::{PermissionProfile, API_SCHEDULES};
+use crate::quotas::{QuotaTracker, Quotas};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use cron::CronSchedule;
+use openhands_sdk_rs::agent::tools::default_tools;
+use openhands_sdk_rs::agent::{Agent, AgentConfig};
+use openhands_sdk_rs::events::{Event, MessageEvent};
+use openhands_sdk_rs::llm::{LLMConfig, TokenUsage, LLM};
+use openhands_sdk_rs::paths;
+use openhands_sdk_rs::runtime::LocalRuntime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Run records kept per task.
+const MAX_RUNS_PER_TASK: usize = 50;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScheduledTask {
+    pub id: String,
+    /// Id of the user who created the task.
+    #[serde(default)]
+    pub owner: String,
+    pub name: String,
+    /// Five-field cron expression, evaluated in UTC.
+    pub cron: String,
+    pub prompt: String,
+    /// Directory the agent works in, relative to `workspace_root`; defaults to
+    /// `scheduled/<id>` there.
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+    /// The owner's workspace when the task was created, which the agent may not leave;
+    /// `<workspace>/users/<owner>` if unset.
+    #[serde(default)]
+    pub workspace_root: Option<PathBuf>,
+    /// Agent tools the owner's permission profile did not allow when the task was created.
+    #[serde(default)]
+    pub disabled_tools: Vec<String>,
+    /// Quotas of the owner's permission profile when the task was created; runs are skipped
+    /// while the owner is over the daily LLM spend limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quotas: Option<Quotas>,
+}
+
+impl ScheduledTask {
+    /// The directory a run works in, refusing one outside the owner's workspace (e.g.
+    /// through `..` or a symlink created since the task was added).
+    fn run_dir(&self, workspace_dir: &std::path::Path) -> Result<PathBuf, String> {
+        let root = self
+            .workspace_root
+            .clone()
+            .unwrap_or_else(|| user_workspace_dir(workspace_dir, &self.owner));
+        let dir = match &self.working_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(&root)
+                    .map_err(|e| format!("Failed to create {}: {}", root.display(), e))?;
+                paths::confine(&root, &dir.to_string_lossy())?
+            }
+            None => root.join("scheduled").join(&self.id),
+        };
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        // `confine` does not follow the last component, so check where it leads.
+        let canonical = dir.canonicalize().map_err(|e| e.to_string())?;
+        if !canonical.starts_with(root.canonicalize().map_err(|e| e.to_string())?) {
+            return Err(format!(
+                "Working directory {} is outside the workspace",
+                dir.display()
+            ));
+        }
+        Ok(canonical)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NewScheduledTask {
+    pub name: String,
+    pub cron: String,
+    pub prompt: String,
+    /// Relative to the owner's workspace.
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Running,
+    Succeeded,
+    Failed,
+    /// The previous run was still in progress when the task came due.
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunRecord {
+    pub run_id: String,
+    pub task_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: RunStatus,
+    /// The agent's final message, or the error.
+    pub summary: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ScheduleFile {
+    tasks: Vec<ScheduledTask>,
+    runs: Vec<RunRecord>,
+}
+
+pub struct Scheduler {
+    path: PathBuf,
+    workspace_dir: PathBuf,
+    data: Mutex<ScheduleFile>,
+    running: Mutex<HashSet<String>>,
+    quotas: Option<Arc<QuotaTracker>>,
+    agent_config: AgentConfig,
+}
+
+impl Scheduler {
+    /// Load schedules from `path`, starting empty if it does not exist yet. Runs left in
+    /// the `running` state by a previous process are marked as failed.
+    pub fn load(path: PathBuf, workspace_dir: PathBuf) -> Result<Self, String> {
+        let mut data: ScheduleFile = if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?
+        } else {
+            ScheduleFile::default()
+        };
+        for run in data.runs.iter_mut() {
+            if run.status == RunStatus::Running {
+                run.status = RunStatus::Failed;
+                run.summary = "Interrupted by server restart".to_string();
+            }
+        }
+
+        Ok(Self {
+            path,
+            workspace_dir,
+            data: Mutex::new(data),
+            running: Mutex::new(HashSet::new()),
+            quotas: None,
+            agent_config: AgentConfig::default(),
+        })
+    }
+
+    /// Count the LLM spend of runs against their owners' quotas.
+    pub fn with_quotas(mut self, quotas: Arc<QuotaTracker>) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    /// Run the agent with `config`, on top of which each task's disabled tools apply.
+    pub fn with_agent_config(mut self, config: AgentConfig) -> Self {
+        self.agent_config = config;
+        self
+    }
+
+    fn save(&self, data: &ScheduleFile) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let content = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, content)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+
+    /// Tasks visible to `user`: their own, or all of them for admins.
+    pub async fn tasks_for(&self, user: &User) -> Vec<ScheduledTask> {
+        self.data
+            .lock()
+            .await
+            .tasks
+            .iter()
+            .filter(|t| user.admin || t.owner == user.id)
+            .cloned()
+            .collect()
+    }
+
+    /// Whether `user` may see and manage the task `id`.
+    pub async fn can_access(&self, user: &User, id: &str) -> bool {
+        self.data
+            .lock()
+            .await
+            .tasks
+            .iter()
+            .any(|t| t.id == id && (user.admin || t.owner == user.id))
+    }
+
+    /// Add a task of `owner`, whose runs stay within `workspace_root` and go without
+    /// `disabled_tools`.
+    pub async fn add(
+        &self,
+        owner: &str,
+        task: NewScheduledTask,
+        workspace_root: PathBuf,
+        disabled_tools: Vec<String>,
+        quotas: Option<Quotas>,
+    ) -> Result<ScheduledTask, String> {
+        CronSchedule::parse(&task.cron)?;
+        let task = ScheduledTask {
+            id: uuid::Uuid::new_v4().to_string(),
+            owner: owner.to_string(),
+            name: task.name,
+            cron: task.cron,
+            prompt: task.prompt,
+            working_dir: task.working_dir,
+            workspace_root: Some(workspace_root),
+            disabled_tools,
+            quotas,
+        };
+        task.run_dir(&self.workspace_dir)?;
+        let mut data = self.data.lock().await;
+        data.tasks.push(task.clone());
+        self.save(&data)?;
+        Ok(task)
+    }
+
+    /// Remove a task and its history. Returns false if it does not exist.
+    pub async fn remove(&self, id: &str) -> Result<bool, String> {
+        let mut data = self.data.lock().await;
+        let before = data.tasks.len();
+        data.tasks.retain(|t| t.id != id);
+        if data.tasks.len() == before {
+            return Ok(false);
+        }
+        data.runs.retain(|r| r.task_id != id);
+        self.save(&data)?;
+        Ok(true)
+    }
+
+    /// Run history of a task, most recent first.
+    pub async fn runs(&self, task_id: &str) -> Vec<RunRecord> {
+        let data = self.data.lock().await;
+        data.runs
+            .iter()
+            .rev()
+            .filter(|r| r.task_id == task_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Tasks whose schedule matches `now`. Tasks with an invalid expression (e.g. edited
+    /// by hand in the schedule file) are logged and never fire.
+    async fn due(&self, now: &DateTime<Utc>) -> Vec<ScheduledTask> {
+        let data = self.data.lock().await;
+        data.tasks
+            .iter()
+            .filter(|task| match CronSchedule::parse(&task.cron) {
+                Ok(schedule) => schedule.matches(now),
+                Err(e) => {
+                    tracing::error!("Schedule '{}': {}", task.name, e);
+                    false
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    async fn record(&self, record: RunRecord) {
+        let mut data = self.data.lock().await;
+        match data.runs.iter_mut().find(|r| r.run_id == record.run_id) {
+            Some(existing) => *existing = record,
+            None => {
+                let task_runs = data
+                    .runs
+                    .iter()
+                    .filter(|r| r.task_id == record.task_id)
+                    .count();
+                if task_runs >= MAX_RUNS_PER_TASK {
+                    if let Some(oldest) = data
+                        .runs
+                        .iter()
+                        .position(|r| r.task_id == record.task_id && r.status != RunStatus::Running)
+                    {
+                        data.runs.remove(oldest);
+                    }
+                }
+                data.runs.push(record);
+            }
+        }
+        if let Err(e) = self.save(&data) {
+            tracing::error!("Failed to save run history: {}", e);
+        }
+    }
+
+    /// Start every task due at `now`, returning the handles of the runs started.
+    pub async fn tick(self: &Arc<Self>, now: DateTime<Utc>) -> Vec<tokio::task::JoinHandle<()>> {
+        let mut handles = Vec::new();
+        for task in self.due(&now).await {
+            let mut record = RunRecord {
+                run_id: uuid::Uuid::new_v4().to_string(),
+                task_id: task.id.clone(),
+                started_at: now,
+                finished_at: None,
+                status: RunStatus::Running,
+                summary: String::new(),
+            };
+
+            if let Some(quotas) = &self.quotas {
+                let limits = quotas.limits_with(task.quotas);
+                if let Err(e) = quotas.check_spend(&task.owner, &limits, now).await {
+                    tracing::warn!("Skipping '{}': {}", task.name, e.message);
+                    record.finished_at = Some(now);
+                    record.status = RunStatus::Skipped;
+                    record.summary = format!("Quota exceeded: {}", e.message);
+                    self.record(record).await;
+                    continue;
+                }
+            }
+
+            if !self.running.lock().await.insert(task.id.clone()) {
+                tracing::warn!("Skipping '{}': previous run still in progress", task.name);
+                record.finished_at = Some(now);
+                record.status = RunStatus::Skipped;
+                record.summary = "Previous run still in progress".to_string();
+                self.record(record).await;
+                continue;
+            }
+
+            tracing::info!(
+                "Scheduled run {} started for '{}'",
+                record.run_id,
+                task.name
+            );
+            self.record(record.clone()).await;
+            let scheduler = self.clone();
+            handles.push(tokio::spawn(async move {
+                let result = match task.run_dir(&scheduler.workspace_dir) {
+                    Ok(working_dir) => {
+                        let (result, usage) =
+                            run_task(&task, working_dir, &scheduler.agent_config).await;
+                        if let Some(quotas) = &scheduler.quotas {
+                            quotas.record_usage(&task.owner, &usage, Utc::now()).await;
+                        }
+                        result
+                    }
+                    Err(e) => Err(e),
+                };
+                record.finished_at = Some(Utc::now());
+                (record.status, record.summary) = match result {
+                    Ok(summary) => (RunStatus::Succeeded, summary),
+                    Err(e) => {
+                        tracing::error!("Scheduled run for '{}' failed: {}", task.name, e);
+                        (RunStatus::Failed, e)
+                    }
+                };
+                scheduler.record(record).await;
+                scheduler.running.lock().await.remove(&task.id);
+            }));
+        }
+        handles
+    }
+
+    /// Check the schedules at the start of every minute, forever.
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            let now = Utc::now();
+            let minute = now.duration_trunc(Duration::minutes(1)).unwrap_or(now);
+            let next = minute + Duration::minutes(1);
+            tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+            self.tick(next).await;
+        }
+    }
+}
+
+/// The agent's final message, and the tokens it used whether it succeeded or not.
+async fn run_task(
+    task: &ScheduledTask,
+    working_dir: PathBuf,
+    config: &AgentConfig,
+) -> (Result<String, String>, TokenUsage) {
+    let mut config = config.clone();
+    for tool in &task.disabled_tools {
+        if !config.disabled_tools.contains(tool) {
+            config.disabled_tools.push(tool.clone());
+        }
+    }
+    let mut runtime = LocalRuntime::new(default_tools(working_dir.clone()));
+    let agent = Agent::new(
+        LLM::new(LLMConfig::from_env()),
+        format!(
+            "You run the recurring task '{}' unattended. Your workspace is {}.",
+            task.name,
+            working_dir.display()
+        ),
+    )
+    .with_config(config);
+    let history = vec![Event::Message(MessageEvent {
+        source: "user".to_string(),
+        content: task.prompt.clone(),
+        file_refs: Vec::new(),
+    })];
+    let result = agent
+        .step_events(&history, &mut runtime)
+        .await
+        .map(|events| match events.last() {
+            Some(Event::Message(m)) => m.content.clone(),
+            _ => String::new(),
+        })
+        .map_err(|e| e.to_string());
+    (result, agent.usage())
+}
+
+pub async fn list_schedules(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Json<Vec<ScheduledTask>> {
+    Json(state.scheduler.tasks_for(&user).await)
+}
+
+pub async fn create_schedule(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Json(task): Json<NewScheduledTask>,
+) -> Result<(StatusCode, Json<ScheduledTask>), (StatusCode, String)> {
+    profile.require_api(API_SCHEDULES)?;
+    CronSchedule::parse(&task.cron).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let disabled_tools = profile.disabled_tools(&state.conversations.lock().await.tool_names());
+    let task = state
+        .scheduler
+        .add(
+            &user.id,
+            task,
+            profile.workspace_dir(&state.workspace_dir, &user.id),
+            disabled_tools,
+            profile.quotas,
+        )
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    state.audit.record(&user, "schedule.create", &task.id).await;
+    Ok((StatusCode::CREATED, Json(task)))
+}
+
+pub async fn delete_schedule(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    profile.require_api(API_SCHEDULES)?;
+    if !state.scheduler.can_access(&user, &id).await {
+        return Err((StatusCode::NOT_FOUND, format!("No schedule {}", id)));
+    }
+    match state.scheduler.remove(&id).await {
+        Ok(true) => {
+            state.audit.record(&user, "schedule.delete", &id).await;
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Err((StatusCode::NOT_FOUND, format!("No schedule {}", id))),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+pub async fn list_runs(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<RunRecord>>, (StatusCode, String)> {
+    if !state.scheduler.can_access(&user, &id).await {
+        return Err((StatusCode::NOT_FOUND, format!("No schedule {}", id)));
+    }
+    Ok(Json(state.scheduler.runs(&id).await))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn new_task(cron: &str) -> NewScheduledTask {
+        NewScheduledTask {
+            name: "nightly".to_string(),
+            cron: cron.to_string(),
+            prompt: "Update the changelog".to_string(),
+            working_dir: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_schedules_persist() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("schedules.json");
+        let scheduler = Scheduler::load(path.clone(), temp_dir.path().to_path_buf()).unwrap();
+        let alice_root = user_workspace_dir(temp_dir.path(), "alice");
+
+        assert!(scheduler
+            .add(
+                "alice",
+                new_task("not cron"),
+                alice_root.clone(),
+                Vec::new(),
+                None
+            )
+            .await
+            .is_err());
+        // The profile's restrictions are stored with the task and apply to its runs.
+        let task = scheduler
+            .add(
+                "alice",
+                new_task("0 3 * * *"),
+                alice_root.clone(),
+                vec!["cmd".to_string()],
+                None,
+            )
+            .await
+            .unwrap();
+
+        let user = |id: &str, admin| User {
+            id: id.to_string(),
+            name: id.to_string(),
+            admin,
+            profile: None,
+            created_at: Utc::now(),
+        };
+        let reloaded = Scheduler::load(path, temp_dir.path().to_path_buf()).unwrap();
+        assert_eq!(
+            reloaded.tasks_for(&user("alice", false)).await,
+            vec![task.clone()]
+        );
+        assert!(reloaded.tasks_for(&user("bob", false)).await.is_empty());
+        assert!(!reloaded.can_access(&user("bob", false), &task.id).await);
+        assert!(reloaded.can_access(&user("root", true), &task.id).await);
+        assert!(reloaded.remove(&task.id).await.unwrap());
+        assert!(!reloaded.remove(&task.id).await.unwrap());
+        assert!(reloaded.tasks_for(&user("alice", false)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_run_is_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        let scheduler = Arc::new(
+            Scheduler::load(
+                temp_dir.path().join("schedules.json"),
+                temp_dir.path().to_path_buf(),
+            )
+            .unwrap(),
+        );
+        let task = scheduler
+            .add(
+                "alice",
+                new_task("*/5 * * * *"),
+                user_workspace_dir(temp_dir.path(), "alice"),
+                Vec::new(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        let at = |mi| Utc.with_ymd_and_hms(2026, 10, 12, 9, mi, 0).unwrap();
+        assert!(scheduler.tick(at(1)).await.is_empty());
+
+        // Pretend a run is still in progress when the task comes due.
+        scheduler.running.lock().await.insert(task.id.clone());
+        assert!(scheduler.tick(at(5)).await.is_empty());
+
+        let runs = scheduler.runs(&task.id).await;
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, RunStatus::Skipped);
+        assert_eq!(runs[0].started_at, at(5));
+    }
+
+    #[tokio::test]
+    async fn test_run_over_quota_is_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        let quotas = Arc::new(
+            QuotaTracker::load(
+                temp_dir.path().join("quotas.json"),
+                Quotas::default(),
+                Default::default(),
+            )
+            .unwrap(),
+        );
+        let scheduler = Arc::new(
+            Scheduler::load(
+                temp_dir.path().join("schedules.json"),
+                temp_dir.path().to_path_buf(),
+            )
+            .unwrap()
+            .with_quotas(quotas.clone()),
+        );
+        // The owner's profile limits spend to $0.01 a day.
+        let task = scheduler
+            .add(
+                "alice",
+                new_task("*/5 * * * *"),
+                user_workspace_dir(temp_dir.path(), "alice"),
+                Vec::new(),
+                Some(Quotas {
+                    daily_llm_spend_usd: Some(0.01),
+                    ..Quotas::default()
+                }),
+            )
+            .await
+            .unwrap();
+
+        let at = |mi| Utc.with_ymd_and_hms(2026, 10, 12, 9, mi, 0).unwrap();
+        let usage = TokenUsage {
+            prompt_tokens: 100_000,
+            completion_tokens: 20_000,
+        };
+        quotas.record_usage("alice", &usage, at(0)).await;
+        assert!(scheduler.tick(at(5)).await.is_empty());
+
+        let runs = scheduler.runs(&task.id).await;
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, RunStatus::Skipped);
+        assert!(runs[0].summary.starts_with("Quota exceeded"));
+    }
+
+    #[tokio::test]
+    async fn test_working_dir_stays_in_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let scheduler = Scheduler::load(
+            temp_dir.path().join("schedules.json"),
+            temp_dir.path().to_path_buf(),
+        )
+        .unwrap();
+        let root = user_workspace_dir(temp_dir.path(), "alice");
+        let in_dir = |dir: &str| NewScheduledTask {
+            working_dir: Some(PathBuf::from(dir)),
+            ..new_task("0 3 * * *")
+        };
+
+        let task = scheduler
+            .add("alice", in_dir("reports"), root.clone(), Vec::new(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            task.run_dir(temp_dir.path()).unwrap(),
+            root.canonicalize().unwrap().join("reports")
+        );
+        for outside in ["../bob", "/etc"] {
+            assert!(scheduler
+                .add("alice", in_dir(outside), root.clone(), Vec::new(), None)
+                .await
+                .is_err());
+        }
+
+        // A symlink planted after the task was added is refused when it runs.
+        #[cfg(unix)]
+        {
+            std::fs::remove_dir(root.join("reports")).unwrap();
+            std::os::unix::fs::symlink("/etc", root.join("reports")).unwrap();
+            assert!(task.run_dir(temp_dir.path()).is_err());
+        }
+    }
+}
@@ -1,41 +1,186 @@
-use openhands_sdk_rs::models::{BashEvent, ExecuteBashRequest};
+use axum::http::request::Parts;
+use base64::prelude::*;
+use openhands_sdk_rs::bus::{BroadcastSink, BusPayload, DropPolicy, Subscription};
+use openhands_sdk_rs::hints::hint_for;
+use openhands_sdk_rs::journal::FileMutationJournal;
+use openhands_sdk_rs::models::{
+    BashCommand, BashEvent, BashEventPage, BashEventQuery, BashOutput, DownloadRequest,
+    ExecuteBashRequest, FileTransferRequest,
+};
 use openhands_sdk_rs::runtime::bash::BashEventService;
-use openhands_sdk_rs::runtime::file::FileService;
+use openhands_sdk_rs::runtime::download::download_file;
+use openhands_sdk_rs::runtime::file::{copy_path, move_path, FileService};
+use openhands_sdk_rs::shell_output;
 use rmcp::{
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
+    handler::server::{
+        router::tool::ToolRouter,
+        tool::{Extension, ToolCallContext},
+        wrapper::Parameters,
+    },
     model::*,
-    schemars,
     service::RequestContext,
-    tool, tool_handler, tool_router, ErrorData as McpError, RoleServer, ServerHandler,
+    tool, tool_router, ErrorData as McpError, RoleServer, ServerHandler,
 };
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tokio::time::{sleep, Duration};
+use tokio::time::{sleep, timeout, Duration, Instant};
+use uuid::Uuid;
 
+use crate::auth::User;
+use crate::display::DisplayService;
+use crate::limits::{ToolLimiter, ToolLimits};
+use crate::permissions::PermissionProfile;
+use crate::tools::blocking::run_blocking;
+use crate::tools::database::{run_database, DatabaseArgs};
+use crate::tools::descriptions::describe_tools;
+use crate::tools::environment_info::run_environment_info;
 use crate::tools::file_editor::{run_file_editor, FileEditorArgs};
 use crate::tools::file_ops::{
-    run_delete_file, run_list_files, run_read_file, run_write_file, DeleteFileArgs, ListFilesArgs,
-    ReadFileArgs, WriteFileArgs,
+    run_delete_file, run_list_files, run_read_file, run_read_media, run_stat_file, run_write_file,
+    DeleteFileArgs, ListFilesArgs, ReadFileArgs, StatFileArgs, WriteFileArgs,
 };
 use crate::tools::glob::{run_glob, GlobArgs};
 use crate::tools::grep::{run_grep, GrepArgs};
+use crate::tools::query_data::{run_query_data, QueryDataArgs};
+use crate::tools::recent_commands::{run_recent_commands, RecentCommandsArgs};
 use crate::tools::task_tracker::{run_task_tracker, TaskTrackerArgs};
 
 #[derive(Clone)]
 pub struct OpenHandsService {
     bash: Arc<BashEventService>,
     file: Arc<FileService>,
-    editor_history: Arc<Mutex<HashMap<PathBuf, Vec<String>>>>,
+    /// Shared by the file tools, so the editor's undo_edit reverts any tool's change.
+    journal: FileMutationJournal,
+    display: Option<Arc<DisplayService>>,
+    events: Option<Arc<BroadcastSink>>,
+    /// Postgres/MySQL URLs the `database` tool may query, by name.
+    databases: Arc<BTreeMap<String, String>>,
+    /// How many tool calls run at once.
+    limiter: Arc<ToolLimiter>,
     tool_router: ToolRouter<OpenHandsService>,
 }
 
-#[derive(serde::Deserialize, schemars::JsonSchema)]
-pub struct ExecuteBashArgs {
-    pub command: String,
-    pub cwd: Option<String>,
-    pub timeout: Option<u64>,
+/// Only offered when the server runs a virtual display.
+const SCREENSHOT_TOOL: &str = "screenshot_display";
+
+/// Root directory for a tool call, chosen by the caller's permission profile.
+#[derive(Clone)]
+struct Workspace(PathBuf);
+
+/// How long `execute_bash` waits for a command's output.
+const BASH_OUTPUT_WAIT: Duration = Duration::from_secs(300);
+
+/// The structured content of an `execute_bash` or `terminal` result.
+#[derive(serde::Serialize, Debug)]
+struct BashResult {
+    command_id: Uuid,
+    exit_code: Option<i32>,
+    /// Exited with 0 within its timeout.
+    success: bool,
+    timed_out: bool,
+    duration_ms: i64,
+    /// stdout and stderr in the order the command wrote them.
+    output: String,
+    hint: Option<String>,
+}
+
+/// The result of running `command`: a status line followed by its output as text, the
+/// same as [`BashResult`] in structured content, and flagged as an error unless the
+/// command succeeded.
+fn bash_result(command: &BashCommand, out: &BashOutput) -> CallToolResult {
+    let duration = out.timestamp - command.timestamp;
+    let success = out.exit_code == Some(0) && !out.timed_out;
+    let status = if out.timed_out {
+        format!("[Command timed out after {}s]", command.timeout)
+    } else {
+        let exit_code = out
+            .exit_code
+            .map_or("?".to_string(), |code| code.to_string());
+        let outcome = if success { "succeeded" } else { "FAILED" };
+        format!(
+            "[Command {} with exit code {} in {:.1}s]",
+            outcome,
+            exit_code,
+            duration.num_milliseconds() as f64 / 1000.0
+        )
+    };
+    let output = shell_output::clean(&out.combined_output()).into_owned();
+    let structured = BashResult {
+        command_id: command.id,
+        exit_code: out.exit_code,
+        success,
+        timed_out: out.timed_out,
+        duration_ms: duration.num_milliseconds(),
+        hint: hint_for(out.exit_code, &output).map(|hint| hint.to_string()),
+        output,
+    };
+    CallToolResult {
+        content: vec![Content::text(format!(
+            "{}\n{}",
+            status,
+            format_bash_output(out)
+        ))],
+        structured_content: serde_json::to_value(&structured).ok(),
+        is_error: Some(!success),
+        meta: None,
+    }
+}
+
+/// The output of a command, stdout and stderr in the order it wrote them, and its exit
+/// code as one text, with a hint if it failed with a known error. The output is cleaned
+/// as `[shell_output]` says; the stored event keeps it raw.
+fn format_bash_output(out: &BashOutput) -> String {
+    let mut result_str = shell_output::clean(&out.combined_output()).into_owned();
+    if let Some(exit_code) = out.exit_code {
+        if !result_str.is_empty() {
+            result_str.push('\n');
+        }
+        result_str.push_str(&format!("[Command finished with exit code {}]", exit_code));
+    }
+    if let Some(hint) = hint_for(out.exit_code, &result_str) {
+        result_str.push_str(&format!("\n{}", hint));
+    }
+    result_str
+}
+
+/// Bash events as text for an agent, one command or output after another.
+fn format_bash_events(page: &BashEventPage) -> String {
+    if page.items.is_empty() {
+        return "No matching bash events.".to_string();
+    }
+    page.items
+        .iter()
+        .map(|event| match event {
+            BashEvent::BashCommand(c) => format!(
+                "[{}] command {}: $ {}",
+                c.timestamp.to_rfc3339(),
+                c.id,
+                c.command
+            ),
+            BashEvent::BashOutput(o) => format!(
+                "[{}] output of {}:\n{}",
+                o.timestamp.to_rfc3339(),
+                o.command_id,
+                format_bash_output(o)
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A file operation's response as structured content, flagged as an error if it failed.
+fn structured_result(
+    response: impl serde::Serialize,
+    success: bool,
+) -> Result<CallToolResult, McpError> {
+    let value = serde_json::to_value(&response)
+        .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+    Ok(if success {
+        CallToolResult::structured(value)
+    } else {
+        CallToolResult::structured_error(value)
+    })
 }
 
 #[tool_router]
@@ -44,20 +189,104 @@ impl OpenHandsService {
         Self {
             bash: Arc::new(bash),
             file: Arc::new(file),
-            editor_history: Arc::new(Mutex::new(HashMap::new())),
+            journal: FileMutationJournal::default(),
+            display: None,
+            events: None,
+            databases: Arc::new(BTreeMap::new()),
+            limiter: Arc::new(ToolLimiter::default()),
             tool_router: Self::tool_router(),
         }
     }
 
+    /// Wait for bash output on the event bus instead of polling the bash service.
+    pub fn with_events(mut self, events: Arc<BroadcastSink>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Wait for the output of `command_id` on the event bus.
+    async fn wait_for_output(
+        &self,
+        mut events: Subscription,
+        command_id: Uuid,
+    ) -> Option<BashOutput> {
+        let wait = async {
+            while let Some(event) = events.recv().await {
+                match event.payload {
+                    BusPayload::Bash(BashEvent::BashOutput(out))
+                        if out.command_id == command_id =>
+                    {
+                        return Some(out);
+                    }
+                    // The output may have been among the missed events.
+                    BusPayload::Gap { .. } => {
+                        if let Some(out) = self.saved_output(command_id) {
+                            return Some(out);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            None
+        };
+        timeout(BASH_OUTPUT_WAIT, wait).await.ok().flatten()
+    }
+
+    /// Poll the bash service until the output of `command_id` has been saved.
+    async fn poll_for_output(&self, command_id: Uuid) -> Option<BashOutput> {
+        let deadline = Instant::now() + BASH_OUTPUT_WAIT;
+        while Instant::now() < deadline {
+            sleep(Duration::from_millis(100)).await;
+            if let Some(out) = self.saved_output(command_id) {
+                return Some(out);
+            }
+        }
+        None
+    }
+
+    /// The output of `command_id`, if it has been saved.
+    fn saved_output(&self, command_id: Uuid) -> Option<BashOutput> {
+        let page = self
+            .bash
+            .search_bash_events(&BashEventQuery::for_command(command_id))
+            .ok()?;
+        match page.items.last() {
+            Some(BashEvent::BashOutput(out)) => Some(out.clone()),
+            _ => None,
+        }
+    }
+
+    /// The bash service the MCP tools run commands with.
+    pub fn bash_events(&self) -> Arc<BashEventService> {
+        self.bash.clone()
+    }
+
+    pub fn with_display(mut self, display: Arc<DisplayService>) -> Self {
+        self.display = Some(display);
+        self
+    }
+
+    pub fn with_databases(mut self, databases: BTreeMap<String, String>) -> Self {
+        self.databases = Arc::new(databases);
+        self
+    }
+
+    /// Limit how many tool calls run at once; unlimited by default.
+    pub fn with_limits(mut self, limits: &ToolLimits) -> Self {
+        self.limiter = Arc::new(ToolLimiter::new(limits));
+        self
+    }
+
     #[tool(
         name = "glob",
         description = "Fast file pattern matching tool. Finds files by name patterns (e.g. '**/*.js'). Returns matching file paths."
     )]
     async fn glob_files(
         &self,
+        Extension(workspace): Extension<Workspace>,
         Parameters(args): Parameters<GlobArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_glob(&args, &self.file.workspace_dir)?;
+        let output = run_blocking(move || run_glob(&args, &workspace.0)).await?;
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
 
@@ -67,9 +296,10 @@ impl OpenHandsService {
     )]
     async fn grep_files(
         &self,
+        Extension(workspace): Extension<Workspace>,
         Parameters(args): Parameters<GrepArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_grep(&args, &self.file.workspace_dir)?;
+        let output = run_blocking(move || run_grep(&args, &workspace.0)).await?;
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
 
@@ -79,21 +309,55 @@ impl OpenHandsService {
     )]
     async fn task_tracker(
         &self,
+        Extension(workspace): Extension<Workspace>,
         Parameters(args): Parameters<TaskTrackerArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_task_tracker(&args, &self.file.workspace_dir)?;
+        let output = run_blocking(move || run_task_tracker(&args, &workspace.0)).await?;
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
 
     #[tool(
         name = "file_editor",
-        description = "Edit files. Commands: view, create, str_replace, insert, undo_edit."
+        description = "Edit files. Commands: view, create, str_replace, insert, replace_lines, regex_replace, undo_edit. view takes view_range, tail_lines or find to show part of a file; long files are truncated."
     )]
     async fn file_editor(
         &self,
+        Extension(workspace): Extension<Workspace>,
         Parameters(args): Parameters<FileEditorArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_file_editor(&args, &self.file.workspace_dir, &self.editor_history).await?;
+        let output = run_file_editor(&args, &workspace.0, &self.journal).await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "search_bash_events",
+        description = "Search the commands run with execute_bash and terminal, and their output. Filter by command text (`command`) or regular expression (`command_regex`), `exit_code`, `failed` for failures only, time (`since`/`until` in RFC 3339, or `within_secs` for the last N seconds) and `kind` (BashCommand or BashOutput)."
+    )]
+    async fn search_bash_events(
+        &self,
+        Parameters(query): Parameters<BashEventQuery>,
+    ) -> Result<CallToolResult, McpError> {
+        let bash = self.bash.clone();
+        let output = run_blocking(move || {
+            Ok(match bash.search_bash_events(&query) {
+                Ok(page) => format_bash_events(&page),
+                Err(e) => format!("Error: {}", e),
+            })
+        })
+        .await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "recent_commands",
+        description = "Summarize the last bash commands (10 by default, `count` for more): each command with its exit code, duration and the end of its output (`output_chars`, 200 by default). Use it to recall what was already tried instead of running commands again."
+    )]
+    async fn recent_commands(
+        &self,
+        Parameters(args): Parameters<RecentCommandsArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let bash = self.bash.clone();
+        let output = run_blocking(move || run_recent_commands(&args, &bash)).await?;
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
 
@@ -103,7 +367,7 @@ impl OpenHandsService {
     )]
     async fn terminal(
         &self,
-        Parameters(args): Parameters<ExecuteBashArgs>,
+        Parameters(args): Parameters<ExecuteBashRequest>,
     ) -> Result<CallToolResult, McpError> {
         // Reuse execute_bash logic
         self.execute_bash(Parameters(args)).await
@@ -112,63 +376,50 @@ impl OpenHandsService {
     #[tool(name = "execute_bash", description = "Execute a bash command")]
     async fn execute_bash(
         &self,
-        Parameters(args): Parameters<ExecuteBashArgs>,
+        Parameters(req): Parameters<ExecuteBashRequest>,
     ) -> Result<CallToolResult, McpError> {
-        let req = ExecuteBashRequest {
-            command: args.command,
-            cwd: args.cwd,
-            timeout: args.timeout,
+        // Subscribe before starting so the output cannot be missed.
+        let events = self
+            .events
+            .as_ref()
+            .map(|events| events.subscribe(DropPolicy::DropOldest));
+        let cmd = self
+            .bash
+            .start_bash_command(req)
+            .map_err(|e| McpError::internal_error(e, None))?;
+        let output = match events {
+            Some(events) => self.wait_for_output(events, cmd.id).await,
+            None => self.poll_for_output(cmd.id).await,
         };
-
-        let cmd = self.bash.start_bash_command(req);
-
-        // Simple polling loop
-        let mut attempts = 0;
-        loop {
-            sleep(Duration::from_millis(100)).await;
-            let page = self.bash.search_bash_events(Some(cmd.id));
-            if let Some(last_item) = page.items.last() {
-                if let BashEvent::BashOutput(out) = last_item {
-                    // Combine stdout and stderr
-                    let mut result_str = String::new();
-                    if let Some(stdout) = &out.stdout {
-                        result_str.push_str(stdout);
-                    }
-                    if let Some(stderr) = &out.stderr {
-                        if !result_str.is_empty() {
-                            result_str.push('\n');
-                        }
-                        result_str.push_str(stderr);
-                    }
-                    if let Some(exit_code) = out.exit_code {
-                        if !result_str.is_empty() {
-                            result_str.push('\n');
-                        }
-                        result_str
-                            .push_str(&format!("[Command finished with exit code {}]", exit_code));
-                    }
-                    return Ok(CallToolResult::success(vec![Content::text(result_str)]));
-                }
-            }
-
-            attempts += 1;
-            if attempts > 3000 {
-                return Err(McpError {
-                    code: ErrorCode(0),
-                    message: "Polling timed out".to_string().into(),
-                    data: None,
-                });
-            }
+        match output {
+            Some(out) => Ok(bash_result(&cmd, &out)),
+            None => Err(McpError {
+                code: ErrorCode(0),
+                message: "Timed out waiting for the command's output"
+                    .to_string()
+                    .into(),
+                data: None,
+            }),
         }
     }
 
-    #[tool(name = "read_file", description = "Read a file from the workspace")]
+    #[tool(
+        name = "read_file",
+        description = "Read a file from the workspace. Images are returned as images."
+    )]
     async fn read_file(
         &self,
+        Extension(workspace): Extension<Workspace>,
         Parameters(args): Parameters<ReadFileArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_read_file(&args, &self.file.workspace_dir)?;
-        Ok(CallToolResult::success(vec![Content::text(output)]))
+        run_blocking(move || {
+            if let Some(media) = run_read_media(&args, &workspace.0)? {
+                return Ok(CallToolResult::success(vec![media]));
+            }
+            let output = run_read_file(&args, &workspace.0)?;
+            Ok(CallToolResult::success(vec![Content::text(output)]))
+        })
+        .await
     }
 
     #[tool(
@@ -177,35 +428,172 @@ impl OpenHandsService {
     )]
     async fn write_file(
         &self,
+        Extension(workspace): Extension<Workspace>,
         Parameters(args): Parameters<WriteFileArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_write_file(&args, &self.file.workspace_dir)?;
+        let journal = self.journal.clone();
+        let output = run_blocking(move || run_write_file(&args, &workspace.0, &journal)).await?;
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
 
     #[tool(
         name = "list_files",
-        description = "List files in a directory in the workspace"
+        description = "List files in a directory in the workspace, optionally recursively, filtered by include/exclude globs and drawn as a tree. Entries ignored by .gitignore are skipped."
     )]
     async fn list_files(
         &self,
+        Extension(workspace): Extension<Workspace>,
         Parameters(args): Parameters<ListFilesArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_list_files(&args, &self.file.workspace_dir)?;
+        let output = run_blocking(move || run_list_files(&args, &workspace.0)).await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "query_data",
+        description = "Query a JSON, YAML, TOML or CSV file in the workspace without reading all of it: jq-style paths like `.dependencies | keys` or `.items[].name`, or for CSV `SELECT cols WHERE col > 3 ORDER BY col DESC LIMIT 10`"
+    )]
+    async fn query_data(
+        &self,
+        Extension(workspace): Extension<Workspace>,
+        Parameters(args): Parameters<QueryDataArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_blocking(move || run_query_data(&args, &workspace.0)).await?;
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
 
+    #[tool(
+        name = "database",
+        description = "Inspect a SQLite file in the workspace (path) or a database configured on the server (database), read-only: command `tables` lists tables, `schema` shows a table's definition, `query` runs one read-only SQL statement and returns up to `limit` rows as CSV"
+    )]
+    async fn database(
+        &self,
+        Extension(workspace): Extension<Workspace>,
+        Parameters(args): Parameters<DatabaseArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_database(&args, &workspace.0, &self.databases).await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "environment_info",
+        description = "Report the OS, architecture, CPUs, memory, installed interpreters and compilers with their versions (python, node, cargo, go, gcc, java, ...) and available package managers"
+    )]
+    async fn environment_info(&self) -> Result<CallToolResult, McpError> {
+        Ok(CallToolResult::success(vec![Content::text(
+            run_environment_info().await,
+        )]))
+    }
+
+    #[tool(
+        name = "stat_file",
+        description = "Get the type, size, modification time, permissions, line count and SHA-256 hash of a path in the workspace, without reading it"
+    )]
+    async fn stat_file(
+        &self,
+        Extension(workspace): Extension<Workspace>,
+        Parameters(args): Parameters<StatFileArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let output = run_blocking(move || run_stat_file(&args, &workspace.0)).await?;
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(
+        name = "screenshot_display",
+        description = "Take a screenshot of the virtual display that GUI applications started from the shell draw on."
+    )]
+    async fn screenshot_display(&self) -> Result<CallToolResult, McpError> {
+        let display = self.display.as_ref().ok_or_else(|| {
+            McpError::invalid_request("Virtual display is not enabled".to_string(), None)
+        })?;
+        let png = display
+            .screenshot()
+            .await
+            .map_err(|e| McpError::internal_error(e, None))?;
+        Ok(CallToolResult::success(vec![Content::image(
+            BASE64_STANDARD.encode(png),
+            "image/png",
+        )]))
+    }
+
+    #[tool(
+        name = "move_path",
+        description = "Move or rename a file or directory within the workspace. Fails if the destination exists unless overwrite is set."
+    )]
+    async fn move_path(
+        &self,
+        Extension(workspace): Extension<Workspace>,
+        Parameters(args): Parameters<FileTransferRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        let response = run_blocking(move || Ok(move_path(&workspace.0, args))).await?;
+        structured_result(&response, response.success)
+    }
+
+    #[tool(
+        name = "copy_path",
+        description = "Copy a file or directory within the workspace. Fails if the destination exists unless overwrite is set."
+    )]
+    async fn copy_path(
+        &self,
+        Extension(workspace): Extension<Workspace>,
+        Parameters(args): Parameters<FileTransferRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        let response = run_blocking(move || Ok(copy_path(&workspace.0, args))).await?;
+        structured_result(&response, response.success)
+    }
+
+    #[tool(
+        name = "download_file",
+        description = "Download an http(s) URL into the workspace, up to a size limit. Verifies the content against sha256 if given, and resumes an interrupted download of the same path."
+    )]
+    async fn download_file(
+        &self,
+        Extension(workspace): Extension<Workspace>,
+        Parameters(args): Parameters<DownloadRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        let response = download_file(&workspace.0, args).await;
+        structured_result(&response, response.success)
+    }
+
     #[tool(name = "delete_file", description = "Delete a file from the workspace")]
     async fn delete_file(
         &self,
+        Extension(workspace): Extension<Workspace>,
         Parameters(args): Parameters<DeleteFileArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let output = run_delete_file(&args, &self.file.workspace_dir)?;
+        let journal = self.journal.clone();
+        let output = run_blocking(move || run_delete_file(&args, &workspace.0, &journal)).await?;
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
 }
 
-#[tool_handler]
+impl OpenHandsService {
+    /// Every MCP tool with its argument schema, regardless of permissions.
+    pub fn tool_definitions(display_enabled: bool) -> Vec<Tool> {
+        Self::tool_router()
+            .list_all()
+            .into_iter()
+            .filter(|tool| display_enabled || tool.name != SCREENSHOT_TOOL)
+            .collect()
+    }
+
+    /// The caller's permission profile and tool workspace, from the extensions the HTTP
+    /// authentication middleware attached to the request. Without them (authentication
+    /// disabled or not served over HTTP) every tool is allowed on the shared workspace.
+    fn caller_access(&self, context: &RequestContext<RoleServer>) -> (PermissionProfile, PathBuf) {
+        let parts = context.extensions.get::<Parts>();
+        let profile = parts
+            .and_then(|p| p.extensions.get::<PermissionProfile>())
+            .cloned()
+            .unwrap_or_else(PermissionProfile::unrestricted);
+        let workspace = match parts.and_then(|p| p.extensions.get::<User>()) {
+            Some(user) => profile.workspace_dir(&self.file.workspace_dir, &user.id),
+            None => self.file.workspace_dir.clone(),
+        };
+        (profile, workspace)
+    }
+}
+
 impl ServerHandler for OpenHandsService {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
@@ -221,6 +609,111 @@ impl ServerHandler for OpenHandsService {
         _request: InitializeRequestParam,
         _context: RequestContext<RoleServer>,
     ) -> Result<InitializeResult, McpError> {
-        Ok(self.get_info().into())
+        Ok(self.get_info())
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let (profile, workspace) = self.caller_access(&context);
+        let tools = Self::tool_definitions(self.display.is_some())
+            .into_iter()
+            .filter(|tool| profile.allows_tool(&tool.name))
+            .collect();
+        Ok(ListToolsResult {
+            tools: describe_tools(tools, &workspace),
+            next_cursor: None,
+            meta: None,
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        mut context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let (profile, workspace) = self.caller_access(&context);
+        if !profile.allows_tool(&request.name) {
+            return Err(McpError::invalid_request(
+                format!("Tool '{}' is not permitted for this API key", request.name),
+                None,
+            ));
+        }
+        std::fs::create_dir_all(&workspace).map_err(|e| {
+            McpError::internal_error(format!("Failed to create workspace: {}", e), None)
+        })?;
+        context.extensions.insert(Workspace(workspace));
+        let _permit = self
+            .limiter
+            .acquire(&request.name)
+            .await
+            .map_err(|e| McpError::internal_error(e, None))?;
+        self.tool_router
+            .call(ToolCallContext::new(self, request, context))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration as ChronoDuration, Utc};
+    use openhands_sdk_rs::models::SCHEMA_VERSION;
+
+    fn output(command: &BashCommand, exit_code: i32, timed_out: bool, text: &str) -> BashOutput {
+        BashOutput {
+            schema_version: SCHEMA_VERSION,
+            id: Uuid::new_v4(),
+            timestamp: command.timestamp + ChronoDuration::milliseconds(1500),
+            command_id: command.id,
+            order: 0,
+            exit_code: Some(exit_code),
+            stdout: None,
+            stderr: Some(text.to_string()),
+            merged_output: Some(text.to_string()),
+            chunks: Vec::new(),
+            timed_out,
+        }
+    }
+
+    #[test]
+    fn test_bash_result_reports_exit_status() {
+        let command = BashCommand {
+            schema_version: SCHEMA_VERSION,
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            command: "make".to_string(),
+            cwd: None,
+            timeout: 60,
+        };
+        let text = |result: &CallToolResult| result.content[0].as_text().unwrap().text.clone();
+
+        let ok = bash_result(&command, &output(&command, 0, false, "built\n"));
+        assert_eq!(ok.is_error, Some(false));
+        assert!(text(&ok).starts_with("[Command succeeded with exit code 0 in 1.5s]\nbuilt\n"));
+
+        let failed = bash_result(
+            &command,
+            &output(&command, 127, false, "bash: make: command not found"),
+        );
+        assert_eq!(failed.is_error, Some(true));
+        assert!(text(&failed).starts_with("[Command FAILED with exit code 127 in 1.5s]"));
+        let structured = failed.structured_content.unwrap();
+        assert_eq!(structured["exit_code"], 127);
+        assert_eq!(structured["success"], false);
+        assert_eq!(structured["timed_out"], false);
+        assert_eq!(structured["duration_ms"], 1500);
+        assert_eq!(structured["output"], "bash: make: command not found");
+        assert!(structured["hint"]
+            .as_str()
+            .unwrap()
+            .starts_with("[Hint (missing_command)]"));
+
+        let timed_out = bash_result(&command, &output(&command, -1, true, "Command timed out"));
+        assert_eq!(timed_out.is_error, Some(true));
+        assert!(text(&timed_out).starts_with("[Command timed out after 60s]"));
+        assert_eq!(timed_out.structured_content.unwrap()["timed_out"], true);
     }
 }
@@ -0,0 +1,658 @@
+//! Headless tasks: run the agent on a task to completion with a single request.
+//!
+//! `POST /api/tasks` queues the task and returns at once. At most
+//! `OPENHANDS_MAX_CONCURRENT_TASKS` (default 2) tasks run at a time; the others wait in the
+//! queue, which holds up to `OPENHANDS_MAX_QUEUED_TASKS` (default 100). A running task gets
+//! a conversation of its own in a fresh workspace, with the repository cloned into it if
+//! one was given, and the agent is prompted to continue until it calls `finish`. The
+//! repository's `.openhands/config.toml`, if any, adds to the server's setup, disabled tools
+//! and prompt; see [`crate::project`].
+//!
+//! Every task is saved as `<id>.json` in the tasks directory, and after each turn of the
+//! agent so is a checkpoint of its history. Tasks that were queued or running when the
+//! server stopped are started again on the next start: those with a checkpoint continue in
+//! their workspace from the last completed turn, the others start over. The task's
+//! conversation stays around after it finishes so its events and files can be inspected;
+//! delete it when done.
+
+use crate::api::AppState;
+use crate::auth::User;
+use crate::conversations::{run_turn, DEFAULT_SYSTEM_MESSAGE};
+use crate::permissions::{PermissionProfile, API_TASKS};
+use crate::project::{ProjectConfig, PROJECT_CONFIG_PATH};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::Utc;
+use openhands_sdk_rs::events::Event;
+use openhands_sdk_rs::models::{PromptUpdate, TaskInfo, TaskRequest, TaskState};
+use openhands_sdk_rs::outcome::TaskResult;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::Semaphore;
+
+pub const DEFAULT_MAX_CONCURRENT_TASKS: usize = 2;
+pub const DEFAULT_MAX_QUEUED_TASKS: usize = 100;
+/// Turns the agent gets to call `finish` before the task is failed.
+const MAX_TASK_TURNS: usize = 10;
+const CONTINUE_PROMPT: &str = "Continue with the task. Call `finish` when you are done.";
+const RESUME_PROMPT: &str = "The server restarted while you were working on the task. \
+    Check the state of the workspace, then continue. Call `finish` when you are done.";
+
+/// The agent's progress on a task as of its last completed turn.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Checkpoint {
+    history: Vec<Event>,
+    turns: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Task {
+    owner: String,
+    info: TaskInfo,
+    workspace_dir: PathBuf,
+    #[serde(default)]
+    checkpoint: Option<Checkpoint>,
+    /// Agent tools the owner's permission profile does not allow.
+    #[serde(default)]
+    disabled_tools: Vec<String>,
+}
+
+pub struct TaskQueue {
+    dir: PathBuf,
+    tasks: Mutex<HashMap<String, Task>>,
+    /// Held while a task file is written, so writes land in the order of their snapshots.
+    saving: Mutex<()>,
+    slots: Arc<Semaphore>,
+    max_queued: usize,
+}
+
+impl TaskQueue {
+    /// Load the tasks saved in `dir`, starting empty if it does not exist yet. Running
+    /// tasks are queued again; see [`resume`]. Files that do not parse, e.g. cut off by a
+    /// crash, are renamed to `<id>.json.invalid` and skipped.
+    pub fn load(dir: PathBuf, max_concurrent: usize, max_queued: usize) -> Result<Self, String> {
+        let mut tasks = HashMap::new();
+        if dir.exists() {
+            let entries = std::fs::read_dir(&dir)
+                .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+            for entry in entries.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                if path.extension().is_none_or(|ext| ext != "json") {
+                    continue;
+                }
+                let content = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                let mut task: Task = match serde_json::from_str(&content) {
+                    Ok(task) => task,
+                    Err(e) => {
+                        tracing::error!("Skipping task {}: {}", path.display(), e);
+                        if let Err(e) = std::fs::rename(&path, path.with_extension("json.invalid"))
+                        {
+                            tracing::error!("Failed to set aside {}: {}", path.display(), e);
+                        }
+                        continue;
+                    }
+                };
+                if task.info.state == TaskState::Running {
+                    task.info.state = TaskState::Queued;
+                }
+                tasks.insert(task.info.id.clone(), task);
+            }
+        }
+        Ok(Self {
+            dir,
+            tasks: Mutex::new(tasks),
+            saving: Mutex::new(()),
+            slots: Arc::new(Semaphore::new(max_concurrent)),
+            max_queued,
+        })
+    }
+
+    /// [`TaskQueue::load`] with limits from `OPENHANDS_MAX_CONCURRENT_TASKS` and
+    /// `OPENHANDS_MAX_QUEUED_TASKS`.
+    pub fn from_env(dir: PathBuf) -> Result<Self, String> {
+        let limit = |name: &str, default: usize| match std::env::var(name) {
+            Ok(value) => value
+                .parse::<usize>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("Invalid {}: {}", name, value)),
+            Err(_) => Ok(default),
+        };
+        Self::load(
+            dir,
+            limit(
+                "OPENHANDS_MAX_CONCURRENT_TASKS",
+                DEFAULT_MAX_CONCURRENT_TASKS,
+            )?,
+            limit("OPENHANDS_MAX_QUEUED_TASKS", DEFAULT_MAX_QUEUED_TASKS)?,
+        )
+    }
+
+    /// Write task `id` to disk through a temporary file, so a crash never leaves it cut off.
+    /// The task is serialized under `tasks`, which is released before the write.
+    fn save(&self, tasks: MutexGuard<HashMap<String, Task>>, id: &str) {
+        let Some(task) = tasks.get(id) else {
+            return;
+        };
+        let content = serde_json::to_string(task);
+        let _saving = self.saving.lock().unwrap();
+        drop(tasks);
+
+        let path = self.dir.join(format!("{}.json", id));
+        let tmp = path.with_extension("json.tmp");
+        let saved = std::fs::create_dir_all(&self.dir)
+            .map_err(|e| e.to_string())
+            .and(content.map_err(|e| e.to_string()))
+            .and_then(|content| std::fs::write(&tmp, content).map_err(|e| e.to_string()))
+            .and_then(|()| std::fs::rename(&tmp, &path).map_err(|e| e.to_string()));
+        if let Err(e) = saved {
+            tracing::error!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+
+    /// Queue a task of `owner`, to work in `<tasks_dir>/<id>` without `disabled_tools`.
+    /// Fails when the queue is full.
+    pub fn submit(
+        &self,
+        owner: &str,
+        request: TaskRequest,
+        tasks_dir: &std::path::Path,
+        disabled_tools: Vec<String>,
+    ) -> Result<TaskInfo, String> {
+        let mut tasks = self.tasks.lock().unwrap();
+        let queued = tasks
+            .values()
+            .filter(|t| t.info.state == TaskState::Queued)
+            .count();
+        if queued >= self.max_queued {
+            return Err(format!("Limit of {} queued tasks reached", self.max_queued));
+        }
+        let info = TaskInfo {
+            id: uuid::Uuid::new_v4().to_string(),
+            task: request.task,
+            repo: request.repo,
+            branch: request.branch,
+            state: TaskState::Queued,
+            conversation_id: None,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            checkpoint_at: None,
+            result: None,
+            error: None,
+        };
+        let task = Task {
+            owner: owner.to_string(),
+            workspace_dir: tasks_dir.join(&info.id),
+            info: info.clone(),
+            checkpoint: None,
+            disabled_tools,
+        };
+        tasks.insert(info.id.clone(), task);
+        self.save(tasks, &info.id);
+        Ok(info)
+    }
+
+    /// The task `id` if it belongs to `user` (or `user` is an admin).
+    pub fn get(&self, user: &User, id: &str) -> Option<TaskInfo> {
+        self.tasks
+            .lock()
+            .unwrap()
+            .get(id)
+            .filter(|t| user.admin || t.owner == user.id)
+            .map(|t| t.info.clone())
+    }
+
+    /// Tasks of `user`, most recent first.
+    pub fn list(&self, user: &User) -> Vec<TaskInfo> {
+        let mut tasks: Vec<TaskInfo> = self
+            .tasks
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|t| user.admin || t.owner == user.id)
+            .map(|t| t.info.clone())
+            .collect();
+        tasks.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+        tasks
+    }
+
+    /// Ids of the tasks waiting to run, oldest first.
+    fn queued(&self) -> Vec<String> {
+        let tasks = self.tasks.lock().unwrap();
+        let mut queued: Vec<&TaskInfo> = tasks
+            .values()
+            .map(|t| &t.info)
+            .filter(|info| info.state == TaskState::Queued)
+            .collect();
+        queued.sort_by_key(|info| info.created_at);
+        queued.iter().map(|info| info.id.clone()).collect()
+    }
+
+    fn task(&self, id: &str) -> Option<Task> {
+        self.tasks.lock().unwrap().get(id).cloned()
+    }
+
+    fn update(&self, id: &str, update: impl FnOnce(&mut TaskInfo)) {
+        let mut tasks = self.tasks.lock().unwrap();
+        if let Some(task) = tasks.get_mut(id) {
+            update(&mut task.info);
+            self.save(tasks, id);
+        }
+    }
+
+    /// Record the history of task `id` after `turns` completed turns.
+    fn checkpoint(&self, id: &str, history: Vec<Event>, turns: usize) {
+        let mut tasks = self.tasks.lock().unwrap();
+        if let Some(task) = tasks.get_mut(id) {
+            task.checkpoint = Some(Checkpoint { history, turns });
+            task.info.checkpoint_at = Some(Utc::now());
+            self.save(tasks, id);
+        }
+    }
+
+    fn finish(&self, id: &str, outcome: Result<TaskResult, String>) {
+        self.update(id, |info| {
+            info.finished_at = Some(Utc::now());
+            match outcome {
+                Ok(result) => {
+                    info.state = TaskState::Succeeded;
+                    info.result = Some(result);
+                }
+                Err(e) => {
+                    info.state = TaskState::Failed;
+                    info.error = Some(e);
+                }
+            }
+        });
+    }
+}
+
+/// Start the tasks left queued or running by a previous server.
+pub fn resume(state: &AppState) {
+    for id in state.tasks.queued() {
+        tracing::info!("Resuming task {}", id);
+        tokio::spawn(run(state.clone(), id));
+    }
+}
+
+/// Wait for a free slot, then run task `id`.
+async fn run(state: AppState, id: String) {
+    let tasks = state.tasks.clone();
+    let Ok(_slot) = tasks.slots.clone().acquire_owned().await else {
+        return;
+    };
+    let Some(task) = tasks.task(&id) else {
+        return;
+    };
+    tasks.update(&id, |info| {
+        info.state = TaskState::Running;
+        info.started_at = Some(Utc::now());
+    });
+    tracing::info!("Task {} started", id);
+    let outcome = run_task(&state, task).await;
+    if let Err(e) = &outcome {
+        tracing::error!("Task {} failed: {}", id, e);
+    }
+    tasks.finish(&id, outcome);
+}
+
+async fn run_task(state: &AppState, task: Task) -> Result<TaskResult, String> {
+    let Task {
+        owner,
+        info,
+        workspace_dir,
+        checkpoint,
+        disabled_tools,
+    } = task;
+    if checkpoint.is_none() {
+        // Whatever an interrupted first turn left behind is discarded.
+        let dir = workspace_dir.clone();
+        tokio::task::spawn_blocking(move || {
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir)
+                    .map_err(|e| format!("Failed to clear workspace: {}", e))?;
+            }
+            std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create workspace: {}", e))
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        if let Some(repo) = &info.repo {
+            clone_repo(repo, info.branch.as_deref(), &workspace_dir).await?;
+        }
+    }
+
+    let project = ProjectConfig::discover(&workspace_dir)?.unwrap_or_default();
+    let shell_allowed = !disabled_tools.iter().any(|tool| tool == "cmd");
+    let conversation = {
+        let mut conversations = state.conversations.lock().await;
+        let mut config = project.agent_config(conversations.agent_config());
+        for tool in disabled_tools {
+            if !config.disabled_tools.contains(&tool) {
+                config.disabled_tools.push(tool);
+            }
+        }
+        conversations.create_conversation_configured(
+            &owner,
+            DEFAULT_SYSTEM_MESSAGE.to_string(),
+            workspace_dir,
+            config,
+        )
+    };
+    conversation.agent.update_prompt(PromptUpdate {
+        system_message: None,
+        extensions: Some(project.prompt_extensions()),
+    });
+    state.tasks.update(&info.id, |task| {
+        task.conversation_id = Some(conversation.id.clone());
+    });
+
+    let (mut prompt, first_turn) = match checkpoint {
+        Some(checkpoint) => {
+            if let Some(result) = TaskResult::from_history(&checkpoint.history) {
+                return Ok(result);
+            }
+            *conversation.history.write().await = checkpoint.history;
+            (RESUME_PROMPT.to_string(), checkpoint.turns)
+        }
+        None => {
+            // The repository's setup runs commands, which the owner may not be allowed to.
+            let setup = if shell_allowed {
+                project.setup(&state.setup)
+            } else {
+                if !project.setup.is_empty() {
+                    tracing::warn!(
+                        "Skipping the setup of {} for task {}: its owner may not run commands",
+                        PROJECT_CONFIG_PATH,
+                        info.id
+                    );
+                }
+                (*state.setup).clone()
+            };
+            if !setup.is_empty() {
+                let runtime = conversation.runtime.read().await;
+                let outcome = setup
+                    .run_in(&conversation.workspace_dir, runtime.as_ref())
+                    .await;
+                drop(runtime);
+                conversation
+                    .history
+                    .write()
+                    .await
+                    .push(Event::Observation(outcome));
+            }
+            (
+                format!(
+                    "{}\n\nYou are working unattended, so nobody will answer questions. \
+                    When you are done, call `finish` with the outcome.",
+                    info.task
+                ),
+                0,
+            )
+        }
+    };
+    for turn in first_turn..MAX_TASK_TURNS {
+        run_turn(state, &owner, &conversation, prompt, Vec::new()).await?;
+        if let Some(result) = conversation.task_result() {
+            return Ok(result);
+        }
+        let history = conversation.history.read().await.clone();
+        state.tasks.checkpoint(&info.id, history, turn + 1);
+        prompt = CONTINUE_PROMPT.to_string();
+    }
+    Err(format!(
+        "The agent did not call finish within {} turns",
+        MAX_TASK_TURNS
+    ))
+}
+
+/// Refuse anything but `https://`, `ssh://` and `user@host:path` remotes, so a task cannot
+/// clone local paths such as other users' workspaces.
+fn check_remote(repo: &str) -> Result<(), String> {
+    let scp_like = !repo.contains("://")
+        && !repo.starts_with('-')
+        && repo
+            .split_once(':')
+            .is_some_and(|(host, _)| host.contains('@') && !host.contains('/'));
+    if repo.starts_with("https://") || repo.starts_with("ssh://") || scp_like {
+        Ok(())
+    } else {
+        Err(format!(
+            "Only https:// and ssh remotes can be cloned, not {}",
+            repo
+        ))
+    }
+}
+
+async fn clone_repo(repo: &str, branch: Option<&str>, dir: &std::path::Path) -> Result<(), String> {
+    check_remote(repo)?;
+    let mut command = tokio::process::Command::new("git");
+    // Submodules and redirects are held to the same protocols.
+    command.env("GIT_ALLOW_PROTOCOL", "https:ssh");
+    command.args(["clone", "--depth", "1"]);
+    if let Some(branch) = branch {
+        command.args(["--branch", branch]);
+    }
+    let output = command
+        .args(["--", repo, "."])
+        .current_dir(dir)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git clone failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+pub async fn create_task(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(profile): Extension<PermissionProfile>,
+    Json(request): Json<TaskRequest>,
+) -> Result<(StatusCode, Json<TaskInfo>), Response> {
+    profile
+        .require_api(API_TASKS)
+        .map_err(IntoResponse::into_response)?;
+    state
+        .quotas
+        .check_spend(&user.id, &state.quotas.limits(&profile), Utc::now())
+        .await
+        .map_err(IntoResponse::into_response)?;
+    if request.task.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "The task is empty").into_response());
+    }
+    if let Some(repo) = &request.repo {
+        check_remote(repo).map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    }
+    let tasks_dir = profile
+        .workspace_dir(&state.workspace_dir, &user.id)
+        .join("tasks");
+    let disabled_tools = profile.disabled_tools(&state.conversations.lock().await.tool_names());
+    let info = state
+        .tasks
+        .submit(&user.id, request, &tasks_dir, disabled_tools)
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e).into_response())?;
+    state.audit.record(&user, "task.create", &info.id).await;
+    tokio::spawn(run(state.clone(), info.id.clone()));
+    Ok((StatusCode::ACCEPTED, Json(info)))
+}
+
+pub async fn list_tasks(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+) -> Json<Vec<TaskInfo>> {
+    Json(state.tasks.list(&user))
+}
+
+pub async fn get_task(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<Json<TaskInfo>, (StatusCode, String)> {
+    state
+        .tasks
+        .get(&user, &id)
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("No task {}", id)))
+}
+
+/// The outcome the agent reported, or `null` until the task has succeeded.
+pub async fn get_task_result(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<String>,
+) -> Result<Json<Option<TaskResult>>, (StatusCode, String)> {
+    state
+        .tasks
+        .get(&user, &id)
+        .map(|info| Json(info.result))
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("No task {}", id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openhands_sdk_rs::events::MessageEvent;
+    use openhands_sdk_rs::outcome::TaskStatus;
+    use tempfile::TempDir;
+
+    fn user(id: &str, admin: bool) -> User {
+        User {
+            id: id.to_string(),
+            name: id.to_string(),
+            admin,
+            profile: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn request(task: &str) -> TaskRequest {
+        TaskRequest {
+            task: task.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_remote() {
+        for repo in [
+            "https://github.com/o/r.git",
+            "ssh://git@github.com/o/r.git",
+            "git@github.com:o/r.git",
+        ] {
+            assert!(check_remote(repo).is_ok(), "{}", repo);
+        }
+        for repo in [
+            "/ws/users/bob/project",
+            "file:///ws/users/bob/project",
+            "../bob",
+            "http://example.com/r.git",
+            "ext::sh -c touch% /tmp/pwned",
+            "-uhelp",
+        ] {
+            assert!(check_remote(repo).is_err(), "{}", repo);
+        }
+    }
+
+    #[test]
+    fn test_task_queue() {
+        let temp_dir = TempDir::new().unwrap();
+        let queue = TaskQueue::load(temp_dir.path().join("tasks"), 1, 2).unwrap();
+        let workspace = temp_dir.path().join("workspace");
+        let first = queue
+            .submit("alice", request("Fix the build"), &workspace, Vec::new())
+            .unwrap();
+        let second = queue
+            .submit("alice", request("Update docs"), &workspace, Vec::new())
+            .unwrap();
+        assert_eq!(first.state, TaskState::Queued);
+        let err = queue
+            .submit("bob", request("Anything"), &workspace, Vec::new())
+            .unwrap_err();
+        assert_eq!(err, "Limit of 2 queued tasks reached");
+
+        // Running tasks no longer count against the queue.
+        queue.update(&first.id, |info| info.state = TaskState::Running);
+        let third = queue
+            .submit("bob", request("Anything"), &workspace, Vec::new())
+            .unwrap();
+
+        assert!(queue.get(&user("bob", false), &first.id).is_none());
+        assert!(queue.get(&user("root", true), &first.id).is_some());
+        assert_eq!(queue.list(&user("alice", false)).len(), 2);
+        assert_eq!(queue.list(&user("bob", false))[0].id, third.id);
+
+        let result = TaskResult {
+            status: TaskStatus::Success,
+            summary: "Fixed".to_string(),
+            changed_files: Vec::new(),
+            tests: None,
+            finished_at: Utc::now(),
+        };
+        queue.finish(&first.id, Ok(result.clone()));
+        queue.finish(&second.id, Err("git clone failed".to_string()));
+        let alice = user("alice", false);
+        let first = queue.get(&alice, &first.id).unwrap();
+        assert_eq!(first.state, TaskState::Succeeded);
+        assert_eq!(first.result, Some(result));
+        let second = queue.get(&alice, &second.id).unwrap();
+        assert_eq!(second.state, TaskState::Failed);
+        assert_eq!(second.error.as_deref(), Some("git clone failed"));
+        assert!(second.finished_at.is_some());
+    }
+
+    #[test]
+    fn test_tasks_resume_from_checkpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("tasks");
+        let workspace = temp_dir.path().join("workspace");
+        let queue = TaskQueue::load(dir.clone(), 1, 10).unwrap();
+        let done = queue
+            .submit("alice", request("Done"), &workspace, Vec::new())
+            .unwrap();
+        let running = queue
+            .submit("alice", request("Refactor"), &workspace, Vec::new())
+            .unwrap();
+        let waiting = queue
+            .submit("alice", request("Later"), &workspace, Vec::new())
+            .unwrap();
+        queue.finish(&done.id, Err("failed".to_string()));
+        queue.update(&running.id, |info| info.state = TaskState::Running);
+        let history = vec![Event::Message(MessageEvent {
+            source: "user".to_string(),
+            content: "Refactor".to_string(),
+            file_refs: Vec::new(),
+        })];
+        queue.checkpoint(&running.id, history, 3);
+
+        // As after a crash: the running task is queued again with its checkpoint, and a
+        // file cut off mid-write is set aside.
+        std::fs::write(dir.join("torn.json"), "{\"owner\": \"al").unwrap();
+        let reloaded = TaskQueue::load(dir.clone(), 1, 10).unwrap();
+        assert!(dir.join("torn.json.invalid").exists());
+        assert_eq!(reloaded.queued(), vec![running.id.clone(), waiting.id]);
+        let task = reloaded.task(&running.id).unwrap();
+        assert_eq!(task.info.state, TaskState::Queued);
+        assert!(task.info.checkpoint_at.is_some());
+        assert_eq!(task.workspace_dir, workspace.join(&running.id));
+        let checkpoint = task.checkpoint.unwrap();
+        assert_eq!(checkpoint.turns, 3);
+        assert_eq!(checkpoint.history.len(), 1);
+        assert_eq!(
+            reloaded.task(&done.id).unwrap().info.state,
+            TaskState::Failed
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! A server for end-to-end tests.
+//!
+//! [`TestServer`] serves the HTTP API and MCP on a free local port, with its state in a
+//! temporary directory, a [`TestWorkspace`] as the workspace and a [`MockLLM`] answering
+//! every conversation, so tests can go through the API like a client without a provider.
+
+use crate::api::AppState;
+use crate::audit::AuditLog;
+use crate::auth::UserStore;
+use crate::config::ServerConfig;
+use crate::events::EventHub;
+use crate::permissions::Profiles;
+use crate::quotas::QuotaTracker;
+use crate::scheduler::Scheduler;
+use crate::service::OpenHandsService;
+use crate::tasks::TaskQueue;
+use crate::webhooks::{WebhookConfig, WebhookState};
+use openhands_sdk_rs::client::AgentServerClient;
+use openhands_sdk_rs::llm::fixtures::MockLLM;
+use openhands_sdk_rs::plugins::PluginRegistry;
+use openhands_sdk_rs::runtime::bash::BashEventService;
+use openhands_sdk_rs::runtime::file::FileService;
+use openhands_sdk_rs::session::ConversationManager;
+use openhands_sdk_rs::testing::TestWorkspace;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+/// A running server, stopped when dropped.
+pub struct TestServer {
+    pub url: String,
+    pub workspace: TestWorkspace,
+    pub llm: Arc<MockLLM>,
+    _state_dir: TempDir,
+    server: JoinHandle<()>,
+}
+
+impl TestServer {
+    /// Serve `workspace` with the default configuration, without authentication.
+    pub async fn start(workspace: TestWorkspace, llm: MockLLM) -> Self {
+        let state_dir = TempDir::new().unwrap();
+        let cwd = state_dir.path();
+        let workspace_dir = workspace.path().to_path_buf();
+        let config = ServerConfig::default();
+        let llm = Arc::new(llm);
+        let events = Arc::new(EventHub::from_env().unwrap());
+
+        let bash = BashEventService::new(cwd.join("bash_events"))
+            .unwrap()
+            .with_bus(events.bus.clone());
+        let service = OpenHandsService::new(bash, FileService::new(workspace_dir.clone()))
+            .with_events(events.stream.clone());
+        let bash = service.bash_events();
+        let plugins = PluginRegistry::new();
+        let conversations = ConversationManager::new()
+            .with_plugins(plugins.clone())
+            .with_mock_llm(llm.clone());
+        let state = AppState {
+            workspace_dir: workspace_dir.clone(),
+            display: None,
+            webhooks: Arc::new(WebhookState::new(WebhookConfig::from_env())),
+            scheduler: Arc::new(
+                Scheduler::load(cwd.join("schedules.json"), workspace_dir).unwrap(),
+            ),
+            tasks: Arc::new(TaskQueue::load(cwd.join("tasks"), 1, 10).unwrap()),
+            users: Arc::new(UserStore::load(cwd.join("users.json"), None).unwrap()),
+            profiles: Arc::new(Profiles::new(config.profiles)),
+            quotas: Arc::new(
+                QuotaTracker::load(cwd.join("quotas.json"), config.quotas, config.llm_pricing)
+                    .unwrap(),
+            ),
+            audit: Arc::new(AuditLog::new(cwd.join("audit.log"))),
+            conversations: Arc::new(Mutex::new(conversations)),
+            setup: Arc::new(config.setup),
+            resolve_runs: Arc::default(),
+            plugins,
+            events,
+            bash,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = crate::router(state, service);
+        let server = tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+        Self {
+            url,
+            workspace,
+            llm,
+            _state_dir: state_dir,
+            server,
+        }
+    }
+
+    pub fn client(&self) -> AgentServerClient {
+        AgentServerClient::new(&self.url)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openhands_sdk_rs::export::ExportFormat;
+    use openhands_sdk_rs::llm::LLMResponse;
+    use openhands_sdk_rs::models::InitConversationRequest;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_conversation_end_to_end() {
+        let llm = MockLLM::new(
+            serde_json::from_value::<Vec<LLMResponse>>(json!([
+                {
+                    "content": "",
+                    "tool_calls": [{
+                        "call_id": "call_1",
+                        "fn_name": "glob",
+                        "fn_arguments": { "pattern": "**/*.rs" },
+                    }],
+                    "usage": { "prompt_tokens": 0, "completion_tokens": 0 },
+                },
+                {
+                    "content": "There are no Rust files yet.",
+                    "tool_calls": [],
+                    "usage": { "prompt_tokens": 0, "completion_tokens": 0 },
+                },
+            ]))
+            .unwrap(),
+        );
+        let server = TestServer::start(TestWorkspace::seeded(1, 2), llm).await;
+        let client = server.client();
+
+        let conversation = client
+            .create_conversation(&InitConversationRequest::default())
+            .await
+            .unwrap();
+        let reply = client
+            .send_message(&conversation.id, "List the Rust files")
+            .await
+            .unwrap();
+        assert_eq!(reply.response, "There are no Rust files yet.");
+        assert_eq!(server.llm.remaining(), 0);
+        // Each conversation works in a directory of its own, so the fixture is unchanged.
+        assert_eq!(
+            server.workspace.files(),
+            TestWorkspace::seeded(1, 2).files()
+        );
+
+        let report = client
+            .export(&conversation.id, ExportFormat::Markdown)
+            .await
+            .unwrap();
+        assert!(report.contains("### User\n\nList the Rust files"));
+        assert!(report.contains("### Agent ran `glob`"));
+        let html = client
+            .export(&conversation.id, ExportFormat::Html)
+            .await
+            .unwrap();
+        assert!(html.contains("<p class=\"text\">There are no Rust files yet.</p>"));
+    }
+}
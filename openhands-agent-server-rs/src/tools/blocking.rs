@@ -0,0 +1,96 @@
+//! Running the file tools' filesystem work off the async runtime.
+//!
+//! The tools read, walk and write files with `std::fs`. Called directly from an MCP
+//! handler, a slow disk or a large tree stalls every other request served by the same
+//! worker thread. [`run_blocking`] moves the work to tokio's blocking threads instead, and
+//! bounds how many tool calls use them at once so a burst of calls cannot take them all.
+
+use rmcp::ErrorData as McpError;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Tool calls doing blocking work at once; further calls wait for one to finish.
+pub const MAX_BLOCKING_CALLS: usize = 64;
+
+lazy_static::lazy_static! {
+    static ref POOL: BlockingPool = BlockingPool::new(MAX_BLOCKING_CALLS);
+}
+
+/// Blocking work, at most `size` at a time.
+#[derive(Clone)]
+pub struct BlockingPool {
+    slots: Arc<Semaphore>,
+}
+
+impl BlockingPool {
+    pub fn new(size: usize) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(size.max(1))),
+        }
+    }
+
+    /// Run `work` on a blocking thread once a slot is free.
+    pub async fn run<T, F>(&self, work: F) -> Result<T, McpError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T, McpError> + Send + 'static,
+    {
+        let _slot = self
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        tokio::task::spawn_blocking(work)
+            .await
+            .map_err(|e| McpError::internal_error(format!("Tool call failed: {}", e), None))?
+    }
+}
+
+/// Run `work` in the pool shared by the server's tools.
+pub async fn run_blocking<T, F>(work: F) -> Result<T, McpError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, McpError> + Send + 'static,
+{
+    POOL.run(work).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// Calls that each block for `duration`, made at once on a single-threaded runtime.
+    async fn concurrent_calls(pool: &BlockingPool, calls: usize, duration: Duration) -> Duration {
+        let started = Instant::now();
+        let handles: Vec<_> = (0..calls)
+            .map(|_| {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    pool.run(move || {
+                        std::thread::sleep(duration);
+                        Ok(())
+                    })
+                    .await
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+        started.elapsed()
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_blocking_calls_run_concurrently() {
+        let duration = Duration::from_millis(100);
+        // The one runtime thread stays free, so the calls overlap instead of queueing.
+        let elapsed = concurrent_calls(&BlockingPool::new(8), 8, duration).await;
+        assert!(elapsed < duration * 4, "took {:?}", elapsed);
+
+        // And no more than the pool's size run at once.
+        let elapsed = concurrent_calls(&BlockingPool::new(2), 4, duration).await;
+        assert!(elapsed >= duration * 2, "took {:?}", elapsed);
+    }
+}
@@ -0,0 +1,287 @@
+//! The descriptions MCP clients see for the server's tools.
+//!
+//! The `#[tool]` attributes hold a short summary of each tool. [`describe_tools`] expands
+//! it when the tools are listed, in the manner of OpenHands' tool prompts: where the tool
+//! works (the caller's workspace), what it will and will not do, example arguments, and
+//! how it reports errors. Models pick and call tools more accurately with all of that in
+//! front of them.
+
+use rmcp::model::Tool;
+use std::borrow::Cow;
+use std::path::Path;
+
+/// How a tool tells the caller that a call failed.
+#[derive(Clone, Copy)]
+enum Errors {
+    /// A text reply starting with `Error:`.
+    Text,
+    /// Structured content with `success: false` and an `error`.
+    Structured,
+    /// A status line with the exit code; failed commands are flagged as errors.
+    ExitStatus,
+}
+
+impl Errors {
+    fn describe(self) -> &'static str {
+        match self {
+            Errors::Text => {
+                "A failed call replies with text starting with `Error:` that says what went wrong. Correct the arguments rather than repeating the same call."
+            }
+            Errors::Structured => {
+                "The result is JSON with `success`; when it is false, `error` says what went wrong and the result is flagged as an error."
+            }
+            Errors::ExitStatus => {
+                "The result starts with a status line with the exit code and duration. A non-zero exit or a timeout flags the result as an error, and known errors end with a `[Hint (...)]` line suggesting a fix."
+            }
+        }
+    }
+}
+
+/// What a tool's description adds to its summary. `{workspace}` in `notes` and `examples`
+/// stands for the caller's workspace.
+struct Guide {
+    tool: &'static str,
+    notes: &'static [&'static str],
+    /// Arguments of typical calls, as JSON.
+    examples: &'static [&'static str],
+    errors: Errors,
+}
+
+/// Said of every tool taking workspace paths.
+const RELATIVE_PATHS: &str = "Relative paths are resolved against the workspace {workspace}.";
+
+const GUIDES: &[Guide] = &[
+    Guide {
+        tool: "execute_bash",
+        notes: &[
+            "Runs `command` with `bash -c` in `cwd`, which defaults to the server's working directory; pass `\"cwd\": \"{workspace}\"` to run in the workspace.",
+            "Each call is a new shell: `cd` and exported variables do not carry over, so chain dependent steps with `&&`.",
+            "The command is killed after `timeout` seconds (300 by default). Start long-running servers in the background with output redirected to a file.",
+            "Commands must not wait for input; pass flags like `-y` or pipe the answers in.",
+        ],
+        examples: &[
+            r#"{"command": "cargo test 2>&1 | tail -50", "cwd": "{workspace}"}"#,
+            r#"{"command": "npm install && npm run build", "cwd": "{workspace}/frontend", "timeout": 900}"#,
+        ],
+        errors: Errors::ExitStatus,
+    },
+    Guide {
+        tool: "terminal",
+        notes: &["The same as `execute_bash`: see its description for how commands run."],
+        examples: &[r#"{"command": "ls -la", "cwd": "{workspace}"}"#],
+        errors: Errors::ExitStatus,
+    },
+    Guide {
+        tool: "file_editor",
+        notes: &[
+            RELATIVE_PATHS,
+            "`view` a file before editing it. `str_replace` needs `old_str` to match exactly once, whitespace included; add surrounding lines to make it unique.",
+            "`create` refuses to overwrite an existing file. `undo_edit` reverts the last edit of `path`.",
+        ],
+        examples: &[
+            r#"{"command": "view", "path": "src/main.rs", "view_range": [1, 40]}"#,
+            r#"{"command": "str_replace", "path": "src/main.rs", "old_str": "let x = 1;", "new_str": "let x = 2;"}"#,
+            r#"{"command": "create", "path": "notes/todo.md", "file_text": "- write tests\n"}"#,
+        ],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "read_file",
+        notes: &[
+            RELATIVE_PATHS,
+            "`offset` and `limit` select lines of long files. Lines are numbered in the reply.",
+        ],
+        examples: &[r#"{"path": "README.md", "offset": 0, "limit": 100}"#],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "write_file",
+        notes: &[
+            RELATIVE_PATHS,
+            "Replaces the whole file; prefer `file_editor` to change part of one.",
+        ],
+        examples: &[r#"{"path": "config/settings.json", "content": "{\"debug\": true}\n"}"#],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "list_files",
+        notes: &[RELATIVE_PATHS],
+        examples: &[r#"{"path": ".", "recursive": true, "depth": 2, "tree": true}"#],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "delete_file",
+        notes: &[
+            RELATIVE_PATHS,
+            "A deleted file can be restored with `file_editor`'s `undo_edit`.",
+        ],
+        examples: &[r#"{"path": "tmp/output.log"}"#],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "stat_file",
+        notes: &[RELATIVE_PATHS],
+        examples: &[r#"{"path": "data/export.csv"}"#],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "glob",
+        notes: &["Searches `path`, the workspace {workspace} by default."],
+        examples: &[r#"{"pattern": "**/*.test.ts", "path": "src"}"#],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "grep",
+        notes: &[
+            "Searches `path`, the workspace {workspace} by default. `include` limits the search to files matching a glob.",
+        ],
+        examples: &[r#"{"pattern": "fn\\s+main", "include": "*.rs"}"#],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "query_data",
+        notes: &[RELATIVE_PATHS],
+        examples: &[
+            r#"{"path": "package.json", "query": ".dependencies | keys"}"#,
+            r#"{"path": "results.csv", "query": "SELECT name, score WHERE score > 3 ORDER BY score DESC LIMIT 10"}"#,
+        ],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "database",
+        notes: &[
+            "A `path` is resolved against the workspace {workspace} and may not leave it.",
+            "Statements that would change the database are refused.",
+        ],
+        examples: &[
+            r#"{"path": "app.db", "command": "tables"}"#,
+            r#"{"path": "app.db", "command": "query", "sql": "SELECT * FROM users", "limit": 20}"#,
+        ],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "move_path",
+        notes: &[RELATIVE_PATHS],
+        examples: &[r#"{"source": "src/old_name.rs", "destination": "src/new_name.rs"}"#],
+        errors: Errors::Structured,
+    },
+    Guide {
+        tool: "copy_path",
+        notes: &[RELATIVE_PATHS],
+        examples: &[r#"{"source": "templates/base", "destination": "site/base", "overwrite": true}"#],
+        errors: Errors::Structured,
+    },
+    Guide {
+        tool: "download_file",
+        notes: &[RELATIVE_PATHS],
+        examples: &[
+            r#"{"url": "https://example.com/data.zip", "path": "downloads/data.zip", "sha256": "<hex digest>"}"#,
+        ],
+        errors: Errors::Structured,
+    },
+    Guide {
+        tool: "task_tracker",
+        notes: &[
+            "`plan` replaces the whole list; each task has a `title`, `notes` and a `status` of `todo`, `in_progress` or `done`.",
+        ],
+        examples: &[
+            r#"{"command": "plan", "task_list": [{"title": "Reproduce the bug", "notes": "", "status": "in_progress"}]}"#,
+            r#"{"command": "view"}"#,
+        ],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "search_bash_events",
+        notes: &[],
+        examples: &[r#"{"failed": true, "within_secs": 600}"#],
+        errors: Errors::Text,
+    },
+    Guide {
+        tool: "recent_commands",
+        notes: &[],
+        examples: &[r#"{"count": 5, "output_chars": 500}"#],
+        errors: Errors::Text,
+    },
+];
+
+/// The description of `tool` for a caller working in `workspace`: its summary followed by
+/// the tool's guide, if it has one.
+pub fn describe(tool: &Tool, workspace: &Path) -> String {
+    let summary = tool.description.as_deref().unwrap_or_default();
+    let Some(guide) = GUIDES.iter().find(|guide| guide.tool == tool.name) else {
+        return summary.to_string();
+    };
+    let workspace = workspace.display().to_string();
+    let fill = |text: &str| text.replace("{workspace}", &workspace);
+
+    let mut description = summary.to_string();
+    if !description.ends_with('.') {
+        description.push('.');
+    }
+    for note in guide.notes {
+        description.push_str(&format!("\n\n{}", fill(note)));
+    }
+    if !guide.examples.is_empty() {
+        description.push_str("\n\nExamples:");
+        for example in guide.examples {
+            description.push_str(&format!("\n- {}", fill(example)));
+        }
+    }
+    description.push_str(&format!("\n\n{}", guide.errors.describe()));
+    description
+}
+
+/// `tools` with their descriptions expanded by [`describe`].
+pub fn describe_tools(tools: Vec<Tool>, workspace: &Path) -> Vec<Tool> {
+    tools
+        .into_iter()
+        .map(|mut tool| {
+            tool.description = Some(Cow::Owned(describe(&tool, workspace)));
+            tool
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::OpenHandsService;
+
+    #[test]
+    fn test_descriptions_fill_in_the_workspace() {
+        let tools = describe_tools(
+            OpenHandsService::tool_definitions(false),
+            Path::new("/work/alice"),
+        );
+        let description = |name: &str| {
+            tools
+                .iter()
+                .find(|tool| tool.name == name)
+                .and_then(|tool| tool.description.clone())
+                .unwrap()
+        };
+
+        let bash = description("execute_bash");
+        assert!(bash.starts_with("Execute a bash command.\n\n"));
+        assert!(bash.contains(r#""cwd": "/work/alice"}"#));
+        assert!(bash.contains("flags the result as an error"));
+        let editor = description("file_editor");
+        assert!(editor.contains("resolved against the workspace /work/alice."));
+        assert!(editor.contains("\nExamples:\n- {\"command\": \"view\""));
+        assert!(editor.ends_with("Correct the arguments rather than repeating the same call."));
+        assert!(!tools.iter().any(|tool| tool
+            .description
+            .as_deref()
+            .is_some_and(|d| d.contains("{workspace}"))));
+
+        // Every guide belongs to a tool, so a renamed tool cannot lose its guide unnoticed.
+        let all = OpenHandsService::tool_definitions(true);
+        for guide in GUIDES {
+            assert!(
+                all.iter().any(|tool| tool.name == guide.tool),
+                "no tool named {}",
+                guide.tool
+            );
+        }
+    }
+}
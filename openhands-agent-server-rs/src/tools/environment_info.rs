@@ -0,0 +1,94 @@
+use openhands_sdk_rs::runtime::env::ToolEnv;
+use openhands_sdk_rs::system::{environment_info, EnvironmentInfo};
+
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+pub async fn run_environment_info() -> String {
+    format_environment_info(&environment_info(&ToolEnv::default()).await)
+}
+
+fn format_environment_info(info: &EnvironmentInfo) -> String {
+    let mut lines = vec![match &info.kernel {
+        Some(kernel) => format!("OS: {} (kernel {}), {}", info.os, kernel, info.arch),
+        None => format!("OS: {}, {}", info.os, info.arch),
+    }];
+    let mut cpu = format!("CPU: {} cores", info.cpus);
+    if let Some(brand) = &info.cpu_brand {
+        cpu.push_str(&format!(" ({})", brand));
+    }
+    if let Some(cpus) = info.container.as_ref().and_then(|c| c.cpus) {
+        cpu.push_str(&format!(", container quota {:.1} CPUs", cpus));
+    }
+    lines.push(cpu);
+    let mut memory = format!(
+        "Memory: {:.1} GiB available of {:.1} GiB",
+        info.memory_available as f64 / GIB,
+        info.memory_total as f64 / GIB
+    );
+    if let Some(limit) = info.container.as_ref().and_then(|c| c.memory_bytes) {
+        memory.push_str(&format!(", container limit {:.1} GiB", limit as f64 / GIB));
+    }
+    lines.push(memory);
+
+    lines.push("Toolchains:".to_string());
+    for toolchain in &info.toolchains {
+        lines.push(match (&toolchain.path, &toolchain.version) {
+            (Some(path), Some(version)) => format!("  {}: {} ({})", toolchain.name, version, path),
+            (Some(path), None) => format!("  {}: unknown version ({})", toolchain.name, path),
+            (None, _) => format!("  {}: not found", toolchain.name),
+        });
+    }
+    lines.push(format!(
+        "Package managers: {}",
+        if info.package_managers.is_empty() {
+            "none found".to_string()
+        } else {
+            info.package_managers.join(", ")
+        }
+    ));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openhands_sdk_rs::system::{ContainerLimits, Toolchain};
+
+    #[test]
+    fn test_format_environment_info() {
+        let info = EnvironmentInfo {
+            os: "Linux (Debian 12)".to_string(),
+            kernel: Some("6.1.0".to_string()),
+            arch: "x86_64".to_string(),
+            cpus: 8,
+            cpu_brand: None,
+            memory_total: 16 << 30,
+            memory_available: 12 << 30,
+            container: Some(ContainerLimits {
+                memory_bytes: Some(4 << 30),
+                cpus: Some(2.0),
+            }),
+            toolchains: vec![
+                Toolchain {
+                    name: "python3".to_string(),
+                    path: Some("/usr/bin/python3".to_string()),
+                    version: Some("Python 3.11.2".to_string()),
+                },
+                Toolchain {
+                    name: "node".to_string(),
+                    path: None,
+                    version: None,
+                },
+            ],
+            package_managers: vec!["apt-get".to_string(), "pip3".to_string()],
+        };
+        assert_eq!(
+            format_environment_info(&info),
+            "OS: Linux (Debian 12) (kernel 6.1.0), x86_64\n\
+             CPU: 8 cores, container quota 2.0 CPUs\n\
+             Memory: 12.0 GiB available of 16.0 GiB, container limit 4.0 GiB\n\
+             Toolchains:\n  python3: Python 3.11.2 (/usr/bin/python3)\n  node: not found\n\
+             Package managers: apt-get, pip3"
+        );
+    }
+}
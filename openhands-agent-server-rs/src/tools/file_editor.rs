@@ -1,23 +1,48 @@
+use crate::tools::blocking::run_blocking;
+use crate::tools::messages::msg;
+use openhands_sdk_rs::file_locks::FileLocks;
+use openhands_sdk_rs::journal::FileMutationJournal;
+use openhands_sdk_rs::paths::{encode_path, resolve};
+use regex::Regex;
 use rmcp::model::ErrorCode;
 use rmcp::schemars;
 use rmcp::ErrorData as McpError;
 use serde::Deserialize;
-use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use tokio::sync::Mutex;
 
 const SNIPPET_CONTEXT_WINDOW: usize = 4;
+/// Lines `view` shows at once; longer ranges are cut off with a notice.
+const MAX_VIEW_LINES: usize = 2000;
+/// Lines `view` shows before and after the first match of `find`.
+const FIND_CONTEXT_LINES: usize = 10;
+/// Line numbers of further `find` matches listed in the reply.
+const MAX_FIND_MATCHES_LISTED: usize = 20;
 
-#[derive(Deserialize, schemars::JsonSchema)]
+#[derive(Deserialize, schemars::JsonSchema, Default, Clone)]
 pub struct FileEditorArgs {
-    pub command: String, // view, create, str_replace, insert, undo_edit
+    pub command: String, // view, create, str_replace, insert, replace_lines, regex_replace, undo_edit
     pub path: String,
     pub file_text: Option<String>,
     pub view_range: Option<Vec<u64>>,
+    /// For view: show only the last this many lines.
+    pub tail_lines: Option<u64>,
+    /// For view: a regular expression; shows the lines around its first match.
+    pub find: Option<String>,
     pub old_str: Option<String>,
+    /// New text for str_replace, insert and replace_lines; the replacement for
+    /// regex_replace, where `$1` or `${name}` refer to capture groups.
     pub new_str: Option<String>,
     pub insert_line: Option<u64>,
+    /// First line replace_lines replaces, from 1.
+    pub start_line: Option<u64>,
+    /// Last line replace_lines replaces, inclusive.
+    pub end_line: Option<u64>,
+    /// Regular expression regex_replace looks for.
+    pub pattern: Option<String>,
+    /// How many matches regex_replace replaces, from the top. Without it the pattern must
+    /// match exactly once.
+    pub count: Option<u64>,
 }
 
 fn make_output(snippet_content: &str, snippet_description: &str, start_line: usize) -> String {
@@ -29,26 +54,65 @@ fn make_output(snippet_content: &str, snippet_description: &str, start_line: usi
         .collect();
 
     format!(
-        "Here's the result of running `cat -n` on {}:\n{}\n",
-        snippet_description,
+        "{}\n{}\n",
+        msg!("file_editor.cat_header", description = snippet_description),
         numbered_lines.join("\n")
     )
 }
 
+/// The reply to an edit: a numbered snippet of `new_content` around `line`, where the edit
+/// put `new_text`.
+fn edited_output(path: &Path, new_content: &str, line: usize, new_text: &str) -> String {
+    let start_line = line.saturating_sub(SNIPPET_CONTEXT_WINDOW);
+    let end_line = line + SNIPPET_CONTEXT_WINDOW + new_text.matches('\n').count();
+
+    let output_snippet = new_content
+        .lines()
+        .skip(start_line)
+        .take(end_line - start_line)
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    let description = msg!("file_editor.snippet_of", path = path.display());
+    msg!(
+        "file_editor.edited",
+        path = path.display(),
+        snippet = make_output(&output_snippet, &description, start_line + 1)
+    )
+}
+
+/// Line numbers, from 1, of the byte offsets `indices` into `content`.
+fn line_numbers(content: &str, indices: impl Iterator<Item = usize>) -> Vec<usize> {
+    indices
+        .map(|idx| content[..idx].chars().filter(|&c| c == '\n').count() + 1)
+        .collect()
+}
+
 pub async fn run_file_editor(
     args: &FileEditorArgs,
     workspace_dir: &Path,
-    editor_history: &Mutex<HashMap<PathBuf, Vec<String>>>,
+    journal: &FileMutationJournal,
 ) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
+    let path = resolve(workspace_dir, &args.path);
+    // Edits of the file by other calls, here or in agents' editors, wait for this one.
+    let _lock = match FileLocks::global().lock(&path).await {
+        Ok(lock) => lock,
+        Err(e) => return Ok(msg!("file_editor.error", error = e)),
+    };
+    let (args, journal) = (args.clone(), journal.clone());
+    run_blocking(move || edit(&args, path, &journal)).await
+}
 
+/// Run an editor command on `path`, holding its lock.
+fn edit(
+    args: &FileEditorArgs,
+    path: PathBuf,
+    journal: &FileMutationJournal,
+) -> Result<String, McpError> {
     match args.command.as_str() {
         "view" => {
             if !path.exists() {
-                return Ok(format!(
-                    "Error: The path {} does not exist. Please provide a valid path.",
-                    path.display()
-                ));
+                return Ok(msg!("file_editor.view_not_found", path = path.display()));
             }
             if path.is_dir() {
                 let mut formatted_paths = Vec::new();
@@ -67,7 +131,7 @@ pub async fn run_file_editor(
                 match fs::read_dir(&path) {
                     Ok(entries) => {
                         for entry in entries.flatten() {
-                            let name = entry.file_name().to_string_lossy().to_string();
+                            let name = encode_path(Path::new(&entry.file_name())).into_owned();
                             if !name.starts_with('.') {
                                 if entry.path().is_dir() {
                                     formatted_paths.push(format!("{}/", name));
@@ -78,15 +142,15 @@ pub async fn run_file_editor(
                         }
                         formatted_paths.sort();
                         Ok(format!(
-                            "Here's the files and directories in {}, excluding hidden items:\n{}",
-                            path.display(),
+                            "{}\n{}",
+                            msg!("file_editor.directory_header", path = path.display()),
                             formatted_paths.join("\n")
                         ))
                     }
-                    Err(e) => Ok(format!(
-                        "Error: Failed to list directory {}: {}",
-                        path.display(),
-                        e
+                    Err(e) => Ok(msg!(
+                        "file_editor.list_failed",
+                        path = path.display(),
+                        error = e
                     )),
                 }
             } else {
@@ -94,104 +158,177 @@ pub async fn run_file_editor(
                     Ok(content) => {
                         let lines: Vec<&str> = content.lines().collect();
                         let num_lines = lines.len();
+                        let modes = [
+                            args.view_range.is_some(),
+                            args.tail_lines.is_some(),
+                            args.find.is_some(),
+                        ];
+                        if modes.iter().filter(|&&mode| mode).count() > 1 {
+                            return Ok(msg!("file_editor.view_modes"));
+                        }
+
+                        let mut header = String::new();
                         let (start_line, end_line) = if let Some(range) = &args.view_range {
                             if range.len() != 2 {
-                                return Ok("Error: view_range should be a list of two integers."
-                                    .to_string());
+                                return Ok(msg!("file_editor.view_range_length"));
                             }
                             let s = range[0] as usize;
                             let e = range[1] as usize;
                             if s < 1 || s > num_lines {
-                                return Ok(format!("Error: Its first element `{}` should be within the range of lines of the file: [1, {}].", s, num_lines));
+                                return Ok(msg!(
+                                    "file_editor.view_range_start",
+                                    start = s,
+                                    lines = num_lines
+                                ));
                             }
                             if e < s {
-                                return Ok(format!("Error: Its second element `{}` should be greater than or equal to the first element `{}`.", e, s));
+                                return Ok(msg!("file_editor.view_range_end", end = e, start = s));
                             }
                             (s, e)
+                        } else if let Some(tail) = args.tail_lines {
+                            if tail == 0 {
+                                return Ok(msg!("file_editor.tail_lines_zero"));
+                            }
+                            (num_lines.saturating_sub(tail as usize) + 1, num_lines)
+                        } else if let Some(find) = &args.find {
+                            let regex = match Regex::new(find) {
+                                Ok(regex) => regex,
+                                Err(e) => {
+                                    return Ok(msg!(
+                                        "file_editor.invalid_find",
+                                        pattern = find,
+                                        error = e
+                                    ))
+                                }
+                            };
+                            let matches: Vec<usize> = lines
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, line)| regex.is_match(line))
+                                .map(|(i, _)| i + 1)
+                                .collect();
+                            let Some(&first) = matches.first() else {
+                                return Ok(msg!(
+                                    "file_editor.find_no_match",
+                                    pattern = find,
+                                    path = path.display()
+                                ));
+                            };
+                            header = if matches.len() == 1 {
+                                msg!("file_editor.find_match", pattern = find, line = first)
+                            } else {
+                                let shown = &matches[..matches.len().min(MAX_FIND_MATCHES_LISTED)];
+                                let mut listed = format!("{:?}", shown);
+                                if matches.len() > shown.len() {
+                                    listed.push_str(&msg!("file_editor.find_more"));
+                                }
+                                msg!(
+                                    "file_editor.find_matches",
+                                    pattern = find,
+                                    count = matches.len(),
+                                    line = first,
+                                    lines = listed
+                                )
+                            };
+                            header.push('\n');
+                            (
+                                first.saturating_sub(FIND_CONTEXT_LINES).max(1),
+                                first + FIND_CONTEXT_LINES,
+                            )
                         } else {
                             (1, num_lines)
                         };
 
-                        let end_line = std::cmp::min(end_line, num_lines);
+                        let requested_end = std::cmp::min(end_line, num_lines);
+                        let end_line = requested_end.min(start_line + MAX_VIEW_LINES - 1);
                         let snippet_lines = lines
                             .iter()
                             .skip(start_line - 1)
-                            .take(end_line - start_line + 1)
+                            .take((end_line + 1).saturating_sub(start_line))
                             .cloned()
                             .collect::<Vec<&str>>()
                             .join("\n");
 
-                        Ok(make_output(
-                            &snippet_lines,
-                            &path.to_string_lossy(),
-                            start_line,
-                        ))
+                        let mut output = format!(
+                            "{}{}",
+                            header,
+                            make_output(&snippet_lines, &path.to_string_lossy(), start_line)
+                        );
+                        if end_line < requested_end {
+                            output.push_str(&msg!(
+                                "file_editor.view_truncated",
+                                start = start_line,
+                                end = end_line,
+                                requested_end = requested_end,
+                                lines = num_lines
+                            ));
+                            output.push('\n');
+                        }
+                        Ok(output)
                     }
-                    Err(e) => Ok(format!(
-                        "Error: Failed to read file {}: {}",
-                        path.display(),
-                        e
+                    Err(e) => Ok(msg!(
+                        "file_editor.read_failed",
+                        path = path.display(),
+                        error = e
                     )),
                 }
             }
         }
         "create" => {
             if path.exists() {
-                return Ok(format!("Error: File already exists at: {}. Cannot overwrite files using command `create`. Use `str_replace` to edit the file instead.", path.display()));
+                return Ok(msg!("file_editor.create_exists", path = path.display()));
             }
             let content = match args.file_text.clone() {
                 Some(c) => c,
-                None => {
-                    return Ok("Error: Missing file_text parameter for create command.".to_string())
-                }
+                None => return Ok(msg!("file_editor.create_missing_text")),
             };
             // Create parent directories if they don't exist
             if let Some(parent) = path.parent() {
                 if let Err(e) = fs::create_dir_all(parent) {
-                    return Ok(format!(
-                        "Error: Failed to create parent directories for {}: {}",
-                        path.display(),
-                        e
+                    return Ok(msg!(
+                        "file_editor.create_parent_failed",
+                        path = path.display(),
+                        error = e
                     ));
                 }
             }
+            journal.record("file_editor", &path);
             if let Err(e) = fs::write(&path, &content) {
-                return Ok(format!(
-                    "Error: Failed to write to {}: {}",
-                    path.display(),
-                    e
+                return Ok(msg!(
+                    "file_editor.write_failed",
+                    path = path.display(),
+                    error = e
                 ));
             }
-            Ok(format!("File created successfully at: {}", path.display()))
+            Ok(msg!("file_editor.created", path = path.display()))
         }
         "str_replace" => {
             if !path.exists() {
-                return Ok(format!(
-                    "Error: The path {} does not exist. Please check the file path.",
-                    path.display()
-                ));
+                return Ok(msg!("file_editor.not_found", path = path.display()));
             }
             let old_str = match args.old_str.clone() {
                 Some(s) => s,
                 None => {
-                    return Ok(
-                        "Error: Missing old_str parameter for str_replace command.".to_string()
-                    )
+                    return Ok(msg!(
+                        "file_editor.missing_parameter",
+                        parameter = "old_str",
+                        command = "str_replace"
+                    ))
                 }
             };
             let new_str = match args.new_str.clone() {
                 Some(s) => s,
                 None => {
-                    return Ok(
-                        "Error: Missing new_str parameter for str_replace command.".to_string()
-                    )
+                    return Ok(msg!(
+                        "file_editor.missing_parameter",
+                        parameter = "new_str",
+                        command = "str_replace"
+                    ))
                 }
             };
 
             if old_str == new_str {
-                return Ok(
-                    "Error: No replacement was performed. `new_str` and `old_str` must be different. Please provide different values.".to_string()
-                );
+                return Ok(msg!("file_editor.replace_same"));
             }
 
             let content = fs::read_to_string(&path).map_err(|e| McpError {
@@ -204,18 +341,19 @@ pub async fn run_file_editor(
             let occurrences: Vec<_> = content.match_indices(&old_str).collect();
 
             if occurrences.is_empty() {
-                return Ok(format!(
-                    "Error: No replacement was performed, old_str `{}` did not appear verbatim in {}. Please check the file content and try again with the correct string.",
-                    old_str,
-                    path.display()
+                return Ok(msg!(
+                    "file_editor.replace_not_found",
+                    old_str = old_str,
+                    path = path.display()
                 ));
             }
             if occurrences.len() > 1 {
-                let line_numbers: Vec<usize> = occurrences
-                    .iter()
-                    .map(|(idx, _)| content[..*idx].chars().filter(|&c| c == '\n').count() + 1)
-                    .collect();
-                return Ok(format!("Error: No replacement was performed. Multiple occurrences of old_str `{}` in lines {:?}. Please provide more context to make the match unique.", old_str, line_numbers));
+                let line_numbers = line_numbers(&content, occurrences.iter().map(|(idx, _)| *idx));
+                return Ok(msg!(
+                    "file_editor.replace_ambiguous",
+                    old_str = old_str,
+                    lines = format!("{:?}", line_numbers)
+                ));
             }
 
             let (idx, matched_text) = occurrences[0];
@@ -228,14 +366,7 @@ pub async fn run_file_editor(
                 &content[idx + matched_text.len()..]
             );
 
-            // Save history
-            {
-                let mut history = editor_history.lock().await;
-                history
-                    .entry(path.clone())
-                    .or_default()
-                    .push(content.clone());
-            }
+            journal.record("file_editor", &path);
 
             fs::write(&path, &new_content).map_err(|e| McpError {
                 code: ErrorCode(-32603),
@@ -243,61 +374,51 @@ pub async fn run_file_editor(
                 data: None,
             })?;
 
-            // Create snippet
-            // Create snippet
-            let start_line = replacement_line.saturating_sub(SNIPPET_CONTEXT_WINDOW);
-            let end_line =
-                replacement_line + SNIPPET_CONTEXT_WINDOW + new_str.matches('\n').count();
-
-            let lines: Vec<&str> = new_content.lines().collect();
-            // Adjust for make_output
-            let snippet_display_start_line = start_line + 1; // if 0 -> 1
-
-            // Slicing logic on vector:
-            let s_idx = start_line; // 0-based index to start reading from
-            let output_snippet = lines
-                .iter()
-                .skip(s_idx)
-                .take(end_line - s_idx)
-                .cloned()
-                .collect::<Vec<&str>>()
-                .join("\n");
-
-            Ok(format!(
-                "The file {} has been edited. {}Review the changes and make sure they are as expected. Edit the file again if necessary.",
-                 path.display(),
-                 make_output(&output_snippet, &format!("a snippet of {}", path.display()), snippet_display_start_line)
+            Ok(edited_output(
+                &path,
+                &new_content,
+                replacement_line,
+                &new_str,
             ))
         }
         "insert" => {
             let insert_line = match args.insert_line {
                 Some(l) => l,
-                None => return Ok("Error: Missing insert_line parameter for insert command.".to_string()),
+                None => {
+                    return Ok(msg!(
+                        "file_editor.missing_parameter",
+                        parameter = "insert_line",
+                        command = "insert"
+                    ))
+                }
             };
             let text_to_insert = match args.new_str.clone().or(args.file_text.clone()) {
                 Some(t) => t,
-                None => return Ok("Error: Missing new_str (or file_text) for insert command.".to_string()),
+                None => return Ok(msg!("file_editor.insert_missing_text")),
             };
 
             let content = match fs::read_to_string(&path) {
                 Ok(c) => c,
-                Err(e) => return Ok(format!("Error: Failed to read file {}: {}", path.display(), e)),
+                Err(e) => {
+                    return Ok(msg!(
+                        "file_editor.read_failed",
+                        path = path.display(),
+                        error = e
+                    ))
+                }
             };
 
-            // Save history
-            {
-                let mut history = editor_history.lock().await;
-                history
-                    .entry(path.clone())
-                    .or_default()
-                    .push(content.clone());
-            }
+            journal.record("file_editor", &path);
 
             let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
             let idx = (insert_line as usize).saturating_sub(1);
 
             if idx > lines.len() {
-                return Ok(format!("Error: insert_line {} should be within the range of allowed values: [0, {}]", insert_line, lines.len()));
+                return Ok(msg!(
+                    "file_editor.insert_line_range",
+                    line = insert_line,
+                    lines = lines.len()
+                ));
             }
 
             let inserted_lines_count = text_to_insert.lines().count();
@@ -310,7 +431,11 @@ pub async fn run_file_editor(
 
             let new_content = lines.join("\n");
             if let Err(e) = fs::write(&path, &new_content) {
-                return Ok(format!("Error: Failed to write file {}: {}", path.display(), e));
+                return Ok(msg!(
+                    "file_editor.write_failed",
+                    path = path.display(),
+                    error = e
+                ));
             }
 
             // Snippet
@@ -326,29 +451,183 @@ pub async fn run_file_editor(
                 .collect::<Vec<&str>>()
                 .join("\n");
 
-            Ok(format!(
-                "The file {} has been edited. {}Review the changes and make sure they are as expected (correct indentation, no duplicate lines, etc). Edit the file again if necessary.",
-                path.display(),
-                make_output(&output_snippet, "a snippet of the edited file", start_line + 1)
+            let description = msg!("file_editor.snippet_of_edited");
+            Ok(msg!(
+                "file_editor.inserted",
+                path = path.display(),
+                snippet = make_output(&output_snippet, &description, start_line + 1)
             ))
         }
-        "undo_edit" => {
-            let mut history = editor_history.lock().await;
-            if let Some(versions) = history.get_mut(&path) {
-                if let Some(prev_content) = versions.pop() {
-                    if let Err(e) = fs::write(&path, &prev_content) {
-                        return Ok(format!("Error: Failed to restore file {}: {}", path.display(), e));
-                    }
-                    return Ok(format!(
-                        "Last edit to {} undone successfully. {}",
-                        path.display(),
-                        make_output(&prev_content, &path.to_string_lossy(), 1)
-                    ));
+        "replace_lines" => {
+            let (Some(start_line), Some(end_line)) = (args.start_line, args.end_line) else {
+                return Ok(msg!(
+                    "file_editor.missing_parameter",
+                    parameter = "start_line or end_line",
+                    command = "replace_lines"
+                ));
+            };
+            let Some(new_text) = args.new_str.clone() else {
+                return Ok(msg!("file_editor.replace_lines_missing_text"));
+            };
+            let content = match fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(e) => {
+                    return Ok(msg!(
+                        "file_editor.read_failed",
+                        path = path.display(),
+                        error = e
+                    ))
+                }
+            };
+
+            let lines: Vec<&str> = content.lines().collect();
+            let (start, end) = (start_line as usize, end_line as usize);
+            if start < 1 || start > lines.len() {
+                return Ok(msg!(
+                    "file_editor.start_line_range",
+                    start = start,
+                    lines = lines.len()
+                ));
+            }
+            if end < start || end > lines.len() {
+                return Ok(msg!(
+                    "file_editor.end_line_range",
+                    end = end,
+                    start = start,
+                    lines = lines.len()
+                ));
+            }
+
+            let mut new_lines: Vec<&str> = lines[..start - 1].to_vec();
+            new_lines.extend(new_text.lines());
+            new_lines.extend(&lines[end..]);
+            let mut new_content = new_lines.join("\n");
+            if content.ends_with('\n') && !new_lines.is_empty() {
+                new_content.push('\n');
+            }
+
+            journal.record("file_editor", &path);
+            if let Err(e) = fs::write(&path, &new_content) {
+                return Ok(msg!(
+                    "file_editor.write_failed",
+                    path = path.display(),
+                    error = e
+                ));
+            }
+
+            Ok(edited_output(&path, &new_content, start, &new_text))
+        }
+        "regex_replace" => {
+            let Some(pattern) = args.pattern.as_deref() else {
+                return Ok(msg!(
+                    "file_editor.missing_parameter",
+                    parameter = "pattern",
+                    command = "regex_replace"
+                ));
+            };
+            let Some(replacement) = args.new_str.as_deref() else {
+                return Ok(msg!(
+                    "file_editor.missing_parameter",
+                    parameter = "new_str",
+                    command = "regex_replace"
+                ));
+            };
+            let regex = match Regex::new(pattern) {
+                Ok(regex) => regex,
+                Err(e) => {
+                    return Ok(msg!(
+                        "file_editor.invalid_pattern",
+                        pattern = pattern,
+                        error = e
+                    ))
                 }
+            };
+            if args.count == Some(0) {
+                return Ok(msg!("file_editor.count_zero"));
+            }
+            let content = match fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(e) => {
+                    return Ok(msg!(
+                        "file_editor.read_failed",
+                        path = path.display(),
+                        error = e
+                    ))
+                }
+            };
+
+            let matches: Vec<_> = regex.find_iter(&content).collect();
+            if matches.is_empty() {
+                return Ok(msg!(
+                    "file_editor.pattern_not_found",
+                    pattern = pattern,
+                    path = path.display()
+                ));
+            }
+            // Empty matches would insert the replacement between arbitrary characters.
+            if matches.iter().any(|m| m.is_empty()) {
+                return Ok(msg!("file_editor.pattern_matches_empty", pattern = pattern));
+            }
+            let lines = line_numbers(&content, matches.iter().map(|m| m.start()));
+            if args.count.is_none() && matches.len() > 1 {
+                return Ok(msg!(
+                    "file_editor.pattern_ambiguous",
+                    pattern = pattern,
+                    lines = format!("{:?}", lines)
+                ));
+            }
+
+            let count = args.count.map_or(1, |n| n as usize).min(matches.len());
+            let new_content = regex.replacen(&content, count, replacement).into_owned();
+            if new_content == content {
+                return Ok(msg!("file_editor.replace_unchanged"));
+            }
+
+            journal.record("file_editor", &path);
+            if let Err(e) = fs::write(&path, &new_content) {
+                return Ok(msg!(
+                    "file_editor.write_failed",
+                    path = path.display(),
+                    error = e
+                ));
+            }
+
+            // Show the first replacement; the others are listed by line.
+            let first_text = regex.replace(matches[0].as_str(), replacement);
+            let output = edited_output(&path, &new_content, lines[0], &first_text);
+            if count > 1 {
+                Ok(msg!(
+                    "file_editor.replaced_matches",
+                    count = count,
+                    lines = format!("{:?}", &lines[..count]),
+                    output = output
+                ))
+            } else {
+                Ok(output)
             }
-            Ok(format!("Error: No edit history found for {}", path.display()))
         }
-        _ => Ok(format!("Error: Unrecognized command '{}'. Use view, create, str_replace, insert, or undo_edit.", args.command)),
+        "undo_edit" => {
+            // Undoes the file's last change by any file tool, not only by this editor.
+            let undone = match journal.undo_last(&path) {
+                Ok(undone) => undone,
+                Err(e) => return Ok(msg!("file_editor.error", error = e)),
+            };
+            if undone.created {
+                return Ok(msg!(
+                    "file_editor.undone_created",
+                    path = path.display(),
+                    tool = undone.tool
+                ));
+            }
+            let restored = fs::read_to_string(&path).unwrap_or_default();
+            Ok(msg!(
+                "file_editor.undone",
+                path = path.display(),
+                tool = undone.tool,
+                content = make_output(&restored, &path.to_string_lossy(), 1)
+            ))
+        }
+        _ => Ok(msg!("file_editor.unknown_command", command = args.command)),
     }
 }
 
@@ -360,7 +639,7 @@ mod tests {
     #[tokio::test]
     async fn test_file_editor_create_and_view() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
+        let history = FileMutationJournal::default();
 
         let args_create = FileEditorArgs {
             command: "create".to_string(),
@@ -370,6 +649,7 @@ mod tests {
             old_str: None,
             new_str: None,
             insert_line: None,
+            ..Default::default()
         };
 
         let result = run_file_editor(&args_create, dir.path(), &history)
@@ -385,6 +665,7 @@ mod tests {
             old_str: None,
             new_str: None,
             insert_line: None,
+            ..Default::default()
         };
 
         let content = run_file_editor(&args_view, dir.path(), &history)
@@ -394,10 +675,41 @@ mod tests {
         assert!(content.contains("cat -n"));
     }
 
+    #[tokio::test]
+    async fn test_undo_edit_reverts_write_file() {
+        use crate::tools::file_ops::{run_write_file, WriteFileArgs};
+        let dir = tempdir().unwrap();
+        let journal = FileMutationJournal::default();
+        let undo = FileEditorArgs {
+            command: "undo_edit".to_string(),
+            path: "notes.txt".to_string(),
+            ..Default::default()
+        };
+
+        for content in ["first", "second"] {
+            let write = WriteFileArgs {
+                path: "notes.txt".to_string(),
+                content: content.to_string(),
+            };
+            run_write_file(&write, dir.path(), &journal).unwrap();
+        }
+        let res = run_file_editor(&undo, dir.path(), &journal).await.unwrap();
+        assert!(res.contains("(by write_file) undone successfully"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("notes.txt")).unwrap(),
+            "first"
+        );
+
+        // The first write created the file, so undoing it removes the file.
+        let res = run_file_editor(&undo, dir.path(), &journal).await.unwrap();
+        assert!(res.contains("removed"));
+        assert!(!dir.path().join("notes.txt").exists());
+    }
+
     #[tokio::test]
     async fn test_file_editor_replace_and_undo() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
+        let history = FileMutationJournal::default();
         let file_path = dir.path().join("test.txt");
         fs::write(&file_path, "hello world").unwrap();
 
@@ -410,6 +722,7 @@ mod tests {
             file_text: None,
             view_range: None,
             insert_line: None,
+            ..Default::default()
         };
 
         let res = run_file_editor(&args_replace, dir.path(), &history)
@@ -432,6 +745,7 @@ mod tests {
             old_str: None,
             new_str: None,
             insert_line: None,
+            ..Default::default()
         };
 
         let undo_res = run_file_editor(&args_undo, dir.path(), &history)
@@ -444,12 +758,215 @@ mod tests {
         assert_eq!(content, "hello world");
     }
 
+    #[tokio::test]
+    async fn test_file_editor_replace_lines() {
+        let dir = tempdir().unwrap();
+        let history = FileMutationJournal::default();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "one\ntwo\nthree\nfour\n").unwrap();
+
+        let replace = |start, end, new_str: &str| FileEditorArgs {
+            command: "replace_lines".to_string(),
+            path: "test.txt".to_string(),
+            start_line: Some(start),
+            end_line: Some(end),
+            new_str: Some(new_str.to_string()),
+            ..Default::default()
+        };
+
+        let res = run_file_editor(&replace(2, 3, "TWO\n2.5\nTHREE"), dir.path(), &history)
+            .await
+            .unwrap();
+        assert!(res.contains("     3\t2.5"));
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "one\nTWO\n2.5\nTHREE\nfour\n"
+        );
+
+        // An empty new_str deletes the lines.
+        run_file_editor(&replace(2, 4, ""), dir.path(), &history)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "one\nfour\n");
+
+        for (start, end) in [(0, 1), (3, 3), (2, 1), (1, 3)] {
+            let res = run_file_editor(&replace(start, end, "x"), dir.path(), &history)
+                .await
+                .unwrap();
+            assert!(res.starts_with("Error:"), "{} {}: {}", start, end, res);
+        }
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "one\nfour\n");
+    }
+
+    #[tokio::test]
+    async fn test_file_editor_regex_replace() {
+        let dir = tempdir().unwrap();
+        let history = FileMutationJournal::default();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "let a = 1;\nlet b = 2;\nlet c = 3;\n").unwrap();
+
+        let regex_replace = |pattern: &str, new_str: &str, count| FileEditorArgs {
+            command: "regex_replace".to_string(),
+            path: "test.txt".to_string(),
+            pattern: Some(pattern.to_string()),
+            new_str: Some(new_str.to_string()),
+            count,
+            ..Default::default()
+        };
+
+        // Without count, the pattern must match once.
+        let res = run_file_editor(
+            &regex_replace(r"let (\w)", "const $1", None),
+            dir.path(),
+            &history,
+        )
+        .await
+        .unwrap();
+        assert!(res.contains("matches in lines [1, 2, 3]"));
+
+        let res = run_file_editor(
+            &regex_replace(r"b = (\d)", "b = ${1}0", None),
+            dir.path(),
+            &history,
+        )
+        .await
+        .unwrap();
+        assert!(res.contains("     2\tlet b = 20;"));
+
+        let res = run_file_editor(
+            &regex_replace(r"let (\w)", "const $1", Some(2)),
+            dir.path(),
+            &history,
+        )
+        .await
+        .unwrap();
+        assert!(res.contains("Replaced 2 matches, at lines [1, 2]"));
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "const a = 1;\nconst b = 20;\nlet c = 3;\n"
+        );
+
+        for args in [
+            regex_replace("(", "x", None),
+            regex_replace("x*", "y", Some(1)),
+            regex_replace("missing", "x", None),
+            regex_replace("c", "x", Some(0)),
+        ] {
+            let res = run_file_editor(&args, dir.path(), &history).await.unwrap();
+            assert!(res.starts_with("Error:"), "{}", res);
+        }
+
+        // Each replacement is one undo step.
+        let undo = FileEditorArgs {
+            command: "undo_edit".to_string(),
+            path: "test.txt".to_string(),
+            ..Default::default()
+        };
+        run_file_editor(&undo, dir.path(), &history).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "let a = 1;\nlet b = 20;\nlet c = 3;\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_editor_view_tail_find_and_truncation() {
+        let dir = tempdir().unwrap();
+        let history = FileMutationJournal::default();
+        let content: String = (1..=3000).map(|i| format!("line {}\n", i)).collect();
+        fs::write(dir.path().join("big.txt"), content).unwrap();
+        let view = |args: FileEditorArgs| FileEditorArgs {
+            command: "view".to_string(),
+            path: "big.txt".to_string(),
+            ..args
+        };
+
+        let res = run_file_editor(&view(FileEditorArgs::default()), dir.path(), &history)
+            .await
+            .unwrap();
+        assert!(res.contains("  2000\tline 2000\n"));
+        assert!(!res.contains("line 2001"));
+        assert!(res.contains("[Output truncated: showing lines 1-2000 of 3000"));
+
+        let tail = view(FileEditorArgs {
+            tail_lines: Some(2),
+            ..Default::default()
+        });
+        let res = run_file_editor(&tail, dir.path(), &history).await.unwrap();
+        assert!(res.contains("  2999\tline 2999\n  3000\tline 3000\n"));
+        assert!(!res.contains("line 2998"));
+        assert!(!res.contains("truncated"));
+
+        let find = view(FileEditorArgs {
+            find: Some(r"^line 15\d\d$".to_string()),
+            ..Default::default()
+        });
+        let res = run_file_editor(&find, dir.path(), &history).await.unwrap();
+        assert!(res.starts_with("Pattern `^line 15\\d\\d$` matches 100 lines, first at line 1500"));
+        assert!(res.contains("and more"));
+        assert!(res.contains("  1490\tline 1490"));
+        assert!(res.contains("  1510\tline 1510"));
+        assert!(!res.contains("line 1511"));
+
+        for args in [
+            FileEditorArgs {
+                find: Some("nowhere".to_string()),
+                ..Default::default()
+            },
+            FileEditorArgs {
+                tail_lines: Some(0),
+                ..Default::default()
+            },
+            FileEditorArgs {
+                tail_lines: Some(5),
+                view_range: Some(vec![1, 2]),
+                ..Default::default()
+            },
+        ] {
+            let res = run_file_editor(&view(args), dir.path(), &history)
+                .await
+                .unwrap();
+            assert!(res.starts_with("Error:"), "{}", res);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_file_editor_concurrent_edits_serialize() {
+        let dir = tempdir().unwrap();
+        let history = FileMutationJournal::default();
+        fs::write(dir.path().join("test.txt"), "end\n").unwrap();
+
+        let edits = (0..20).map(|i| {
+            let workspace = dir.path().to_path_buf();
+            let history = history.clone();
+            tokio::spawn(async move {
+                let args = FileEditorArgs {
+                    command: "insert".to_string(),
+                    path: "test.txt".to_string(),
+                    insert_line: Some(1),
+                    new_str: Some(format!("line {}", i)),
+                    ..Default::default()
+                };
+                run_file_editor(&args, &workspace, &history).await.unwrap()
+            })
+        });
+        for edit in edits.collect::<Vec<_>>() {
+            edit.await.unwrap();
+        }
+
+        let content = fs::read_to_string(dir.path().join("test.txt")).unwrap();
+        assert_eq!(content.lines().count(), 21);
+        for i in 0..20 {
+            assert!(content.contains(&format!("line {}\n", i)));
+        }
+    }
+
     // Error handling tests - verify errors return Ok with error message
 
     #[tokio::test]
     async fn test_view_file_not_found_returns_ok() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
+        let history = FileMutationJournal::default();
         let args = FileEditorArgs {
             command: "view".to_string(),
             path: "nonexistent.txt".to_string(),
@@ -458,6 +975,7 @@ mod tests {
             old_str: None,
             new_str: None,
             insert_line: None,
+            ..Default::default()
         };
         let result = run_file_editor(&args, dir.path(), &history).await.unwrap();
         assert!(result.contains("Error:"));
@@ -467,7 +985,7 @@ mod tests {
     #[tokio::test]
     async fn test_create_file_exists_returns_ok() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
+        let history = FileMutationJournal::default();
         fs::write(dir.path().join("test.txt"), "existing content").unwrap();
 
         let args = FileEditorArgs {
@@ -478,6 +996,7 @@ mod tests {
             old_str: None,
             new_str: None,
             insert_line: None,
+            ..Default::default()
         };
         let result = run_file_editor(&args, dir.path(), &history).await.unwrap();
         assert!(result.contains("Error:"));
@@ -487,7 +1006,7 @@ mod tests {
     #[tokio::test]
     async fn test_str_replace_not_found_returns_ok() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
+        let history = FileMutationJournal::default();
         fs::write(dir.path().join("test.txt"), "hello world").unwrap();
 
         let args = FileEditorArgs {
@@ -498,6 +1017,7 @@ mod tests {
             file_text: None,
             view_range: None,
             insert_line: None,
+            ..Default::default()
         };
         let result = run_file_editor(&args, dir.path(), &history).await.unwrap();
         assert!(result.contains("Error:"));
@@ -507,7 +1027,7 @@ mod tests {
     #[tokio::test]
     async fn test_str_replace_multiple_occurrences_returns_ok() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
+        let history = FileMutationJournal::default();
         fs::write(dir.path().join("test.txt"), "hello hello hello").unwrap();
 
         let args = FileEditorArgs {
@@ -518,6 +1038,7 @@ mod tests {
             file_text: None,
             view_range: None,
             insert_line: None,
+            ..Default::default()
         };
         let result = run_file_editor(&args, dir.path(), &history).await.unwrap();
         assert!(result.contains("Error:"));
@@ -527,7 +1048,7 @@ mod tests {
     #[tokio::test]
     async fn test_file_editor_unknown_command_returns_ok() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
+        let history = FileMutationJournal::default();
         let args = FileEditorArgs {
             command: "unknown".to_string(),
             path: "test.txt".to_string(),
@@ -536,6 +1057,7 @@ mod tests {
             old_str: None,
             new_str: None,
             insert_line: None,
+            ..Default::default()
         };
         let result = run_file_editor(&args, dir.path(), &history).await.unwrap();
         assert!(result.contains("Error: Unrecognized command"));
@@ -544,7 +1066,7 @@ mod tests {
     #[tokio::test]
     async fn test_file_editor_missing_parameters_returns_ok() {
         let dir = tempdir().unwrap();
-        let history = Mutex::new(HashMap::new());
+        let history = FileMutationJournal::default();
 
         // Missing file_text for create
         let args_create = FileEditorArgs {
@@ -555,6 +1077,7 @@ mod tests {
             old_str: None,
             new_str: None,
             insert_line: None,
+            ..Default::default()
         };
         let res_create = run_file_editor(&args_create, dir.path(), &history)
             .await
@@ -573,6 +1096,7 @@ mod tests {
             file_text: None,
             view_range: None,
             insert_line: None,
+            ..Default::default()
         };
         let res_replace = run_file_editor(&args_replace, dir.path(), &history)
             .await
@@ -588,6 +1112,7 @@ mod tests {
             file_text: None,
             view_range: None,
             old_str: None,
+            ..Default::default()
         };
         let res_insert = run_file_editor(&args_insert, dir.path(), &history)
             .await
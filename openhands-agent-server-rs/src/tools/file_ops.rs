@@ -1,7 +1,16 @@
+use crate::tools::messages::msg;
+use base64::prelude::*;
+use memmap2::Mmap;
+use openhands_sdk_rs::agent::tools::image_mime_type;
+use openhands_sdk_rs::journal::FileMutationJournal;
+use openhands_sdk_rs::paths::{encode_path, resolve, Walk, WalkEntry};
+use rmcp::model::{Content, ResourceContents};
 use rmcp::schemars;
 use rmcp::ErrorData as McpError;
 use serde::Deserialize;
-use std::fs;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
 use std::path::Path;
 
 #[derive(Deserialize, schemars::JsonSchema)]
@@ -17,10 +26,21 @@ pub struct WriteFileArgs {
     pub content: String,
 }
 
-#[derive(Deserialize, schemars::JsonSchema)]
+#[derive(Deserialize, schemars::JsonSchema, Default)]
 pub struct ListFilesArgs {
     pub path: String,
+    /// List subdirectories too, as deep as `depth` allows.
     pub recursive: Option<bool>,
+    /// Levels to list; 1 is the directory's own entries. Unlimited when recursive.
+    pub depth: Option<usize>,
+    /// Only list files matching one of these globs, e.g. `*.rs` or `src/**/*.ts`.
+    pub include: Option<Vec<String>>,
+    /// Skip files and directories matching one of these globs.
+    pub exclude: Option<Vec<String>>,
+    /// Stop after this many entries (default 1000).
+    pub max_entries: Option<usize>,
+    /// Draw the listing as a tree, like `tree -L <depth>`.
+    pub tree: Option<bool>,
 }
 
 #[derive(Deserialize, schemars::JsonSchema)]
@@ -28,7 +48,19 @@ pub struct DeleteFileArgs {
     pub path: String,
 }
 
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct StatFileArgs {
+    pub path: String,
+}
+
 const MAX_LINES_PER_READ: usize = 1000;
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+/// Largest file `stat_file` counts the lines and hashes the content of.
+const MAX_STAT_HASH_BYTES: u64 = 64 * 1024 * 1024;
+/// Largest image or binary file `read_file` returns.
+const MAX_MEDIA_BYTES: u64 = 5 * 1024 * 1024;
+/// Smallest file `read_file` memory-maps.
+const MMAP_MIN_BYTES: u64 = 16 * 1024 * 1024;
 
 fn make_numbered_output(content: &str, start_line: usize) -> String {
     let lines: Vec<&str> = content.lines().collect();
@@ -41,69 +73,201 @@ fn make_numbered_output(content: &str, start_line: usize) -> String {
 }
 
 pub fn run_read_file(args: &ReadFileArgs, workspace_dir: &Path) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
+    let path = resolve(workspace_dir, &args.path);
     if !path.exists() {
-        return Ok(format!(
-            "Error: File not found: {}. Please check the path and try again.",
-            path.display()
-        ));
+        return Ok(msg!("read_file.not_found", path = path.display()));
     }
     if path.is_dir() {
-        return Ok(format!(
-            "Error: Path is a directory, not a file: {}. Use list_files instead.",
-            path.display()
-        ));
+        return Ok(msg!("read_file.is_directory", path = path.display()));
     }
 
-    let content = match fs::read_to_string(&path) {
-        Ok(c) => c,
-        Err(e) => return Ok(format!("Error reading file {}: {}", path.display(), e)),
-    };
-
-    let lines: Vec<&str> = content.lines().collect();
-    let total_lines = lines.len();
     let offset = args.offset.unwrap_or(0);
+    let limit = args.limit.unwrap_or(MAX_LINES_PER_READ);
+    let read =
+        fs::metadata(&path).and_then(|m| Ok((m.len(), read_lines(&path, m.len(), offset, limit)?)));
+    let (bytes, window) = match read {
+        Ok(read) => read,
+        Err(e) => {
+            return Ok(msg!(
+                "read_file.read_failed",
+                path = path.display(),
+                error = e
+            ))
+        }
+    };
+    let total_lines = window.total_lines;
 
     if offset >= total_lines && total_lines > 0 {
-        return Ok(format!(
-            "Error: Offset {} is beyond file length ({} lines). Use a smaller offset.",
-            offset, total_lines
+        return Ok(msg!(
+            "read_file.offset_beyond_end",
+            offset = offset,
+            lines = total_lines
         ));
     }
 
-    let limit = args.limit.unwrap_or(MAX_LINES_PER_READ);
-    let end = std::cmp::min(offset + limit, total_lines);
-
-    let lines_to_show = &lines[offset..end];
-    let content_to_show = lines_to_show.join("\n");
+    let end = offset + window.lines.len();
+    let content_to_show = window.lines.join("\n");
     let numbered_content = make_numbered_output(&content_to_show, offset + 1);
 
     let is_truncated = end < total_lines;
-    let mut header = format!("Read file: {}", path.display());
+    let mut header = msg!("read_file.header", path = path.display(), bytes = bytes);
     if is_truncated {
-        header.push_str(&format!(
-            " (showing lines {}-{} of {})",
-            offset + 1,
-            end,
-            total_lines
+        header.push_str(&msg!(
+            "read_file.showing_lines",
+            start = offset + 1,
+            end = end,
+            lines = total_lines
         ));
-        header.push_str(&format!(
-            "\nTo read more, use: read_file(path='{}', offset={}, limit={})",
-            args.path, end, limit
+        header.push('\n');
+        header.push_str(&msg!(
+            "read_file.read_more",
+            path = args.path,
+            offset = end,
+            limit = limit
         ));
     }
 
     Ok(format!("{}\n\n{}", header, numbered_content))
 }
 
-pub fn run_write_file(args: &WriteFileArgs, workspace_dir: &Path) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
+/// Lines `offset..offset + limit` of a file, split like `str::lines`, and how many lines
+/// the whole file has.
+struct LineWindow {
+    lines: Vec<String>,
+    total_lines: usize,
+}
+
+/// Read the lines `offset..offset + limit` of the file at `path`, `size` bytes long, without
+/// holding the rest of it: the lines after the window are only counted. Files from
+/// [`MMAP_MIN_BYTES`] on are memory-mapped instead of copied through a buffer.
+fn read_lines(path: &Path, size: u64, offset: usize, limit: usize) -> io::Result<LineWindow> {
+    let file = File::open(path)?;
+    if size >= MMAP_MIN_BYTES {
+        // SAFETY: the map is only read here, while the file is open. Another process
+        // truncating the file meanwhile is the usual risk of memory-mapped reads.
+        let map = unsafe { Mmap::map(&file)? };
+        return mapped_lines(&map, offset, limit);
+    }
+
+    let mut reader = BufReader::new(file);
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    let mut total_lines = 0;
+    while total_lines < offset + limit {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(LineWindow { lines, total_lines });
+        }
+        if total_lines >= offset {
+            lines.push(decode_line(&line)?);
+        }
+        total_lines += 1;
+    }
+    // Count the rest; a last line without a newline counts too.
+    let mut open_line = false;
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            break;
+        }
+        total_lines += memchr::memchr_iter(b'\n', buffer).count();
+        open_line = buffer.last() != Some(&b'\n');
+        let consumed = buffer.len();
+        reader.consume(consumed);
+    }
+    if open_line {
+        total_lines += 1;
+    }
+    Ok(LineWindow { lines, total_lines })
+}
+
+/// [`read_lines`] of a file's mapped content.
+fn mapped_lines(data: &[u8], offset: usize, limit: usize) -> io::Result<LineWindow> {
+    let mut total_lines = memchr::memchr_iter(b'\n', data).count();
+    if data.last().is_some_and(|b| *b != b'\n') {
+        total_lines += 1;
+    }
+    let mut start = 0;
+    for _ in 0..offset {
+        match memchr::memchr(b'\n', &data[start..]) {
+            Some(i) => start += i + 1,
+            None => start = data.len(),
+        }
+    }
+    let mut lines = Vec::new();
+    while lines.len() < limit && start < data.len() {
+        let end = memchr::memchr(b'\n', &data[start..]).map_or(data.len(), |i| start + i);
+        lines.push(decode_line(&data[start..end])?);
+        start = end + 1;
+    }
+    Ok(LineWindow { lines, total_lines })
+}
+
+/// A line without its line ending.
+fn decode_line(line: &[u8]) -> io::Result<String> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    String::from_utf8(line.to_vec()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    })
+}
+
+/// `read_file` of an image or other binary file: its content as MCP media (an image, or an
+/// embedded blob resource) instead of text. `None` for text files and files that cannot be
+/// read, which `run_read_file` reports on.
+pub fn run_read_media(
+    args: &ReadFileArgs,
+    workspace_dir: &Path,
+) -> Result<Option<Content>, McpError> {
+    let path = resolve(workspace_dir, &args.path);
+    let Ok(metadata) = fs::metadata(&path) else {
+        return Ok(None);
+    };
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+    let image = image_mime_type(&path);
+    if metadata.len() > MAX_MEDIA_BYTES {
+        return Ok(image.map(|_| {
+            Content::text(msg!(
+                "read_file.image_too_large",
+                path = path.display(),
+                bytes = metadata.len(),
+                limit = MAX_MEDIA_BYTES
+            ))
+        }));
+    }
+    let Ok(bytes) = fs::read(&path) else {
+        return Ok(None);
+    };
+    if image.is_none() && std::str::from_utf8(&bytes).is_ok() {
+        return Ok(None);
+    }
+
+    let data = BASE64_STANDARD.encode(bytes);
+    Ok(Some(match image {
+        Some(mime_type) => Content::image(data, mime_type),
+        None => Content::resource(ResourceContents::BlobResourceContents {
+            uri: format!("file://{}", path.display()),
+            mime_type: Some("application/octet-stream".to_string()),
+            blob: data,
+            meta: None,
+        }),
+    }))
+}
+
+pub fn run_write_file(
+    args: &WriteFileArgs,
+    workspace_dir: &Path,
+    journal: &FileMutationJournal,
+) -> Result<String, McpError> {
+    let path = resolve(workspace_dir, &args.path);
 
     if path.exists() && path.is_dir() {
-        return Ok(format!(
-            "Error: Path is a directory, not a file: {}. Cannot write to a directory.",
-            path.display()
-        ));
+        return Ok(msg!("write_file.is_directory", path = path.display()));
     }
 
     let is_new_file = !path.exists();
@@ -111,124 +275,272 @@ pub fn run_write_file(args: &WriteFileArgs, workspace_dir: &Path) -> Result<Stri
     // Create parent dirs
     if let Some(parent) = path.parent() {
         if let Err(e) = fs::create_dir_all(parent) {
-            return Ok(format!(
-                "Error creating parent directory for {}: {}",
-                path.display(),
-                e
+            return Ok(msg!(
+                "write_file.create_parent_failed",
+                path = path.display(),
+                error = e
             ));
         }
     }
 
+    journal.record("write_file", &path);
     if let Err(e) = fs::write(&path, &args.content) {
-        return Ok(format!("Error writing file {}: {}", path.display(), e));
+        return Ok(msg!(
+            "write_file.write_failed",
+            path = path.display(),
+            error = e
+        ));
     }
 
-    let action_verb = if is_new_file { "Created" } else { "Updated" };
-    Ok(format!("{} file: {}", action_verb, path.display()))
+    if is_new_file {
+        Ok(msg!("write_file.created", path = path.display()))
+    } else {
+        Ok(msg!("write_file.updated", path = path.display()))
+    }
 }
 
 pub fn run_list_files(args: &ListFilesArgs, workspace_dir: &Path) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
+    let path = resolve(workspace_dir, &args.path);
     if !path.exists() {
-        return Ok(format!(
-            "Error: Directory not found: {}. Please check the path.",
-            path.display()
-        ));
+        return Ok(msg!("list_files.not_found", path = path.display()));
     }
     if !path.is_dir() {
-        return Ok(format!(
-            "Error: Path is not a directory: {}. Use read_file for files.",
-            path.display()
+        return Ok(msg!("list_files.not_a_directory", path = path.display()));
+    }
+    if let Err(e) = fs::read_dir(&path) {
+        return Ok(msg!(
+            "list_files.list_failed",
+            path = path.display(),
+            error = e
         ));
     }
 
-    let mut entries = Vec::new();
-    let recursive = args.recursive.unwrap_or(false);
-
-    if recursive {
-        // Simple recursive implementation (limit to 2 levels deep like Python)
-        for entry in walkdir::WalkDir::new(&path)
-            .max_depth(2)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            let rel_path = entry.path().strip_prefix(&path).unwrap_or(entry.path());
-            if rel_path.as_os_str().is_empty() {
-                continue;
-            }
+    let depth = match (args.depth, args.recursive.unwrap_or(false)) {
+        (Some(depth), _) => depth.max(1),
+        (None, true) => usize::MAX,
+        (None, false) => 1,
+    };
+    let include = args.include.clone().unwrap_or_default();
+    let walk = Walk::new()
+        .with_max_depth(depth)
+        .with_include(&include)
+        .and_then(|walk| walk.with_exclude(args.exclude.as_deref().unwrap_or_default()));
+    let walk = match walk {
+        Ok(walk) => walk,
+        Err(e) => return Ok(format!("Error: {}", e)),
+    };
+    let max_entries = args.max_entries.unwrap_or(DEFAULT_MAX_ENTRIES);
 
-            let name = rel_path.to_string_lossy().to_string();
-            let type_str = if entry.file_type().is_dir() {
-                "dir"
-            } else {
-                "file"
-            };
-            entries.push(format!("{} ({})", name, type_str));
-            if entries.len() >= 1000 {
-                break;
-            }
-        }
-    } else {
-        let read_dir = match fs::read_dir(&path) {
-            Ok(rd) => rd,
-            Err(e) => {
-                return Ok(format!(
-                    "Error: Failed to list directory {}: {}",
-                    path.display(),
-                    e
-                ))
-            }
-        };
-        for entry in read_dir.flatten() {
-            let name = entry.file_name().to_string_lossy().to_string();
-            let type_str = if entry.path().is_dir() { "dir" } else { "file" };
-            entries.push(format!("{} ({})", name, type_str));
-            if entries.len() >= 1000 {
-                break;
-            }
+    let mut entries = Vec::new();
+    let mut truncated = false;
+    walk.run(&path, |entry| {
+        if entries.len() >= max_entries {
+            truncated = true;
+            return false;
         }
+        entries.push(entry);
+        true
+    });
+    if !include.is_empty() {
+        entries = without_empty_dirs(entries);
     }
 
-    entries.sort();
     let total_count = entries.len();
-    let truncated = total_count >= 1000;
+    let header = if truncated {
+        msg!(
+            "list_files.header_truncated",
+            path = encode_path(&path),
+            count = total_count,
+            limit = max_entries
+        )
+    } else {
+        msg!(
+            "list_files.header",
+            path = encode_path(&path),
+            count = total_count
+        )
+    };
+
+    let listing = if args.tree.unwrap_or(false) {
+        format_tree(&entries)
+    } else {
+        let mut lines: Vec<String> = entries
+            .iter()
+            .map(|entry| {
+                let type_str = if entry.is_dir { "dir" } else { "file" };
+                format!("{} ({})", encode_path(&entry.relative), type_str)
+            })
+            .collect();
+        lines.sort();
+        lines.join("\n")
+    };
+    Ok(format!("{}\n{}", header, listing))
+}
 
-    let mut header = format!(
-        "Listed directory: {} ({} entries",
-        path.display(),
-        total_count
-    );
-    if truncated {
-        header.push_str(", truncated to 1000");
+/// `entries` without the directories that have no entries listed below them.
+fn without_empty_dirs(entries: Vec<WalkEntry>) -> Vec<WalkEntry> {
+    let mut kept: Vec<WalkEntry> = Vec::with_capacity(entries.len());
+    for entry in entries.into_iter().rev() {
+        if !entry.is_dir || kept.last().is_some_and(|next| next.depth > entry.depth) {
+            kept.push(entry);
+        }
     }
-    header.push_str(")");
+    kept.reverse();
+    kept
+}
 
-    Ok(format!("{}\n{}", header, entries.join("\n")))
+/// `entries`, in walk order, drawn with `tree`'s box characters.
+fn format_tree(entries: &[WalkEntry]) -> String {
+    // Whether each entry is the last of its siblings, found walking backwards.
+    let mut is_last = vec![false; entries.len()];
+    let mut later_sibling: Vec<bool> = Vec::new();
+    for (i, entry) in entries.iter().enumerate().rev() {
+        later_sibling.resize(entry.depth + 1, false);
+        is_last[i] = !later_sibling[entry.depth];
+        later_sibling[entry.depth] = true;
+    }
+
+    let mut lines = vec![".".to_string()];
+    let mut ancestors_last: Vec<bool> = Vec::new();
+    for (entry, last) in entries.iter().zip(is_last) {
+        ancestors_last.truncate(entry.depth - 1);
+        let mut line: String = ancestors_last
+            .iter()
+            .map(|&last| if last { "    " } else { "│   " })
+            .collect();
+        line.push_str(if last { "└── " } else { "├── " });
+        let name = entry.relative.file_name().unwrap_or_default();
+        line.push_str(&encode_path(Path::new(name)));
+        if entry.is_dir {
+            line.push('/');
+        }
+        lines.push(line);
+        ancestors_last.push(last);
+    }
+    lines.join("\n")
 }
 
-pub fn run_delete_file(args: &DeleteFileArgs, workspace_dir: &Path) -> Result<String, McpError> {
-    let path = workspace_dir.join(&args.path);
+/// Deleted files can be restored with the editor's `undo_edit`; deleted directories cannot.
+pub fn run_delete_file(
+    args: &DeleteFileArgs,
+    workspace_dir: &Path,
+    journal: &FileMutationJournal,
+) -> Result<String, McpError> {
+    let path = resolve(workspace_dir, &args.path);
     if !path.exists() {
-        return Ok(format!(
-            "Error: File not found: {}. Cannot delete a file that doesn't exist.",
-            path.display()
-        ));
+        return Ok(msg!("delete_file.not_found", path = path.display()));
     }
 
     if path.is_dir() {
         if let Err(e) = fs::remove_dir_all(&path) {
-            return Ok(format!(
-                "Error deleting directory {}: {}",
-                path.display(),
-                e
+            return Ok(msg!(
+                "delete_file.delete_directory_failed",
+                path = path.display(),
+                error = e
             ));
         }
-        Ok(format!("Deleted directory: {}", path.display()))
+        Ok(msg!("delete_file.deleted_directory", path = path.display()))
     } else {
+        journal.record("delete_file", &path);
         if let Err(e) = fs::remove_file(&path) {
-            return Ok(format!("Error deleting file {}: {}", path.display(), e));
+            return Ok(msg!(
+                "delete_file.delete_failed",
+                path = path.display(),
+                error = e
+            ));
         }
-        Ok(format!("Deleted file: {}", path.display()))
+        Ok(msg!("delete_file.deleted", path = path.display()))
+    }
+}
+
+pub fn run_stat_file(args: &StatFileArgs, workspace_dir: &Path) -> Result<String, McpError> {
+    let path = resolve(workspace_dir, &args.path);
+    let link = match fs::symlink_metadata(&path) {
+        Ok(link) => link,
+        Err(_) => return Ok(msg!("stat_file.not_found", path = path.display())),
+    };
+    // Symlinks are described by their target, if there is one.
+    let metadata = fs::metadata(&path).unwrap_or_else(|_| link.clone());
+
+    let file_type = if metadata.is_dir() {
+        "directory"
+    } else if metadata.is_file() {
+        "file"
+    } else if metadata.file_type().is_symlink() {
+        "broken symlink"
+    } else {
+        "other"
+    };
+    let mut lines = vec![
+        format!("Stat: {}", encode_path(&path)),
+        format!("type: {}", file_type),
+    ];
+    if link.file_type().is_symlink() {
+        let target = fs::read_link(&path).unwrap_or_default();
+        lines.push(format!("symlink to: {}", encode_path(&target)));
+    }
+    lines.push(format!("size: {} bytes", metadata.len()));
+    if let Ok(modified) = metadata.modified() {
+        let modified: chrono::DateTime<chrono::Utc> = modified.into();
+        lines.push(format!(
+            "modified: {}",
+            modified.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        ));
+    }
+    lines.push(format!("mode: {}", format_mode(&metadata)));
+
+    if metadata.is_file() {
+        if metadata.len() > MAX_STAT_HASH_BYTES {
+            lines.push(format!(
+                "lines, sha256: not computed for files over {} bytes",
+                MAX_STAT_HASH_BYTES
+            ));
+        } else {
+            match fs::read(&path) {
+                Ok(bytes) => {
+                    match std::str::from_utf8(&bytes) {
+                        Ok(text) => lines.push(format!("lines: {}", text.lines().count())),
+                        Err(_) => lines.push("lines: n/a (binary)".to_string()),
+                    }
+                    let hash: String = Sha256::digest(&bytes)
+                        .iter()
+                        .map(|b| format!("{:02x}", b))
+                        .collect();
+                    lines.push(format!("sha256: {}", hash));
+                }
+                Err(e) => lines.push(format!("content: unreadable ({})", e)),
+            }
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Permission bits in octal and `ls` form, e.g. `644 (rw-r--r--)`.
+#[cfg(unix)]
+fn format_mode(metadata: &fs::Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode() & 0o777;
+    let symbolic: String = (0..9)
+        .map(|i| {
+            let bit = 0o400 >> i;
+            match (mode & bit != 0, i % 3) {
+                (false, _) => '-',
+                (true, 0) => 'r',
+                (true, 1) => 'w',
+                (true, _) => 'x',
+            }
+        })
+        .collect();
+    format!("{:o} ({})", mode, symbolic)
+}
+
+#[cfg(not(unix))]
+fn format_mode(metadata: &fs::Metadata) -> String {
+    if metadata.permissions().readonly() {
+        "read-only".to_string()
+    } else {
+        "read-write".to_string()
     }
 }
 
@@ -257,6 +569,63 @@ mod tests {
         assert!(!result.contains("line1"));
     }
 
+    #[test]
+    fn test_read_lines_streams_a_window() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("log.txt");
+        let content = "one\r\ntwo\n\nfour\nfive";
+        fs::write(&path, content).unwrap();
+        let size = content.len() as u64;
+
+        let window = read_lines(&path, size, 1, 2).unwrap();
+        assert_eq!(window.lines, vec!["two", ""]);
+        assert_eq!(window.total_lines, 5);
+        // The mapped read of large files splits the same way.
+        let mapped = mapped_lines(content.as_bytes(), 1, 2).unwrap();
+        assert_eq!(mapped.lines, window.lines);
+        assert_eq!(mapped.total_lines, window.total_lines);
+
+        let tail = read_lines(&path, size, 3, 10).unwrap();
+        assert_eq!(tail.lines, vec!["four", "five"]);
+        assert_eq!(
+            mapped_lines(content.as_bytes(), 3, 10).unwrap().lines,
+            tail.lines
+        );
+        assert_eq!(read_lines(&path, size, 9, 1).unwrap().total_lines, 5);
+
+        let args = ReadFileArgs {
+            path: "log.txt".to_string(),
+            offset: None,
+            limit: Some(1),
+        };
+        let result = run_read_file(&args, dir.path()).unwrap();
+        assert!(result.contains(&format!("({} bytes) (showing lines 1-1 of 5)", size)));
+        assert!(result.ends_with("     1\tone"));
+    }
+
+    #[test]
+    fn test_read_media() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("notes.txt"), "text").unwrap();
+        fs::write(dir.path().join("shot.png"), [0x89, b'P', b'N', b'G']).unwrap();
+        fs::write(dir.path().join("data.bin"), [0xff, 0xfe, 0x00]).unwrap();
+        let read = |path: &str| {
+            let args = ReadFileArgs {
+                path: path.to_string(),
+                offset: None,
+                limit: None,
+            };
+            run_read_media(&args, dir.path()).unwrap()
+        };
+
+        assert!(read("notes.txt").is_none());
+        assert!(read("missing.png").is_none());
+        let image = read("shot.png").unwrap();
+        assert_eq!(image.as_image().unwrap().mime_type, "image/png");
+        let blob = read("data.bin").unwrap();
+        assert!(blob.as_resource().is_some());
+    }
+
     #[test]
     fn test_write_file_new_and_update() {
         let dir = tempdir().unwrap();
@@ -265,14 +634,16 @@ mod tests {
             path: "new.txt".to_string(),
             content: "hello".to_string(),
         };
-        let res1 = run_write_file(&args_create, dir.path()).unwrap();
+        let res1 =
+            run_write_file(&args_create, dir.path(), &FileMutationJournal::default()).unwrap();
         assert!(res1.contains("Created file"));
 
         let args_update = WriteFileArgs {
             path: "new.txt".to_string(),
             content: "world".to_string(),
         };
-        let res2 = run_write_file(&args_update, dir.path()).unwrap();
+        let res2 =
+            run_write_file(&args_update, dir.path(), &FileMutationJournal::default()).unwrap();
         assert!(res2.contains("Updated file"));
     }
 
@@ -285,12 +656,160 @@ mod tests {
         let args = ListFilesArgs {
             path: ".".to_string(),
             recursive: Some(false),
+            ..Default::default()
         };
         let result = run_list_files(&args, dir.path()).unwrap();
         assert!(result.contains("f1.txt (file)"));
         assert!(result.contains("d1 (dir)"));
     }
 
+    #[test]
+    fn test_list_files_options() {
+        let dir = tempdir().unwrap();
+        for sub in ["a/b/c", "node_modules/pkg", "src"] {
+            fs::create_dir_all(dir.path().join(sub)).unwrap();
+        }
+        for file in [
+            "a/b/c/deep.rs",
+            "a/one.rs",
+            "node_modules/pkg/index.js",
+            "src/main.rs",
+            "src/notes.md",
+            "z.txt",
+        ] {
+            fs::write(dir.path().join(file), "").unwrap();
+        }
+        fs::write(dir.path().join(".gitignore"), "node_modules/\n").unwrap();
+        let list = |args: ListFilesArgs| run_list_files(&args, dir.path()).unwrap();
+
+        let recursive = list(ListFilesArgs {
+            path: ".".to_string(),
+            recursive: Some(true),
+            ..Default::default()
+        });
+        assert!(recursive.contains("a/b/c/deep.rs (file)"));
+        assert!(!recursive.contains("node_modules"));
+
+        let shallow = list(ListFilesArgs {
+            path: ".".to_string(),
+            depth: Some(2),
+            ..Default::default()
+        });
+        assert!(shallow.contains("a/b (dir)"));
+        assert!(!shallow.contains("a/b/c"));
+
+        let tree = list(ListFilesArgs {
+            path: ".".to_string(),
+            recursive: Some(true),
+            include: Some(vec!["*.rs".to_string()]),
+            exclude: Some(vec!["a/b".to_string()]),
+            tree: Some(true),
+            ..Default::default()
+        });
+        assert!(tree.contains("(4 entries)"));
+        assert!(tree.ends_with(".\n├── a/\n│   └── one.rs\n└── src/\n    └── main.rs"));
+
+        let capped = list(ListFilesArgs {
+            path: ".".to_string(),
+            recursive: Some(true),
+            max_entries: Some(3),
+            ..Default::default()
+        });
+        assert!(capped.contains("(3 entries, truncated to 3)"));
+
+        let invalid = list(ListFilesArgs {
+            path: ".".to_string(),
+            include: Some(vec!["[".to_string()]),
+            ..Default::default()
+        });
+        assert!(invalid.starts_with("Error: Invalid glob '['"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_file_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempdir().unwrap();
+        let sub_dir = dir.path().join(OsStr::from_bytes(b"d\xe9j\xe0"));
+        fs::create_dir(&sub_dir).unwrap();
+        fs::write(
+            sub_dir.join(OsStr::from_bytes(b"r\xe9sum\xe9.txt")),
+            "hello",
+        )
+        .unwrap();
+        fs::write(dir.path().join("plain.txt"), "").unwrap();
+
+        let list = |recursive| {
+            let args = ListFilesArgs {
+                path: ".".to_string(),
+                recursive: Some(recursive),
+                ..Default::default()
+            };
+            run_list_files(&args, dir.path()).unwrap()
+        };
+        let flat = list(false);
+        assert!(flat.contains("d%E9j%E0 (dir)"));
+        assert!(flat.contains("plain.txt (file)"));
+        let recursive = list(true);
+        assert!(recursive.contains("d%E9j%E0/r%E9sum%E9.txt (file)"));
+        assert!(!recursive.contains('\u{FFFD}'));
+
+        // Paths come back in the form they were listed in.
+        let args = ReadFileArgs {
+            path: "d%E9j%E0/r%E9sum%E9.txt".to_string(),
+            offset: None,
+            limit: None,
+        };
+        assert!(run_read_file(&args, dir.path())
+            .unwrap()
+            .contains("1\thello"));
+        let args = DeleteFileArgs {
+            path: "d%E9j%E0".to_string(),
+        };
+        run_delete_file(&args, dir.path(), &FileMutationJournal::default()).unwrap();
+        assert!(!sub_dir.exists());
+    }
+
+    #[test]
+    fn test_long_path_names() {
+        let dir = tempdir().unwrap();
+        // The longest name most filesystems allow, in directories nested past 2000 bytes.
+        let name = format!("{}.txt", "n".repeat(251));
+        let deep = (0..16)
+            .map(|i| format!("{:0>120}", i))
+            .collect::<Vec<_>>()
+            .join("/");
+        let path = format!("{}/{}", deep, name);
+
+        let args = WriteFileArgs {
+            path: path.clone(),
+            content: "deep".to_string(),
+        };
+        assert!(
+            run_write_file(&args, dir.path(), &FileMutationJournal::default())
+                .unwrap()
+                .contains("Created file")
+        );
+        let args = ReadFileArgs {
+            path,
+            offset: None,
+            limit: None,
+        };
+        assert!(run_read_file(&args, dir.path())
+            .unwrap()
+            .contains("1\tdeep"));
+        let args = ListFilesArgs {
+            path: deep,
+            recursive: None,
+            ..Default::default()
+        };
+        assert!(run_list_files(&args, dir.path())
+            .unwrap()
+            .contains(&format!("{} (file)", name)));
+    }
+
     #[test]
     fn test_delete_file_and_dir() {
         let dir = tempdir().unwrap();
@@ -302,13 +821,13 @@ mod tests {
         let args_f = DeleteFileArgs {
             path: "f.txt".to_string(),
         };
-        run_delete_file(&args_f, dir.path()).unwrap();
+        run_delete_file(&args_f, dir.path(), &FileMutationJournal::default()).unwrap();
         assert!(!file_path.exists());
 
         let args_d = DeleteFileArgs {
             path: "d".to_string(),
         };
-        run_delete_file(&args_d, dir.path()).unwrap();
+        run_delete_file(&args_d, dir.path(), &FileMutationJournal::default()).unwrap();
         assert!(!sub_dir.exists());
     }
 
@@ -359,6 +878,7 @@ mod tests {
         let args = ListFilesArgs {
             path: "nonexistent".to_string(),
             recursive: None,
+            ..Default::default()
         };
         let result = run_list_files(&args, dir.path()).unwrap();
         assert!(result.contains("Error: Directory not found"));
@@ -370,7 +890,45 @@ mod tests {
         let args = DeleteFileArgs {
             path: "nonexistent.txt".to_string(),
         };
-        let result = run_delete_file(&args, dir.path()).unwrap();
+        let result = run_delete_file(&args, dir.path(), &FileMutationJournal::default()).unwrap();
         assert!(result.contains("Error: File not found"));
     }
+
+    #[test]
+    fn test_stat_file() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("notes.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(dir.path().join("blob.bin"), [0xff, 0x00, 0xfe]).unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let stat = |path: &str| {
+            let args = StatFileArgs {
+                path: path.to_string(),
+            };
+            run_stat_file(&args, dir.path()).unwrap()
+        };
+
+        let text = stat("notes.txt");
+        assert!(text.contains("type: file"));
+        assert!(text.contains("size: 14 bytes"));
+        assert!(text.contains("lines: 3"));
+        assert!(text
+            .contains("sha256: b6285c57e8797db5d4c51c80d6f11938afda9b11c6a003549709189e9b4b92a2"));
+        assert!(text.contains("modified: "));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(
+                dir.path().join("notes.txt"),
+                fs::Permissions::from_mode(0o640),
+            )
+            .unwrap();
+            assert!(stat("notes.txt").contains("mode: 640 (rw-r-----)"));
+        }
+
+        assert!(stat("blob.bin").contains("lines: n/a (binary)"));
+        let sub = stat("sub");
+        assert!(sub.contains("type: directory"));
+        assert!(!sub.contains("sha256"));
+        assert!(stat("missing").starts_with("Error: File not found"));
+    }
 }
@@ -1,4 +1,5 @@
-use glob::glob;
+use crate::tools::messages::msg;
+use openhands_sdk_rs::paths::{encode_path, glob_paths};
 use rmcp::schemars;
 use rmcp::ErrorData as McpError;
 use serde::Deserialize;
@@ -18,66 +19,50 @@ pub fn run_glob(args: &GlobArgs, workspace_dir: &Path) -> Result<String, McpErro
     };
 
     if !base_path.is_dir() {
-        return Ok(format!(
-            "Path '{}' is not a valid directory",
-            base_path.display()
-        ));
+        return Ok(msg!("glob.not_a_directory", path = base_path.display()));
     }
 
-    let pattern_str = if Path::new(&args.pattern).is_absolute() {
-        args.pattern.clone()
-    } else {
-        base_path.join(&args.pattern).to_string_lossy().to_string()
-    };
-
     let mut matches = Vec::new();
-    // glob returns Result<Paths, PatternError>
-    let paths = match glob(&pattern_str) {
+    let paths = match glob_paths(&base_path, &args.pattern) {
         Ok(p) => p,
         Err(e) => {
-            return Ok(format!(
-                "Error: Invalid glob pattern '{}': {}",
-                args.pattern, e
+            return Ok(msg!(
+                "glob.invalid_pattern",
+                pattern = args.pattern,
+                error = e
             ))
         }
     };
 
-    for entry in paths {
-        match entry {
-            Ok(path) => {
-                matches.push(path.to_string_lossy().to_string());
-                if matches.len() >= 100 {
-                    break;
-                }
-            }
-            Err(e) => {
-                return Ok(format!("Error while iterating glob matches: {}", e));
-            }
+    for path in paths {
+        matches.push(encode_path(&path).into_owned());
+        if matches.len() >= 100 {
+            break;
         }
     }
 
     let truncated = matches.len() >= 100;
     let count = matches.len();
     let matches_str = matches.join("\n");
-    let mut output = format!(
-        "Found {} file(s) matching pattern '{}' in '{}':\n{}",
-        count,
-        args.pattern,
-        base_path.display(),
-        matches_str
+    let mut output = msg!(
+        "glob.found",
+        count = count,
+        pattern = args.pattern,
+        path = base_path.display()
     );
+    output.push('\n');
+    output.push_str(&matches_str);
 
     if truncated {
-        output.push_str(
-            "\n\n[Results truncated to first 100 files. Consider using a more specific pattern.]",
-        );
+        output.push_str("\n\n");
+        output.push_str(&msg!("glob.truncated", limit = 100));
     }
 
     if count == 0 {
-        output = format!(
-            "No files found matching pattern '{}' in directory '{}'",
-            args.pattern,
-            base_path.display()
+        output = msg!(
+            "glob.none",
+            pattern = args.pattern,
+            path = base_path.display()
         );
     }
 
@@ -118,6 +103,25 @@ mod tests {
         assert!(result.contains("No files found"));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_glob_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join(OsStr::from_bytes(b"caf\xe9.txt"))).unwrap();
+        File::create(dir.path().join("tea.txt")).unwrap();
+
+        let args = GlobArgs {
+            pattern: "*.txt".to_string(),
+            path: Some(dir.path().to_string_lossy().to_string()),
+        };
+        let result = run_glob(&args, dir.path()).unwrap();
+        assert!(result.contains("Found 2 file(s)"));
+        assert!(result.contains("caf%E9.txt"));
+    }
+
     #[test]
     fn test_glob_recursive() {
         let dir = tempdir().unwrap();
@@ -1,3 +1,5 @@
+use crate::tools::messages::msg;
+use openhands_sdk_rs::paths::encode_path;
 use regex::Regex;
 use rmcp::schemars;
 use rmcp::ErrorData as McpError;
@@ -20,18 +22,16 @@ pub fn run_grep(args: &GrepArgs, workspace_dir: &Path) -> Result<String, McpErro
     };
 
     if !base_path.is_dir() {
-        return Ok(format!(
-            "Path '{}' is not a valid directory",
-            base_path.display()
-        ));
+        return Ok(msg!("grep.not_a_directory", path = base_path.display()));
     }
 
     let re = match Regex::new(&args.pattern) {
         Ok(r) => r,
         Err(e) => {
-            return Ok(format!(
-                "Error: Invalid regex pattern '{}': {}",
-                args.pattern, e
+            return Ok(msg!(
+                "grep.invalid_pattern",
+                pattern = args.pattern,
+                error = e
             ))
         }
     };
@@ -40,12 +40,7 @@ pub fn run_grep(args: &GrepArgs, workspace_dir: &Path) -> Result<String, McpErro
     let include_glob = if let Some(p) = include_pattern {
         match glob::Pattern::new(p) {
             Ok(pat) => Some(pat),
-            Err(e) => {
-                return Ok(format!(
-                    "Error: Invalid include glob pattern '{}': {}",
-                    p, e
-                ))
-            }
+            Err(e) => return Ok(msg!("grep.invalid_include", include = p, error = e)),
         }
     } else {
         None
@@ -71,7 +66,7 @@ pub fn run_grep(args: &GrepArgs, workspace_dir: &Path) -> Result<String, McpErro
         let path = entry.path();
         if let Ok(content) = std::fs::read_to_string(path) {
             if re.is_match(&content) {
-                matches.push(path.to_string_lossy().to_string());
+                matches.push(encode_path(path).into_owned());
             }
         }
     }
@@ -79,32 +74,31 @@ pub fn run_grep(args: &GrepArgs, workspace_dir: &Path) -> Result<String, McpErro
     let truncated = matches.len() >= 100;
     let count = matches.len();
     let matches_str = matches.join("\n");
-    let mut output = format!(
-        "Found {} file(s) containing pattern '{}' in '{}'",
-        count,
-        args.pattern,
-        base_path.display()
+    let mut output = msg!(
+        "grep.found",
+        count = count,
+        pattern = args.pattern,
+        path = base_path.display()
     );
     if let Some(inc) = include_pattern {
-        output.push_str(&format!(" (filtered by '{}')", inc));
+        output.push_str(&msg!("grep.filtered", include = inc));
     }
     output.push_str(":\n");
     output.push_str(&matches_str);
 
     if truncated {
-        output.push_str(
-            "\n\n[Results truncated to first 100 files. Consider using a more specific pattern.]",
-        );
+        output.push_str("\n\n");
+        output.push_str(&msg!("grep.truncated", limit = 100));
     }
 
     if count == 0 {
-        output = format!(
-            "No files found containing pattern '{}' in directory '{}'",
-            args.pattern,
-            base_path.display()
+        output = msg!(
+            "grep.none",
+            pattern = args.pattern,
+            path = base_path.display()
         );
         if let Some(inc) = include_pattern {
-            output.push_str(&format!(" (filtered by '{}')", inc));
+            output.push_str(&msg!("grep.filtered", include = inc));
         }
     }
 
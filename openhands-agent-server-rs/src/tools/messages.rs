@@ -0,0 +1,255 @@
+//! The messages the MCP file tools reply with, by key.
+//!
+//! The replies of `file_editor`, the file operations, `grep`, `glob` and `task_tracker` are
+//! templates with `{name}` placeholders, looked up in the process-wide [`MessageCatalog`].
+//! The `[messages]` table of `openhands.toml` overrides templates by key, e.g. for shorter
+//! phrasing or another language; keys it leaves out keep their default.
+
+use regex::{Captures, Regex};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::sync::{Arc, RwLock};
+
+/// Fill in the template `$key` of the process-wide catalog with `name = value` arguments.
+macro_rules! msg {
+    ($key:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::tools::messages::catalog().render(
+            $key,
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),*],
+        )
+    };
+}
+pub(crate) use msg;
+
+/// The default templates.
+const DEFAULTS: &[(&str, &str)] = &[
+    // file_editor
+    ("file_editor.error", "Error: {error}"),
+    ("file_editor.unknown_command", "Error: Unrecognized command '{command}'. Use view, create, str_replace, insert, replace_lines, regex_replace, or undo_edit."),
+    ("file_editor.missing_parameter", "Error: Missing {parameter} parameter for {command} command."),
+    ("file_editor.read_failed", "Error: Failed to read file {path}: {error}"),
+    ("file_editor.write_failed", "Error: Failed to write file {path}: {error}"),
+    ("file_editor.not_found", "Error: The path {path} does not exist. Please check the file path."),
+    ("file_editor.cat_header", "Here's the result of running `cat -n` on {description}:"),
+    ("file_editor.snippet_of", "a snippet of {path}"),
+    ("file_editor.snippet_of_edited", "a snippet of the edited file"),
+    ("file_editor.edited", "The file {path} has been edited. {snippet}Review the changes and make sure they are as expected. Edit the file again if necessary."),
+    ("file_editor.view_not_found", "Error: The path {path} does not exist. Please provide a valid path."),
+    ("file_editor.directory_header", "Here's the files and directories in {path}, excluding hidden items:"),
+    ("file_editor.list_failed", "Error: Failed to list directory {path}: {error}"),
+    ("file_editor.view_modes", "Error: Pass at most one of view_range, tail_lines and find."),
+    ("file_editor.view_range_length", "Error: view_range should be a list of two integers."),
+    ("file_editor.view_range_start", "Error: Its first element `{start}` should be within the range of lines of the file: [1, {lines}]."),
+    ("file_editor.view_range_end", "Error: Its second element `{end}` should be greater than or equal to the first element `{start}`."),
+    ("file_editor.tail_lines_zero", "Error: tail_lines should be at least 1."),
+    ("file_editor.invalid_find", "Error: Invalid find pattern `{pattern}`: {error}"),
+    ("file_editor.find_no_match", "Error: Pattern `{pattern}` does not match any line of {path}."),
+    ("file_editor.find_match", "Pattern `{pattern}` matches line {line}."),
+    ("file_editor.find_matches", "Pattern `{pattern}` matches {count} lines, first at line {line}: {lines}."),
+    ("file_editor.find_more", " and more"),
+    ("file_editor.view_truncated", "[Output truncated: showing lines {start}-{end} of {requested_end} ({lines} lines in the file). Use view_range, tail_lines or find to see other parts.]"),
+    ("file_editor.create_exists", "Error: File already exists at: {path}. Cannot overwrite files using command `create`. Use `str_replace` to edit the file instead."),
+    ("file_editor.create_missing_text", "Error: Missing file_text parameter for create command."),
+    ("file_editor.create_parent_failed", "Error: Failed to create parent directories for {path}: {error}"),
+    ("file_editor.created", "File created successfully at: {path}"),
+    ("file_editor.replace_same", "Error: No replacement was performed. `new_str` and `old_str` must be different. Please provide different values."),
+    ("file_editor.replace_not_found", "Error: No replacement was performed, old_str `{old_str}` did not appear verbatim in {path}. Please check the file content and try again with the correct string."),
+    ("file_editor.replace_ambiguous", "Error: No replacement was performed. Multiple occurrences of old_str `{old_str}` in lines {lines}. Please provide more context to make the match unique."),
+    ("file_editor.insert_missing_text", "Error: Missing new_str (or file_text) for insert command."),
+    ("file_editor.insert_line_range", "Error: insert_line {line} should be within the range of allowed values: [0, {lines}]"),
+    ("file_editor.inserted", "The file {path} has been edited. {snippet}Review the changes and make sure they are as expected (correct indentation, no duplicate lines, etc). Edit the file again if necessary."),
+    ("file_editor.replace_lines_missing_text", "Error: Missing new_str parameter for replace_lines command. Pass an empty string to delete the lines."),
+    ("file_editor.start_line_range", "Error: start_line `{start}` should be within the range of lines of the file: [1, {lines}]."),
+    ("file_editor.end_line_range", "Error: end_line `{end}` should be within [{start}, {lines}], the lines from start_line to the end of the file."),
+    ("file_editor.invalid_pattern", "Error: Invalid pattern `{pattern}`: {error}"),
+    ("file_editor.count_zero", "Error: count should be at least 1."),
+    ("file_editor.pattern_not_found", "Error: No replacement was performed, pattern `{pattern}` did not match {path}."),
+    ("file_editor.pattern_matches_empty", "Error: No replacement was performed, pattern `{pattern}` matches the empty string. Make it match at least one character."),
+    ("file_editor.pattern_ambiguous", "Error: No replacement was performed. Pattern `{pattern}` matches in lines {lines}. Make it match once, or pass count to replace that many matches from the top."),
+    ("file_editor.replace_unchanged", "Error: No replacement was performed. The replacement leaves the file unchanged."),
+    ("file_editor.replaced_matches", "Replaced {count} matches, at lines {lines} of the original file. {output}"),
+    ("file_editor.undone", "Last edit to {path} (by {tool}) undone successfully. {content}"),
+    ("file_editor.undone_created", "Last edit to {path} (by {tool}) undone successfully; the edit created the file, so it was removed."),
+    // read_file, write_file, list_files, delete_file and stat_file
+    ("read_file.not_found", "Error: File not found: {path}. Please check the path and try again."),
+    ("read_file.is_directory", "Error: Path is a directory, not a file: {path}. Use list_files instead."),
+    ("read_file.read_failed", "Error reading file {path}: {error}"),
+    ("read_file.offset_beyond_end", "Error: Offset {offset} is beyond file length ({lines} lines). Use a smaller offset."),
+    ("read_file.header", "Read file: {path} ({bytes} bytes)"),
+    ("read_file.showing_lines", " (showing lines {start}-{end} of {lines})"),
+    ("read_file.read_more", "To read more, use: read_file(path='{path}', offset={offset}, limit={limit})"),
+    ("read_file.image_too_large", "Error: Image {path} is too large to read ({bytes} bytes, limit {limit})."),
+    ("write_file.is_directory", "Error: Path is a directory, not a file: {path}. Cannot write to a directory."),
+    ("write_file.create_parent_failed", "Error creating parent directory for {path}: {error}"),
+    ("write_file.write_failed", "Error writing file {path}: {error}"),
+    ("write_file.created", "Created file: {path}"),
+    ("write_file.updated", "Updated file: {path}"),
+    ("list_files.not_found", "Error: Directory not found: {path}. Please check the path."),
+    ("list_files.not_a_directory", "Error: Path is not a directory: {path}. Use read_file for files."),
+    ("list_files.list_failed", "Error: Failed to list directory {path}: {error}"),
+    ("list_files.header", "Listed directory: {path} ({count} entries)"),
+    ("list_files.header_truncated", "Listed directory: {path} ({count} entries, truncated to {limit})"),
+    ("delete_file.not_found", "Error: File not found: {path}. Cannot delete a file that doesn't exist."),
+    ("delete_file.delete_directory_failed", "Error deleting directory {path}: {error}"),
+    ("delete_file.deleted_directory", "Deleted directory: {path}"),
+    ("delete_file.delete_failed", "Error deleting file {path}: {error}"),
+    ("delete_file.deleted", "Deleted file: {path}"),
+    ("stat_file.not_found", "Error: File not found: {path}. Please check the path."),
+    // glob
+    ("glob.not_a_directory", "Path '{path}' is not a valid directory"),
+    ("glob.invalid_pattern", "Error: Invalid glob pattern '{pattern}': {error}"),
+    ("glob.found", "Found {count} file(s) matching pattern '{pattern}' in '{path}':"),
+    ("glob.truncated", "[Results truncated to first {limit} files. Consider using a more specific pattern.]"),
+    ("glob.none", "No files found matching pattern '{pattern}' in directory '{path}'"),
+    // grep
+    ("grep.not_a_directory", "Path '{path}' is not a valid directory"),
+    ("grep.invalid_pattern", "Error: Invalid regex pattern '{pattern}': {error}"),
+    ("grep.invalid_include", "Error: Invalid include glob pattern '{include}': {error}"),
+    ("grep.found", "Found {count} file(s) containing pattern '{pattern}' in '{path}'"),
+    ("grep.filtered", " (filtered by '{include}')"),
+    ("grep.truncated", "[Results truncated to first {limit} files. Consider using a more specific pattern.]"),
+    ("grep.none", "No files found containing pattern '{pattern}' in directory '{path}'"),
+    // task_tracker
+    ("task_tracker.read_failed", "Error: Failed to read tasks.json: {error}"),
+    ("task_tracker.serialize_failed", "Error: Failed to serialize tasks: {error}"),
+    ("task_tracker.write_failed", "Error: Failed to write tasks.json: {error}"),
+    ("task_tracker.unknown_command", "Error: Unknown command '{command}'. Use 'view' or 'plan'."),
+    ("task_tracker.empty", "No tasks in the list."),
+];
+
+lazy_static::lazy_static! {
+    static ref PLACEHOLDER: Regex = Regex::new(r"\{([a-z_]+)\}").unwrap();
+    static ref CATALOG: RwLock<Arc<MessageCatalog>> = RwLock::new(Arc::new(MessageCatalog::default()));
+}
+
+/// Message templates by key.
+pub struct MessageCatalog {
+    templates: HashMap<&'static str, String>,
+}
+
+impl Default for MessageCatalog {
+    fn default() -> Self {
+        Self {
+            templates: DEFAULTS
+                .iter()
+                .map(|(key, template)| (*key, template.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl MessageCatalog {
+    /// The defaults with `overrides` replacing templates by key. An override may leave out
+    /// placeholders of the default but not add others, and must name a known key.
+    pub fn with_overrides(overrides: &BTreeMap<String, String>) -> Result<Self, String> {
+        let mut catalog = Self::default();
+        for (key, template) in overrides {
+            let Some((key, default)) = DEFAULTS.iter().find(|(k, _)| k == key) else {
+                return Err(format!("[messages]: unknown message '{}'", key));
+            };
+            let known = placeholders(default);
+            if let Some(unknown) = placeholders(template)
+                .into_iter()
+                .find(|p| !known.contains(p))
+            {
+                let known: Vec<String> = known.iter().map(|k| format!("{{{}}}", k)).collect();
+                return Err(format!(
+                    "[messages]: '{}' has no {{{}}}; it can use {}",
+                    key,
+                    unknown,
+                    known.join(", ")
+                ));
+            }
+            catalog.templates.insert(key, template.clone());
+        }
+        Ok(catalog)
+    }
+
+    /// The template `key` with its placeholders replaced by `args`.
+    pub fn render(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        let Some(template) = self.templates.get(key) else {
+            return key.to_string();
+        };
+        PLACEHOLDER
+            .replace_all(template, |caps: &Captures| {
+                match args.iter().find(|(name, _)| *name == &caps[1]) {
+                    Some((_, value)) => value.to_string(),
+                    None => caps[0].to_string(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+fn placeholders(template: &str) -> Vec<&str> {
+    PLACEHOLDER
+        .captures_iter(template)
+        .map(|caps| caps.get(1).unwrap().as_str())
+        .collect()
+}
+
+/// The process-wide catalog.
+pub fn catalog() -> Arc<MessageCatalog> {
+    CATALOG.read().unwrap().clone()
+}
+
+pub fn set_catalog(catalog: MessageCatalog) {
+    *CATALOG.write().unwrap() = Arc::new(catalog);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_overrides() {
+        let overrides = BTreeMap::from([(
+            "task_tracker.unknown_command".to_string(),
+            "Unbekannter Befehl: {command}".to_string(),
+        )]);
+        let catalog = MessageCatalog::with_overrides(&overrides).unwrap();
+        assert_eq!(
+            catalog.render("task_tracker.unknown_command", &[("command", &"run")]),
+            "Unbekannter Befehl: run"
+        );
+        // Keys that are not overridden keep the default.
+        assert_eq!(
+            catalog.render("task_tracker.empty", &[]),
+            "No tasks in the list."
+        );
+
+        let unknown_key = BTreeMap::from([("nope".to_string(), "x".to_string())]);
+        assert!(MessageCatalog::with_overrides(&unknown_key).is_err());
+        let unknown_placeholder = BTreeMap::from([(
+            "task_tracker.empty".to_string(),
+            "{count} tasks".to_string(),
+        )]);
+        let error = MessageCatalog::with_overrides(&unknown_placeholder)
+            .err()
+            .unwrap();
+        assert!(error.contains("{count}"));
+    }
+
+    #[test]
+    fn test_default_templates() {
+        let catalog = MessageCatalog::default();
+        assert_eq!(
+            catalog.templates.len(),
+            DEFAULTS.len(),
+            "duplicate message keys"
+        );
+        assert_eq!(
+            msg!("task_tracker.unknown_command", command = "run"),
+            "Error: Unknown command 'run'. Use 'view' or 'plan'."
+        );
+        // A placeholder the tool does not fill in is left as it is.
+        assert_eq!(
+            catalog.render("glob.truncated", &[]),
+            DEFAULTS
+                .iter()
+                .find(|(k, _)| *k == "glob.truncated")
+                .unwrap()
+                .1
+        );
+    }
+}
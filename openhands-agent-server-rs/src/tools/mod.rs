@@ -1,5 +1,12 @@
+pub mod blocking;
+pub mod database;
+pub mod descriptions;
+pub mod environment_info;
 pub mod file_editor;
 pub mod file_ops;
 pub mod glob;
 pub mod grep;
+pub mod messages;
+pub mod query_data;
+pub mod recent_commands;
 pub mod task_tracker;
@@ -0,0 +1,620 @@
+//! `query_data`: answer questions about structured files without reading them whole.
+//!
+//! JSON, YAML and TOML take jq-style paths: `.name`, `.["odd key"]`, `.[0]`, `.[-1]`,
+//! `.[2:5]`, `.[]` over arrays and objects, and stages joined with `|`, including `keys`
+//! and `length`. CSV takes `SELECT cols|*|COUNT(*) [WHERE col op value [AND ...]]
+//! [ORDER BY col [ASC|DESC]] [LIMIT n]`, with `=`, `!=`, `<`, `<=`, `>`, `>=` and
+//! `CONTAINS`; values compare as numbers when both sides are numbers.
+
+use openhands_sdk_rs::paths::resolve;
+use rmcp::schemars;
+use rmcp::ErrorData as McpError;
+use serde::Deserialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::path::Path;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct QueryDataArgs {
+    pub path: String,
+    /// A jq-style path such as `.dependencies | keys`, or for CSV a query such as
+    /// `SELECT name, age WHERE age > 30 ORDER BY age DESC LIMIT 5`.
+    pub query: String,
+    /// `json`, `yaml`, `toml` or `csv`; guessed from the file extension if not given.
+    pub format: Option<String>,
+    /// Most results (values or rows) to return (default 100).
+    pub limit: Option<usize>,
+}
+
+const DEFAULT_LIMIT: usize = 100;
+/// Results beyond this many characters are cut off.
+const MAX_OUTPUT_CHARS: usize = 20_000;
+
+pub fn run_query_data(args: &QueryDataArgs, workspace_dir: &Path) -> Result<String, McpError> {
+    let path = resolve(workspace_dir, &args.path);
+    let format = match args.format.as_deref().map(str::to_ascii_lowercase) {
+        Some(format) => format,
+        None => match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => "json".to_string(),
+            Some("yaml" | "yml") => "yaml".to_string(),
+            Some("toml") => "toml".to_string(),
+            Some("csv") => "csv".to_string(),
+            _ => {
+                return Ok(format!(
+                    "Error: Cannot tell the format of {} from its extension. Pass format (json, yaml, toml or csv).",
+                    path.display()
+                ))
+            }
+        },
+    };
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) => return Ok(format!("Error reading file {}: {}", path.display(), e)),
+    };
+    let limit = args.limit.unwrap_or(DEFAULT_LIMIT);
+
+    let result = match format.as_str() {
+        "json" => serde_json::from_str(&content).map_err(|e| e.to_string()),
+        "yaml" | "yml" => serde_yaml::from_str(&content).map_err(|e| e.to_string()),
+        "toml" => toml::from_str::<toml::Value>(&content)
+            .map_err(|e| e.to_string())
+            .and_then(|value| serde_json::to_value(value).map_err(|e| e.to_string())),
+        "csv" => {
+            return Ok(query_csv(&content, &args.query, limit)
+                .map(|output| truncate(output, MAX_OUTPUT_CHARS))
+                .unwrap_or_else(|e| format!("Error: {}", e)))
+        }
+        other => {
+            return Ok(format!(
+                "Error: Unsupported format '{}'. Use json, yaml, toml or csv.",
+                other
+            ))
+        }
+    };
+    let document: Value = match result {
+        Ok(document) => document,
+        Err(e) => {
+            return Ok(format!(
+                "Error parsing {} as {}: {}",
+                path.display(),
+                format,
+                e
+            ))
+        }
+    };
+
+    let values = match query_value(document, &args.query) {
+        Ok(values) => values,
+        Err(e) => return Ok(format!("Error: {}", e)),
+    };
+    let total = values.len();
+    let mut lines: Vec<String> = values
+        .iter()
+        .take(limit)
+        .map(|value| value.to_string())
+        .collect();
+    if total > limit {
+        lines.push(format!("(showing {} of {} results)", limit, total));
+    }
+    Ok(truncate(lines.join("\n"), MAX_OUTPUT_CHARS))
+}
+
+fn truncate(mut output: String, max_chars: usize) -> String {
+    if let Some((index, _)) = output.char_indices().nth(max_chars) {
+        output.truncate(index);
+        output.push_str("\n(output truncated)");
+    }
+    output
+}
+
+#[derive(Debug, PartialEq)]
+enum Step {
+    Key(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>),
+    Iterate,
+}
+
+#[derive(Debug, PartialEq)]
+enum Stage {
+    Path(Vec<Step>),
+    Keys,
+    Length,
+}
+
+/// Split `query` on `|` outside of brackets and quotes.
+fn split_stages(query: &str) -> Vec<&str> {
+    let mut stages = Vec::new();
+    let (mut depth, mut quoted, mut start) = (0, false, 0);
+    for (i, c) in query.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '[' if !quoted => depth += 1,
+            ']' if !quoted => depth -= 1,
+            '|' if !quoted && depth == 0 => {
+                stages.push(&query[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    stages.push(&query[start..]);
+    stages
+}
+
+fn parse_query(query: &str) -> Result<Vec<Stage>, String> {
+    split_stages(query)
+        .into_iter()
+        .map(|stage| match stage.trim() {
+            "keys" => Ok(Stage::Keys),
+            "length" => Ok(Stage::Length),
+            path => parse_path(path).map(Stage::Path),
+        })
+        .collect()
+}
+
+fn parse_path(path: &str) -> Result<Vec<Step>, String> {
+    let invalid = |reason: &str| format!("Invalid path '{}': {}", path, reason);
+    if !path.starts_with('.') {
+        return Err(invalid("paths start with '.'"));
+    }
+    let chars: Vec<char> = path.chars().collect();
+    let mut steps = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                i += 1;
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || "_-$".contains(chars[i])) {
+                    i += 1;
+                }
+                if i > start {
+                    steps.push(Step::Key(chars[start..i].iter().collect()));
+                }
+            }
+            '[' => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|&c| c == ']')
+                    .map(|p| i + p)
+                    .ok_or_else(|| invalid("unclosed '['"))?;
+                let inner: String = chars[i + 1..end].iter().collect();
+                let inner = inner.trim();
+                let parse_int = |s: &str| {
+                    s.trim()
+                        .parse::<i64>()
+                        .map_err(|_| invalid(&format!("'{}' is not an index", s)))
+                };
+                steps.push(if inner.is_empty() {
+                    Step::Iterate
+                } else if inner.len() >= 2 && inner.starts_with('"') && inner.ends_with('"') {
+                    Step::Key(inner[1..inner.len() - 1].to_string())
+                } else if let Some((from, to)) = inner.split_once(':') {
+                    let bound = |s: &str| {
+                        if s.trim().is_empty() {
+                            Ok(None)
+                        } else {
+                            parse_int(s).map(Some)
+                        }
+                    };
+                    Step::Slice(bound(from)?, bound(to)?)
+                } else {
+                    Step::Index(parse_int(inner)?)
+                });
+                i = end + 1;
+            }
+            c => return Err(invalid(&format!("unexpected '{}'", c))),
+        }
+    }
+    Ok(steps)
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// `index` into a collection of `len` items, counting from the end if negative.
+fn clamp_index(index: i64, len: usize) -> usize {
+    if index < 0 {
+        len.saturating_sub(index.unsigned_abs() as usize)
+    } else {
+        (index as usize).min(len)
+    }
+}
+
+fn apply_step(value: Value, step: &Step, out: &mut Vec<Value>) -> Result<(), String> {
+    match (step, value) {
+        (Step::Key(key), Value::Object(mut map)) => {
+            out.push(map.remove(key).unwrap_or(Value::Null))
+        }
+        (Step::Key(_), Value::Null) | (Step::Index(_), Value::Null) => out.push(Value::Null),
+        (Step::Index(index), Value::Array(mut items)) => {
+            let position = if *index < 0 {
+                items.len().checked_sub(index.unsigned_abs() as usize)
+            } else {
+                Some(*index as usize)
+            };
+            out.push(match position.filter(|&p| p < items.len()) {
+                Some(p) => items.swap_remove(p),
+                None => Value::Null,
+            });
+        }
+        (Step::Slice(from, to), Value::Array(items)) => {
+            let len = items.len();
+            let from = from.map_or(0, |f| clamp_index(f, len));
+            let to = to.map_or(len, |t| clamp_index(t, len)).max(from);
+            out.push(Value::Array(
+                items.into_iter().skip(from).take(to - from).collect(),
+            ));
+        }
+        (Step::Iterate, Value::Array(items)) => out.extend(items),
+        (Step::Iterate, Value::Object(map)) => out.extend(map.into_iter().map(|(_, v)| v)),
+        (step, value) => {
+            return Err(format!(
+                "Cannot apply {:?} to {} value",
+                step,
+                type_name(&value)
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// The values `query` produces from `document`.
+fn query_value(document: Value, query: &str) -> Result<Vec<Value>, String> {
+    let mut values = vec![document];
+    for stage in parse_query(query)? {
+        let mut next = Vec::new();
+        for value in values {
+            match &stage {
+                Stage::Path(steps) => {
+                    let mut current = vec![value];
+                    for step in steps {
+                        let mut stepped = Vec::new();
+                        for value in current {
+                            apply_step(value, step, &mut stepped)?;
+                        }
+                        current = stepped;
+                    }
+                    next.extend(current);
+                }
+                Stage::Keys => match value {
+                    Value::Object(map) => {
+                        next.push(Value::from(map.keys().cloned().collect::<Vec<_>>()))
+                    }
+                    Value::Array(items) => {
+                        next.push(Value::from((0..items.len()).collect::<Vec<_>>()))
+                    }
+                    other => return Err(format!("{} has no keys", type_name(&other))),
+                },
+                Stage::Length => next.push(Value::from(match &value {
+                    Value::Object(map) => map.len(),
+                    Value::Array(items) => items.len(),
+                    Value::String(s) => s.chars().count(),
+                    Value::Null => 0,
+                    other => return Err(format!("{} has no length", type_name(other))),
+                })),
+            }
+        }
+        values = next;
+    }
+    Ok(values)
+}
+
+/// Words, quoted strings, operators and punctuation of a CSV query.
+fn tokenize(query: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|&e| e == c)
+                .map(|p| i + 1 + p)
+                .ok_or_else(|| "Unclosed quote in query".to_string())?;
+            // Keep the opening quote so values can be told from keywords.
+            tokens.push(chars[i..end].iter().collect());
+            i = end + 1;
+        } else if "<>!=".contains(c) {
+            let two = chars.get(i + 1) == Some(&'=');
+            let len = if two { 2 } else { 1 };
+            tokens.push(chars[i..i + len].iter().collect());
+            i += len;
+        } else if ",()*".contains(c) {
+            tokens.push(c.to_string());
+            i += 1;
+        } else {
+            let start = i;
+            while i < chars.len() && !chars[i].is_whitespace() && !"<>!=,()*'\"".contains(chars[i])
+            {
+                i += 1;
+            }
+            tokens.push(chars[start..i].iter().collect());
+        }
+    }
+    Ok(tokens)
+}
+
+/// A token's text, without the marker of a quoted one.
+fn unquote(token: &str) -> &str {
+    token.trim_start_matches(['\'', '"'])
+}
+
+fn is_keyword(token: &str, keyword: &str) -> bool {
+    token.eq_ignore_ascii_case(keyword)
+}
+
+struct Condition {
+    column: usize,
+    op: String,
+    value: String,
+}
+
+impl Condition {
+    fn matches(&self, row: &csv::StringRecord) -> bool {
+        let cell = row.get(self.column).unwrap_or_default();
+        if self.op.eq_ignore_ascii_case("contains") {
+            return cell.contains(&self.value);
+        }
+        let ordering = compare_cells(cell, &self.value);
+        match self.op.as_str() {
+            "=" => ordering == Ordering::Equal,
+            "!=" => ordering != Ordering::Equal,
+            "<" => ordering == Ordering::Less,
+            "<=" => ordering != Ordering::Greater,
+            ">" => ordering == Ordering::Greater,
+            _ => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// Numbers compare as numbers, anything else as text.
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+fn query_csv(content: &str, query: &str, default_limit: usize) -> Result<String, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .or_else(|| headers.iter().position(|h| h.eq_ignore_ascii_case(name)))
+            .ok_or_else(|| {
+                format!(
+                    "No column '{}'. Columns: {}",
+                    name,
+                    headers.iter().collect::<Vec<_>>().join(", ")
+                )
+            })
+    };
+
+    let tokens = tokenize(query)?;
+    let mut tokens = tokens.iter().map(String::as_str).peekable();
+    if !tokens.next().is_some_and(|t| is_keyword(t, "select")) {
+        return Err("CSV queries start with SELECT".to_string());
+    }
+    let mut count = false;
+    let mut selected = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            "*" => selected.extend(0..headers.len()),
+            "," => {}
+            t if is_keyword(t, "count") => {
+                let star: Vec<_> = tokens.by_ref().take(3).collect();
+                if star != ["(", "*", ")"] {
+                    return Err("Only COUNT(*) is supported".to_string());
+                }
+                count = true;
+            }
+            t => selected.push(column(unquote(t))?),
+        }
+        if tokens
+            .peek()
+            .is_some_and(|t| ["where", "order", "limit"].iter().any(|k| is_keyword(t, k)))
+        {
+            break;
+        }
+    }
+    if selected.is_empty() && !count {
+        return Err("SELECT needs columns, * or COUNT(*)".to_string());
+    }
+
+    let mut conditions = Vec::new();
+    let mut order: Option<(usize, bool)> = None;
+    let mut limit = default_limit;
+    while let Some(token) = tokens.next() {
+        if is_keyword(token, "where") || is_keyword(token, "and") {
+            let (Some(name), Some(op), Some(value)) = (tokens.next(), tokens.next(), tokens.next())
+            else {
+                return Err("WHERE needs <column> <operator> <value>".to_string());
+            };
+            if !["=", "!=", "<", "<=", ">", ">="].contains(&op) && !is_keyword(op, "contains") {
+                return Err(format!("Unknown operator '{}'", op));
+            }
+            conditions.push(Condition {
+                column: column(unquote(name))?,
+                op: op.to_string(),
+                value: unquote(value).to_string(),
+            });
+        } else if is_keyword(token, "order") {
+            if !tokens.next().is_some_and(|t| is_keyword(t, "by")) {
+                return Err("Expected BY after ORDER".to_string());
+            }
+            let name = tokens.next().ok_or("ORDER BY needs a column")?;
+            let descending = match tokens.peek() {
+                Some(t) if is_keyword(t, "desc") || is_keyword(t, "asc") => {
+                    is_keyword(tokens.next().unwrap_or_default(), "desc")
+                }
+                _ => false,
+            };
+            order = Some((column(unquote(name))?, descending));
+        } else if is_keyword(token, "limit") {
+            limit = tokens
+                .next()
+                .and_then(|n| n.parse().ok())
+                .ok_or("LIMIT needs a number")?;
+        } else {
+            return Err(format!("Unexpected '{}' in query", unquote(token)));
+        }
+    }
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        if conditions.iter().all(|c| c.matches(&record)) {
+            rows.push(record);
+        }
+    }
+    if count {
+        return Ok(rows.len().to_string());
+    }
+    if let Some((column, descending)) = order {
+        rows.sort_by(|a, b| {
+            let ordering = compare_cells(
+                a.get(column).unwrap_or_default(),
+                b.get(column).unwrap_or_default(),
+            );
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    let total = rows.len();
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let header: Vec<&str> = selected.iter().map(|&i| &headers[i]).collect();
+    writer.write_record(&header).map_err(|e| e.to_string())?;
+    for row in rows.iter().take(limit) {
+        let cells: Vec<&str> = selected
+            .iter()
+            .map(|&i| row.get(i).unwrap_or_default())
+            .collect();
+        writer.write_record(&cells).map_err(|e| e.to_string())?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    let mut output = String::from_utf8_lossy(&bytes).trim_end().to_string();
+    if total > limit {
+        output.push_str(&format!("\n(showing {} of {} rows)", limit, total));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn query(dir: &Path, path: &str, query: &str) -> String {
+        let args = QueryDataArgs {
+            path: path.to_string(),
+            query: query.to_string(),
+            format: None,
+            limit: None,
+        };
+        run_query_data(&args, dir).unwrap()
+    }
+
+    #[test]
+    fn test_query_documents() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("package.json"),
+            r#"{"name": "app", "dependencies": {"react": "18", "vite": "5"},
+                "files": [{"path": "a.js", "size": 1}, {"path": "b.js", "size": 2}],
+                "odd key": true}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("compose.yml"),
+            "services:\n  web:\n    image: nginx\n    ports: [\"80:80\"]\n  db:\n    image: postgres\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\n\n[dependencies]\nserde = \"1\"\n",
+        )
+        .unwrap();
+
+        let json = |q| query(dir.path(), "package.json", q);
+        assert_eq!(json(".name"), r#""app""#);
+        assert_eq!(json(".dependencies | keys"), r#"["react","vite"]"#);
+        assert_eq!(json(".files[].path"), "\"a.js\"\n\"b.js\"");
+        assert_eq!(json(".files[-1].size"), "2");
+        assert_eq!(json(".files[0:1] | length"), "1");
+        assert_eq!(json(r#".["odd key"]"#), "true");
+        assert_eq!(json(".missing"), "null");
+        assert!(json(".name[0]").starts_with("Error: Cannot apply"));
+        assert!(json("name").starts_with("Error: Invalid path"));
+
+        assert_eq!(
+            query(dir.path(), "compose.yml", ".services[].image"),
+            // Keys come out sorted: db, web.
+            "\"postgres\"\n\"nginx\""
+        );
+        assert_eq!(
+            query(dir.path(), "Cargo.toml", ".dependencies.serde"),
+            r#""1""#
+        );
+
+        let args = QueryDataArgs {
+            path: "package.json".to_string(),
+            query: ".files[]".to_string(),
+            format: None,
+            limit: Some(1),
+        };
+        assert!(run_query_data(&args, dir.path())
+            .unwrap()
+            .ends_with("(showing 1 of 2 results)"));
+    }
+
+    #[test]
+    fn test_query_csv() {
+        let dir = tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("people.csv"),
+            "name,age,city\nAda,36,London\nAlan,41,Wilmslow\n\"Hopper, Grace\",85,New York\nLinus,29,Portland\n",
+        )
+        .unwrap();
+        let csv = |q| query(dir.path(), "people.csv", q);
+
+        assert_eq!(
+            csv("SELECT name, age WHERE age > 35 ORDER BY age DESC"),
+            "name,age\n\"Hopper, Grace\",85\nAlan,41\nAda,36"
+        );
+        assert_eq!(
+            csv("select count(*) where city contains 'o' and age < 80"),
+            "3"
+        );
+        assert_eq!(
+            csv("SELECT * WHERE city = 'New York'"),
+            "name,age,city\n\"Hopper, Grace\",85,New York"
+        );
+        assert_eq!(
+            csv("SELECT name ORDER BY name LIMIT 2"),
+            "name\nAda\nAlan\n(showing 2 of 4 rows)"
+        );
+        assert!(csv("SELECT salary").starts_with("Error: No column 'salary'"));
+        assert!(csv("name, age").starts_with("Error: CSV queries start with SELECT"));
+    }
+}
@@ -0,0 +1,177 @@
+//! `recent_commands`: the last bash commands in brief, so an agent can recall what it
+//! already tried without keeping every output in its context.
+
+use openhands_sdk_rs::models::{BashEvent, BashEventQuery, BashOutput};
+use openhands_sdk_rs::runtime::bash::BashEventService;
+use openhands_sdk_rs::shell_output;
+use rmcp::schemars;
+use rmcp::ErrorData as McpError;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Commands shown by default, and at most.
+pub const DEFAULT_COUNT: usize = 10;
+pub const MAX_COUNT: usize = 100;
+/// Characters of each command's output shown by default.
+pub const DEFAULT_OUTPUT_CHARS: usize = 200;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct RecentCommandsArgs {
+    /// How many of the latest commands to show; 10 by default, at most 100.
+    #[serde(default)]
+    pub count: Option<usize>,
+    /// Characters of each command's output to show, from its end; 200 by default.
+    #[serde(default)]
+    pub output_chars: Option<usize>,
+}
+
+pub fn run_recent_commands(
+    args: &RecentCommandsArgs,
+    bash: &BashEventService,
+) -> Result<String, McpError> {
+    let count = args.count.unwrap_or(DEFAULT_COUNT).clamp(1, MAX_COUNT);
+    let output_chars = args.output_chars.unwrap_or(DEFAULT_OUTPUT_CHARS);
+    let page = match bash.search_bash_events(&BashEventQuery::default()) {
+        Ok(page) => page,
+        Err(e) => return Ok(format!("Error: {}", e)),
+    };
+
+    let mut commands = Vec::new();
+    let mut outputs: HashMap<_, &BashOutput> = HashMap::new();
+    for event in &page.items {
+        match event {
+            BashEvent::BashCommand(c) => commands.push(c),
+            BashEvent::BashOutput(o) => {
+                outputs.insert(o.command_id, o);
+            }
+        }
+    }
+    if commands.is_empty() {
+        return Ok("No commands have been run yet.".to_string());
+    }
+
+    let skipped = commands.len().saturating_sub(count);
+    let mut summary = if skipped > 0 {
+        format!(
+            "The last {} of {} commands, oldest first:\n",
+            count,
+            commands.len()
+        )
+    } else {
+        format!("{} commands, oldest first:\n", commands.len())
+    };
+    for (i, command) in commands.iter().enumerate().skip(skipped) {
+        let status = match outputs.get(&command.id) {
+            Some(out) => format!(
+                "exit {}, {}s",
+                out.exit_code
+                    .map_or("?".to_string(), |code| code.to_string()),
+                (out.timestamp - command.timestamp).num_seconds()
+            ),
+            None => "running".to_string(),
+        };
+        summary.push_str(&format!(
+            "{}. $ {}  ({}, {})\n",
+            i + 1,
+            command.command,
+            status,
+            command.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+        ));
+        if let Some(out) = outputs.get(&command.id) {
+            let text = out.combined_output();
+            let text = shell_output::clean(&text);
+            let tail = tail_chars(text.trim_end(), output_chars);
+            for line in tail.lines() {
+                summary.push_str(&format!("   {}\n", line));
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// The last `max` characters of `text`, marked if anything was cut.
+fn tail_chars(text: &str, max: usize) -> String {
+    let total = text.chars().count();
+    if total <= max {
+        return text.to_string();
+    }
+    let tail: String = text.chars().skip(total - max).collect();
+    format!("[{} earlier characters omitted] ...{}", total - max, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+    use openhands_sdk_rs::models::{BashCommand, SCHEMA_VERSION};
+    use std::path::Path;
+    use tempfile::tempdir;
+    use uuid::Uuid;
+
+    /// Store `command`, run at minute `minute`, with an output after `seconds`.
+    fn record(dir: &Path, minute: u32, command: &str, seconds: i64, exit_code: i32, stdout: &str) {
+        let id = Uuid::new_v4();
+        let timestamp = Utc.with_ymd_and_hms(2025, 6, 1, 12, minute, 0).unwrap();
+        let events = [
+            BashEvent::BashCommand(BashCommand {
+                schema_version: SCHEMA_VERSION,
+                id,
+                timestamp,
+                command: command.to_string(),
+                cwd: None,
+                timeout: 300,
+            }),
+            BashEvent::BashOutput(BashOutput {
+                schema_version: SCHEMA_VERSION,
+                id: Uuid::new_v4(),
+                timestamp: timestamp + Duration::seconds(seconds),
+                command_id: id,
+                order: 0,
+                exit_code: Some(exit_code),
+                stdout: Some(stdout.to_string()),
+                stderr: None,
+                merged_output: Some(stdout.to_string()),
+                chunks: Vec::new(),
+                timed_out: false,
+            }),
+        ];
+        for event in events {
+            let name = format!("{}_{}", minute, event.id().simple());
+            std::fs::write(dir.join(name), serde_json::to_string(&event).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_recent_commands() {
+        let dir = tempdir().unwrap();
+        let bash = BashEventService::new(dir.path().to_path_buf()).unwrap();
+        let args = RecentCommandsArgs {
+            count: Some(2),
+            output_chars: Some(12),
+        };
+        assert_eq!(
+            run_recent_commands(&args, &bash).unwrap(),
+            "No commands have been run yet."
+        );
+
+        record(dir.path(), 0, "true", 0, 0, "");
+        record(dir.path(), 1, "echo first", 0, 0, "first\n");
+        record(
+            dir.path(),
+            2,
+            "cargo test",
+            42,
+            101,
+            "a long line of output\n",
+        );
+
+        assert_eq!(
+            run_recent_commands(&args, &bash).unwrap(),
+            "The last 2 of 3 commands, oldest first:\n\
+             2. $ echo first  (exit 0, 0s, 2025-06-01 12:01:00 UTC)\n\
+             \x20  first\n\
+             3. $ cargo test  (exit 101, 42s, 2025-06-01 12:02:00 UTC)\n\
+             \x20  [9 earlier characters omitted] ...ne of output\n"
+        );
+    }
+}
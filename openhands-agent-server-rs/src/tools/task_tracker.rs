@@ -1,3 +1,4 @@
+use crate::tools::messages::msg;
 use rmcp::schemars;
 use rmcp::ErrorData as McpError;
 use serde::{Deserialize, Serialize};
@@ -24,7 +25,7 @@ pub fn run_task_tracker(args: &TaskTrackerArgs, workspace_dir: &Path) -> Result<
     let mut tasks: Vec<TaskItem> = if tasks_file.exists() {
         match fs::read_to_string(&tasks_file) {
             Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-            Err(e) => return Ok(format!("Error: Failed to read tasks.json: {}", e)),
+            Err(e) => return Ok(msg!("task_tracker.read_failed", error = e)),
         }
     } else {
         Vec::new()
@@ -40,18 +41,15 @@ pub fn run_task_tracker(args: &TaskTrackerArgs, workspace_dir: &Path) -> Result<
                 tasks = new_tasks.clone();
                 let content = match serde_json::to_string_pretty(&tasks) {
                     Ok(c) => c,
-                    Err(e) => return Ok(format!("Error: Failed to serialize tasks: {}", e)),
+                    Err(e) => return Ok(msg!("task_tracker.serialize_failed", error = e)),
                 };
                 if let Err(e) = fs::write(&tasks_file, content) {
-                    return Ok(format!("Error: Failed to write tasks.json: {}", e));
+                    return Ok(msg!("task_tracker.write_failed", error = e));
                 }
             }
         }
         _ => {
-            return Ok(format!(
-                "Error: Unknown command '{}'. Use 'view' or 'plan'.",
-                args.command
-            ));
+            return Ok(msg!("task_tracker.unknown_command", command = args.command));
         }
     }
 
@@ -72,7 +70,7 @@ pub fn run_task_tracker(args: &TaskTrackerArgs, workspace_dir: &Path) -> Result<
         ));
     }
     if output.is_empty() {
-        output = "No tasks in the list.".to_string();
+        output = msg!("task_tracker.empty");
     }
 
     Ok(output)
@@ -0,0 +1,341 @@
+//! `openhands-agent-server-rs top`: a terminal dashboard of a running server's
+//! conversations.
+//!
+//! It polls `GET /api/activity` every second and shows each conversation's state, the tool
+//! call it waits on, its tokens and estimated cost, and the errors the conversations ran into
+//! lately. The selected conversation can be paused, resumed or have its step stopped;
+//! stopping asks for the key a second time.
+
+use chrono::{DateTime, Utc};
+use openhands_sdk_rs::activity::ActivityReport;
+use openhands_sdk_rs::client::AgentServerClient;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Row, Table, TableState};
+use ratatui::Frame;
+use std::time::{Duration, Instant};
+
+const REFRESH: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+struct Dashboard {
+    server: String,
+    conversations: Vec<ActivityReport>,
+    table: TableState,
+    /// The outcome of the last refresh or key press.
+    status: String,
+    /// The conversation whose step `s` stops when pressed again.
+    stop_armed: Option<String>,
+}
+
+impl Dashboard {
+    fn selected(&self) -> Option<&ActivityReport> {
+        self.table
+            .selected()
+            .and_then(|i| self.conversations.get(i))
+    }
+
+    fn update(&mut self, conversations: Vec<ActivityReport>) {
+        let selected = self.selected().map(|c| c.id.clone());
+        self.conversations = conversations;
+        let index = selected
+            .and_then(|id| self.conversations.iter().position(|c| c.id == id))
+            .or((!self.conversations.is_empty()).then_some(0));
+        self.table.select(index);
+    }
+
+    /// Press `s` on the selected conversation: the first press asks for confirmation, the
+    /// second returns the conversation to stop.
+    fn confirm_stop(&mut self) -> Option<String> {
+        let id = self.selected()?.id.clone();
+        if self.stop_armed.take().as_ref() == Some(&id) {
+            return Some(id);
+        }
+        self.status = format!("Press s again to stop the step of {}", id);
+        self.stop_armed = Some(id);
+        None
+    }
+}
+
+/// Run the dashboard until `q` or Esc is pressed.
+pub async fn run(client: AgentServerClient, server: &str) -> Result<(), String> {
+    let mut terminal = ratatui::try_init().map_err(|e| e.to_string())?;
+    let mut dashboard = Dashboard {
+        server: server.to_string(),
+        ..Default::default()
+    };
+    let result = run_loop(&client, &mut terminal, &mut dashboard).await;
+    ratatui::restore();
+    result
+}
+
+async fn run_loop(
+    client: &AgentServerClient,
+    terminal: &mut ratatui::DefaultTerminal,
+    dashboard: &mut Dashboard,
+) -> Result<(), String> {
+    let mut refreshed: Option<Instant> = None;
+    loop {
+        if refreshed.is_none_or(|at| at.elapsed() >= REFRESH) {
+            match client.activity().await {
+                Ok(conversations) => dashboard.update(conversations),
+                Err(e) => dashboard.status = format!("Failed to refresh: {}", e),
+            }
+            refreshed = Some(Instant::now());
+        }
+        terminal
+            .draw(|frame| draw(frame, dashboard, Utc::now()))
+            .map_err(|e| e.to_string())?;
+
+        if !event::poll(Duration::from_millis(200)).map_err(|e| e.to_string())? {
+            continue;
+        }
+        let Event::Key(key) = event::read().map_err(|e| e.to_string())? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if key.code != KeyCode::Char('s') {
+            dashboard.stop_armed = None;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Down | KeyCode::Char('j') => dashboard.table.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => dashboard.table.select_previous(),
+            KeyCode::Char('s') => {
+                let Some(id) = dashboard.confirm_stop() else {
+                    continue;
+                };
+                dashboard.status = match client.stop(&id).await {
+                    Ok(()) => format!("Stopped the step of {}", id),
+                    Err(e) => e,
+                };
+                refreshed = None;
+            }
+            KeyCode::Char('p') => {
+                let Some(conversation) = dashboard.selected() else {
+                    continue;
+                };
+                let id = conversation.id.clone();
+                let (result, done) = if conversation.paused {
+                    (client.resume(&id).await, "Resumed")
+                } else {
+                    (client.pause(&id).await, "Paused")
+                };
+                dashboard.status = match result {
+                    Ok(()) => format!("{} {}", done, id),
+                    Err(e) => e,
+                };
+                refreshed = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, dashboard: &mut Dashboard, now: DateTime<Utc>) {
+    let [header, table, errors, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(5),
+        Constraint::Length(8),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let conversations = &dashboard.conversations;
+    let running = conversations.iter().filter(|c| c.running).count();
+    let tokens: u64 = conversations.iter().map(total_tokens).sum();
+    let cost: f64 = conversations.iter().map(|c| c.cost_usd).sum();
+    frame.render_widget(
+        Paragraph::new(format!(
+            "openhands top - {} - {} conversations, {} running - {} tokens, ${:.4}",
+            dashboard.server,
+            conversations.len(),
+            running,
+            tokens,
+            cost
+        ))
+        .style(Style::new().add_modifier(Modifier::BOLD)),
+        header,
+    );
+
+    let rows = conversations.iter().map(|c| {
+        let state = match (c.running, c.paused) {
+            (_, true) => "paused",
+            (true, false) => "running",
+            (false, false) => "idle",
+        };
+        let tool = c
+            .current_tool
+            .as_ref()
+            .map(|tool| {
+                let secs = (now - tool.started_at).num_seconds().max(0);
+                format!("{} ({}s)", tool.tool_name, secs)
+            })
+            .unwrap_or_default();
+        Row::new(vec![
+            c.id.clone(),
+            state.to_string(),
+            tool,
+            total_tokens(c).to_string(),
+            format!("${:.4}", c.cost_usd),
+            c.recent_errors.len().to_string(),
+        ])
+    });
+    let widths = [
+        Constraint::Length(36),
+        Constraint::Length(8),
+        Constraint::Min(20),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(6),
+    ];
+    frame.render_stateful_widget(
+        Table::new(rows, widths)
+            .header(
+                Row::new(["CONVERSATION", "STATE", "TOOL", "TOKENS", "COST", "ERRORS"])
+                    .style(Style::new().add_modifier(Modifier::BOLD)),
+            )
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+            .block(Block::bordered().title("Conversations")),
+        table,
+        &mut dashboard.table,
+    );
+
+    let mut recent: Vec<_> = conversations
+        .iter()
+        .flat_map(|c| c.recent_errors.iter().map(move |e| (c.id.as_str(), e)))
+        .collect();
+    recent.sort_by_key(|(_, error)| std::cmp::Reverse(error.timestamp));
+    let items = recent.into_iter().map(|(id, error)| {
+        ListItem::new(format!(
+            "{} {} {}: {}",
+            error.timestamp.format("%H:%M:%S"),
+            &id[..id.len().min(8)],
+            error.tool_name.as_deref().unwrap_or("step"),
+            error.message.lines().next().unwrap_or_default()
+        ))
+        .style(Style::new().fg(Color::Red))
+    });
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title("Recent errors")),
+        errors,
+    );
+
+    frame.render_widget(
+        Paragraph::new(Line::from(format!(
+            "j/k select  p pause/resume  s stop step  q quit  {}",
+            dashboard.status
+        )))
+        .style(Style::new().fg(Color::DarkGray)),
+        footer,
+    );
+}
+
+fn total_tokens(conversation: &ActivityReport) -> u64 {
+    conversation.usage.prompt_tokens + conversation.usage.completion_tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openhands_sdk_rs::activity::{CurrentTool, RecentError};
+    use openhands_sdk_rs::llm::TokenUsage;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    #[test]
+    fn test_dashboard_shows_activity() {
+        let now = Utc::now();
+        let report = |id: &str| ActivityReport {
+            id: id.to_string(),
+            running: false,
+            paused: false,
+            current_tool: None,
+            recent_errors: Vec::new(),
+            usage: TokenUsage {
+                prompt_tokens: 1000,
+                completion_tokens: 200,
+            },
+            cost_usd: 0.01,
+        };
+        let mut dashboard = Dashboard {
+            server: "http://localhost:3000".to_string(),
+            ..Default::default()
+        };
+        dashboard.update(vec![
+            ActivityReport {
+                running: true,
+                current_tool: Some(CurrentTool {
+                    tool_name: "cmd".to_string(),
+                    tool_call_id: "call_1".to_string(),
+                    started_at: now - chrono::Duration::seconds(12),
+                }),
+                recent_errors: vec![RecentError {
+                    timestamp: now,
+                    tool_name: Some("file_editor".to_string()),
+                    message: "old_str not found".to_string(),
+                }],
+                ..report("conv-a")
+            },
+            ActivityReport {
+                paused: true,
+                ..report("conv-b")
+            },
+        ]);
+        assert_eq!(dashboard.selected().unwrap().id, "conv-a");
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal
+            .draw(|frame| draw(frame, &mut dashboard, now))
+            .unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("2 conversations, 1 running - 2400 tokens, $0.0200"));
+        assert!(screen.contains("running"));
+        assert!(screen.contains("cmd (12s)"));
+        assert!(screen.contains("paused"));
+        assert!(screen.contains("conv-a file_editor: old_str not found"));
+
+        // The selection follows its conversation when the list changes.
+        dashboard.table.select(Some(1));
+        dashboard.update(vec![report("conv-b"), report("conv-c")]);
+        assert_eq!(dashboard.selected().unwrap().id, "conv-b");
+        dashboard.update(Vec::new());
+        assert!(dashboard.selected().is_none());
+    }
+
+    #[test]
+    fn test_stop_needs_confirmation() {
+        let report = |id: &str| ActivityReport {
+            id: id.to_string(),
+            running: true,
+            paused: false,
+            current_tool: None,
+            recent_errors: Vec::new(),
+            usage: TokenUsage::default(),
+            cost_usd: 0.0,
+        };
+        let mut dashboard = Dashboard::default();
+        assert_eq!(dashboard.confirm_stop(), None);
+
+        dashboard.update(vec![report("conv-a"), report("conv-b")]);
+        assert_eq!(dashboard.confirm_stop(), None);
+        assert_eq!(dashboard.status, "Press s again to stop the step of conv-a");
+        assert_eq!(dashboard.confirm_stop().as_deref(), Some("conv-a"));
+
+        // Moving on asks again, for the newly selected conversation.
+        assert_eq!(dashboard.confirm_stop(), None);
+        dashboard.table.select_next();
+        assert_eq!(dashboard.confirm_stop(), None);
+        assert_eq!(dashboard.confirm_stop().as_deref(), Some("conv-b"));
+    }
+}
@@ -0,0 +1,62 @@
+//! The web UI at `/ui`.
+//!
+//! A single page, embedded in the binary from `ui/`, that lists the user's conversations,
+//! follows their events as they happen, shows the diffs of the files they changed and
+//! approves or rejects actions waiting in `confirm` mode. It talks to the HTTP API like any
+//! other client, with the API key the user enters, so the page itself is served without
+//! authentication.
+
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use rust_embed::RustEmbed;
+
+#[derive(RustEmbed)]
+#[folder = "ui/"]
+struct Assets;
+
+/// The page itself.
+pub async fn index() -> Response {
+    asset("index.html")
+}
+
+/// Its scripts and styles.
+pub async fn serve_asset(Path(path): Path<String>) -> Response {
+    asset(&path)
+}
+
+fn asset(path: &str) -> Response {
+    match Assets::get(path) {
+        Some(file) => (
+            [(header::CONTENT_TYPE, file.metadata.mimetype().to_string())],
+            file.data,
+        )
+            .into_response(),
+        None => (StatusCode::NOT_FOUND, format!("No UI asset {}", path)).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assets_are_embedded() {
+        let page = asset("index.html");
+        assert_eq!(page.status(), StatusCode::OK);
+        assert!(page.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+        // Everything the page loads is embedded with it.
+        let html = String::from_utf8(Assets::get("index.html").unwrap().data.to_vec()).unwrap();
+        for reference in ["/ui/app.js", "/ui/style.css"] {
+            assert!(html.contains(reference));
+            let name = reference.trim_start_matches("/ui/");
+            assert_eq!(asset(name).status(), StatusCode::OK);
+        }
+        assert_eq!(asset("missing.js").status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,596 @@
+//! Webhook-triggered agent runs.
+//!
+//! `POST /webhooks/{provider}` accepts GitHub, GitLab and Bitbucket deliveries. After the
+//! signature is verified, an issue labeled with the trigger label or a comment mentioning
+//! `@openhands` starts the issue resolver in the background; the outcome is posted back as
+//! a comment. Redelivered events are ignored, only one run per issue is active at a time,
+//! and the number of concurrent runs is bounded.
+//!
+//! Anyone can comment on a public issue, so a mention only starts a run when its author is
+//! trusted: on GitHub an owner, member or collaborator of the repository, or anywhere a
+//! user listed in `WEBHOOK_ALLOWED_AUTHORS`. GitLab and Bitbucket do not say how the author
+//! relates to the repository, so there only the list counts. Bitbucket nicknames can be
+//! changed by their owners, so Bitbucket authors are listed by account id or UUID instead.
+//! Labels can only be applied by users with triage access, so labeling needs no further
+//! check.
+
+use crate::api::{issue_resolver, AppState};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use hmac::{Hmac, Mac};
+use openhands_sdk_rs::repo_host::host_for_url;
+use openhands_sdk_rs::resolver::{ResolveIssueRequest, ResolveIssueResult};
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{Mutex, Semaphore};
+
+/// Number of delivery ids remembered for deduplication.
+const SEEN_DELIVERIES: usize = 1000;
+
+/// Longest comment GitHub accepts, in characters. Longer diffs are cut to fit.
+const MAX_COMMENT_CHARS: usize = 65_536;
+
+/// GitHub `author_association`s whose mentions start runs.
+const TRUSTED_ASSOCIATIONS: [&str; 3] = ["OWNER", "MEMBER", "COLLABORATOR"];
+
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    /// Shared secret configured on the hosting service. Webhooks are disabled without one.
+    pub secret: Option<String>,
+    pub trigger_label: String,
+    pub mention: String,
+    /// Users whose mentions start runs whatever their role in the repository.
+    pub allowed_authors: Vec<String>,
+    pub max_concurrent_runs: usize,
+}
+
+impl WebhookConfig {
+    /// Read `WEBHOOK_SECRET`, `WEBHOOK_TRIGGER_LABEL` (default `openhands`),
+    /// `WEBHOOK_ALLOWED_AUTHORS` (comma-separated usernames, or account ids/UUIDs for
+    /// Bitbucket) and
+    /// `WEBHOOK_MAX_CONCURRENT_RUNS` (default 2).
+    pub fn from_env() -> Self {
+        Self {
+            secret: std::env::var("WEBHOOK_SECRET").ok(),
+            trigger_label: std::env::var("WEBHOOK_TRIGGER_LABEL")
+                .unwrap_or_else(|_| "openhands".to_string()),
+            mention: "@openhands".to_string(),
+            allowed_authors: std::env::var("WEBHOOK_ALLOWED_AUTHORS")
+                .map(|authors| {
+                    authors
+                        .split(',')
+                        .map(|a| a.trim().to_string())
+                        .filter(|a| !a.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            max_concurrent_runs: std::env::var("WEBHOOK_MAX_CONCURRENT_RUNS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+        }
+    }
+}
+
+pub struct WebhookState {
+    config: WebhookConfig,
+    seen: Mutex<VecDeque<String>>,
+    active: std::sync::Mutex<HashSet<String>>,
+    runs: Arc<Semaphore>,
+}
+
+impl WebhookState {
+    pub fn new(config: WebhookConfig) -> Self {
+        Self {
+            runs: Arc::new(Semaphore::new(config.max_concurrent_runs.max(1))),
+            config,
+            seen: Mutex::new(VecDeque::new()),
+            active: std::sync::Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Record a delivery id, returning false if it was already seen.
+    async fn first_delivery(&self, id: &str) -> bool {
+        let mut seen = self.seen.lock().await;
+        if seen.iter().any(|s| s == id) {
+            return false;
+        }
+        if seen.len() == SEEN_DELIVERIES {
+            seen.pop_front();
+        }
+        seen.push_back(id.to_string());
+        true
+    }
+}
+
+/// Marks the run for an issue as active until dropped, also when the run panics.
+struct ActiveRun {
+    webhooks: Arc<WebhookState>,
+    key: String,
+}
+
+impl ActiveRun {
+    /// Mark `key` active, or return `None` if a run for it already is.
+    fn start(webhooks: Arc<WebhookState>, key: String) -> Option<Self> {
+        let inserted = webhooks
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.clone());
+        inserted.then_some(Self { webhooks, key })
+    }
+}
+
+impl Drop for ActiveRun {
+    fn drop(&mut self) {
+        self.webhooks
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.key);
+    }
+}
+
+/// An event that should start an agent run.
+#[derive(Debug, PartialEq)]
+pub struct Trigger {
+    pub repo_url: String,
+    pub issue_number: u64,
+    pub instructions: Option<String>,
+}
+
+pub async fn handle_webhook(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, String) {
+    let webhooks = state.webhooks.clone();
+    let Some(secret) = &webhooks.config.secret else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Webhooks are not configured".to_string(),
+        );
+    };
+    if !verify_signature(&provider, &headers, &body, secret) {
+        return (StatusCode::UNAUTHORIZED, "Invalid signature".to_string());
+    }
+
+    let payload: Value = match serde_json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid payload: {}", e)),
+    };
+
+    let Some(trigger) = parse_event(&provider, &headers, &payload, &webhooks.config) else {
+        return (StatusCode::OK, "Ignored".to_string());
+    };
+
+    if let Some(id) = delivery_id(&provider, &headers) {
+        if !webhooks.first_delivery(&id).await {
+            return (StatusCode::OK, "Duplicate delivery".to_string());
+        }
+    }
+
+    let key = format!("{}#{}", trigger.repo_url, trigger.issue_number);
+    let Some(active) = ActiveRun::start(webhooks.clone(), key.clone()) else {
+        return (
+            StatusCode::CONFLICT,
+            format!("A run for {} is already in progress", key),
+        );
+    };
+
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let run_dir = state.workspace_dir.join("webhook-runs").join(&run_id);
+    tracing::info!("Webhook run {} started for {}", run_id, key);
+    tokio::spawn(async move {
+        let _active = active;
+        let _permit = webhooks.runs.clone().acquire_owned().await;
+        run_trigger(trigger, run_dir).await;
+    });
+
+    (StatusCode::ACCEPTED, run_id)
+}
+
+async fn run_trigger(trigger: Trigger, run_dir: PathBuf) {
+    let request = ResolveIssueRequest {
+        repo_url: trigger.repo_url,
+        issue_number: trigger.issue_number,
+        instructions: trigger.instructions,
+        notify: Vec::new(),
+    };
+    let result = match issue_resolver(run_dir, &request.repo_url) {
+        Ok(resolver) => resolver.resolve(&request).await,
+        Err(e) => Err(e),
+    };
+
+    let comment = result_comment(&result);
+    if let Err(e) = &result {
+        tracing::error!("Webhook run for {} failed: {}", request.repo_url, e);
+    }
+
+    match host_for_url(&request.repo_url) {
+        Ok((host, repo)) => {
+            if let Err(e) = host.comment(&repo, request.issue_number, &comment).await {
+                tracing::error!("Failed to post run result: {}", e);
+            }
+        }
+        Err(e) => tracing::error!("Failed to post run result: {}", e),
+    }
+}
+
+/// The comment reporting `result`, with the diff cut at a line boundary so the comment
+/// stays within [`MAX_COMMENT_CHARS`].
+fn result_comment(result: &Result<ResolveIssueResult, String>) -> String {
+    let r = match result {
+        Ok(r) if r.diff.is_empty() => {
+            return format!("OpenHands finished without changes.\n\n{}", r.summary)
+        }
+        Ok(r) => r,
+        Err(e) => return format!("OpenHands failed: {}", e),
+    };
+    let comment = |diff: &str, notice: &str| {
+        format!(
+            "OpenHands finished:\n\n{}\n\n<details><summary>Diff</summary>\n\n```diff\n{}\n```\n{}</details>",
+            r.summary, diff, notice
+        )
+    };
+
+    let full = comment(&r.diff, "");
+    if full.chars().count() <= MAX_COMMENT_CHARS {
+        return full;
+    }
+    let total_lines = r.diff.lines().count();
+    let notice = |shown: usize| {
+        format!(
+            "\nThe diff was cut to its first {} of {} lines to fit in a comment.\n",
+            shown, total_lines
+        )
+    };
+    let budget =
+        MAX_COMMENT_CHARS.saturating_sub(comment("", &notice(total_lines)).chars().count());
+    let mut diff = String::new();
+    let mut shown = 0;
+    let mut used = 0;
+    for line in r.diff.lines() {
+        let len = line.chars().count() + 1;
+        if used + len > budget {
+            break;
+        }
+        diff.push_str(line);
+        diff.push('\n');
+        used += len;
+        shown += 1;
+    }
+    comment(diff.trim_end_matches('\n'), &notice(shown))
+}
+
+/// Verify the delivery came from the hosting service. GitHub and Bitbucket sign the body
+/// with HMAC-SHA256; GitLab sends the secret itself as a token.
+pub fn verify_signature(provider: &str, headers: &HeaderMap, body: &[u8], secret: &str) -> bool {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    match provider {
+        "github" | "bitbucket" => {
+            let Some(signature) = header("x-hub-signature-256")
+                .or_else(|| header("x-hub-signature"))
+                .and_then(|s| s.strip_prefix("sha256="))
+                .and_then(decode_hex)
+            else {
+                return false;
+            };
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+                .expect("HMAC accepts keys of any length");
+            mac.update(body);
+            mac.verify_slice(&signature).is_ok()
+        }
+        "gitlab" => header("x-gitlab-token")
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), secret.as_bytes())),
+        _ => false,
+    }
+}
+
+/// Decide whether a delivery should start a run.
+pub fn parse_event(
+    provider: &str,
+    headers: &HeaderMap,
+    payload: &Value,
+    config: &WebhookConfig,
+) -> Option<Trigger> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let str_at = |pointer: &str| payload.pointer(pointer).and_then(|v| v.as_str());
+    let mention = |body: &str| body.contains(&config.mention).then(|| body.to_string());
+    let allowed = |author: Option<&str>| {
+        author.is_some_and(|author| config.allowed_authors.iter().any(|a| a == author))
+    };
+
+    match (
+        provider,
+        header("x-github-event"),
+        header("x-gitlab-event"),
+        header("x-event-key"),
+    ) {
+        ("github", Some("issues"), _, _) => {
+            let labeled = str_at("/action") == Some("labeled")
+                && str_at("/label/name") == Some(config.trigger_label.as_str());
+            if !labeled {
+                return None;
+            }
+            Some(Trigger {
+                repo_url: str_at("/repository/html_url")?.to_string(),
+                issue_number: payload.pointer("/issue/number")?.as_u64()?,
+                instructions: None,
+            })
+        }
+        ("github", Some("issue_comment"), _, _) => {
+            let trusted = str_at("/comment/author_association")
+                .is_some_and(|association| TRUSTED_ASSOCIATIONS.contains(&association))
+                || allowed(str_at("/comment/user/login"));
+            if str_at("/action") != Some("created") || !trusted {
+                return None;
+            }
+            Some(Trigger {
+                repo_url: str_at("/repository/html_url")?.to_string(),
+                issue_number: payload.pointer("/issue/number")?.as_u64()?,
+                instructions: Some(mention(str_at("/comment/body")?)?),
+            })
+        }
+        ("gitlab", _, Some("Issue Hook"), _) => {
+            let has_label = |pointer: &str| {
+                payload
+                    .pointer(pointer)
+                    .and_then(|v| v.as_array())
+                    .is_some_and(|labels| {
+                        labels.iter().any(|l| {
+                            l.get("title").and_then(|t| t.as_str())
+                                == Some(config.trigger_label.as_str())
+                        })
+                    })
+            };
+            let labeled =
+                has_label("/changes/labels/current") && !has_label("/changes/labels/previous");
+            if !labeled {
+                return None;
+            }
+            Some(Trigger {
+                repo_url: str_at("/project/web_url")?.to_string(),
+                issue_number: payload.pointer("/object_attributes/iid")?.as_u64()?,
+                instructions: None,
+            })
+        }
+        ("gitlab", _, Some("Note Hook"), _) => {
+            if str_at("/object_attributes/noteable_type") != Some("Issue")
+                || !allowed(str_at("/user/username"))
+            {
+                return None;
+            }
+            Some(Trigger {
+                repo_url: str_at("/project/web_url")?.to_string(),
+                issue_number: payload.pointer("/issue/iid")?.as_u64()?,
+                instructions: Some(mention(str_at("/object_attributes/note")?)?),
+            })
+        }
+        ("bitbucket", _, _, Some("issue:comment_created")) => {
+            if !allowed(str_at("/actor/account_id")) && !allowed(str_at("/actor/uuid")) {
+                return None;
+            }
+            Some(Trigger {
+                repo_url: str_at("/repository/links/html/href")?.to_string(),
+                issue_number: payload.pointer("/issue/id")?.as_u64()?,
+                instructions: Some(mention(str_at("/comment/content/raw")?)?),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn delivery_id(provider: &str, headers: &HeaderMap) -> Option<String> {
+    let name = match provider {
+        "github" => "x-github-delivery",
+        "gitlab" => "x-gitlab-event-uuid",
+        "bitbucket" => "x-request-uuid",
+        _ => return None,
+    };
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|id| format!("{}:{}", provider, id))
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> WebhookConfig {
+        WebhookConfig {
+            secret: Some("s3cret".to_string()),
+            trigger_label: "openhands".to_string(),
+            mention: "@openhands".to_string(),
+            allowed_authors: vec!["trusted-bot".to_string()],
+            max_concurrent_runs: 1,
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (k, v) in pairs {
+            map.insert(*k, v.parse().unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn test_verify_github_signature() {
+        let body = br#"{"zen":"Keep it simple."}"#;
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+        mac.update(body);
+        let hex: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        let signed = headers(&[("x-hub-signature-256", &format!("sha256={}", hex))]);
+        assert!(verify_signature("github", &signed, body, "s3cret"));
+        assert!(!verify_signature("github", &signed, b"tampered", "s3cret"));
+        assert!(!verify_signature(
+            "github",
+            &HeaderMap::new(),
+            body,
+            "s3cret"
+        ));
+
+        let gitlab = headers(&[("x-gitlab-token", "s3cret")]);
+        assert!(verify_signature("gitlab", &gitlab, body, "s3cret"));
+        assert!(!verify_signature("gitlab", &gitlab, body, "other"));
+    }
+
+    #[test]
+    fn test_parse_github_events() {
+        let labeled = serde_json::json!({
+            "action": "labeled",
+            "label": { "name": "openhands" },
+            "issue": { "number": 12 },
+            "repository": { "html_url": "https://github.com/octo/hello" }
+        });
+        let trigger = parse_event(
+            "github",
+            &headers(&[("x-github-event", "issues")]),
+            &labeled,
+            &config(),
+        )
+        .unwrap();
+        assert_eq!(trigger.issue_number, 12);
+        assert_eq!(trigger.instructions, None);
+
+        let comment = |association: &str, login: &str| {
+            serde_json::json!({
+                "action": "created",
+                "comment": {
+                    "body": "@openhands please fix",
+                    "author_association": association,
+                    "user": { "login": login }
+                },
+                "issue": { "number": 12 },
+                "repository": { "html_url": "https://github.com/octo/hello" }
+            })
+        };
+        let comment_headers = headers(&[("x-github-event", "issue_comment")]);
+        let trigger = parse_event(
+            "github",
+            &comment_headers,
+            &comment("MEMBER", "octocat"),
+            &config(),
+        )
+        .unwrap();
+        assert_eq!(
+            trigger.instructions.as_deref(),
+            Some("@openhands please fix")
+        );
+        // Anyone can comment on a public issue; only trusted authors start runs.
+        let outsider = comment("NONE", "drive-by");
+        assert!(parse_event("github", &comment_headers, &outsider, &config()).is_none());
+        let listed = comment("CONTRIBUTOR", "trusted-bot");
+        assert!(parse_event("github", &comment_headers, &listed, &config()).is_some());
+
+        let unrelated = serde_json::json!({
+            "action": "created",
+            "comment": { "body": "looks good", "author_association": "OWNER" },
+            "issue": { "number": 12 },
+            "repository": { "html_url": "https://github.com/octo/hello" }
+        });
+        assert!(parse_event("github", &comment_headers, &unrelated, &config()).is_none());
+    }
+
+    #[test]
+    fn test_parse_bitbucket_comment_by_account() {
+        let comment = |account_id: &str, uuid: &str| {
+            serde_json::json!({
+                "actor": {
+                    "nickname": "trusted-bot",
+                    "account_id": account_id,
+                    "uuid": uuid
+                },
+                "comment": { "content": { "raw": "@openhands please fix" } },
+                "issue": { "id": 7 },
+                "repository": { "links": { "html": { "href": "https://bitbucket.org/octo/hello" } } }
+            })
+        };
+        let comment_headers = headers(&[("x-event-key", "issue:comment_created")]);
+        let mut config = config();
+        config.allowed_authors = vec![
+            "557058:trusted".to_string(),
+            "{0d4a7d1e-trusted}".to_string(),
+        ];
+
+        let by_account = comment("557058:trusted", "{other}");
+        let trigger = parse_event("bitbucket", &comment_headers, &by_account, &config).unwrap();
+        assert_eq!(trigger.issue_number, 7);
+        let by_uuid = comment("557058:other", "{0d4a7d1e-trusted}");
+        assert!(parse_event("bitbucket", &comment_headers, &by_uuid, &config).is_some());
+        // Anyone can pick a listed nickname; it does not identify the author.
+        config.allowed_authors = vec!["trusted-bot".to_string()];
+        assert!(parse_event("bitbucket", &comment_headers, &by_account, &config).is_none());
+    }
+
+    #[test]
+    fn test_result_comment_fits_github_limit() {
+        let result = |diff: String| {
+            Ok(ResolveIssueResult {
+                issue_title: "Crash".to_string(),
+                issue_url: "https://github.com/octo/hello/issues/1".to_string(),
+                repo_dir: PathBuf::from("/tmp/repo"),
+                summary: "Fixed the crash.".to_string(),
+                diff,
+                usage: Default::default(),
+            })
+        };
+
+        let small = result_comment(&result("+fn main() {}".to_string()));
+        assert!(small.contains("+fn main() {}"));
+        assert!(!small.contains("was cut"));
+
+        let line = format!("+{}", "é".repeat(99));
+        let large = result_comment(&result(vec![line.as_str(); 2000].join("\n")));
+        assert!(large.chars().count() <= MAX_COMMENT_CHARS);
+        assert!(large.contains("of 2000 lines"));
+        assert!(large.contains("Fixed the crash."));
+    }
+
+    #[test]
+    fn test_active_run_released_on_drop() {
+        let state = Arc::new(WebhookState::new(config()));
+        let run = ActiveRun::start(state.clone(), "repo#1".to_string()).unwrap();
+        assert!(ActiveRun::start(state.clone(), "repo#1".to_string()).is_none());
+        drop(run);
+        assert!(ActiveRun::start(state, "repo#1".to_string()).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_deduplicates_deliveries() {
+        let state = WebhookState::new(config());
+        assert!(state.first_delivery("github:abc").await);
+        assert!(!state.first_delivery("github:abc").await);
+        assert!(state.first_delivery("github:def").await);
+    }
+}
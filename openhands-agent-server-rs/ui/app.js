@@ -0,0 +1,277 @@
+// The agent server's web UI. Everything goes through the HTTP API with the API key kept in
+// localStorage; events are read from the SSE stream with fetch, which, unlike
+// EventSource, can send the Authorization header.
+"use strict";
+
+const KEY_STORAGE = "openhands.apiKey";
+const $ = (id) => document.getElementById(id);
+
+let selected = null;
+let stream = null;
+
+async function api(method, path, body) {
+  const headers = {};
+  const key = localStorage.getItem(KEY_STORAGE);
+  if (key) headers["Authorization"] = `Bearer ${key}`;
+  if (body !== undefined) headers["Content-Type"] = "application/json";
+  const res = await fetch(path, {
+    method,
+    headers,
+    body: body === undefined ? undefined : JSON.stringify(body),
+    signal: method === "GET" && path.endsWith("/events") ? stream.signal : undefined,
+  });
+  if (res.status === 401 && askForKey()) return api(method, path, body);
+  if (!res.ok) throw new Error(`${res.status} ${await res.text()}`);
+  return res;
+}
+
+async function json(method, path, body) {
+  const res = await api(method, path, body);
+  const text = await res.text();
+  return text ? JSON.parse(text) : null;
+}
+
+function askForKey() {
+  const key = prompt("API key", localStorage.getItem(KEY_STORAGE) || "");
+  if (key === null) return false;
+  localStorage.setItem(KEY_STORAGE, key.trim());
+  return true;
+}
+
+function el(tag, attrs = {}, ...children) {
+  const node = document.createElement(tag);
+  for (const [name, value] of Object.entries(attrs)) {
+    if (name.startsWith("on")) node.addEventListener(name.slice(2), value);
+    else node.setAttribute(name, value);
+  }
+  for (const child of children) {
+    if (child != null) node.append(child);
+  }
+  return node;
+}
+
+function showError(error) {
+  $("events").append(el("div", { class: "event gap" }, `Error: ${error.message}`));
+}
+
+// Conversations
+
+async function loadConversations() {
+  const list = $("conversations");
+  list.replaceChildren();
+  for (const conversation of await json("GET", "/api/conversations")) {
+    list.append(
+      el(
+        "li",
+        {
+          class: conversation.id === selected ? "selected" : "",
+          onclick: () => select(conversation.id),
+        },
+        conversation.id,
+      ),
+    );
+  }
+}
+
+async function createConversation(event) {
+  event.preventDefault();
+  const mode = $("security-mode").value;
+  const conversation = await json("POST", "/api/conversations", mode ? { security_mode: mode } : {});
+  await select(conversation.id);
+}
+
+async function select(id) {
+  selected = id;
+  $("empty").hidden = true;
+  $("conversation").hidden = false;
+  $("conversation-id").textContent = id;
+  $("events").replaceChildren();
+  await Promise.all([loadConversations(), loadPending(), loadChanges()]);
+  follow(id);
+}
+
+// Events
+
+async function follow(id) {
+  if (stream) stream.abort();
+  stream = new AbortController();
+  const status = $("stream-status");
+  status.textContent = "connecting";
+  try {
+    const res = await api("GET", `/api/conversations/${id}/events`);
+    status.textContent = "live";
+    const reader = res.body.pipeThrough(new TextDecoderStream()).getReader();
+    let buffer = "";
+    for (;;) {
+      const { value, done } = await reader.read();
+      if (done) break;
+      buffer += value;
+      let end;
+      while ((end = buffer.indexOf("\n\n")) >= 0) {
+        const frame = buffer.slice(0, end);
+        buffer = buffer.slice(end + 2);
+        const data = frame
+          .split("\n")
+          .filter((line) => line.startsWith("data:"))
+          .map((line) => line.slice(5).trimStart())
+          .join("\n");
+        if (data) onEvent(JSON.parse(data));
+      }
+    }
+    status.textContent = "disconnected";
+  } catch (error) {
+    if (error.name !== "AbortError") status.textContent = `disconnected: ${error.message}`;
+  }
+}
+
+function onEvent(busEvent) {
+  const events = $("events");
+  const atBottom = events.scrollHeight - events.scrollTop - events.clientHeight < 40;
+  const node = renderEvent(busEvent);
+  if (node) events.append(node);
+  if (atBottom) events.scrollTop = events.scrollHeight;
+
+  const event = busEvent.event;
+  if (busEvent.topic === "agent" && event.type === "Action") loadPending();
+  if (busEvent.topic === "file_change" || (busEvent.topic === "agent" && event.type === "Observation")) {
+    loadChanges();
+  }
+}
+
+function renderEvent({ topic, event }) {
+  if (topic === "gap") {
+    return el("div", { class: "event gap" }, `${event.missed} events were missed.`);
+  }
+  if (topic !== "agent") return null;
+  switch (event.type) {
+    case "Message":
+      return el(
+        "div",
+        { class: `event ${event.source}` },
+        el("div", { class: "label" }, event.source),
+        el("div", { class: "text" }, event.content),
+      );
+    case "Action": {
+      const risk = event.security_risk;
+      return el(
+        "div",
+        { class: "event action" },
+        el("div", { class: "label" }, `action · ${event.tool_name}`),
+        event.thought ? el("div", { class: "text" }, event.thought) : null,
+        el("pre", {}, describeArguments(event)),
+        risk && risk.level !== "low"
+          ? el("div", { class: "risk" }, `${risk.level} risk${risk.reason ? `: ${risk.reason}` : ""}`)
+          : null,
+      );
+    }
+    case "Observation": {
+      const lines = event.content.split("\n").length;
+      return el(
+        "details",
+        { class: "event observation" },
+        el("summary", {}, `${event.tool_name} output (${lines} line${lines === 1 ? "" : "s"})`),
+        el("pre", {}, event.content),
+      );
+    }
+    default:
+      return null;
+  }
+}
+
+function describeArguments(action) {
+  const command = action.arguments && action.arguments.command;
+  if (typeof command === "string" && (action.tool_name === "cmd" || action.tool_name === "terminal")) {
+    return `$ ${command}`;
+  }
+  return JSON.stringify(action.arguments, null, 2);
+}
+
+async function sendMessage(event) {
+  event.preventDefault();
+  const input = $("message-content");
+  const content = input.value.trim();
+  if (!content || !selected) return;
+  input.value = "";
+  const id = selected;
+  // Appear right away; the agent's events follow on the stream.
+  $("events").append(renderEvent({ topic: "agent", event: { type: "Message", source: "user", content } }));
+  try {
+    await json("POST", `/api/conversations/${id}/messages`, { content });
+  } catch (error) {
+    showError(error);
+  }
+  if (id === selected) {
+    loadPending();
+    loadChanges();
+  }
+}
+
+// Confirmation mode
+
+async function loadPending() {
+  if (!selected) return;
+  const actions = await json("GET", `/api/conversations/${selected}/pending_actions`);
+  const list = $("pending-actions");
+  list.replaceChildren();
+  for (const action of actions) {
+    const decide = (approve) => async () => {
+      try {
+        await api("POST", `/api/conversations/${selected}/pending_actions/${action.tool_call_id}`, { approve });
+      } catch (error) {
+        showError(error);
+      }
+      loadPending();
+    };
+    list.append(
+      el(
+        "li",
+        {},
+        el("pre", {}, `${action.tool_name}\n${describeArguments(action)}`),
+        el("button", { class: "approve", onclick: decide(true) }, "Approve"),
+        el("button", { class: "reject", onclick: decide(false) }, "Reject"),
+      ),
+    );
+  }
+  $("pending").hidden = actions.length === 0;
+}
+
+// Diffs
+
+async function loadChanges() {
+  if (!selected) return;
+  const changes = await json("GET", `/api/conversations/${selected}/changes`);
+  $("changes-count").textContent = changes.length ? `(${changes.length})` : "";
+  $("changes").replaceChildren(
+    ...changes.map((change) =>
+      el(
+        "div",
+        { class: "diff" },
+        el("div", { class: "path" }, `${change.path} (${change.status}, +${change.additions} -${change.deletions})`),
+        el("pre", {}, ...change.diff.split("\n").map(diffLine)),
+      ),
+    ),
+  );
+}
+
+function diffLine(line) {
+  let cls = "";
+  if (line.startsWith("@@")) cls = "hunk";
+  else if (line.startsWith("+") && !line.startsWith("+++")) cls = "add";
+  else if (line.startsWith("-") && !line.startsWith("---")) cls = "del";
+  return cls ? el("span", { class: cls }, line) : `${line}\n`;
+}
+
+async function openReport() {
+  const res = await api("GET", `/api/conversations/${selected}/export?format=html`);
+  const url = URL.createObjectURL(new Blob([await res.text()], { type: "text/html" }));
+  window.open(url, "_blank");
+}
+
+$("api-key").addEventListener("click", askForKey);
+$("new-conversation").addEventListener("submit", (e) => createConversation(e).catch(showError));
+$("message").addEventListener("submit", sendMessage);
+$("message-content").addEventListener("keydown", (e) => {
+  if (e.key === "Enter" && (e.ctrlKey || e.metaKey)) $("message").requestSubmit();
+});
+$("report").addEventListener("click", () => openReport().catch(showError));
+loadConversations().catch(showError);
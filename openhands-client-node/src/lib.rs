@@ -0,0 +1,190 @@
+//! Node.js bindings for the agent server client, for VS Code extensions and Electron UIs.
+//!
+//! ```js
+//! const { AgentServerClient } = require("./openhands-client-node.node");
+//!
+//! const client = new AgentServerClient("http://localhost:3000", "oh-...");
+//! const { id } = await client.createConversation();
+//! const { response } = await client.sendMessage(id, "List the files");
+//! ```
+//!
+//! `napi build` also writes `index.d.ts` with the types below. Like the server API, there is
+//! no event stream: `sendMessage` resolves once the agent has replied.
+
+use napi::Result;
+use napi_derive::napi;
+use openhands_sdk_rs::client;
+use openhands_sdk_rs::models::{self, ExecuteBashRequest, InitConversationRequest};
+use openhands_sdk_rs::runtime::ports;
+
+fn to_napi(error: String) -> napi::Error {
+    napi::Error::from_reason(error)
+}
+
+#[napi(object)]
+pub struct Conversation {
+    pub id: String,
+    pub status: String,
+}
+
+impl From<models::ConversationResponse> for Conversation {
+    fn from(response: models::ConversationResponse) -> Self {
+        Self {
+            id: response.id,
+            status: response.status,
+        }
+    }
+}
+
+#[napi(object)]
+pub struct MessageReply {
+    pub response: String,
+}
+
+#[napi(object)]
+pub struct ExposedPort {
+    pub container_port: u32,
+    pub host_port: u32,
+    pub url: String,
+}
+
+impl From<ports::ExposedPort> for ExposedPort {
+    fn from(port: ports::ExposedPort) -> Self {
+        Self {
+            container_port: port.container_port.into(),
+            host_port: port.host_port.into(),
+            url: port.url,
+        }
+    }
+}
+
+#[napi(object)]
+pub struct BashResult {
+    pub exit_code: Option<i32>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+}
+
+#[napi(object)]
+pub struct FileResult {
+    pub path: String,
+    pub content: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+impl From<models::FileResponse> for FileResult {
+    fn from(response: models::FileResponse) -> Self {
+        Self {
+            path: response.path,
+            content: response.content,
+            success: response.success,
+            error: response.error,
+        }
+    }
+}
+
+#[napi]
+pub struct AgentServerClient(client::AgentServerClient);
+
+#[napi]
+impl AgentServerClient {
+    #[napi(constructor)]
+    pub fn new(base_url: String, api_key: Option<String>) -> Self {
+        let client = client::AgentServerClient::new(&base_url);
+        Self(match api_key {
+            Some(key) => client.with_api_key(&key),
+            None => client,
+        })
+    }
+
+    #[napi(getter)]
+    pub fn base_url(&self) -> String {
+        self.0.base_url().to_string()
+    }
+
+    /// Version, runtime and tool inventory, as returned by `GET /server_info`.
+    #[napi]
+    pub async fn server_info(&self) -> Result<serde_json::Value> {
+        let info = self.0.server_info().await.map_err(to_napi)?;
+        serde_json::to_value(info).map_err(|e| to_napi(e.to_string()))
+    }
+
+    #[napi]
+    pub async fn create_conversation(
+        &self,
+        system_message: Option<String>,
+    ) -> Result<Conversation> {
+        let request = InitConversationRequest {
+            system_message,
+            setup: None,
+            secrets: Default::default(),
+            security_mode: None,
+            dry_run: false,
+        };
+        let conversation = self
+            .0
+            .create_conversation(&request)
+            .await
+            .map_err(to_napi)?;
+        Ok(conversation.into())
+    }
+
+    #[napi]
+    pub async fn list_conversations(&self) -> Result<Vec<Conversation>> {
+        let conversations = self.0.list_conversations().await.map_err(to_napi)?;
+        Ok(conversations.into_iter().map(Conversation::from).collect())
+    }
+
+    #[napi]
+    pub async fn delete_conversation(&self, id: String) -> Result<()> {
+        self.0.delete_conversation(&id).await.map_err(to_napi)
+    }
+
+    /// Send a user message and wait for the agent's reply.
+    #[napi]
+    pub async fn send_message(&self, id: String, content: String) -> Result<MessageReply> {
+        let reply = self.0.send_message(&id, &content).await.map_err(to_napi)?;
+        Ok(MessageReply {
+            response: reply.response,
+        })
+    }
+
+    #[napi]
+    pub async fn list_ports(&self, id: String) -> Result<Vec<ExposedPort>> {
+        let ports = self.0.list_ports(&id).await.map_err(to_napi)?;
+        Ok(ports.into_iter().map(ExposedPort::from).collect())
+    }
+
+    #[napi]
+    pub async fn execute_bash(
+        &self,
+        command: String,
+        cwd: Option<String>,
+        timeout: Option<u32>,
+    ) -> Result<BashResult> {
+        let request = ExecuteBashRequest {
+            command,
+            cwd,
+            timeout: timeout.map(u64::from),
+        };
+        let output = self.0.execute_bash(&request).await.map_err(to_napi)?;
+        Ok(BashResult {
+            exit_code: output.exit_code,
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+
+    #[napi]
+    pub async fn read_file(&self, path: String) -> Result<FileResult> {
+        let response = self.0.read_file(&path).await.map_err(to_napi)?;
+        Ok(response.into())
+    }
+
+    #[napi]
+    pub async fn write_file(&self, path: String, content: String) -> Result<FileResult> {
+        let response = self.0.write_file(&path, &content).await.map_err(to_napi)?;
+        Ok(response.into())
+    }
+}
@@ -0,0 +1,258 @@
+//! Python bindings for the SDK, built with maturin as the `openhands_rs` module.
+//!
+//! ```python
+//! import asyncio
+//! from openhands_rs import Conversation, LLMConfig
+//!
+//! conversation = Conversation(
+//!     "/path/to/workspace",
+//!     llm=LLMConfig("gpt-5-nano", api_key="sk-..."),
+//!     runtime="local",
+//!     on_event=print,
+//! )
+//!
+//! async def main():
+//!     print(await conversation.send_message("List the files"))
+//!
+//! asyncio.run(main())
+//! ```
+//!
+//! The agent loop runs on a shared tokio runtime with the GIL released. `send_message` returns
+//! an awaitable for asyncio; `send_message_blocking` runs the step on the calling thread.
+//! Events are passed to Python as dicts, in the JSON form of `openhands_sdk_rs::events::Event`.
+
+use openhands_sdk_rs::events::{Event, MessageEvent};
+use openhands_sdk_rs::llm::LLMConfig;
+use openhands_sdk_rs::session::{self, ConversationManager, RuntimeKind};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+use std::path::PathBuf;
+
+/// Owner recorded on conversations created from Python.
+const OWNER: &str = "python";
+
+#[pyclass(name = "LLMConfig", from_py_object)]
+#[derive(Clone)]
+struct PyLLMConfig(LLMConfig);
+
+#[pymethods]
+impl PyLLMConfig {
+    #[new]
+    #[pyo3(signature = (model, api_key=None, reasoning_effort=None, thinking_budget=None))]
+    fn new(
+        model: String,
+        api_key: Option<String>,
+        reasoning_effort: Option<String>,
+        thinking_budget: Option<u32>,
+    ) -> Self {
+        Self(LLMConfig {
+            model,
+            api_key,
+            reasoning_effort,
+            thinking_budget,
+        })
+    }
+
+    /// Configuration from `LLM_MODEL` and `OPENAI_API_KEY`.
+    #[staticmethod]
+    fn from_env() -> Self {
+        Self(LLMConfig::from_env())
+    }
+
+    #[getter]
+    fn model(&self) -> String {
+        self.0.model.clone()
+    }
+
+    #[getter]
+    fn reasoning_effort(&self) -> Option<String> {
+        self.0.reasoning_effort.clone()
+    }
+
+    #[getter]
+    fn thinking_budget(&self) -> Option<u32> {
+        self.0.thinking_budget
+    }
+
+    fn __repr__(&self) -> String {
+        format!("LLMConfig(model={:?})", self.0.model)
+    }
+}
+
+fn runtime_kind(name: &str) -> PyResult<RuntimeKind> {
+    match name {
+        "local" => Ok(RuntimeKind::Local),
+        "docker" => Ok(RuntimeKind::Docker),
+        _ => Err(PyValueError::new_err(format!(
+            "Unknown runtime '{}': use 'local' or 'docker'",
+            name
+        ))),
+    }
+}
+
+/// An agent with its history and runtime.
+#[pyclass]
+struct Conversation {
+    inner: session::Conversation,
+    on_event: Option<Py<PyAny>>,
+}
+
+/// Add a user message to the history and run the agent until it replies. Returns the reply
+/// and the events of the step.
+async fn step(
+    conversation: session::Conversation,
+    content: String,
+) -> Result<(String, Vec<Event>), String> {
+    let mut history = conversation.history.write().await;
+    let message = Event::Message(MessageEvent {
+        source: "user".to_string(),
+        content,
+        file_refs: Vec::new(),
+    });
+    history.push(message.clone());
+    let mut runtime = conversation.runtime.write().await;
+    let events = conversation
+        .agent
+        .step_events(&history, runtime.as_mut())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let reply = match events.last() {
+        Some(Event::Message(m)) => m.content.clone(),
+        _ => String::new(),
+    };
+    history.extend(events.iter().cloned());
+    Ok((reply, std::iter::once(message).chain(events).collect()))
+}
+
+/// An event as a Python dict.
+fn event_to_py<'py>(py: Python<'py>, event: &Event) -> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(event).map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+/// Pass each event to the `on_event` callback, if there is one.
+fn notify(py: Python<'_>, on_event: Option<&Py<PyAny>>, events: &[Event]) -> PyResult<()> {
+    if let Some(callback) = on_event {
+        for event in events {
+            callback.call1(py, (event_to_py(py, event)?,))?;
+        }
+    }
+    Ok(())
+}
+
+#[pymethods]
+impl Conversation {
+    /// `runtime` is `local` (tools run in `workspace_dir`) or `docker` (a sandbox container).
+    /// The LLM is configured from the environment unless `llm` is given.
+    #[new]
+    #[pyo3(signature = (workspace_dir, llm=None, system_message=String::new(), runtime="local", on_event=None))]
+    fn new(
+        py: Python<'_>,
+        workspace_dir: PathBuf,
+        llm: Option<PyLLMConfig>,
+        system_message: String,
+        runtime: &str,
+        on_event: Option<Py<PyAny>>,
+    ) -> PyResult<Self> {
+        let runtime = runtime_kind(runtime)?;
+        let llm = llm.map_or_else(LLMConfig::from_env, |llm| llm.0);
+        // Starting a sandbox takes a while; let other Python threads run meanwhile.
+        let inner = py.detach(|| {
+            let _guard = pyo3_async_runtimes::tokio::get_runtime().enter();
+            ConversationManager::new().create_conversation_with(
+                OWNER,
+                system_message,
+                workspace_dir,
+                llm,
+                runtime,
+            )
+        });
+        Ok(Self { inner, on_event })
+    }
+
+    #[getter]
+    fn id(&self) -> String {
+        self.inner.id.clone()
+    }
+
+    /// Send a user message; the returned awaitable resolves to the agent's reply.
+    fn send_message<'py>(&self, py: Python<'py>, content: String) -> PyResult<Bound<'py, PyAny>> {
+        let conversation = self.inner.clone();
+        let on_event = self.on_event.as_ref().map(|c| c.clone_ref(py));
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let (reply, events) = step(conversation, content)
+                .await
+                .map_err(PyRuntimeError::new_err)?;
+            Python::attach(|py| notify(py, on_event.as_ref(), &events))?;
+            Ok(reply)
+        })
+    }
+
+    /// Send a user message and wait for the agent's reply.
+    fn send_message_blocking(&self, py: Python<'_>, content: String) -> PyResult<String> {
+        let conversation = self.inner.clone();
+        let (reply, events) = py
+            .detach(|| {
+                pyo3_async_runtimes::tokio::get_runtime().block_on(step(conversation, content))
+            })
+            .map_err(PyRuntimeError::new_err)?;
+        notify(py, self.on_event.as_ref(), &events)?;
+        Ok(reply)
+    }
+
+    /// The events so far, as dicts.
+    fn history<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        // A running step holds the history; wait for it without blocking other threads.
+        let history = py.detach(|| self.inner.history.blocking_read().clone());
+        let events = history
+            .iter()
+            .map(|event| event_to_py(py, event))
+            .collect::<PyResult<Vec<_>>>()?;
+        PyList::new(py, events)
+    }
+
+    /// Prompt and completion tokens used so far.
+    fn usage(&self) -> (u64, u64) {
+        let usage = self.inner.agent.usage();
+        (usage.prompt_tokens, usage.completion_tokens)
+    }
+}
+
+#[pymodule]
+fn openhands_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyLLMConfig>()?;
+    m.add_class::<Conversation>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_to_py() {
+        Python::initialize();
+        Python::attach(|py| {
+            let event = Event::Message(MessageEvent {
+                source: "agent".to_string(),
+                content: "Done".to_string(),
+                file_refs: Vec::new(),
+            });
+            let dict = event_to_py(py, &event).unwrap();
+            assert_eq!(
+                dict.get_item("type").unwrap().extract::<String>().unwrap(),
+                "Message"
+            );
+            assert_eq!(
+                dict.get_item("content")
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "Done"
+            );
+            assert!(runtime_kind("vm").is_err());
+        });
+    }
+}
@@ -4,8 +4,8 @@ use openhands_sdk_rs::{
         tools::{CmdTool, FileReadTool, FileWriteTool},
     },
     events::{Event, MessageEvent},
-    llm::{LLM, LLMConfig},
-    runtime::DockerRuntime,
+    llm::LLM,
+    runtime::{DockerRuntime, sandbox::sandbox_image},
 };
 
 #[tokio::main]
@@ -23,30 +23,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     println!("Initializing Coding Agent...");
 
     // 1. Configure LLM
-    let config = LLMConfig {
-        model: "gpt-5-nano".to_string(),
-        api_key,
-        reasoning_effort: Some("minimal".to_string()),
-    };
-    let llm = LLM::new(config);
+    let mut llm = LLM::builder()
+        .model("gpt-5-nano")
+        .reasoning_effort("minimal");
+    if let Some(api_key) = api_key {
+        llm = llm.api_key(api_key);
+    }
+    let llm = llm.build()?;
 
     // 2. Initialize Agent with System Prompt
-    let agent = Agent::new(
-        llm,
-        "You are a skilled Python coding assistant. You can write files and execute commands."
-            .to_string(),
-    );
+    let agent = Agent::builder()
+        .llm(llm)
+        .system_message(
+            "You are a skilled Python coding assistant. You can write files and execute commands.",
+        )
+        .max_iterations(20)
+        .build()?;
 
     // 3. Initialize Runtime (DockerRuntime)
     //    We separate execution from decision making. The runtime holds the tools.
-    let mut runtime = DockerRuntime::new(
-        "openhands-agent-server-rs:latest",
-        vec![
-            Box::new(CmdTool),
+    let mut runtime = DockerRuntime::builder()
+        .image(sandbox_image())
+        .tools(vec![
+            Box::new(CmdTool::default()),
             Box::new(FileReadTool),
-            Box::new(FileWriteTool),
-        ],
-    );
+            Box::new(FileWriteTool::default()),
+        ])
+        .build()?;
 
     // 4. Define the Task
     let task = "Write a Python script named 'hello.py' that prints 'Hello from Rust Agent!', then execute it.";
@@ -55,6 +58,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let history = vec![Event::Message(MessageEvent {
         source: "user".to_string(),
         content: task.to_string(),
+        file_refs: Vec::new(),
     })];
 
     // 5. Run Step
@@ -25,6 +25,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         model: "gpt-5-nano".to_string(),
         api_key,
         reasoning_effort: Some("minimal".to_string()),
+        thinking_budget: None,
     };
     let llm = LLM::new(config);
 
@@ -38,9 +39,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let mut runtime = RemoteRuntime::new(
         "http://localhost:3000".to_string(),
         vec![
-            Box::new(CmdTool),
+            Box::new(CmdTool::default()),
             Box::new(FileReadTool),
-            Box::new(FileWriteTool),
+            Box::new(FileWriteTool::default()),
         ],
     );
 
@@ -52,6 +53,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let history = vec![Event::Message(MessageEvent {
         source: "user".to_string(),
         content: user_task.to_string(),
+        file_refs: Vec::new(),
     })];
 
     println!("\n--- Running Agent ---");
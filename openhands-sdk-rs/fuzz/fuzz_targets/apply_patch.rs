@@ -0,0 +1,12 @@
+//! Feeds arbitrary patches and file contents to `apply_patch`, which must reject what it
+//! cannot apply instead of panicking.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openhands_sdk_rs::agent::tools::apply_patch_to_content;
+
+fuzz_target!(|input: (&str, &str)| {
+    let (patch, content) = input;
+    let _ = apply_patch_to_content(patch, content);
+});
@@ -0,0 +1,254 @@
+//! What a conversation is doing right now, and the controls to pause or stop it.
+//!
+//! [`ConversationActivity`] follows a conversation's steps: whether one is running, the tool
+//! call it is waiting on and the errors it ran into lately. A paused conversation finishes
+//! the tool call it is running and waits before the next one; stopping ends the running
+//! step. Monitors such as `openhands-agent-server-rs top` show it through
+//! `GET /api/activity`.
+
+use crate::events::Event;
+use crate::profile::TokenUsage;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// Errors kept per conversation.
+const RECENT_ERRORS: usize = 10;
+
+/// Characters of an error message kept.
+const ERROR_CHARS: usize = 300;
+
+/// A tool call the agent is waiting on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CurrentTool {
+    pub tool_name: String,
+    pub tool_call_id: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// A failed tool call, or a step that failed as a whole (`tool_name` is then `None`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecentError {
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    pub message: String,
+}
+
+/// A conversation's activity as `GET /api/activity` reports it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ActivityReport {
+    pub id: String,
+    /// Whether a step is running.
+    pub running: bool,
+    pub paused: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_tool: Option<CurrentTool>,
+    /// Newest last.
+    pub recent_errors: Vec<RecentError>,
+    /// Tokens used by the conversation's LLM so far.
+    pub usage: TokenUsage,
+    pub cost_usd: f64,
+}
+
+#[derive(Default)]
+struct State {
+    running: bool,
+    current_tool: Option<CurrentTool>,
+    recent_errors: VecDeque<RecentError>,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    paused: watch::Sender<bool>,
+    /// Counts stop requests; a running step ends when it changes.
+    stops: watch::Sender<u64>,
+}
+
+/// Shared by a conversation's steps and the API controlling them.
+#[derive(Clone)]
+pub struct ConversationActivity {
+    inner: Arc<Inner>,
+}
+
+impl Default for ConversationActivity {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State::default()),
+                paused: watch::Sender::new(false),
+                stops: watch::Sender::new(0),
+            }),
+        }
+    }
+}
+
+/// Resolves when the step it was handed out for is asked to stop.
+pub struct StopSignal(watch::Receiver<u64>);
+
+impl StopSignal {
+    pub async fn requested(&mut self) {
+        // The sender lives as long as the activity, which outlives its steps.
+        let _ = self.0.changed().await;
+    }
+}
+
+impl ConversationActivity {
+    /// Mark a step as running. The returned signal fires if [`ConversationActivity::stop`]
+    /// is called before [`ConversationActivity::end_step`].
+    pub fn begin_step(&self) -> StopSignal {
+        self.inner.state.lock().unwrap().running = true;
+        StopSignal(self.inner.stops.subscribe())
+    }
+
+    pub fn end_step(&self) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.running = false;
+        state.current_tool = None;
+    }
+
+    /// Ask the running step to stop. `false` if no step is running.
+    pub fn stop(&self) -> bool {
+        if !self.inner.state.lock().unwrap().running {
+            return false;
+        }
+        self.inner.stops.send_modify(|stops| *stops += 1);
+        true
+    }
+
+    pub fn pause(&self) {
+        self.inner.paused.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        self.inner.paused.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.inner.paused.borrow()
+    }
+
+    /// Wait until the conversation is not paused.
+    pub async fn wait_while_paused(&self) {
+        let mut paused = self.inner.paused.subscribe();
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+
+    /// Follow a step's event: an action starts a tool call, its observation ends it and
+    /// is recorded if it reports an error.
+    pub fn observe(&self, event: &Event) {
+        let mut state = self.inner.state.lock().unwrap();
+        match event {
+            Event::Action(action) => {
+                state.current_tool = Some(CurrentTool {
+                    tool_name: action.tool_name.clone(),
+                    tool_call_id: action.tool_call_id.clone(),
+                    started_at: Utc::now(),
+                });
+            }
+            Event::Observation(observation) => {
+                if state
+                    .current_tool
+                    .as_ref()
+                    .is_some_and(|tool| tool.tool_call_id == observation.tool_call_id)
+                {
+                    state.current_tool = None;
+                }
+                if let Some(message) = observation.content.strip_prefix("Error: ") {
+                    push_error(&mut state, Some(&observation.tool_name), message);
+                }
+            }
+            Event::Message(_) => {}
+        }
+    }
+
+    /// Record an error that is not a tool's, such as a failed LLM call.
+    pub fn record_error(&self, message: &str) {
+        push_error(&mut self.inner.state.lock().unwrap(), None, message);
+    }
+
+    /// The activity of conversation `id`, with its LLM's `usage` and what it cost.
+    pub fn report(&self, id: &str, usage: TokenUsage, cost_usd: f64) -> ActivityReport {
+        let state = self.inner.state.lock().unwrap();
+        ActivityReport {
+            id: id.to_string(),
+            running: state.running,
+            paused: self.is_paused(),
+            current_tool: state.current_tool.clone(),
+            recent_errors: state.recent_errors.iter().cloned().collect(),
+            usage,
+            cost_usd,
+        }
+    }
+}
+
+fn push_error(state: &mut State, tool_name: Option<&str>, message: &str) {
+    if state.recent_errors.len() == RECENT_ERRORS {
+        state.recent_errors.pop_front();
+    }
+    state.recent_errors.push_back(RecentError {
+        timestamp: Utc::now(),
+        tool_name: tool_name.map(str::to_string),
+        message: message.trim().chars().take(ERROR_CHARS).collect(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{ActionEvent, ObservationEvent};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_pause_and_stop() {
+        let activity = ConversationActivity::default();
+        assert!(!activity.stop());
+        let mut stop = activity.begin_step();
+
+        activity.observe(&Event::Action(ActionEvent {
+            source: "agent".to_string(),
+            tool_name: "cmd".to_string(),
+            tool_call_id: "call_1".to_string(),
+            arguments: serde_json::json!({ "command": "make" }),
+            thought: None,
+            security_risk: None,
+            reasoning: None,
+        }));
+        let report = activity.report("c1", TokenUsage::default(), 0.0);
+        assert!(report.running);
+        assert_eq!(report.current_tool.unwrap().tool_name, "cmd");
+        activity.observe(&Event::Observation(ObservationEvent {
+            source: "runtime".to_string(),
+            tool_name: "cmd".to_string(),
+            tool_call_id: "call_1".to_string(),
+            content: "Error: make: not found".to_string(),
+            file_changes: Vec::new(),
+            untracked_changes: false,
+            attachments: Vec::new(),
+            injection: None,
+        }));
+        let report = activity.report("c1", TokenUsage::default(), 0.0);
+        assert!(report.current_tool.is_none());
+        assert_eq!(report.recent_errors[0].message, "make: not found");
+
+        // A paused conversation waits until it is resumed.
+        activity.pause();
+        let waiting = tokio::spawn({
+            let activity = activity.clone();
+            async move { activity.wait_while_paused().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+        activity.resume();
+        waiting.await.unwrap();
+
+        assert!(activity.stop());
+        tokio::time::timeout(Duration::from_secs(1), stop.requested())
+            .await
+            .unwrap();
+        activity.end_step();
+        assert!(!activity.report("c1", TokenUsage::default(), 0.0).running);
+    }
+}
@@ -0,0 +1,129 @@
+//! How tool results are presented to the model.
+//!
+//! Tools write their results for people, with some prose around them. An
+//! [`OutputFormatter`] rewrites each result as the model sees it, without changing what the
+//! tool did or the observation recorded in the history. [`OutputFormat`] names the built-in
+//! formatters, e.g. to pick one in a config file.
+
+use crate::events::ObservationEvent;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Renders tool results for the model.
+pub trait OutputFormatter: Send + Sync {
+    fn format(&self, observation: &ObservationEvent) -> String;
+}
+
+/// The built-in formatters.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// The results as the tools write them.
+    #[default]
+    Markdown,
+    /// Without the prose around results, and with unpadded line numbers.
+    Plain,
+    /// A JSON object per result with the tool, whether it failed, the plain output and the
+    /// files it changed.
+    Json,
+}
+
+lazy_static::lazy_static! {
+    /// Prose the tools put around results, and what [`OutputFormat::Plain`] keeps of it.
+    static ref PROSE: Vec<(Regex, &'static str)> = vec![
+        (Regex::new(r"(?m)Here's the result of running `cat -n` on (.+):$").unwrap(), "$1:"),
+        (Regex::new(r"The file (.+?) has been edited\. ").unwrap(), "Edited $1. "),
+        (
+            Regex::new(r"\n?Review the changes and make sure they are as expected[^.]*\. Edit the file again if necessary\.").unwrap(),
+            "",
+        ),
+        (Regex::new(r"(?m)^ +(\d+)\t").unwrap(), "$1\t"),
+    ];
+}
+
+impl OutputFormatter for OutputFormat {
+    fn format(&self, observation: &ObservationEvent) -> String {
+        match self {
+            OutputFormat::Markdown => observation.content.clone(),
+            OutputFormat::Plain => plain(&observation.content),
+            OutputFormat::Json => {
+                let files: Vec<&str> = observation
+                    .file_changes
+                    .iter()
+                    .map(|c| c.path.as_str())
+                    .collect();
+                let mut value = serde_json::json!({
+                    "tool": observation.tool_name,
+                    "ok": !observation.content.starts_with("Error"),
+                    "output": plain(&observation.content),
+                });
+                if !files.is_empty() {
+                    value["files_changed"] = serde_json::json!(files);
+                }
+                value.to_string()
+            }
+        }
+    }
+}
+
+fn plain(content: &str) -> String {
+    let content = PROSE
+        .iter()
+        .fold(content.to_string(), |content, (pattern, replacement)| {
+            pattern.replace_all(&content, *replacement).into_owned()
+        });
+    content.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::FileChange;
+
+    fn observation(content: &str) -> ObservationEvent {
+        ObservationEvent {
+            source: "runtime".to_string(),
+            tool_name: "file_editor".to_string(),
+            tool_call_id: "call_1".to_string(),
+            content: content.to_string(),
+            file_changes: Vec::new(),
+            untracked_changes: false,
+            attachments: Vec::new(),
+            injection: None,
+        }
+    }
+
+    #[test]
+    fn test_output_formats() {
+        let view = observation(
+            "Here's the result of running `cat -n` on /w/a.py:\n     1\timport os\n     2\t\n",
+        );
+        assert_eq!(OutputFormat::Markdown.format(&view), view.content);
+        assert_eq!(
+            OutputFormat::Plain.format(&view),
+            "/w/a.py:\n1\timport os\n2"
+        );
+
+        let mut edit = observation(
+            "The file /w/a.py has been edited. Here's the result of running `cat -n` on a snippet of /w/a.py:\n     3\tx = 1\nReview the changes and make sure they are as expected. Edit the file again if necessary.",
+        );
+        edit.file_changes = FileChange::capture("/w/a.py", Some(""), Some("x = 1\n"))
+            .into_iter()
+            .collect();
+        assert_eq!(
+            OutputFormat::Plain.format(&edit),
+            "Edited /w/a.py. a snippet of /w/a.py:\n3\tx = 1"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&OutputFormat::Json.format(&edit)).unwrap();
+        assert_eq!(json["tool"], "file_editor");
+        assert_eq!(json["ok"], true);
+        assert_eq!(json["files_changed"][0], "/w/a.py");
+
+        let failed: serde_json::Value =
+            serde_json::from_str(&OutputFormat::Json.format(&observation("Error: no such file")))
+                .unwrap();
+        assert_eq!(failed["ok"], false);
+        assert!(failed.get("files_changed").is_none());
+    }
+}
@@ -0,0 +1,188 @@
+//! Repairs the messages a history converts to, so providers accept them.
+//!
+//! A history can convert to sequences providers reject with a 400: a tool call whose result
+//! was never recorded (the step was stopped), a result whose call is gone (the history was
+//! trimmed), a result that arrives after another message, or two messages of the same role
+//! in a row, which Anthropic and Gemini refuse. [`normalize`] answers unanswered calls,
+//! turns results without a call into user messages and merges adjacent messages.
+
+use genai::chat::{ChatMessage, ChatRole, ContentPart, MessageContent, ToolResponse};
+
+/// The result given to a tool call that has none in the history.
+pub const INTERRUPTED_RESULT: &str = "The tool call was interrupted before it returned a result.";
+
+/// `messages` with every tool call answered right after the message that made it, no
+/// result without a call, and no two user or assistant messages in a row.
+pub fn normalize(messages: Vec<ChatMessage>) -> Vec<ChatMessage> {
+    let mut normalized: Vec<ChatMessage> = Vec::with_capacity(messages.len());
+    // Calls of the last assistant message that have no result yet.
+    let mut pending: Vec<String> = Vec::new();
+
+    for message in messages {
+        if matches!(message.role, ChatRole::Tool) {
+            let mut orphans = Vec::new();
+            let mut answered = Vec::new();
+            for part in message.content.into_parts() {
+                match part {
+                    ContentPart::ToolResponse(response) => {
+                        match pending.iter().position(|id| *id == response.call_id) {
+                            Some(index) => {
+                                pending.remove(index);
+                                answered.push(ContentPart::ToolResponse(response));
+                            }
+                            None => orphans.push(ContentPart::Text(format!(
+                                "Result of tool call {}:\n{}",
+                                response.call_id, response.content
+                            ))),
+                        }
+                    }
+                    other => answered.push(other),
+                }
+            }
+            if !answered.is_empty() {
+                normalized.push(ChatMessage {
+                    role: ChatRole::Tool,
+                    content: MessageContent::from_parts(answered),
+                    options: message.options,
+                });
+            }
+            if !orphans.is_empty() {
+                answer_pending(&mut normalized, &mut pending);
+                push_merged(
+                    &mut normalized,
+                    ChatMessage {
+                        role: ChatRole::User,
+                        content: MessageContent::from_parts(orphans),
+                        options: None,
+                    },
+                );
+            }
+            continue;
+        }
+
+        answer_pending(&mut normalized, &mut pending);
+        if matches!(message.role, ChatRole::Assistant) {
+            pending.extend(
+                message
+                    .content
+                    .tool_calls()
+                    .into_iter()
+                    .map(|call| call.call_id.clone()),
+            );
+        }
+        push_merged(&mut normalized, message);
+    }
+    answer_pending(&mut normalized, &mut pending);
+    normalized
+}
+
+fn answer_pending(messages: &mut Vec<ChatMessage>, pending: &mut Vec<String>) {
+    for call_id in pending.drain(..) {
+        messages.push(ChatMessage::from(ToolResponse::new(
+            call_id,
+            INTERRUPTED_RESULT.to_string(),
+        )));
+    }
+}
+
+/// Append `message`, or add its parts to the last message if both are user or both are
+/// assistant messages.
+fn push_merged(messages: &mut Vec<ChatMessage>, message: ChatMessage) {
+    if let Some(last) = messages.last_mut()
+        && last.options.is_none()
+        && message.options.is_none()
+        && matches!(
+            (&last.role, &message.role),
+            (ChatRole::User, ChatRole::User) | (ChatRole::Assistant, ChatRole::Assistant)
+        )
+    {
+        for part in message.content.into_parts() {
+            last.content.push(part);
+        }
+        return;
+    }
+    messages.push(message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use genai::chat::ToolCall;
+
+    fn call(id: &str) -> ChatMessage {
+        ChatMessage {
+            role: ChatRole::Assistant,
+            content: MessageContent::from_tool_calls(vec![ToolCall {
+                call_id: id.to_string(),
+                fn_name: "cmd".to_string(),
+                fn_arguments: serde_json::json!({ "command": "ls" }),
+            }]),
+            options: None,
+        }
+    }
+
+    fn result(id: &str) -> ChatMessage {
+        ChatMessage::from(ToolResponse::new(id.to_string(), "a.txt".to_string()))
+    }
+
+    fn roles(messages: &[ChatMessage]) -> Vec<String> {
+        messages.iter().map(|m| m.role.to_string()).collect()
+    }
+
+    #[test]
+    fn test_normalize() {
+        let messages = normalize(vec![
+            ChatMessage::system("Be brief."),
+            ChatMessage::user("List the files"),
+            ChatMessage::user("Only in src/"),
+            // Answered.
+            call("call_1"),
+            result("call_1"),
+            // Interrupted, then answered too late.
+            call("call_2"),
+            ChatMessage::user("Stop"),
+            result("call_2"),
+            // Its call was trimmed.
+            result("call_0"),
+            ChatMessage::assistant("Stopped."),
+            ChatMessage::assistant("Anything else?"),
+            call("call_3"),
+        ]);
+
+        assert_eq!(
+            roles(&messages),
+            [
+                "System",
+                "User",
+                "Assistant",
+                "Tool",
+                "Assistant",
+                "Tool",
+                "User",
+                "Assistant",
+                "Tool"
+            ]
+        );
+        assert_eq!(
+            messages[1].content.texts(),
+            ["List the files", "Only in src/"]
+        );
+        let interrupted = messages[5].content.tool_responses();
+        assert_eq!(interrupted[0].call_id, "call_2");
+        assert_eq!(interrupted[0].content, INTERRUPTED_RESULT);
+        assert_eq!(
+            messages[6].content.texts(),
+            [
+                "Stop",
+                "Result of tool call call_2:\na.txt",
+                "Result of tool call call_0:\na.txt"
+            ]
+        );
+        assert_eq!(messages[7].content.texts(), ["Stopped.", "Anything else?"]);
+        assert_eq!(messages[7].content.tool_calls()[0].call_id, "call_3");
+        assert_eq!(
+            messages[8].content.tool_responses()[0].content,
+            INTERRUPTED_RESULT
+        );
+    }
+}
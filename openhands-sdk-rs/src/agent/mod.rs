@@ -1,242 +1,106 @@
+pub mod format;
+#[cfg(feature = "llm")]
+pub mod history;
+#[cfg(feature = "llm")]
+pub mod middleware;
 pub mod prompts;
+#[cfg(feature = "llm")]
+mod runner;
 pub mod tools;
 
-use self::prompts::SYSTEM_PROMPT;
-use crate::events::{Event, MessageEvent};
-use crate::llm::LLM;
-use crate::runtime::Runtime;
-use colored::*;
-use genai::chat::{ChatMessage, ChatRole, ContentPart, ToolCall, ToolResponse};
-use tracing::{error, info};
+use self::prompts::REFLECTION_PROMPT;
+use crate::events::{ActionEvent, Event, LLMDelta};
+use crate::outcome::FINISH_TOOL;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+#[cfg(feature = "llm")]
+pub use runner::{Agent, AgentBuilder};
+
+/// Source of the message events in which the agent reflects on a failing tool.
+pub const REFLECTION_SOURCE: &str = "reflection";
+
+/// `[agent]`: how the agent runs its steps.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AgentConfig {
+    /// LLM calls a step may make before it ends; 10 by default.
+    pub max_iterations: usize,
+    /// Failures of one tool within a step after which the agent is asked to reflect before
+    /// it may call tools again; 0 turns reflection off. 3 by default.
+    pub reflect_after_failures: usize,
+    /// What the agent is asked when it reflects.
+    pub reflection_prompt: String,
+    /// Stream LLM responses, reporting them to the observer as deltas while they arrive.
+    /// Off by default.
+    pub stream: bool,
+    /// Check tool results for prompt injection and warn the model about suspicious ones; see
+    /// [`crate::injection`]. On by default.
+    pub detect_injection: bool,
+    /// Middleware each LLM call passes through, in order; see
+    /// `agent::middleware`.
+    pub middleware: Vec<MiddlewareConfig>,
+    /// Tools, by name, the agent is not offered; a call to one fails. `finish` cannot be
+    /// disabled.
+    pub disabled_tools: Vec<String>,
+}
 
-pub struct Agent {
-    llm: LLM,
-    system_message: String,
+/// A built-in LLM middleware, as named in [`AgentConfig::middleware`].
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MiddlewareConfig {
+    /// Redact secrets from everything sent to the model.
+    Redact,
+    /// Mark tool results that read like instructions to the agent.
+    InjectionFilter,
+    /// Fail calls once the LLM has used `max_tokens` tokens.
+    TokenBudget { max_tokens: u64 },
+    /// Answer repeated requests from memory, keeping `capacity` responses.
+    Cache { capacity: usize },
+    /// Append the token usage of every call to the JSONL file at `path`.
+    UsageLog { path: PathBuf },
 }
 
-impl Agent {
-    pub fn new(llm: LLM, system_message: String) -> Self {
-        let combined_system = format!("{}\n\n{}", SYSTEM_PROMPT, system_message);
+impl Default for AgentConfig {
+    fn default() -> Self {
         Self {
-            llm,
-            system_message: combined_system,
-        }
-    }
-
-    pub async fn step(
-        &self,
-        history: &[Event],
-        runtime: &mut dyn Runtime,
-    ) -> Result<Event, Box<dyn std::error::Error + Send + Sync>> {
-        let mut messages = vec![ChatMessage::system(self.system_message.clone())];
-
-        for event in history {
-            match event {
-                Event::Message(m) => {
-                    if m.source == "user" {
-                        messages.push(ChatMessage::user(m.content.clone()));
-                    } else {
-                        messages.push(ChatMessage::assistant(m.content.clone()));
-                    }
-                }
-                Event::Action(a) => {
-                    let mut parts = vec![];
-                    if let Some(thought) = &a.thought {
-                        parts.push(ContentPart::Text(thought.clone()));
-                    }
-                    parts.push(ContentPart::ToolCall(ToolCall {
-                        call_id: a.tool_call_id.clone(),
-                        fn_name: a.tool_name.clone(),
-                        fn_arguments: a.arguments.clone(),
-                    }));
-                    messages.push(ChatMessage {
-                        role: ChatRole::Assistant,
-                        content: parts.into(),
-                        options: None,
-                    });
-                }
-                Event::Observation(o) => {
-                    messages.push(ChatMessage::from(ToolResponse::new(
-                        o.tool_call_id.clone(),
-                        o.content.clone(),
-                    )));
-                }
-            }
-        }
-
-        let genai_tools: Vec<genai::chat::Tool> = runtime
-            .tools()
-            .iter()
-            .map(|t| genai::chat::Tool {
-                name: t.name(),
-                description: Some(t.description()),
-                schema: Some(t.parameters()),
-                config: None,
-            })
-            .collect();
-
-        let tools_arg = if genai_tools.is_empty() {
-            None
-        } else {
-            Some(genai_tools)
-        };
-
-        let mut current_messages = messages.clone();
-        let max_iterations = 10;
-
-        for _ in 0..max_iterations {
-            let response = self
-                .llm
-                .completion(current_messages.clone(), tools_arg.clone())
-                .await?;
-
-            if !response.tool_calls.is_empty() {
-                let mut assistant_parts = vec![];
-                if !response.content.is_empty() {
-                    assistant_parts.push(ContentPart::Text(response.content.clone()));
-                }
-
-                for tool_call in &response.tool_calls {
-                    assistant_parts.push(ContentPart::ToolCall(tool_call.clone()));
-                }
-
-                current_messages.push(ChatMessage {
-                    role: ChatRole::Assistant,
-                    content: assistant_parts.into(),
-                    options: None,
-                });
-
-                for tool_call in &response.tool_calls {
-                    let fn_name = &tool_call.fn_name;
-                    let fn_args = tool_call.fn_arguments.clone();
-
-                    info!(
-                        "Agent executing tool: {} with args: {}",
-                        fn_name.cyan(),
-                        fn_args.to_string().dimmed()
-                    );
-
-                    let result = runtime.execute(fn_name, fn_args).await;
-                    let output_content = match result {
-                        Ok(s) => s,
-                        Err(e) => {
-                            let err_msg = format!("Error: {}", e);
-                            error!("{}", err_msg.red());
-                            err_msg
-                        }
-                    };
-
-                    info!(
-                        "Agent tool output ({}): {}",
-                        fn_name.cyan(),
-                        output_content.dimmed()
-                    );
-
-                    current_messages.push(ChatMessage::from(ToolResponse::new(
-                        tool_call.call_id.clone(),
-                        output_content,
-                    )));
-                }
-            } else {
-                return Ok(Event::Message(MessageEvent {
-                    source: "agent".to_string(),
-                    content: response.content,
-                }));
-            }
+            max_iterations: 10,
+            reflect_after_failures: 3,
+            reflection_prompt: REFLECTION_PROMPT.to_string(),
+            stream: false,
+            detect_injection: true,
+            middleware: Vec::new(),
+            disabled_tools: Vec::new(),
         }
-
-        Err("Max iterations reached".into())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::llm::LLMConfig;
-
-    #[tokio::test]
-    async fn test_agent_step() {
-        dotenv::dotenv().ok();
-        let api_key = std::env::var("OPENAI_API_KEY").ok();
-        if api_key.is_none() {
-            println!("Skipping test_agent_step because OPENAI_API_KEY is not set");
-            return;
-        }
-
-        let config = LLMConfig {
-            model: "gpt-5-nano".to_string(),
-            api_key,
-            reasoning_effort: Some("minimal".to_string()),
-        };
-        let llm = LLM::new(config);
-        let agent = Agent::new(llm, "You are a helpful assistant.".to_string());
-
-        // Runtime
-        use crate::runtime::LocalRuntime;
-        let mut runtime = LocalRuntime::new(vec![]);
-
-        let history = vec![Event::Message(MessageEvent {
-            source: "user".to_string(),
-            content: "Hello".to_string(),
-        })];
-
-        let event = agent
-            .step(&history, &mut runtime)
-            .await
-            .expect("Step failed");
-
-        if let Event::Message(m) = event {
-            assert_eq!(m.source, "agent");
-            assert!(!m.content.is_empty());
-            println!("Agent Response: {}", m.content);
-        } else {
-            panic!("Expected MessageEvent");
-        }
+impl AgentConfig {
+    /// Whether the agent may use the tool called `name`.
+    pub fn tool_enabled(&self, name: &str) -> bool {
+        name == FINISH_TOOL || !self.disabled_tools.iter().any(|tool| tool == name)
     }
+}
 
-    #[tokio::test]
-    async fn test_agent_tool_loop() {
-        dotenv::dotenv().ok();
-        let api_key = std::env::var("OPENAI_API_KEY").ok();
-        if api_key.is_none() {
-            return;
-        }
-
-        let config = LLMConfig {
-            model: "gpt-5-nano".to_string(),
-            api_key,
-            reasoning_effort: Some("minimal".to_string()),
-        };
-        let llm = LLM::new(config);
-        let agent = Agent::new(
-            llm,
-            "You are a helpful assistant that can execute commands.".to_string(),
-        );
-
-        // Runtime with CmdTool
-        use crate::agent::tools::CmdTool;
-        use crate::runtime::LocalRuntime;
-        let mut runtime = LocalRuntime::new(vec![Box::new(CmdTool)]);
-
-        // Request that requires tool execution
-        let history = vec![Event::Message(MessageEvent {
-            source: "user".to_string(),
-            content: "Execute 'echo hello_world' using the cmd tool.".to_string(),
-        })];
-
-        let event = agent
-            .step(&history, &mut runtime)
-            .await
-            .expect("Step failed");
-
-        if let Event::Message(m) = event {
-            println!("Agent Tool Response: {}", m.content);
-            assert!(
-                m.content.contains("hello_world") || m.content.contains("executed"),
-                "Response should mention the output or action"
-            );
-        } else {
-            panic!("Expected final MessageEvent");
-        }
+/// Follows a step while it runs, e.g. to stream it to a client or ask a user before tools
+/// run. `()` observes nothing and approves everything.
+#[async_trait]
+pub trait StepObserver: Send + Sync {
+    /// Called with each event as soon as it is produced.
+    async fn on_event(&self, _event: &Event) {}
+
+    /// Called with each part of an LLM response while it streams in, if
+    /// [`AgentConfig::stream`] is on. Runs on the task reading the stream, so it must not
+    /// block.
+    fn on_delta(&self, _delta: &LLMDelta) {}
+
+    /// Whether an action may run. Called after [`StepObserver::on_event`] reported it; a
+    /// denied action is answered with an error observation carrying the reason instead of
+    /// running.
+    async fn approve(&self, _action: &ActionEvent) -> Result<(), String> {
+        Ok(())
     }
 }
+
+impl StepObserver for () {}
@@ -1,16 +1,65 @@
 mod apply_patch;
+mod cwd;
 mod file_editor;
+mod finish;
+#[cfg(feature = "http")]
+mod github;
 mod glob;
 mod grep;
+mod memory;
+mod scratchpad;
+mod semantic_search;
+mod terminal;
 
-pub use apply_patch::ApplyPatchTool;
+pub use apply_patch::{ApplyPatchTool, apply_patch_to_content};
+pub use cwd::WorkingDir;
 pub use file_editor::FileEditorTool;
+pub use finish::FinishTool;
+#[cfg(feature = "http")]
+pub use github::GitHubTool;
 pub use glob::GlobTool;
 pub use grep::GrepTool;
+pub use memory::{RecallTool, RememberTool};
+pub use scratchpad::{ScratchpadTool, remove_scratchpad, scratchpad_root};
+pub use semantic_search::SemanticSearchTool;
+pub use terminal::TerminalTool;
 
+use crate::events::Attachment;
+use crate::hints::hint_for;
+use crate::journal::FileMutationJournal;
+use crate::runtime::env::ToolEnv;
+use crate::shell_output;
 use async_trait::async_trait;
 use serde_json::Value;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+
+/// What a tool call produced: text for the model, plus media such as screenshots.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolOutput {
+    pub content: String,
+    pub attachments: Vec<Attachment>,
+}
+
+impl From<String> for ToolOutput {
+    fn from(content: String) -> Self {
+        Self {
+            content,
+            attachments: Vec::new(),
+        }
+    }
+}
+
+/// MIME type of image files the model can look at, by extension.
+pub fn image_mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
 
 #[async_trait]
 pub trait Tool: Send + Sync {
@@ -18,9 +67,110 @@ pub trait Tool: Send + Sync {
     fn description(&self) -> String;
     fn parameters(&self) -> Value; // JSON Schema
     async fn call(&self, args: Value) -> Result<String, String>;
+
+    /// Like [`Tool::call`], for tools that can also return media. The agent calls this one.
+    async fn call_with_attachments(&self, args: Value) -> Result<ToolOutput, String> {
+        self.call(args).await.map(ToolOutput::from)
+    }
+
+    /// Files that calling the tool with `args` may modify.
+    ///
+    /// The agent snapshots these before and after the call to record `FileChange`s.
+    /// Read-only tools keep the default.
+    fn affected_paths(&self, _args: &Value) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Whether calling the tool with `args` may change workspace files that
+    /// [`Tool::affected_paths`] does not name, e.g. through a shell command. Replay cannot
+    /// reproduce such calls.
+    fn has_untracked_changes(&self, args: &Value) -> bool {
+        !self.is_read_only(args) && self.affected_paths(args).is_empty()
+    }
+
+    /// Whether calling the tool with `args` leaves the workspace and the outside world as
+    /// they are. Dry runs (see [`crate::runtime::dry_run`]) execute these calls and only
+    /// propose the others.
+    fn is_read_only(&self, _args: &Value) -> bool {
+        false
+    }
+
+    /// Whether sandboxed runtimes call the tool in this process instead of inside the
+    /// sandbox, for tools that talk to external services rather than the workspace.
+    fn runs_on_host(&self) -> bool {
+        false
+    }
 }
 
-pub struct CmdTool;
+/// The standard coding tool set, with file tools rooted at `working_dir`.
+/// `tools` as the tool definitions of a completion request.
+#[cfg(feature = "llm")]
+pub fn genai_tools(tools: &[Box<dyn Tool>]) -> Vec<genai::chat::Tool> {
+    tools
+        .iter()
+        .map(|t| genai::chat::Tool {
+            name: t.name(),
+            description: Some(t.description()),
+            schema: Some(t.parameters()),
+            config: None,
+        })
+        .collect()
+}
+
+pub fn default_tools(working_dir: PathBuf) -> Vec<Box<dyn Tool>> {
+    default_tools_with_env(
+        working_dir,
+        ToolEnv::default(),
+        FileMutationJournal::default(),
+    )
+}
+
+/// [`default_tools`] whose commands run with `env` and whose file tools record their
+/// changes in `journal`.
+pub fn default_tools_with_env(
+    working_dir: PathBuf,
+    env: ToolEnv,
+    journal: FileMutationJournal,
+) -> Vec<Box<dyn Tool>> {
+    // One working directory for the shell and the file tools, moved by `cd`.
+    let cwd = WorkingDir::new(working_dir.clone());
+    vec![
+        Box::new(CmdTool::new(env.clone()).with_cwd(cwd.clone())),
+        Box::new(
+            FileEditorTool::new(working_dir.clone())
+                .with_journal(journal.clone())
+                .with_cwd(cwd.clone()),
+        ),
+        Box::new(ApplyPatchTool::new(working_dir.clone()).with_journal(journal)),
+        Box::new(GlobTool::new(working_dir.clone()).with_cwd(cwd.clone())),
+        Box::new(GrepTool::new(working_dir.clone()).with_cwd(cwd.clone())),
+        Box::new(TerminalTool::new(working_dir).with_env(env).with_cwd(cwd)),
+    ]
+}
+
+/// The variable naming the file a `cmd` command's shell writes its final directory to.
+const PWD_FILE_VAR: &str = "__OPENHANDS_PWD_FILE";
+
+/// Runs commands with the environment and network policy of its [`ToolEnv`].
+#[derive(Default)]
+pub struct CmdTool {
+    env: ToolEnv,
+    /// Where commands start, moved by their `cd`s; the process's directory without it.
+    cwd: Option<WorkingDir>,
+}
+
+impl CmdTool {
+    pub fn new(env: ToolEnv) -> Self {
+        Self { env, cwd: None }
+    }
+
+    /// Start commands in the current directory of `cwd`, and move it to where each
+    /// command ends, as if they ran in one shell.
+    pub fn with_cwd(mut self, cwd: WorkingDir) -> Self {
+        self.cwd = Some(cwd);
+        self
+    }
+}
 
 #[async_trait]
 impl Tool for CmdTool {
@@ -29,7 +179,15 @@ impl Tool for CmdTool {
     }
 
     fn description(&self) -> String {
-        "Execute a shell command (bash)".to_string()
+        match &self.cwd {
+            Some(cwd) => format!(
+                "Execute a shell command (bash). Commands run in the current working directory, \
+                now {}; a `cd` in a command changes it for the following commands and for \
+                relative paths of the file tools.",
+                cwd.get().display()
+            ),
+            None => "Execute a shell command (bash)".to_string(),
+        }
     }
 
     fn parameters(&self) -> Value {
@@ -53,21 +211,65 @@ impl Tool for CmdTool {
 
         // Simple std::process implementation for now.
         // In real agent this might call BashEventService or unsafe shell.
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(command)
-            .output()
-            .map_err(|e| e.to_string())?;
+        let mut process = self.env.command("bash");
+        let pwd_file = match &self.cwd {
+            Some(cwd) => {
+                // The shell writes where it ended up when it exits.
+                let pwd_file = tempfile::NamedTempFile::new().map_err(|e| e.to_string())?;
+                process
+                    .current_dir(cwd.get())
+                    .env(PWD_FILE_VAR, pwd_file.path())
+                    .arg("-c")
+                    .arg(format!(
+                        "trap 'pwd > \"${}\"' EXIT\n{}",
+                        PWD_FILE_VAR, command
+                    ));
+                Some(pwd_file)
+            }
+            None => {
+                process.arg("-c").arg(command);
+                None
+            }
+        };
+        let output = process.output().map_err(|e| e.to_string())?;
+        if let (Some(cwd), Some(pwd_file)) = (&self.cwd, pwd_file) {
+            let pwd = std::fs::read_to_string(pwd_file.path()).unwrap_or_default();
+            if !pwd.trim().is_empty() {
+                let _ = cwd.set(pwd.trim_end_matches('\n'));
+            }
+        }
 
         let stdout = String::from_utf8_lossy(&output.stdout);
         let stderr = String::from_utf8_lossy(&output.stderr);
+        let (stdout, stderr) = (shell_output::clean(&stdout), shell_output::clean(&stderr));
 
-        if !stderr.is_empty() {
-            Ok(format!("STDOUT:\n{}\nSTDERR:\n{}", stdout, stderr))
+        let mut result = if !stderr.is_empty() {
+            format!("STDOUT:\n{}\nSTDERR:\n{}", stdout, stderr)
         } else {
-            Ok(stdout.to_string())
+            stdout.to_string()
+        };
+        if !output.status.success() {
+            let exit_code = exit_code(&output.status);
+            result.push_str(&format!("\n[Command failed with exit code {}]", exit_code));
+            if let Some(hint) = hint_for(Some(exit_code), &result) {
+                result.push_str(&format!("\n{}", hint));
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// The exit code a shell would report: the process's own, or 128 plus the signal that
+/// killed it.
+fn exit_code(status: &std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
         }
     }
+    status.code().unwrap_or(-1)
 }
 
 pub struct FileReadTool;
@@ -95,6 +297,10 @@ impl Tool for FileReadTool {
         })
     }
 
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
     async fn call(&self, args: Value) -> Result<String, String> {
         let path = args
             .get("path")
@@ -107,7 +313,18 @@ impl Tool for FileReadTool {
     }
 }
 
-pub struct FileWriteTool;
+#[derive(Default)]
+pub struct FileWriteTool {
+    journal: FileMutationJournal,
+}
+
+impl FileWriteTool {
+    /// Record writes in `journal`, so editors sharing it can undo them.
+    pub fn with_journal(mut self, journal: FileMutationJournal) -> Self {
+        self.journal = journal;
+        self
+    }
+}
 
 #[async_trait]
 impl Tool for FileWriteTool {
@@ -147,9 +364,17 @@ impl Tool for FileWriteTool {
             .and_then(|v| v.as_str())
             .ok_or("Missing 'content' argument")?;
 
+        self.journal.record(&self.name(), Path::new(path));
         tokio::fs::write(path, content)
             .await
             .map_err(|e| e.to_string())?;
         Ok(format!("Successfully wrote to {}", path))
     }
+
+    fn affected_paths(&self, args: &Value) -> Vec<PathBuf> {
+        args.get("path")
+            .and_then(|v| v.as_str())
+            .map(|p| vec![PathBuf::from(p)])
+            .unwrap_or_default()
+    }
 }
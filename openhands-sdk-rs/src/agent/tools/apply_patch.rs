@@ -1,230 +1,622 @@
 use async_trait::async_trait;
 use serde_json::Value;
-use std::path::PathBuf;
+use similar::{Algorithm, DiffOp, capture_diff_slices};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
 
 use super::Tool;
+use crate::journal::FileMutationJournal;
+use crate::paths::{Walk, encode_path};
 
 pub struct ApplyPatchTool {
     working_dir: PathBuf,
+    three_way_merge: bool,
+    git_mv: bool,
+    journal: FileMutationJournal,
+    /// Each file as this tool last wrote it: the version the model's next patch of it is
+    /// most likely written against.
+    bases: Mutex<HashMap<PathBuf, String>>,
 }
 
 impl ApplyPatchTool {
     pub fn new(working_dir: PathBuf) -> Self {
-        Self { working_dir }
+        Self {
+            working_dir,
+            three_way_merge: true,
+            git_mv: true,
+            journal: FileMutationJournal::default(),
+            bases: Mutex::new(HashMap::new()),
+        }
     }
 
-    fn parse_patch(&self, patch_text: &str) -> Result<Vec<FilePatch>, String> {
-        let lines: Vec<&str> = patch_text.lines().collect();
-        let mut patches = Vec::new();
-        let mut i = 0;
+    /// Whether a patch whose hunks no longer match a file it wrote earlier is applied to
+    /// that earlier version and merged with the changes made since (the default). Without
+    /// it such patches fail.
+    pub fn with_three_way_merge(mut self, enabled: bool) -> Self {
+        self.three_way_merge = enabled;
+        self
+    }
 
-        // Find "*** Begin Patch" marker
-        while i < lines.len() {
-            if lines[i].trim() == "*** Begin Patch" {
-                i += 1;
-                break;
+    /// Record changes in `journal`, so editors sharing it can undo them.
+    pub fn with_journal(mut self, journal: FileMutationJournal) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Whether files tracked by git are moved with `git mv` (the default), so the rename is
+    /// staged, rather than renamed on disk only.
+    pub fn with_git_mv(mut self, enabled: bool) -> Self {
+        self.git_mv = enabled;
+        self
+    }
+
+    /// The new content of the file `file_patch` changes, given its current `content`, and
+    /// whether it had to be merged.
+    fn patched_content(
+        &self,
+        file_patch: &FilePatch,
+        file_path: &Path,
+        content: &str,
+    ) -> Result<(String, bool), String> {
+        let error = match apply_hunks(content, &file_patch.hunks) {
+            Ok(new_content) => return Ok((new_content, false)),
+            Err(e) => format!("'{}': {}", file_patch.new_path, e),
+        };
+        let base = match self.bases.lock().unwrap().get(file_path) {
+            Some(base) if self.three_way_merge && base != content => base.clone(),
+            _ => return Err(error),
+        };
+        let Ok(patched_base) = apply_hunks(&base, &file_patch.hunks) else {
+            return Err(error);
+        };
+
+        let merge = merge3(&base, content, &patched_base);
+        if !merge.conflicts.is_empty() {
+            return Err(format!(
+                "'{}' changed since this tool last wrote it, and {} of the changes conflict \
+                with the patch. No files were changed. The conflicting regions, as current \
+                content against patched content:\n{}",
+                file_patch.new_path,
+                merge.conflicts.len(),
+                merge.conflicts.join("\n")
+            ));
+        }
+        Ok((merge.content, true))
+    }
+
+    /// The error for patches that update files that do not exist, with the existing files
+    /// whose path differs only in case, directory or extension.
+    fn missing_files_error(&self, missing: &[&str]) -> String {
+        let mut candidates: Vec<Vec<(u8, String)>> = vec![Vec::new(); missing.len()];
+        let mut visited = 0;
+        Walk::new().run(&self.working_dir, |entry| {
+            visited += 1;
+            if !entry.is_dir {
+                let existing = encode_path(&entry.relative).to_string();
+                for (path, candidates) in missing.iter().zip(candidates.iter_mut()) {
+                    if let Some(rank) = near_miss(path, &existing) {
+                        candidates.push((rank, existing.clone()));
+                    }
+                }
+            }
+            visited < MAX_SUGGESTION_WALK
+        });
+
+        let mut message = format!("{} file(s) to update do not exist:", missing.len());
+        for (path, mut candidates) in missing.iter().zip(candidates) {
+            candidates.sort();
+            let suggestions: Vec<String> = candidates
+                .into_iter()
+                .take(MAX_SUGGESTIONS)
+                .map(|(_, path)| path)
+                .collect();
+            if suggestions.is_empty() {
+                message.push_str(&format!("\n- {}", path));
+            } else {
+                message.push_str(&format!(
+                    "\n- {} (did you mean: {}?)",
+                    path,
+                    suggestions.join(", ")
+                ));
             }
-            i += 1;
         }
+        message.push_str(
+            "\nNo files were changed. Fix the paths, or set create_missing to create files \
+            whose hunks only add lines.",
+        );
+        message
+    }
+}
+
+/// Suggestions offered for each missing file.
+const MAX_SUGGESTIONS: usize = 3;
+/// Workspace entries searched for suggestions, so a huge workspace cannot stall the error.
+const MAX_SUGGESTION_WALK: usize = 20_000;
+
+/// How close `existing` is to the `missing` path, lower being closer: the same path in
+/// another case, the same file name in another directory, then the same name with another
+/// extension. `None` if it is none of those.
+fn near_miss(missing: &str, existing: &str) -> Option<u8> {
+    let missing = Path::new(missing.trim_start_matches("./"));
+    let existing = Path::new(existing);
+    let lower = |s: Option<&OsStr>| s.map(|s| s.to_string_lossy().to_lowercase());
+
+    if lower(Some(missing.as_os_str())) == lower(Some(existing.as_os_str())) {
+        return Some(0);
+    }
+    if lower(missing.file_name()) == lower(existing.file_name()) {
+        return Some(1);
+    }
+    if lower(missing.file_stem()) == lower(existing.file_stem()) {
+        let same_dir = lower(missing.parent().map(Path::as_os_str))
+            == lower(existing.parent().map(Path::as_os_str));
+        return Some(if same_dir { 2 } else { 3 });
+    }
+    None
+}
+
+/// Apply a patch that changes a single file to that file's `content`, without touching the
+/// disk. The result ends with a newline if `content` did, or if the file is new.
+pub fn apply_patch_to_content(patch_text: &str, content: &str) -> Result<String, String> {
+    match parse_patch(patch_text)?.as_slice() {
+        [file_patch] => apply_hunks(content, &file_patch.hunks),
+        patches => Err(format!(
+            "Expected a patch of one file, found {}",
+            patches.len()
+        )),
+    }
+}
 
-        if i == 0 || i >= lines.len() {
-            return Err("Patch must start with '*** Begin Patch'".to_string());
+fn parse_patch(patch_text: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = patch_text.lines().collect();
+    let mut patches = Vec::new();
+    let mut i = 0;
+
+    // Find "*** Begin Patch" marker
+    while i < lines.len() {
+        if lines[i].trim() == "*** Begin Patch" {
+            i += 1;
+            break;
         }
+        i += 1;
+    }
 
-        // Parse file patches until "*** End Patch"
-        while i < lines.len() {
-            if lines[i].trim() == "*** End Patch" {
-                break;
-            }
+    if i == 0 || i >= lines.len() {
+        return Err("Patch must start with '*** Begin Patch'".to_string());
+    }
 
-            // Look for file header (e.g., "--- a/path/to/file.txt")
-            if lines[i].starts_with("--- ") {
-                let old_file = lines[i]
-                    .strip_prefix("--- ")
-                    .and_then(|s| s.strip_prefix("a/"))
-                    .unwrap_or(&lines[i][4..])
-                    .trim();
+    // Parse file patches until "*** End Patch"
+    while i < lines.len() {
+        if lines[i].trim() == "*** End Patch" {
+            break;
+        }
 
-                i += 1;
-                if i >= lines.len() || !lines[i].starts_with("+++ ") {
-                    return Err("Expected '+++ ' line after '--- ' line".to_string());
-                }
+        // Look for file header (e.g., "--- a/path/to/file.txt")
+        if lines[i].starts_with("--- ") {
+            let old_file = lines[i]
+                .strip_prefix("--- ")
+                .and_then(|s| s.strip_prefix("a/"))
+                .unwrap_or(&lines[i][4..])
+                .trim();
 
-                let new_file = lines[i]
-                    .strip_prefix("+++ ")
-                    .and_then(|s| s.strip_prefix("b/"))
-                    .unwrap_or(&lines[i][4..])
-                    .trim();
+            i += 1;
+            if i >= lines.len() || !lines[i].starts_with("+++ ") {
+                return Err("Expected '+++ ' line after '--- ' line".to_string());
+            }
 
-                i += 1;
+            let mut new_file = lines[i]
+                .strip_prefix("+++ ")
+                .and_then(|s| s.strip_prefix("b/"))
+                .unwrap_or(&lines[i][4..])
+                .trim();
 
-                // Parse hunks for this file
-                let mut hunks = Vec::new();
-                while i < lines.len()
-                    && !lines[i].starts_with("---")
-                    && lines[i].trim() != "*** End Patch"
-                {
-                    if lines[i].starts_with("@@ ") {
-                        // Parse hunk header
-                        let hunk_start = i;
-                        i += 1;
+            i += 1;
 
-                        // Collect hunk lines
-                        let mut hunk_lines = Vec::new();
-                        while i < lines.len()
-                            && !lines[i].starts_with("@@")
-                            && !lines[i].starts_with("---")
-                            && lines[i].trim() != "*** End Patch"
-                        {
-                            hunk_lines.push(lines[i].to_string());
-                            i += 1;
-                        }
-
-                        hunks.push(Hunk {
-                            header: lines[hunk_start].to_string(),
-                            lines: hunk_lines,
-                        });
-                    } else {
+            // Parse hunks for this file
+            let mut hunks = Vec::new();
+            while i < lines.len()
+                && !lines[i].starts_with("---")
+                && lines[i].trim() != "*** End Patch"
+            {
+                if lines[i].starts_with("@@ ") {
+                    // Parse hunk header
+                    let hunk_start = i;
+                    i += 1;
+
+                    // Collect hunk lines
+                    let mut hunk_lines = Vec::new();
+                    while i < lines.len()
+                        && !lines[i].starts_with("@@")
+                        && !lines[i].starts_with("---")
+                        && lines[i].trim() != "*** End Patch"
+                    {
+                        hunk_lines.push(lines[i].to_string());
                         i += 1;
                     }
-                }
 
-                patches.push(FilePatch {
-                    old_path: old_file.to_string(),
-                    new_path: new_file.to_string(),
-                    hunks,
-                });
-            } else {
-                i += 1;
+                    hunks.push(Hunk {
+                        header: lines[hunk_start].to_string(),
+                        lines: hunk_lines,
+                    });
+                } else if let Some(target) = lines[i].strip_prefix("*** Move to:") {
+                    // The other patch format names the new path of a moved file this way.
+                    new_file = target.trim();
+                    i += 1;
+                } else {
+                    i += 1;
+                }
             }
-        }
 
-        if patches.is_empty() {
-            return Err("No valid patches found in input".to_string());
+            patches.push(FilePatch {
+                old_path: old_file.to_string(),
+                new_path: new_file.to_string(),
+                hunks,
+            });
+        } else {
+            i += 1;
         }
+    }
 
-        Ok(patches)
+    if patches.is_empty() {
+        return Err("No valid patches found in input".to_string());
     }
 
-    fn apply_file_patch(&self, file_patch: &FilePatch) -> Result<String, String> {
-        let file_path = self.working_dir.join(&file_patch.new_path);
+    Ok(patches)
+}
 
-        // Read existing file or start with empty content
-        let original_content = if file_path.exists() {
-            std::fs::read_to_string(&file_path)
-                .map_err(|e| format!("Failed to read '{}': {}", file_patch.new_path, e))?
-        } else {
-            String::new()
-        };
+fn apply_hunks(content: &str, hunks: &[Hunk]) -> Result<String, String> {
+    let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+
+    // Hunk headers count lines of the original file, so later hunks are shifted by the
+    // lines earlier ones added or removed, and by how far earlier ones had moved.
+    let mut offset = 0;
+    // Where the previous hunk ended, in the original file and in `lines`.
+    let (mut old_end, mut end) = (0, 0);
+    for (i, hunk) in hunks.iter().enumerate() {
+        let (start, expected_lines, new_lines) = hunk_lines(hunk)?;
+        if start < old_end {
+            return Err(format!(
+                "Hunk {} ({}) overlaps the hunk before it",
+                i + 1,
+                hunk.header
+            ));
+        }
+        let at =
+            locate(&lines, &expected_lines, start as isize + offset, end).ok_or_else(|| {
+                format!(
+                    "Hunk {} ({}) does not match file content: its context and removed lines \
+                    were not found after the previous hunk",
+                    i + 1,
+                    hunk.header
+                )
+            })?;
+
+        old_end = start + expected_lines.len();
+        end = at + new_lines.len();
+        offset = end as isize - old_end as isize;
+        lines.splice(at..at + expected_lines.len(), new_lines);
+    }
 
-        let mut lines: Vec<String> = original_content.lines().map(|s| s.to_string()).collect();
+    let mut new_content = lines.join("\n");
+    if !lines.is_empty() && (content.is_empty() || content.ends_with('\n')) {
+        new_content.push('\n');
+    }
+    Ok(new_content)
+}
 
-        // Apply each hunk
-        for hunk in &file_patch.hunks {
-            self.apply_hunk(&mut lines, hunk)?;
-        }
+/// Where `hunk` starts in the original file, the lines it expects there, and the lines
+/// that replace them.
+fn hunk_lines(hunk: &Hunk) -> Result<(usize, Vec<String>, Vec<String>), String> {
+    let (old_start, old_count) = old_range(&hunk.header)?;
 
-        // Write modified content
-        let new_content = lines.join("\n");
-        if let Some(parent) = file_path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create directory: {}", e))?;
-        }
+    // Build expected and new content from hunk
+    let mut expected_lines = Vec::new();
+    let mut new_lines = Vec::new();
 
-        std::fs::write(&file_path, &new_content)
-            .map_err(|e| format!("Failed to write '{}': {}", file_patch.new_path, e))?;
+    for line in &hunk.lines {
+        let mut chars = line.chars();
+        let Some(first_char) = chars.next() else {
+            continue;
+        };
+        let content = chars.as_str();
 
-        Ok(format!("Applied patch to '{}'", file_patch.new_path))
+        match first_char {
+            '-' => {
+                expected_lines.push(content.to_string());
+            }
+            '+' => {
+                new_lines.push(content.to_string());
+            }
+            ' ' => {
+                expected_lines.push(content.to_string());
+                new_lines.push(content.to_string());
+            }
+            _ => {}
+        }
     }
 
-    fn apply_hunk(&self, lines: &mut Vec<String>, hunk: &Hunk) -> Result<(), String> {
-        // Parse hunk header to get line numbers
-        // Format: @@ -old_start,old_count +new_start,new_count @@
-        let header_parts: Vec<&str> = hunk.header.split_whitespace().collect();
-        if header_parts.len() < 3 {
-            return Err(format!("Invalid hunk header: {}", hunk.header));
-        }
+    // `-5,0` inserts after line 5, otherwise the range starts at the line itself.
+    let start = if old_count == 0 {
+        old_start
+    } else {
+        old_start.saturating_sub(1)
+    };
+    Ok((start, expected_lines, new_lines))
+}
 
-        let old_range = header_parts[1].trim_start_matches('-');
-        let old_start: usize = old_range
-            .split(',')
-            .next()
-            .and_then(|s| s.parse().ok())
-            .ok_or("Invalid old line number")?;
+/// The start line and line count of the original file in a hunk header.
+fn old_range(header: &str) -> Result<(usize, usize), String> {
+    // Format: @@ -old_start,old_count +new_start,new_count @@
+    let header_parts: Vec<&str> = header.split_whitespace().collect();
+    if header_parts.len() < 3 {
+        return Err(format!("Invalid hunk header: {}", header));
+    }
 
-        // Build expected and new content from hunk
-        let mut expected_lines = Vec::new();
-        let mut new_lines = Vec::new();
+    let mut old_range = header_parts[1].trim_start_matches('-').split(',');
+    let old_start: usize = old_range
+        .next()
+        .and_then(|s| s.parse().ok())
+        .ok_or("Invalid old line number")?;
+    let old_count: usize = match old_range.next() {
+        Some(count) => count.parse().map_err(|_| "Invalid old line count")?,
+        None => 1,
+    };
+    Ok((old_start, old_count))
+}
 
-        for line in &hunk.lines {
-            if line.is_empty() {
-                continue;
-            }
+/// The index from `min` on where `lines` holds `expected`, ignoring surrounding whitespace,
+/// closest to `want`. Every line is checked, so a hunk is never spliced over content it
+/// does not describe.
+fn locate(lines: &[String], expected: &[String], want: isize, min: usize) -> Option<usize> {
+    let last = lines.len().checked_sub(expected.len())?;
+    if min > last {
+        return None;
+    }
+    let want = want.clamp(min as isize, last as isize) as usize;
+    let matches_at = |at: usize| {
+        expected
+            .iter()
+            .zip(&lines[at..])
+            .all(|(exp, act)| exp.trim() == act.trim())
+    };
+    (0..=(want - min).max(last - want))
+        .flat_map(|distance| [want.checked_sub(distance), Some(want + distance)])
+        .flatten()
+        .filter(|&at| (min..=last).contains(&at))
+        .find(|&at| matches_at(at))
+}
 
-            let first_char = line.chars().next().unwrap();
-            let content = if line.len() > 1 { &line[1..] } else { "" };
+/// The result of a three-way merge.
+struct Merge {
+    /// The merged text, with conflict markers around each conflict.
+    content: String,
+    /// Each conflict with its markers.
+    conflicts: Vec<String>,
+}
 
-            match first_char {
-                '-' => {
-                    expected_lines.push(content.to_string());
-                }
-                '+' => {
-                    new_lines.push(content.to_string());
-                }
-                ' ' => {
-                    expected_lines.push(content.to_string());
-                    new_lines.push(content.to_string());
-                }
-                _ => {}
+/// Merge the changes from `base` to `ours` with those from `base` to `theirs`, line by line.
+/// Changes to the same lines of `base` that differ conflict, like in `git merge`.
+fn merge3(base: &str, ours: &str, theirs: &str) -> Merge {
+    let base_lines: Vec<&str> = base.lines().collect();
+    let our_lines: Vec<&str> = ours.lines().collect();
+    let their_lines: Vec<&str> = theirs.lines().collect();
+    let our_changes = changed_regions(&base_lines, &our_lines);
+    let their_changes = changed_regions(&base_lines, &their_lines);
+
+    let mut lines: Vec<&str> = Vec::new();
+    let mut conflicts = Vec::new();
+    let (mut i, mut j, mut pos) = (0, 0, 0);
+    while i < our_changes.len() || j < their_changes.len() {
+        // The next group of changes that touch overlapping lines of the base.
+        let start = match (our_changes.get(i), their_changes.get(j)) {
+            (Some(a), Some(b)) => a.0.start.min(b.0.start),
+            (Some(a), None) => a.0.start,
+            (None, Some(b)) => b.0.start,
+            (None, None) => break,
+        };
+        let mut end = start;
+        let (first_ours, first_theirs) = (i, j);
+        loop {
+            let overlaps = |region: Option<&(Range<usize>, Range<usize>)>| {
+                region.is_some_and(|(old, _)| old.start < end || old.start == start)
+            };
+            if overlaps(our_changes.get(i)) {
+                end = end.max(our_changes[i].0.end);
+                i += 1;
+            } else if overlaps(their_changes.get(j)) {
+                end = end.max(their_changes[j].0.end);
+                j += 1;
+            } else {
+                break;
             }
         }
 
-        // Find matching location (with fuzzy matching)
-        let start_idx = old_start.saturating_sub(1);
-        let end_idx = (start_idx + expected_lines.len()).min(lines.len());
-
-        // Check if lines match
-        let actual_lines: Vec<String> = if start_idx < lines.len() {
-            lines[start_idx..end_idx].to_vec()
+        lines.extend(&base_lines[pos..start]);
+        let mine = apply_regions(
+            &base_lines,
+            start..end,
+            &our_changes[first_ours..i],
+            &our_lines,
+        );
+        let patched = apply_regions(
+            &base_lines,
+            start..end,
+            &their_changes[first_theirs..j],
+            &their_lines,
+        );
+        if first_ours == i || mine == patched {
+            lines.extend(&patched);
+        } else if first_theirs == j {
+            lines.extend(&mine);
         } else {
-            Vec::new()
-        };
-
-        // Simple fuzzy matching: allow if at least 70% of lines match
-        let matching_lines = expected_lines
-            .iter()
-            .zip(actual_lines.iter())
-            .filter(|(exp, act)| exp.trim() == act.trim())
-            .count();
+            let mut conflict = vec!["<<<<<<< current"];
+            conflict.extend(&mine);
+            conflict.push("=======");
+            conflict.extend(&patched);
+            conflict.push(">>>>>>> patch");
+            conflicts.push(conflict.join("\n"));
+            lines.extend(conflict);
+        }
+        pos = end;
+    }
+    lines.extend(&base_lines[pos..]);
 
-        let match_ratio = if expected_lines.is_empty() {
-            1.0
-        } else {
-            matching_lines as f64 / expected_lines.len() as f64
-        };
+    let mut content = lines.join("\n");
+    if !lines.is_empty() && (ours.is_empty() || ours.ends_with('\n')) {
+        content.push('\n');
+    }
+    Merge { content, conflicts }
+}
 
-        if match_ratio < 0.7 {
-            return Err(format!(
-                "Hunk does not match file content ({}% match)",
-                (match_ratio * 100.0) as usize
-            ));
+/// The ranges of `old` that differ in `new`, with what replaces them.
+fn changed_regions(old: &[&str], new: &[&str]) -> Vec<(Range<usize>, Range<usize>)> {
+    let mut regions: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, old, new) {
+        if matches!(op, DiffOp::Equal { .. }) {
+            continue;
         }
+        let (old, new) = (op.old_range(), op.new_range());
+        // A deletion followed by an insertion is one replacement.
+        match regions.last_mut() {
+            Some(last) if last.0.end == old.start && last.1.end == new.start => {
+                last.0.end = old.end;
+                last.1.end = new.end;
+            }
+            _ => regions.push((old, new)),
+        }
+    }
+    regions
+}
 
-        // Apply the change
-        lines.splice(start_idx..end_idx, new_lines);
+/// `base[range]` with `regions`, which lie within it, replaced by their lines of `new`.
+fn apply_regions<'a>(
+    base: &[&'a str],
+    range: Range<usize>,
+    regions: &[(Range<usize>, Range<usize>)],
+    new: &[&'a str],
+) -> Vec<&'a str> {
+    let mut lines = Vec::new();
+    let mut pos = range.start;
+    for (old, replacement) in regions {
+        lines.extend(&base[pos..old.start]);
+        lines.extend(&new[replacement.clone()]);
+        pos = old.end;
+    }
+    lines.extend(&base[pos..range.end]);
+    lines
+}
 
+/// A file [`ApplyPatchTool::call`] is about to write.
+struct PendingWrite {
+    path: PathBuf,
+    content: String,
+    /// The file it replaces, for moves.
+    moved_from: Option<PathBuf>,
+    result: AppliedFile,
+}
+
+/// What a patch did to one file, as reported to the model.
+struct AppliedFile {
+    path: String,
+    /// The path the file had before, if the patch moved it.
+    moved_from: Option<String>,
+    /// Whether the move was made with `git mv`.
+    git_mv: bool,
+    /// Whether the patch was merged with changes made since this tool last wrote the file.
+    merged: bool,
+}
+
+impl fmt::Display for AppliedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.moved_from {
+            Some(from) => write!(f, "Moved '{}' to '{}'", from, self.path)?,
+            None => write!(f, "Applied patch to '{}'", self.path)?,
+        }
+        if self.git_mv {
+            write!(f, " with git mv")?;
+        }
+        if self.merged {
+            write!(
+                f,
+                ", merged with changes made to it since it was last patched"
+            )?;
+        }
         Ok(())
     }
 }
 
+/// Move a file that git tracks with `git mv`, so the index records the rename. Returns
+/// whether it did; files outside a repository or not tracked are left to the caller.
+fn git_mv(working_dir: &Path, from: &Path, to: &Path) -> bool {
+    let git = |args: &[&OsStr]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(working_dir)
+            .output()
+            .is_ok_and(|output| output.status.success())
+    };
+    git(&[
+        "ls-files".as_ref(),
+        "--error-unmatch".as_ref(),
+        "--".as_ref(),
+        from.as_os_str(),
+    ]) && git(&[
+        "mv".as_ref(),
+        "--".as_ref(),
+        from.as_os_str(),
+        to.as_os_str(),
+    ])
+}
+
 #[derive(Debug)]
 struct FilePatch {
-    #[allow(dead_code)]
     old_path: String,
     new_path: String,
     hunks: Vec<Hunk>,
 }
 
+impl FilePatch {
+    /// Whether the patch moves its file to another path.
+    fn is_move(&self) -> bool {
+        self.old_path != self.new_path
+            && self.old_path != "/dev/null"
+            && self.new_path != "/dev/null"
+    }
+
+    /// The path of the file the hunks apply to.
+    fn source_path(&self) -> &str {
+        if self.is_move() {
+            &self.old_path
+        } else {
+            &self.new_path
+        }
+    }
+
+    /// Whether the patch creates its file: it comes from `/dev/null`, or it has hunks and
+    /// every one starts at `-0,0`.
+    fn is_addition(&self) -> bool {
+        self.old_path == "/dev/null"
+            || (!self.hunks.is_empty()
+                && self
+                    .hunks
+                    .iter()
+                    .all(|hunk| old_range(&hunk.header) == Ok((0, 0))))
+    }
+
+    /// Whether no hunk has context or removed lines, so applying it to an empty file
+    /// cannot fail.
+    fn only_adds_lines(&self) -> bool {
+        self.hunks
+            .iter()
+            .all(|hunk| hunk.lines.iter().all(|line| !line.starts_with([' ', '-'])))
+    }
+}
+
 #[derive(Debug)]
 struct Hunk {
     header: String,
@@ -240,6 +632,7 @@ impl Tool for ApplyPatchTool {
     fn description(&self) -> String {
         format!(
             "Apply unified text patches to files. Input must start with '*** Begin Patch' and end with '*** End Patch'. \
+            To move a file, give its new path in the '+++' header or on a '*** Move to: <path>' line after the headers. \
             Your current working directory is: {}",
             self.working_dir.display()
         )
@@ -252,6 +645,10 @@ impl Tool for ApplyPatchTool {
                 "patch": {
                     "type": "string",
                     "description": "Patch content following the '*** Begin Patch' ... '*** End Patch' format"
+                },
+                "create_missing": {
+                    "type": "boolean",
+                    "description": "Create files that do not exist when their hunks only add lines, instead of failing (default false)"
                 }
             },
             "required": ["patch"]
@@ -264,16 +661,85 @@ impl Tool for ApplyPatchTool {
             .and_then(|v| v.as_str())
             .ok_or("Missing 'patch' argument")?;
 
+        let create_missing = args
+            .get("create_missing")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
         // Parse the patch
-        let file_patches = self.parse_patch(patch_text)?;
+        let file_patches = parse_patch(patch_text)?;
 
-        // Apply each file patch
-        let mut results = Vec::new();
+        // Updates of files that do not exist fail before any file is written.
+        let missing: Vec<&str> = file_patches
+            .iter()
+            .filter(|fp| {
+                !self.working_dir.join(fp.source_path()).exists()
+                    && !fp.is_addition()
+                    && (!create_missing || !fp.only_adds_lines())
+            })
+            .map(|fp| fp.source_path())
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "Failed to apply patch: {}",
+                self.missing_files_error(&missing)
+            ));
+        }
+
+        // Patch every file in memory first, so a hunk that fails leaves all files as they
+        // were. Later patches of the same file apply to the earlier result.
+        let mut pending: Vec<PendingWrite> = Vec::new();
         for file_patch in &file_patches {
-            match self.apply_file_patch(file_patch) {
-                Ok(msg) => results.push(msg),
-                Err(e) => return Err(format!("Failed to apply patch: {}", e)),
+            let source = self.working_dir.join(file_patch.source_path());
+            let target = self.working_dir.join(&file_patch.new_path);
+            if file_patch.is_move() && target.exists() {
+                return Err(format!(
+                    "Failed to apply patch: cannot move '{}' to '{}', which already exists",
+                    file_patch.old_path, file_patch.new_path
+                ));
             }
+            let content = match pending.iter().rposition(|write| write.path == source) {
+                Some(i) => pending.remove(i).content,
+                None if source.exists() => std::fs::read_to_string(&source)
+                    .map_err(|e| format!("Failed to read '{}': {}", file_patch.source_path(), e))?,
+                None => String::new(),
+            };
+            let (content, merged) = self
+                .patched_content(file_patch, &source, &content)
+                .map_err(|e| format!("Failed to apply patch: {}", e))?;
+            pending.push(PendingWrite {
+                path: target,
+                content,
+                moved_from: (file_patch.is_move() && source.exists()).then_some(source),
+                result: AppliedFile {
+                    path: file_patch.new_path.clone(),
+                    moved_from: file_patch.is_move().then(|| file_patch.old_path.clone()),
+                    git_mv: false,
+                    merged,
+                },
+            });
+        }
+
+        let mut results = Vec::new();
+        for mut write in pending {
+            if let Some(parent) = write.path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create directory: {}", e))?;
+            }
+            self.journal.record(&self.name(), &write.path);
+            if let Some(from) = &write.moved_from {
+                self.journal.record(&self.name(), from);
+                write.result.git_mv = self.git_mv && git_mv(&self.working_dir, from, &write.path);
+                if !write.result.git_mv {
+                    std::fs::rename(from, &write.path)
+                        .map_err(|e| format!("Failed to move '{}': {}", from.display(), e))?;
+                }
+                self.bases.lock().unwrap().remove(from);
+            }
+            std::fs::write(&write.path, &write.content)
+                .map_err(|e| format!("Failed to write '{}': {}", write.path.display(), e))?;
+            self.bases.lock().unwrap().insert(write.path, write.content);
+            results.push(write.result.to_string());
         }
 
         Ok(format!(
@@ -282,6 +748,26 @@ impl Tool for ApplyPatchTool {
             results.join("\n")
         ))
     }
+
+    fn affected_paths(&self, args: &Value) -> Vec<PathBuf> {
+        let Some(patch_text) = args.get("patch").and_then(|v| v.as_str()) else {
+            return Vec::new();
+        };
+        let Ok(file_patches) = parse_patch(patch_text) else {
+            return Vec::new();
+        };
+
+        let mut paths = Vec::new();
+        for file_patch in &file_patches {
+            for p in [&file_patch.old_path, &file_patch.new_path] {
+                let full = self.working_dir.join(p);
+                if p != "/dev/null" && !paths.contains(&full) {
+                    paths.push(full);
+                }
+            }
+        }
+        paths
+    }
 }
 
 #[cfg(test)]
@@ -379,4 +865,311 @@ mod tests {
         assert!(content.contains("line1"));
         assert!(content.contains("line2"));
     }
+
+    #[tokio::test]
+    async fn test_apply_patch_missing_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+        fs::create_dir(temp_path.join("src")).unwrap();
+        fs::write(temp_path.join("src/Main.rs"), "fn main() {}\n").unwrap();
+        fs::write(temp_path.join("config.yaml"), "a: 1\n").unwrap();
+        fs::write(temp_path.join("keep.txt"), "keep\n").unwrap();
+
+        let tool = ApplyPatchTool::new(temp_path.to_path_buf());
+        let patch = r#"*** Begin Patch
+--- a/keep.txt
++++ b/keep.txt
+@@ -1,1 +1,1 @@
+-keep
++changed
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,1 +1,2 @@
+ fn main() {}
++// done
+--- a/config.yml
++++ b/config.yml
+@@ -1,1 +1,1 @@
+-a: 1
++a: 2
+--- a/notes.txt
++++ b/notes.txt
+@@ -1,1 +1,1 @@
++note
+*** End Patch"#;
+
+        let error = tool
+            .call(serde_json::json!({ "patch": patch }))
+            .await
+            .unwrap_err();
+        assert!(error.contains("3 file(s) to update do not exist"));
+        assert!(error.contains("- src/main.rs (did you mean: src/Main.rs?)"));
+        assert!(error.contains("- config.yml (did you mean: config.yaml?)"));
+        assert!(error.contains("- notes.txt\n"));
+        // Nothing is written when any file is missing.
+        assert_eq!(
+            fs::read_to_string(temp_path.join("keep.txt")).unwrap(),
+            "keep\n"
+        );
+
+        // With create_missing, only the update that has no context becomes an add.
+        let error = tool
+            .call(serde_json::json!({ "patch": patch, "create_missing": true }))
+            .await
+            .unwrap_err();
+        assert!(error.contains("2 file(s) to update do not exist"));
+        assert!(!error.contains("notes.txt"));
+
+        let patch = r#"*** Begin Patch
+--- a/notes.txt
++++ b/notes.txt
+@@ -1,1 +1,1 @@
++note
+*** End Patch"#;
+        tool.call(serde_json::json!({ "patch": patch, "create_missing": true }))
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(temp_path.join("notes.txt")).unwrap(),
+            "note\n"
+        );
+    }
+
+    fn patch(body: &str) -> serde_json::Value {
+        serde_json::json!({
+            "patch": format!("*** Begin Patch\n--- a/test.txt\n+++ b/test.txt\n{}\n*** End Patch", body)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_rejects_overlaps_and_stale_context() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+        let original = "a\nb\nc\nd\ne\n";
+        fs::write(temp_path.join("test.txt"), original).unwrap();
+        let tool = ApplyPatchTool::new(temp_path.to_path_buf());
+
+        let error = tool
+            .call(patch(
+                "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n@@ -2,2 +2,2 @@\n-b\n+X\n c",
+            ))
+            .await
+            .unwrap_err();
+        assert!(error.contains("Hunk 2 (@@ -2,2 +2,2 @@) overlaps the hunk before it"));
+
+        // Two of three lines match, which must not be enough to replace them.
+        let error = tool
+            .call(patch("@@ -2,3 +2,3 @@\n b\n-x\n+X\n d"))
+            .await
+            .unwrap_err();
+        assert!(error.contains("does not match file content"));
+        assert_eq!(
+            fs::read_to_string(temp_path.join("test.txt")).unwrap(),
+            original
+        );
+
+        // Context that moved is found where it is now.
+        tool.call(patch("@@ -1,2 +1,2 @@\n c\n-d\n+D"))
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(temp_path.join("test.txt")).unwrap(),
+            "a\nb\nc\nD\ne\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_three_way_merge() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+        let file = temp_path.join("test.txt");
+        fs::write(&file, "a\nb\nc\nd\ne\n").unwrap();
+        let tool = ApplyPatchTool::new(temp_path.to_path_buf());
+        tool.call(patch("@@ -1,1 +1,1 @@\n-a\n+A")).await.unwrap();
+
+        // Someone else changes a line the next patch uses as context.
+        fs::write(&file, "A\nB\nc\nd\ne\n").unwrap();
+        let next = patch("@@ -2,3 +2,3 @@\n b\n c\n-d\n+D");
+        let result = tool.call(next.clone()).await.unwrap();
+        assert!(result.contains("merged with changes made to it"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "A\nB\nc\nD\ne\n");
+
+        // Changes to the same line conflict, and the file is left alone.
+        fs::write(&file, "A\nB\nc\nD\nE\n").unwrap();
+        let error = tool
+            .call(patch("@@ -4,2 +4,2 @@\n D\n-e\n+e2"))
+            .await
+            .unwrap_err();
+        assert!(error.contains("1 of the changes conflict"));
+        assert!(error.contains("<<<<<<< current\nE\n=======\ne2\n>>>>>>> patch"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "A\nB\nc\nD\nE\n");
+
+        // Without merging, a patch against the old version just fails.
+        fs::write(&file, "A\nb\nc\nd\ne\n").unwrap();
+        let tool = ApplyPatchTool::new(temp_path.to_path_buf()).with_three_way_merge(false);
+        tool.call(patch("@@ -1,1 +1,1 @@\n-A\n+a")).await.unwrap();
+        fs::write(&file, "a\nB\nc\nd\ne\n").unwrap();
+        assert!(tool.call(next).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_moves_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+        fs::write(temp_path.join("old.txt"), "one\ntwo\n").unwrap();
+        let tool = ApplyPatchTool::new(temp_path.to_path_buf());
+
+        let result = tool
+            .call(serde_json::json!({ "patch": r#"*** Begin Patch
+--- a/old.txt
++++ b/nested/dir/new.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++TWO
+*** End Patch"# }))
+            .await
+            .unwrap();
+        assert!(result.contains("Moved 'old.txt' to 'nested/dir/new.txt'"));
+        assert!(!temp_path.join("old.txt").exists());
+        assert_eq!(
+            fs::read_to_string(temp_path.join("nested/dir/new.txt")).unwrap(),
+            "one\nTWO\n"
+        );
+
+        // In a repository, the move of a tracked file is staged as a rename.
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .args(args)
+                .current_dir(temp_path)
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).to_string()
+        };
+        git(&["init", "-q"]);
+        git(&["add", "nested/dir/new.txt"]);
+        let result = tool
+            .call(serde_json::json!({ "patch": r#"*** Begin Patch
+--- a/nested/dir/new.txt
++++ b/nested/dir/new.txt
+*** Move to: moved.txt
+*** End Patch"# }))
+            .await
+            .unwrap();
+        assert!(result.contains("Moved 'nested/dir/new.txt' to 'moved.txt' with git mv"));
+        assert_eq!(
+            git(&["diff", "--cached", "--name-status"]),
+            "A\tmoved.txt\n"
+        );
+        assert_eq!(
+            fs::read_to_string(temp_path.join("moved.txt")).unwrap(),
+            "one\nTWO\n"
+        );
+
+        // A move onto an existing file fails.
+        fs::write(temp_path.join("other.txt"), "x\n").unwrap();
+        let error = tool
+            .call(serde_json::json!({ "patch": "*** Begin Patch\n--- a/moved.txt\n+++ b/other.txt\n*** End Patch" }))
+            .await
+            .unwrap_err();
+        assert!(error.contains("already exists"));
+        assert!(temp_path.join("moved.txt").exists());
+    }
+
+    mod properties {
+        use super::super::apply_patch_to_content;
+        use proptest::prelude::*;
+        use proptest::sample::Index;
+        use similar::TextDiff;
+
+        /// The patch from `old` to `new` in the `*** Begin Patch` format, as `diff -u`
+        /// would produce it.
+        fn emit_patch(old: &str, new: &str) -> String {
+            let diff = TextDiff::from_lines(old, new);
+            format!(
+                "*** Begin Patch\n{}*** End Patch",
+                diff.unified_diff().header("a/file.txt", "b/file.txt")
+            )
+        }
+
+        fn content(lines: &[String]) -> String {
+            lines.iter().map(|l| format!("{}\n", l)).collect()
+        }
+
+        fn line() -> impl Strategy<Value = String> {
+            "[a-z ]{0,6}"
+        }
+
+        #[derive(Debug, Clone)]
+        enum Edit {
+            Delete(Index),
+            Insert(Index, String),
+            Replace(Index, String),
+        }
+
+        fn edit() -> impl Strategy<Value = Edit> {
+            prop_oneof![
+                any::<Index>().prop_map(Edit::Delete),
+                (any::<Index>(), line()).prop_map(|(i, l)| Edit::Insert(i, l)),
+                (any::<Index>(), line()).prop_map(|(i, l)| Edit::Replace(i, l)),
+            ]
+        }
+
+        fn edited(mut lines: Vec<String>, edits: &[Edit]) -> Vec<String> {
+            for edit in edits {
+                match edit {
+                    Edit::Insert(i, line) => lines.insert(i.index(lines.len() + 1), line.clone()),
+                    Edit::Delete(i) if !lines.is_empty() => {
+                        lines.remove(i.index(lines.len()));
+                    }
+                    Edit::Replace(i, line) if !lines.is_empty() => {
+                        let i = i.index(lines.len());
+                        lines[i] = line.clone();
+                    }
+                    _ => {}
+                }
+            }
+            lines
+        }
+
+        /// Unified diff lines, some of them mangled the way models mangle patches.
+        fn patch_like() -> impl Strategy<Value = String> {
+            let line = prop_oneof![
+                Just("*** Begin Patch".to_string()),
+                Just("*** End Patch".to_string()),
+                "--- (a/)?[a-zé./]{0,6}",
+                "\\+\\+\\+ (b/)?[a-zé./]{0,6}",
+                "@@ -?[0-9]{0,3}(,[0-9]{0,3})? \\+?[0-9]{0,3}(,[0-9]{0,3})? @@",
+                "[ +\\-\\\\é@]?[a-zé ]{0,5}",
+            ];
+            prop::collection::vec(line, 0..20).prop_map(|lines| lines.join("\n"))
+        }
+
+        proptest! {
+            #[test]
+            fn test_patch_round_trip(
+                old in prop::collection::vec(line(), 0..30),
+                edits in prop::collection::vec(edit(), 1..8),
+            ) {
+                let new = edited(old.clone(), &edits);
+                let (old, new) = (content(&old), content(&new));
+                prop_assume!(old != new);
+                let patch = emit_patch(&old, &new);
+                prop_assert_eq!(apply_patch_to_content(&patch, &old), Ok(new));
+            }
+
+            #[test]
+            fn test_malformed_patches_do_not_panic(
+                patch in patch_like(),
+                content in "[a-zé \n]{0,40}",
+            ) {
+                let _ = apply_patch_to_content(&patch, &content);
+            }
+
+            #[test]
+            fn test_arbitrary_input_does_not_panic(patch in ".*", content in ".*") {
+                let _ = apply_patch_to_content(&patch, &content);
+            }
+        }
+    }
 }
@@ -0,0 +1,114 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// The working directory of a conversation, shared by its tools like a terminal's.
+///
+/// It starts at the workspace. A `cd` in a `cmd` command moves it for the commands that
+/// follow, and the file tools resolve relative paths against it, so the agent does not
+/// have to repeat `cd` or spell out full paths.
+#[derive(Clone, Debug)]
+pub struct WorkingDir {
+    root: PathBuf,
+    current: Arc<RwLock<PathBuf>>,
+}
+
+impl WorkingDir {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(root.clone())),
+            root,
+        }
+    }
+
+    /// The directory it started at.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn get(&self) -> PathBuf {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Move to `dir`, resolved against the current directory. Fails if it is not a
+    /// directory.
+    pub fn set(&self, dir: impl AsRef<Path>) -> Result<PathBuf, String> {
+        let dir = self.resolve(dir);
+        if !dir.is_dir() {
+            return Err(format!("'{}' is not a directory", dir.display()));
+        }
+        *self.current.write().unwrap() = dir.clone();
+        Ok(dir)
+    }
+
+    /// `path` resolved against the current directory; absolute paths stay as they are.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.get().join(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::tools::default_tools;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_working_dir() {
+        let root = TempDir::new().unwrap();
+        std::fs::create_dir_all(root.path().join("src/api")).unwrap();
+        let cwd = WorkingDir::new(root.path().to_path_buf());
+        let shared = cwd.clone();
+
+        assert_eq!(cwd.set("src").unwrap(), root.path().join("src"));
+        assert_eq!(shared.resolve("api"), root.path().join("src/api"));
+        assert!(cwd.set("missing").is_err());
+        assert_eq!(shared.get(), root.path().join("src"));
+        assert_eq!(cwd.resolve("/etc/hosts"), PathBuf::from("/etc/hosts"));
+        assert_eq!(cwd.root(), root.path());
+    }
+
+    #[tokio::test]
+    async fn test_cd_moves_commands_and_file_tools() {
+        let root = TempDir::new().unwrap();
+        std::fs::create_dir_all(root.path().join("src")).unwrap();
+        std::fs::write(root.path().join("src/lib.rs"), "pub fn f() {}\n").unwrap();
+        let tools = default_tools(root.path().to_path_buf());
+        let tool = |name: &str| tools.iter().find(|t| t.name() == name).unwrap();
+        let src = root.path().join("src").canonicalize().unwrap();
+
+        let output = tool("cmd")
+            .call(json!({ "command": "cd src && pwd" }))
+            .await;
+        assert_eq!(output.unwrap().trim(), src.to_string_lossy());
+        let output = tool("cmd").call(json!({ "command": "pwd" })).await;
+        assert_eq!(output.unwrap().trim(), src.to_string_lossy());
+        assert!(tool("cmd").description().contains(&*src.to_string_lossy()));
+
+        // A failing command still moves the directory, and keeps its exit code.
+        let output = tool("cmd")
+            .call(json!({ "command": "cd .. && exit 3" }))
+            .await;
+        assert!(output.unwrap().contains("exit code 3"));
+        let output = tool("cmd").call(json!({ "command": "pwd" })).await;
+        assert_eq!(
+            output.unwrap().trim(),
+            root.path().canonicalize().unwrap().to_string_lossy()
+        );
+        tool("cmd")
+            .call(json!({ "command": "cd src" }))
+            .await
+            .unwrap();
+
+        let view = tool("file_editor")
+            .call(json!({ "operation": "view", "path": "lib.rs" }))
+            .await
+            .unwrap();
+        assert!(view.contains("pub fn f() {}"));
+        let found = tool("glob")
+            .call(json!({ "pattern": "*.rs" }))
+            .await
+            .unwrap();
+        assert!(found.contains("lib.rs"));
+    }
+}
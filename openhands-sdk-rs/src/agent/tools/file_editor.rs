@@ -1,66 +1,60 @@
 use async_trait::async_trait;
 use serde_json::Value;
-use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
 
-use super::Tool;
+use super::{Tool, ToolOutput, WorkingDir, image_mime_type};
+use crate::events::Attachment;
+use crate::file_locks::FileLocks;
+use crate::journal::FileMutationJournal;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 
-#[derive(Clone)]
-struct FileState {
-    content: String,
-    history: Vec<String>,
-}
+/// Largest image `view` passes to the model.
+const MAX_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
 
 pub struct FileEditorTool {
-    working_dir: PathBuf,
-    file_states: Arc<Mutex<HashMap<String, FileState>>>,
+    working_dir: WorkingDir,
+    journal: FileMutationJournal,
+    locks: FileLocks,
 }
 
 impl FileEditorTool {
     pub fn new(working_dir: PathBuf) -> Self {
         Self {
-            working_dir,
-            file_states: Arc::new(Mutex::new(HashMap::new())),
+            working_dir: WorkingDir::new(working_dir),
+            journal: FileMutationJournal::default(),
+            locks: FileLocks::global(),
         }
     }
 
-    fn get_or_load_file(&self, path: &str) -> Result<FileState, String> {
-        let mut states = self.file_states.lock().unwrap();
+    /// Record changes in `journal`, which `undo` also reverts the changes of the other tools
+    /// sharing it through.
+    pub fn with_journal(mut self, journal: FileMutationJournal) -> Self {
+        self.journal = journal;
+        self
+    }
 
-        if let Some(state) = states.get(path) {
-            Ok(state.clone())
-        } else {
-            // Load file from disk
-            let full_path = self.working_dir.join(path);
-            let content = std::fs::read_to_string(&full_path)
-                .map_err(|e| format!("Failed to read file '{}': {}", path, e))?;
-
-            let state = FileState {
-                content: content.clone(),
-                history: vec![content],
-            };
-            states.insert(path.to_string(), state.clone());
-            Ok(state)
-        }
+    /// Resolve relative paths against `cwd`, shared with the conversation's other tools.
+    pub fn with_cwd(mut self, cwd: WorkingDir) -> Self {
+        self.working_dir = cwd;
+        self
     }
 
-    fn save_file_state(&self, path: &str, new_content: String) -> Result<(), String> {
-        let mut states = self.file_states.lock().unwrap();
+    /// Serialize edits with `locks` instead of the process-wide [`FileLocks::global`].
+    pub fn with_file_locks(mut self, locks: FileLocks) -> Self {
+        self.locks = locks;
+        self
+    }
 
-        if let Some(state) = states.get_mut(path) {
-            state.history.push(state.content.clone());
-            state.content = new_content.clone();
-        } else {
-            let state = FileState {
-                content: new_content.clone(),
-                history: vec![],
-            };
-            states.insert(path.to_string(), state);
-        }
+    /// The file as it is now, whoever wrote it last.
+    fn read_file(&self, path: &str) -> Result<String, String> {
+        std::fs::read_to_string(self.working_dir.resolve(path))
+            .map_err(|e| format!("Failed to read file '{}': {}", path, e))
+    }
 
-        // Write to disk
-        let full_path = self.working_dir.join(path);
+    fn write_file(&self, path: &str, new_content: String) -> Result<(), String> {
+        let full_path = self.working_dir.resolve(path);
+        self.journal.record(&self.name(), &full_path);
         if let Some(parent) = full_path.parent() {
             std::fs::create_dir_all(parent)
                 .map_err(|e| format!("Failed to create directory: {}", e))?;
@@ -72,8 +66,8 @@ impl FileEditorTool {
     }
 
     fn view_operation(&self, path: &str, start_line: Option<usize>, end_line: Option<usize>) -> Result<String, String> {
-        let state = self.get_or_load_file(path)?;
-        let lines: Vec<&str> = state.content.lines().collect();
+        let content = self.read_file(path)?;
+        let lines: Vec<&str> = content.lines().collect();
 
         let start = start_line.unwrap_or(1).saturating_sub(1);
         let end = end_line.unwrap_or(lines.len()).min(lines.len());
@@ -97,9 +91,32 @@ impl FileEditorTool {
         ))
     }
 
+    /// `view` of an image file: the image itself, for models that accept images.
+    fn view_image(&self, path: &str, mime_type: &str) -> Result<ToolOutput, String> {
+        let full_path = self.working_dir.resolve(path);
+        let size = std::fs::metadata(&full_path)
+            .map_err(|e| format!("Failed to read file '{}': {}", path, e))?
+            .len();
+        if size > MAX_IMAGE_BYTES {
+            return Err(format!(
+                "Image '{}' is too large to view ({} bytes, limit {})",
+                path, size, MAX_IMAGE_BYTES
+            ));
+        }
+        let data = std::fs::read(&full_path)
+            .map_err(|e| format!("Failed to read file '{}': {}", path, e))?;
+        Ok(ToolOutput {
+            content: format!("Viewing image '{}' ({} bytes)", path, size),
+            attachments: vec![Attachment::Image {
+                mime_type: mime_type.to_string(),
+                data: BASE64_STANDARD.encode(data),
+            }],
+        })
+    }
+
     fn insert_operation(&self, path: &str, line: usize, content: &str) -> Result<String, String> {
-        let state = self.get_or_load_file(path)?;
-        let mut lines: Vec<String> = state.content.lines().map(|s| s.to_string()).collect();
+        let original = self.read_file(path)?;
+        let mut lines: Vec<String> = original.lines().map(|s| s.to_string()).collect();
 
         let insert_pos = line.saturating_sub(1).min(lines.len());
         let new_lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
@@ -109,10 +126,10 @@ impl FileEditorTool {
         }
 
         let new_content = lines.join("\n");
-        if !state.content.is_empty() && !new_content.ends_with('\n') {
-            self.save_file_state(path, format!("{}\n", new_content))?;
+        if !original.is_empty() && !new_content.ends_with('\n') {
+            self.write_file(path, format!("{}\n", new_content))?;
         } else {
-            self.save_file_state(path, new_content)?;
+            self.write_file(path, new_content)?;
         }
 
         Ok(format!(
@@ -130,8 +147,8 @@ impl FileEditorTool {
         end_line: usize,
         content: &str,
     ) -> Result<String, String> {
-        let state = self.get_or_load_file(path)?;
-        let mut lines: Vec<String> = state.content.lines().map(|s| s.to_string()).collect();
+        let original = self.read_file(path)?;
+        let mut lines: Vec<String> = original.lines().map(|s| s.to_string()).collect();
 
         let start = start_line.saturating_sub(1);
         let end = end_line.min(lines.len());
@@ -150,10 +167,10 @@ impl FileEditorTool {
         }
 
         let new_content = lines.join("\n");
-        if !state.content.is_empty() && !new_content.ends_with('\n') {
-            self.save_file_state(path, format!("{}\n", new_content))?;
+        if !original.is_empty() && !new_content.ends_with('\n') {
+            self.write_file(path, format!("{}\n", new_content))?;
         } else {
-            self.save_file_state(path, new_content)?;
+            self.write_file(path, new_content)?;
         }
 
         Ok(format!(
@@ -166,8 +183,8 @@ impl FileEditorTool {
     }
 
     fn delete_operation(&self, path: &str, start_line: usize, end_line: usize) -> Result<String, String> {
-        let state = self.get_or_load_file(path)?;
-        let mut lines: Vec<String> = state.content.lines().map(|s| s.to_string()).collect();
+        let content = self.read_file(path)?;
+        let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
 
         let start = start_line.saturating_sub(1);
         let end = end_line.min(lines.len());
@@ -180,10 +197,10 @@ impl FileEditorTool {
         lines.drain(start..end);
 
         let new_content = lines.join("\n");
-        if !state.content.is_empty() && !new_content.ends_with('\n') {
-            self.save_file_state(path, format!("{}\n", new_content))?;
+        if !content.is_empty() && !new_content.ends_with('\n') {
+            self.write_file(path, format!("{}\n", new_content))?;
         } else {
-            self.save_file_state(path, new_content)?;
+            self.write_file(path, new_content)?;
         }
 
         Ok(format!(
@@ -193,24 +210,11 @@ impl FileEditorTool {
     }
 
     fn undo_operation(&self, path: &str) -> Result<String, String> {
-        let mut states = self.file_states.lock().unwrap();
-
-        if let Some(state) = states.get_mut(path) {
-            if let Some(previous_content) = state.history.pop() {
-                state.content = previous_content.clone();
-
-                // Write to disk
-                let full_path = self.working_dir.join(path);
-                std::fs::write(&full_path, &previous_content)
-                    .map_err(|e| format!("Failed to write file '{}': {}", path, e))?;
-
-                Ok(format!("Undid last change to '{}'", path))
-            } else {
-                Err(format!("No history available for '{}'", path))
-            }
-        } else {
-            Err(format!("File '{}' not in edit session", path))
-        }
+        let undone = self.journal.undo_last(&self.working_dir.resolve(path))?;
+        Ok(format!(
+            "Undid last change to '{}', made by {}",
+            path, undone.tool
+        ))
     }
 }
 
@@ -224,7 +228,7 @@ impl Tool for FileEditorTool {
         format!(
             "Structured file editing tool. Supports view, insert, replace, delete, and undo operations. \
             Your current working directory is: {}",
-            self.working_dir.display()
+            self.working_dir.get().display()
         )
     }
 
@@ -262,6 +266,21 @@ impl Tool for FileEditorTool {
         })
     }
 
+    async fn call_with_attachments(&self, args: Value) -> Result<ToolOutput, String> {
+        let operation = args.get("operation").and_then(|v| v.as_str());
+        let path = args.get("path").and_then(|v| v.as_str());
+        if let (Some("view"), Some(path)) = (operation, path)
+            && let Some(mime_type) = image_mime_type(std::path::Path::new(path))
+        {
+            return self.view_image(path, mime_type);
+        }
+        self.call(args).await.map(ToolOutput::from)
+    }
+
+    fn is_read_only(&self, args: &Value) -> bool {
+        args.get("operation").and_then(|v| v.as_str()) == Some("view")
+    }
+
     async fn call(&self, args: Value) -> Result<String, String> {
         let operation = args
             .get("operation")
@@ -273,6 +292,7 @@ impl Tool for FileEditorTool {
             .and_then(|v| v.as_str())
             .ok_or("Missing 'path' argument")?;
 
+        let _lock = self.locks.lock(&self.working_dir.resolve(path)).await?;
         match operation {
             "view" => {
                 let start_line = args.get("start_line").and_then(|v| v.as_u64()).map(|n| n as usize);
@@ -320,12 +340,24 @@ impl Tool for FileEditorTool {
             _ => Err(format!("Unknown operation: {}", operation)),
         }
     }
+
+    fn affected_paths(&self, args: &Value) -> Vec<PathBuf> {
+        let operation = args.get("operation").and_then(|v| v.as_str());
+        let path = args.get("path").and_then(|v| v.as_str());
+        match (operation, path) {
+            (Some("insert" | "replace" | "delete" | "undo"), Some(path)) => {
+                vec![self.working_dir.resolve(path)]
+            }
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
+    use std::sync::Arc;
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -413,6 +445,52 @@ mod tests {
         assert_eq!(content, "line1\nline3\n");
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_file_editor_concurrent_edits_serialize() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+        fs::write(temp_path.join("test.txt"), "end\n").unwrap();
+        let tool = Arc::new(FileEditorTool::new(temp_path.to_path_buf()));
+
+        let edits: Vec<_> = (0..20)
+            .map(|i| {
+                let tool = tool.clone();
+                tokio::spawn(async move {
+                    let args = serde_json::json!({
+                        "operation": "insert",
+                        "path": "test.txt",
+                        "line": 1,
+                        "content": format!("line {}", i)
+                    });
+                    tool.call(args).await.unwrap()
+                })
+            })
+            .collect();
+        for edit in edits {
+            edit.await.unwrap();
+        }
+
+        // A write by someone else is edited, not overwritten.
+        let content = fs::read_to_string(temp_path.join("test.txt")).unwrap();
+        fs::write(temp_path.join("test.txt"), format!("{}outside\n", content)).unwrap();
+        tool.call(serde_json::json!({
+            "operation": "insert",
+            "path": "test.txt",
+            "line": 1,
+            "content": "first"
+        }))
+        .await
+        .unwrap();
+
+        let content = fs::read_to_string(temp_path.join("test.txt")).unwrap();
+        assert_eq!(content.lines().count(), 23);
+        assert!(content.starts_with("first\n"));
+        assert!(content.ends_with("end\noutside\n"));
+        for i in 0..20 {
+            assert!(content.contains(&format!("line {}\n", i)));
+        }
+    }
+
     #[tokio::test]
     async fn test_file_editor_undo() {
         let temp_dir = TempDir::new().unwrap();
@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::Tool;
+use crate::outcome::{FINISH_TOOL, FinishArgs, TaskStatus};
+
+/// Ends the agent's turn with a structured report of the outcome (see [`crate::outcome`]).
+#[derive(Default)]
+pub struct FinishTool;
+
+impl FinishTool {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for FinishTool {
+    fn name(&self) -> String {
+        FINISH_TOOL.to_string()
+    }
+
+    fn description(&self) -> String {
+        "Call this when you are done with the task to report the outcome: whether it succeeded, \
+        a short summary of what you did (and what is left), and the result of the tests you ran. \
+        Your turn ends after this call."
+            .to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "status": {
+                    "type": "string",
+                    "enum": ["success", "partial", "failure"],
+                    "description": "Whether the task is done, partly done or could not be done"
+                },
+                "summary": {
+                    "type": "string",
+                    "description": "What you did and, unless the task succeeded, what is left"
+                },
+                "tests": {
+                    "type": "object",
+                    "description": "Result of the last test run, if you ran tests",
+                    "properties": {
+                        "command": { "type": "string" },
+                        "passed": { "type": "integer" },
+                        "failed": { "type": "integer" },
+                        "skipped": { "type": "integer" }
+                    },
+                    "required": ["passed", "failed"]
+                }
+            },
+            "required": ["status", "summary"]
+        })
+    }
+
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
+    async fn call(&self, args: Value) -> Result<String, String> {
+        let args: FinishArgs =
+            serde_json::from_value(args).map_err(|e| format!("Invalid arguments: {}", e))?;
+        let status = match args.status {
+            TaskStatus::Success => "success",
+            TaskStatus::Partial => "partial",
+            TaskStatus::Failure => "failure",
+        };
+        Ok(format!("Task finished with status {}", status))
+    }
+
+    /// The report is about the conversation, not the sandbox.
+    fn runs_on_host(&self) -> bool {
+        true
+    }
+}
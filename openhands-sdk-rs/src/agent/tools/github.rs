@@ -0,0 +1,559 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::process::Command;
+
+use super::Tool;
+use crate::github::{GitHubClient, GitHubConfig, NewPullRequest, parse_github_remote};
+use crate::repo_host::{HostKind, RepoHost, RepoId, host_for_url, parse_repo_url};
+
+/// Pathspec for the whole workspace except the agent's own `.openhands` directory (memory,
+/// scratch files), which must not be published.
+const WORKSPACE_CHANGES: [&str; 3] = ["--", ".", ":(exclude).openhands"];
+
+/// Publishes the agent's work as a pull request: commits the workspace changes to a new
+/// branch, pushes it, and opens a (draft) pull request on the host of the `origin` remote.
+/// GitHub (including GitHub Enterprise, through `api_base`) uses the configured client;
+/// GitLab and Bitbucket remotes use a [`RepoHost`] with credentials from the environment
+/// (see [`host_for_url`]).
+pub struct GitHubTool {
+    working_dir: PathBuf,
+    client: Arc<GitHubClient>,
+}
+
+impl GitHubTool {
+    pub fn new(working_dir: PathBuf, config: GitHubConfig) -> Self {
+        Self {
+            working_dir,
+            client: Arc::new(GitHubClient::new(config)),
+        }
+    }
+
+    /// Create the tool if credentials for any host are configured: `GITHUB_TOKEN`,
+    /// `GITLAB_TOKEN` or `BITBUCKET_USERNAME`.
+    pub fn from_env(working_dir: PathBuf) -> Option<Self> {
+        match GitHubConfig::from_env() {
+            Some(config) => Some(Self::new(working_dir, config)),
+            None if ["GITLAB_TOKEN", "BITBUCKET_USERNAME"]
+                .iter()
+                .any(|name| std::env::var_os(name).is_some()) =>
+            {
+                Some(Self {
+                    working_dir,
+                    client: Arc::new(GitHubClient::anonymous()),
+                })
+            }
+            None => None,
+        }
+    }
+
+    async fn git(&self, args: &[&str]) -> Result<String, String> {
+        self.git_redacted(args, None).await
+    }
+
+    /// Run git, keeping `secret` (the credentials in a push URL) out of the error.
+    async fn git_redacted(&self, args: &[&str], secret: Option<&str>) -> Result<String, String> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(&self.working_dir)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run git: {}", e))?;
+
+        if !output.status.success() {
+            let mut stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            if let Some(secret) = secret.filter(|s| !s.is_empty()) {
+                stderr = stderr.replace(secret, "***");
+            }
+            return Err(format!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                stderr.trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// The branch the `HEAD` of the remote at `url` points to, i.e. its default branch.
+    async fn default_branch(&self, url: &str, secret: Option<&str>) -> Result<String, String> {
+        let refs = self
+            .git_redacted(&["ls-remote", "--symref", url, "HEAD"], secret)
+            .await
+            .map_err(|e| {
+                format!(
+                    "Could not determine the default branch of 'origin', pass 'base': {}",
+                    e
+                )
+            })?;
+        refs.lines()
+            .find_map(|line| {
+                line.strip_prefix("ref: refs/heads/")?
+                    .strip_suffix("\tHEAD")
+            })
+            .map(|branch| branch.to_string())
+            .ok_or_else(|| "Remote 'origin' has no default branch, pass 'base'".to_string())
+    }
+
+    /// Commit the staged workspace changes to the current branch and push it. Returns the
+    /// diff stat of the commit.
+    async fn commit_and_push(
+        &self,
+        title: &str,
+        branch: &str,
+        push_url: &str,
+        credentials: Option<&str>,
+    ) -> Result<String, String> {
+        self.git(&[&["add", "-A"][..], &WORKSPACE_CHANGES].concat())
+            .await?;
+        let diff_stat = self.git(&["diff", "--cached", "--stat"]).await?;
+
+        let mut commit_args = Vec::new();
+        if self.git(&["config", "user.email"]).await.is_err() {
+            commit_args.extend([
+                "-c",
+                "user.name=OpenHands",
+                "-c",
+                "user.email=openhands@localhost",
+            ]);
+        }
+        commit_args.extend(["commit", "-m", title]);
+        self.git(&commit_args).await?;
+
+        self.git_redacted(
+            &["push", push_url, &format!("{}:{}", branch, branch)],
+            credentials,
+        )
+        .await?;
+        Ok(diff_stat)
+    }
+
+    /// Undo a failed [`GitHubTool::commit_and_push`] on `branch`: go back to `original` at
+    /// commit `head` with the changes left uncommitted in the working tree, and delete `branch`.
+    async fn restore(&self, original: &str, head: &str, branch: &str) {
+        for args in [
+            &["reset", "-q", head][..],
+            &["checkout", "-q", original],
+            &["branch", "-q", "-D", branch],
+        ] {
+            if let Err(e) = self.git(args).await {
+                tracing::warn!("Failed to restore the workspace after a failed push: {}", e);
+            }
+        }
+    }
+
+    /// The hosting service and repository `remote_url` points to. A GitHub remote must be
+    /// on the configured GitHub host, so its token is not sent anywhere else.
+    fn repo_host(&self, remote_url: &str) -> Result<(Arc<dyn RepoHost>, RepoId), String> {
+        let github_host = self.client.web_host();
+        if let Some(remote) = parse_github_remote(remote_url)
+            && remote.host == github_host
+        {
+            let repo = RepoId {
+                host: remote.host,
+                owner: remote.owner,
+                name: remote.repo,
+            };
+            return Ok((self.client.clone(), repo));
+        }
+        match parse_repo_url(remote_url) {
+            Some((HostKind::GitHub, repo)) => Err(format!(
+                "Remote 'origin' is on {}, not the configured GitHub host {}",
+                repo.host, github_host
+            )),
+            Some(_) => host_for_url(remote_url),
+            None => Err(format!(
+                "Remote 'origin' is not a GitHub, GitLab or Bitbucket repository: {}",
+                remote_url
+            )),
+        }
+    }
+
+    async fn create_pr(&self, args: &Value) -> Result<String, String> {
+        let title = args
+            .get("title")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing 'title' argument")?;
+        let body = args.get("body").and_then(|v| v.as_str()).unwrap_or("");
+        let branch = args
+            .get("branch")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| format!("openhands/{}", slugify(title)));
+        let draft = args.get("draft").and_then(|v| v.as_bool()).unwrap_or(true);
+        let dry_run = args
+            .get("dry_run")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(self.client.config.dry_run);
+
+        if branch.starts_with('-')
+            || self
+                .git(&["check-ref-format", "--branch", &branch])
+                .await
+                .is_err()
+        {
+            return Err(format!("Invalid branch name: {}", branch));
+        }
+
+        let remote_url = self.git(&["remote", "get-url", "origin"]).await?;
+        let (host, repo) = self.repo_host(&remote_url)?;
+
+        let changed_files = self
+            .git(&[&["status", "--porcelain"][..], &WORKSPACE_CHANGES].concat())
+            .await?;
+        if changed_files.is_empty() {
+            return Err("No changes to commit in the workspace".to_string());
+        }
+        let file_count = changed_files.lines().count();
+
+        let push_url = host.authenticated_remote(&repo);
+        let credentials = push_url
+            .split_once("://")
+            .and_then(|(_, rest)| rest.split_once('@'))
+            .map(|(credentials, _)| credentials);
+        let base = match args.get("base").and_then(|v| v.as_str()) {
+            Some(base) => base.to_string(),
+            None => self.default_branch(&push_url, credentials).await?,
+        };
+
+        if dry_run {
+            return Ok(format!(
+                "Dry run: would commit {} changed file(s) to branch '{}', push it to {} on {} and open a {}pull request against '{}' titled '{}'.\n\nChanged files:\n{}",
+                file_count,
+                branch,
+                repo.full_name(),
+                repo.host,
+                if draft { "draft " } else { "" },
+                base,
+                title,
+                changed_files
+            ));
+        }
+
+        let head = self
+            .git(&["rev-parse", "--verify", "HEAD"])
+            .await
+            .map_err(|_| "The workspace repository has no commit to branch from".to_string())?;
+        let original = self
+            .git(&["symbolic-ref", "--short", "-q", "HEAD"])
+            .await
+            .unwrap_or_else(|_| head.clone());
+        self.git(&["checkout", "-b", &branch]).await?;
+        let diff_stat = match self
+            .commit_and_push(title, &branch, &push_url, credentials)
+            .await
+        {
+            Ok(diff_stat) => diff_stat,
+            Err(e) => {
+                self.restore(&original, &head, &branch).await;
+                return Err(e);
+            }
+        };
+
+        let pr = host
+            .open_pull_request(
+                &repo,
+                &NewPullRequest {
+                    title: title.to_string(),
+                    body: format!("{}\n\n---\nChanges:\n```\n{}\n```", body, diff_stat),
+                    head: branch.clone(),
+                    base,
+                    draft,
+                },
+            )
+            .await?;
+
+        Ok(format!(
+            "Opened pull request #{} from branch '{}': {}",
+            pr.number, branch, pr.html_url
+        ))
+    }
+}
+
+/// Turn a PR title into a branch-name-safe slug.
+fn slugify(title: &str) -> String {
+    let slug: String = title
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug: Vec<&str> = slug.split('-').filter(|s| !s.is_empty()).collect();
+    slug.join("-").chars().take(50).collect()
+}
+
+#[async_trait]
+impl Tool for GitHubTool {
+    fn name(&self) -> String {
+        "github".to_string()
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Publish your changes on GitHub, GitLab or Bitbucket, wherever the 'origin' remote is. Operation 'create_pr' commits all workspace changes to a new branch, \
+            pushes it, and opens a pull request. Call it once the task is complete, with a concise title and a body \
+            summarizing what changed and why. Set dry_run to preview without pushing. \
+            Your current working directory is: {}",
+            self.working_dir.display()
+        )
+    }
+
+    fn parameters(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "operation": {
+                    "type": "string",
+                    "enum": ["create_pr"],
+                    "description": "The operation to perform"
+                },
+                "title": {
+                    "type": "string",
+                    "description": "Pull request title (also used as the commit message)"
+                },
+                "body": {
+                    "type": "string",
+                    "description": "Pull request description summarizing the changes"
+                },
+                "branch": {
+                    "type": "string",
+                    "description": "Branch name to create (defaults to one derived from the title)"
+                },
+                "base": {
+                    "type": "string",
+                    "description": "Branch to merge into (defaults to the default branch of 'origin')"
+                },
+                "draft": {
+                    "type": "boolean",
+                    "description": "Open the pull request as a draft (default true)"
+                },
+                "dry_run": {
+                    "type": "boolean",
+                    "description": "Describe what would happen without committing or pushing"
+                }
+            },
+            "required": ["operation", "title"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<String, String> {
+        let operation = args
+            .get("operation")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing 'operation' argument")?;
+
+        match operation {
+            "create_pr" => self.create_pr(&args).await,
+            _ => Err(format!("Unknown operation: {}", operation)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn config() -> GitHubConfig {
+        GitHubConfig {
+            token: "secret-token".to_string(),
+            api_base: "https://api.github.com".to_string(),
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(
+            slugify("Fix: crash on empty input!"),
+            "fix-crash-on-empty-input"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_pr_dry_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+
+        let tool = GitHubTool::new(temp_path.to_path_buf(), config());
+        tool.git(&["init", "-q"]).await.unwrap();
+        tool.git(&[
+            "remote",
+            "add",
+            "origin",
+            "https://github.com/octo/hello.git",
+        ])
+        .await
+        .unwrap();
+        fs::write(temp_path.join("README.md"), "hello").unwrap();
+        fs::create_dir(temp_path.join(".openhands")).unwrap();
+        fs::write(temp_path.join(".openhands/memory.json"), "[]").unwrap();
+
+        let args = serde_json::json!({
+            "operation": "create_pr",
+            "title": "Add README",
+            "base": "main",
+            "dry_run": true
+        });
+
+        let result = tool.call(args).await.unwrap();
+        assert!(result.contains("Dry run"));
+        assert!(result.contains("octo/hello"));
+        assert!(result.contains("openhands/add-readme"));
+        assert!(result.contains("README.md"));
+        assert!(!result.contains(".openhands"));
+
+        let args = serde_json::json!({
+            "operation": "create_pr",
+            "title": "Add README",
+            "branch": "--upload-pack=touch pwned",
+            "base": "main",
+            "dry_run": true
+        });
+        assert!(
+            tool.call(args)
+                .await
+                .unwrap_err()
+                .starts_with("Invalid branch name")
+        );
+
+        tool.git(&[
+            "remote",
+            "set-url",
+            "origin",
+            "https://evil.example.com/octo/hello.git",
+        ])
+        .await
+        .unwrap();
+        let args = serde_json::json!({
+            "operation": "create_pr",
+            "title": "Add README",
+            "base": "main",
+            "dry_run": true
+        });
+        assert!(
+            tool.call(args)
+                .await
+                .unwrap_err()
+                .contains("evil.example.com")
+        );
+
+        tool.git(&[
+            "remote",
+            "set-url",
+            "origin",
+            "https://gitlab.com/group/sub/project.git",
+        ])
+        .await
+        .unwrap();
+        let args = serde_json::json!({
+            "operation": "create_pr",
+            "title": "Add README",
+            "base": "main",
+            "dry_run": true
+        });
+        let result = tool.call(args).await.unwrap();
+        assert!(
+            result.contains("group/sub/project on gitlab.com"),
+            "{}",
+            result
+        );
+    }
+
+    /// A repository in `dir` on branch `branch` with one commit.
+    async fn repo_with_commit(tool: &GitHubTool, dir: &std::path::Path, branch: &str) {
+        tool.git(&["init", "-q", "-b", branch]).await.unwrap();
+        fs::write(dir.join("README.md"), "hello").unwrap();
+        tool.git(&["add", "-A"]).await.unwrap();
+        tool.git(&[
+            "-c",
+            "user.name=Test",
+            "-c",
+            "user.email=test@localhost",
+            "commit",
+            "-q",
+            "-m",
+            "init",
+        ])
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_default_branch_of_remote() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = GitHubTool::new(temp_dir.path().to_path_buf(), config());
+        repo_with_commit(&tool, temp_dir.path(), "trunk").await;
+
+        let url = temp_dir.path().to_string_lossy().to_string();
+        assert_eq!(tool.default_branch(&url, None).await.unwrap(), "trunk");
+    }
+
+    #[tokio::test]
+    async fn test_failed_push_restores_branch() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+        // Nothing listens on the discard port, so the push fails.
+        let tool = GitHubTool::new(
+            temp_path.to_path_buf(),
+            GitHubConfig {
+                api_base: "http://127.0.0.1:9/api/v3".to_string(),
+                ..config()
+            },
+        );
+        repo_with_commit(&tool, temp_path, "work").await;
+        tool.git(&[
+            "remote",
+            "add",
+            "origin",
+            "https://127.0.0.1:9/octo/hello.git",
+        ])
+        .await
+        .unwrap();
+        fs::write(temp_path.join("README.md"), "changed").unwrap();
+
+        let args = serde_json::json!({
+            "operation": "create_pr",
+            "title": "Change README",
+            "base": "main"
+        });
+        let error = tool.call(args).await.unwrap_err();
+        assert!(error.contains("git push failed"), "{}", error);
+        assert!(!error.contains("secret-token"));
+
+        assert_eq!(
+            tool.git(&["symbolic-ref", "--short", "HEAD"])
+                .await
+                .unwrap(),
+            "work"
+        );
+        assert_eq!(
+            tool.git(&["rev-list", "--count", "HEAD"]).await.unwrap(),
+            "1"
+        );
+        assert!(
+            tool.git(&["branch", "--list", "openhands/*"])
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            fs::read_to_string(temp_path.join("README.md")).unwrap(),
+            "changed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_pr_requires_github_remote() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = GitHubTool::new(temp_dir.path().to_path_buf(), config());
+        tool.git(&["init", "-q"]).await.unwrap();
+
+        let args = serde_json::json!({
+            "operation": "create_pr",
+            "title": "Anything"
+        });
+        assert!(tool.call(args).await.is_err());
+    }
+}
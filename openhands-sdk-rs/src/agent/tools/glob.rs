@@ -1,19 +1,27 @@
 use async_trait::async_trait;
-use glob::glob;
 use serde_json::Value;
 use std::fs;
 use std::path::PathBuf;
 use std::time::SystemTime;
 
-use super::Tool;
+use super::{Tool, WorkingDir};
+use crate::paths::{encode_path, glob_paths};
 
 pub struct GlobTool {
-    working_dir: PathBuf,
+    working_dir: WorkingDir,
 }
 
 impl GlobTool {
     pub fn new(working_dir: PathBuf) -> Self {
-        Self { working_dir }
+        Self {
+            working_dir: WorkingDir::new(working_dir),
+        }
+    }
+
+    /// Search the current directory of `cwd` by default.
+    pub fn with_cwd(mut self, cwd: WorkingDir) -> Self {
+        self.working_dir = cwd;
+        self
     }
 }
 
@@ -29,7 +37,7 @@ impl Tool for GlobTool {
             Returns matching file paths sorted by modification time. \
             Only the first 100 results are returned. \
             Your current working directory is: {}",
-            self.working_dir.display()
+            self.working_dir.get().display()
         )
     }
 
@@ -50,6 +58,10 @@ impl Tool for GlobTool {
         })
     }
 
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
     async fn call(&self, args: Value) -> Result<String, String> {
         let pattern = args
             .get("pattern")
@@ -57,9 +69,9 @@ impl Tool for GlobTool {
             .ok_or("Missing 'pattern' argument")?;
 
         let search_path = if let Some(path_str) = args.get("path").and_then(|v| v.as_str()) {
-            PathBuf::from(path_str)
+            self.working_dir.resolve(path_str)
         } else {
-            self.working_dir.clone()
+            self.working_dir.get()
         };
 
         // Validate search path
@@ -70,29 +82,15 @@ impl Tool for GlobTool {
             ));
         }
 
-        // Build full glob pattern
-        let full_pattern = search_path.join(pattern);
-        let pattern_str = full_pattern
-            .to_str()
-            .ok_or("Invalid path encoding")?;
-
         // Execute glob search
         let mut matches: Vec<(PathBuf, SystemTime)> = Vec::new();
-        
-        for entry in glob(pattern_str).map_err(|e| e.to_string())? {
-            match entry {
-                Ok(path) => {
-                    if path.is_file() {
-                        if let Ok(metadata) = fs::metadata(&path) {
-                            if let Ok(modified) = metadata.modified() {
-                                matches.push((path, modified));
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    tracing::warn!("Glob error: {}", e);
-                }
+
+        for path in glob_paths(&search_path, pattern)? {
+            if path.is_file()
+                && let Ok(metadata) = fs::metadata(&path)
+                && let Ok(modified) = metadata.modified()
+            {
+                matches.push((path, modified));
             }
 
             // Limit to 100 files
@@ -102,12 +100,12 @@ impl Tool for GlobTool {
         }
 
         // Sort by modification time (newest first)
-        matches.sort_by(|a, b| b.1.cmp(&a.1));
+        matches.sort_by_key(|m| std::cmp::Reverse(m.1));
 
         let truncated = matches.len() >= 100;
         let file_paths: Vec<String> = matches
             .into_iter()
-            .map(|(path, _)| path.to_string_lossy().to_string())
+            .map(|(path, _)| encode_path(&path).into_owned())
             .collect();
 
         // Format output
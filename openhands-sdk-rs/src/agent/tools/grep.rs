@@ -5,15 +5,24 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
-use super::Tool;
+use super::{Tool, WorkingDir};
+use crate::paths::encode_path;
 
 pub struct GrepTool {
-    working_dir: PathBuf,
+    working_dir: WorkingDir,
 }
 
 impl GrepTool {
     pub fn new(working_dir: PathBuf) -> Self {
-        Self { working_dir }
+        Self {
+            working_dir: WorkingDir::new(working_dir),
+        }
+    }
+
+    /// Search the current directory of `cwd` by default.
+    pub fn with_cwd(mut self, cwd: WorkingDir) -> Self {
+        self.working_dir = cwd;
+        self
     }
 
     fn search_directory(
@@ -38,10 +47,10 @@ impl GrepTool {
             let path = entry.path();
 
             // Skip hidden files and directories
-            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                if name.starts_with('.') {
-                    continue;
-                }
+            if let Some(name) = path.file_name().and_then(|n| n.to_str())
+                && name.starts_with('.')
+            {
+                continue;
             }
 
             if path.is_dir() {
@@ -49,23 +58,20 @@ impl GrepTool {
                 self.search_directory(&path, pattern, include_filter, matches)?;
             } else if path.is_file() {
                 // Check include filter
-                if let Some(filter) = include_filter {
-                    if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
-                        if !filter.is_match(filename) {
-                            continue;
-                        }
-                    }
+                if let Some(filter) = include_filter
+                    && let Some(filename) = path.file_name().and_then(|n| n.to_str())
+                    && !filter.is_match(filename)
+                {
+                    continue;
                 }
 
                 // Try to read and search file content
-                if let Ok(content) = fs::read_to_string(&path) {
-                    if pattern.is_match(&content) {
-                        if let Ok(metadata) = fs::metadata(&path) {
-                            if let Ok(modified) = metadata.modified() {
-                                matches.push((path.clone(), modified));
-                            }
-                        }
-                    }
+                if let Ok(content) = fs::read_to_string(&path)
+                    && pattern.is_match(&content)
+                    && let Ok(metadata) = fs::metadata(&path)
+                    && let Ok(modified) = metadata.modified()
+                {
+                    matches.push((path.clone(), modified));
                 }
             }
         }
@@ -87,7 +93,7 @@ impl Tool for GrepTool {
             Returns matching file paths sorted by modification time. \
             Only the first 100 results are returned. \
             Your current working directory is: {}",
-            self.working_dir.display()
+            self.working_dir.get().display()
         )
     }
 
@@ -112,6 +118,10 @@ impl Tool for GrepTool {
         })
     }
 
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
     async fn call(&self, args: Value) -> Result<String, String> {
         let pattern_str = args
             .get("pattern")
@@ -123,9 +133,9 @@ impl Tool for GrepTool {
             .map_err(|e| format!("Invalid regex pattern: {}", e))?;
 
         let search_path = if let Some(path_str) = args.get("path").and_then(|v| v.as_str()) {
-            PathBuf::from(path_str)
+            self.working_dir.resolve(path_str)
         } else {
-            self.working_dir.clone()
+            self.working_dir.get()
         };
 
         // Validate search path
@@ -159,12 +169,12 @@ impl Tool for GrepTool {
         self.search_directory(&search_path, &pattern, include_filter.as_ref(), &mut matches)?;
 
         // Sort by modification time (newest first)
-        matches.sort_by(|a, b| b.1.cmp(&a.1));
+        matches.sort_by_key(|m| std::cmp::Reverse(m.1));
 
         let truncated = matches.len() >= 100;
         let file_paths: Vec<String> = matches
             .into_iter()
-            .map(|(path, _)| path.to_string_lossy().to_string())
+            .map(|(path, _)| encode_path(&path).into_owned())
             .collect();
 
         // Format output
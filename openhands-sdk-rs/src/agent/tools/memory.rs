@@ -0,0 +1,168 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+
+use super::Tool;
+use crate::memory::MemoryStore;
+
+const DEFAULT_RECALL_LIMIT: usize = 5;
+
+/// Saves a note to the long-term memory of the current project.
+pub struct RememberTool {
+    store: Arc<MemoryStore>,
+}
+
+impl RememberTool {
+    pub fn new(store: Arc<MemoryStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl Tool for RememberTool {
+    fn name(&self) -> String {
+        "remember".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Save a note to long-term memory so it is available in future conversations about this project \
+        (e.g. build commands, conventions, gotchas). Saving under an existing key replaces the old note."
+            .to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "key": {
+                    "type": "string",
+                    "description": "Short identifier for the memory (e.g. 'test_command')"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "The information to remember"
+                }
+            },
+            "required": ["key", "content"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<String, String> {
+        let key = args
+            .get("key")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing 'key' argument")?;
+        let content = args
+            .get("content")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing 'content' argument")?;
+
+        self.store.remember(key, content).await?;
+        Ok(format!("Remembered '{}'", key))
+    }
+}
+
+/// Searches the long-term memory of the current project.
+pub struct RecallTool {
+    store: Arc<MemoryStore>,
+}
+
+impl RecallTool {
+    pub fn new(store: Arc<MemoryStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl Tool for RecallTool {
+    fn name(&self) -> String {
+        "recall".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Search long-term memory for notes saved in earlier conversations about this project."
+            .to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "What you want to recall"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of memories to return (default 5)"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
+    async fn call(&self, args: Value) -> Result<String, String> {
+        let query = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing 'query' argument")?;
+        let limit = args
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .map(|n| n as usize)
+            .unwrap_or(DEFAULT_RECALL_LIMIT);
+
+        let memories = self.store.recall(query, limit).await?;
+        if memories.is_empty() {
+            return Ok(format!("No memories found for '{}'", query));
+        }
+
+        let lines: Vec<String> = memories
+            .iter()
+            .map(|m| format!("- {}: {}", m.key, m.content))
+            .collect();
+        Ok(format!(
+            "Found {} memory(ies) for '{}':\n{}",
+            memories.len(),
+            query,
+            lines.join("\n")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_remember_then_recall() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = Arc::new(MemoryStore::for_workspace(temp_dir.path()).unwrap());
+
+        let remember = RememberTool::new(store.clone());
+        let recall = RecallTool::new(store);
+
+        let args = serde_json::json!({
+            "key": "lint",
+            "content": "Run clippy with -D warnings before committing"
+        });
+        remember.call(args).await.unwrap();
+
+        let result = recall
+            .call(serde_json::json!({ "query": "clippy" }))
+            .await
+            .unwrap();
+        assert!(result.contains("lint: Run clippy"));
+
+        let result = recall
+            .call(serde_json::json!({ "query": "deployment" }))
+            .await
+            .unwrap();
+        assert!(result.contains("No memories found"));
+    }
+}
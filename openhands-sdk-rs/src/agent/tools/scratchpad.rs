@@ -0,0 +1,296 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::Tool;
+
+/// Largest note the scratchpad keeps.
+pub const MAX_NOTE_BYTES: usize = 1024 * 1024;
+/// Characters of a note returned by one `get` before it is truncated.
+const MAX_GET_CHARS: usize = 20_000;
+
+/// Directory holding the scratchpads of all conversations: `OPENHANDS_SCRATCHPAD_DIR`, or
+/// `openhands-scratchpad` in the system temp directory.
+pub fn scratchpad_root() -> PathBuf {
+    std::env::var_os("OPENHANDS_SCRATCHPAD_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("openhands-scratchpad"))
+}
+
+/// Remove the scratchpad of conversation `id`.
+pub fn remove_scratchpad(id: &str) {
+    let _ = fs::remove_dir_all(scratchpad_root().join(id));
+}
+
+/// Named notes the agent keeps for itself, stored outside the workspace so they neither
+/// show up in the repository nor need to stay in the context window.
+pub struct ScratchpadTool {
+    dir: PathBuf,
+}
+
+impl ScratchpadTool {
+    /// A scratchpad whose notes are files in `dir`.
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The scratchpad of conversation `id`, under [`scratchpad_root`].
+    pub fn for_conversation(id: &str) -> Self {
+        Self::new(scratchpad_root().join(id))
+    }
+
+    fn note_path(&self, name: &str) -> Result<PathBuf, String> {
+        let valid = !name.is_empty()
+            && name.len() <= 100
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(format!(
+                "Invalid note name '{}': use letters, digits, '-', '_' and '.'",
+                name
+            ));
+        }
+        Ok(self.dir.join(name))
+    }
+
+    fn read(&self, name: &str) -> Result<String, String> {
+        let path = self.note_path(name)?;
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(format!("No note named '{}'", name))
+            }
+            Err(e) => Err(format!("Failed to read note '{}': {}", name, e)),
+        }
+    }
+
+    fn write(&self, name: &str, content: &str, append: bool) -> Result<usize, String> {
+        let path = self.note_path(name)?;
+        let existing = match fs::metadata(&path) {
+            Ok(metadata) if append => metadata.len() as usize,
+            _ => 0,
+        };
+        let size = existing + content.len();
+        if size > MAX_NOTE_BYTES {
+            return Err(format!(
+                "Note '{}' would be {} bytes, over the {} byte limit",
+                name, size, MAX_NOTE_BYTES
+            ));
+        }
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&path)
+            .map_err(|e| format!("Failed to open note '{}': {}", name, e))?;
+        file.write_all(content.as_bytes())
+            .map_err(|e| format!("Failed to write note '{}': {}", name, e))?;
+        Ok(size)
+    }
+
+    fn list(&self) -> Result<String, String> {
+        let mut notes: Vec<(String, u64)> = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+                    Some((entry.file_name().to_str()?.to_string(), metadata.len()))
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        if notes.is_empty() {
+            return Ok("The scratchpad is empty".to_string());
+        }
+        notes.sort();
+        let lines: Vec<String> = notes
+            .iter()
+            .map(|(name, size)| format!("- {} ({} bytes)", name, size))
+            .collect();
+        Ok(format!("{} note(s):\n{}", notes.len(), lines.join("\n")))
+    }
+}
+
+/// Lines `start..=end` (1-based) of `content`, or all of it.
+fn line_range(content: &str, start: Option<usize>, end: Option<usize>) -> String {
+    if start.is_none() && end.is_none() {
+        return content.to_string();
+    }
+    let start = start.unwrap_or(1).max(1);
+    let end = end.unwrap_or(usize::MAX);
+    content
+        .lines()
+        .enumerate()
+        .filter(|(i, _)| (start..=end).contains(&(i + 1)))
+        .map(|(_, line)| line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[async_trait]
+impl Tool for ScratchpadTool {
+    fn name(&self) -> String {
+        "scratchpad".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Keep named notes for this conversation outside the workspace, e.g. long command output \
+        or intermediate analysis you want to come back to without keeping it in context. \
+        Commands: set, append, get (optionally a line range), list, delete."
+            .to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "command": {
+                    "type": "string",
+                    "enum": ["set", "append", "get", "list", "delete"],
+                    "description": "What to do"
+                },
+                "name": {
+                    "type": "string",
+                    "description": "Note name (letters, digits, '-', '_' and '.'); required except for list"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "Text to store (set) or add to the end of the note (append)"
+                },
+                "start_line": {
+                    "type": "integer",
+                    "description": "For get: first line to return (1-based)"
+                },
+                "end_line": {
+                    "type": "integer",
+                    "description": "For get: last line to return (inclusive)"
+                }
+            },
+            "required": ["command"]
+        })
+    }
+
+    /// Notes live outside the workspace.
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
+    async fn call(&self, args: Value) -> Result<String, String> {
+        let command = args
+            .get("command")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing 'command' argument")?;
+        if command == "list" {
+            return self.list();
+        }
+
+        let name = args
+            .get("name")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing 'name' argument")?;
+        let content = || {
+            args.get("content")
+                .and_then(|v| v.as_str())
+                .ok_or("Missing 'content' argument")
+        };
+        let line = |key: &str| args.get(key).and_then(|v| v.as_u64()).map(|n| n as usize);
+
+        match command {
+            "set" => {
+                let size = self.write(name, content()?, false)?;
+                Ok(format!("Saved note '{}' ({} bytes)", name, size))
+            }
+            "append" => {
+                let size = self.write(name, content()?, true)?;
+                Ok(format!("Appended to note '{}' ({} bytes)", name, size))
+            }
+            "get" => {
+                let note = line_range(&self.read(name)?, line("start_line"), line("end_line"));
+                if note.chars().count() <= MAX_GET_CHARS {
+                    return Ok(note);
+                }
+                let truncated: String = note.chars().take(MAX_GET_CHARS).collect();
+                Ok(format!(
+                    "{}\n[Truncated at {} characters; use start_line/end_line to read the rest]",
+                    truncated, MAX_GET_CHARS
+                ))
+            }
+            "delete" => {
+                let path = self.note_path(name)?;
+                fs::remove_file(&path).map_err(|_| format!("No note named '{}'", name))?;
+                Ok(format!("Deleted note '{}'", name))
+            }
+            _ => Err(format!("Unknown command: {}", command)),
+        }
+    }
+
+    /// Notes live on the server, not in the sandbox.
+    fn runs_on_host(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_scratchpad() {
+        let temp_dir = TempDir::new().unwrap();
+        let pad = ScratchpadTool::new(temp_dir.path().join("conversation"));
+
+        let result = pad.call(json!({ "command": "list" })).await.unwrap();
+        assert_eq!(result, "The scratchpad is empty");
+
+        pad.call(json!({ "command": "set", "name": "build.log", "content": "one\ntwo\n" }))
+            .await
+            .unwrap();
+        pad.call(json!({ "command": "append", "name": "build.log", "content": "three\n" }))
+            .await
+            .unwrap();
+        let note = pad
+            .call(json!({ "command": "get", "name": "build.log" }))
+            .await
+            .unwrap();
+        assert_eq!(note, "one\ntwo\nthree\n");
+        let lines = pad
+            .call(json!({ "command": "get", "name": "build.log", "start_line": 2, "end_line": 3 }))
+            .await
+            .unwrap();
+        assert_eq!(lines, "two\nthree");
+
+        let list = pad.call(json!({ "command": "list" })).await.unwrap();
+        assert!(list.contains("- build.log (14 bytes)"));
+
+        let err = pad
+            .call(json!({ "command": "set", "name": "../escape", "content": "x" }))
+            .await
+            .unwrap_err();
+        assert!(err.contains("Invalid note name"));
+
+        let big = "x".repeat(MAX_NOTE_BYTES);
+        let err = pad
+            .call(json!({ "command": "append", "name": "build.log", "content": big }))
+            .await
+            .unwrap_err();
+        assert!(err.contains("byte limit"));
+
+        pad.call(json!({ "command": "delete", "name": "build.log" }))
+            .await
+            .unwrap();
+        let err = pad
+            .call(json!({ "command": "get", "name": "build.log" }))
+            .await
+            .unwrap_err();
+        assert_eq!(err, "No note named 'build.log'");
+    }
+}
@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::Tool;
+use crate::index::{CodeIndex, EmbeddingProvider, HashingEmbeddingProvider, provider_from_env};
+
+const DEFAULT_TOP_K: usize = 5;
+const MAX_SNIPPET_LINES: usize = 20;
+
+pub struct SemanticSearchTool {
+    working_dir: PathBuf,
+    index: CodeIndex,
+}
+
+impl SemanticSearchTool {
+    pub fn new(working_dir: PathBuf, provider: Arc<dyn EmbeddingProvider>) -> Self {
+        Self {
+            index: CodeIndex::new(working_dir.clone(), provider),
+            working_dir,
+        }
+    }
+
+    /// Use OpenAI embeddings when `OPENAI_API_KEY` is set, otherwise the local hashing model.
+    pub fn from_env(working_dir: PathBuf) -> Self {
+        let provider =
+            provider_from_env().unwrap_or_else(|| Arc::new(HashingEmbeddingProvider::default()));
+        Self::new(working_dir, provider)
+    }
+}
+
+#[async_trait]
+impl Tool for SemanticSearchTool {
+    fn name(&self) -> String {
+        "semantic_search".to_string()
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Search the codebase by meaning rather than exact text. \
+            Use this for conceptual queries (e.g. 'where are passwords hashed?'); use grep for exact patterns. \
+            Returns the most relevant code snippets with file paths and line ranges. \
+            Your current working directory is: {}",
+            self.working_dir.display()
+        )
+    }
+
+    fn parameters(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Natural language description of the code you are looking for"
+                },
+                "top_k": {
+                    "type": "integer",
+                    "description": "Number of snippets to return (default 5)"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn is_read_only(&self, _args: &Value) -> bool {
+        true
+    }
+
+    async fn call(&self, args: Value) -> Result<String, String> {
+        let query = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing 'query' argument")?;
+        let top_k = args
+            .get("top_k")
+            .and_then(|v| v.as_u64())
+            .map(|n| n as usize)
+            .unwrap_or(DEFAULT_TOP_K);
+
+        self.index.refresh().await?;
+        let hits = self.index.search(query, top_k).await?;
+
+        if hits.is_empty() {
+            return Ok(format!(
+                "No indexed code found in directory '{}'",
+                self.working_dir.display()
+            ));
+        }
+
+        let mut output = format!("Top {} result(s) for '{}':\n", hits.len(), query);
+        for hit in hits {
+            let snippet: Vec<&str> = hit.snippet.lines().take(MAX_SNIPPET_LINES).collect();
+            output.push_str(&format!(
+                "\n{}:{}-{} (score {:.3})\n{}\n",
+                hit.path,
+                hit.start_line,
+                hit.end_line,
+                hit.score,
+                snippet.join("\n")
+            ));
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_semantic_search_ranks_relevant_file_first() {
+        crate::index::use_test_index_root();
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+
+        fs::write(
+            temp_path.join("db.rs"),
+            "fn open_database_connection(url: &str) -> Connection {}",
+        )
+        .unwrap();
+        fs::write(temp_path.join("ui.rs"), "fn draw_button(label: &str) {}").unwrap();
+
+        let tool = SemanticSearchTool::new(
+            temp_path.to_path_buf(),
+            Arc::new(HashingEmbeddingProvider::default()),
+        );
+        let args = serde_json::json!({
+            "query": "database connection",
+            "top_k": 1
+        });
+
+        let result = tool.call(args).await.unwrap();
+        assert!(result.contains("db.rs:1-1"));
+        assert!(!result.contains("ui.rs"));
+    }
+}
@@ -0,0 +1,290 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::path::PathBuf;
+use tokio::process::Command;
+use uuid::Uuid;
+
+use super::{Tool, WorkingDir};
+use crate::runtime::env::ToolEnv;
+
+const DEFAULT_READ_LINES: i64 = 200;
+
+/// Named, long-lived shells backed by tmux, so a dev server can keep running in one terminal
+/// while tests run in another.
+///
+/// Each tool instance uses its own tmux server socket; its sessions are killed when the tool
+/// is dropped.
+pub struct TerminalTool {
+    working_dir: WorkingDir,
+    socket: String,
+    env: ToolEnv,
+}
+
+impl TerminalTool {
+    pub fn new(working_dir: PathBuf) -> Self {
+        Self {
+            working_dir: WorkingDir::new(working_dir),
+            socket: format!("openhands-{}", Uuid::new_v4().simple()),
+            env: ToolEnv::default(),
+        }
+    }
+
+    /// Start new terminals in the current directory of `cwd`.
+    pub fn with_cwd(mut self, cwd: WorkingDir) -> Self {
+        self.working_dir = cwd;
+        self
+    }
+
+    /// Start the terminals' tmux server with `env`.
+    pub fn with_env(mut self, env: ToolEnv) -> Self {
+        self.env = env;
+        self
+    }
+
+    async fn tmux(&self, args: &[&str]) -> Result<String, String> {
+        let output = Command::from(self.env.command("tmux"))
+            .arg("-L")
+            .arg(&self.socket)
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run tmux: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Exact-match target, so `web` does not resolve to a session named `webpack`.
+    fn target(name: &str) -> String {
+        format!("={}:", name)
+    }
+}
+
+impl Drop for TerminalTool {
+    fn drop(&mut self) {
+        let _ = std::process::Command::new("tmux")
+            .args(["-L", &self.socket, "kill-server"])
+            .output();
+    }
+}
+
+#[async_trait]
+impl Tool for TerminalTool {
+    fn name(&self) -> String {
+        "terminal".to_string()
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "Named terminal sessions that keep running between calls. Use them for long-running \
+            processes (dev servers, watchers) instead of backgrounding with '&'. \
+            Commands: 'create' opens a terminal, 'send' types keys into it (followed by Enter \
+            unless enter=false), 'read' shows its recent output, 'list' lists terminals and \
+            'kill' closes one. New terminals start in: {}",
+            self.working_dir.get().display()
+        )
+    }
+
+    fn parameters(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "command": {
+                    "type": "string",
+                    "enum": ["create", "send", "read", "list", "kill"],
+                    "description": "The terminal operation"
+                },
+                "name": {
+                    "type": "string",
+                    "description": "Terminal name (required except for 'list')"
+                },
+                "keys": {
+                    "type": "string",
+                    "description": "Text to type for 'send'. Use 'C-c' with literal=false to interrupt"
+                },
+                "enter": {
+                    "type": "boolean",
+                    "description": "Press Enter after the keys (default true)"
+                },
+                "literal": {
+                    "type": "boolean",
+                    "description": "Type the keys as text (default true); false interprets tmux key names such as 'C-c' or 'Up'"
+                },
+                "lines": {
+                    "type": "integer",
+                    "description": "Lines of scrollback to return for 'read' (default 200)"
+                }
+            },
+            "required": ["command"]
+        })
+    }
+
+    fn is_read_only(&self, args: &Value) -> bool {
+        matches!(
+            args.get("command").and_then(|v| v.as_str()),
+            Some("read" | "list")
+        )
+    }
+
+    async fn call(&self, args: Value) -> Result<String, String> {
+        let command = args
+            .get("command")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing 'command' argument")?;
+
+        if command == "list" {
+            return match self.tmux(&["list-sessions", "-F", "#{session_name}"]).await {
+                Ok(names) if !names.trim().is_empty() => Ok(names.trim_end().to_string()),
+                // No tmux server yet means no terminals.
+                _ => Ok("No terminals".to_string()),
+            };
+        }
+
+        let name = args
+            .get("name")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing 'name' argument")?;
+        if name.is_empty() || name.contains([':', '.']) {
+            return Err(format!("Invalid terminal name '{}'", name));
+        }
+        let target = Self::target(name);
+
+        match command {
+            "create" => {
+                let dir = self.working_dir.get();
+                let dir = dir.to_string_lossy();
+                self.tmux(&[
+                    "new-session",
+                    "-d",
+                    "-s",
+                    name,
+                    "-c",
+                    &dir,
+                    "-x",
+                    "200",
+                    "-y",
+                    "50",
+                ])
+                .await?;
+                Ok(format!("Created terminal '{}'", name))
+            }
+            "send" => {
+                let keys = args
+                    .get("keys")
+                    .and_then(|v| v.as_str())
+                    .ok_or("Missing 'keys' argument")?;
+                let literal = args
+                    .get("literal")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                let enter = args.get("enter").and_then(|v| v.as_bool()).unwrap_or(true);
+
+                let mut send = vec!["send-keys", "-t", &target];
+                if literal {
+                    send.push("-l");
+                }
+                // Keys starting with `-` are keys, not tmux options.
+                send.extend(["--", keys]);
+                self.tmux(&send).await?;
+                if enter {
+                    self.tmux(&["send-keys", "-t", &target, "Enter"]).await?;
+                }
+                Ok(format!("Sent keys to terminal '{}'", name))
+            }
+            "read" => {
+                let lines = args
+                    .get("lines")
+                    .and_then(|v| v.as_i64())
+                    .unwrap_or(DEFAULT_READ_LINES)
+                    .max(1);
+                let start = format!("-{}", lines);
+                let output = self
+                    .tmux(&["capture-pane", "-p", "-J", "-t", &target, "-S", &start])
+                    .await?;
+                let output = output.trim_end();
+                // Scrollback plus the visible screen can exceed the requested line count.
+                let skip = output.lines().count().saturating_sub(lines as usize);
+                Ok(output.lines().skip(skip).collect::<Vec<_>>().join("\n"))
+            }
+            "kill" => {
+                self.tmux(&["kill-session", "-t", &target]).await?;
+                Ok(format!("Killed terminal '{}'", name))
+            }
+            other => Err(format!("Unknown command '{}'", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_named_terminals() {
+        if std::process::Command::new("tmux")
+            .arg("-V")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let tool = TerminalTool::new(temp_dir.path().to_path_buf());
+        let call = |args: Value| tool.call(args);
+
+        call(serde_json::json!({"command": "create", "name": "server"}))
+            .await
+            .unwrap();
+        call(serde_json::json!({"command": "create", "name": "tests"}))
+            .await
+            .unwrap();
+        call(serde_json::json!({"command": "send", "name": "server", "keys": "echo serving-$((40+2))"}))
+            .await
+            .unwrap();
+
+        let mut output = String::new();
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            output = call(serde_json::json!({"command": "read", "name": "server"}))
+                .await
+                .unwrap();
+            if output.contains("serving-42") {
+                break;
+            }
+        }
+        assert!(output.contains("serving-42"));
+        let tests = call(serde_json::json!({"command": "read", "name": "tests"}))
+            .await
+            .unwrap();
+        assert!(!tests.contains("serving-42"));
+
+        call(serde_json::json!({"command": "send", "name": "tests", "keys": "-Rdashed", "enter": false}))
+            .await
+            .unwrap();
+        let mut tests = String::new();
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            tests = call(serde_json::json!({"command": "read", "name": "tests"}))
+                .await
+                .unwrap();
+            if tests.contains("-Rdashed") {
+                break;
+            }
+        }
+        assert!(tests.contains("-Rdashed"));
+
+        call(serde_json::json!({"command": "kill", "name": "tests"}))
+            .await
+            .unwrap();
+        let list = call(serde_json::json!({"command": "list"})).await.unwrap();
+        assert_eq!(list, "server");
+        assert!(
+            call(serde_json::json!({"command": "read", "name": "tests"}))
+                .await
+                .is_err()
+        );
+    }
+}
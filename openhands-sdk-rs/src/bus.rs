@@ -0,0 +1,450 @@
+//! In-process event bus. Subsystems publish what happens (agent events, bash commands and
+//! their output, file changes) and sinks decide what to do with it: fan it out to
+//! subscribers, append it to a log, post it to a webhook or count it.
+//!
+//! Publishing never blocks or fails; sinks that do I/O hand the work off to a task. Live
+//! subscribers read from a bounded buffer, so a slow client cannot make the server's memory
+//! grow: once it falls a full buffer behind, its [`DropPolicy`] decides what it gets.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::agent::StepObserver;
+use crate::events::{Event, FileChange, LLMDelta};
+use crate::models::BashEvent;
+use crate::redact::{Redactor, redactor};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BusEvent {
+    pub timestamp: DateTime<Utc>,
+    /// The conversation the event belongs to; `None` for server-wide events such as bash
+    /// commands run through the MCP tools.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+    #[serde(flatten)]
+    pub payload: BusPayload,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "topic", content = "event", rename_all = "snake_case")]
+pub enum BusPayload {
+    Agent(Event),
+    Bash(BashEvent),
+    FileChange(FileChange),
+    /// Part of an LLM response while it streams in. Only live subscribers receive deltas:
+    /// the log and webhook sinks skip them, as the response is published again as agent
+    /// events once complete.
+    LlmDelta(LLMDelta),
+    /// Delivered to a subscriber in place of `missed` events it fell too far behind to
+    /// receive. Never published.
+    Gap {
+        missed: u64,
+    },
+}
+
+impl BusEvent {
+    pub fn new(conversation_id: Option<&str>, payload: BusPayload) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            conversation_id: conversation_id.map(str::to_string),
+            payload,
+        }
+    }
+
+    /// A short name for the kind of event, e.g. `agent.action` or `bash.output`.
+    pub fn kind(&self) -> &'static str {
+        match &self.payload {
+            BusPayload::Agent(Event::Message(_)) => "agent.message",
+            BusPayload::Agent(Event::Action(_)) => "agent.action",
+            BusPayload::Agent(Event::Observation(_)) => "agent.observation",
+            BusPayload::Bash(BashEvent::BashCommand(_)) => "bash.command",
+            BusPayload::Bash(BashEvent::BashOutput(_)) => "bash.output",
+            BusPayload::FileChange(_) => "file_change",
+            BusPayload::LlmDelta(LLMDelta::Reasoning { .. }) => "llm.reasoning",
+            BusPayload::LlmDelta(LLMDelta::Content { .. }) => "llm.content",
+            BusPayload::LlmDelta(LLMDelta::ToolCall { .. }) => "llm.tool_call",
+            BusPayload::Gap { .. } => "gap",
+        }
+    }
+
+    /// A copy with secrets redacted, for sinks that store or forward events.
+    pub fn redacted(&self, redactor: &Redactor) -> Self {
+        let payload = match &self.payload {
+            BusPayload::Agent(event) => BusPayload::Agent(redactor.redact_event(event)),
+            BusPayload::Bash(event) => BusPayload::Bash(redactor.redact_bash_event(event)),
+            BusPayload::FileChange(change) => {
+                let mut change = change.clone();
+                for edit in &mut change.edits {
+                    for line in &mut edit.new_lines {
+                        *line = redactor.redact(line);
+                    }
+                }
+                BusPayload::FileChange(change)
+            }
+            BusPayload::LlmDelta(LLMDelta::Reasoning { text }) => {
+                BusPayload::LlmDelta(LLMDelta::Reasoning {
+                    text: redactor.redact(text),
+                })
+            }
+            BusPayload::LlmDelta(LLMDelta::Content { text }) => {
+                BusPayload::LlmDelta(LLMDelta::Content {
+                    text: redactor.redact(text),
+                })
+            }
+            BusPayload::LlmDelta(LLMDelta::ToolCall {
+                call_id,
+                tool_name,
+                arguments,
+            }) => BusPayload::LlmDelta(LLMDelta::ToolCall {
+                call_id: call_id.clone(),
+                tool_name: tool_name.clone(),
+                arguments: redactor.redact(arguments),
+            }),
+            BusPayload::Gap { missed } => BusPayload::Gap { missed: *missed },
+        };
+        Self {
+            timestamp: self.timestamp,
+            conversation_id: self.conversation_id.clone(),
+            payload,
+        }
+    }
+}
+
+/// Receives every event published on the bus, on the publisher's task, so it must not block.
+pub trait EventSink: Send + Sync {
+    fn handle(&self, event: &BusEvent);
+}
+
+#[derive(Default)]
+pub struct EventBus {
+    sinks: RwLock<Vec<Arc<dyn EventSink>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn add_sink(&self, sink: Arc<dyn EventSink>) {
+        self.sinks.write().unwrap().push(sink);
+    }
+
+    pub fn publish(&self, event: BusEvent) {
+        for sink in self.sinks.read().unwrap().iter() {
+            sink.handle(&event);
+        }
+    }
+
+    /// Publish an agent event of a conversation, followed by the file changes it recorded.
+    pub fn publish_agent_event(&self, conversation_id: &str, event: &Event) {
+        self.publish(BusEvent::new(
+            Some(conversation_id),
+            BusPayload::Agent(event.clone()),
+        ));
+        if let Event::Observation(observation) = event {
+            for change in &observation.file_changes {
+                self.publish(BusEvent::new(
+                    Some(conversation_id),
+                    BusPayload::FileChange(change.clone()),
+                ));
+            }
+        }
+    }
+
+    /// An observer that publishes the events of an agent step in `conversation_id`.
+    pub fn observer(self: &Arc<Self>, conversation_id: &str) -> BusObserver {
+        BusObserver {
+            bus: self.clone(),
+            conversation_id: conversation_id.to_string(),
+        }
+    }
+}
+
+pub struct BusObserver {
+    bus: Arc<EventBus>,
+    conversation_id: String,
+}
+
+#[async_trait]
+impl StepObserver for BusObserver {
+    async fn on_event(&self, event: &Event) {
+        self.bus.publish_agent_event(&self.conversation_id, event);
+    }
+
+    fn on_delta(&self, delta: &LLMDelta) {
+        self.bus.publish(BusEvent::new(
+            Some(&self.conversation_id),
+            BusPayload::LlmDelta(delta.clone()),
+        ));
+    }
+}
+
+/// What a subscriber that falls a full buffer behind gets.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DropPolicy {
+    /// Skip the oldest events and receive a [`BusPayload::Gap`] marker in their place.
+    #[default]
+    DropOldest,
+    /// End the subscription.
+    Disconnect,
+}
+
+/// Fans events out to live subscribers, e.g. SSE streams, through a buffer of `capacity`
+/// events per subscriber.
+pub struct BroadcastSink {
+    sender: broadcast::Sender<BusEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl BroadcastSink {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Events published from now on.
+    pub fn subscribe(&self, policy: DropPolicy) -> Subscription {
+        Subscription {
+            receiver: self.sender.subscribe(),
+            policy,
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    /// Events subscribers missed because they lagged, summed over all subscribers.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl EventSink for BroadcastSink {
+    fn handle(&self, event: &BusEvent) {
+        // No subscribers is not an error.
+        let _ = self.sender.send(event.clone());
+    }
+}
+
+pub struct Subscription {
+    receiver: broadcast::Receiver<BusEvent>,
+    policy: DropPolicy,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscription {
+    /// The next event or gap marker. `None` once the bus is gone, or after lagging with
+    /// [`DropPolicy::Disconnect`].
+    pub async fn recv(&mut self) -> Option<BusEvent> {
+        match self.receiver.recv().await {
+            Ok(event) => Some(event),
+            Err(broadcast::error::RecvError::Closed) => None,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                self.dropped.fetch_add(missed, Ordering::Relaxed);
+                tracing::warn!("Event subscriber lagged and missed {} events", missed);
+                match self.policy {
+                    DropPolicy::DropOldest => Some(BusEvent::new(None, BusPayload::Gap { missed })),
+                    DropPolicy::Disconnect => None,
+                }
+            }
+        }
+    }
+}
+
+/// Appends events to a file, one JSON object per line, with secrets redacted.
+pub struct JsonlSink {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl JsonlSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn append(&self, event: &BusEvent) -> Result<(), String> {
+        let line =
+            serde_json::to_string(&event.redacted(&redactor())).map_err(|e| e.to_string())?;
+        let _guard = self.lock.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| e.to_string())
+    }
+}
+
+impl EventSink for JsonlSink {
+    fn handle(&self, event: &BusEvent) {
+        if matches!(event.payload, BusPayload::LlmDelta(_)) {
+            return;
+        }
+        if let Err(e) = self.append(event) {
+            tracing::error!("Failed to persist event: {}", e);
+        }
+    }
+}
+
+/// Posts each event as JSON to a URL, with secrets redacted. Delivery failures are logged
+/// and not retried.
+#[cfg(feature = "http")]
+pub struct WebhookSink {
+    url: String,
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "http")]
+impl WebhookSink {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl EventSink for WebhookSink {
+    fn handle(&self, event: &BusEvent) {
+        if matches!(event.payload, BusPayload::LlmDelta(_)) {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("Dropping webhook event outside of a tokio runtime");
+            return;
+        };
+        let request = self
+            .client
+            .post(&self.url)
+            .json(&event.redacted(&redactor()));
+        runtime.spawn(async move {
+            match request.send().await {
+                Ok(res) if !res.status().is_success() => {
+                    tracing::warn!("Event webhook returned {}", res.status());
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Failed to deliver event webhook: {}", e),
+            }
+        });
+    }
+}
+
+/// Counts published events by [`BusEvent::kind`].
+#[derive(Default)]
+pub struct MetricsSink {
+    counts: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl MetricsSink {
+    pub fn counts(&self) -> BTreeMap<&'static str, u64> {
+        self.counts.lock().unwrap().clone()
+    }
+}
+
+impl EventSink for MetricsSink {
+    fn handle(&self, event: &BusEvent) {
+        *self.counts.lock().unwrap().entry(event.kind()).or_default() += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{MessageEvent, ObservationEvent};
+
+    #[tokio::test]
+    async fn test_sinks_receive_published_events() {
+        let bus = EventBus::new();
+        let broadcast = Arc::new(BroadcastSink::new(16));
+        let metrics = Arc::new(MetricsSink::default());
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("events.jsonl");
+        bus.add_sink(broadcast.clone());
+        bus.add_sink(metrics.clone());
+        bus.add_sink(Arc::new(JsonlSink::new(log.clone())));
+        let mut events = broadcast.subscribe(DropPolicy::DropOldest);
+
+        let observation = Event::Observation(ObservationEvent {
+            source: "environment".to_string(),
+            tool_name: "write_file".to_string(),
+            tool_call_id: "call_1".to_string(),
+            content: "Wrote a.txt".to_string(),
+            file_changes: vec![FileChange::capture("a.txt", None, Some("hi\n")).unwrap()],
+            untracked_changes: false,
+            attachments: vec![],
+            injection: None,
+        });
+        bus.observer("c1").on_event(&observation).await;
+        bus.observer("c1").on_delta(&LLMDelta::Content {
+            text: "Done".to_string(),
+        });
+
+        let first = events.recv().await.unwrap();
+        assert_eq!(first.kind(), "agent.observation");
+        assert_eq!(first.conversation_id.as_deref(), Some("c1"));
+        assert_eq!(events.recv().await.unwrap().kind(), "file_change");
+        assert_eq!(events.recv().await.unwrap().kind(), "llm.content");
+
+        assert_eq!(metrics.counts().get("agent.observation"), Some(&1));
+        assert_eq!(metrics.counts().get("file_change"), Some(&1));
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["topic"], "agent");
+        assert_eq!(lines[0]["event"]["type"], "Observation");
+        assert_eq!(lines[1]["topic"], "file_change");
+        assert_eq!(lines[1]["event"]["path"], "a.txt");
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscribers() {
+        let broadcast = BroadcastSink::new(2);
+        let mut drop_oldest = broadcast.subscribe(DropPolicy::DropOldest);
+        let mut disconnect = broadcast.subscribe(DropPolicy::Disconnect);
+        for i in 0..5 {
+            let message = Event::Message(MessageEvent {
+                source: "agent".to_string(),
+                content: i.to_string(),
+                file_refs: Vec::new(),
+            });
+            broadcast.handle(&BusEvent::new(Some("c1"), BusPayload::Agent(message)));
+        }
+
+        let gap = drop_oldest.recv().await.unwrap();
+        assert!(matches!(gap.payload, BusPayload::Gap { missed: 3 }));
+        for expected in ["3", "4"] {
+            match drop_oldest.recv().await.unwrap().payload {
+                BusPayload::Agent(Event::Message(m)) => assert_eq!(m.content, expected),
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+
+        assert!(disconnect.recv().await.is_none());
+        assert_eq!(broadcast.dropped(), 6);
+    }
+}
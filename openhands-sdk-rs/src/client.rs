@@ -0,0 +1,402 @@
+//! Typed client for the agent server's HTTP API.
+//!
+//! ```no_run
+//! # async fn demo() -> Result<(), String> {
+//! use openhands_sdk_rs::client::AgentServerClient;
+//! use openhands_sdk_rs::models::InitConversationRequest;
+//!
+//! let client = AgentServerClient::new("http://localhost:3000").with_api_key("oh-...");
+//! let conversation = client
+//!     .create_conversation(&InitConversationRequest {
+//!         system_message: None,
+//!         setup: None,
+//!         secrets: Default::default(),
+//!         security_mode: None,
+//!         dry_run: false,
+//!     })
+//!     .await?;
+//! let reply = client.send_message(&conversation.id, "List the files").await?;
+//! println!("{}", reply.response);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The server has no event stream: [`AgentServerClient::send_message`] returns once the
+//! agent has replied.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::activity::ActivityReport;
+use crate::events::ActionEvent;
+use crate::export::ExportFormat;
+use crate::journal::MutationRecord;
+use crate::models::{
+    ActionDecision, BashOutput, ConversationPrompt, ConversationResponse, ExecuteBashRequest,
+    FileReadRequest, FileResponse, FileWriteRequest, InitConversationRequest, MessageRequest,
+    MessageResponse, PlanExecution, PlanResponse, PromptUpdate, ServerInfo, TaskInfo, TaskRequest,
+    UndoRequest, UndoResponse,
+};
+use crate::outcome::TaskResult;
+use crate::plugins::{RegisteredTool, ToolRegistration};
+use crate::profile::ProfileReport;
+use crate::runtime::baseline::FileDiff;
+use crate::runtime::ports::ExposedPort;
+
+#[derive(Clone, Debug)]
+pub struct AgentServerClient {
+    base_url: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl AgentServerClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Authenticate requests with `Authorization: Bearer <api_key>`.
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let builder = self
+            .client
+            .request(method, format!("{}{}", self.base_url, path));
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+
+    /// Send a request, turning non-success responses into errors with the response body.
+    async fn send(&self, builder: reqwest::RequestBuilder) -> Result<reqwest::Response, String> {
+        let res = builder.send().await.map_err(|e| e.to_string())?;
+        if !res.status().is_success() {
+            let status = res.status();
+            let error_text = res.text().await.unwrap_or_default();
+            return Err(format!("Server returned error {}: {}", status, error_text));
+        }
+        Ok(res)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        let res = self.send(self.request(reqwest::Method::GET, path)).await?;
+        res.json().await.map_err(|e| e.to_string())
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, String> {
+        let res = self
+            .send(self.request(reqwest::Method::POST, path).json(body))
+            .await?;
+        res.json().await.map_err(|e| e.to_string())
+    }
+
+    async fn patch<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, String> {
+        let res = self
+            .send(self.request(reqwest::Method::PATCH, path).json(body))
+            .await?;
+        res.json().await.map_err(|e| e.to_string())
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), String> {
+        self.send(self.request(reqwest::Method::DELETE, path))
+            .await
+            .map(|_| ())
+    }
+
+    /// Whether the server answers at all; `GET /health` needs no API key.
+    pub async fn health(&self) -> Result<(), String> {
+        self.send(self.client.get(format!("{}/health", self.base_url)))
+            .await
+            .map(|_| ())
+    }
+
+    /// The server's version, runtime and tool inventory.
+    pub async fn server_info(&self) -> Result<ServerInfo, String> {
+        self.get("/server_info").await
+    }
+
+    pub async fn create_conversation(
+        &self,
+        request: &InitConversationRequest,
+    ) -> Result<ConversationResponse, String> {
+        self.post("/api/conversations", request).await
+    }
+
+    pub async fn list_conversations(&self) -> Result<Vec<ConversationResponse>, String> {
+        self.get("/api/conversations").await
+    }
+
+    pub async fn delete_conversation(&self, id: &str) -> Result<(), String> {
+        self.delete(&format!("/api/conversations/{}", id)).await
+    }
+
+    /// Send a user message and wait for the agent's reply.
+    pub async fn send_message(&self, id: &str, content: &str) -> Result<MessageResponse, String> {
+        self.send_message_with_files(id, content, &[]).await
+    }
+
+    /// Send a message with workspace files attached, given relative to the workspace.
+    pub async fn send_message_with_files(
+        &self,
+        id: &str,
+        content: &str,
+        file_refs: &[String],
+    ) -> Result<MessageResponse, String> {
+        let request = MessageRequest {
+            content: content.to_string(),
+            file_refs: file_refs.to_vec(),
+        };
+        self.post(&format!("/api/conversations/{}/messages", id), &request)
+            .await
+    }
+
+    /// High risk actions of the conversation awaiting confirmation.
+    pub async fn pending_actions(&self, id: &str) -> Result<Vec<ActionEvent>, String> {
+        self.get(&format!("/api/conversations/{}/pending_actions", id))
+            .await
+    }
+
+    /// Approve or reject a pending action, letting the agent's step continue.
+    pub async fn decide_action(
+        &self,
+        id: &str,
+        tool_call_id: &str,
+        approve: bool,
+    ) -> Result<(), String> {
+        let path = format!("/api/conversations/{}/pending_actions/{}", id, tool_call_id);
+        self.send(
+            self.request(reqwest::Method::POST, &path)
+                .json(&ActionDecision { approve }),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// The outcome the agent reported with `finish`, or `None` until it has.
+    pub async fn conversation_result(&self, id: &str) -> Result<Option<TaskResult>, String> {
+        self.get(&format!("/api/conversations/{}/result", id)).await
+    }
+
+    /// The files the conversation changed, with unified diffs against their original state.
+    pub async fn changes(&self, id: &str) -> Result<Vec<FileDiff>, String> {
+        self.get(&format!("/api/conversations/{}/changes", id))
+            .await
+    }
+
+    /// The system message and extensions the conversation's agent builds its prompt from.
+    pub async fn prompt(&self, id: &str) -> Result<ConversationPrompt, String> {
+        self.get(&format!("/api/conversations/{}/prompt", id)).await
+    }
+
+    /// Change the conversation's system message or extensions from the agent's next step on.
+    pub async fn update_prompt(
+        &self,
+        id: &str,
+        update: &PromptUpdate,
+    ) -> Result<ConversationPrompt, String> {
+        self.patch(&format!("/api/conversations/{}/prompt", id), update)
+            .await
+    }
+
+    /// Undo the conversation's last `count` file changes; returns them, newest first.
+    pub async fn undo(&self, id: &str, count: usize) -> Result<Vec<MutationRecord>, String> {
+        let response: UndoResponse = self
+            .post(
+                &format!("/api/conversations/{}/undo", id),
+                &UndoRequest { count },
+            )
+            .await?;
+        Ok(response.undone)
+    }
+
+    /// Latency, token usage and estimated cost of the conversation's steps.
+    pub async fn profile(&self, id: &str) -> Result<ProfileReport, String> {
+        self.get(&format!("/api/conversations/{}/profile", id))
+            .await
+    }
+
+    /// What the caller's conversations are doing; see [`crate::activity`].
+    pub async fn activity(&self) -> Result<Vec<ActivityReport>, String> {
+        self.get("/api/activity").await
+    }
+
+    /// Have the conversation wait before its next tool call until it is resumed.
+    pub async fn pause(&self, id: &str) -> Result<(), String> {
+        self.control(id, "pause").await
+    }
+
+    pub async fn resume(&self, id: &str) -> Result<(), String> {
+        self.control(id, "resume").await
+    }
+
+    /// End the conversation's running step.
+    pub async fn stop(&self, id: &str) -> Result<(), String> {
+        self.control(id, "stop").await
+    }
+
+    async fn control(&self, id: &str, control: &str) -> Result<(), String> {
+        let path = format!("/api/conversations/{}/{}", id, control);
+        self.send(self.request(reqwest::Method::POST, &path))
+            .await
+            .map(|_| ())
+    }
+
+    /// The conversation as a report in `format`; see [`crate::export`].
+    pub async fn export(&self, id: &str, format: ExportFormat) -> Result<String, String> {
+        let path = format!(
+            "/api/conversations/{}/export?format={}",
+            id,
+            format.as_str()
+        );
+        let res = self.send(self.request(reqwest::Method::GET, &path)).await?;
+        res.text().await.map_err(|e| e.to_string())
+    }
+
+    /// Whether the conversation is in dry-run mode, and the actions proposed so far.
+    pub async fn plan(&self, id: &str) -> Result<PlanResponse, String> {
+        self.get(&format!("/api/conversations/{}/plan", id)).await
+    }
+
+    /// Approve the proposed actions of a dry run and execute them.
+    pub async fn execute_plan(&self, id: &str) -> Result<PlanExecution, String> {
+        self.post(&format!("/api/conversations/{}/plan/execute", id), &())
+            .await
+    }
+
+    /// Discard the proposed actions of a dry run.
+    pub async fn discard_plan(&self, id: &str) -> Result<(), String> {
+        self.delete(&format!("/api/conversations/{}/plan", id))
+            .await
+    }
+
+    /// Ports the conversation's sandbox forwards to the host.
+    pub async fn list_ports(&self, id: &str) -> Result<Vec<ExposedPort>, String> {
+        self.get(&format!("/api/conversations/{}/ports", id)).await
+    }
+
+    /// Queue a task for the agent to run to completion.
+    pub async fn create_task(&self, request: &TaskRequest) -> Result<TaskInfo, String> {
+        self.post("/api/tasks", request).await
+    }
+
+    pub async fn list_tasks(&self) -> Result<Vec<TaskInfo>, String> {
+        self.get("/api/tasks").await
+    }
+
+    pub async fn get_task(&self, id: &str) -> Result<TaskInfo, String> {
+        self.get(&format!("/api/tasks/{}", id)).await
+    }
+
+    /// The outcome of a task, or `None` until it has succeeded.
+    pub async fn task_result(&self, id: &str) -> Result<Option<TaskResult>, String> {
+        self.get(&format!("/api/tasks/{}/result", id)).await
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<RegisteredTool>, String> {
+        self.get("/api/tools").await
+    }
+
+    pub async fn register_tool(
+        &self,
+        registration: &ToolRegistration,
+    ) -> Result<RegisteredTool, String> {
+        self.post("/api/tools", registration).await
+    }
+
+    pub async fn deregister_tool(&self, name: &str) -> Result<(), String> {
+        self.delete(&format!("/api/tools/{}", name)).await
+    }
+
+    /// Run a command in the server's workspace and wait for its output.
+    pub async fn execute_bash(&self, request: &ExecuteBashRequest) -> Result<BashOutput, String> {
+        self.post("/bash/execute_bash_command", request).await
+    }
+
+    pub async fn read_file(&self, path: &str) -> Result<FileResponse, String> {
+        let request = FileReadRequest {
+            path: path.to_string(),
+        };
+        self.post("/file/read", &request).await
+    }
+
+    pub async fn write_file(&self, path: &str, content: &str) -> Result<FileResponse, String> {
+        let request = FileWriteRequest {
+            path: path.to_string(),
+            content: content.to_string(),
+        };
+        self.post("/file/write", &request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Answer one request with `body`, returning the request as received.
+    async fn serve_once(body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            // Until the headers and the `content-length` bytes of body are in.
+            while !String::from_utf8_lossy(&request)
+                .split_once("\r\n\r\n")
+                .is_some_and(|(head, body)| {
+                    let length = head
+                        .lines()
+                        .filter_map(|l| l.split_once(": "))
+                        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+                        .map_or(0, |(_, v)| v.parse().unwrap());
+                    body.len() >= length
+                })
+            {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let (url, server) = serve_once(r#"{"response": "Done"}"#).await;
+        let client = AgentServerClient::new(&url).with_api_key("oh-secret");
+        let reply = client.send_message("c1", "Hello").await.unwrap();
+        assert_eq!(reply.response, "Done");
+
+        let request = server.await.unwrap().to_lowercase();
+        assert!(request.starts_with("post /api/conversations/c1/messages "));
+        assert!(request.contains("authorization: bearer oh-secret"));
+        assert!(request.ends_with(r#"{"content":"hello"}"#));
+    }
+}
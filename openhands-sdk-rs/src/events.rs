@@ -1,4 +1,9 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use similar::{DiffTag, TextDiff};
+
+use crate::file_refs::FileRef;
+use crate::security::SecurityRisk;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -12,6 +17,9 @@ pub enum Event {
 pub struct MessageEvent {
     pub source: String,
     pub content: String,
+    /// Workspace files attached to a user message; the agent reads them after `content`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_refs: Vec<FileRef>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +29,13 @@ pub struct ActionEvent {
     pub tool_call_id: String,
     pub arguments: serde_json::Value,
     pub thought: Option<String>,
+    /// Set on actions that run a shell command; see [`crate::security`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security_risk: Option<SecurityRisk>,
+    /// What the model reasoned before it chose the action, for models that expose their
+    /// reasoning. Set on the first action of a response, like `thought`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,4 +44,185 @@ pub struct ObservationEvent {
     pub tool_name: String,
     pub tool_call_id: String,
     pub content: String,
+    /// Workspace files modified by the action, in the order they were changed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_changes: Vec<FileChange>,
+    /// The action may have changed files that `file_changes` does not record, e.g. a shell
+    /// command; see [`crate::agent::tools::Tool::has_untracked_changes`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub untracked_changes: bool,
+    /// Media returned alongside `content`, e.g. screenshots.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// Why `content` looks like a prompt injection, if it does; see [`crate::injection`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub injection: Option<String>,
+}
+
+/// Non-text output of a tool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Attachment {
+    /// Base64 encoded image data.
+    Image { mime_type: String, data: String },
+    /// A resource whose content is included; `blob` is base64 encoded binary content.
+    Resource {
+        uri: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blob: Option<String>,
+    },
+    /// A reference to a resource the agent can fetch itself.
+    ResourceLink {
+        uri: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+    },
+}
+
+/// Part of an LLM response, reported while the response streams in. Deltas are not events:
+/// the response still ends up in the history as the usual message and action events.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LLMDelta {
+    /// Reasoning the model does before it answers, for models that expose it.
+    Reasoning { text: String },
+    /// Text appended to the response.
+    Content { text: String },
+    /// A tool call being generated; `arguments` is the argument text received so far.
+    ToolCall {
+        call_id: String,
+        tool_name: String,
+        arguments: String,
+    },
+}
+
+/// A recorded modification of a single file, sufficient to re-apply it deterministically.
+///
+/// `None` hashes mean the file did not exist, so a change with `before_hash: None` is a
+/// creation and one with `after_hash: None` is a deletion.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub before_hash: Option<String>,
+    pub after_hash: Option<String>,
+    pub edits: Vec<LineEdit>,
+}
+
+/// Replace `old_len` lines starting at `old_start` (0-indexed, in the original file)
+/// with `new_lines`. Lines keep their terminators so content is reproduced byte for byte.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineEdit {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_lines: Vec<String>,
+}
+
+pub fn content_hash(content: &str) -> String {
+    let digest = Sha256::digest(content.as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl FileChange {
+    /// Build the change between two versions of a file. Returns `None` if nothing changed.
+    pub fn capture(path: &str, before: Option<&str>, after: Option<&str>) -> Option<Self> {
+        if before == after {
+            return None;
+        }
+
+        let old = before.unwrap_or("");
+        let new = after.unwrap_or("");
+        let diff = TextDiff::from_lines(old, new);
+        let new_slices = diff.new_slices();
+
+        let edits = diff
+            .ops()
+            .iter()
+            .map(|op| op.as_tag_tuple())
+            .filter(|(tag, _, _)| *tag != DiffTag::Equal)
+            .map(|(_, old_range, new_range)| LineEdit {
+                old_start: old_range.start,
+                old_len: old_range.len(),
+                new_lines: new_slices[new_range]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            })
+            .collect();
+
+        Some(Self {
+            path: path.to_string(),
+            before_hash: before.map(content_hash),
+            after_hash: after.map(content_hash),
+            edits,
+        })
+    }
+
+    /// Apply the edits to `before`, returning the new content (`None` for a deletion).
+    pub fn apply(&self, before: Option<&str>) -> Result<Option<String>, String> {
+        if before.map(content_hash) != self.before_hash {
+            return Err(format!(
+                "Cannot apply change to '{}': current content does not match the recorded base",
+                self.path
+            ));
+        }
+        if self.after_hash.is_none() {
+            return Ok(None);
+        }
+
+        let old_lines: Vec<&str> = before.unwrap_or("").split_inclusive('\n').collect();
+        let mut result = String::new();
+        let mut cursor = 0;
+        for edit in &self.edits {
+            if edit.old_start < cursor || edit.old_start + edit.old_len > old_lines.len() {
+                return Err(format!("Invalid line edit recorded for '{}'", self.path));
+            }
+            old_lines[cursor..edit.old_start]
+                .iter()
+                .for_each(|l| result.push_str(l));
+            edit.new_lines.iter().for_each(|l| result.push_str(l));
+            cursor = edit.old_start + edit.old_len;
+        }
+        old_lines[cursor..].iter().for_each(|l| result.push_str(l));
+
+        if Some(content_hash(&result)) != self.after_hash {
+            return Err(format!(
+                "Replaying change to '{}' did not reproduce the recorded content",
+                self.path
+            ));
+        }
+        Ok(Some(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_change_round_trip() {
+        let before = "line1\nline2\nline3\n";
+        let after = "line1\nchanged\nline3\nline4";
+        let change = FileChange::capture("a.txt", Some(before), Some(after)).unwrap();
+
+        assert_eq!(change.apply(Some(before)).unwrap().as_deref(), Some(after));
+        assert!(change.apply(Some("other")).is_err());
+    }
+
+    #[test]
+    fn test_file_change_create_and_delete() {
+        let created = FileChange::capture("new.txt", None, Some("hello\n")).unwrap();
+        assert_eq!(created.before_hash, None);
+        assert_eq!(created.apply(None).unwrap().as_deref(), Some("hello\n"));
+
+        let deleted = FileChange::capture("new.txt", Some("hello\n"), None).unwrap();
+        assert_eq!(deleted.apply(Some("hello\n")).unwrap(), None);
+
+        assert!(FileChange::capture("same.txt", Some("x"), Some("x")).is_none());
+    }
 }
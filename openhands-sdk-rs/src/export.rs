@@ -0,0 +1,494 @@
+//! Conversation reports for people to read.
+//!
+//! A [`ConversationReport`] lays out a conversation in order: the messages, the agent's
+//! thoughts, the commands and tool calls it made with their output, then the files it
+//! changed as diffs and the result it reported with `finish`. It renders as Markdown, for
+//! pull requests and issue comments, or as one HTML page with its styles inline, which opens
+//! anywhere without network access.
+
+use crate::agent::REFLECTION_SOURCE;
+use crate::events::{ActionEvent, Event, MessageEvent, ObservationEvent};
+use crate::outcome::{FINISH_TOOL, TaskResult};
+use crate::runtime::baseline::FileDiff;
+use crate::setup::SETUP_SOURCE;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// What a report is rendered as.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl ExportFormat {
+    /// The name of the format, as the `format` query parameter takes it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "markdown",
+            ExportFormat::Html => "html",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+
+    /// The usual file extension, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "html" => Ok(ExportFormat::Html),
+            _ => Err(format!(
+                "Unknown export format '{}': use markdown or html",
+                s
+            )),
+        }
+    }
+}
+
+/// Everything a report shows of a conversation.
+#[derive(Debug, Clone)]
+pub struct ConversationReport {
+    pub id: String,
+    pub events: Vec<Event>,
+    pub changes: Vec<FileDiff>,
+    pub result: Option<TaskResult>,
+    pub exported_at: DateTime<Utc>,
+}
+
+/// A piece of a report, rendered the same way in both formats.
+enum Block {
+    Heading(u8, String),
+    /// Prose written by a person or the model; Markdown output keeps it as it is.
+    Text(String),
+    /// A line of details about the block before it.
+    Note(String),
+    /// Preformatted text. `summary` titles a collapsible section in HTML.
+    Code {
+        language: &'static str,
+        text: String,
+        summary: Option<String>,
+    },
+}
+
+impl ConversationReport {
+    pub fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Markdown => self.to_markdown(),
+            ExportFormat::Html => self.to_html(),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        for block in self.blocks() {
+            match block {
+                Block::Heading(level, text) => {
+                    out.push_str(&format!("{} {}\n\n", "#".repeat(level as usize), text))
+                }
+                Block::Text(text) => out.push_str(&format!("{}\n\n", text.trim_end())),
+                Block::Note(text) => out.push_str(&format!("_{}_\n\n", text)),
+                Block::Code {
+                    language,
+                    text,
+                    summary,
+                } => {
+                    if let Some(summary) = summary {
+                        out.push_str(&format!("{}:\n\n", summary));
+                    }
+                    let fence = fence(&text);
+                    out.push_str(&format!(
+                        "{}{}\n{}\n{}\n\n",
+                        fence,
+                        language,
+                        text.trim_end_matches('\n'),
+                        fence
+                    ));
+                }
+            }
+        }
+        out.truncate(out.trim_end().len());
+        out.push('\n');
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut body = String::new();
+        for block in self.blocks() {
+            match block {
+                Block::Heading(level, text) => {
+                    body.push_str(&format!("<h{0}>{1}</h{0}>\n", level, escape(&text)))
+                }
+                Block::Text(text) => body.push_str(&format!(
+                    "<p class=\"text\">{}</p>\n",
+                    escape(text.trim_end())
+                )),
+                Block::Note(text) => {
+                    body.push_str(&format!("<p class=\"note\">{}</p>\n", escape(&text)))
+                }
+                Block::Code {
+                    language,
+                    text,
+                    summary,
+                } => {
+                    let pre = format!(
+                        "<pre class=\"{}\">{}</pre>",
+                        language,
+                        highlight(language, text.trim_end_matches('\n'))
+                    );
+                    match summary {
+                        Some(summary) => body.push_str(&format!(
+                            "<details><summary>{}</summary>{}</details>\n",
+                            escape(&summary),
+                            pre
+                        )),
+                        None => body.push_str(&format!("{}\n", pre)),
+                    }
+                }
+            }
+        }
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Conversation {}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+            escape(&self.id),
+            STYLE,
+            body
+        )
+    }
+
+    fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![
+            Block::Heading(1, format!("Conversation {}", self.id)),
+            Block::Note(format!(
+                "Exported {}",
+                self.exported_at.format("%Y-%m-%d %H:%M:%S UTC")
+            )),
+        ];
+        if let Some(result) = &self.result {
+            blocks.extend(result_blocks(result));
+        }
+
+        blocks.push(Block::Heading(2, "Timeline".to_string()));
+        for event in &self.events {
+            match event {
+                Event::Message(message) => blocks.extend(message_blocks(message)),
+                Event::Action(action) => blocks.extend(action_blocks(action)),
+                Event::Observation(observation) => blocks.extend(observation_blocks(observation)),
+            }
+        }
+
+        if !self.changes.is_empty() {
+            blocks.push(Block::Heading(2, "Changes".to_string()));
+            for change in &self.changes {
+                blocks.push(Block::Code {
+                    language: "diff",
+                    text: change.diff.clone(),
+                    summary: Some(format!(
+                        "{} ({}, +{} -{})",
+                        change.path,
+                        name(change.status),
+                        change.additions,
+                        change.deletions
+                    )),
+                });
+            }
+        }
+        blocks
+    }
+}
+
+fn result_blocks(result: &TaskResult) -> Vec<Block> {
+    let mut blocks = vec![
+        Block::Heading(2, format!("Result: {}", name(result.status))),
+        Block::Text(result.summary.clone()),
+    ];
+    if let Some(tests) = &result.tests {
+        let mut line = format!(
+            "Tests: {} passed, {} failed, {} skipped",
+            tests.passed, tests.failed, tests.skipped
+        );
+        if let Some(command) = &tests.command {
+            line.push_str(&format!(" ({})", command));
+        }
+        blocks.push(Block::Note(line));
+    }
+    if !result.changed_files.is_empty() {
+        let files: Vec<String> = result
+            .changed_files
+            .iter()
+            .map(|f| format!("{} (+{} -{})", f.path, f.additions, f.deletions))
+            .collect();
+        blocks.push(Block::Note(format!("Changed: {}", files.join(", "))));
+    }
+    blocks.push(Block::Note(format!(
+        "Finished {}",
+        result.finished_at.format("%Y-%m-%d %H:%M:%S UTC")
+    )));
+    blocks
+}
+
+fn message_blocks(message: &MessageEvent) -> Vec<Block> {
+    let speaker = match message.source.as_str() {
+        "user" => "User",
+        "agent" => "Agent",
+        REFLECTION_SOURCE => "Agent reflection",
+        other => other,
+    };
+    let mut blocks = vec![
+        Block::Heading(3, speaker.to_string()),
+        Block::Text(message.content.clone()),
+    ];
+    if !message.file_refs.is_empty() {
+        let paths: Vec<&str> = message.file_refs.iter().map(|f| f.path.as_str()).collect();
+        blocks.push(Block::Note(format!("Attached: {}", paths.join(", "))));
+    }
+    blocks
+}
+
+fn action_blocks(action: &ActionEvent) -> Vec<Block> {
+    let mut blocks = vec![Block::Heading(
+        3,
+        format!("Agent ran `{}`", action.tool_name),
+    )];
+    if let Some(thought) = &action.thought {
+        blocks.push(Block::Text(thought.clone()));
+    }
+    // The result section shows what `finish` reported.
+    if action.tool_name == FINISH_TOOL {
+        return blocks;
+    }
+    let command = action.arguments.get("command").and_then(|c| c.as_str());
+    blocks.push(match command {
+        Some(command) if action.tool_name == "cmd" || action.tool_name == "terminal" => {
+            Block::Code {
+                language: "bash",
+                text: command.to_string(),
+                summary: None,
+            }
+        }
+        _ => Block::Code {
+            language: "json",
+            text: serde_json::to_string_pretty(&action.arguments).unwrap_or_default(),
+            summary: None,
+        },
+    });
+    if let Some(risk) = &action.security_risk
+        && let Some(reason) = &risk.reason
+    {
+        blocks.push(Block::Note(format!(
+            "Security risk: {} ({})",
+            name(risk.level),
+            reason
+        )));
+    }
+    blocks
+}
+
+fn observation_blocks(observation: &ObservationEvent) -> Vec<Block> {
+    if observation.source == SETUP_SOURCE {
+        return vec![
+            Block::Heading(3, "Workspace setup".to_string()),
+            Block::Code {
+                language: "",
+                text: observation.content.clone(),
+                summary: Some("Output".to_string()),
+            },
+        ];
+    }
+    if observation.tool_name == FINISH_TOOL {
+        return Vec::new();
+    }
+    let lines = observation.content.lines().count();
+    let mut blocks = vec![Block::Code {
+        language: "",
+        text: observation.content.clone(),
+        summary: Some(format!(
+            "Output ({} line{})",
+            lines,
+            if lines == 1 { "" } else { "s" }
+        )),
+    }];
+    if !observation.file_changes.is_empty() {
+        let paths: Vec<&str> = observation
+            .file_changes
+            .iter()
+            .map(|c| c.path.as_str())
+            .collect();
+        blocks.push(Block::Note(format!("Changed: {}", paths.join(", "))));
+    }
+    blocks
+}
+
+/// How `value`, a unit enum variant, is serialized.
+fn name(value: impl Serialize) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// A code fence longer than any run of backticks in `text`.
+fn fence(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `text` escaped, with added and removed lines of a diff marked for coloring.
+fn highlight(language: &str, text: &str) -> String {
+    if language != "diff" {
+        return escape(text);
+    }
+    text.lines()
+        .map(|line| {
+            let class = if line.starts_with("+++") || line.starts_with("---") {
+                "file"
+            } else if line.starts_with('+') {
+                "add"
+            } else if line.starts_with('-') {
+                "del"
+            } else if line.starts_with("@@") {
+                "hunk"
+            } else {
+                return escape(line);
+            };
+            format!("<span class=\"{}\">{}</span>", class, escape(line))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const STYLE: &str = "
+body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; max-width: 960px; margin: 2rem auto; padding: 0 1rem; color: #1f2328; line-height: 1.5; }
+h1 { border-bottom: 1px solid #d1d9e0; padding-bottom: .3rem; }
+h2 { margin-top: 2rem; border-bottom: 1px solid #d1d9e0; padding-bottom: .2rem; }
+h3 { margin: 1.5rem 0 .3rem; font-size: 1rem; }
+.text { white-space: pre-wrap; margin: .3rem 0; }
+.note { color: #59636e; font-size: .9rem; margin: .3rem 0; }
+pre { background: #f6f8fa; border: 1px solid #d1d9e0; border-radius: 6px; padding: .7rem; overflow-x: auto; font-size: .85rem; }
+pre.bash { background: #1f2328; color: #f0f6fc; }
+details summary { cursor: pointer; color: #59636e; font-size: .9rem; }
+.add { color: #1a7f37; } .del { color: #d1242f; } .hunk { color: #8250df; } .file { font-weight: bold; }
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outcome::{ChangedFile, TaskStatus, TestResults};
+    use crate::runtime::baseline::DiffStatus;
+    use serde_json::json;
+
+    fn report() -> ConversationReport {
+        let exported_at = DateTime::parse_from_rfc3339("2026-10-17T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        ConversationReport {
+            id: "conv-1".to_string(),
+            events: vec![
+                Event::Message(MessageEvent {
+                    source: "user".to_string(),
+                    content: "Fix the <greeting>".to_string(),
+                    file_refs: Vec::new(),
+                }),
+                Event::Action(ActionEvent {
+                    source: "agent".to_string(),
+                    tool_name: "cmd".to_string(),
+                    tool_call_id: "call_1".to_string(),
+                    arguments: json!({ "command": "grep -rn hello src" }),
+                    thought: Some("Let me find it.".to_string()),
+                    security_risk: None,
+                    reasoning: None,
+                }),
+                Event::Observation(ObservationEvent {
+                    source: "runtime".to_string(),
+                    tool_name: "cmd".to_string(),
+                    tool_call_id: "call_1".to_string(),
+                    content: "src/main.rs:2: println!(\"hello\")\n```".to_string(),
+                    file_changes: Vec::new(),
+                    untracked_changes: false,
+                    attachments: Vec::new(),
+                    injection: None,
+                }),
+            ],
+            changes: vec![FileDiff {
+                path: "src/main.rs".to_string(),
+                status: DiffStatus::Modified,
+                additions: 1,
+                deletions: 1,
+                diff: "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -2 +2 @@\n-hello\n+Hello\n"
+                    .to_string(),
+            }],
+            result: Some(TaskResult {
+                status: TaskStatus::Success,
+                summary: "Capitalized the greeting.".to_string(),
+                changed_files: vec![ChangedFile {
+                    path: "src/main.rs".to_string(),
+                    additions: 1,
+                    deletions: 1,
+                }],
+                tests: Some(TestResults {
+                    command: Some("cargo test".to_string()),
+                    passed: 4,
+                    failed: 0,
+                    skipped: 0,
+                }),
+                finished_at: exported_at,
+            }),
+            exported_at,
+        }
+    }
+
+    #[test]
+    fn test_markdown_and_html_reports() {
+        let report = report();
+        let markdown = report.render(ExportFormat::Markdown);
+        assert!(
+            markdown.starts_with("# Conversation conv-1\n\n_Exported 2026-10-17 12:00:00 UTC_")
+        );
+        assert!(markdown.contains("## Result: success\n\nCapitalized the greeting.\n\n_Tests: 4 passed, 0 failed, 0 skipped (cargo test)_"));
+        assert!(markdown.contains(
+            "### Agent ran `cmd`\n\nLet me find it.\n\n```bash\ngrep -rn hello src\n```"
+        ));
+        // Output holding a fence gets a longer one.
+        assert!(markdown.contains("Output (2 lines):\n\n````\nsrc/main.rs:2"));
+        assert!(markdown.contains("src/main.rs (modified, +1 -1):\n\n```diff\n--- a/src/main.rs"));
+        assert!(markdown.ends_with("+Hello\n```\n"));
+
+        let html = report.render(ExportFormat::Html);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<p class=\"text\">Fix the &lt;greeting&gt;</p>"));
+        assert!(html.contains("<span class=\"add\">+Hello</span>"));
+        assert!(!html.contains("<greeting>"));
+        assert!(!html.contains("http"));
+
+        assert_eq!("md".parse(), Ok(ExportFormat::Markdown));
+        assert!("pdf".parse::<ExportFormat>().is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//! Locks that serialize edits of the same file.
+//!
+//! An editor reads a file, changes it and writes it back; two edits of one file running at
+//! once would each write their own change over the other's. Editors take the file's lock
+//! from [`FileLocks`] around the whole edit. [`FileLocks::global`] is shared by every editor
+//! in the process, so conversations whose workspaces overlap wait for each other too.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::OwnedMutexGuard;
+
+/// How long an edit waits for another edit of the same file by default.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+lazy_static::lazy_static! {
+    static ref GLOBAL: FileLocks = FileLocks::default();
+}
+
+type PathLock = tokio::sync::Mutex<()>;
+
+/// A lock per file; clones share the locks.
+#[derive(Clone)]
+pub struct FileLocks {
+    locks: Arc<Mutex<HashMap<PathBuf, Weak<PathLock>>>>,
+    timeout: Duration,
+}
+
+impl Default for FileLocks {
+    fn default() -> Self {
+        Self {
+            locks: Arc::new(Mutex::new(HashMap::new())),
+            timeout: DEFAULT_LOCK_TIMEOUT,
+        }
+    }
+}
+
+impl FileLocks {
+    /// The locks every editor uses unless given others.
+    pub fn global() -> Self {
+        GLOBAL.clone()
+    }
+
+    /// Give up waiting for a lock after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Wait for the lock of `path`, held until the returned guard is dropped. Fails if
+    /// another edit holds it for longer than the timeout.
+    pub async fn lock(&self, path: &Path) -> Result<FileLock, String> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap();
+            // Forget the files nobody is editing.
+            locks.retain(|_, lock| lock.strong_count() > 0);
+            let key = lock_key(path);
+            match locks.get(&key).and_then(Weak::upgrade) {
+                Some(lock) => lock,
+                None => {
+                    let lock = Arc::new(PathLock::new(()));
+                    locks.insert(key, Arc::downgrade(&lock));
+                    lock
+                }
+            }
+        };
+        match tokio::time::timeout(self.timeout, lock.lock_owned()).await {
+            Ok(guard) => Ok(FileLock { _guard: guard }),
+            Err(_) => Err(format!(
+                "Another edit of '{}' is still in progress after {}s; try again once it is done",
+                path.display(),
+                self.timeout.as_secs_f64()
+            )),
+        }
+    }
+}
+
+/// Holds the lock of a file; see [`FileLocks::lock`].
+pub struct FileLock {
+    _guard: OwnedMutexGuard<()>,
+}
+
+/// The same key for every spelling of a path: canonical when the file or its directory
+/// exists.
+fn lock_key(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map(|parent| parent.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_locks_serialize_edits_of_a_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        std::fs::write(&path, "").unwrap();
+        let locks = FileLocks::default().with_timeout(Duration::from_millis(50));
+
+        let held = locks.lock(&path).await.unwrap();
+        // Another spelling of the same path waits, and gives up after the timeout.
+        let dotted = temp_dir.path().join(".").join("a.txt");
+        let error = locks.lock(&dotted).await.err().unwrap();
+        assert!(error.contains("still in progress"));
+        // Other files are not affected.
+        locks.lock(&temp_dir.path().join("b.txt")).await.unwrap();
+
+        drop(held);
+        locks.lock(&dotted).await.unwrap();
+        assert!(locks.locks.lock().unwrap().len() <= 1);
+    }
+}
@@ -0,0 +1,183 @@
+//! Workspace files a user message refers to.
+//!
+//! A message can name files of the conversation's workspace. Each becomes a [`FileRef`]
+//! recorded with the message, whose context block the agent reads after the message text
+//! without a tool call. Files up to [`MAX_INLINE_BYTES`] are included whole; larger ones are
+//! summarized by their first and last lines, and binary files are only described.
+
+use crate::paths::confine;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Largest file whose whole content is included.
+pub const MAX_INLINE_BYTES: usize = 32 * 1024;
+/// Files a single message can refer to.
+pub const MAX_FILE_REFS: usize = 20;
+/// Lines a summary shows from the start and the end of a file.
+const SUMMARY_HEAD_LINES: usize = 100;
+const SUMMARY_TAIL_LINES: usize = 20;
+
+/// How much of a referenced file the agent is given.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Inclusion {
+    Full,
+    /// The first and last lines.
+    Summary,
+    /// Nothing but the size, for binary files.
+    None,
+}
+
+/// A workspace file attached to a message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileRef {
+    /// As given with the message, relative to the workspace.
+    pub path: String,
+    pub bytes: u64,
+    pub inclusion: Inclusion,
+    /// What the agent is given: the content or summary between `<file>` delimiters.
+    pub block: String,
+}
+
+impl FileRef {
+    /// Read `path`, which must be a file inside `workspace_dir`.
+    pub fn read(workspace_dir: &Path, path: &str) -> Result<Self, String> {
+        let resolved = confine(workspace_dir, path)?;
+        // `confine` leaves the last component alone; a symlink must not lead outside either.
+        let canonical = resolved
+            .canonicalize()
+            .map_err(|_| format!("File '{}' not found in the workspace", path))?;
+        let workspace = workspace_dir.canonicalize().map_err(|e| e.to_string())?;
+        if !canonical.starts_with(&workspace) {
+            return Err(format!("Path '{}' is outside the workspace", path));
+        }
+        if canonical.is_dir() {
+            return Err(format!("'{}' is a directory, not a file", path));
+        }
+        let data =
+            std::fs::read(&canonical).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+        let bytes = data.len() as u64;
+
+        let text = String::from_utf8(data).ok().filter(|t| !t.contains('\0'));
+        let (inclusion, block) = match text {
+            None => (
+                Inclusion::None,
+                format!(
+                    "<file path=\"{}\" bytes=\"{}\">\nBinary file, not included.\n</file>",
+                    path, bytes
+                ),
+            ),
+            Some(text) if text.len() <= MAX_INLINE_BYTES => (
+                Inclusion::Full,
+                format!(
+                    "<file path=\"{}\">\n{}\n</file>",
+                    path,
+                    text.trim_end_matches('\n')
+                ),
+            ),
+            Some(text) => (Inclusion::Summary, summary_block(path, &text)),
+        };
+        Ok(Self {
+            path: path.to_string(),
+            bytes,
+            inclusion,
+            block,
+        })
+    }
+}
+
+/// The first and last lines of `text`, within [`MAX_INLINE_BYTES`] together.
+fn summary_block(path: &str, text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let head = take_lines(
+        lines.iter().copied(),
+        SUMMARY_HEAD_LINES,
+        MAX_INLINE_BYTES * 3 / 4,
+    );
+    let rest = &lines[head.len()..];
+    let mut tail = take_lines(
+        rest.iter().rev().copied(),
+        SUMMARY_TAIL_LINES,
+        MAX_INLINE_BYTES / 4,
+    );
+    tail.reverse();
+    let omitted = rest.len() - tail.len();
+    format!(
+        "<file path=\"{}\" lines=\"{}\" summarized=\"true\">\n{}\n[... {} lines omitted; view the file for the rest ...]\n{}\n</file>",
+        path,
+        lines.len(),
+        head.join("\n"),
+        omitted,
+        tail.join("\n")
+    )
+}
+
+/// Up to `max_lines` of `lines` within `max_bytes`; a line that does not fit is cut short.
+fn take_lines<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    max_lines: usize,
+    max_bytes: usize,
+) -> Vec<&'a str> {
+    let mut taken = Vec::new();
+    let mut budget = max_bytes;
+    for line in lines.take(max_lines) {
+        if line.len() > budget {
+            let mut end = budget;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            if end > 0 {
+                taken.push(&line[..end]);
+            }
+            break;
+        }
+        budget -= line.len();
+        taken.push(line);
+    }
+    taken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_file_refs() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path();
+        std::fs::write(workspace.join("small.rs"), "fn main() {}\n").unwrap();
+        let large: String = (0..5000).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(workspace.join("large.txt"), &large).unwrap();
+        std::fs::write(
+            workspace.join("image.png"),
+            [0x89, b'P', b'N', b'G', 0, 0xff],
+        )
+        .unwrap();
+
+        let small = FileRef::read(workspace, "small.rs").unwrap();
+        assert_eq!(small.inclusion, Inclusion::Full);
+        assert_eq!(
+            small.block,
+            "<file path=\"small.rs\">\nfn main() {}\n</file>"
+        );
+
+        let summary = FileRef::read(workspace, "large.txt").unwrap();
+        assert_eq!(summary.inclusion, Inclusion::Summary);
+        assert_eq!(summary.bytes, large.len() as u64);
+        assert!(summary.block.contains("line 99\n[... 4880 lines omitted"));
+        assert!(summary.block.ends_with("line 4999\n</file>"));
+
+        let binary = FileRef::read(workspace, "image.png").unwrap();
+        assert_eq!(binary.inclusion, Inclusion::None);
+
+        assert!(FileRef::read(workspace, "missing.txt").is_err());
+        assert!(FileRef::read(workspace, "../outside.txt").is_err());
+        std::fs::create_dir(workspace.join("src")).unwrap();
+        assert!(
+            FileRef::read(workspace, "src")
+                .unwrap_err()
+                .contains("directory")
+        );
+    }
+}
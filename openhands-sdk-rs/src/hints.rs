@@ -0,0 +1,181 @@
+//! Hints for failed commands.
+//!
+//! When a shell command fails, [`hint_for`] looks for well-known error signatures in its
+//! output (a missing module or crate, a missing command, permission denied, a port in use,
+//! the process killed for lack of memory, a full disk) and suggests a next step. Tools
+//! append the hint to their output as one `[Hint (<kind>)] ...` line, so the agent recovers
+//! without another LLM call to diagnose the error.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Exit code of a process killed by SIGKILL, which is what the OOM killer sends.
+const SIGKILL_EXIT_CODE: i32 = 137;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    MissingModule,
+    MissingCrate,
+    MissingCommand,
+    PermissionDenied,
+    PortInUse,
+    OutOfMemory,
+    DiskFull,
+}
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::MissingModule => "missing_module",
+            ErrorKind::MissingCrate => "missing_crate",
+            ErrorKind::MissingCommand => "missing_command",
+            ErrorKind::PermissionDenied => "permission_denied",
+            ErrorKind::PortInUse => "port_in_use",
+            ErrorKind::OutOfMemory => "out_of_memory",
+            ErrorKind::DiskFull => "disk_full",
+        }
+    }
+}
+
+/// What went wrong and what to try next.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorHint {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl fmt::Display for ErrorHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[Hint ({})] {}", self.kind.as_str(), self.message)
+    }
+}
+
+type Suggest = fn(&regex::Captures) -> String;
+
+lazy_static::lazy_static! {
+    /// Signatures in the order they are tried; the first match wins.
+    static ref SIGNATURES: Vec<(ErrorKind, Regex, Suggest)> = vec![
+        (
+            ErrorKind::MissingModule,
+            Regex::new(r"ModuleNotFoundError: No module named '([^'.]+)").unwrap(),
+            |c| format!("The Python module '{}' is not installed. Install it (e.g. `pip install {}`) or activate the project's virtual environment.", &c[1], &c[1]),
+        ),
+        (
+            ErrorKind::MissingModule,
+            Regex::new(r"Cannot find module '([^']+)'").unwrap(),
+            |c| format!("The Node module '{}' cannot be resolved. Run `npm install` (or `npm install {}`) in the project, or check the import path.", &c[1], &c[1]),
+        ),
+        (
+            ErrorKind::MissingCrate,
+            Regex::new(r"(?:can't find crate for|use of (?:undeclared|unresolved) (?:crate or )?module|unresolved import) `([A-Za-z0-9_]+)").unwrap(),
+            |c| format!("`{}` is not a dependency or module of this crate. Add it with `cargo add {}` if it is a crate, or fix the `use` path.", &c[1], &c[1].replace('_', "-")),
+        ),
+        (
+            ErrorKind::MissingCommand,
+            Regex::new(r"(?m)(?:^|: )([^\s:]+): (?:command not found|not found)$").unwrap(),
+            |c| format!("`{}` is not installed or not on PATH. Install it with the system's package manager, or check `environment_info` for what is available.", &c[1]),
+        ),
+        (
+            ErrorKind::PortInUse,
+            Regex::new(r"(?i)(?:address already in use|EADDRINUSE)(?:.*?:(\d{2,5}))?").unwrap(),
+            |c| match c.get(1) {
+                Some(port) => format!("Port {} is already taken, probably by a server started earlier. Stop it (`fuser -k {}/tcp`) or use another port.", port.as_str(), port.as_str()),
+                None => "The port is already taken, probably by a server started earlier. Stop it or use another port.".to_string(),
+            },
+        ),
+        (
+            ErrorKind::DiskFull,
+            Regex::new(r"No space left on device").unwrap(),
+            |_| "The disk is full. Remove build artifacts or caches (e.g. `cargo clean`, `rm -rf node_modules`) and try again.".to_string(),
+        ),
+        (
+            ErrorKind::OutOfMemory,
+            Regex::new(r"(?m)(?:^Killed$|MemoryError|Out of memory|Cannot allocate memory|memory allocation of \d+ bytes failed)").unwrap(),
+            |_| "The process ran out of memory. Use smaller inputs, fewer parallel jobs (e.g. `-j 1`) or stream the data instead of loading it at once.".to_string(),
+        ),
+        (
+            ErrorKind::PermissionDenied,
+            Regex::new(r"(?m)(?:^|: )(\S+): Permission denied").unwrap(),
+            |c| format!("Permission denied on `{}`. Check its owner and mode (`ls -l`); make scripts executable with `chmod +x` or write somewhere the user may.", &c[1]),
+        ),
+    ];
+}
+
+/// A hint for a command that exited with `exit_code` and printed `output`, if it failed
+/// with a known error.
+pub fn hint_for(exit_code: Option<i32>, output: &str) -> Option<ErrorHint> {
+    if exit_code == Some(0) {
+        return None;
+    }
+    let matched = SIGNATURES.iter().find_map(|(kind, pattern, suggest)| {
+        let captures = pattern.captures(output)?;
+        Some(ErrorHint {
+            kind: *kind,
+            message: suggest(&captures),
+        })
+    });
+    matched.or_else(|| {
+        (exit_code == Some(SIGKILL_EXIT_CODE)).then(|| ErrorHint {
+            kind: ErrorKind::OutOfMemory,
+            message: "The process was killed (exit code 137), most likely for running out of memory. Use smaller inputs or fewer parallel jobs.".to_string(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(exit_code: i32, output: &str) -> Option<ErrorKind> {
+        hint_for(Some(exit_code), output).map(|hint| hint.kind)
+    }
+
+    #[test]
+    fn test_error_signatures() {
+        let hint = hint_for(
+            Some(1),
+            "Traceback (most recent call last):\nModuleNotFoundError: No module named 'yaml'",
+        )
+        .unwrap();
+        assert_eq!(
+            hint.to_string(),
+            "[Hint (missing_module)] The Python module 'yaml' is not installed. Install it (e.g. `pip install yaml`) or activate the project's virtual environment."
+        );
+        assert_eq!(
+            kind(1, "Error: Cannot find module 'express'"),
+            Some(ErrorKind::MissingModule)
+        );
+        let hint = hint_for(
+            Some(101),
+            "error[E0433]: failed to resolve: use of undeclared crate or module `serde_json`",
+        )
+        .unwrap();
+        assert!(hint.message.contains("cargo add serde-json"));
+        assert_eq!(
+            kind(127, "bash: line 1: rg: command not found"),
+            Some(ErrorKind::MissingCommand)
+        );
+        assert_eq!(
+            kind(126, "bash: ./run.sh: Permission denied"),
+            Some(ErrorKind::PermissionDenied)
+        );
+        let hint = hint_for(
+            Some(1),
+            "Error: listen EADDRINUSE: address already in use :::3000",
+        )
+        .unwrap();
+        assert_eq!(hint.kind, ErrorKind::PortInUse);
+        assert!(hint.message.starts_with("Port 3000 is already taken"));
+        assert_eq!(kind(137, ""), Some(ErrorKind::OutOfMemory));
+        assert_eq!(
+            kind(1, "cp: error writing 'out.bin': No space left on device"),
+            Some(ErrorKind::DiskFull)
+        );
+
+        // Successful commands and unknown errors get no hint.
+        assert_eq!(kind(0, "ModuleNotFoundError: No module named 'yaml'"), None);
+        assert_eq!(kind(1, "assertion failed: left == right"), None);
+    }
+}
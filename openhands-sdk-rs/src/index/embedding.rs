@@ -0,0 +1,218 @@
+use async_trait::async_trait;
+#[cfg(feature = "http")]
+use serde::Deserialize;
+use std::sync::Arc;
+
+use super::fnv1a;
+
+/// Computes vector embeddings for chunks of text.
+///
+/// Implementations must return one vector per input text, all with the same dimensionality.
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Identifier of the embedding model, stored alongside the index so that
+    /// vectors from different models are never compared.
+    fn model_id(&self) -> String;
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String>;
+}
+
+/// [`OpenAIEmbeddingProvider`] when `OPENAI_API_KEY` is set and the `http` feature is
+/// enabled.
+pub fn provider_from_env() -> Option<Arc<dyn EmbeddingProvider>> {
+    #[cfg(feature = "http")]
+    if let Some(provider) = OpenAIEmbeddingProvider::from_env() {
+        return Some(Arc::new(provider));
+    }
+    None
+}
+
+/// Embeddings from the OpenAI `/v1/embeddings` endpoint.
+#[cfg(feature = "http")]
+pub struct OpenAIEmbeddingProvider {
+    pub model: String,
+    pub api_key: String,
+    pub base_url: String,
+}
+
+#[cfg(feature = "http")]
+impl OpenAIEmbeddingProvider {
+    const BATCH_SIZE: usize = 64;
+
+    pub fn new(api_key: String) -> Self {
+        Self {
+            model: "text-embedding-3-small".to_string(),
+            api_key,
+            base_url: "https://api.openai.com/v1".to_string(),
+        }
+    }
+
+    /// Create a provider using `OPENAI_API_KEY` from the environment.
+    pub fn from_env() -> Option<Self> {
+        std::env::var("OPENAI_API_KEY").ok().map(Self::new)
+    }
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[cfg(feature = "http")]
+#[async_trait]
+impl EmbeddingProvider for OpenAIEmbeddingProvider {
+    fn model_id(&self) -> String {
+        format!("openai/{}", self.model)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let client = reqwest::Client::new();
+        let mut vectors = Vec::with_capacity(texts.len());
+
+        for batch in texts.chunks(Self::BATCH_SIZE) {
+            let res = client
+                .post(format!("{}/embeddings", self.base_url))
+                .bearer_auth(&self.api_key)
+                .json(&serde_json::json!({ "model": self.model, "input": batch }))
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if !res.status().is_success() {
+                let status = res.status();
+                let error_text = res.text().await.unwrap_or_default();
+                return Err(format!(
+                    "Embedding request failed {}: {}",
+                    status, error_text
+                ));
+            }
+
+            let mut body: EmbeddingResponse = res.json().await.map_err(|e| e.to_string())?;
+            body.data.sort_by_key(|d| d.index);
+            vectors.extend(body.data.into_iter().map(|d| d.embedding));
+        }
+
+        Ok(vectors)
+    }
+}
+
+/// A local, dependency-free embedding model based on feature hashing of identifier tokens.
+///
+/// It captures lexical rather than deep semantic similarity, but works offline and is
+/// deterministic, which makes it a sensible fallback when no API key is configured.
+pub struct HashingEmbeddingProvider {
+    pub dimensions: usize,
+}
+
+impl Default for HashingEmbeddingProvider {
+    fn default() -> Self {
+        Self { dimensions: 256 }
+    }
+}
+
+impl HashingEmbeddingProvider {
+    fn embed_one(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        for token in tokenize(text) {
+            let h = fnv1a(token.as_bytes());
+            let bucket = (h % self.dimensions as u64) as usize;
+            let sign = if (h >> 63) == 0 { 1.0 } else { -1.0 };
+            vector[bucket] += sign;
+        }
+        normalize(&mut vector);
+        vector
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashingEmbeddingProvider {
+    fn model_id(&self) -> String {
+        format!("local/hashing-{}", self.dimensions)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        Ok(texts.iter().map(|t| self.embed_one(t)).collect())
+    }
+}
+
+/// Split text into lowercase word tokens, breaking `camelCase` and `snake_case` identifiers.
+pub(crate) fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        if word.is_empty() {
+            continue;
+        }
+        let mut current = String::new();
+        let mut prev_lower = false;
+        for c in word.chars() {
+            if c.is_uppercase() && prev_lower && !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+            current.extend(c.to_lowercase());
+        }
+        if !current.is_empty() {
+            tokens.push(current);
+        }
+    }
+    tokens
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|v| v * v).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_splits_identifiers() {
+        assert_eq!(
+            tokenize("parseHttpRequest snake_case"),
+            vec!["parse", "http", "request", "snake", "case"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hashing_provider_similarity() {
+        let provider = HashingEmbeddingProvider::default();
+        let vectors = provider
+            .embed(&[
+                "fn parse_config(path: &str) -> Config".to_string(),
+                "parse the config file".to_string(),
+                "render html template".to_string(),
+            ])
+            .await
+            .unwrap();
+
+        let related = cosine_similarity(&vectors[0], &vectors[1]);
+        let unrelated = cosine_similarity(&vectors[0], &vectors[2]);
+        assert!(related > unrelated);
+    }
+}
@@ -0,0 +1,342 @@
+//! Semantic code search over the files of a workspace.
+//!
+//! Files are split into overlapping line-based chunks, embedded through an
+//! [`EmbeddingProvider`], and stored in a small JSON vector store under [`index_root`],
+//! outside the workspace so the index never ends up in the repository. Rebuilding the
+//! index only re-embeds files whose content changed since the last build.
+
+pub mod embedding;
+
+#[cfg(feature = "http")]
+pub use embedding::OpenAIEmbeddingProvider;
+pub use embedding::{
+    EmbeddingProvider, HashingEmbeddingProvider, cosine_similarity, provider_from_env,
+};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const CHUNK_LINES: usize = 40;
+const CHUNK_OVERLAP: usize = 10;
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Directories that never contain code worth indexing.
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "__pycache__", "dist", "build"];
+
+/// Directory holding the indexes and memories of all workspaces: `OPENHANDS_INDEX_DIR`, or
+/// `openhands-index` in the system temp directory.
+pub fn index_root() -> PathBuf {
+    std::env::var_os("OPENHANDS_INDEX_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("openhands-index"))
+}
+
+/// File name stem under [`index_root`] of the data kept for the workspace at `root`: a hash
+/// of its canonical path.
+pub(crate) fn workspace_key(root: &Path) -> String {
+    let canonical = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    format!("{:016x}", fnv1a(canonical.to_string_lossy().as_bytes()))
+}
+
+/// 64-bit FNV-1a hash. Stable across Rust versions, unlike `DefaultHasher`,
+/// so it is safe to persist.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Chunk {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct VectorStore {
+    model_id: String,
+    /// Content hash per relative file path, used for incremental rebuilds.
+    file_hashes: HashMap<String, u64>,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub score: f32,
+    pub snippet: String,
+}
+
+pub struct CodeIndex {
+    root: PathBuf,
+    store_path: PathBuf,
+    provider: Arc<dyn EmbeddingProvider>,
+    store: Mutex<Option<VectorStore>>,
+}
+
+impl CodeIndex {
+    /// The index of the workspace at `root`, stored under [`index_root`] in a file named
+    /// after the workspace's path.
+    pub fn new(root: PathBuf, provider: Arc<dyn EmbeddingProvider>) -> Self {
+        let store_path = index_root().join(format!("{}.json", workspace_key(&root)));
+        Self {
+            root,
+            store_path,
+            provider,
+            store: Mutex::new(None),
+        }
+    }
+
+    /// Bring the index up to date with the workspace. Returns the number of re-embedded files.
+    pub async fn refresh(&self) -> Result<usize, String> {
+        let mut guard = self.store.lock().await;
+        let mut store = match guard.take() {
+            Some(s) => s,
+            None => self.load_store(),
+        };
+
+        let model_id = self.provider.model_id();
+        if store.model_id != model_id {
+            store = VectorStore {
+                model_id,
+                ..Default::default()
+            };
+        }
+
+        let mut current_files = HashMap::new();
+        collect_files(&self.root, &self.root, &mut current_files);
+
+        // Drop chunks of files that were deleted or changed.
+        let changed: HashSet<String> = current_files
+            .iter()
+            .filter(|(path, (hash, _))| store.file_hashes.get(*path) != Some(hash))
+            .map(|(path, _)| path.clone())
+            .collect();
+        store
+            .chunks
+            .retain(|c| current_files.contains_key(&c.path) && !changed.contains(&c.path));
+        store
+            .file_hashes
+            .retain(|path, _| current_files.contains_key(path));
+
+        let mut pending = Vec::new();
+        for path in &changed {
+            let (hash, content) = &current_files[path];
+            for (start_line, end_line, text) in chunk_lines(content) {
+                pending.push(Chunk {
+                    path: path.clone(),
+                    start_line,
+                    end_line,
+                    text,
+                    embedding: Vec::new(),
+                });
+            }
+            store.file_hashes.insert(path.clone(), *hash);
+        }
+
+        if !pending.is_empty() {
+            let texts: Vec<String> = pending
+                .iter()
+                .map(|c| format!("{}\n{}", c.path, c.text))
+                .collect();
+            let embeddings = self.provider.embed(&texts).await?;
+            if embeddings.len() != pending.len() {
+                return Err(format!(
+                    "Embedding provider returned {} vectors for {} chunks",
+                    embeddings.len(),
+                    pending.len()
+                ));
+            }
+            for (chunk, embedding) in pending.iter_mut().zip(embeddings) {
+                chunk.embedding = embedding;
+            }
+            store.chunks.extend(pending);
+        }
+
+        self.save_store(&store)?;
+        *guard = Some(store);
+        Ok(changed.len())
+    }
+
+    /// Return the `top_k` chunks most similar to `query`. The index must have been refreshed.
+    pub async fn search(&self, query: &str, top_k: usize) -> Result<Vec<SearchHit>, String> {
+        let guard = self.store.lock().await;
+        let store = guard
+            .as_ref()
+            .ok_or("Index has not been built yet. Call refresh() first.")?;
+
+        let query_embedding = self
+            .provider
+            .embed(&[query.to_string()])
+            .await?
+            .pop()
+            .ok_or("Embedding provider returned no vector for the query")?;
+
+        let mut hits: Vec<SearchHit> = store
+            .chunks
+            .iter()
+            .map(|c| SearchHit {
+                path: c.path.clone(),
+                start_line: c.start_line,
+                end_line: c.end_line,
+                score: cosine_similarity(&query_embedding, &c.embedding),
+                snippet: c.text.clone(),
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(top_k);
+        Ok(hits)
+    }
+
+    fn load_store(&self) -> VectorStore {
+        fs::read_to_string(&self.store_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_store(&self, store: &VectorStore) -> Result<(), String> {
+        if let Some(parent) = self.store_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create index dir: {}", e))?;
+        }
+        let json = serde_json::to_string(store).map_err(|e| e.to_string())?;
+        fs::write(&self.store_path, json).map_err(|e| format!("Failed to write index: {}", e))
+    }
+}
+
+/// Collect indexable text files as `relative path -> (content hash, content)`.
+fn collect_files(root: &Path, dir: &Path, files: &mut HashMap<String, (u64, String)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+
+        // Symlinks are skipped so the index never follows a link out of the workspace.
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if !SKIPPED_DIRS.contains(&name) {
+                collect_files(root, &path, files);
+            }
+        } else if file_type.is_file() {
+            if entry.metadata().map(|m| m.len()).unwrap_or(u64::MAX) > MAX_FILE_BYTES {
+                continue;
+            }
+            // Non-UTF8 content is treated as binary and skipped.
+            if let Ok(content) = fs::read_to_string(&path) {
+                if content.contains('\0') {
+                    continue;
+                }
+                let rel = path
+                    .strip_prefix(root)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .to_string();
+                files.insert(rel, (fnv1a(content.as_bytes()), content));
+            }
+        }
+    }
+}
+
+/// Split content into overlapping windows of lines: `(start_line, end_line, text)`, 1-indexed.
+fn chunk_lines(content: &str) -> Vec<(usize, usize, String)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start + CHUNK_LINES).min(lines.len());
+        let text = lines[start..end].join("\n");
+        if !text.trim().is_empty() {
+            chunks.push((start + 1, end, text));
+        }
+        if end == lines.len() {
+            break;
+        }
+        start = end - CHUNK_OVERLAP;
+    }
+    chunks
+}
+
+/// Point `OPENHANDS_INDEX_DIR` at a temp dir shared by all tests of this process, so tests
+/// never write to the real index location.
+#[cfg(test)]
+pub(crate) fn use_test_index_root() {
+    static DIR: std::sync::OnceLock<tempfile::TempDir> = std::sync::OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = tempfile::TempDir::new().unwrap();
+        // SAFETY: set once, before any test reads the variable through `index_root`.
+        unsafe { std::env::set_var("OPENHANDS_INDEX_DIR", dir.path()) };
+        dir
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_chunk_lines_overlap() {
+        let content: String = (1..=100).map(|i| format!("line{}\n", i)).collect();
+        let chunks = chunk_lines(&content);
+        assert_eq!(chunks[0].0, 1);
+        assert_eq!(chunks[0].1, 40);
+        assert_eq!(chunks[1].0, 31);
+        assert_eq!(chunks.last().unwrap().1, 100);
+    }
+
+    #[tokio::test]
+    async fn test_index_search_and_incremental_refresh() {
+        use_test_index_root();
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(
+            root.join("auth.rs"),
+            "fn verify_password(hash: &str, password: &str) -> bool {}",
+        )
+        .unwrap();
+        fs::write(root.join("render.rs"), "fn render_template(html: &str) {}").unwrap();
+
+        let index = CodeIndex::new(
+            root.to_path_buf(),
+            Arc::new(HashingEmbeddingProvider::default()),
+        );
+        assert_eq!(index.refresh().await.unwrap(), 2);
+
+        let hits = index.search("verify password", 1).await.unwrap();
+        assert_eq!(hits[0].path, "auth.rs");
+
+        // Unchanged files are not re-embedded, also after reloading from disk.
+        let reloaded = CodeIndex::new(
+            root.to_path_buf(),
+            Arc::new(HashingEmbeddingProvider::default()),
+        );
+        assert_eq!(reloaded.refresh().await.unwrap(), 0);
+        assert!(!root.join(".openhands").exists());
+        assert!(reloaded.store_path.starts_with(index_root()));
+
+        fs::write(root.join("render.rs"), "fn render_page() {}").unwrap();
+        assert_eq!(reloaded.refresh().await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,151 @@
+//! The files tools changed, as they were before, for undo across tools.
+//!
+//! Every tool that writes or deletes a file records the file's content first in the
+//! [`FileMutationJournal`] it shares with the other file tools of its conversation. An
+//! editor's undo then restores the file as it was before its last change, whichever tool
+//! made it, and [`FileMutationJournal::undo`] takes back a conversation's last changes.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Changes the journal remembers; older ones can no longer be undone.
+pub const MAX_MUTATIONS: usize = 500;
+
+/// A change of a file, without its content.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MutationRecord {
+    pub path: PathBuf,
+    /// The tool that made the change.
+    pub tool: String,
+    pub timestamp: DateTime<Utc>,
+    /// Whether the change created the file, so undoing it deletes the file.
+    pub created: bool,
+}
+
+struct Mutation {
+    record: MutationRecord,
+    /// The file's content before the change; `None` if it did not exist.
+    before: Option<Vec<u8>>,
+}
+
+impl Mutation {
+    /// Put the file back as it was before the change.
+    fn restore(&self) -> Result<(), String> {
+        let path = &self.record.path;
+        match &self.before {
+            Some(content) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to restore {}: {}", path.display(), e))?;
+                }
+                std::fs::write(path, content)
+                    .map_err(|e| format!("Failed to restore {}: {}", path.display(), e))
+            }
+            None => match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(format!("Failed to remove {}: {}", path.display(), e))
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+/// The changes made by a set of tools, oldest first; clones share them.
+#[derive(Clone, Default)]
+pub struct FileMutationJournal {
+    mutations: Arc<Mutex<Vec<Mutation>>>,
+}
+
+impl FileMutationJournal {
+    /// Remember `path` as it is now, before `tool` changes it.
+    pub fn record(&self, tool: &str, path: &Path) {
+        let before = std::fs::read(path).ok();
+        let mut mutations = self.mutations.lock().unwrap();
+        if mutations.len() >= MAX_MUTATIONS {
+            mutations.remove(0);
+        }
+        mutations.push(Mutation {
+            record: MutationRecord {
+                path: path.to_path_buf(),
+                tool: tool.to_string(),
+                timestamp: Utc::now(),
+                created: before.is_none(),
+            },
+            before,
+        });
+    }
+
+    /// The changes that can be undone, oldest first.
+    pub fn records(&self) -> Vec<MutationRecord> {
+        let mutations = self.mutations.lock().unwrap();
+        mutations.iter().map(|m| m.record.clone()).collect()
+    }
+
+    /// Undo the last change of `path`, by any tool.
+    pub fn undo_last(&self, path: &Path) -> Result<MutationRecord, String> {
+        let mut mutations = self.mutations.lock().unwrap();
+        let index = mutations
+            .iter()
+            .rposition(|m| m.record.path == path)
+            .ok_or_else(|| format!("No edit history found for {}", path.display()))?;
+        mutations[index].restore()?;
+        Ok(mutations.remove(index).record)
+    }
+
+    /// Undo the last `count` changes, newest first. Stops at the first change that cannot
+    /// be undone, which stays in the journal.
+    pub fn undo(&self, count: usize) -> Result<Vec<MutationRecord>, String> {
+        let mut mutations = self.mutations.lock().unwrap();
+        let mut undone = Vec::new();
+        while undone.len() < count {
+            let Some(mutation) = mutations.last() else {
+                break;
+            };
+            if let Err(e) = mutation.restore() {
+                return match undone.is_empty() {
+                    true => Err(e),
+                    false => Ok(undone),
+                };
+            }
+            undone.push(mutations.pop().unwrap().record);
+        }
+        Ok(undone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_undo_across_tools() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = temp_dir.path().join("a.txt");
+        let b = temp_dir.path().join("b.txt");
+        std::fs::write(&a, "one").unwrap();
+        let journal = FileMutationJournal::default();
+
+        journal.record("file_editor", &a);
+        std::fs::write(&a, "two").unwrap();
+        journal.record("write_file", &b);
+        std::fs::write(&b, "new").unwrap();
+        journal.record("apply_patch", &a);
+        std::fs::write(&a, "three").unwrap();
+
+        // Undoing a file restores it before its last change, whatever tool made it.
+        let undone = journal.undo_last(&a).unwrap();
+        assert_eq!(undone.tool, "apply_patch");
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "two");
+
+        let undone = journal.undo(5).unwrap();
+        assert_eq!(undone.len(), 2);
+        assert!(undone[0].created);
+        assert!(!b.exists());
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "one");
+        assert!(journal.undo_last(&a).is_err());
+    }
+}
@@ -1,8 +1,45 @@
+pub mod activity;
 pub mod agent;
+pub mod bus;
+#[cfg(feature = "remote-runtime")]
+pub mod client;
 pub mod events;
+pub mod export;
+pub mod file_locks;
+pub mod file_refs;
+#[cfg(feature = "http")]
+pub mod github;
+pub mod hints;
+pub mod index;
+pub mod injection;
+pub mod journal;
+#[cfg(feature = "llm")]
 pub mod llm;
+#[cfg(feature = "logging")]
 pub mod logger;
+pub mod mcp;
+pub mod memory;
 pub mod models;
+#[cfg(feature = "http")]
+pub mod notifications;
+pub mod outcome;
+pub mod paths;
+#[cfg(feature = "http")]
+pub mod plugins;
+pub mod profile;
+pub mod provenance;
+pub mod redact;
+pub mod replay;
+#[cfg(feature = "http")]
+pub mod repo_host;
+#[cfg(feature = "llm")]
+pub mod resolver;
+pub mod retention;
 pub mod runtime;
+pub mod security;
+#[cfg(feature = "llm")]
 pub mod session;
+pub mod setup;
+pub mod shell_output;
 pub mod system;
+pub mod testing;
@@ -1,7 +1,19 @@
+pub mod fixtures;
+pub mod schema;
+
+use self::fixtures::{FixtureRecorder, MockLLM, RecordedRequest};
+use self::schema::SchemaProfile;
+pub use crate::events::LLMDelta;
+pub use crate::profile::TokenUsage;
+use futures::StreamExt;
 use genai::Client;
-use genai::chat::{ChatMessage, ChatRequest};
-use serde::Deserialize;
+use genai::adapter::AdapterKind;
+use genai::chat::{ChatMessage, ChatOptions, ChatRequest, ChatStreamEvent, ReasoningEffort};
+use serde::{Deserialize, Serialize};
 use std::env;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct LLM {
@@ -9,13 +21,49 @@ pub struct LLM {
     pub client: Client,
     pub api_key: Option<String>,
     pub reasoning_effort: Option<String>,
+    pub thinking_budget: Option<u32>,
+    /// Tokens used by all completions so far; shared between clones.
+    usage: Arc<Mutex<TokenUsage>>,
+    /// Answers completions instead of the provider, in tests and replays.
+    mock: Option<Arc<MockLLM>>,
+    /// Where every exchange is recorded, if anywhere.
+    recorder: Option<Arc<FixtureRecorder>>,
+}
+
+fn token_usage(usage: &genai::chat::Usage) -> TokenUsage {
+    TokenUsage {
+        prompt_tokens: usage.prompt_tokens.unwrap_or(0).max(0) as u64,
+        completion_tokens: usage.completion_tokens.unwrap_or(0).max(0) as u64,
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct LLMConfig {
     pub model: String,
     pub api_key: Option<String>,
+    /// `minimal`, `low`, `medium` or `high`, for models that reason before they answer.
+    /// Other values are ignored.
     pub reasoning_effort: Option<String>,
+    /// Tokens the model may spend thinking before it answers, for providers that take a
+    /// budget (Anthropic extended thinking, Gemini). Takes precedence over
+    /// `reasoning_effort`.
+    #[serde(default)]
+    pub thinking_budget: Option<u32>,
+}
+
+impl LLMConfig {
+    /// Configuration from `LLM_MODEL` (default `gpt-5-nano`), `OPENAI_API_KEY`,
+    /// `LLM_REASONING_EFFORT` and `LLM_THINKING_BUDGET`.
+    pub fn from_env() -> Self {
+        Self {
+            model: env::var("LLM_MODEL").unwrap_or_else(|_| "gpt-5-nano".to_string()),
+            api_key: env::var("OPENAI_API_KEY").ok(),
+            reasoning_effort: env::var("LLM_REASONING_EFFORT").ok(),
+            thinking_budget: env::var("LLM_THINKING_BUDGET")
+                .ok()
+                .and_then(|budget| budget.parse().ok()),
+        }
+    }
 }
 
 impl LLM {
@@ -26,18 +74,250 @@ impl LLM {
             client,
             api_key: config.api_key,
             reasoning_effort: config.reasoning_effort,
+            thinking_budget: config.thinking_budget,
+            usage: Arc::default(),
+            mock: None,
+            recorder: None,
+        }
+    }
+
+    /// An LLM configured step by step; see [`LLMBuilder`].
+    pub fn builder() -> LLMBuilder {
+        LLMBuilder::default()
+    }
+
+    /// An LLM answered by `mock` instead of a provider.
+    pub fn mock(mock: Arc<MockLLM>) -> Self {
+        let mut llm = Self::new(LLMConfig {
+            model: "mock".to_string(),
+            api_key: None,
+            reasoning_effort: None,
+            thinking_budget: None,
+        });
+        llm.mock = Some(mock);
+        llm
+    }
+
+    /// Append every request and response, redacted, to the JSONL fixture at `path`; see
+    /// [`fixtures`].
+    pub fn with_recorder(mut self, path: PathBuf) -> Self {
+        self.recorder = Some(Arc::new(FixtureRecorder::new(path)));
+        self
+    }
+
+    /// Total tokens used by this LLM (and its clones).
+    pub fn usage(&self) -> TokenUsage {
+        *self.usage.lock().unwrap()
+    }
+
+    /// Check that the provider is reachable and accepts the API key by listing its models,
+    /// which costs no tokens.
+    pub async fn check_connectivity(&self) -> Result<(), String> {
+        let target = self
+            .client
+            .resolve_service_target(&self.model)
+            .await
+            .map_err(|e| e.to_string())?;
+        let key = match &self.api_key {
+            Some(key) => key.clone(),
+            None => target
+                .auth
+                .single_key_value()
+                .map_err(|_| "No API key configured".to_string())?,
+        };
+
+        let request = reqwest::Client::new()
+            .get(format!("{}models", target.endpoint.base_url()))
+            .timeout(Duration::from_secs(10));
+        let request = match target.model.adapter_kind {
+            _ if key.is_empty() => request,
+            AdapterKind::Anthropic => request
+                .header("x-api-key", &key)
+                .header("anthropic-version", "2023-06-01"),
+            AdapterKind::Gemini => request.header("x-goog-api-key", &key),
+            _ => request.bearer_auth(&key),
+        };
+
+        let res = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach LLM provider: {}", e))?;
+        match res.status() {
+            status if status.is_success() => Ok(()),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                Err("LLM provider rejected the API key".to_string())
+            }
+            status => Err(format!("LLM provider returned {}", status)),
         }
     }
 
+    /// Check that the provider of this LLM accepts `tools` once their schemas are adapted
+    /// to it, so a schema it rejects fails at startup instead of mid-conversation.
+    pub fn check_tools(&self, tools: &[genai::chat::Tool]) -> Result<(), String> {
+        let profile = SchemaProfile::for_model(&self.model);
+        profile.validate(&profile.adapt_tools(tools.to_vec()))
+    }
+
     pub async fn completion(
         &self,
         messages: Vec<ChatMessage>,
         tools: Option<Vec<genai::chat::Tool>>, // Use genai Tool type
     ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let mut chat_req = ChatRequest::new(messages);
+        let request = RecordedRequest { messages, tools };
+        let result = match &self.mock {
+            Some(mock) => mock.respond(&request),
+            None => self.exec_chat(&request).await.map_err(|e| e.to_string()),
+        };
+        self.finish(&request, result)
+    }
+
+    /// Like [`LLM::completion`], but streams the response, calling `on_delta` with its text
+    /// and tool calls as they arrive. Returns the whole response once the stream ends. A
+    /// mock answers with one delta for its reasoning, one for its text and one per tool call.
+    pub async fn completion_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<genai::chat::Tool>>,
+        on_delta: &(dyn Fn(LLMDelta) + Send + Sync),
+    ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let request = RecordedRequest { messages, tools };
+        let result = match &self.mock {
+            Some(mock) => mock.respond(&request).inspect(|response| {
+                if let Some(reasoning) = &response.reasoning {
+                    on_delta(LLMDelta::Reasoning {
+                        text: reasoning.clone(),
+                    });
+                }
+                if !response.content.is_empty() {
+                    on_delta(LLMDelta::Content {
+                        text: response.content.clone(),
+                    });
+                }
+                for call in &response.tool_calls {
+                    on_delta(tool_call_delta(call));
+                }
+            }),
+            None => self
+                .exec_chat_stream(&request, on_delta)
+                .await
+                .map_err(|e| e.to_string()),
+        };
+        self.finish(&request, result)
+    }
+
+    /// Record the exchange and count its tokens.
+    fn finish(
+        &self,
+        request: &RecordedRequest,
+        result: Result<LLMResponse, String>,
+    ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&self.model, request, &result);
+        }
+        let response = result?;
+
+        let mut total = self.usage.lock().unwrap();
+        total.prompt_tokens += response.usage.prompt_tokens;
+        total.completion_tokens += response.usage.completion_tokens;
+        Ok(response)
+    }
+
+    async fn exec_chat(
+        &self,
+        request: &RecordedRequest,
+    ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let chat_req = self.chat_request(request);
+        let output = self
+            .client
+            .exec_chat(&self.model, chat_req, Some(&self.chat_options()))
+            .await?;
+
+        let text: String = output.content.texts().join("");
+        let tool_calls: Vec<genai::chat::ToolCall> =
+            output.tool_calls().iter().map(|t| (*t).clone()).collect();
+
+        Ok(LLMResponse {
+            content: text,
+            tool_calls,
+            usage: token_usage(&output.usage),
+            reasoning: output.reasoning_content.filter(|r| !r.is_empty()),
+        })
+    }
+
+    async fn exec_chat_stream(
+        &self,
+        request: &RecordedRequest,
+        on_delta: &(dyn Fn(LLMDelta) + Send + Sync),
+    ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let chat_req = self.chat_request(request);
+        let options = self
+            .chat_options()
+            .with_capture_usage(true)
+            .with_capture_content(true)
+            .with_capture_tool_calls(true);
+        let mut stream = self
+            .client
+            .exec_chat_stream(&self.model, chat_req, Some(&options))
+            .await?
+            .stream;
+
+        while let Some(event) = stream.next().await {
+            match event? {
+                ChatStreamEvent::Chunk(chunk) if !chunk.content.is_empty() => {
+                    on_delta(LLMDelta::Content {
+                        text: chunk.content,
+                    });
+                }
+                ChatStreamEvent::ReasoningChunk(chunk) if !chunk.content.is_empty() => {
+                    on_delta(LLMDelta::Reasoning {
+                        text: chunk.content,
+                    });
+                }
+                ChatStreamEvent::ToolCallChunk(chunk) => {
+                    on_delta(tool_call_delta(&chunk.tool_call))
+                }
+                ChatStreamEvent::End(end) => {
+                    let content = end.captured_content.unwrap_or_default();
+                    return Ok(LLMResponse {
+                        content: content.texts().join(""),
+                        tool_calls: content.tool_calls().into_iter().cloned().collect(),
+                        usage: end
+                            .captured_usage
+                            .as_ref()
+                            .map(token_usage)
+                            .unwrap_or_default(),
+                        reasoning: end.captured_reasoning_content.filter(|r| !r.is_empty()),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Err("The LLM response stream ended early".into())
+    }
 
-        if let Some(t) = tools {
-            chat_req = chat_req.with_tools(t);
+    /// Reasoning as configured, with the reasoning the provider returns kept, including
+    /// `<think>` blocks some models put in their text.
+    fn chat_options(&self) -> ChatOptions {
+        let options = ChatOptions::default()
+            .with_capture_reasoning_content(true)
+            .with_normalize_reasoning_content(true);
+        let effort = match (self.thinking_budget, &self.reasoning_effort) {
+            (Some(budget), _) => Some(ReasoningEffort::Budget(budget)),
+            (None, Some(keyword)) => ReasoningEffort::from_keyword(keyword),
+            (None, None) => None,
+        };
+        match effort {
+            Some(effort) => options.with_reasoning_effort(effort),
+            None => options,
+        }
+    }
+
+    fn chat_request(&self, request: &RecordedRequest) -> ChatRequest {
+        let mut chat_req = ChatRequest::new(request.messages.clone());
+
+        if let Some(t) = &request.tools {
+            let profile = SchemaProfile::for_model(&self.model);
+            chat_req = chat_req.with_tools(profile.adapt_tools(t.clone()));
         }
 
         if let Some(key) = &self.api_key {
@@ -53,24 +333,109 @@ impl LLM {
             }
         }
 
-        // We use full stream for consistency if we wanted, but exec_chat is fine.
-        let output = self.client.exec_chat(&self.model, chat_req, None).await?;
+        chat_req
+    }
+}
 
-        let text: String = output.content.texts().join("");
-        let tool_calls: Vec<genai::chat::ToolCall> =
-            output.tool_calls().iter().map(|t| (*t).clone()).collect();
+/// The delta reporting `call` as received so far. Providers that stream the arguments send
+/// them as accumulated text; the others send the call once, complete.
+fn tool_call_delta(call: &genai::chat::ToolCall) -> LLMDelta {
+    let arguments = match &call.fn_arguments {
+        serde_json::Value::String(text) => text.clone(),
+        value => value.to_string(),
+    };
+    LLMDelta::ToolCall {
+        call_id: call.call_id.clone(),
+        tool_name: call.fn_name.clone(),
+        arguments,
+    }
+}
 
-        Ok(LLMResponse {
-            content: text,
-            tool_calls,
-        })
+/// Builds an [`LLM`]. The model is required unless the LLM is a mock.
+#[derive(Default)]
+pub struct LLMBuilder {
+    model: Option<String>,
+    api_key: Option<String>,
+    reasoning_effort: Option<String>,
+    thinking_budget: Option<u32>,
+    mock: Option<Arc<MockLLM>>,
+    recorder: Option<PathBuf>,
+}
+
+impl LLMBuilder {
+    /// Starts from `config`, e.g. [`LLMConfig::from_env`].
+    pub fn config(mut self, config: LLMConfig) -> Self {
+        self.model = Some(config.model);
+        self.api_key = config.api_key;
+        self.reasoning_effort = config.reasoning_effort;
+        self.thinking_budget = config.thinking_budget;
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn reasoning_effort(mut self, reasoning_effort: impl Into<String>) -> Self {
+        self.reasoning_effort = Some(reasoning_effort.into());
+        self
+    }
+
+    /// See [`LLMConfig::thinking_budget`].
+    pub fn thinking_budget(mut self, tokens: u32) -> Self {
+        self.thinking_budget = Some(tokens);
+        self
+    }
+
+    /// See [`LLM::mock`].
+    pub fn mock(mut self, mock: Arc<MockLLM>) -> Self {
+        self.mock = Some(mock);
+        self
+    }
+
+    /// See [`LLM::with_recorder`].
+    pub fn recorder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.recorder = Some(path.into());
+        self
+    }
+
+    pub fn build(self) -> Result<LLM, String> {
+        if let Some(effort) = &self.reasoning_effort
+            && ReasoningEffort::from_keyword(effort).is_none()
+        {
+            return Err(format!("Unknown reasoning effort {:?}", effort));
+        }
+        let mut llm = match (self.mock, self.model) {
+            (Some(mock), _) => LLM::mock(mock),
+            (None, Some(model)) if !model.trim().is_empty() => LLM::new(LLMConfig {
+                model,
+                api_key: self.api_key,
+                reasoning_effort: self.reasoning_effort,
+                thinking_budget: self.thinking_budget,
+            }),
+            (None, _) => return Err("An LLM needs a model".to_string()),
+        };
+        if let Some(path) = self.recorder {
+            llm = llm.with_recorder(path);
+        }
+        Ok(llm)
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LLMResponse {
     pub content: String,
     pub tool_calls: Vec<genai::chat::ToolCall>,
+    pub usage: TokenUsage,
+    /// What the model reasoned before it answered, for providers that return it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
 }
 
 #[cfg(test)]
@@ -83,11 +448,60 @@ mod tests {
             model: "gpt-3.5-turbo".to_string(),
             api_key: Some("test-key".to_string()),
             reasoning_effort: None,
+            thinking_budget: None,
         };
         let llm = LLM::new(config);
         assert_eq!(llm.model, "gpt-3.5-turbo");
         assert_eq!(llm.api_key, Some("test-key".to_string()));
     }
+
+    #[test]
+    fn test_builder() {
+        let llm = LLM::builder()
+            .model("gpt-4o")
+            .api_key("test-key")
+            .build()
+            .unwrap();
+        assert_eq!(llm.model, "gpt-4o");
+        assert_eq!(llm.api_key.as_deref(), Some("test-key"));
+        assert!(LLM::builder().build().is_err());
+        assert!(LLM::builder().model(" ").build().is_err());
+
+        let mock = LLM::builder()
+            .mock(Arc::new(MockLLM::new(Vec::new())))
+            .build()
+            .unwrap();
+        assert_eq!(mock.model, "mock");
+    }
+
+    #[test]
+    fn test_reasoning_options() {
+        let llm = |builder: LLMBuilder| builder.model("claude-sonnet-4-5").build().unwrap();
+        let effort = |llm: LLM| llm.chat_options().reasoning_effort;
+
+        assert!(effort(llm(LLM::builder())).is_none());
+        assert!(matches!(
+            effort(llm(LLM::builder().reasoning_effort("high"))),
+            Some(ReasoningEffort::High)
+        ));
+        assert!(matches!(
+            effort(llm(LLM::builder()
+                .reasoning_effort("low")
+                .thinking_budget(2048))),
+            Some(ReasoningEffort::Budget(2048))
+        ));
+        assert!(
+            LLM::builder()
+                .model("o3")
+                .reasoning_effort("max")
+                .build()
+                .is_err()
+        );
+        assert_eq!(
+            llm(LLM::builder()).chat_options().capture_reasoning_content,
+            Some(true)
+        );
+    }
     #[tokio::test]
     async fn test_llm_completion() {
         // Load .env file if present
@@ -103,6 +517,7 @@ mod tests {
             model: "gpt-5-nano".to_string(),
             api_key,
             reasoning_effort: Some("minimal".to_string()),
+            thinking_budget: None,
         };
         let llm = LLM::new(config);
 
@@ -0,0 +1,472 @@
+//! Tool schemas as each provider accepts them.
+//!
+//! Providers accept different subsets of JSON Schema for tool parameters. OpenAI limits
+//! descriptions to 1024 characters. Gemini takes an OpenAPI subset without `$ref`,
+//! `oneOf` or `"type": [..., "null"]`. Every provider wants function names of at most 64
+//! plain characters. A schema one of them rejects fails the whole completion with a 400,
+//! in the middle of a conversation. A [`SchemaProfile`] rewrites schemas into what its
+//! provider accepts, and [`SchemaProfile::validate`] reports what cannot be rewritten
+//! before the first request is sent.
+
+use genai::adapter::AdapterKind;
+use genai::chat::Tool;
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+/// How deep `$ref`s are followed; recursive definitions become plain objects below.
+const MAX_REF_DEPTH: usize = 8;
+
+const COMBINATORS: &[&str] = &["anyOf", "oneOf", "allOf"];
+
+/// The OpenAPI subset Gemini accepts.
+const GEMINI_KEYWORDS: &[&str] = &[
+    "type",
+    "format",
+    "description",
+    "nullable",
+    "enum",
+    "properties",
+    "required",
+    "items",
+    "minItems",
+    "maxItems",
+    "minimum",
+    "maximum",
+    "propertyOrdering",
+];
+const GEMINI_FORMATS: &[&str] = &["float", "double", "int32", "int64", "enum", "date-time"];
+
+lazy_static::lazy_static! {
+    static ref TOOL_NAME: Regex = Regex::new(r"^[a-zA-Z0-9_-]{1,64}$").unwrap();
+}
+
+/// What a provider accepts in tool definitions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SchemaProfile {
+    pub provider: &'static str,
+    /// Whether `anyOf`, `oneOf` and `allOf` are accepted.
+    pub combinators: bool,
+    /// Whether `"type"` may list several types; otherwise `null` among them becomes
+    /// `"nullable": true`.
+    pub type_arrays: bool,
+    /// The longest tool description accepted, in characters.
+    pub max_description_chars: Option<usize>,
+    /// The only schema keywords accepted; others are dropped.
+    pub keywords: Option<&'static [&'static str]>,
+    /// The only `format`s accepted; others are dropped.
+    pub formats: Option<&'static [&'static str]>,
+    /// Strict function calling: every object requires all its properties and allows no
+    /// others, and optional properties become nullable instead.
+    pub strict: bool,
+}
+
+pub const OPENAI: SchemaProfile = SchemaProfile {
+    provider: "openai",
+    combinators: true,
+    type_arrays: true,
+    max_description_chars: Some(1024),
+    keywords: None,
+    formats: None,
+    strict: false,
+};
+
+pub const ANTHROPIC: SchemaProfile = SchemaProfile {
+    provider: "anthropic",
+    combinators: true,
+    type_arrays: true,
+    max_description_chars: None,
+    keywords: None,
+    formats: None,
+    strict: false,
+};
+
+pub const GEMINI: SchemaProfile = SchemaProfile {
+    provider: "gemini",
+    combinators: false,
+    type_arrays: false,
+    max_description_chars: None,
+    keywords: Some(GEMINI_KEYWORDS),
+    formats: Some(GEMINI_FORMATS),
+    strict: false,
+};
+
+impl SchemaProfile {
+    /// The profile of the provider serving `model`. Providers speaking the OpenAI
+    /// protocol, and models genai does not recognize, get OpenAI's.
+    pub fn for_model(model: &str) -> Self {
+        match AdapterKind::from_model(model) {
+            Ok(AdapterKind::Anthropic) => ANTHROPIC,
+            Ok(AdapterKind::Gemini) => GEMINI,
+            _ => OPENAI,
+        }
+    }
+
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// `tools` rewritten into what the provider accepts.
+    pub fn adapt_tools(&self, tools: Vec<Tool>) -> Vec<Tool> {
+        tools
+            .into_iter()
+            .map(|tool| self.adapt_tool(tool))
+            .collect()
+    }
+
+    pub fn adapt_tool(&self, mut tool: Tool) -> Tool {
+        if let (Some(description), Some(max)) = (&tool.description, self.max_description_chars) {
+            tool.description = Some(truncate(description, max));
+        }
+        tool.schema = tool.schema.map(|schema| self.adapt_schema(&schema));
+        tool
+    }
+
+    /// `schema` with its `$ref`s inlined and rewritten into what the provider accepts.
+    pub fn adapt_schema(&self, schema: &Value) -> Value {
+        let definitions = ["$defs", "definitions"]
+            .iter()
+            .filter_map(|key| schema.get(*key).and_then(Value::as_object))
+            .flat_map(|defs| defs.clone())
+            .collect();
+        self.adapt_node(schema, &definitions, 0)
+    }
+
+    fn adapt_node(&self, node: &Value, definitions: &Map<String, Value>, depth: usize) -> Value {
+        let Some(object) = node.as_object() else {
+            return node.clone();
+        };
+        let mut object = object.clone();
+        object.remove("$schema");
+        object.remove("$defs");
+        object.remove("definitions");
+
+        if let Some(Value::String(reference)) = object.remove("$ref") {
+            let name = reference.rsplit('/').next().unwrap_or_default();
+            let target = match definitions.get(name) {
+                Some(target) if depth < MAX_REF_DEPTH => target.as_object().cloned(),
+                _ => None,
+            };
+            let mut resolved = target
+                .unwrap_or_else(|| Map::from_iter([("type".to_string(), Value::from("object"))]));
+            // Keywords next to the `$ref`, like a description, win over the definition's.
+            resolved.extend(object);
+            return self.adapt_node(&Value::Object(resolved), definitions, depth + 1);
+        }
+
+        if !self.combinators {
+            object = merge_combinators(object);
+        }
+        if !self.type_arrays
+            && let Some(Value::Array(types)) = object.get("type")
+        {
+            let nullable = types.iter().any(|t| t == "null");
+            let first = types.iter().find(|t| *t != "null").cloned();
+            object.insert("type".to_string(), first.unwrap_or(Value::from("string")));
+            if nullable {
+                object.insert("nullable".to_string(), Value::Bool(true));
+            }
+        }
+
+        if let Some(Value::Object(properties)) = object.get_mut("properties") {
+            for schema in properties.values_mut() {
+                *schema = self.adapt_node(schema, definitions, depth);
+            }
+        }
+        for key in ["items", "additionalProperties"] {
+            if let Some(schema) = object.get_mut(key).filter(|s| s.is_object()) {
+                *schema = self.adapt_node(schema, definitions, depth);
+            }
+        }
+        for key in COMBINATORS {
+            if let Some(Value::Array(variants)) = object.get_mut(*key) {
+                for variant in variants.iter_mut() {
+                    *variant = self.adapt_node(variant, definitions, depth);
+                }
+            }
+        }
+
+        if self.strict && object.contains_key("properties") {
+            make_strict(&mut object);
+        }
+        if let Some(keywords) = self.keywords {
+            object.retain(|key, _| keywords.contains(&key.as_str()));
+        }
+        if let (Some(formats), Some(format)) = (self.formats, object.get("format"))
+            && !format.as_str().is_some_and(|f| formats.contains(&f))
+        {
+            object.remove("format");
+        }
+        Value::Object(object)
+    }
+
+    /// Check adapted `tools` for what the provider would still reject: invalid or
+    /// duplicate names, and schemas that are not plain objects.
+    pub fn validate(&self, tools: &[Tool]) -> Result<(), String> {
+        let mut problems = Vec::new();
+        let mut names = HashSet::new();
+        for tool in tools {
+            if !TOOL_NAME.is_match(&tool.name) {
+                problems.push(format!(
+                    "`{}`: names may only have letters, digits, `_` and `-`, at most 64",
+                    tool.name
+                ));
+            }
+            if !names.insert(tool.name.as_str()) {
+                problems.push(format!("`{}`: defined more than once", tool.name));
+            }
+            let Some(schema) = &tool.schema else {
+                continue;
+            };
+            if schema.get("type").and_then(Value::as_str) != Some("object") {
+                problems.push(format!(
+                    "`{}`: the parameters must be an object schema",
+                    tool.name
+                ));
+            }
+            if let Some(key) = COMBINATORS
+                .iter()
+                .chain(&["enum", "not"])
+                .find(|key| schema.get(**key).is_some())
+            {
+                problems.push(format!(
+                    "`{}`: `{}` is not allowed at the top of the parameters",
+                    tool.name, key
+                ));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Tool schemas the {} API would reject: {}",
+                self.provider,
+                problems.join("; ")
+            ))
+        }
+    }
+}
+
+/// `anyOf`/`oneOf`/`allOf` of `object` folded into it, for providers without them. Null
+/// variants make it nullable, alternatives of constants become an `enum`, and of other
+/// alternatives the first is kept.
+fn merge_combinators(mut object: Map<String, Value>) -> Map<String, Value> {
+    if let Some(Value::Array(variants)) = object.remove("allOf") {
+        for variant in variants {
+            if let Value::Object(variant) = variant {
+                object = merge(object, merge_combinators(variant));
+            }
+        }
+    }
+    for key in ["anyOf", "oneOf"] {
+        let Some(Value::Array(variants)) = object.remove(key) else {
+            continue;
+        };
+        let (nulls, variants): (Vec<Value>, Vec<Value>) = variants
+            .into_iter()
+            .partition(|v| v.get("type").is_some_and(|t| t == "null"));
+        if !nulls.is_empty() {
+            object.insert("nullable".to_string(), Value::Bool(true));
+        }
+        let constants: Option<Vec<Value>> = variants
+            .iter()
+            .map(|v| match (v.get("const"), v.get("enum")) {
+                (Some(c), _) => Some(vec![c.clone()]),
+                (None, Some(Value::Array(values))) => Some(values.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|values| values.into_iter().flatten().collect());
+        match constants {
+            Some(values) if !values.is_empty() => {
+                let kind = if values.iter().all(Value::is_string) {
+                    "string"
+                } else {
+                    "number"
+                };
+                object.entry("type").or_insert(Value::from(kind));
+                object.insert("enum".to_string(), Value::Array(values));
+            }
+            _ => {
+                if let Some(Value::Object(first)) = variants.into_iter().next() {
+                    object = merge(object, merge_combinators(first));
+                }
+            }
+        }
+    }
+    if let Some(Value::String(_)) = object.get("const") {
+        let value = object.remove("const").unwrap();
+        object.entry("type").or_insert(Value::from("string"));
+        object.insert("enum".to_string(), Value::Array(vec![value]));
+    }
+    object
+}
+
+/// `extra` merged into `base`: properties and required lists are combined, other keywords
+/// of `base` are kept.
+fn merge(mut base: Map<String, Value>, extra: Map<String, Value>) -> Map<String, Value> {
+    for (key, value) in extra {
+        match (key.as_str(), base.get_mut(&key), value) {
+            ("properties", Some(Value::Object(mine)), Value::Object(theirs)) => {
+                mine.extend(theirs);
+            }
+            ("required", Some(Value::Array(mine)), Value::Array(theirs)) => {
+                for name in theirs {
+                    if !mine.contains(&name) {
+                        mine.push(name);
+                    }
+                }
+            }
+            (_, None, value) => {
+                base.insert(key, value);
+            }
+            _ => {}
+        }
+    }
+    base
+}
+
+/// Require every property of `object` and forbid others; properties that were optional
+/// may be null instead.
+fn make_strict(object: &mut Map<String, Value>) {
+    let required: HashSet<String> = object
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|n| n.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    let Some(Value::Object(properties)) = object.get_mut("properties") else {
+        return;
+    };
+    for (name, schema) in properties.iter_mut() {
+        if required.contains(name) {
+            continue;
+        }
+        match schema.get_mut("type") {
+            Some(Value::String(kind)) => {
+                let kind = kind.clone();
+                schema["type"] = Value::from(vec![kind, "null".to_string()]);
+            }
+            Some(Value::Array(kinds)) => {
+                if !kinds.iter().any(|k| k == "null") {
+                    kinds.push(Value::from("null"));
+                }
+            }
+            _ => {
+                *schema = serde_json::json!({ "anyOf": [schema.clone(), { "type": "null" }] });
+            }
+        }
+    }
+    let names: Vec<Value> = properties.keys().cloned().map(Value::from).collect();
+    object.insert("required".to_string(), Value::Array(names));
+    object.insert("additionalProperties".to_string(), Value::Bool(false));
+}
+
+/// `text` cut to `max` characters, marking the cut.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::tools::{default_tools, genai_tools};
+    use serde_json::json;
+
+    fn tool(name: &str, schema: Value) -> Tool {
+        Tool {
+            name: name.to_string(),
+            description: Some("d".repeat(2000)),
+            schema: Some(schema),
+            config: None,
+        }
+    }
+
+    #[test]
+    fn test_schemas_are_adapted_per_provider() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": {
+                "path": { "type": ["string", "null"], "format": "uri-reference" },
+                "mode": { "oneOf": [{ "const": "fast" }, { "const": "full" }] },
+                "task": { "$ref": "#/definitions/Task", "description": "The task." }
+            },
+            "required": ["mode"],
+            "definitions": {
+                "Task": {
+                    "type": "object",
+                    "properties": { "title": { "type": "string", "default": "" } }
+                }
+            }
+        });
+
+        assert_eq!(SchemaProfile::for_model("gemini-2.5-flash"), GEMINI);
+        let gemini = GEMINI.adapt_tool(tool("plan", schema.clone()));
+        assert_eq!(
+            gemini.schema.unwrap(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "nullable": true },
+                    "mode": { "type": "string", "enum": ["fast", "full"] },
+                    "task": {
+                        "type": "object",
+                        "description": "The task.",
+                        "properties": { "title": { "type": "string" } }
+                    }
+                },
+                "required": ["mode"]
+            })
+        );
+        assert_eq!(gemini.description.unwrap().chars().count(), 2000);
+
+        let openai = OPENAI.with_strict(true).adapt_tool(tool("plan", schema));
+        assert_eq!(openai.description.unwrap().chars().count(), 1024);
+        let schema = openai.schema.unwrap();
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(schema["required"], json!(["mode", "path", "task"]));
+        assert_eq!(
+            schema["properties"]["path"]["type"],
+            json!(["string", "null"])
+        );
+        assert_eq!(schema["properties"]["mode"]["oneOf"][1]["const"], "full");
+        assert_eq!(
+            schema["properties"]["task"]["type"],
+            json!(["object", "null"])
+        );
+        assert!(schema.get("definitions").is_none());
+    }
+
+    #[test]
+    fn test_validation_reports_what_cannot_be_adapted() {
+        let tools = OPENAI.adapt_tools(vec![
+            tool("read file", json!({ "type": "object" })),
+            tool("run", json!({ "type": "object" })),
+            tool("run", json!({ "oneOf": [{ "type": "object" }] })),
+        ]);
+        let error = OPENAI.validate(&tools).unwrap_err();
+        assert!(error.starts_with("Tool schemas the openai API would reject: `read file`: names"));
+        assert!(error.contains("`run`: defined more than once"));
+        assert!(error.contains("`run`: the parameters must be an object schema"));
+        assert!(error.contains("`run`: `oneOf` is not allowed"));
+
+        // The built-in tools pass everywhere.
+        let builtin = genai_tools(&default_tools(std::env::temp_dir()));
+        for profile in [OPENAI, ANTHROPIC, GEMINI, OPENAI.with_strict(true)] {
+            profile
+                .validate(&profile.adapt_tools(builtin.clone()))
+                .unwrap();
+        }
+    }
+}
@@ -2,12 +2,26 @@ use chrono::Local;
 use colored::*;
 use std::env;
 use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::MakeWriter;
 
 /// Initializes the global logging system with colorized output and environment-based level filtering.
 ///
 /// The `RUST_LOG` environment variable can be used to control the log level (default: info).
 /// Example: `RUST_LOG=debug cargo run --example remote_test`
 pub fn init_logging() {
+    init_logging_with_writer(std::io::stdout);
+}
+
+/// Like [`init_logging`], but logs to stderr, for processes whose stdout carries a protocol
+/// (e.g. MCP over stdio).
+pub fn init_stderr_logging() {
+    init_logging_with_writer(std::io::stderr);
+}
+
+fn init_logging_with_writer<W>(writer: W)
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
     if env::var("RUST_LOG").is_err() {
         unsafe { env::set_var("RUST_LOG", "info") };
     }
@@ -18,7 +32,7 @@ pub fn init_logging() {
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env())
         .with_ansi(true)
-        .with_writer(std::io::stdout)
+        .with_writer(writer)
         .event_format(CustomFormatter)
         .init();
 }
@@ -56,7 +70,7 @@ where
         };
         event.record(&mut visitor);
 
-        write!(writer, "{}", message)?;
+        write!(writer, "{}", crate::redact::redact(&message))?;
         writeln!(writer)
     }
 }
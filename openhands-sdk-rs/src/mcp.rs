@@ -0,0 +1,326 @@
+//! Tools from external MCP servers.
+//!
+//! [`McpToolProvider`] connects to the servers configured under `[mcp_servers.<name>]` in
+//! `openhands.toml`, either by spawning a stdio server or over streamable HTTP:
+//!
+//! ```toml
+//! [mcp_servers.filesystem]
+//! command = "npx"
+//! args = ["-y", "@modelcontextprotocol/server-filesystem", "/data"]
+//!
+//! [mcp_servers.search]
+//! url = "http://localhost:8931/mcp"
+//! ```
+//!
+//! Their tools are offered to the agent as `<server>__<tool>`. The servers run next to this
+//! process, so sandboxed runtimes call them from the host.
+
+use async_trait::async_trait;
+use rmcp::model::{
+    CallToolRequestParam, CallToolResult, Content, RawContent, RawResource, ResourceContents,
+};
+use rmcp::service::{Peer, RunningService};
+use rmcp::transport::TokioChildProcess;
+#[cfg(feature = "http")]
+use rmcp::transport::StreamableHttpClientTransport;
+use rmcp::{RoleClient, ServiceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::agent::tools::{Tool, ToolOutput};
+use crate::events::Attachment;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct McpServerConfig {
+    /// Program of a stdio server.
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Environment of the stdio server, on top of this process's.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Endpoint of a streamable HTTP server, used when there is no `command`.
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+struct McpServer {
+    name: String,
+    client: RunningService<RoleClient, ()>,
+    tools: Vec<rmcp::model::Tool>,
+}
+
+/// Connections to external MCP servers and the tools they list.
+#[derive(Default)]
+pub struct McpToolProvider {
+    servers: Vec<McpServer>,
+}
+
+impl McpToolProvider {
+    /// Connect to every configured server. Servers that cannot be reached are logged and
+    /// left out, so one broken server does not take the others down.
+    pub async fn connect(configs: &BTreeMap<String, McpServerConfig>) -> Self {
+        let mut servers = Vec::new();
+        for (name, config) in configs {
+            match connect_server(name, config).await {
+                Ok(server) => {
+                    tracing::info!("MCP server {} offers {} tools", name, server.tools.len());
+                    servers.push(server);
+                }
+                Err(e) => tracing::warn!("Failed to connect to MCP server {}: {}", name, e),
+            }
+        }
+        Self { servers }
+    }
+
+    /// The tools of all connected servers.
+    pub fn tools(&self) -> Vec<Box<dyn Tool>> {
+        self.servers
+            .iter()
+            .flat_map(|server| {
+                server.tools.iter().map(|tool| {
+                    Box::new(McpTool {
+                        name: tool_name(&server.name, &tool.name),
+                        tool: tool.clone(),
+                        peer: server.client.peer().clone(),
+                    }) as Box<dyn Tool>
+                })
+            })
+            .collect()
+    }
+}
+
+async fn connect_server(name: &str, config: &McpServerConfig) -> Result<McpServer, String> {
+    let client = match (&config.command, &config.url) {
+        (Some(command), _) => {
+            let mut cmd = tokio::process::Command::new(command);
+            cmd.args(&config.args).envs(&config.env);
+            let transport = TokioChildProcess::new(cmd).map_err(|e| e.to_string())?;
+            ().serve(transport).await.map_err(|e| e.to_string())?
+        }
+        #[cfg(feature = "http")]
+        (None, Some(url)) => {
+            let transport = StreamableHttpClientTransport::from_uri(url.as_str());
+            ().serve(transport).await.map_err(|e| e.to_string())?
+        }
+        #[cfg(not(feature = "http"))]
+        (None, Some(_)) => {
+            return Err("'url' servers need the `http` feature; use 'command'".to_string());
+        }
+        (None, None) => return Err("either 'command' or 'url' is required".to_string()),
+    };
+    let tools = client.list_all_tools().await.map_err(|e| e.to_string())?;
+    Ok(McpServer {
+        name: name.to_string(),
+        client,
+        tools,
+    })
+}
+
+/// `<server>__<tool>`, with characters LLM APIs reject in tool names replaced by `_`.
+fn tool_name(server: &str, tool: &str) -> String {
+    format!("{}__{}", server, tool)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// A tool result as text plus attachments. Results flagged as errors become `Err`, with
+/// their text.
+fn tool_output(result: CallToolResult) -> Result<ToolOutput, String> {
+    let mut parts = Vec::new();
+    let mut attachments = Vec::new();
+    for content in &result.content {
+        match &content.raw {
+            RawContent::Text(text) => parts.push(text.text.clone()),
+            RawContent::Audio(audio) => parts.push(format!("[audio: {}]", audio.mime_type)),
+            _ => attachments.extend(attachment(content)),
+        }
+    }
+    if parts.is_empty()
+        && let Some(structured) = &result.structured_content
+    {
+        parts.push(structured.to_string());
+    }
+    let content = parts.join("\n");
+    if result.is_error == Some(true) {
+        return Err(content);
+    }
+    Ok(ToolOutput {
+        content,
+        attachments,
+    })
+}
+
+/// The [`Attachment`] for image and resource content.
+pub fn attachment(content: &Content) -> Option<Attachment> {
+    match &content.raw {
+        RawContent::Image(image) => Some(Attachment::Image {
+            mime_type: image.mime_type.clone(),
+            data: image.data.clone(),
+        }),
+        RawContent::Resource(resource) => Some(match &resource.resource {
+            ResourceContents::TextResourceContents {
+                uri,
+                mime_type,
+                text,
+                ..
+            } => Attachment::Resource {
+                uri: uri.clone(),
+                mime_type: mime_type.clone(),
+                text: Some(text.clone()),
+                blob: None,
+            },
+            ResourceContents::BlobResourceContents {
+                uri,
+                mime_type,
+                blob,
+                ..
+            } => Attachment::Resource {
+                uri: uri.clone(),
+                mime_type: mime_type.clone(),
+                text: None,
+                blob: Some(blob.clone()),
+            },
+        }),
+        RawContent::ResourceLink(link) => Some(Attachment::ResourceLink {
+            uri: link.uri.clone(),
+            name: Some(link.name.clone()),
+            mime_type: link.mime_type.clone(),
+        }),
+        RawContent::Text(_) | RawContent::Audio(_) => None,
+    }
+}
+
+/// MCP content for an [`Attachment`], for serving tool output over MCP.
+pub fn content(attachment: &Attachment) -> Content {
+    match attachment.clone() {
+        Attachment::Image { mime_type, data } => Content::image(data, mime_type),
+        Attachment::Resource {
+            uri,
+            mime_type,
+            text,
+            blob,
+        } => Content::resource(match blob {
+            Some(blob) => ResourceContents::BlobResourceContents {
+                uri,
+                mime_type,
+                blob,
+                meta: None,
+            },
+            None => ResourceContents::TextResourceContents {
+                uri,
+                mime_type,
+                text: text.unwrap_or_default(),
+                meta: None,
+            },
+        }),
+        Attachment::ResourceLink {
+            uri,
+            name,
+            mime_type,
+        } => {
+            let mut link = RawResource::new(uri.clone(), name.unwrap_or(uri));
+            link.mime_type = mime_type;
+            Content::resource_link(link)
+        }
+    }
+}
+
+pub struct McpTool {
+    name: String,
+    tool: rmcp::model::Tool,
+    peer: Peer<RoleClient>,
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn description(&self) -> String {
+        self.tool
+            .description
+            .as_deref()
+            .or(self.tool.title.as_deref())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        Value::Object(self.tool.input_schema.as_ref().clone())
+    }
+
+    fn runs_on_host(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, args: Value) -> Result<String, String> {
+        self.call_with_attachments(args)
+            .await
+            .map(|output| output.content)
+    }
+
+    async fn call_with_attachments(&self, args: Value) -> Result<ToolOutput, String> {
+        let arguments = match args {
+            Value::Object(object) => Some(object),
+            Value::Null => None,
+            _ => return Err("Arguments must be a JSON object".to_string()),
+        };
+        let result = self
+            .peer
+            .call_tool(CallToolRequestParam {
+                name: self.tool.name.clone(),
+                arguments,
+            })
+            .await
+            .map_err(|e| format!("MCP call to {} failed: {}", self.name, e))?;
+        tool_output(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_name_and_output() {
+        assert_eq!(
+            tool_name("web search", "fetch.page"),
+            "web_search__fetch_page"
+        );
+
+        let image = Attachment::Image {
+            mime_type: "image/png".to_string(),
+            data: "aGk=".to_string(),
+        };
+        let link = Attachment::ResourceLink {
+            uri: "file:///data/report.csv".to_string(),
+            name: Some("report.csv".to_string()),
+            mime_type: Some("text/csv".to_string()),
+        };
+        let ok = CallToolResult::success(vec![
+            Content::text("first"),
+            content(&image),
+            content(&link),
+        ]);
+        assert_eq!(
+            tool_output(ok),
+            Ok(ToolOutput {
+                content: "first".to_string(),
+                attachments: vec![image, link],
+            })
+        );
+        let failed = CallToolResult::error(vec![Content::text("no such table")]);
+        assert_eq!(tool_output(failed), Err("no such table".to_string()));
+    }
+}
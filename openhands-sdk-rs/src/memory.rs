@@ -0,0 +1,322 @@
+//! Long-term memory that survives across conversations.
+//!
+//! Memories are keyed notes persisted per project in `<hash>.memory.json` under
+//! [`index_root`], next to the project's code index, so they never end up in the repository.
+//! Retrieval uses embedding similarity when an [`EmbeddingProvider`] is configured and falls
+//! back to keyword overlap otherwise. A memory file that does not parse is renamed aside
+//! (`memory.json.corrupt-<timestamp>`) rather than overwritten, so its notes can be recovered.
+
+use crate::index::embedding::tokenize;
+use crate::index::{
+    EmbeddingProvider, cosine_similarity, index_root, provider_from_env, workspace_key,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Weak};
+use tokio::sync::Mutex;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemoryEntry {
+    pub key: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+}
+
+pub struct MemoryStore {
+    path: PathBuf,
+    provider: Option<Arc<dyn EmbeddingProvider>>,
+    entries: Mutex<Vec<MemoryEntry>>,
+}
+
+impl MemoryStore {
+    /// Open (or create) the memory file at `path`. Fails if the file exists but cannot be
+    /// read; a file that does not parse is quarantined and the store starts empty.
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        let entries = match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    quarantine(&path, &e.to_string())?;
+                    Vec::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(format!(
+                    "Failed to read memory file {}: {}",
+                    path.display(),
+                    e
+                ));
+            }
+        };
+        Ok(Self {
+            path,
+            provider: None,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Open the memory store belonging to a workspace/project directory.
+    pub fn for_workspace(workspace_dir: &Path) -> Result<Self, String> {
+        Self::open(index_root().join(format!("{}.memory.json", workspace_key(workspace_dir))))
+    }
+
+    /// The memory store of the project at `workspace_dir`, shared by the conversations of
+    /// this process that work on it so their notes do not overwrite each other. Uses
+    /// embeddings when `OPENAI_API_KEY` is set.
+    pub fn shared(workspace_dir: &Path) -> Result<Arc<Self>, String> {
+        static STORES: LazyLock<std::sync::Mutex<HashMap<String, Weak<MemoryStore>>>> =
+            LazyLock::new(Default::default);
+
+        let key = workspace_key(workspace_dir);
+        let mut stores = STORES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(store) = stores.get(&key).and_then(Weak::upgrade) {
+            return Ok(store);
+        }
+        let mut store = Self::for_workspace(workspace_dir)?;
+        if let Some(provider) = provider_from_env() {
+            store = store.with_embeddings(provider);
+        }
+        let store = Arc::new(store);
+        stores.retain(|_, s| s.strong_count() > 0);
+        stores.insert(key, Arc::downgrade(&store));
+        Ok(store)
+    }
+
+    /// Enable embedding-based retrieval.
+    pub fn with_embeddings(mut self, provider: Arc<dyn EmbeddingProvider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Store `content` under `key`, replacing any existing memory with the same key.
+    pub async fn remember(&self, key: &str, content: &str) -> Result<(), String> {
+        let embedding = match &self.provider {
+            Some(p) => p.embed(&[format!("{}\n{}", key, content)]).await?.pop(),
+            None => None,
+        };
+
+        let mut entries = self.entries.lock().await;
+        let now = Utc::now();
+        if let Some(entry) = entries.iter_mut().find(|e| e.key == key) {
+            entry.content = content.to_string();
+            entry.updated_at = now;
+            entry.embedding = embedding;
+        } else {
+            entries.push(MemoryEntry {
+                key: key.to_string(),
+                content: content.to_string(),
+                created_at: now,
+                updated_at: now,
+                embedding,
+            });
+        }
+        self.save(&entries)
+    }
+
+    /// Remove the memory stored under `key`. Returns whether it existed.
+    pub async fn forget(&self, key: &str) -> Result<bool, String> {
+        let mut entries = self.entries.lock().await;
+        let before = entries.len();
+        entries.retain(|e| e.key != key);
+        let removed = entries.len() != before;
+        if removed {
+            self.save(&entries)?;
+        }
+        Ok(removed)
+    }
+
+    /// Return up to `limit` memories relevant to `query`, most relevant first.
+    pub async fn recall(&self, query: &str, limit: usize) -> Result<Vec<MemoryEntry>, String> {
+        let query_embedding = match &self.provider {
+            Some(p) => p.embed(&[query.to_string()]).await?.pop(),
+            None => None,
+        };
+        let query_tokens: HashSet<String> = tokenize(query).into_iter().collect();
+
+        let entries = self.entries.lock().await;
+        let mut scored: Vec<(f32, &MemoryEntry)> = entries
+            .iter()
+            .map(|entry| {
+                let score = match (&query_embedding, &entry.embedding) {
+                    (Some(q), Some(e)) => cosine_similarity(q, e),
+                    _ => keyword_score(&query_tokens, entry),
+                };
+                (score, entry)
+            })
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, e)| e.clone())
+            .collect())
+    }
+
+    pub async fn len(&self) -> usize {
+        self.entries.lock().await.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.entries.lock().await.is_empty()
+    }
+
+    fn save(&self, entries: &[MemoryEntry]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create memory dir: {}", e))?;
+        }
+        let json = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+        fs::write(&self.path, json).map_err(|e| format!("Failed to write memory file: {}", e))
+    }
+}
+
+/// Move a memory file that does not parse out of the way, so the next save does not
+/// overwrite the notes in it.
+fn quarantine(path: &Path, error: &str) -> Result<(), String> {
+    let mut moved = path.as_os_str().to_owned();
+    moved.push(format!(".corrupt-{}", Utc::now().format("%Y%m%dT%H%M%S")));
+    let moved = PathBuf::from(moved);
+    fs::rename(path, &moved).map_err(|e| {
+        format!(
+            "Memory file {} is corrupt ({}) and could not be moved aside: {}",
+            path.display(),
+            error,
+            e
+        )
+    })?;
+    tracing::warn!(
+        "Memory file {} is corrupt ({}); moved it to {}",
+        path.display(),
+        error,
+        moved.display()
+    );
+    Ok(())
+}
+
+/// Fraction of query tokens found in the memory key or content.
+fn keyword_score(query_tokens: &HashSet<String>, entry: &MemoryEntry) -> f32 {
+    if query_tokens.is_empty() {
+        return 0.0;
+    }
+    let entry_tokens: HashSet<String> = tokenize(&format!("{} {}", entry.key, entry.content))
+        .into_iter()
+        .collect();
+    let hits = query_tokens.intersection(&entry_tokens).count();
+    hits as f32 / query_tokens.len() as f32
+}
+
+/// Render memories as a prompt section, or `None` if there is nothing to inject.
+pub fn format_memories(memories: &[MemoryEntry]) -> Option<String> {
+    if memories.is_empty() {
+        return None;
+    }
+    let body: Vec<String> = memories
+        .iter()
+        .map(|m| format!("- {}: {}", m.key, m.content))
+        .collect();
+    Some(format!(
+        "<RELEVANT_MEMORIES>\nNotes saved in earlier conversations about this project:\n{}\n</RELEVANT_MEMORIES>",
+        body.join("\n")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{HashingEmbeddingProvider, use_test_index_root};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_remember_and_recall_persist() {
+        use_test_index_root();
+        let temp_dir = TempDir::new().unwrap();
+
+        let store = MemoryStore::for_workspace(temp_dir.path()).unwrap();
+        store
+            .remember("test_command", "Run tests with `cargo test --workspace`")
+            .await
+            .unwrap();
+        store
+            .remember("style", "Use snake_case for file names")
+            .await
+            .unwrap();
+
+        // A new store instance (e.g. a later conversation) sees the same memories.
+        let reopened = MemoryStore::for_workspace(temp_dir.path()).unwrap();
+        let recalled = reopened.recall("how do I run the tests", 5).await.unwrap();
+        assert_eq!(recalled.len(), 1);
+        assert_eq!(recalled[0].key, "test_command");
+        assert!(!temp_dir.path().join(".openhands").exists());
+    }
+
+    #[tokio::test]
+    async fn test_conversations_of_a_project_share_its_store() {
+        use_test_index_root();
+        let temp_dir = TempDir::new().unwrap();
+        let other_dir = TempDir::new().unwrap();
+
+        let first = MemoryStore::shared(temp_dir.path()).unwrap();
+        let second = MemoryStore::shared(temp_dir.path()).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(
+            &first,
+            &MemoryStore::shared(other_dir.path()).unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_remember_overwrites_and_forget() {
+        use_test_index_root();
+        let temp_dir = TempDir::new().unwrap();
+        let store = MemoryStore::for_workspace(temp_dir.path())
+            .unwrap()
+            .with_embeddings(Arc::new(HashingEmbeddingProvider::default()));
+
+        store.remember("db", "postgres on port 5432").await.unwrap();
+        store
+            .remember("db", "sqlite file at data.db")
+            .await
+            .unwrap();
+        assert_eq!(store.len().await, 1);
+
+        let recalled = store.recall("sqlite database", 1).await.unwrap();
+        assert_eq!(recalled[0].content, "sqlite file at data.db");
+
+        assert!(store.forget("db").await.unwrap());
+        assert!(store.is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_memory_file_is_quarantined() {
+        use_test_index_root();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = index_root();
+        let file = format!("{}.memory.json", workspace_key(temp_dir.path()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(&file), "[{\"key\": \"db\", truncated").unwrap();
+
+        let store = MemoryStore::for_workspace(temp_dir.path()).unwrap();
+        assert!(store.is_empty().await);
+        store.remember("style", "snake_case").await.unwrap();
+
+        let quarantined: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with(&format!("{}.corrupt-", file)))
+            .collect();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(
+            fs::read_to_string(dir.join(&quarantined[0])).unwrap(),
+            "[{\"key\": \"db\", truncated"
+        );
+    }
+}
@@ -1,8 +1,17 @@
+#[cfg(feature = "server-models")]
+mod api;
+
+#[cfg(feature = "server-models")]
+pub use api::*;
+
 use chrono::{DateTime, Utc};
+use rmcp::schemars::{self, JsonSchema};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use uuid::Uuid;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Also the arguments of the server's `execute_bash` and `terminal` MCP tools.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 pub struct ExecuteBashRequest {
     pub command: String,
     pub cwd: Option<String>,
@@ -28,6 +37,77 @@ pub struct FileResponse {
     pub error: Option<String>,
 }
 
+/// Also the arguments of the server's `move_path` and `copy_path` MCP tools.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct FileTransferRequest {
+    /// File or directory to move or copy, relative to the workspace.
+    pub source: String,
+    /// New path, relative to the workspace. Missing parent directories are created.
+    pub destination: String,
+    /// Replace an existing destination instead of failing.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileTransferResponse {
+    pub source: String,
+    pub destination: String,
+    pub success: bool,
+    pub is_dir: bool,
+    /// Files moved or copied, counting those inside a directory.
+    pub files: u64,
+    /// Whether an existing destination was replaced.
+    pub replaced: bool,
+    pub error: Option<String>,
+}
+
+/// Also the arguments of the server's `download_file` MCP tool.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct DownloadRequest {
+    /// `http://` or `https://` URL to fetch.
+    pub url: String,
+    /// Where to save it, relative to the workspace.
+    pub path: String,
+    /// Expected SHA-256 of the content, in hex. The file is discarded if it differs.
+    pub sha256: Option<String>,
+    /// Refuse files larger than this many bytes (at most the server's limit).
+    pub max_bytes: Option<u64>,
+    /// Replace an existing file at `path`.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DownloadResponse {
+    pub url: String,
+    pub path: String,
+    pub success: bool,
+    /// Size of the downloaded file.
+    pub bytes: u64,
+    /// SHA-256 of the downloaded file, in hex.
+    pub sha256: Option<String>,
+    /// Whether an interrupted earlier download was continued.
+    pub resumed: bool,
+    pub error: Option<String>,
+}
+
+/// Version of the persisted models (`BashEvent`). Bump it when their JSON changes in a way
+/// serde defaults cannot absorb, and add the upgrade from the previous version to
+/// [`MIGRATIONS`].
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Events written before versioning have no `schema_version`.
+fn unversioned() -> u32 {
+    0
+}
+
+/// `MIGRATIONS[i]` upgrades a serialized event from version `i` to `i + 1`.
+const MIGRATIONS: &[fn(&mut Value)] = &[
+    // 0 -> 1: versioning introduced; the fields are unchanged.
+    |_| {},
+];
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "kind")]
 pub enum BashEvent {
@@ -49,26 +129,166 @@ impl BashEvent {
             BashEvent::BashOutput(o) => o.timestamp,
         }
     }
+
+    /// The id of the command the event belongs to.
+    pub fn command_id(&self) -> Uuid {
+        match self {
+            BashEvent::BashCommand(c) => c.id,
+            BashEvent::BashOutput(o) => o.command_id,
+        }
+    }
+
+    pub fn kind(&self) -> BashEventKind {
+        match self {
+            BashEvent::BashCommand(_) => BashEventKind::BashCommand,
+            BashEvent::BashOutput(_) => BashEventKind::BashOutput,
+        }
+    }
+
+    /// Parse a serialized event of any schema version, upgrading older ones. Also returns
+    /// whether it was upgraded, so stores can rewrite it. Events from a newer version are
+    /// parsed as far as the current models allow.
+    pub fn from_json(mut value: Value) -> Result<(Self, bool), String> {
+        let version = value
+            .get("schema_version")
+            .and_then(Value::as_u64)
+            .map_or(0, |v| v as usize);
+        let migrated = version < MIGRATIONS.len();
+        for migration in MIGRATIONS.iter().skip(version) {
+            migration(&mut value);
+        }
+        if migrated && let Some(object) = value.as_object_mut() {
+            object.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+        }
+        let event = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        Ok((event, migrated))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BashCommand {
+    #[serde(default = "unversioned")]
+    pub schema_version: u32,
     pub id: Uuid,
     pub timestamp: DateTime<Utc>,
     pub command: String,
+    #[serde(default)]
     pub cwd: Option<String>,
+    #[serde(default = "default_bash_timeout")]
     pub timeout: u64,
 }
 
+fn default_bash_timeout() -> u64 {
+    300
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BashOutput {
+    #[serde(default = "unversioned")]
+    pub schema_version: u32,
     pub id: Uuid,
     pub timestamp: DateTime<Utc>,
     pub command_id: Uuid,
+    #[serde(default)]
     pub order: i32,
+    #[serde(default)]
     pub exit_code: Option<i32>,
+    #[serde(default)]
     pub stdout: Option<String>,
+    #[serde(default)]
     pub stderr: Option<String>,
+    /// stdout and stderr together, in the order the command wrote them, so errors show
+    /// where they happened among the rest of the output.
+    #[serde(default)]
+    pub merged_output: Option<String>,
+    /// The merged output as it arrived, line by line.
+    #[serde(default)]
+    pub chunks: Vec<OutputChunk>,
+    /// Whether the command was killed for running past its timeout.
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+impl BashOutput {
+    /// The command's output as one text: the merged output, or for events recorded
+    /// without one, stdout followed by stderr.
+    pub fn combined_output(&self) -> String {
+        if let Some(merged) = &self.merged_output {
+            return merged.clone();
+        }
+        [self.stdout.as_deref(), self.stderr.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Consecutive output of one stream, and when it arrived.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutputChunk {
+    pub stream: OutputStream,
+    pub timestamp: DateTime<Utc>,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+pub enum BashEventKind {
+    BashCommand,
+    BashOutput,
+}
+
+/// Filters for searching bash events; all given ones must match. The filters on a
+/// command (`command`, `command_regex`, `exit_code`, `failed`) keep or drop all of its
+/// events together. Also the arguments of the server's `search_bash_events` MCP tool and
+/// the query of `GET /api/bash/events`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+pub struct BashEventQuery {
+    /// Only events of this command.
+    #[serde(default)]
+    #[schemars(with = "Option<String>")]
+    pub command_id: Option<Uuid>,
+    /// Only commands containing this text.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Only commands matching this regular expression.
+    #[serde(default)]
+    pub command_regex: Option<String>,
+    /// Only commands that exited with this code.
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// Only commands that failed: a non-zero exit code, a timeout or a cancellation.
+    #[serde(default)]
+    pub failed: bool,
+    /// Only events at or after this time (RFC 3339).
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    /// Only events at or before this time (RFC 3339).
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// Only events of the last this many seconds.
+    #[serde(default)]
+    pub within_secs: Option<u64>,
+    /// Only events of this kind.
+    #[serde(default)]
+    pub kind: Option<BashEventKind>,
+}
+
+impl BashEventQuery {
+    /// All events of `command_id`.
+    pub fn for_command(command_id: Uuid) -> Self {
+        Self {
+            command_id: Some(command_id),
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -77,23 +297,72 @@ pub struct BashEventPage {
     pub next_page_id: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct InitConversationRequest {
-    pub system_message: Option<String>,
+/// The parts of a conversation's system prompt that can change while it runs. The agent's
+/// base prompt comes first, then the system message, then each extension.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ConversationPrompt {
+    pub system_message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ConversationResponse {
-    pub id: String,
-    pub status: String,
+/// Body of `PATCH /api/conversations/{id}/prompt`. Fields left out keep their value.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PromptUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_message: Option<String>,
+    /// Replaces all extensions; an empty list removes them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Vec<String>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct MessageRequest {
-    pub content: String,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct MessageResponse {
-    pub response: String,
+    #[test]
+    fn test_bash_events_match_python_server() {
+        // As served by the Python agent server's `/api/bash/bash_events/search`.
+        let page: BashEventPage = serde_json::from_str(
+            r#"{
+                "items": [
+                    {"kind": "BashCommand", "id": "0b7e5c1e-3f4a-4d8e-9a51-2a3c7a2f9b10",
+                     "timestamp": "2025-06-01T12:00:00Z", "command": "ls", "cwd": null,
+                     "timeout": 300},
+                    {"kind": "BashOutput", "id": "5f0d2b8a-6c1e-4e7f-8d2a-9b3c4d5e6f70",
+                     "timestamp": "2025-06-01T12:00:01Z",
+                     "command_id": "0b7e5c1e-3f4a-4d8e-9a51-2a3c7a2f9b10", "order": 0,
+                     "exit_code": 0, "stdout": "README.md\n", "stderr": null}
+                ],
+                "next_page_id": null
+            }"#,
+        )
+        .unwrap();
+        let [
+            BashEvent::BashCommand(command),
+            BashEvent::BashOutput(output),
+        ] = &page.items[..]
+        else {
+            panic!("unexpected events: {:?}", page.items);
+        };
+        assert_eq!(output.command_id, command.id);
+        assert_eq!(output.stdout.as_deref(), Some("README.md\n"));
+
+        // Fields the Python server reads are written under the same names.
+        let json = serde_json::to_value(BashEvent::BashOutput(output.clone())).unwrap();
+        for field in [
+            "kind",
+            "id",
+            "timestamp",
+            "command_id",
+            "order",
+            "exit_code",
+        ] {
+            assert!(json.get(field).is_some(), "missing {}", field);
+        }
+        let request: ExecuteBashRequest =
+            serde_json::from_str(r#"{"command": "ls", "cwd": "/workspace", "timeout": 30}"#)
+                .unwrap();
+        assert_eq!(request.timeout, Some(30));
+    }
 }
@@ -0,0 +1,168 @@
+//! Request and response bodies of the agent server's HTTP API, shared by the server and
+//! [`crate::client`]. Behind the `server-models` feature.
+
+use crate::events::Event;
+use crate::journal::MutationRecord;
+use crate::outcome::TaskResult;
+use crate::runtime::dry_run::ProposedAction;
+use crate::security::SecurityMode;
+use crate::setup::SetupConfig;
+use crate::system::SystemInfo;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct InitConversationRequest {
+    pub system_message: Option<String>,
+    /// Setup to run in the workspace after the server-wide setup.
+    #[serde(default)]
+    pub setup: Option<SetupConfig>,
+    /// Secrets of the conversation by name. All are redacted from events, logs and prompts.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub secrets: BTreeMap<String, ConversationSecret>,
+    /// What to do with high risk commands, instead of the server's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security_mode: Option<SecurityMode>,
+    /// Start in dry-run mode: the agent proposes changes and commands instead of running them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConversationSecret {
+    pub value: String,
+    /// Pass the secret to commands run by tools as an environment variable of its name.
+    #[serde(default)]
+    pub expose_to_tools: bool,
+}
+
+/// A conversation's dry-run mode and the actions proposed in it, from
+/// `GET /api/conversations/{id}/plan`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlanResponse {
+    pub dry_run: bool,
+    pub actions: Vec<ProposedAction>,
+}
+
+/// What `POST /api/conversations/{id}/plan/execute` ran. Execution stops at the first action
+/// that fails; it and the ones after it stay in the plan.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlanExecution {
+    /// An action and an observation per executed step.
+    pub events: Vec<Event>,
+    pub remaining: Vec<ProposedAction>,
+}
+
+/// Body of `POST /api/conversations/{id}/undo`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UndoRequest {
+    /// How many of the latest file changes to undo.
+    #[serde(default = "default_undo_count")]
+    pub count: usize,
+}
+
+fn default_undo_count() -> usize {
+    1
+}
+
+/// The file changes `POST /api/conversations/{id}/undo` reverted, newest first.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UndoResponse {
+    pub undone: Vec<MutationRecord>,
+}
+
+/// A human's decision on an action awaiting confirmation.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ActionDecision {
+    pub approve: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConversationResponse {
+    pub id: String,
+    pub status: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MessageRequest {
+    pub content: String,
+    /// Workspace files to attach, relative to the workspace; see [`crate::file_refs`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_refs: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MessageResponse {
+    pub response: String,
+}
+
+/// Body of `POST /api/tasks`: a task for the agent to work on unattended.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TaskRequest {
+    /// What the agent should do.
+    pub task: String,
+    /// Git repository (an `https://` or ssh remote) cloned into the task's workspace before
+    /// the agent starts.
+    #[serde(default)]
+    pub repo: Option<String>,
+    /// Branch of `repo` to check out instead of its default branch.
+    #[serde(default)]
+    pub branch: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    /// Waiting for one of the concurrent task slots.
+    Queued,
+    Running,
+    /// The agent called `finish`; see the result for how it went.
+    Succeeded,
+    /// The task could not be run, or the agent stopped without calling `finish`.
+    Failed,
+}
+
+/// A task submitted to `POST /api/tasks` and what became of it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskInfo {
+    pub id: String,
+    pub task: String,
+    pub repo: Option<String>,
+    pub branch: Option<String>,
+    pub state: TaskState,
+    /// The conversation running the task, once it has started.
+    pub conversation_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// When the agent's progress was last saved; a restarted server resumes from there.
+    #[serde(default)]
+    pub checkpoint_at: Option<DateTime<Utc>>,
+    pub result: Option<TaskResult>,
+    pub error: Option<String>,
+}
+
+/// Capabilities of an agent server, returned by `GET /server_info`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServerInfo {
+    pub version: String,
+    /// e.g. `http` (REST API) and `mcp` (MCP over streamable HTTP at `/mcp`).
+    pub transports: Vec<String>,
+    /// Runtime conversations execute in: `local` or `docker`.
+    pub runtime: String,
+    pub workspace_dir: String,
+    /// Tools the caller may use.
+    pub tools: Vec<ToolInfo>,
+    /// Uptime, idle time and resource usage.
+    #[serde(flatten)]
+    pub system: SystemInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ToolInfo {
+    pub name: String,
+    pub description: Option<String>,
+    /// JSON Schema of the tool arguments.
+    pub input_schema: serde_json::Value,
+}
@@ -0,0 +1,262 @@
+//! Run lifecycle notifications posted to Slack or Discord incoming webhooks.
+//!
+//! Targets come from the server environment (`SLACK_WEBHOOK_URL`, `DISCORD_WEBHOOK_URL`)
+//! and can be extended per run, so long-running autonomous tasks report back instead of
+//! having to be polled. Delivery failures are logged and never fail the run.
+//!
+//! Targets given per run are only accepted if they are Slack or Discord webhook URLs (see
+//! [`NotificationTarget::validate`]), so a request cannot make the server post to an
+//! arbitrary, possibly internal, address.
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+/// Discord rejects messages longer than this.
+const DISCORD_MAX_LEN: usize = 2000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NotificationTarget {
+    Slack { webhook_url: String },
+    Discord { webhook_url: String },
+}
+
+impl NotificationTarget {
+    /// Check that the target is an incoming webhook of its service: an `https` URL on
+    /// `hooks.slack.com` for Slack, under `/api/webhooks/` on `discord.com` for Discord.
+    pub fn validate(&self) -> Result<(), String> {
+        let (url, hosts, path): (&str, &[&str], &str) = match self {
+            Self::Slack { webhook_url } => (webhook_url, &["hooks.slack.com"], "/"),
+            Self::Discord { webhook_url } => (
+                webhook_url,
+                &["discord.com", "discordapp.com"],
+                "/api/webhooks/",
+            ),
+        };
+        let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+        let valid = parsed.scheme() == "https"
+            && parsed.port().is_none()
+            && parsed.username().is_empty()
+            && parsed.host_str().is_some_and(|host| hosts.contains(&host))
+            && parsed.path().starts_with(path);
+        if !valid {
+            return Err(format!(
+                "Not a {} webhook URL: {}",
+                match self {
+                    Self::Slack { .. } => "Slack",
+                    Self::Discord { .. } => "Discord",
+                },
+                url
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiffStats {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl DiffStats {
+    /// Count changed files and lines in a unified diff.
+    pub fn from_unified_diff(diff: &str) -> Self {
+        let mut stats = Self::default();
+        for line in diff.lines() {
+            if line.starts_with("diff --git ") {
+                stats.files_changed += 1;
+            } else if line.starts_with("+++") || line.starts_with("---") {
+                continue;
+            } else if line.starts_with('+') {
+                stats.insertions += 1;
+            } else if line.starts_with('-') {
+                stats.deletions += 1;
+            }
+        }
+        stats
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RunNotification {
+    Started {
+        run: String,
+        task: String,
+    },
+    AwaitingApproval {
+        run: String,
+        action: String,
+    },
+    Finished {
+        run: String,
+        success: bool,
+        summary: String,
+        diff_stats: Option<DiffStats>,
+    },
+}
+
+impl RunNotification {
+    pub fn render(&self) -> String {
+        match self {
+            Self::Started { run, task } => format!(":rocket: Run `{}` started\n{}", run, task),
+            Self::AwaitingApproval { run, action } => {
+                format!(
+                    ":raised_hand: Run `{}` is awaiting approval for: {}",
+                    run, action
+                )
+            }
+            Self::Finished {
+                run,
+                success,
+                summary,
+                diff_stats,
+            } => {
+                let mut text = if *success {
+                    format!(":white_check_mark: Run `{}` finished\n{}", run, summary)
+                } else {
+                    format!(":x: Run `{}` failed\n{}", run, summary)
+                };
+                if let Some(stats) = diff_stats {
+                    text.push_str(&format!(
+                        "\n{} file(s) changed, {} insertion(s)(+), {} deletion(s)(-)",
+                        stats.files_changed, stats.insertions, stats.deletions
+                    ));
+                }
+                text
+            }
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Notifier {
+    targets: Vec<NotificationTarget>,
+    client: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(targets: Vec<NotificationTarget>) -> Self {
+        Self {
+            targets,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Server-wide targets from `SLACK_WEBHOOK_URL` and `DISCORD_WEBHOOK_URL`.
+    pub fn from_env() -> Self {
+        let mut targets = Vec::new();
+        if let Ok(webhook_url) = std::env::var("SLACK_WEBHOOK_URL") {
+            targets.push(NotificationTarget::Slack { webhook_url });
+        }
+        if let Ok(webhook_url) = std::env::var("DISCORD_WEBHOOK_URL") {
+            targets.push(NotificationTarget::Discord { webhook_url });
+        }
+        Self::new(targets)
+    }
+
+    /// Add per-run/per-conversation targets. Targets that fail
+    /// [`NotificationTarget::validate`] are skipped.
+    pub fn with_targets(mut self, targets: impl IntoIterator<Item = NotificationTarget>) -> Self {
+        for target in targets {
+            if let Err(e) = target.validate() {
+                warn!("Skipping notification target: {}", e);
+                continue;
+            }
+            if !self.targets.contains(&target) {
+                self.targets.push(target);
+            }
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    pub async fn notify(&self, notification: &RunNotification) {
+        let text = notification.render();
+        for target in &self.targets {
+            let (url, payload) = match target {
+                NotificationTarget::Slack { webhook_url } => {
+                    (webhook_url, serde_json::json!({ "text": text }))
+                }
+                NotificationTarget::Discord { webhook_url } => (
+                    webhook_url,
+                    serde_json::json!({
+                        "content": text.chars().take(DISCORD_MAX_LEN).collect::<String>()
+                    }),
+                ),
+            };
+
+            match self.client.post(url).json(&payload).send().await {
+                Ok(res) if !res.status().is_success() => {
+                    error!("Notification webhook returned {}", res.status())
+                }
+                Err(e) => error!("Failed to send notification: {}", e),
+                Ok(_) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_stats() {
+        let diff = "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1 +1,2 @@\n-one\n+two\n+three\n\
+                    diff --git a/b.txt b/b.txt\n--- /dev/null\n+++ b/b.txt\n@@ -0,0 +1 @@\n+new\n";
+        assert_eq!(
+            DiffStats::from_unified_diff(diff),
+            DiffStats {
+                files_changed: 2,
+                insertions: 3,
+                deletions: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_render_and_targets() {
+        let finished = RunNotification::Finished {
+            run: "octo/hello#7".to_string(),
+            success: true,
+            summary: "Fixed the crash".to_string(),
+            diff_stats: Some(DiffStats {
+                files_changed: 1,
+                insertions: 2,
+                deletions: 0,
+            }),
+        };
+        let text = finished.render();
+        assert!(text.contains("octo/hello#7"));
+        assert!(text.contains("1 file(s) changed, 2 insertion(s)(+), 0 deletion(s)(-)"));
+
+        let target: NotificationTarget = serde_json::from_value(serde_json::json!({
+            "kind": "discord",
+            "webhook_url": "https://discord.com/api/webhooks/1/abc"
+        }))
+        .unwrap();
+        let notifier = Notifier::default().with_targets([target.clone(), target]);
+        assert_eq!(notifier.targets.len(), 1);
+
+        for url in [
+            "http://hooks.slack.com/services/T/B/x",
+            "https://hooks.slack.com.evil.test/services/T/B/x",
+            "https://hooks.slack.com:8443/services/T/B/x",
+            "https://169.254.169.254/latest/meta-data",
+        ] {
+            let target = NotificationTarget::Slack {
+                webhook_url: url.to_string(),
+            };
+            assert!(target.validate().is_err(), "{}", url);
+        }
+        let internal = NotificationTarget::Discord {
+            webhook_url: "https://discord.com/users/@me".to_string(),
+        };
+        assert!(Notifier::default().with_targets([internal]).is_empty());
+    }
+}
@@ -0,0 +1,204 @@
+//! Machine-readable outcome of a conversation's task.
+//!
+//! The agent reports the outcome by calling the `finish` tool with a status, a summary and
+//! optionally the result of the tests it ran. [`TaskResult::from_history`] combines the last
+//! such call with the files the conversation changed.
+
+use crate::events::{Event, FileChange};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Name of the tool the agent calls when it is done.
+pub const FINISH_TOOL: &str = "finish";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    /// The task is done.
+    Success,
+    /// Part of the task is done; the summary says what is left.
+    Partial,
+    /// The task could not be done.
+    Failure,
+}
+
+/// Outcome of the tests the agent ran before finishing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestResults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    pub passed: u64,
+    pub failed: u64,
+    #[serde(default)]
+    pub skipped: u64,
+}
+
+/// Arguments of the `finish` tool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinishArgs {
+    pub status: TaskStatus,
+    pub summary: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tests: Option<TestResults>,
+}
+
+/// A file the conversation changed, with the number of lines added and removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangedFile {
+    pub path: String,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskResult {
+    pub status: TaskStatus,
+    pub summary: String,
+    pub changed_files: Vec<ChangedFile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tests: Option<TestResults>,
+    pub finished_at: DateTime<Utc>,
+}
+
+impl TaskResult {
+    /// The result reported by the last successful `finish` call in `history`, or `None` if
+    /// the agent has not finished.
+    pub fn from_history(history: &[Event]) -> Option<Self> {
+        let finish = history.iter().enumerate().rev().find_map(|(i, event)| {
+            let Event::Action(action) = event else {
+                return None;
+            };
+            if action.tool_name != FINISH_TOOL {
+                return None;
+            }
+            let succeeded = history[i..].iter().any(|e| {
+                matches!(e, Event::Observation(o)
+                    if o.tool_call_id == action.tool_call_id && !o.content.starts_with("Error:"))
+            });
+            let args = serde_json::from_value::<FinishArgs>(action.arguments.clone()).ok()?;
+            succeeded.then_some(args)
+        })?;
+
+        Some(Self {
+            status: finish.status,
+            summary: finish.summary,
+            changed_files: changed_files(history),
+            tests: finish.tests,
+            finished_at: Utc::now(),
+        })
+    }
+}
+
+/// Lines added and removed per file over all the file changes in `history`, by path.
+pub fn changed_files(history: &[Event]) -> Vec<ChangedFile> {
+    let mut files: BTreeMap<&str, ChangedFile> = BTreeMap::new();
+    let changes = history.iter().flat_map(|event| match event {
+        Event::Observation(o) => o.file_changes.as_slice(),
+        _ => &[],
+    });
+    for change in changes {
+        let FileChange { path, edits, .. } = change;
+        let file = files.entry(path).or_insert_with(|| ChangedFile {
+            path: path.clone(),
+            additions: 0,
+            deletions: 0,
+        });
+        for edit in edits {
+            file.additions += edit.new_lines.len();
+            file.deletions += edit.old_len;
+        }
+    }
+    files.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{ActionEvent, ObservationEvent};
+    use serde_json::json;
+
+    fn action(id: &str, tool_name: &str, arguments: serde_json::Value) -> Event {
+        Event::Action(ActionEvent {
+            source: "agent".to_string(),
+            tool_name: tool_name.to_string(),
+            tool_call_id: id.to_string(),
+            arguments,
+            thought: None,
+            security_risk: None,
+            reasoning: None,
+        })
+    }
+
+    fn observation(id: &str, tool_name: &str, content: &str, changes: Vec<FileChange>) -> Event {
+        Event::Observation(ObservationEvent {
+            source: "runtime".to_string(),
+            tool_name: tool_name.to_string(),
+            tool_call_id: id.to_string(),
+            content: content.to_string(),
+            file_changes: changes,
+            untracked_changes: false,
+            attachments: Vec::new(),
+            injection: None,
+        })
+    }
+
+    #[test]
+    fn test_task_result_from_history() {
+        let edit = FileChange::capture("src/lib.rs", Some("a\nb\n"), Some("a\nc\nd\n")).unwrap();
+        let created = FileChange::capture("NOTES.md", None, Some("x\n")).unwrap();
+        let mut history = vec![
+            action("1", "file_editor", json!({})),
+            observation("1", "file_editor", "ok", vec![edit, created]),
+        ];
+        assert_eq!(TaskResult::from_history(&history), None);
+
+        history.push(action("2", FINISH_TOOL, json!({ "status": "done" })));
+        history.push(observation(
+            "2",
+            FINISH_TOOL,
+            "Error: unknown status",
+            vec![],
+        ));
+        assert_eq!(TaskResult::from_history(&history), None);
+
+        let args = json!({
+            "status": "partial",
+            "summary": "Fixed the parser; the docs still need updating",
+            "tests": { "command": "cargo test", "passed": 12, "failed": 1 }
+        });
+        history.push(action("3", FINISH_TOOL, args));
+        history.push(observation("3", FINISH_TOOL, "Task finished", vec![]));
+
+        let result = TaskResult::from_history(&history).unwrap();
+        assert_eq!(result.status, TaskStatus::Partial);
+        assert_eq!(
+            result.summary,
+            "Fixed the parser; the docs still need updating"
+        );
+        assert_eq!(
+            result.tests,
+            Some(TestResults {
+                command: Some("cargo test".to_string()),
+                passed: 12,
+                failed: 1,
+                skipped: 0,
+            })
+        );
+        assert_eq!(
+            result.changed_files,
+            vec![
+                ChangedFile {
+                    path: "NOTES.md".to_string(),
+                    additions: 1,
+                    deletions: 0,
+                },
+                ChangedFile {
+                    path: "src/lib.rs".to_string(),
+                    additions: 2,
+                    deletions: 1,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,579 @@
+//! File paths in tool output and arguments.
+//!
+//! Tool output is text, but file names are arbitrary bytes on Unix. A path that is valid
+//! UTF-8 is shown as is; any other path is percent-encoded: each byte that is not part of
+//! valid UTF-8 becomes `%XX`, and `%` itself becomes `%25`, so the name can be recovered
+//! exactly. [`resolve`] accepts either form back from the agent or a client.
+
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+use walkdir::WalkDir;
+
+/// `path` as text, percent-encoded if it is not valid UTF-8.
+pub fn encode_path(path: &Path) -> Cow<'_, str> {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Cow::Borrowed(text);
+    }
+    let mut encoded = String::with_capacity(bytes.len() + 8);
+    for chunk in bytes.utf8_chunks() {
+        encoded.push_str(&chunk.valid().replace('%', "%25"));
+        for byte in chunk.invalid() {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    Cow::Owned(encoded)
+}
+
+/// The path a percent-encoded string stands for, or `None` if it has no valid escapes.
+#[cfg(unix)]
+pub fn decode_path(text: &str) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    let mut escaped = false;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                escaped = true;
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    escaped.then(|| PathBuf::from(std::ffi::OsString::from_vec(decoded)))
+}
+
+/// Only Unix file names can be arbitrary bytes.
+#[cfg(not(unix))]
+pub fn decode_path(_text: &str) -> Option<PathBuf> {
+    None
+}
+
+/// `path` relative to `base` (or absolute), taken literally if that exists and as an
+/// [`encode_path`] encoding otherwise.
+pub fn resolve(base: &Path, path: &str) -> PathBuf {
+    let literal = base.join(path);
+    if literal.symlink_metadata().is_ok() {
+        return literal;
+    }
+    match decode_path(path).map(|decoded| base.join(decoded)) {
+        Some(decoded) if decoded.symlink_metadata().is_ok() => decoded,
+        _ => literal,
+    }
+}
+
+/// [`resolve`], refusing paths outside of `base` through `..` or symlinked directories.
+/// The last component is not followed, so a symlink itself can be moved or deleted.
+pub fn confine(base: &Path, path: &str) -> Result<PathBuf, String> {
+    let outside = || format!("Path '{}' is outside the workspace", path);
+    let mut normalized = PathBuf::new();
+    for component in resolve(base, path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return Err(outside());
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    let base = base.canonicalize().map_err(|e| e.to_string())?;
+    let Some(name) = normalized.file_name().map(|n| n.to_os_string()) else {
+        return Err(outside());
+    };
+
+    // Canonicalize the deepest existing ancestor; the rest does not exist to point anywhere.
+    let mut existing = normalized
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let mut missing = Vec::new();
+    let parent = loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            break canonical;
+        }
+        match existing.file_name() {
+            Some(component) => missing.push(component.to_os_string()),
+            None => return Err(outside()),
+        }
+        existing.pop();
+    };
+    let confined = missing
+        .into_iter()
+        .rev()
+        .fold(parent, |path, component| path.join(component))
+        .join(name);
+    if confined.starts_with(&base) {
+        Ok(confined)
+    } else {
+        Err(outside())
+    }
+}
+
+/// Paths under `base` matching the glob `pattern` (relative to `base`, or absolute), in
+/// file name order. Unlike the `glob` crate, names that are not valid UTF-8 are matched in
+/// their [`encode_path`] form instead of being skipped.
+pub fn glob_paths(base: &Path, pattern: &str) -> Result<impl Iterator<Item = PathBuf>, String> {
+    let (base, pattern) = match pattern.strip_prefix('/') {
+        Some(relative) => (Path::new("/"), relative),
+        None => (base, pattern),
+    };
+    let compiled = Pattern::new(pattern).map_err(|e| e.to_string())?;
+
+    // Walk only below the pattern's literal leading directories, and no deeper than it
+    // reaches unless it contains `**`.
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    let literal = components
+        .iter()
+        .take(components.len().saturating_sub(1))
+        .take_while(|c| !c.contains(['*', '?', '[']))
+        .count();
+    let root = components[..literal]
+        .iter()
+        .fold(base.to_path_buf(), |root, c| root.join(c));
+    let max_depth = if pattern.contains("**") {
+        usize::MAX
+    } else {
+        components.len() - literal
+    };
+
+    let base = base.to_path_buf();
+    Ok(WalkDir::new(root)
+        .min_depth(1)
+        .max_depth(max_depth)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(move |entry| {
+            let relative = entry.path().strip_prefix(&base).unwrap_or(entry.path());
+            // `./` prefixes in the walk root would not be in the pattern.
+            let relative: PathBuf = relative
+                .components()
+                .filter(|c| !matches!(c, Component::CurDir))
+                .collect();
+            compiled.matches_with(&encode_path(&relative), MATCH_OPTIONS)
+        })
+        .map(|entry| entry.into_path()))
+}
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// One entry of a [`Walk`].
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    pub path: PathBuf,
+    /// `path` relative to the walk's root.
+    pub relative: PathBuf,
+    /// 1 for the root's own entries.
+    pub depth: usize,
+    pub is_dir: bool,
+}
+
+/// A directory walk that skips `.git` and whatever `.gitignore` files ignore, in file name
+/// order with each directory before its contents.
+///
+/// Globs match the [`encode_path`] form of the path relative to the root, or just the file
+/// name if they contain no `/`. Exclude globs prune directories; include globs select files.
+#[derive(Debug, Clone)]
+pub struct Walk {
+    max_depth: usize,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    gitignore: bool,
+}
+
+impl Default for Walk {
+    fn default() -> Self {
+        Self {
+            max_depth: usize::MAX,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            gitignore: true,
+        }
+    }
+}
+
+impl Walk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Descend at most `depth` levels; 1 lists only the root's own entries.
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    pub fn with_include(mut self, globs: &[String]) -> Result<Self, String> {
+        self.include = compile_globs(globs)?;
+        Ok(self)
+    }
+
+    pub fn with_exclude(mut self, globs: &[String]) -> Result<Self, String> {
+        self.exclude = compile_globs(globs)?;
+        Ok(self)
+    }
+
+    /// Whether `.gitignore` files are honoured (the default).
+    pub fn with_gitignore(mut self, gitignore: bool) -> Self {
+        self.gitignore = gitignore;
+        self
+    }
+
+    /// Pass the entries under `root` to `visit` until it returns `false`. Directories that
+    /// cannot be read are skipped.
+    pub fn run(&self, root: &Path, mut visit: impl FnMut(WalkEntry) -> bool) {
+        let mut ignores = Vec::new();
+        self.visit_dir(root, Path::new(""), 1, &mut ignores, &mut visit);
+    }
+
+    fn visit_dir(
+        &self,
+        root: &Path,
+        relative: &Path,
+        depth: usize,
+        ignores: &mut Vec<IgnoreFile>,
+        visit: &mut impl FnMut(WalkEntry) -> bool,
+    ) -> bool {
+        let dir = root.join(relative);
+        let Ok(read_dir) = fs::read_dir(&dir) else {
+            return true;
+        };
+        let mut children: Vec<_> = read_dir.flatten().collect();
+        children.sort_by_key(|child| child.file_name());
+
+        let loaded = self.gitignore && IgnoreFile::load(&dir, relative, ignores);
+        let mut more = true;
+        for child in children {
+            let Ok(file_type) = child.file_type() else {
+                continue;
+            };
+            let path = child.path();
+            let is_dir = file_type.is_dir() || (file_type.is_symlink() && path.is_dir());
+            let name = child.file_name();
+            let child_relative = relative.join(&name);
+            if (is_dir && name == ".git")
+                || ignores
+                    .iter()
+                    .rev()
+                    .find_map(|file| file.matches(&child_relative, is_dir))
+                    .unwrap_or(false)
+                || matches_any(&self.exclude, &child_relative)
+                || (!is_dir
+                    && !self.include.is_empty()
+                    && !matches_any(&self.include, &child_relative))
+            {
+                continue;
+            }
+
+            let entry = WalkEntry {
+                path,
+                relative: child_relative.clone(),
+                depth,
+                is_dir,
+            };
+            if !visit(entry) {
+                more = false;
+                break;
+            }
+            // Symlinked directories are listed but not followed, so cycles cannot form.
+            if file_type.is_dir()
+                && depth < self.max_depth
+                && !self.visit_dir(root, &child_relative, depth + 1, ignores, visit)
+            {
+                more = false;
+                break;
+            }
+        }
+        if loaded {
+            ignores.pop();
+        }
+        more
+    }
+}
+
+fn compile_globs(globs: &[String]) -> Result<Vec<Pattern>, String> {
+    globs
+        .iter()
+        .map(|glob| Pattern::new(glob).map_err(|e| format!("Invalid glob '{}': {}", glob, e)))
+        .collect()
+}
+
+fn matches_any(globs: &[Pattern], relative: &Path) -> bool {
+    let encoded = encode_path(relative);
+    let name = relative.file_name().map(|n| encode_path(Path::new(n)));
+    globs.iter().any(|glob| {
+        if glob.as_str().contains('/') {
+            glob.matches_with(&encoded, MATCH_OPTIONS)
+        } else {
+            name.as_ref()
+                .is_some_and(|name| glob.matches_with(name, MATCH_OPTIONS))
+        }
+    })
+}
+
+/// The rules of one `.gitignore`. Supported: comments, `!` negation, trailing `/` for
+/// directories only, and patterns anchored to their directory when they contain a `/`.
+#[derive(Debug)]
+struct IgnoreFile {
+    /// The directory holding the file, relative to the walk's root.
+    dir: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+#[derive(Debug)]
+struct IgnoreRule {
+    pattern: Pattern,
+    negate: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl IgnoreFile {
+    /// Push the `.gitignore` of `dir` onto `ignores`, returning whether there was one.
+    fn load(dir: &Path, relative: &Path, ignores: &mut Vec<IgnoreFile>) -> bool {
+        let Ok(text) = fs::read_to_string(dir.join(".gitignore")) else {
+            return false;
+        };
+        let rules = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let (negate, line) = match line.strip_prefix('!') {
+                    Some(line) => (true, line),
+                    None => (false, line),
+                };
+                let (dir_only, line) = match line.strip_suffix('/') {
+                    Some(line) => (true, line),
+                    None => (false, line),
+                };
+                let anchored = line.contains('/');
+                let pattern = Pattern::new(line.trim_start_matches('/')).ok()?;
+                Some(IgnoreRule {
+                    pattern,
+                    negate,
+                    dir_only,
+                    anchored,
+                })
+            })
+            .collect();
+        ignores.push(IgnoreFile {
+            dir: relative.to_path_buf(),
+            rules,
+        });
+        true
+    }
+
+    /// Whether the last rule matching `relative` ignores it, or `None` if none matches.
+    fn matches(&self, relative: &Path, is_dir: bool) -> Option<bool> {
+        let relative = relative.strip_prefix(&self.dir).ok()?;
+        let encoded = encode_path(relative);
+        let name = encode_path(Path::new(relative.file_name()?));
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| is_dir || !rule.dir_only)
+            .find(|rule| {
+                let target = if rule.anchored { &encoded } else { &name };
+                rule.pattern.matches_with(target, MATCH_OPTIONS)
+            })
+            .map(|rule| !rule.negate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_and_resolve() {
+        assert_eq!(encode_path(Path::new("src/50%.rs")), "src/50%.rs");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let dir = tempfile::tempdir().unwrap();
+            let name = std::ffi::OsStr::from_bytes(b"caf\xe9 100%.txt");
+            std::fs::write(dir.path().join(name), "x").unwrap();
+            let encoded = encode_path(Path::new(name)).into_owned();
+            assert_eq!(encoded, "caf%E9 100%25.txt");
+            assert_eq!(decode_path(&encoded).unwrap().as_os_str(), name);
+            assert_eq!(resolve(dir.path(), &encoded), dir.path().join(name));
+            assert_eq!(resolve(dir.path(), "new.txt"), dir.path().join("new.txt"));
+        }
+    }
+
+    #[test]
+    fn test_confine() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("ws");
+        std::fs::create_dir_all(base.join("src")).unwrap();
+        let base = base.canonicalize().unwrap();
+
+        assert_eq!(
+            confine(&base, "src/new/a.rs").unwrap(),
+            base.join("src/new/a.rs")
+        );
+        assert_eq!(confine(&base, "src/../b.rs").unwrap(), base.join("b.rs"));
+        assert!(confine(&base, "../outside.txt").is_err());
+        assert!(confine(&base, "/etc/passwd").is_err());
+        assert_eq!(confine(&base, ".").unwrap(), base);
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path(), base.join("up")).unwrap();
+            assert!(confine(&base, "up/x.txt").is_err());
+            // The link itself is inside.
+            assert_eq!(confine(&base, "up").unwrap(), base.join("up"));
+        }
+    }
+
+    #[test]
+    fn test_glob_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let long = "x".repeat(250);
+        std::fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        for file in ["a.rs", "src/b.rs", "src/nested/c.rs", "src/notes.md"] {
+            std::fs::write(dir.path().join(file), "").unwrap();
+        }
+        std::fs::write(dir.path().join("src").join(format!("{}.rs", long)), "").unwrap();
+        let names = |pattern: &str| -> Vec<String> {
+            glob_paths(dir.path(), pattern)
+                .unwrap()
+                .map(|p| encode_path(p.strip_prefix(dir.path()).unwrap()).into_owned())
+                .collect()
+        };
+
+        assert_eq!(names("*.rs"), ["a.rs"]);
+        assert_eq!(
+            names("src/*.rs"),
+            ["src/b.rs", format!("src/{}.rs", long).as_str()]
+        );
+        assert_eq!(
+            names("**/*.rs"),
+            [
+                "a.rs",
+                "src/b.rs",
+                "src/nested/c.rs",
+                format!("src/{}.rs", long).as_str()
+            ]
+        );
+        assert!(glob_paths(dir.path(), "[").is_err());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let name = std::ffi::OsStr::from_bytes(b"\xff.rs");
+            std::fs::write(dir.path().join("src").join(name), "").unwrap();
+            assert!(names("src/*.rs").contains(&"src/%FF.rs".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        for sub in [".git", "src/gen", "target/debug", "docs"] {
+            std::fs::create_dir_all(dir.path().join(sub)).unwrap();
+        }
+        for file in [
+            ".git/HEAD",
+            "README.md",
+            "build.log",
+            "keep.log",
+            "src/lib.rs",
+            "src/gen/out.rs",
+            "src/notes.txt",
+            "target/debug/app",
+            "docs/guide.md",
+        ] {
+            std::fs::write(dir.path().join(file), "").unwrap();
+        }
+        std::fs::write(
+            dir.path().join(".gitignore"),
+            "# build output\n/target/\n*.log\n!keep.log\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("src/.gitignore"), "gen/\n").unwrap();
+
+        let list = |walk: Walk| {
+            let mut entries = Vec::new();
+            walk.run(dir.path(), |entry| {
+                entries.push(format!("{}:{}", entry.depth, encode_path(&entry.relative)));
+                true
+            });
+            entries
+        };
+
+        assert_eq!(
+            list(Walk::new()),
+            [
+                "1:.gitignore",
+                "1:README.md",
+                "1:docs",
+                "2:docs/guide.md",
+                "1:keep.log",
+                "1:src",
+                "2:src/.gitignore",
+                "2:src/lib.rs",
+                "2:src/notes.txt",
+            ]
+        );
+        assert_eq!(
+            list(Walk::new().with_max_depth(1)),
+            [
+                "1:.gitignore",
+                "1:README.md",
+                "1:docs",
+                "1:keep.log",
+                "1:src"
+            ]
+        );
+        let walk = Walk::new()
+            .with_include(&["*.rs".to_string()])
+            .unwrap()
+            .with_exclude(&["docs".to_string()])
+            .unwrap()
+            .with_gitignore(false);
+        assert_eq!(
+            list(walk),
+            [
+                "1:src",
+                "2:src/gen",
+                "3:src/gen/out.rs",
+                "2:src/lib.rs",
+                "1:target",
+                "2:target/debug",
+            ]
+        );
+        assert!(Walk::new().with_include(&["[".to_string()]).is_err());
+
+        let mut first = Vec::new();
+        Walk::new().run(dir.path(), |entry| {
+            first.push(entry.relative);
+            first.len() < 2
+        });
+        assert_eq!(first.len(), 2);
+    }
+}
@@ -0,0 +1,264 @@
+//! Tools provided by external processes, registered with a running server instead of being
+//! compiled in.
+//!
+//! A plugin registers a [`ToolRegistration`]: a name, a description, a JSON Schema for the
+//! arguments and an HTTP endpoint. Calls are `POST`ed to the endpoint as
+//! `{"name": ..., "arguments": {...}}`; a 2xx response is the tool output (either
+//! `{"output": "..."}` or plain text), anything else is reported to the agent as an error.
+//!
+//! Plugins with a `health_url` are checked periodically. A failing plugin is hidden from new
+//! conversations, and removed after [`MAX_HEALTH_FAILURES`] consecutive failures.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::agent::tools::Tool;
+
+pub const MAX_HEALTH_FAILURES: u32 = 3;
+
+const CALL_TIMEOUT: Duration = Duration::from_secs(300);
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ToolRegistration {
+    pub name: String,
+    pub description: String,
+    /// JSON Schema of the arguments; must be an object schema.
+    pub parameters: Value,
+    /// URL the calls are `POST`ed to.
+    pub endpoint: String,
+    /// URL answering `GET` with 2xx while the plugin is up.
+    #[serde(default)]
+    pub health_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RegisteredTool {
+    #[serde(flatten)]
+    pub registration: ToolRegistration,
+    pub registered_at: DateTime<Utc>,
+    pub healthy: bool,
+    /// Consecutive failed health checks.
+    pub failures: u32,
+}
+
+impl ToolRegistration {
+    fn validate(&self) -> Result<(), String> {
+        let valid_name = !self.name.is_empty()
+            && self.name.len() <= 64
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_name {
+            return Err(format!(
+                "Invalid tool name '{}': use up to 64 letters, digits, '_' or '-'",
+                self.name
+            ));
+        }
+        if self.parameters.get("type").and_then(Value::as_str) != Some("object") {
+            return Err("'parameters' must be a JSON Schema of type object".to_string());
+        }
+        for url in std::iter::once(&self.endpoint).chain(&self.health_url) {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("Invalid URL '{}'", url));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct PluginRegistry {
+    tools: RwLock<BTreeMap<String, RegisteredTool>>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Add a tool, replacing an earlier registration of the same name (e.g. by a restarted
+    /// plugin).
+    pub fn register(&self, registration: ToolRegistration) -> Result<RegisteredTool, String> {
+        registration.validate()?;
+        let tool = RegisteredTool {
+            registration,
+            registered_at: Utc::now(),
+            healthy: true,
+            failures: 0,
+        };
+        self.tools
+            .write()
+            .unwrap()
+            .insert(tool.registration.name.clone(), tool.clone());
+        Ok(tool)
+    }
+
+    /// Remove a tool, returning whether it was registered.
+    pub fn deregister(&self, name: &str) -> bool {
+        self.tools.write().unwrap().remove(name).is_some()
+    }
+
+    pub fn list(&self) -> Vec<RegisteredTool> {
+        self.tools.read().unwrap().values().cloned().collect()
+    }
+
+    /// The healthy plugins as agent tools.
+    pub fn tools(&self) -> Vec<Box<dyn Tool>> {
+        self.tools
+            .read()
+            .unwrap()
+            .values()
+            .filter(|t| t.healthy)
+            .map(|t| Box::new(HttpTool(t.registration.clone())) as Box<dyn Tool>)
+            .collect()
+    }
+
+    /// Probe every plugin with a `health_url` once.
+    pub async fn check_health(&self) {
+        let targets: Vec<(String, String)> = self
+            .tools
+            .read()
+            .unwrap()
+            .values()
+            .filter_map(|t| {
+                Some((
+                    t.registration.name.clone(),
+                    t.registration.health_url.clone()?,
+                ))
+            })
+            .collect();
+        let client = reqwest::Client::new();
+        for (name, url) in targets {
+            let healthy = client
+                .get(&url)
+                .timeout(HEALTH_TIMEOUT)
+                .send()
+                .await
+                .is_ok_and(|res| res.status().is_success());
+
+            let mut tools = self.tools.write().unwrap();
+            let Some(tool) = tools.get_mut(&name) else {
+                continue;
+            };
+            tool.healthy = healthy;
+            tool.failures = if healthy { 0 } else { tool.failures + 1 };
+            if tool.failures >= MAX_HEALTH_FAILURES {
+                tracing::warn!("Removing plugin tool {}: {} is unreachable", name, url);
+                tools.remove(&name);
+            }
+        }
+    }
+
+    /// Run [`PluginRegistry::check_health`] every `interval`.
+    pub async fn run_health_checks(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            self.check_health().await;
+        }
+    }
+}
+
+/// A registered tool, called over HTTP. Plugins talk to services rather than the workspace,
+/// so sandboxed runtimes call them from the host.
+pub struct HttpTool(pub ToolRegistration);
+
+#[async_trait]
+impl Tool for HttpTool {
+    fn name(&self) -> String {
+        self.0.name.clone()
+    }
+
+    fn description(&self) -> String {
+        self.0.description.clone()
+    }
+
+    fn parameters(&self) -> Value {
+        self.0.parameters.clone()
+    }
+
+    fn runs_on_host(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, args: Value) -> Result<String, String> {
+        let res = reqwest::Client::new()
+            .post(&self.0.endpoint)
+            .timeout(CALL_TIMEOUT)
+            .json(&serde_json::json!({ "name": self.0.name, "arguments": args }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach {}: {}", self.0.name, e))?;
+        let status = res.status();
+        let body = res.text().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            return Err(format!("{} returned {}: {}", self.0.name, status, body));
+        }
+        match serde_json::from_str::<Value>(&body) {
+            Ok(Value::Object(object)) => match object.get("output") {
+                Some(Value::String(output)) => Ok(output.clone()),
+                Some(output) => Ok(output.to_string()),
+                None => Ok(body),
+            },
+            _ => Ok(body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(name: &str, health_url: Option<&str>) -> ToolRegistration {
+        ToolRegistration {
+            name: name.to_string(),
+            description: "Open a ticket".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": { "title": { "type": "string" } }
+            }),
+            endpoint: "http://127.0.0.1:1/call".to_string(),
+            health_url: health_url.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_lifecycle() {
+        let registry = PluginRegistry::new();
+        assert!(registry.register(registration("bad name", None)).is_err());
+        let mut no_schema = registration("ticket", None);
+        no_schema.parameters = serde_json::json!({});
+        assert!(registry.register(no_schema).is_err());
+
+        registry.register(registration("deploy", None)).unwrap();
+        registry
+            .register(registration("ticket", Some("http://127.0.0.1:1/health")))
+            .unwrap();
+        let names: Vec<String> = registry.tools().iter().map(|t| t.name()).collect();
+        assert_eq!(names, vec!["deploy", "ticket"]);
+
+        // An unreachable plugin is hidden, then dropped; plugins without a health URL stay.
+        registry.check_health().await;
+        assert_eq!(registry.tools().len(), 1);
+        assert_eq!(registry.list()[1].failures, 1);
+        for _ in 1..MAX_HEALTH_FAILURES {
+            registry.check_health().await;
+        }
+        let names: Vec<String> = registry
+            .list()
+            .into_iter()
+            .map(|t| t.registration.name)
+            .collect();
+        assert_eq!(names, vec!["deploy"]);
+
+        assert!(registry.deregister("deploy"));
+        assert!(!registry.deregister("deploy"));
+    }
+}
@@ -0,0 +1,272 @@
+//! Where an agent's time and tokens go.
+//!
+//! The agent records every step in its [`Profiler`]: the latency and token usage of each LLM
+//! call and the latency of each tool call. [`Profiler::report`] aggregates them per step and
+//! per tool, so the slow tools and the expensive steps of a run stand out.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Tokens used by LLM calls.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Usage accrued since `earlier`.
+    pub fn since(&self, earlier: &TokenUsage) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_tokens.saturating_sub(earlier.prompt_tokens),
+            completion_tokens: self
+                .completion_tokens
+                .saturating_sub(earlier.completion_tokens),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ToolCallRecord {
+    tool: String,
+    duration: Duration,
+    failed: bool,
+}
+
+#[derive(Debug, Clone)]
+struct StepRecord {
+    started_at: DateTime<Utc>,
+    duration: Duration,
+    llm_calls: Vec<(Duration, Option<TokenUsage>)>,
+    tool_calls: Vec<ToolCallRecord>,
+}
+
+/// The steps an agent ran; shared between clones.
+#[derive(Clone, Default)]
+pub struct Profiler {
+    steps: Arc<Mutex<Vec<StepRecord>>>,
+}
+
+impl Profiler {
+    /// Start recording a step, which is added to the profile when the returned guard is
+    /// dropped, however the step ends.
+    pub fn step(&self) -> StepProfiler<'_> {
+        StepProfiler {
+            profiler: self,
+            started: Instant::now(),
+            record: StepRecord {
+                started_at: Utc::now(),
+                duration: Duration::ZERO,
+                llm_calls: Vec::new(),
+                tool_calls: Vec::new(),
+            },
+        }
+    }
+
+    /// The steps recorded so far, aggregated.
+    pub fn report(&self) -> ProfileReport {
+        let steps = self.steps.lock().unwrap().clone();
+        let mut llm = LlmProfile::default();
+        let mut tools: BTreeMap<&str, ToolProfile> = BTreeMap::new();
+        let mut last_failed: BTreeMap<&str, bool> = BTreeMap::new();
+
+        for step in &steps {
+            for (duration, usage) in &step.llm_calls {
+                llm.calls += 1;
+                llm.total_ms += millis(*duration);
+                llm.max_ms = llm.max_ms.max(millis(*duration));
+                match usage {
+                    Some(usage) => {
+                        llm.usage.prompt_tokens += usage.prompt_tokens;
+                        llm.usage.completion_tokens += usage.completion_tokens;
+                    }
+                    None => llm.errors += 1,
+                }
+            }
+            for call in &step.tool_calls {
+                let tool = tools
+                    .entry(&call.tool)
+                    .or_insert_with(|| ToolProfile::new(&call.tool));
+                tool.calls += 1;
+                tool.total_ms += millis(call.duration);
+                tool.max_ms = tool.max_ms.max(millis(call.duration));
+                if call.failed {
+                    tool.errors += 1;
+                }
+                if last_failed.insert(&call.tool, call.failed) == Some(true) {
+                    tool.retries += 1;
+                }
+            }
+        }
+
+        let mut tools: Vec<ToolProfile> = tools.into_values().collect();
+        tools.sort_by_key(|t| std::cmp::Reverse(t.total_ms));
+        ProfileReport {
+            total_ms: steps.iter().map(|s| millis(s.duration)).sum(),
+            steps: steps.iter().map(StepProfile::from).collect(),
+            llm,
+            tools,
+            cost_usd: None,
+        }
+    }
+}
+
+/// Records one step; see [`Profiler::step`].
+pub struct StepProfiler<'a> {
+    profiler: &'a Profiler,
+    started: Instant,
+    record: StepRecord,
+}
+
+impl StepProfiler<'_> {
+    /// An LLM call that took `duration`, with its usage if it succeeded.
+    pub fn llm_call(&mut self, duration: Duration, usage: Option<TokenUsage>) {
+        self.record.llm_calls.push((duration, usage));
+    }
+
+    /// A call of `tool` that took `duration`.
+    pub fn tool_call(&mut self, tool: &str, duration: Duration, failed: bool) {
+        self.record.tool_calls.push(ToolCallRecord {
+            tool: tool.to_string(),
+            duration,
+            failed,
+        });
+    }
+}
+
+impl Drop for StepProfiler<'_> {
+    fn drop(&mut self) {
+        self.record.duration = self.started.elapsed();
+        let record = self.record.clone();
+        self.profiler.steps.lock().unwrap().push(record);
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProfileReport {
+    /// Wall time of all steps.
+    pub total_ms: u64,
+    pub steps: Vec<StepProfile>,
+    pub llm: LlmProfile,
+    /// By total time, slowest first.
+    pub tools: Vec<ToolProfile>,
+    /// Estimated LLM spend, from servers that know the token prices.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StepProfile {
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub llm_calls: u64,
+    pub llm_ms: u64,
+    pub tool_calls: u64,
+    pub tool_ms: u64,
+    pub usage: TokenUsage,
+}
+
+impl From<&StepRecord> for StepProfile {
+    fn from(step: &StepRecord) -> Self {
+        let mut usage = TokenUsage::default();
+        for (_, call_usage) in &step.llm_calls {
+            let call_usage = call_usage.unwrap_or_default();
+            usage.prompt_tokens += call_usage.prompt_tokens;
+            usage.completion_tokens += call_usage.completion_tokens;
+        }
+        Self {
+            started_at: step.started_at,
+            duration_ms: millis(step.duration),
+            llm_calls: step.llm_calls.len() as u64,
+            llm_ms: step.llm_calls.iter().map(|(d, _)| millis(*d)).sum(),
+            tool_calls: step.tool_calls.len() as u64,
+            tool_ms: step.tool_calls.iter().map(|c| millis(c.duration)).sum(),
+            usage,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct LlmProfile {
+    pub calls: u64,
+    /// Calls that failed.
+    pub errors: u64,
+    pub total_ms: u64,
+    pub max_ms: u64,
+    pub usage: TokenUsage,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ToolProfile {
+    pub name: String,
+    pub calls: u64,
+    pub errors: u64,
+    /// Calls made after the previous call of the tool failed.
+    pub retries: u64,
+    pub total_ms: u64,
+    pub max_ms: u64,
+}
+
+impl ToolProfile {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            calls: 0,
+            errors: 0,
+            retries: 0,
+            total_ms: 0,
+            max_ms: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_aggregates_steps() {
+        let profiler = Profiler::default();
+        let usage = TokenUsage {
+            prompt_tokens: 100,
+            completion_tokens: 10,
+        };
+        {
+            let mut step = profiler.step();
+            step.llm_call(Duration::from_millis(200), Some(usage));
+            step.tool_call("cmd", Duration::from_millis(50), true);
+            step.tool_call("cmd", Duration::from_millis(70), false);
+            step.tool_call("grep", Duration::from_millis(5), false);
+            step.llm_call(Duration::from_millis(300), Some(usage));
+        }
+        {
+            let mut step = profiler.step();
+            step.llm_call(Duration::from_millis(100), None);
+        }
+
+        let report = profiler.report();
+        assert_eq!(report.steps.len(), 2);
+        assert_eq!(report.steps[0].llm_calls, 2);
+        assert_eq!(report.steps[0].llm_ms, 500);
+        assert_eq!(report.steps[0].tool_ms, 125);
+        assert_eq!(report.steps[0].usage.prompt_tokens, 200);
+        assert_eq!(report.llm.calls, 3);
+        assert_eq!(report.llm.errors, 1);
+        assert_eq!(report.llm.max_ms, 300);
+        assert_eq!(report.llm.usage.completion_tokens, 20);
+
+        assert_eq!(report.tools.len(), 2);
+        let cmd = &report.tools[0];
+        assert_eq!(cmd.name, "cmd");
+        assert_eq!((cmd.calls, cmd.errors, cmd.retries), (2, 1, 1));
+        assert_eq!((cmd.total_ms, cmd.max_ms), (120, 70));
+        assert_eq!(report.tools[1].retries, 0);
+    }
+}
@@ -0,0 +1,200 @@
+//! Reconstruct workspace state by replaying the file changes recorded in events.
+//!
+//! Starting from a snapshot of the workspace taken before the conversation began,
+//! applying every [`FileChange`] in event order reproduces the workspace exactly.
+//! Each change verifies the content hash before and after it is applied, so a replay
+//! onto the wrong base fails loudly instead of producing a subtly different tree.
+//! Replaying a prefix of the event list yields the state at that point in time.
+//!
+//! Only edits made through file tools are recorded. Actions that may change files some
+//! other way, such as `cmd` and `terminal` commands, are marked `untracked_changes` and
+//! listed in [`ReplayReport::untracked`]; the replayed tree matches the original only up
+//! to the first of them.
+
+use crate::events::{Event, FileChange};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    /// Number of file changes applied.
+    pub applied: usize,
+    /// Files touched by the replay, relative to the target root.
+    pub files: Vec<PathBuf>,
+    /// Tool call ids of the replayed actions whose file changes were not recorded.
+    pub untracked: Vec<String>,
+}
+
+/// Iterate over all file changes recorded in `events`, in order.
+pub fn file_changes(events: &[Event]) -> impl Iterator<Item = &FileChange> {
+    events.iter().flat_map(|e| match e {
+        Event::Observation(o) => o.file_changes.as_slice(),
+        _ => &[],
+    })
+}
+
+/// Apply the file changes in `events` onto `target_root`.
+///
+/// Absolute paths recorded in the events are rebased from `source_root` (the workspace
+/// the conversation originally ran in) onto `target_root`.
+pub fn replay_events(
+    events: &[Event],
+    source_root: &Path,
+    target_root: &Path,
+) -> Result<ReplayReport, String> {
+    let mut report = ReplayReport {
+        untracked: events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Observation(o) if o.untracked_changes => Some(o.tool_call_id.clone()),
+                _ => None,
+            })
+            .collect(),
+        ..Default::default()
+    };
+
+    for change in file_changes(events) {
+        let relative = relative_path(&change.path, source_root)?;
+        let target = target_root.join(&relative);
+
+        let before = if target.is_file() {
+            Some(
+                fs::read_to_string(&target)
+                    .map_err(|e| format!("Failed to read '{}': {}", target.display(), e))?,
+            )
+        } else {
+            None
+        };
+
+        match change.apply(before.as_deref())? {
+            Some(content) => {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create directory: {}", e))?;
+                }
+                fs::write(&target, content)
+                    .map_err(|e| format!("Failed to write '{}': {}", target.display(), e))?;
+            }
+            None => {
+                fs::remove_file(&target)
+                    .map_err(|e| format!("Failed to delete '{}': {}", target.display(), e))?;
+            }
+        }
+
+        report.applied += 1;
+        if !report.files.contains(&relative) {
+            report.files.push(relative);
+        }
+    }
+
+    Ok(report)
+}
+
+fn relative_path(recorded: &str, source_root: &Path) -> Result<PathBuf, String> {
+    let path = Path::new(recorded);
+    let relative = if path.is_absolute() {
+        path.strip_prefix(source_root).map_err(|_| {
+            format!(
+                "Recorded path '{}' is outside of the source workspace '{}'",
+                recorded,
+                source_root.display()
+            )
+        })?
+    } else {
+        path
+    };
+
+    if relative
+        .components()
+        .any(|c| matches!(c, Component::ParentDir | Component::RootDir))
+    {
+        return Err(format!("Refusing to replay change to '{}'", recorded));
+    }
+    Ok(relative.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::ObservationEvent;
+    use tempfile::TempDir;
+
+    fn observation(changes: Vec<FileChange>) -> Event {
+        Event::Observation(ObservationEvent {
+            source: "runtime".to_string(),
+            tool_name: "write_file".to_string(),
+            tool_call_id: "call_1".to_string(),
+            content: String::new(),
+            file_changes: changes,
+            untracked_changes: false,
+            attachments: Vec::new(),
+            injection: None,
+        })
+    }
+
+    #[test]
+    fn test_replay_reconstructs_workspace() {
+        let source = TempDir::new().unwrap();
+        let target = TempDir::new().unwrap();
+        fs::write(target.path().join("keep.txt"), "v1\n").unwrap();
+
+        let abs = source
+            .path()
+            .join("src/new.rs")
+            .to_string_lossy()
+            .to_string();
+        let events = vec![
+            observation(vec![
+                FileChange::capture("keep.txt", Some("v1\n"), Some("v2\n")).unwrap(),
+            ]),
+            observation(vec![
+                FileChange::capture(&abs, None, Some("fn main() {}\n")).unwrap(),
+            ]),
+        ];
+
+        let report = replay_events(&events, source.path(), target.path()).unwrap();
+        assert_eq!(report.applied, 2);
+        assert!(report.untracked.is_empty());
+        assert_eq!(
+            fs::read_to_string(target.path().join("keep.txt")).unwrap(),
+            "v2\n"
+        );
+        assert_eq!(
+            fs::read_to_string(target.path().join("src/new.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+
+        // Replaying again fails because the base no longer matches.
+        assert!(replay_events(&events, source.path(), target.path()).is_err());
+    }
+
+    #[test]
+    fn test_replay_reports_untracked_changes() {
+        let target = TempDir::new().unwrap();
+        let Event::Observation(mut shell) = observation(Vec::new()) else {
+            unreachable!()
+        };
+        shell.tool_name = "cmd".to_string();
+        shell.tool_call_id = "call_2".to_string();
+        shell.untracked_changes = true;
+        let events = vec![
+            observation(vec![
+                FileChange::capture("a.txt", None, Some("a\n")).unwrap(),
+            ]),
+            Event::Observation(shell),
+        ];
+
+        let report = replay_events(&events, Path::new("/src"), target.path()).unwrap();
+        assert_eq!(report.applied, 1);
+        assert_eq!(report.untracked, vec!["call_2".to_string()]);
+    }
+
+    #[test]
+    fn test_replay_rejects_escaping_paths() {
+        let target = TempDir::new().unwrap();
+        let events = vec![observation(vec![
+            FileChange::capture("../evil.txt", None, Some("x")).unwrap(),
+        ])];
+        assert!(replay_events(&events, Path::new("/src"), target.path()).is_err());
+    }
+}
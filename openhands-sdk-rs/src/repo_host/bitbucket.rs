@@ -0,0 +1,216 @@
+use super::{
+    GitHubUser, HostKind, Issue, IssueComment, NewPullRequest, PullRequest, RepoHost, RepoId,
+    send_json,
+};
+use async_trait::async_trait;
+use serde::Deserialize;
+
+const DEFAULT_API_BASE: &str = "https://api.bitbucket.org/2.0";
+
+#[derive(Clone, Debug)]
+pub struct BitbucketConfig {
+    /// Bitbucket username; empty for anonymous access.
+    pub username: String,
+    pub app_password: String,
+    pub api_base: String,
+}
+
+impl BitbucketConfig {
+    /// Read `BITBUCKET_USERNAME`, `BITBUCKET_APP_PASSWORD` and optionally `BITBUCKET_API_URL`.
+    pub fn from_env() -> Self {
+        Self {
+            username: std::env::var("BITBUCKET_USERNAME").unwrap_or_default(),
+            app_password: std::env::var("BITBUCKET_APP_PASSWORD").unwrap_or_default(),
+            api_base: std::env::var("BITBUCKET_API_URL")
+                .unwrap_or_else(|_| DEFAULT_API_BASE.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Links {
+    html: Link,
+}
+
+#[derive(Deserialize)]
+struct Link {
+    href: String,
+}
+
+#[derive(Deserialize)]
+struct Content {
+    raw: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BitbucketIssue {
+    id: u64,
+    title: String,
+    content: Option<Content>,
+    links: Links,
+}
+
+#[derive(Deserialize)]
+struct BitbucketUser {
+    #[serde(default)]
+    nickname: Option<String>,
+    display_name: String,
+}
+
+#[derive(Deserialize)]
+struct BitbucketComment {
+    user: BitbucketUser,
+    content: Option<Content>,
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    values: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct BitbucketPullRequest {
+    id: u64,
+    links: Links,
+}
+
+pub struct BitbucketClient {
+    pub config: BitbucketConfig,
+    client: reqwest::Client,
+}
+
+impl BitbucketClient {
+    pub fn new(config: BitbucketConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        repo: &RepoId,
+        path: &str,
+    ) -> reqwest::RequestBuilder {
+        let builder = self.client.request(
+            method,
+            format!(
+                "{}/repositories/{}/{}{}",
+                self.config.api_base, repo.owner, repo.name, path
+            ),
+        );
+        if self.config.username.is_empty() {
+            builder
+        } else {
+            builder.basic_auth(&self.config.username, Some(&self.config.app_password))
+        }
+    }
+}
+
+#[async_trait]
+impl RepoHost for BitbucketClient {
+    fn kind(&self) -> HostKind {
+        HostKind::Bitbucket
+    }
+
+    async fn get_issue(&self, repo: &RepoId, number: u64) -> Result<Issue, String> {
+        let issue: BitbucketIssue = send_json(
+            "Bitbucket",
+            self.request(reqwest::Method::GET, repo, &format!("/issues/{}", number)),
+        )
+        .await?;
+        Ok(Issue {
+            number: issue.id,
+            title: issue.title,
+            body: issue.content.and_then(|c| c.raw),
+            html_url: issue.links.html.href,
+        })
+    }
+
+    async fn list_issue_comments(
+        &self,
+        repo: &RepoId,
+        number: u64,
+    ) -> Result<Vec<IssueComment>, String> {
+        let page: Page<BitbucketComment> = send_json(
+            "Bitbucket",
+            self.request(
+                reqwest::Method::GET,
+                repo,
+                &format!("/issues/{}/comments?pagelen=100", number),
+            ),
+        )
+        .await?;
+        Ok(page
+            .values
+            .into_iter()
+            .map(|c| IssueComment {
+                user: GitHubUser {
+                    login: c.user.nickname.unwrap_or(c.user.display_name),
+                },
+                body: c.content.and_then(|c| c.raw),
+            })
+            .collect())
+    }
+
+    async fn create_branch(&self, repo: &RepoId, branch: &str, from: &str) -> Result<(), String> {
+        // Bitbucket resolves a branch name given as the target hash.
+        let _: serde_json::Value = send_json(
+            "Bitbucket",
+            self.request(reqwest::Method::POST, repo, "/refs/branches")
+                .json(&serde_json::json!({
+                    "name": branch,
+                    "target": { "hash": from },
+                })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn open_pull_request(
+        &self,
+        repo: &RepoId,
+        pr: &NewPullRequest,
+    ) -> Result<PullRequest, String> {
+        let created: BitbucketPullRequest = send_json(
+            "Bitbucket",
+            self.request(reqwest::Method::POST, repo, "/pullrequests")
+                .json(&serde_json::json!({
+                    "title": pr.title,
+                    "description": pr.body,
+                    "source": { "branch": { "name": pr.head } },
+                    "destination": { "branch": { "name": pr.base } },
+                    "draft": pr.draft,
+                })),
+        )
+        .await?;
+        Ok(PullRequest {
+            number: created.id,
+            html_url: created.links.html.href,
+        })
+    }
+
+    async fn comment(&self, repo: &RepoId, number: u64, body: &str) -> Result<(), String> {
+        let _: serde_json::Value = send_json(
+            "Bitbucket",
+            self.request(
+                reqwest::Method::POST,
+                repo,
+                &format!("/issues/{}/comments", number),
+            )
+            .json(&serde_json::json!({ "content": { "raw": body } })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    fn authenticated_remote(&self, repo: &RepoId) -> String {
+        format!(
+            "https://{}:{}@bitbucket.org/{}.git",
+            self.config.username,
+            self.config.app_password,
+            repo.full_name()
+        )
+    }
+}
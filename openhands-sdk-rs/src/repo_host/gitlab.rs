@@ -0,0 +1,256 @@
+use super::{
+    GitHubUser, HostKind, Issue, IssueComment, NewPullRequest, PullRequest, RepoHost, RepoId,
+    send_json,
+};
+use async_trait::async_trait;
+use serde::Deserialize;
+
+#[derive(Clone, Debug)]
+pub struct GitLabConfig {
+    /// Personal/project access token; empty for anonymous access.
+    pub token: String,
+    /// Instance URL, e.g. `https://gitlab.com`.
+    pub base_url: String,
+}
+
+const DEFAULT_URL: &str = "https://gitlab.com";
+
+impl GitLabConfig {
+    /// Read `GITLAB_TOKEN` and `GITLAB_URL` (default `https://gitlab.com`) for a repository
+    /// on `host`. The token is only sent to the instance at `GITLAB_URL`; repositories on
+    /// other GitLab hosts are accessed anonymously at `https://<host>`.
+    pub fn from_env(host: &str) -> Self {
+        Self::for_host(
+            host,
+            std::env::var("GITLAB_TOKEN").unwrap_or_default(),
+            std::env::var("GITLAB_URL").unwrap_or_else(|_| DEFAULT_URL.to_string()),
+        )
+    }
+
+    fn for_host(host: &str, token: String, instance_url: String) -> Self {
+        let instance_host = instance_url
+            .split_once("://")
+            .map_or(instance_url.as_str(), |(_, rest)| rest)
+            .split('/')
+            .next()
+            .unwrap_or_default();
+        if instance_host.eq_ignore_ascii_case(host) {
+            Self {
+                token,
+                base_url: instance_url,
+            }
+        } else {
+            Self {
+                token: String::new(),
+                base_url: format!("https://{}", host),
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GitLabIssue {
+    iid: u64,
+    title: String,
+    description: Option<String>,
+    web_url: String,
+}
+
+#[derive(Deserialize)]
+struct GitLabNote {
+    author: GitLabUser,
+    body: Option<String>,
+    #[serde(default)]
+    system: bool,
+}
+
+#[derive(Deserialize)]
+struct GitLabUser {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct GitLabMergeRequest {
+    iid: u64,
+    web_url: String,
+}
+
+pub struct GitLabClient {
+    pub config: GitLabConfig,
+    client: reqwest::Client,
+}
+
+impl GitLabClient {
+    pub fn new(config: GitLabConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        repo: &RepoId,
+        path: &str,
+    ) -> reqwest::RequestBuilder {
+        // The project is addressed by its URL-encoded full path.
+        let project = repo.full_name().replace('/', "%2F");
+        let builder = self.client.request(
+            method,
+            format!(
+                "{}/api/v4/projects/{}{}",
+                self.config.base_url.trim_end_matches('/'),
+                project,
+                path
+            ),
+        );
+        if self.config.token.is_empty() {
+            builder
+        } else {
+            builder.header("PRIVATE-TOKEN", &self.config.token)
+        }
+    }
+}
+
+#[async_trait]
+impl RepoHost for GitLabClient {
+    fn kind(&self) -> HostKind {
+        HostKind::GitLab
+    }
+
+    async fn get_issue(&self, repo: &RepoId, number: u64) -> Result<Issue, String> {
+        let issue: GitLabIssue = send_json(
+            "GitLab",
+            self.request(reqwest::Method::GET, repo, &format!("/issues/{}", number)),
+        )
+        .await?;
+        Ok(Issue {
+            number: issue.iid,
+            title: issue.title,
+            body: issue.description,
+            html_url: issue.web_url,
+        })
+    }
+
+    async fn list_issue_comments(
+        &self,
+        repo: &RepoId,
+        number: u64,
+    ) -> Result<Vec<IssueComment>, String> {
+        let notes: Vec<GitLabNote> = send_json(
+            "GitLab",
+            self.request(
+                reqwest::Method::GET,
+                repo,
+                &format!("/issues/{}/notes?sort=asc&per_page=100", number),
+            ),
+        )
+        .await?;
+        // System notes record label/assignee changes rather than discussion.
+        Ok(notes
+            .into_iter()
+            .filter(|n| !n.system)
+            .map(|n| IssueComment {
+                user: GitHubUser {
+                    login: n.author.username,
+                },
+                body: n.body,
+            })
+            .collect())
+    }
+
+    async fn create_branch(&self, repo: &RepoId, branch: &str, from: &str) -> Result<(), String> {
+        let _: serde_json::Value = send_json(
+            "GitLab",
+            self.request(reqwest::Method::POST, repo, "/repository/branches")
+                .json(&serde_json::json!({ "branch": branch, "ref": from })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn open_pull_request(
+        &self,
+        repo: &RepoId,
+        pr: &NewPullRequest,
+    ) -> Result<PullRequest, String> {
+        let title = if pr.draft {
+            format!("Draft: {}", pr.title)
+        } else {
+            pr.title.clone()
+        };
+        let mr: GitLabMergeRequest = send_json(
+            "GitLab",
+            self.request(reqwest::Method::POST, repo, "/merge_requests")
+                .json(&serde_json::json!({
+                    "source_branch": pr.head,
+                    "target_branch": pr.base,
+                    "title": title,
+                    "description": pr.body,
+                })),
+        )
+        .await?;
+        Ok(PullRequest {
+            number: mr.iid,
+            html_url: mr.web_url,
+        })
+    }
+
+    async fn comment(&self, repo: &RepoId, number: u64, body: &str) -> Result<(), String> {
+        let _: serde_json::Value = send_json(
+            "GitLab",
+            self.request(
+                reqwest::Method::POST,
+                repo,
+                &format!("/issues/{}/notes", number),
+            )
+            .json(&serde_json::json!({ "body": body })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    fn authenticated_remote(&self, repo: &RepoId) -> String {
+        let (scheme, host) = self
+            .config
+            .base_url
+            .trim_end_matches('/')
+            .split_once("://")
+            .unwrap_or(("https", &repo.host));
+        format!(
+            "{}://oauth2:{}@{}/{}.git",
+            scheme,
+            self.config.token,
+            host,
+            repo.full_name()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_only_for_configured_instance() {
+        let config = GitLabConfig::for_host(
+            "gitlab.example.com",
+            "secret".to_string(),
+            "https://gitlab.example.com/".to_string(),
+        );
+        assert_eq!(config.token, "secret");
+        assert_eq!(config.base_url, "https://gitlab.example.com/");
+
+        let config =
+            GitLabConfig::for_host("gitlab.com", "secret".to_string(), DEFAULT_URL.to_string());
+        assert_eq!(config.token, "secret");
+
+        for host in ["evil-gitlab.example.net", "gitlab.com.evil.net"] {
+            let config =
+                GitLabConfig::for_host(host, "secret".to_string(), DEFAULT_URL.to_string());
+            assert!(config.token.is_empty(), "{}", host);
+            assert_eq!(config.base_url, format!("https://{}", host));
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Git hosting providers behind a common interface.
+//!
+//! Workflows such as issue resolution only need a handful of operations from the hosting
+//! service. [`RepoHost`] exposes those for GitHub, GitLab and Bitbucket, so the same workflow
+//! runs unchanged against any of them. Issues, comments and pull/merge requests are mapped
+//! onto the GitHub types in [`crate::github`].
+
+pub mod bitbucket;
+pub mod gitlab;
+
+use crate::github::{GitHubClient, GitHubConfig, is_valid_name};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+pub use crate::github::{GitHubUser, Issue, IssueComment, NewPullRequest, PullRequest};
+pub use bitbucket::{BitbucketClient, BitbucketConfig};
+pub use gitlab::{GitLabClient, GitLabConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostKind {
+    GitHub,
+    GitLab,
+    Bitbucket,
+}
+
+/// A repository on a hosting service. `owner` is the GitHub owner, GitLab namespace
+/// (possibly nested, e.g. `group/subgroup`) or Bitbucket workspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoId {
+    pub host: String,
+    pub owner: String,
+    pub name: String,
+}
+
+impl RepoId {
+    pub fn full_name(&self) -> String {
+        format!("{}/{}", self.owner, self.name)
+    }
+}
+
+#[async_trait]
+pub trait RepoHost: Send + Sync {
+    fn kind(&self) -> HostKind;
+
+    async fn get_issue(&self, repo: &RepoId, number: u64) -> Result<Issue, String>;
+
+    async fn list_issue_comments(
+        &self,
+        repo: &RepoId,
+        number: u64,
+    ) -> Result<Vec<IssueComment>, String>;
+
+    /// Create `branch` on the host, starting at `from`.
+    async fn create_branch(&self, repo: &RepoId, branch: &str, from: &str) -> Result<(), String>;
+
+    /// Open a pull request (merge request on GitLab).
+    async fn open_pull_request(
+        &self,
+        repo: &RepoId,
+        pr: &NewPullRequest,
+    ) -> Result<PullRequest, String>;
+
+    /// Comment on an issue.
+    async fn comment(&self, repo: &RepoId, number: u64, body: &str) -> Result<(), String>;
+
+    /// Remote URL with credentials embedded, for pushing without a credential helper.
+    fn authenticated_remote(&self, repo: &RepoId) -> String;
+}
+
+#[async_trait]
+impl RepoHost for GitHubClient {
+    fn kind(&self) -> HostKind {
+        HostKind::GitHub
+    }
+
+    async fn get_issue(&self, repo: &RepoId, number: u64) -> Result<Issue, String> {
+        GitHubClient::get_issue(self, &repo.owner, &repo.name, number).await
+    }
+
+    async fn list_issue_comments(
+        &self,
+        repo: &RepoId,
+        number: u64,
+    ) -> Result<Vec<IssueComment>, String> {
+        GitHubClient::list_issue_comments(self, &repo.owner, &repo.name, number).await
+    }
+
+    async fn create_branch(&self, repo: &RepoId, branch: &str, from: &str) -> Result<(), String> {
+        GitHubClient::create_branch(self, &repo.owner, &repo.name, branch, from).await
+    }
+
+    async fn open_pull_request(
+        &self,
+        repo: &RepoId,
+        pr: &NewPullRequest,
+    ) -> Result<PullRequest, String> {
+        self.create_pull_request(&repo.owner, &repo.name, pr).await
+    }
+
+    async fn comment(&self, repo: &RepoId, number: u64, body: &str) -> Result<(), String> {
+        self.create_issue_comment(&repo.owner, &repo.name, number, body)
+            .await
+    }
+
+    fn authenticated_remote(&self, repo: &RepoId) -> String {
+        GitHubClient::authenticated_remote(self, &repo.owner, &repo.name)
+    }
+}
+
+/// Parse an HTTPS or SSH repository URL into the host kind and repository.
+///
+/// `github.com` and `bitbucket.org` are recognized by name; any host containing `gitlab`
+/// (gitlab.com or a self-managed instance) is treated as GitLab.
+pub fn parse_repo_url(url: &str) -> Option<(HostKind, RepoId)> {
+    let url = url.trim();
+    let (host, path) = if let Some(rest) = url.strip_prefix("git@") {
+        rest.split_once(':')?
+    } else {
+        let rest = url.split_once("://")?.1;
+        let (authority, path) = rest.split_once('/')?;
+        let host = authority.rsplit('@').next()?;
+        (host, path)
+    };
+
+    let kind = match host {
+        "github.com" => HostKind::GitHub,
+        "bitbucket.org" => HostKind::Bitbucket,
+        h if h.contains("gitlab") => HostKind::GitLab,
+        _ => return None,
+    };
+
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+    let (owner, name) = path.rsplit_once('/')?;
+    if !owner.split('/').all(is_valid_name) || !is_valid_name(name) {
+        return None;
+    }
+    // Only GitLab supports nested namespaces.
+    if owner.contains('/') && kind != HostKind::GitLab {
+        return None;
+    }
+
+    Some((
+        kind,
+        RepoId {
+            host: host.to_string(),
+            owner: owner.to_string(),
+            name: name.to_string(),
+        },
+    ))
+}
+
+/// Build a client for the host of `url`, using credentials from the environment
+/// (`GITHUB_TOKEN`, `GITLAB_TOKEN`, `BITBUCKET_USERNAME`/`BITBUCKET_APP_PASSWORD`).
+/// `GITLAB_TOKEN` is only used for the instance at `GITLAB_URL`; see
+/// [`GitLabConfig::from_env`]. Without credentials the client can still read public
+/// repositories.
+pub fn host_for_url(url: &str) -> Result<(Arc<dyn RepoHost>, RepoId), String> {
+    let (kind, repo) =
+        parse_repo_url(url).ok_or_else(|| format!("Unsupported repository URL: {}", url))?;
+
+    let host: Arc<dyn RepoHost> = match kind {
+        HostKind::GitHub => Arc::new(
+            GitHubConfig::from_env()
+                .map(GitHubClient::new)
+                .unwrap_or_else(GitHubClient::anonymous),
+        ),
+        HostKind::GitLab => Arc::new(GitLabClient::new(GitLabConfig::from_env(&repo.host))),
+        HostKind::Bitbucket => Arc::new(BitbucketClient::new(BitbucketConfig::from_env())),
+    };
+    Ok((host, repo))
+}
+
+/// Turn a non-success response into an error mentioning the provider.
+pub(crate) async fn send_json<T: serde::de::DeserializeOwned>(
+    provider: &str,
+    builder: reqwest::RequestBuilder,
+) -> Result<T, String> {
+    let res = builder.send().await.map_err(|e| e.to_string())?;
+
+    if !res.status().is_success() {
+        let status = res.status();
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(format!(
+            "{} returned error {}: {}",
+            provider, status, error_text
+        ));
+    }
+
+    res.json().await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_repo_url() {
+        let (kind, repo) = parse_repo_url("https://github.com/octo/hello.git").unwrap();
+        assert_eq!(kind, HostKind::GitHub);
+        assert_eq!(repo.full_name(), "octo/hello");
+
+        let (kind, repo) = parse_repo_url("git@gitlab.example.com:group/sub/project.git").unwrap();
+        assert_eq!(kind, HostKind::GitLab);
+        assert_eq!(repo.host, "gitlab.example.com");
+        assert_eq!(repo.owner, "group/sub");
+        assert_eq!(repo.name, "project");
+
+        let (kind, repo) = parse_repo_url("https://user@bitbucket.org/team/app").unwrap();
+        assert_eq!(kind, HostKind::Bitbucket);
+        assert_eq!(repo.full_name(), "team/app");
+
+        assert!(parse_repo_url("https://example.com/a/b").is_none());
+        assert!(parse_repo_url("https://github.com/a/b/c").is_none());
+        assert!(parse_repo_url("https://gitlab.com/group/../project").is_none());
+        assert!(parse_repo_url("https://bitbucket.org/team/app%2F..").is_none());
+    }
+}
@@ -0,0 +1,320 @@
+//! Resolve an issue end to end: fetch the issue, clone the repository, let the agent
+//! work on it, and report the resulting diff. Works with any [`RepoHost`].
+
+use crate::agent::Agent;
+use crate::agent::tools::default_tools;
+use crate::events::{Event, MessageEvent};
+use crate::llm::{LLM, LLMConfig, TokenUsage};
+use crate::notifications::{DiffStats, NotificationTarget, Notifier, RunNotification};
+use crate::repo_host::{Issue, IssueComment, RepoHost, RepoId, parse_repo_url};
+use crate::runtime::LocalRuntime;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ResolveIssueRequest {
+    pub repo_url: String,
+    pub issue_number: u64,
+    /// Extra instructions, e.g. from the comment that triggered the run.
+    #[serde(default)]
+    pub instructions: Option<String>,
+    /// Notification targets for this run, in addition to the resolver's own.
+    #[serde(default)]
+    pub notify: Vec<NotificationTarget>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ResolveIssueResult {
+    pub issue_title: String,
+    pub issue_url: String,
+    /// Where the repository was cloned.
+    pub repo_dir: PathBuf,
+    /// The agent's final message.
+    pub summary: String,
+    /// Unified diff of the agent's changes, including new files.
+    pub diff: String,
+    /// Tokens the agent used.
+    pub usage: TokenUsage,
+}
+
+pub struct IssueResolver {
+    host: Arc<dyn RepoHost>,
+    llm_config: LLMConfig,
+    workspace_dir: PathBuf,
+    notifier: Notifier,
+    /// Tokens used by the resolver's runs, failed ones included.
+    usage: Mutex<TokenUsage>,
+}
+
+impl IssueResolver {
+    pub fn new(host: Arc<dyn RepoHost>, llm_config: LLMConfig, workspace_dir: PathBuf) -> Self {
+        Self {
+            host,
+            llm_config,
+            workspace_dir,
+            notifier: Notifier::default(),
+            usage: Mutex::new(TokenUsage::default()),
+        }
+    }
+
+    /// Tokens the agent used in the runs so far, including runs that failed.
+    pub fn usage(&self) -> TokenUsage {
+        *self.usage.lock().unwrap()
+    }
+
+    /// Report run start and completion to these notification targets.
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    pub async fn resolve(
+        &self,
+        request: &ResolveIssueRequest,
+    ) -> Result<ResolveIssueResult, String> {
+        let repo = match parse_repo_url(&request.repo_url) {
+            Some((kind, repo)) if kind == self.host.kind() => repo,
+            _ => {
+                return Err(format!(
+                    "Not a {:?} repository URL: {}",
+                    self.host.kind(),
+                    request.repo_url
+                ));
+            }
+        };
+
+        let notifier = self.notifier.clone().with_targets(request.notify.clone());
+        let run = format!("{}#{}", repo.full_name(), request.issue_number);
+        notifier
+            .notify(&RunNotification::Started {
+                run: run.clone(),
+                task: request
+                    .instructions
+                    .clone()
+                    .unwrap_or_else(|| format!("Resolve issue {}", run)),
+            })
+            .await;
+
+        let result = self.run(request, &repo).await;
+        notifier
+            .notify(&match &result {
+                Ok(r) => RunNotification::Finished {
+                    run,
+                    success: true,
+                    summary: r.summary.clone(),
+                    diff_stats: Some(DiffStats::from_unified_diff(&r.diff)),
+                },
+                Err(e) => RunNotification::Finished {
+                    run,
+                    success: false,
+                    summary: e.clone(),
+                    diff_stats: None,
+                },
+            })
+            .await;
+        result
+    }
+
+    async fn run(
+        &self,
+        request: &ResolveIssueRequest,
+        repo: &RepoId,
+    ) -> Result<ResolveIssueResult, String> {
+        let issue = self.host.get_issue(repo, request.issue_number).await?;
+        let comments = self
+            .host
+            .list_issue_comments(repo, request.issue_number)
+            .await?;
+
+        let repo_dir = self
+            .workspace_dir
+            .join(format!("{}-issue-{}", repo.name, request.issue_number));
+        if repo_dir.exists() {
+            return Err(format!(
+                "Clone target '{}' already exists",
+                repo_dir.display()
+            ));
+        }
+        std::fs::create_dir_all(&self.workspace_dir)
+            .map_err(|e| format!("Failed to create workspace: {}", e))?;
+        git(
+            &self.workspace_dir,
+            &[
+                "clone",
+                "--depth",
+                "1",
+                &request.repo_url,
+                &repo_dir.to_string_lossy(),
+            ],
+        )?;
+
+        let mut runtime = LocalRuntime::new(default_tools(repo_dir.clone()));
+        let agent = Agent::new(
+            LLM::new(self.llm_config.clone()),
+            "You resolve issues by editing the repository checked out in your workspace."
+                .to_string(),
+        );
+
+        let history = vec![Event::Message(MessageEvent {
+            source: "user".to_string(),
+            content: build_issue_prompt(
+                &issue,
+                &comments,
+                request.instructions.as_deref(),
+                &repo_dir,
+            ),
+            file_refs: Vec::new(),
+        })];
+        let result = agent.step_events(&history, &mut runtime).await;
+        let usage = agent.usage();
+        {
+            let mut total = self.usage.lock().unwrap();
+            total.prompt_tokens += usage.prompt_tokens;
+            total.completion_tokens += usage.completion_tokens;
+        }
+        let events = result.map_err(|e| e.to_string())?;
+        let summary = match events.last() {
+            Some(Event::Message(m)) => m.content.clone(),
+            _ => String::new(),
+        };
+
+        Ok(ResolveIssueResult {
+            issue_title: issue.title,
+            issue_url: issue.html_url,
+            diff: git_diff(&repo_dir)?,
+            repo_dir,
+            summary,
+            usage,
+        })
+    }
+}
+
+/// Build the task prompt from the issue and its discussion.
+pub fn build_issue_prompt(
+    issue: &Issue,
+    comments: &[IssueComment],
+    instructions: Option<&str>,
+    repo_dir: &Path,
+) -> String {
+    let mut prompt = format!(
+        "Please fix the following issue for the repository in {}.\n\
+        An environment has been set up for you to start working. You may assume all necessary tools are installed.\n\n\
+        # Issue #{}: {}\n\n{}\n",
+        repo_dir.display(),
+        issue.number,
+        issue.title,
+        issue.body.as_deref().unwrap_or("").trim()
+    );
+
+    if !comments.is_empty() {
+        prompt.push_str("\n# Comments\n");
+        for comment in comments {
+            prompt.push_str(&format!(
+                "\n@{}:\n{}\n",
+                comment.user.login,
+                comment.body.as_deref().unwrap_or("").trim()
+            ));
+        }
+    }
+
+    if let Some(instructions) = instructions {
+        prompt.push_str(&format!("\n# Request\n\n{}\n", instructions.trim()));
+    }
+
+    prompt.push_str(
+        "\nIMPORTANT: Make minimal changes to non-test files. Do not commit your changes; \
+        they will be collected as a diff when you finish. When you are done, reply with a short \
+        summary of the fix.",
+    );
+    prompt
+}
+
+/// Diff of the working tree against HEAD, including untracked files.
+pub fn git_diff(repo_dir: &Path) -> Result<String, String> {
+    git(repo_dir, &["add", "--all", "--intent-to-add"])?;
+    git(repo_dir, &["diff", "HEAD"])
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo_host::GitHubUser;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_build_issue_prompt() {
+        let issue = Issue {
+            number: 7,
+            title: "Crash on empty input".to_string(),
+            body: Some("Running with `''` panics.".to_string()),
+            html_url: "https://github.com/octo/hello/issues/7".to_string(),
+        };
+        let comments = vec![IssueComment {
+            user: GitHubUser {
+                login: "maintainer".to_string(),
+            },
+            body: Some("Probably in parse().".to_string()),
+        }];
+
+        let prompt = build_issue_prompt(
+            &issue,
+            &comments,
+            Some("@openhands please add a regression test"),
+            Path::new("/workspace/hello"),
+        );
+        assert!(prompt.contains("/workspace/hello"));
+        assert!(prompt.contains("# Issue #7: Crash on empty input"));
+        assert!(prompt.contains("@maintainer:\nProbably in parse()."));
+        assert!(prompt.contains("# Request\n\n@openhands please add a regression test"));
+    }
+
+    #[test]
+    fn test_git_diff_includes_new_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        git(dir, &["init", "-q"]).unwrap();
+        fs::write(dir.join("a.txt"), "one\n").unwrap();
+        git(dir, &["add", "a.txt"]).unwrap();
+        git(
+            dir,
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@localhost",
+                "commit",
+                "-qm",
+                "init",
+            ],
+        )
+        .unwrap();
+
+        fs::write(dir.join("a.txt"), "two\n").unwrap();
+        fs::write(dir.join("b.txt"), "new\n").unwrap();
+
+        let diff = git_diff(dir).unwrap();
+        assert!(diff.contains("+two"));
+        assert!(diff.contains("b.txt"));
+        assert!(diff.contains("+new"));
+    }
+}
@@ -0,0 +1,289 @@
+//! Keeping event directories from growing forever.
+//!
+//! Events are stored one file each. A [`RetentionPolicy`] says how many of them, how old
+//! and how large, stay as files; [`EventArchive::compact`] moves the others, oldest first,
+//! into a gzip-compressed bundle in the directory's `archive/` subdirectory. Readers use
+//! [`EventArchive::entries`] to see archived events next to the live ones.
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// The subdirectory of an event directory holding its bundles.
+pub const ARCHIVE_DIR: &str = "archive";
+
+/// `[event_retention]`. Without any limit nothing is archived.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RetentionPolicy {
+    /// Events older than this many days are archived.
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+    /// Only the newest this many events are kept as files.
+    #[serde(default)]
+    pub max_events: Option<usize>,
+    /// Only the newest events up to this many bytes in all are kept as files.
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    /// Seconds between compactions.
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_interval_secs() -> u64 {
+    3600
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_age_days: None,
+            max_events: None,
+            max_bytes: None,
+            interval_secs: default_interval_secs(),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_age_days.is_none() && self.max_events.is_none() && self.max_bytes.is_none()
+    }
+}
+
+/// A line of a bundle: an event file's name and content.
+#[derive(Serialize, Deserialize)]
+struct ArchivedEvent {
+    name: String,
+    content: String,
+}
+
+/// What a compaction did.
+#[derive(Debug, Default, PartialEq)]
+pub struct CompactionReport {
+    pub archived: usize,
+    /// The bundle written, if any event was archived.
+    pub bundle: Option<PathBuf>,
+}
+
+struct EventFile {
+    path: PathBuf,
+    name: String,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// The archive of an event directory whose file names sort oldest first.
+pub struct EventArchive {
+    dir: PathBuf,
+}
+
+impl EventArchive {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn archive_dir(&self) -> PathBuf {
+        self.dir.join(ARCHIVE_DIR)
+    }
+
+    /// The directory's event files, oldest first.
+    fn live_files(&self) -> Result<Vec<EventFile>, String> {
+        let entries = fs::read_dir(&self.dir)
+            .map_err(|e| format!("Failed to read {}: {}", self.dir.display(), e))?;
+        let mut files: Vec<EventFile> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                if !metadata.is_file() {
+                    return None;
+                }
+                Some(EventFile {
+                    path: entry.path(),
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    size: metadata.len(),
+                    modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                })
+            })
+            .collect();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    /// The indices of `files` that `policy` no longer keeps.
+    fn expired(files: &[EventFile], policy: &RetentionPolicy, now: SystemTime) -> BTreeSet<usize> {
+        let mut expired = BTreeSet::new();
+        if let Some(days) = policy.max_age_days {
+            let cutoff = now
+                .checked_sub(Duration::from_secs(days * 24 * 60 * 60))
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            expired.extend((0..files.len()).filter(|&i| files[i].modified < cutoff));
+        }
+        if let Some(max_events) = policy.max_events {
+            expired.extend(0..files.len().saturating_sub(max_events));
+        }
+        if let Some(max_bytes) = policy.max_bytes {
+            let mut kept = 0;
+            for (i, file) in files.iter().enumerate().rev() {
+                kept += file.size;
+                if kept > max_bytes {
+                    expired.extend(0..=i);
+                    break;
+                }
+            }
+        }
+        expired
+    }
+
+    /// Move the events `policy` no longer keeps into a new bundle. The files are only
+    /// removed once the bundle is complete.
+    pub fn compact(
+        &self,
+        policy: &RetentionPolicy,
+        now: SystemTime,
+    ) -> Result<CompactionReport, String> {
+        let files = self.live_files()?;
+        let expired = Self::expired(&files, policy, now);
+        if expired.is_empty() {
+            return Ok(CompactionReport::default());
+        }
+        let expired: Vec<&EventFile> = expired.into_iter().map(|i| &files[i]).collect();
+
+        let archive_dir = self.archive_dir();
+        fs::create_dir_all(&archive_dir)
+            .map_err(|e| format!("Failed to create {}: {}", archive_dir.display(), e))?;
+        let bundle = archive_dir.join(format!(
+            "{}-{}.jsonl.gz",
+            expired[0].name,
+            expired[expired.len() - 1].name
+        ));
+        let partial = bundle.with_extension("partial");
+        let write = || -> std::io::Result<()> {
+            let mut encoder = GzEncoder::new(fs::File::create(&partial)?, Compression::default());
+            for file in &expired {
+                let line = serde_json::to_string(&ArchivedEvent {
+                    name: file.name.clone(),
+                    content: fs::read_to_string(&file.path)?,
+                })?;
+                writeln!(encoder, "{}", line)?;
+            }
+            encoder.finish()?.sync_all()?;
+            fs::rename(&partial, &bundle)
+        };
+        if let Err(e) = write() {
+            let _ = fs::remove_file(&partial);
+            return Err(format!("Failed to write {}: {}", bundle.display(), e));
+        }
+
+        for file in &expired {
+            if let Err(e) = fs::remove_file(&file.path) {
+                tracing::warn!("Failed to remove archived {}: {}", file.path.display(), e);
+            }
+        }
+        Ok(CompactionReport {
+            archived: expired.len(),
+            bundle: Some(bundle),
+        })
+    }
+
+    /// The archived events as `(file name, content)`, oldest first. Unreadable bundles
+    /// are skipped with a warning.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let Ok(dir) = fs::read_dir(self.archive_dir()) else {
+            return Vec::new();
+        };
+        let mut bundles: Vec<PathBuf> = dir
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.to_string_lossy().ends_with(".jsonl.gz"))
+            .collect();
+        bundles.sort();
+
+        let mut entries = Vec::new();
+        for bundle in bundles {
+            match read_bundle(&bundle) {
+                Ok(events) => entries.extend(events),
+                Err(e) => tracing::warn!("Skipping unreadable bundle {}: {}", bundle.display(), e),
+            }
+        }
+        entries
+    }
+}
+
+fn read_bundle(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut events = Vec::new();
+    for line in BufReader::new(GzDecoder::new(file)).lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let event: ArchivedEvent = serde_json::from_str(&line).map_err(|e| e.to_string())?;
+        events.push((event.name, event.content));
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_compaction_archives_oldest_events() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..5 {
+            fs::write(
+                dir.path().join(format!("2025010100000{}_Event", i)),
+                "x".repeat(10),
+            )
+            .unwrap();
+        }
+        let archive = EventArchive::new(dir.path());
+        let now = SystemTime::now();
+
+        assert_eq!(
+            archive.compact(&RetentionPolicy::default(), now).unwrap(),
+            CompactionReport::default()
+        );
+
+        let policy = RetentionPolicy {
+            max_events: Some(4),
+            max_bytes: Some(25),
+            ..Default::default()
+        };
+        let report = archive.compact(&policy, now).unwrap();
+        assert_eq!(report.archived, 3);
+        let names: Vec<String> = archive
+            .entries()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "20250101000000_Event",
+                "20250101000001_Event",
+                "20250101000002_Event"
+            ]
+        );
+        assert_eq!(archive.live_files().unwrap().len(), 2);
+
+        // Everything is past a zero-day age limit; the new bundle sorts after the first.
+        let policy = RetentionPolicy {
+            max_age_days: Some(0),
+            ..Default::default()
+        };
+        let later = now + Duration::from_secs(1);
+        assert_eq!(archive.compact(&policy, later).unwrap().archived, 2);
+        assert!(archive.live_files().unwrap().is_empty());
+        let entries = archive.entries();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(
+            entries[4],
+            ("20250101000004_Event".to_string(), "x".repeat(10))
+        );
+    }
+}
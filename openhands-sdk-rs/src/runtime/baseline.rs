@@ -0,0 +1,268 @@
+//! The files of a workspace as they were when a conversation started.
+//!
+//! [`BaselineRuntime`] wraps a conversation's runtime. Before a tool call runs, it snapshots
+//! the files the call may change (see [`Tool::affected_paths`]) that no earlier call has
+//! touched, so the [`Baseline`] holds each file as it was before the conversation changed
+//! it. [`Baseline::changes`] compares that with the files now, giving a unified diff per
+//! modified file like the review pane of a pull request.
+
+use super::ports::PortRegistry;
+use super::{Runtime, RuntimeStatus};
+use crate::agent::tools::{Tool, ToolOutput};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::{ChangeTag, TextDiff};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffStatus {
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// A file the conversation changed, compared with its state at the start.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileDiff {
+    /// Relative to the workspace when the file is in it.
+    pub path: String,
+    pub status: DiffStatus,
+    pub additions: usize,
+    pub deletions: usize,
+    /// Unified diff with `a/` and `b/` headers, `/dev/null` for a missing side.
+    pub diff: String,
+}
+
+impl FileDiff {
+    /// The diff from `before` to `after`, or `None` if the file is unchanged.
+    fn new(path: String, before: Option<&str>, after: Option<&str>) -> Option<Self> {
+        if before == after {
+            return None;
+        }
+        let status = match (before, after) {
+            (None, _) => DiffStatus::Added,
+            (_, None) => DiffStatus::Deleted,
+            _ => DiffStatus::Modified,
+        };
+        let old = before.unwrap_or("");
+        let new = after.unwrap_or("");
+        let text_diff = TextDiff::from_lines(old, new);
+
+        let (mut additions, mut deletions) = (0, 0);
+        for change in text_diff.iter_all_changes() {
+            match change.tag() {
+                ChangeTag::Insert => additions += 1,
+                ChangeTag::Delete => deletions += 1,
+                ChangeTag::Equal => {}
+            }
+        }
+        let old_header = match before {
+            Some(_) => format!("a/{}", path),
+            None => "/dev/null".to_string(),
+        };
+        let new_header = match after {
+            Some(_) => format!("b/{}", path),
+            None => "/dev/null".to_string(),
+        };
+        let diff = text_diff
+            .unified_diff()
+            .header(&old_header, &new_header)
+            .to_string();
+
+        Some(Self {
+            path,
+            status,
+            additions,
+            deletions,
+            diff,
+        })
+    }
+}
+
+/// Content of the files a conversation touched, from before it first touched them (`None`
+/// for files that did not exist).
+#[derive(Clone, Default)]
+pub struct Baseline {
+    files: Arc<Mutex<BTreeMap<PathBuf, Option<String>>>>,
+}
+
+impl Baseline {
+    /// Snapshot those of `paths` that are not in the baseline yet.
+    fn record(&self, paths: Vec<PathBuf>) {
+        let mut files = self.files.lock().unwrap();
+        for path in paths {
+            files
+                .entry(path)
+                .or_insert_with_key(|p| std::fs::read_to_string(p).ok());
+        }
+    }
+
+    /// The files that differ from the baseline now, by path, with paths under
+    /// `workspace_dir` made relative to it.
+    pub fn changes(&self, workspace_dir: &Path) -> Vec<FileDiff> {
+        let files = self.files.lock().unwrap().clone();
+        let mut diffs: Vec<FileDiff> = files
+            .into_iter()
+            .filter_map(|(path, before)| {
+                let after = std::fs::read_to_string(&path).ok();
+                let display = path
+                    .strip_prefix(workspace_dir)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .to_string();
+                FileDiff::new(display, before.as_deref(), after.as_deref())
+            })
+            .collect();
+        diffs.sort_by(|a, b| a.path.cmp(&b.path));
+        diffs
+    }
+}
+
+/// A runtime that records the [`Baseline`] of the files its tool calls change.
+pub struct BaselineRuntime {
+    inner: Box<dyn Runtime + Send + Sync>,
+    baseline: Baseline,
+}
+
+impl BaselineRuntime {
+    pub fn new(inner: Box<dyn Runtime + Send + Sync>, baseline: Baseline) -> Self {
+        Self { inner, baseline }
+    }
+
+    fn record(&self, action: &str, args: &Value) {
+        if let Some(tool) = self.inner.tools().iter().find(|t| t.name() == action) {
+            self.baseline.record(tool.affected_paths(args));
+        }
+    }
+}
+
+#[async_trait]
+impl Runtime for BaselineRuntime {
+    fn tools(&self) -> &[Box<dyn Tool>] {
+        self.inner.tools()
+    }
+
+    async fn connect(&self) -> Result<(), String> {
+        self.inner.connect().await
+    }
+
+    async fn close(&self) -> Result<(), String> {
+        self.inner.close().await
+    }
+
+    async fn status(&self) -> RuntimeStatus {
+        self.inner.status().await
+    }
+
+    async fn execute(&self, action: &str, args: Value) -> Result<String, String> {
+        self.record(action, &args);
+        self.inner.execute(action, args).await
+    }
+
+    async fn execute_with_attachments(
+        &self,
+        action: &str,
+        args: Value,
+    ) -> Result<ToolOutput, String> {
+        self.record(action, &args);
+        self.inner.execute_with_attachments(action, args).await
+    }
+
+    fn ports(&self) -> Option<PortRegistry> {
+        self.inner.ports()
+    }
+
+    async fn read_file(&self, path: &Path) -> Option<String> {
+        self.inner.read_file(path).await
+    }
+
+    async fn copy_in(&self, host_path: &Path, runtime_path: &str) -> Result<(), String> {
+        self.inner.copy_in(host_path, runtime_path).await
+    }
+
+    async fn copy_out(&self, runtime_path: &str, host_path: &Path) -> Result<(), String> {
+        self.inner.copy_out(runtime_path, host_path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::tools::{FileEditorTool, FileWriteTool};
+    use crate::runtime::LocalRuntime;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_changes_against_baseline() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path();
+        std::fs::write(workspace.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        std::fs::write(workspace.join("same.txt"), "x\n").unwrap();
+        let baseline = Baseline::default();
+        let runtime = BaselineRuntime::new(
+            Box::new(LocalRuntime::new(vec![
+                Box::new(FileEditorTool::new(workspace.to_path_buf())),
+                Box::new(FileWriteTool::default()),
+            ])),
+            baseline.clone(),
+        );
+
+        let replace = |line: usize, content: &str| {
+            json!({
+                "operation": "replace",
+                "path": "a.txt",
+                "start_line": line,
+                "end_line": line,
+                "content": content,
+            })
+        };
+        runtime
+            .execute("file_editor", replace(2, "TWO"))
+            .await
+            .unwrap();
+        // A second edit of the same file still diffs against the original.
+        runtime
+            .execute("file_editor", replace(3, "THREE"))
+            .await
+            .unwrap();
+        let new_file = workspace.join("new.txt");
+        runtime
+            .execute(
+                "write_file",
+                json!({ "path": new_file, "content": "hello\n" }),
+            )
+            .await
+            .unwrap();
+        runtime
+            .execute(
+                "write_file",
+                json!({ "path": workspace.join("same.txt"), "content": "x\n" }),
+            )
+            .await
+            .unwrap();
+
+        let changes = baseline.changes(workspace);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].path, "a.txt");
+        assert_eq!(changes[0].status, DiffStatus::Modified);
+        assert_eq!((changes[0].additions, changes[0].deletions), (2, 2));
+        assert!(changes[0].diff.starts_with("--- a/a.txt\n+++ b/a.txt\n"));
+        assert!(changes[0].diff.contains("-two\n-three\n+TWO\n+THREE\n"));
+        assert_eq!(changes[1].path, "new.txt");
+        assert_eq!(changes[1].status, DiffStatus::Added);
+        assert!(
+            changes[1]
+                .diff
+                .starts_with("--- /dev/null\n+++ b/new.txt\n")
+        );
+
+        std::fs::remove_file(&new_file).unwrap();
+        std::fs::write(workspace.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        assert!(baseline.changes(workspace).is_empty());
+    }
+}
@@ -1,27 +1,103 @@
-use crate::models::{BashCommand, BashEvent, BashEventPage, BashOutput, ExecuteBashRequest};
+use crate::bus::{BusEvent, BusPayload, EventBus};
+use crate::models::{
+    BashCommand, BashEvent, BashEventPage, BashEventQuery, BashOutput, ExecuteBashRequest,
+    OutputChunk, OutputStream, SCHEMA_VERSION,
+};
+use crate::redact::redactor;
+use crate::retention::{CompactionReport, EventArchive, RetentionPolicy};
+use crate::runtime::env::ToolEnv;
 use chrono::Utc;
 use glob::glob;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::time::Duration;
-use tokio::io::AsyncReadExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::{AbortHandle, JoinHandle};
 use tokio::time::timeout;
 use uuid::Uuid;
 
+/// Commands running at once by default; further commands wait for one to finish.
+pub const DEFAULT_MAX_RUNNING: usize = 32;
+
+/// The subdirectory events are written to before they are renamed into place.
+const PENDING_DIR: &str = ".pending";
+
+/// The subdirectory unreadable event files are moved to.
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// The background tasks of a service's commands.
+struct CommandTasks {
+    /// The supervisor of each unfinished command, and the task running it.
+    running: Mutex<HashMap<Uuid, (JoinHandle<()>, AbortHandle)>>,
+    slots: Arc<Semaphore>,
+    /// Set by [`BashEventService::shutdown`]; no commands start afterwards.
+    closed: AtomicBool,
+}
+
+impl CommandTasks {
+    fn new(max_running: usize) -> Self {
+        Self {
+            running: Mutex::new(HashMap::new()),
+            slots: Arc::new(Semaphore::new(max_running.max(1))),
+            closed: AtomicBool::new(false),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct BashEventService {
     pub bash_events_dir: PathBuf,
+    bus: Option<Arc<EventBus>>,
+    env: ToolEnv,
+    tasks: Arc<CommandTasks>,
+    fsync: bool,
 }
 
 impl BashEventService {
-    pub fn new(bash_events_dir: PathBuf) -> Self {
-        fs::create_dir_all(&bash_events_dir).expect("Failed to create bash events dir");
-        Self { bash_events_dir }
+    pub fn new(bash_events_dir: PathBuf) -> Result<Self, String> {
+        let pending = bash_events_dir.join(PENDING_DIR);
+        fs::create_dir_all(&pending)
+            .map_err(|e| format!("Failed to create {}: {}", pending.display(), e))?;
+        Ok(Self {
+            bash_events_dir,
+            bus: None,
+            env: ToolEnv::default(),
+            tasks: Arc::new(CommandTasks::new(DEFAULT_MAX_RUNNING)),
+            fsync: false,
+        })
+    }
+
+    /// Flush every event to disk before it counts as written, so it survives a power
+    /// loss, at the cost of slower writes.
+    pub fn with_fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
     }
 
-    fn save_event(&self, event: &BashEvent) {
+    /// Run at most `max_running` commands at once.
+    pub fn with_max_running(mut self, max_running: usize) -> Self {
+        self.tasks = Arc::new(CommandTasks::new(max_running));
+        self
+    }
+
+    /// Also publish commands and their output on `bus`.
+    pub fn with_bus(mut self, bus: Arc<EventBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Write `event` with secrets redacted, and publish it.
+    fn save_event(&self, event: &BashEvent) -> Result<(), String> {
+        let event = &redactor().redact_bash_event(event);
         let timestamp_str = event.timestamp().format("%Y%m%d%H%M%S");
         let kind = match event {
             BashEvent::BashCommand(_) => "BashCommand",
@@ -39,19 +115,111 @@ impl BashEventService {
             ),
         };
 
+        self.write_event(&filename, event)?;
+        if let Some(bus) = &self.bus {
+            bus.publish(BusEvent::new(None, BusPayload::Bash(event.clone())));
+        }
+        Ok(())
+    }
+
+    /// Write `event` to `filename` all at once: a crash leaves either the old file or the
+    /// new one, never half of it.
+    fn write_event(&self, filename: &str, event: &BashEvent) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(event)
+            .map_err(|e| format!("Failed to serialize event {}: {}", filename, e))?;
+        let pending = self.bash_events_dir.join(PENDING_DIR).join(filename);
         let path = self.bash_events_dir.join(filename);
-        let json = serde_json::to_string_pretty(event).expect("Failed to serialize event");
-        fs::write(path, json).expect("Failed to write event file");
+        let write = || -> std::io::Result<()> {
+            let mut file = fs::File::create(&pending)?;
+            file.write_all(json.as_bytes())?;
+            if self.fsync {
+                file.sync_all()?;
+            }
+            fs::rename(&pending, &path)?;
+            if self.fsync {
+                fs::File::open(&self.bash_events_dir)?.sync_all()?;
+            }
+            Ok(())
+        };
+        write().map_err(|e| {
+            let _ = fs::remove_file(&pending);
+            format!("Failed to write event {}: {}", path.display(), e)
+        })
     }
 
-    fn load_event(path: PathBuf) -> Option<BashEvent> {
-        let content = fs::read_to_string(path).ok()?;
-        serde_json::from_str(&content).ok()
+    /// Parse an archived event; archives are not rewritten, so older ones are upgraded on
+    /// every read.
+    fn parse_archived(name: &str, content: &str) -> Option<BashEvent> {
+        let value = serde_json::from_str(content).ok()?;
+        match BashEvent::from_json(value) {
+            Ok((event, _)) => Some(event),
+            Err(e) => {
+                tracing::warn!("Skipping unreadable archived event {}: {}", name, e);
+                None
+            }
+        }
     }
 
-    pub fn start_bash_command(&self, req: ExecuteBashRequest) -> BashCommand {
+    /// Load an event file, rewriting it in the current schema if it was written by an older
+    /// version. A file that does not parse is moved to the quarantine directory.
+    fn load_event(&self, path: PathBuf) -> Option<BashEvent> {
+        if !path.is_file() {
+            return None;
+        }
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                tracing::warn!("Failed to read event {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let parsed = serde_json::from_str(&content)
+            .map_err(|e| e.to_string())
+            .and_then(BashEvent::from_json);
+        let (event, migrated) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                self.quarantine(&path, &e);
+                return None;
+            }
+        };
+        if migrated {
+            let filename = path.file_name().unwrap_or_default().to_string_lossy();
+            if let Err(e) = self.write_event(&filename, &event) {
+                tracing::warn!("Failed to upgrade event {}: {}", path.display(), e);
+            }
+        }
+        Some(event)
+    }
+
+    /// Move an unreadable event file out of the way, so it is reported once instead of on
+    /// every search.
+    fn quarantine(&self, path: &Path, error: &str) {
+        let dir = self.bash_events_dir.join(QUARANTINE_DIR);
+        let moved = fs::create_dir_all(&dir)
+            .and_then(|_| fs::rename(path, dir.join(path.file_name().unwrap_or_default())));
+        match moved {
+            Ok(()) => tracing::warn!(
+                "Quarantined unreadable event {} in {}: {}",
+                path.display(),
+                dir.display(),
+                error
+            ),
+            Err(e) => tracing::warn!(
+                "Skipping unreadable event {} ({}); failed to quarantine it: {}",
+                path.display(),
+                error,
+                e
+            ),
+        }
+    }
+
+    /// Record `req` and start running it in the background. Fails if the command cannot
+    /// be recorded.
+    pub fn start_bash_command(&self, req: ExecuteBashRequest) -> Result<BashCommand, String> {
         let command_id = Uuid::new_v4();
         let bash_command = BashCommand {
+            schema_version: SCHEMA_VERSION,
             id: command_id,
             timestamp: Utc::now(),
             command: req.command.clone(),
@@ -60,94 +228,164 @@ impl BashEventService {
         };
 
         // Save initial command event synchronously
-        self.save_event(&BashEvent::BashCommand(bash_command.clone()));
+        self.save_event(&BashEvent::BashCommand(bash_command.clone()))?;
 
+        if self.tasks.closed.load(Ordering::SeqCst) {
+            self.save_failure(command_id, "The server is shutting down".to_string());
+            return Ok(bash_command);
+        }
         let service = self.clone();
         let cmd_clone = bash_command.clone();
-
-        // Spawn background task
-        tokio::spawn(async move {
+        let slots = self.tasks.slots.clone();
+        self.supervise(command_id, async move {
+            // Queued until a slot is free.
+            let Ok(_slot) = slots.acquire_owned().await else {
+                return;
+            };
             service.execute_bash_command_background(cmd_clone).await;
         });
 
-        bash_command
+        Ok(bash_command)
+    }
+
+    /// Run `command` in the background as the task of `command_id`. A panic or a
+    /// cancellation is recorded as the command's failed output instead of going unnoticed.
+    fn supervise(&self, command_id: Uuid, command: impl Future<Output = ()> + Send + 'static) {
+        // Held while spawning, so the supervisor cannot finish before it is registered.
+        let mut running = self.tasks.running.lock().unwrap();
+        let task = tokio::spawn(command);
+        let abort = task.abort_handle();
+        let service = self.clone();
+        let supervisor = tokio::spawn(async move {
+            if let Err(e) = task.await {
+                let reason = if e.is_panic() {
+                    format!("Command task panicked: {}", panic_message(e.into_panic()))
+                } else {
+                    "Command cancelled by server shutdown".to_string()
+                };
+                service.save_failure(command_id, reason);
+            }
+            service.tasks.running.lock().unwrap().remove(&command_id);
+        });
+        running.insert(command_id, (supervisor, abort));
+    }
+
+    /// Commands started and not finished yet.
+    pub fn running_commands(&self) -> usize {
+        self.tasks.running.lock().unwrap().len()
+    }
+
+    /// Stop starting commands and wait up to `grace` for the running ones, then cancel
+    /// the rest (killing their processes) and record them as failed.
+    pub async fn shutdown(&self, grace: Duration) {
+        self.tasks.closed.store(true, Ordering::SeqCst);
+        let deadline = tokio::time::Instant::now() + grace;
+        while self.running_commands() > 0 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let supervisors: Vec<JoinHandle<()>> = {
+            let mut running = self.tasks.running.lock().unwrap();
+            running
+                .drain()
+                .map(|(_, (supervisor, command))| {
+                    command.abort();
+                    supervisor
+                })
+                .collect()
+        };
+        for supervisor in supervisors {
+            let _ = supervisor.await;
+        }
+    }
+
+    /// Record a failed output of `command_id` with `reason` as stderr.
+    fn save_failure(&self, command_id: Uuid, reason: String) {
+        self.save_output(Self::failure(command_id, reason));
+    }
+
+    fn failure(command_id: Uuid, reason: String) -> BashOutput {
+        BashOutput {
+            schema_version: SCHEMA_VERSION,
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            command_id,
+            order: 0,
+            exit_code: Some(-1),
+            stdout: None,
+            stderr: Some(reason.clone()),
+            merged_output: Some(reason),
+            chunks: Vec::new(),
+            timed_out: false,
+        }
+    }
+
+    /// Record the output of a background command; nobody is waiting for a result, so a
+    /// failure is logged.
+    fn save_output(&self, out: BashOutput) {
+        let command_id = out.command_id;
+        if let Err(e) = self.save_event(&BashEvent::BashOutput(out)) {
+            tracing::error!("Lost the output of command {}: {}", command_id, e);
+        }
     }
 
     async fn execute_bash_command_background(&self, command: BashCommand) {
-        let mut cmd = Command::new("bash");
+        let mut cmd = Command::from(self.env.command("bash"));
         cmd.arg("-c").arg(&command.command);
         if let Some(cwd) = &command.cwd {
             cmd.current_dir(cwd);
         }
+        // Commands get no input. The server's own stdin may be the MCP protocol stream, and
+        // bash sources ~/.bashrc when its stdin is a socket.
+        cmd.stdin(Stdio::null());
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
+        // A cancelled command does not leave its process behind.
+        cmd.kill_on_drop(true);
 
         let timeout_duration = Duration::from_secs(command.timeout);
 
         let mut child = match cmd.spawn() {
             Ok(c) => c,
             Err(e) => {
-                let out = BashOutput {
-                    id: Uuid::new_v4(),
-                    timestamp: Utc::now(),
-                    command_id: command.id,
-                    order: 0,
-                    exit_code: Some(-1),
-                    stdout: None,
-                    stderr: Some(format!("Failed to spawn: {}", e)),
-                };
-                self.save_event(&BashEvent::BashOutput(out));
+                self.save_failure(command.id, format!("Failed to spawn: {}", e));
                 return;
             }
         };
 
         let wait_output = async {
-            let mut stdout = String::new();
-            let mut stderr = String::new();
-            if let Some(mut out) = child.stdout.take() {
-                let _ = out.read_to_string(&mut stdout).await;
-            }
-            if let Some(mut err) = child.stderr.take() {
-                let _ = err.read_to_string(&mut stderr).await;
-            }
+            let captured = capture_output(child.stdout.take(), child.stderr.take()).await;
             let status = child.wait().await;
-            (status, stdout, stderr)
+            (status, captured)
         };
 
         match timeout(timeout_duration, wait_output).await {
-            Ok((status_res, stdout, stderr)) => {
+            Ok((status_res, captured)) => {
                 let exit_code = status_res.map(|s| s.code().unwrap_or(-1)).unwrap_or(-1);
+                let non_empty = |text: String| (!text.is_empty()).then_some(text);
                 let out = BashOutput {
+                    schema_version: SCHEMA_VERSION,
                     id: Uuid::new_v4(),
                     timestamp: Utc::now(),
                     command_id: command.id,
                     order: 0,
                     exit_code: Some(exit_code),
-                    stdout: if stdout.is_empty() {
-                        None
-                    } else {
-                        Some(stdout)
-                    },
-                    stderr: if stderr.is_empty() {
-                        None
-                    } else {
-                        Some(stderr)
-                    },
+                    stdout: non_empty(captured.stdout),
+                    stderr: non_empty(captured.stderr),
+                    merged_output: non_empty(captured.merged),
+                    chunks: captured.chunks,
+                    timed_out: false,
                 };
-                self.save_event(&BashEvent::BashOutput(out));
+                self.save_output(out);
             }
             Err(_) => {
                 let _ = child.kill().await;
-                let out = BashOutput {
-                    id: Uuid::new_v4(),
-                    timestamp: Utc::now(),
-                    command_id: command.id,
-                    order: 0,
-                    exit_code: Some(-1),
-                    stdout: None,
-                    stderr: Some("Command timed out".to_string()),
-                };
-                self.save_event(&BashEvent::BashOutput(out));
+                self.save_output(BashOutput {
+                    timed_out: true,
+                    ..Self::failure(
+                        command.id,
+                        format!("Command timed out after {}s", command.timeout),
+                    )
+                });
             }
         }
     }
@@ -162,39 +400,552 @@ impl BashEventService {
             .collect();
 
         if let Some(path) = paths.first() {
-            Self::load_event(path.clone())
-        } else {
-            None
+            return self.load_event(path.clone());
         }
+        let suffix = format!("_{}", id.simple());
+        self.archive()
+            .entries()
+            .into_iter()
+            .find(|(name, _)| name.ends_with(&suffix))
+            .and_then(|(name, content)| Self::parse_archived(&name, &content))
     }
 
-    pub fn search_bash_events(&self, command_id: Option<Uuid>) -> BashEventPage {
-        let mut events = Vec::new();
-        let full_pattern = self.bash_events_dir.join("*");
+    /// Events matching `query`, including archived ones, oldest first. Fails if
+    /// `command_regex` is not a valid regular expression.
+    pub fn search_bash_events(&self, query: &BashEventQuery) -> Result<BashEventPage, String> {
+        let regex = query
+            .command_regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| format!("Invalid command_regex: {}", e))?;
+        // A command's own event and its outputs are the files with its id in their name.
+        let pattern = match query.command_id {
+            Some(cid) => format!("*_{}*", cid.simple()),
+            None => "*".to_string(),
+        };
+        let full_pattern = self.bash_events_dir.join(pattern);
 
+        let mut events = Vec::new();
         if let Ok(entries) = glob(full_pattern.to_str().unwrap_or("")) {
-            for entry in entries.filter_map(Result::ok) {
-                if let Some(event) = Self::load_event(entry) {
-                    let match_cmd = match command_id {
-                        Some(cid) => match &event {
-                            BashEvent::BashCommand(c) => c.id == cid,
-                            BashEvent::BashOutput(o) => o.command_id == cid,
-                        },
-                        None => true,
-                    };
-
-                    if match_cmd {
-                        events.push(event);
+            events.extend(
+                entries
+                    .filter_map(Result::ok)
+                    .filter_map(|path| self.load_event(path)),
+            );
+        }
+        let archived = self
+            .archive()
+            .entries()
+            .into_iter()
+            .filter_map(|(name, content)| Self::parse_archived(&name, &content));
+        events.extend(archived);
+        if let Some(cid) = query.command_id {
+            events.retain(|event| event.command_id() == cid);
+        }
+
+        // The filters on whole commands need each command's text and exit codes.
+        let filters_commands =
+            query.command.is_some() || regex.is_some() || query.exit_code.is_some() || query.failed;
+        if filters_commands {
+            let mut texts = HashMap::new();
+            let mut exit_codes: HashMap<Uuid, Vec<i32>> = HashMap::new();
+            for event in &events {
+                match event {
+                    BashEvent::BashCommand(c) => {
+                        texts.insert(c.id, c.command.clone());
                     }
+                    BashEvent::BashOutput(o) => exit_codes
+                        .entry(o.command_id)
+                        .or_default()
+                        .extend(o.exit_code),
                 }
             }
+            let matching: HashSet<Uuid> = texts
+                .into_iter()
+                .filter(|(id, text)| {
+                    let codes = exit_codes.get(id).map(Vec::as_slice).unwrap_or_default();
+                    query
+                        .command
+                        .as_ref()
+                        .is_none_or(|part| text.contains(part))
+                        && regex.as_ref().is_none_or(|regex| regex.is_match(text))
+                        && query.exit_code.is_none_or(|code| codes.contains(&code))
+                        && (!query.failed || codes.iter().any(|&code| code != 0))
+                })
+                .map(|(id, _)| id)
+                .collect();
+            events.retain(|event| matching.contains(&event.command_id()));
         }
 
+        let since = match query.within_secs {
+            Some(secs) => {
+                let window_start = Utc::now() - chrono::Duration::seconds(secs as i64);
+                Some(
+                    query
+                        .since
+                        .map_or(window_start, |since| since.max(window_start)),
+                )
+            }
+            None => query.since,
+        };
+        events.retain(|event| {
+            since.is_none_or(|since| event.timestamp() >= since)
+                && query.until.is_none_or(|until| event.timestamp() <= until)
+                && query.kind.is_none_or(|kind| event.kind() == kind)
+        });
+
         events.sort_by_key(|e| e.timestamp());
 
-        BashEventPage {
+        Ok(BashEventPage {
             items: events,
             next_page_id: None,
+        })
+    }
+
+    fn archive(&self) -> EventArchive {
+        EventArchive::new(&self.bash_events_dir)
+    }
+
+    /// Archive the events `policy` no longer keeps as files; they stay searchable.
+    pub fn compact(&self, policy: &RetentionPolicy) -> Result<CompactionReport, String> {
+        self.archive().compact(policy, SystemTime::now())
+    }
+
+    /// Compact every `policy.interval_secs`, forever.
+    pub async fn run_retention(self, policy: RetentionPolicy) {
+        let mut interval = tokio::time::interval(Duration::from_secs(policy.interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            let service = self.clone();
+            let policy = policy.clone();
+            match tokio::task::spawn_blocking(move || service.compact(&policy)).await {
+                Ok(Ok(report)) if report.archived > 0 => {
+                    tracing::info!("Archived {} bash events", report.archived)
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => tracing::warn!("Bash event compaction failed: {}", e),
+                Err(e) => tracing::warn!("Bash event compaction failed: {}", e),
+            }
+        }
+    }
+}
+
+/// The message a task panicked with.
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .unwrap_or_else(|| "unknown panic".to_string()),
+    }
+}
+
+/// A command's stdout and stderr, apart and merged in the order they arrived.
+#[derive(Default)]
+struct CapturedOutput {
+    stdout: String,
+    stderr: String,
+    merged: String,
+    chunks: Vec<OutputChunk>,
+}
+
+impl CapturedOutput {
+    fn push(&mut self, stream: OutputStream, line: &[u8]) {
+        if line.is_empty() {
+            return;
+        }
+        let text = String::from_utf8_lossy(line);
+        match stream {
+            OutputStream::Stdout => self.stdout.push_str(&text),
+            OutputStream::Stderr => self.stderr.push_str(&text),
+        }
+        self.merged.push_str(&text);
+        match self.chunks.last_mut() {
+            Some(chunk) if chunk.stream == stream => chunk.text.push_str(&text),
+            _ => self.chunks.push(OutputChunk {
+                stream,
+                timestamp: Utc::now(),
+                text: text.into_owned(),
+            }),
+        }
+    }
+}
+
+/// Read the next line of `reader` into `buf`, or never finish once it is gone.
+async fn read_line<R: AsyncRead + Unpin>(
+    reader: &mut Option<BufReader<R>>,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    match reader {
+        Some(reader) => reader.read_until(b'\n', buf).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Read stdout and stderr together line by line, so the merged output keeps the order in
+/// which the command wrote its lines. Lines written to both streams at nearly the same
+/// moment may still come out of order, as the pipes are read separately.
+async fn capture_output(
+    stdout: Option<impl AsyncRead + Unpin>,
+    stderr: Option<impl AsyncRead + Unpin>,
+) -> CapturedOutput {
+    let mut out = stdout.map(BufReader::new);
+    let mut err = stderr.map(BufReader::new);
+    // A line read only in part when the other stream wins stays in its buffer.
+    let (mut out_line, mut err_line) = (Vec::new(), Vec::new());
+    let mut captured = CapturedOutput::default();
+    loop {
+        tokio::select! {
+            read = read_line(&mut out, &mut out_line), if out.is_some() => {
+                if !matches!(read, Ok(n) if n > 0) {
+                    out = None;
+                }
+                captured.push(OutputStream::Stdout, &out_line);
+                out_line.clear();
+            }
+            read = read_line(&mut err, &mut err_line), if err.is_some() => {
+                if !matches!(read, Ok(n) if n > 0) {
+                    err = None;
+                }
+                captured.push(OutputStream::Stderr, &err_line);
+                err_line.clear();
+            }
+            else => break,
+        }
+    }
+    captured
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::BashEventKind;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_legacy_events_are_upgraded_on_load() {
+        let dir = TempDir::new().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf()).unwrap();
+        let command_id = Uuid::new_v4();
+        // Written before versioning, and without the fields that have defaults.
+        let legacy = format!(
+            r#"{{"kind": "BashCommand", "id": "{}", "timestamp": "2025-01-01T00:00:00Z", "command": "ls"}}"#,
+            command_id
+        );
+        let path = dir.path().join(format!(
+            "20250101000000_BashCommand_{}",
+            command_id.simple()
+        ));
+        fs::write(&path, legacy).unwrap();
+
+        let Some(BashEvent::BashCommand(command)) = service.get_bash_event(command_id) else {
+            panic!("legacy event was not loaded");
+        };
+        assert_eq!(command.schema_version, SCHEMA_VERSION);
+        assert_eq!(command.timeout, 300);
+        assert_eq!(command.cwd, None);
+
+        let rewritten: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(rewritten["schema_version"], SCHEMA_VERSION);
+        let (_, migrated) = BashEvent::from_json(rewritten).unwrap();
+        assert!(!migrated);
+    }
+
+    #[test]
+    fn test_corrupt_events_are_quarantined() {
+        let dir = TempDir::new().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf())
+            .unwrap()
+            .with_fsync(true);
+        let command = BashCommand {
+            schema_version: SCHEMA_VERSION,
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            command: "ls".to_string(),
+            cwd: None,
+            timeout: 300,
+        };
+        service
+            .save_event(&BashEvent::BashCommand(command.clone()))
+            .unwrap();
+        // What a crash in the middle of a plain write leaves behind.
+        let torn = format!("20250101000000_BashCommand_{}", Uuid::new_v4().simple());
+        fs::write(dir.path().join(&torn), r#"{"kind": "BashCom"#).unwrap();
+
+        let page = service
+            .search_bash_events(&BashEventQuery::default())
+            .unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert!(matches!(&page.items[0], BashEvent::BashCommand(c) if c.id == command.id));
+        assert!(!dir.path().join(&torn).exists());
+        assert!(dir.path().join(QUARANTINE_DIR).join(&torn).exists());
+        assert_eq!(
+            fs::read_dir(dir.path().join(PENDING_DIR)).unwrap().count(),
+            0
+        );
+    }
+
+    /// Record `command`, run `minutes_ago`, with an output of `exit_code`.
+    fn record(service: &BashEventService, command: &str, minutes_ago: i64, exit_code: i32) -> Uuid {
+        let id = Uuid::new_v4();
+        let timestamp = Utc::now() - chrono::Duration::minutes(minutes_ago);
+        service
+            .save_event(&BashEvent::BashCommand(BashCommand {
+                schema_version: SCHEMA_VERSION,
+                id,
+                timestamp,
+                command: command.to_string(),
+                cwd: None,
+                timeout: 300,
+            }))
+            .unwrap();
+        service
+            .save_event(&BashEvent::BashOutput(BashOutput {
+                schema_version: SCHEMA_VERSION,
+                id: Uuid::new_v4(),
+                timestamp: timestamp + chrono::Duration::seconds(1),
+                command_id: id,
+                order: 0,
+                exit_code: Some(exit_code),
+                stdout: None,
+                stderr: None,
+                merged_output: None,
+                chunks: Vec::new(),
+                timed_out: false,
+            }))
+            .unwrap();
+        id
+    }
+
+    #[test]
+    fn test_search_filters() {
+        let dir = TempDir::new().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf()).unwrap();
+        let test = record(&service, "cargo test", 20, 101);
+        let ls = record(&service, "ls", 1, 0);
+        let build = record(&service, "cargo build", 0, 0);
+        let search = |query: BashEventQuery| -> Vec<(Uuid, BashEventKind)> {
+            service
+                .search_bash_events(&query)
+                .unwrap()
+                .items
+                .iter()
+                .map(|event| (event.command_id(), event.kind()))
+                .collect()
+        };
+        let command = BashEventKind::BashCommand;
+        let output = BashEventKind::BashOutput;
+
+        assert_eq!(search(BashEventQuery::default()).len(), 6);
+        assert_eq!(
+            search(BashEventQuery {
+                failed: true,
+                ..Default::default()
+            }),
+            [(test, command), (test, output)]
+        );
+        assert_eq!(
+            search(BashEventQuery {
+                command: Some("cargo".to_string()),
+                exit_code: Some(0),
+                ..Default::default()
+            }),
+            [(build, command), (build, output)]
+        );
+        assert_eq!(
+            search(BashEventQuery {
+                command_regex: Some("^l".to_string()),
+                ..Default::default()
+            }),
+            [(ls, command), (ls, output)]
+        );
+        assert_eq!(
+            search(BashEventQuery {
+                within_secs: Some(600),
+                kind: Some(output),
+                ..Default::default()
+            }),
+            [(ls, output), (build, output)]
+        );
+        assert_eq!(
+            search(BashEventQuery {
+                until: Some(Utc::now() - chrono::Duration::minutes(10)),
+                ..BashEventQuery::for_command(test)
+            }),
+            [(test, command), (test, output)]
+        );
+        let invalid = BashEventQuery {
+            command_regex: Some("(".to_string()),
+            ..Default::default()
+        };
+        assert!(service.search_bash_events(&invalid).is_err());
+    }
+
+    /// The stderr of the output recorded for `command_id`, once there is one.
+    async fn wait_for_failure(service: &BashEventService, command_id: Uuid) -> String {
+        for _ in 0..100 {
+            let page = service
+                .search_bash_events(&BashEventQuery::for_command(command_id))
+                .unwrap();
+            if let Some(BashEvent::BashOutput(out)) = page.items.last() {
+                assert_eq!(out.exit_code, Some(-1));
+                return out.stderr.clone().unwrap_or_default();
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("no output was recorded");
+    }
+
+    #[tokio::test]
+    async fn test_command_tasks_are_supervised() {
+        let dir = TempDir::new().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf())
+            .unwrap()
+            .with_max_running(1);
+
+        let id = Uuid::new_v4();
+        service.supervise(id, async { panic!("boom") });
+        assert_eq!(
+            wait_for_failure(&service, id).await,
+            "Command task panicked: boom"
+        );
+
+        // The second command waits for the first one's slot; shutdown cancels both.
+        let request = ExecuteBashRequest {
+            command: "sleep 30".to_string(),
+            cwd: None,
+            timeout: None,
+        };
+        let first = service.start_bash_command(request.clone()).unwrap();
+        let second = service.start_bash_command(request.clone()).unwrap();
+        assert_eq!(service.running_commands(), 2);
+        service.shutdown(Duration::from_millis(100)).await;
+        assert_eq!(service.running_commands(), 0);
+        for command in [first, second] {
+            assert_eq!(
+                wait_for_failure(&service, command.id).await,
+                "Command cancelled by server shutdown"
+            );
+        }
+        let late = service.start_bash_command(request).unwrap();
+        assert_eq!(
+            wait_for_failure(&service, late.id).await,
+            "The server is shutting down"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_archived_events_stay_searchable() {
+        let dir = TempDir::new().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf()).unwrap();
+        let command = service
+            .start_bash_command(ExecuteBashRequest {
+                command: "echo archived".to_string(),
+                cwd: None,
+                timeout: None,
+            })
+            .unwrap();
+        while service.running_commands() > 0 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
         }
+
+        let policy = RetentionPolicy {
+            max_events: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(service.compact(&policy).unwrap().archived, 2);
+        let page = service
+            .search_bash_events(&BashEventQuery::for_command(command.id))
+            .unwrap();
+        assert_eq!(page.items.len(), 2);
+        let Some(BashEvent::BashOutput(output)) = page.items.last() else {
+            panic!("no output was archived");
+        };
+        assert_eq!(output.stdout.as_deref(), Some("archived\n"));
+        assert!(matches!(
+            service.get_bash_event(command.id),
+            Some(BashEvent::BashCommand(c)) if c.command == "echo archived"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_output_streams_are_kept_apart_and_merged() {
+        let dir = TempDir::new().unwrap();
+        let service = BashEventService::new(dir.path().to_path_buf()).unwrap();
+        let command = service
+            .start_bash_command(ExecuteBashRequest {
+                command: "echo one; echo two >&2; echo three".to_string(),
+                cwd: None,
+                timeout: None,
+            })
+            .unwrap();
+        while service.running_commands() > 0 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let page = service
+            .search_bash_events(&BashEventQuery::for_command(command.id))
+            .unwrap();
+        let Some(BashEvent::BashOutput(output)) = page.items.last() else {
+            panic!("no output was recorded");
+        };
+        assert_eq!(output.stdout.as_deref(), Some("one\nthree\n"));
+        assert_eq!(output.stderr.as_deref(), Some("two\n"));
+        let combined = output.combined_output();
+        let mut lines: Vec<&str> = combined.lines().collect();
+        lines.sort();
+        assert_eq!(lines, ["one", "three", "two"]);
+        let chunks: String = output.chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(Some(chunks), output.merged_output);
+    }
+
+    #[tokio::test]
+    async fn test_merged_output_keeps_write_order() {
+        use tokio::io::AsyncWriteExt;
+
+        let (mut stdout, stdout_reader) = tokio::io::duplex(64);
+        let (mut stderr, stderr_reader) = tokio::io::duplex(64);
+        let capture = tokio::spawn(capture_output(Some(stdout_reader), Some(stderr_reader)));
+        // On the test's single thread, yielding lets the capture read what was written.
+        let settle = || async {
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+        };
+        stdout
+            .write_all(b"Compiling a\nCompiling b\n")
+            .await
+            .unwrap();
+        settle().await;
+        stderr.write_all(b"error: b failed").await.unwrap();
+        settle().await;
+        stderr.write_all(b" to build\n").await.unwrap();
+        settle().await;
+        stdout.write_all(b"Compiling c\n").await.unwrap();
+        drop((stdout, stderr));
+
+        let captured = capture.await.unwrap();
+        assert_eq!(captured.stdout, "Compiling a\nCompiling b\nCompiling c\n");
+        assert_eq!(captured.stderr, "error: b failed to build\n");
+        assert_eq!(
+            captured.merged,
+            "Compiling a\nCompiling b\nerror: b failed to build\nCompiling c\n"
+        );
+        let chunks: Vec<(OutputStream, &str)> = captured
+            .chunks
+            .iter()
+            .map(|c| (c.stream, c.text.as_str()))
+            .collect();
+        assert_eq!(
+            chunks,
+            [
+                (OutputStream::Stdout, "Compiling a\nCompiling b\n"),
+                (OutputStream::Stderr, "error: b failed to build\n"),
+                (OutputStream::Stdout, "Compiling c\n"),
+            ]
+        );
     }
 }
@@ -0,0 +1,455 @@
+//! Support for projects that describe their development environment in
+//! `.devcontainer/devcontainer.json`.
+//!
+//! The project's image (from `image`, or built from `build`) becomes the base of a derived
+//! image that adds the agent server binary, so the agent works inside the project's own
+//! toolchain. `features` are installed with the `devcontainer` CLI when it is available.
+//! A `build`'s context and Dockerfile must be inside the workspace. Docker Compose based
+//! configurations are not supported.
+
+use crate::paths;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Path of the server binary in the agent server image.
+const SERVER_BINARY: &str = "/usr/local/bin/openhands-agent-server-rs";
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DevContainer {
+    pub image: Option<String>,
+    pub build: Option<DevContainerBuild>,
+    pub docker_compose_file: Option<Value>,
+    #[serde(default)]
+    pub features: serde_json::Map<String, Value>,
+    /// Ports as numbers, or `"host:port"` strings which are ignored.
+    #[serde(default)]
+    pub forward_ports: Vec<Value>,
+    pub post_create_command: Option<LifecycleCommand>,
+    #[serde(default)]
+    pub container_env: HashMap<String, String>,
+    pub remote_user: Option<String>,
+    pub workspace_folder: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DevContainerBuild {
+    pub dockerfile: Option<String>,
+    pub context: Option<String>,
+    #[serde(default)]
+    pub args: HashMap<String, String>,
+}
+
+/// A lifecycle command: a shell string, an argv array, or named commands run in parallel.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum LifecycleCommand {
+    Shell(String),
+    Exec(Vec<String>),
+    Parallel(HashMap<String, LifecycleCommand>),
+}
+
+impl LifecycleCommand {
+    /// Each command as an argv for `docker exec`.
+    pub fn argvs(&self) -> Vec<Vec<String>> {
+        match self {
+            LifecycleCommand::Shell(command) => {
+                vec![vec!["sh".to_string(), "-c".to_string(), command.clone()]]
+            }
+            LifecycleCommand::Exec(argv) => vec![argv.clone()],
+            LifecycleCommand::Parallel(commands) => {
+                let mut names: Vec<_> = commands.keys().collect();
+                names.sort();
+                names
+                    .into_iter()
+                    .flat_map(|name| commands[name].argvs())
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Where a devcontainer configuration was found and what it says.
+#[derive(Debug, Clone)]
+pub struct DevContainerConfig {
+    pub path: PathBuf,
+    pub workspace_dir: PathBuf,
+    pub config: DevContainer,
+    content_hash: String,
+}
+
+impl DevContainerConfig {
+    /// Look for `.devcontainer/devcontainer.json` or `.devcontainer.json` in `workspace_dir`.
+    pub fn find(workspace_dir: &Path) -> Option<PathBuf> {
+        [
+            workspace_dir
+                .join(".devcontainer")
+                .join("devcontainer.json"),
+            workspace_dir.join(".devcontainer.json"),
+        ]
+        .into_iter()
+        .find(|p| p.is_file())
+    }
+
+    pub fn load(workspace_dir: &Path, path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let config: DevContainer = serde_json::from_str(&strip_jsonc(&content))
+            .map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+        if config.docker_compose_file.is_some() {
+            return Err("Docker Compose devcontainers are not supported".to_string());
+        }
+        if config.image.is_none() && config.build.is_none() {
+            return Err(format!("{} has neither image nor build", path.display()));
+        }
+        let mut loaded = Self {
+            path: path.to_path_buf(),
+            workspace_dir: workspace_dir.to_path_buf(),
+            config,
+            content_hash: String::new(),
+        };
+        // The built image is reused while neither the configuration nor its Dockerfile change.
+        let mut hashed = content.into_bytes();
+        if let Some((_, dockerfile)) = loaded.build_paths()? {
+            hashed.extend(std::fs::read(dockerfile).unwrap_or_default());
+        }
+        loaded.content_hash = hex_digest(&hashed);
+        Ok(loaded)
+    }
+
+    /// The context and Dockerfile of `build`, refusing ones outside the workspace.
+    fn build_paths(&self) -> Result<Option<(PathBuf, PathBuf)>, String> {
+        let Some(build) = &self.config.build else {
+            return Ok(None);
+        };
+        let config_dir = self
+            .path
+            .parent()
+            .and_then(|dir| dir.strip_prefix(&self.workspace_dir).ok())
+            .unwrap_or(Path::new(""));
+        let confined = |path: &str| {
+            let path = paths::confine(
+                &self.workspace_dir,
+                &config_dir.join(path).to_string_lossy(),
+            )?;
+            // `confine` does not follow the last component, so check where it leads.
+            if let (Ok(target), Ok(root)) = (path.canonicalize(), self.workspace_dir.canonicalize())
+                && !target.starts_with(root)
+            {
+                return Err(format!("{} is outside the workspace", path.display()));
+            }
+            Ok(path)
+        };
+        Ok(Some((
+            confined(build.context.as_deref().unwrap_or("."))?,
+            confined(build.dockerfile.as_deref().unwrap_or("Dockerfile"))?,
+        )))
+    }
+
+    /// Directory the workspace is mounted at in the container.
+    pub fn container_workspace(&self) -> String {
+        self.config.workspace_folder.clone().unwrap_or_else(|| {
+            let name = self
+                .workspace_dir
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "workspace".to_string());
+            format!("/workspaces/{}", name)
+        })
+    }
+
+    /// Container ports from `forwardPorts`.
+    pub fn forward_ports(&self) -> Vec<u16> {
+        self.config
+            .forward_ports
+            .iter()
+            .filter_map(|p| p.as_u64())
+            .filter_map(|p| u16::try_from(p).ok())
+            .collect()
+    }
+
+    /// Build (or reuse) the project image with the agent server from `server_image` added,
+    /// returning its tag.
+    pub fn build_image(&self, server_image: &str) -> Result<String, String> {
+        // A new server image gets a new derived image.
+        let server_id = image_id(server_image).unwrap_or_else(|| server_image.to_string());
+        let hash = hex_digest(format!("{}\n{}", self.content_hash, server_id).as_bytes());
+        let tag = format!("openhands-devcontainer:{}", &hash[..16]);
+        if image_exists(&tag) {
+            return Ok(tag);
+        }
+
+        let base = self.build_base_image(&format!("{}-base", tag))?;
+        let dockerfile = format!(
+            "FROM {base}\n\
+             COPY --from={server_image} {SERVER_BINARY} {SERVER_BINARY}\n\
+             ENV WORKSPACE_DIR={workspace}\n\
+             WORKDIR {workspace}\n\
+             EXPOSE 3000\n\
+             CMD [\"{SERVER_BINARY}\"]\n",
+            workspace = self.container_workspace(),
+        );
+        let mut child = Command::new("docker")
+            .args(["build", "-t", &tag, "-"])
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run docker build: {}", e))?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(dockerfile.as_bytes())
+            .map_err(|e| e.to_string())?;
+        let status = child.wait().map_err(|e| e.to_string())?;
+        if !status.success() {
+            return Err(format!("Failed to build {}", tag));
+        }
+        Ok(tag)
+    }
+
+    fn build_base_image(&self, tag: &str) -> Result<String, String> {
+        if !self.config.features.is_empty() {
+            if devcontainer_cli_available() {
+                let workspace = self.workspace_dir.to_string_lossy();
+                let config = self.path.to_string_lossy();
+                run(Command::new("devcontainer").args([
+                    "build",
+                    "--workspace-folder",
+                    &workspace,
+                    "--config",
+                    &config,
+                    "--image-name",
+                    tag,
+                ]))?;
+                return Ok(tag.to_string());
+            }
+            tracing::warn!(
+                "devcontainer CLI not found; skipping features of {}",
+                self.path.display()
+            );
+        }
+
+        if let Some(image) = &self.config.image {
+            return Ok(image.clone());
+        }
+        let build = self.config.build.clone().unwrap_or_default();
+        let (context, dockerfile) = self
+            .build_paths()?
+            .ok_or_else(|| format!("{} has neither image nor build", self.path.display()))?;
+        let mut command = Command::new("docker");
+        command.args(["build", "-t", tag, "-f"]).arg(&dockerfile);
+        for (key, value) in &build.args {
+            command.arg("--build-arg").arg(format!("{}={}", key, value));
+        }
+        command.arg(&context);
+        run(&mut command)?;
+        Ok(tag.to_string())
+    }
+}
+
+fn run(command: &mut Command) -> Result<(), String> {
+    let status = command
+        .status()
+        .map_err(|e| format!("Failed to run {:?}: {}", command.get_program(), e))?;
+    if !status.success() {
+        return Err(format!(
+            "{:?} failed with {}",
+            command.get_program(),
+            status
+        ));
+    }
+    Ok(())
+}
+
+fn image_exists(tag: &str) -> bool {
+    Command::new("docker")
+        .args(["image", "inspect", tag])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// The id of the local image `image`, if it exists.
+fn image_id(image: &str) -> Option<String> {
+    let output = Command::new("docker")
+        .args(["image", "inspect", "-f", "{{.Id}}", image])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn devcontainer_cli_available() -> bool {
+    Command::new("devcontainer")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Remove the comments and trailing commas devcontainer.json (JSONC) allows.
+pub fn strip_jsonc(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => while chars.next_if(|&c| c != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            (',', _) => {
+                // Drop the comma if only whitespace/comments precede the closing bracket.
+                if !matches!(next_token(chars.clone()), Some('}') | Some(']')) {
+                    out.push(c);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// The next character that is not whitespace or inside a comment.
+fn next_token(mut chars: std::iter::Peekable<std::str::Chars>) -> Option<char> {
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            (c, _) if c.is_whitespace() => {}
+            ('/', Some('/')) => while chars.next_if(|&c| c != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            _ => return Some(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_devcontainer_jsonc() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path().join("my-app");
+        std::fs::create_dir_all(workspace.join(".devcontainer")).unwrap();
+        std::fs::write(
+            workspace.join(".devcontainer/devcontainer.json"),
+            r#"{
+                // Node toolchain
+                "image": "mcr.microsoft.com/devcontainers/typescript-node:20",
+                /* features are optional */
+                "features": { "ghcr.io/devcontainers/features/github-cli:1": {}, },
+                "forwardPorts": [3000, "db:5432", 5173],
+                "postCreateCommand": { "deps": "npm ci", "hooks": ["git", "config", "core.hooksPath", ".githooks"] },
+                "containerEnv": { "URL": "http://localhost:3000/*x*/" },
+            }"#,
+        )
+        .unwrap();
+
+        let path = DevContainerConfig::find(&workspace).unwrap();
+        let config = DevContainerConfig::load(&workspace, &path).unwrap();
+        assert_eq!(
+            config.config.image.as_deref(),
+            Some("mcr.microsoft.com/devcontainers/typescript-node:20")
+        );
+        assert_eq!(config.config.features.len(), 1);
+        assert_eq!(config.forward_ports(), vec![3000, 5173]);
+        assert_eq!(config.container_workspace(), "/workspaces/my-app");
+        assert_eq!(
+            config.config.container_env["URL"],
+            "http://localhost:3000/*x*/"
+        );
+        assert_eq!(
+            config.config.post_create_command.unwrap().argvs(),
+            vec![
+                vec!["sh", "-c", "npm ci"],
+                vec!["git", "config", "core.hooksPath", ".githooks"],
+            ]
+        );
+
+        std::fs::write(
+            workspace.join(".devcontainer/devcontainer.json"),
+            r#"{ "dockerComposeFile": "compose.yml" }"#,
+        )
+        .unwrap();
+        assert!(DevContainerConfig::load(&workspace, &path).is_err());
+    }
+
+    #[test]
+    fn test_build_stays_in_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path().join("my-app");
+        std::fs::create_dir_all(workspace.join(".devcontainer")).unwrap();
+        let path = workspace.join(".devcontainer/devcontainer.json");
+        let load = |build: &str| {
+            std::fs::write(&path, format!(r#"{{ "build": {} }}"#, build)).unwrap();
+            DevContainerConfig::load(&workspace, &path)
+        };
+
+        let config = load(r#"{ "dockerfile": "Dockerfile", "context": ".." }"#).unwrap();
+        let (context, dockerfile) = config.build_paths().unwrap().unwrap();
+        assert_eq!(context, workspace);
+        assert_eq!(dockerfile, workspace.join(".devcontainer/Dockerfile"));
+
+        assert!(load(r#"{ "context": "../.." }"#).is_err());
+        assert!(load(r#"{ "dockerfile": "../../Dockerfile" }"#).is_err());
+        #[cfg(unix)]
+        {
+            std::fs::write(temp_dir.path().join("Dockerfile"), "FROM scratch\n").unwrap();
+            std::os::unix::fs::symlink(
+                temp_dir.path().join("Dockerfile"),
+                workspace.join(".devcontainer/Dockerfile"),
+            )
+            .unwrap();
+            assert!(load(r#"{ "dockerfile": "Dockerfile" }"#).is_err());
+        }
+    }
+}
@@ -1,8 +1,20 @@
-use crate::agent::tools::Tool;
-use crate::runtime::Runtime;
+use crate::agent::tools::{Tool, ToolOutput};
+use crate::runtime::devcontainer::DevContainerConfig;
+use crate::runtime::network::{INTERNAL_NETWORK, NetworkMode, egress};
+use crate::runtime::pool::{PooledContainer, SandboxPool};
+use crate::runtime::ports::{
+    self, EXPOSE_PORT_TOOL, ExposePortTool, ExposedPort, ListeningPort, PortRegistry,
+};
+use crate::runtime::{RemoteRuntime, Runtime, RuntimeStatus};
 use async_trait::async_trait;
 use serde_json::Value;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 
 /// A runtime that runs the agent within a Docker container.
@@ -15,84 +27,671 @@ use uuid::Uuid;
 /// This allows for:
 /// - Sandboxing: The agent cannot accidentally harm the host system.
 /// - Reproducibility: Every session starts with a clean state.
+///
+/// Unless the network policy is `full`, the container joins the internal
+/// [`INTERNAL_NETWORK`], which has no route out, and its API is reached at the container's
+/// address. In `allowlist` mode the container's commands get the egress proxy, listening on
+/// the network's gateway, as their HTTP(S) proxy.
 pub struct DockerRuntime {
     /// The Docker container ID (name) used for lifecycle management (stop/rm).
     pub container_id: String,
     pub image_name: String,
-    /// The internal RemoteRuntime used for communication with the agent server.
-    remote: crate::runtime::RemoteRuntime,
+    /// Directory in the container that relative paths of `copy_in`/`copy_out` refer to.
+    pub workspace_dir: String,
+    tools: Vec<Box<dyn Tool>>,
+    /// `docker run` arguments for a container [`Runtime::connect`] has yet to start.
+    run_args: Vec<String>,
+    /// Talks to the agent server in the container; set once the container runs.
+    remote: OnceCell<RemoteRuntime>,
+    /// Container ports forwarded to the host, see [`crate::runtime::ports`].
+    ports: PortRegistry,
+    forwarders: Mutex<Vec<JoinHandle<()>>>,
+    /// Set when the container is leased from a [`SandboxPool`], which gets it back on close:
+    /// the pool, the leasing owner and the conversations the container has served.
+    pub(crate) lease: Mutex<Option<(Arc<SandboxPool>, String, u32)>>,
+    /// Set once the agent server in the container answered.
+    connected: OnceCell<()>,
+    /// Set by [`Runtime::close`], after which dropping the runtime has nothing left to do.
+    closed: AtomicBool,
 }
 
 impl DockerRuntime {
-    /// Starts a new Docker container with the specified image and waits for it to be ready.
+    /// A runtime for a new Docker container with the specified image.
     ///
-    /// This function:
-    /// 1. Generates a unique container name.
-    /// 2. Assigns a random host port (3000-4000) to map to the container's port 3000.
-    /// 3. execute `docker run` to start the container in detached mode.
-    /// 4. Waits for the container to initialize (currently a simple sleep).
+    /// Nothing runs until [`Runtime::connect`], which the first tool call runs if it was not
+    /// called before. It:
+    /// 1. executes `docker run` to start the container, under a unique name, in detached
+    ///    mode, with the container's port 3000 on a random host port (3000-4000) if the
+    ///    network policy allows it;
+    /// 2. waits for the agent server in the container to answer.
     ///
     /// # Arguments
     /// * `image` - The Docker image to run (must contain `openhands-agent-server-rs`).
     /// * `tools` - The tools available to this runtime.
     pub fn new(image: &str, tools: Vec<Box<dyn Tool>>) -> Self {
-        // Start the container
-        let container_name = format!("openhands-agent-{}", Uuid::new_v4());
-        let port = 3000 + (rand::random::<u16>() % 1000); // Simple random port for now
+        Self::pending(image, Vec::new(), tools)
+    }
+
+    /// A container configured step by step; see [`DockerRuntimeBuilder`].
+    pub fn builder() -> DockerRuntimeBuilder {
+        DockerRuntimeBuilder::default()
+    }
+
+    /// Starts the workspace's devcontainer: the project image with the agent server from
+    /// `server_image` added, the workspace mounted, `forwardPorts` published and
+    /// `postCreateCommand` run.
+    pub fn from_devcontainer(
+        devcontainer: &DevContainerConfig,
+        server_image: &str,
+        tools: Vec<Box<dyn Tool>>,
+    ) -> Result<Self, String> {
+        let image = devcontainer.build_image(server_image)?;
+        let config = &devcontainer.config;
+
+        let mut args = vec![
+            "-v".to_string(),
+            format!(
+                "{}:{}",
+                devcontainer.workspace_dir.display(),
+                devcontainer.container_workspace()
+            ),
+        ];
+        for port in devcontainer.forward_ports() {
+            args.extend(["-p".to_string(), port.to_string()]);
+        }
+        for (key, value) in &config.container_env {
+            args.extend(["-e".to_string(), format!("{}={}", key, value)]);
+        }
+        if let Some(user) = &config.remote_user {
+            args.extend(["--user".to_string(), user.clone()]);
+        }
+
+        let mut runtime = Self::start(&image, &args, tools)?;
+        runtime.workspace_dir = devcontainer.container_workspace();
+        if let Some(command) = &config.post_create_command {
+            for argv in command.argvs() {
+                let status = Command::new("docker")
+                    .args(["exec", &runtime.container_id])
+                    .args(&argv)
+                    .status()
+                    .map_err(|e| format!("Failed to run postCreateCommand: {}", e))?;
+                if !status.success() {
+                    return Err(format!(
+                        "postCreateCommand {:?} failed with {}",
+                        argv, status
+                    ));
+                }
+            }
+        }
+        Ok(runtime)
+    }
+
+    fn start(
+        image: &str,
+        extra_args: &[String],
+        tools: Vec<Box<dyn Tool>>,
+    ) -> Result<Self, String> {
+        let container_id = Self::container_name();
+        let api_url = Self::run_container(&container_id, image, extra_args)?;
+        Ok(Self::attach(container_id, image, api_url, tools))
+    }
+
+    /// A unique name for a new container.
+    pub(crate) fn container_name() -> String {
+        format!("openhands-agent-{}", Uuid::new_v4())
+    }
+
+    /// `docker run` the agent server image as `container_name` under the network policy,
+    /// returning the URL of its API.
+    pub(crate) fn run_container(
+        container_name: &str,
+        image: &str,
+        extra_args: &[String],
+    ) -> Result<String, String> {
+        let egress = egress();
+        let mut network_args = Vec::new();
+        let mut port = None;
+        match egress.policy.mode {
+            NetworkMode::Full => {
+                let host_port = 3000 + (rand::random::<u16>() % 1000); // Simple random port for now
+                network_args.extend(["-p".to_string(), format!("{}:3000", host_port)]);
+                port = Some(host_port);
+            }
+            NetworkMode::None | NetworkMode::Allowlist => {
+                let gateway = internal_network()?;
+                network_args.extend(["--network".to_string(), INTERNAL_NETWORK.to_string()]);
+                for (name, value) in egress.proxy_vars(&gateway) {
+                    network_args.extend(["-e".to_string(), format!("{}={}", name, value)]);
+                }
+            }
+        }
 
         let status = Command::new("docker")
-            .args(&[
-                "run",
-                "-d",
-                "-p",
-                &format!("{}:3000", port),
-                "--name",
-                &container_name,
-                image,
-            ])
+            .args(["run", "-d", "--name", container_name])
+            .args(&network_args)
+            .args(extra_args)
+            .arg(image)
             .status()
-            .expect("Failed to start docker container");
+            .map_err(|e| format!("Failed to run docker: {}", e))?;
 
         if !status.success() {
-            panic!("Docker run failed");
+            return Err("Docker run failed".to_string());
         }
 
-        // Wait for health check (simplified for now, ideally retry loop)
-        std::thread::sleep(std::time::Duration::from_secs(5));
+        match port {
+            Some(port) => Ok(format!("http://localhost:{}", port)),
+            None => Self::internal_api_url(container_name),
+        }
+    }
+
+    /// The API URL of a container on [`INTERNAL_NETWORK`], at its current address.
+    pub(crate) fn internal_api_url(container_name: &str) -> Result<String, String> {
+        let ip = docker_inspect(&[
+            "inspect",
+            "-f",
+            "{{range .NetworkSettings.Networks}}{{.IPAddress}} {{end}}",
+            container_name,
+        ])?;
+        let ip = ip
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| format!("Container {} has no IP address", container_name))?;
+        Ok(format!("http://{}:3000", ip))
+    }
+
+    /// A runtime for an already running agent server container.
+    pub(crate) fn attach(
+        container_id: String,
+        image: &str,
+        api_url: String,
+        tools: Vec<Box<dyn Tool>>,
+    ) -> Self {
+        let remote = RemoteRuntime::new(api_url, Vec::new());
+        Self::with_remote(container_id, image, Vec::new(), Some(remote), tools)
+    }
+
+    /// A runtime whose container [`Runtime::connect`] starts with `run_args`.
+    pub(crate) fn pending(image: &str, run_args: Vec<String>, tools: Vec<Box<dyn Tool>>) -> Self {
+        Self::with_remote(Self::container_name(), image, run_args, None, tools)
+    }
 
+    fn with_remote(
+        container_id: String,
+        image: &str,
+        run_args: Vec<String>,
+        remote: Option<RemoteRuntime>,
+        mut tools: Vec<Box<dyn Tool>>,
+    ) -> Self {
+        if !tools.iter().any(|t| t.name() == EXPOSE_PORT_TOOL) {
+            tools.push(Box::new(ExposePortTool));
+        }
         Self {
-            container_id: container_name,
+            container_id,
             image_name: image.to_string(),
-            remote: crate::runtime::RemoteRuntime::new(format!("http://localhost:{}", port), tools),
+            workspace_dir: "/workspace".to_string(),
+            tools,
+            run_args,
+            remote: OnceCell::new_with(remote),
+            ports: PortRegistry::default(),
+            forwarders: Mutex::new(Vec::new()),
+            lease: Mutex::new(None),
+            connected: OnceCell::new(),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// The agent server of the container, started first if it is not running yet.
+    async fn remote(&self) -> Result<&RemoteRuntime, String> {
+        self.remote
+            .get_or_try_init(|| async {
+                let name = self.container_id.clone();
+                let image = self.image_name.clone();
+                let args = self.run_args.clone();
+                // The docker CLI blocks, and may have to pull the image.
+                let api_url =
+                    tokio::task::spawn_blocking(move || Self::run_container(&name, &image, &args))
+                        .await
+                        .map_err(|e| e.to_string())??;
+                if self.closed.load(Ordering::SeqCst) {
+                    // Closed while starting: nothing else will remove the container.
+                    Self::docker_output(&["rm", "-f", &self.container_id]).await?;
+                    return Err(format!("Container {} was closed", self.container_id));
+                }
+                Ok(RemoteRuntime::new(api_url, Vec::new()))
+            })
+            .await
+    }
+
+    /// The tool called `action` if it runs in this process (see [`Tool::runs_on_host`]).
+    fn host_tool(&self, action: &str) -> Option<&dyn Tool> {
+        self.tools
+            .iter()
+            .find(|t| t.name() == action && t.runs_on_host())
+            .map(|t| t.as_ref())
+    }
+
+    fn container_path(&self, path: &str) -> String {
+        let path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("{}/{}", self.workspace_dir.trim_end_matches('/'), path)
+        };
+        format!("{}:{}", self.container_id, path)
+    }
+
+    async fn docker_output(args: &[&str]) -> Result<String, String> {
+        let output = tokio::process::Command::new("docker")
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run docker: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    async fn container_ip(&self) -> Result<IpAddr, String> {
+        let ip = Self::docker_output(&[
+            "inspect",
+            "-f",
+            "{{range .NetworkSettings.Networks}}{{.IPAddress}} {{end}}",
+            &self.container_id,
+        ])
+        .await?;
+        ip.split_whitespace()
+            .next()
+            .and_then(|ip| ip.parse().ok())
+            .ok_or_else(|| format!("Container {} has no IP address", self.container_id))
+    }
+
+    /// TCP ports listened on inside the container.
+    pub async fn listening_ports(&self) -> Result<Vec<ListeningPort>, String> {
+        let tcp = Self::docker_output(&[
+            "exec",
+            &self.container_id,
+            "cat",
+            "/proc/net/tcp",
+            "/proc/net/tcp6",
+        ])
+        .await?;
+        Ok(ports::parse_listening_ports(&tcp))
+    }
+
+    /// Forward container `port` to a host port, or return the existing forward.
+    pub async fn expose_port(&self, port: u16) -> Result<ExposedPort, String> {
+        if let Some(existing) = self
+            .ports
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|p| p.container_port == port)
+        {
+            return Ok(existing.clone());
+        }
+        let ip = self.container_ip().await?;
+        let (host_port, task) = ports::forward(SocketAddr::new(ip, port)).await?;
+        let exposed = ExposedPort {
+            container_port: port,
+            host_port,
+            url: ports::preview_url(host_port),
+        };
+        self.forwarders.lock().unwrap().push(task);
+        self.ports.lock().unwrap().push(exposed.clone());
+        Ok(exposed)
+    }
+
+    fn abort_forwarders(&self) {
+        for task in self.forwarders.lock().unwrap().drain(..) {
+            task.abort();
+        }
+    }
+
+    /// The container to return to its pool, if it was started.
+    fn pooled(&self, owner: String, uses: u32) -> Option<PooledContainer> {
+        Some(PooledContainer {
+            id: self.container_id.clone(),
+            api_url: self.remote.get()?.client.base_url().to_string(),
+            owner: Some(owner),
+            uses,
+        })
+    }
+
+    /// Return a leased container to `pool`, or remove the container if it was started.
+    fn release(&self) -> bool {
+        let lease = self.lease.lock().unwrap().take();
+        match lease {
+            Some((pool, owner, uses)) => {
+                if let Some(container) = self.pooled(owner, uses) {
+                    pool.release(container);
+                }
+                true
+            }
+            None => false,
         }
     }
 
     /// Stops and removes the Docker container.
     pub fn stop(&self) {
         let _ = Command::new("docker")
-            .args(&["stop", &self.container_id])
+            .args(["stop", &self.container_id])
             .output();
         let _ = Command::new("docker")
-            .args(&["rm", &self.container_id])
+            .args(["rm", &self.container_id])
             .output();
     }
 }
 
+/// Output of a synchronous `docker` command.
+fn docker_inspect(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("docker")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run docker: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Create [`INTERNAL_NETWORK`] if it does not exist, returning its gateway address.
+fn internal_network() -> Result<String, String> {
+    let gateway = [
+        "network",
+        "inspect",
+        "-f",
+        "{{range .IPAM.Config}}{{.Gateway}}{{end}}",
+    ];
+    if let Ok(gateway) = docker_inspect(&[gateway.as_slice(), &[INTERNAL_NETWORK]].concat()) {
+        return Ok(gateway);
+    }
+    docker_inspect(&["network", "create", "--internal", INTERNAL_NETWORK])
+        .map_err(|e| format!("Failed to create network {}: {}", INTERNAL_NETWORK, e))?;
+    docker_inspect(&[gateway.as_slice(), &[INTERNAL_NETWORK]].concat())
+}
+
+/// Builds and starts a [`DockerRuntime`]. Only the image is required.
+#[derive(Default)]
+pub struct DockerRuntimeBuilder {
+    image: Option<String>,
+    tools: Vec<Box<dyn Tool>>,
+    mounts: Vec<(PathBuf, String)>,
+    env: Vec<(String, String)>,
+    ports: Vec<u16>,
+    workspace_dir: Option<String>,
+}
+
+impl DockerRuntimeBuilder {
+    /// The agent server image to run, e.g. from [`crate::runtime::sandbox::sandbox_image`].
+    pub fn image(mut self, image: impl Into<String>) -> Self {
+        self.image = Some(image.into());
+        self
+    }
+
+    pub fn tools(mut self, tools: Vec<Box<dyn Tool>>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Bind-mount `host_path` at `container_path`, an absolute path.
+    pub fn mount(mut self, host_path: impl Into<PathBuf>, container_path: &str) -> Self {
+        self.mounts
+            .push((host_path.into(), container_path.to_string()));
+        self
+    }
+
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        self.env.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Publish container `port` on the same host port.
+    pub fn port(mut self, port: u16) -> Self {
+        self.ports.push(port);
+        self
+    }
+
+    /// Directory in the container that relative `copy_in`/`copy_out` paths refer to;
+    /// `/workspace` by default.
+    pub fn workspace_dir(mut self, workspace_dir: &str) -> Self {
+        self.workspace_dir = Some(workspace_dir.to_string());
+        self
+    }
+
+    /// The image and the `docker run` arguments, or what is wrong with the configuration.
+    fn docker_args(&self) -> Result<(&str, Vec<String>), String> {
+        let image = self
+            .image
+            .as_deref()
+            .filter(|image| !image.trim().is_empty())
+            .ok_or("A Docker runtime needs an image")?;
+        let mut args = Vec::new();
+        for (host_path, container_path) in &self.mounts {
+            if !container_path.starts_with('/') {
+                return Err(format!(
+                    "Mount target {} is not an absolute path",
+                    container_path
+                ));
+            }
+            let host_path = host_path
+                .canonicalize()
+                .map_err(|e| format!("Cannot mount {}: {}", host_path.display(), e))?;
+            args.extend([
+                "-v".to_string(),
+                format!("{}:{}", host_path.display(), container_path),
+            ]);
+        }
+        for (name, value) in &self.env {
+            if name.is_empty() || name.contains('=') {
+                return Err(format!("Invalid environment variable name {:?}", name));
+            }
+            args.extend(["-e".to_string(), format!("{}={}", name, value)]);
+        }
+        for port in &self.ports {
+            args.extend(["-p".to_string(), format!("{}:{}", port, port)]);
+        }
+        Ok((image, args))
+    }
+
+    /// Check the configuration. The container starts on [`Runtime::connect`], like one of
+    /// [`DockerRuntime::new`].
+    pub fn build(self) -> Result<DockerRuntime, String> {
+        let (image, args) = self.docker_args()?;
+        let image = image.to_string();
+        let mut runtime = DockerRuntime::pending(&image, args, self.tools);
+        if let Some(workspace_dir) = self.workspace_dir {
+            runtime.workspace_dir = workspace_dir;
+        }
+        Ok(runtime)
+    }
+}
+
 impl Drop for DockerRuntime {
-    /// Ensures the container is cleaned up when the Runtime is dropped.
+    /// Cleans up the container (or returns it to its pool) if the runtime was dropped
+    /// without [`Runtime::close`], blocking on the `docker` CLI.
     fn drop(&mut self) {
-        self.stop();
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.abort_forwarders();
+        if !self.release() && self.remote.initialized() {
+            self.stop();
+        }
     }
 }
 
 #[async_trait]
 impl Runtime for DockerRuntime {
     fn tools(&self) -> &[Box<dyn Tool>] {
-        self.remote.tools()
+        &self.tools
+    }
+
+    /// Start the container if it is not running yet, and wait for its agent server to answer.
+    async fn connect(&self) -> Result<(), String> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(format!("Container {} was closed", self.container_id));
+        }
+        self.connected
+            .get_or_try_init(|| async { self.remote().await?.connect().await })
+            .await
+            .map(|_| ())
+    }
+
+    /// Return the container to its pool, or remove it.
+    async fn close(&self) -> Result<(), String> {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.abort_forwarders();
+        if self.release() || !self.remote.initialized() {
+            return Ok(());
+        }
+        Self::docker_output(&["rm", "-f", &self.container_id])
+            .await
+            .map(|_| ())
+    }
+
+    async fn status(&self) -> RuntimeStatus {
+        if self.closed.load(Ordering::SeqCst) {
+            return RuntimeStatus::Closed;
+        }
+        let Some(remote) = self.remote.get() else {
+            return RuntimeStatus::Starting;
+        };
+        let running =
+            Self::docker_output(&["inspect", "-f", "{{.State.Running}}", &self.container_id]).await;
+        match running {
+            Ok(running) if running.trim() == "true" => {}
+            Ok(_) => {
+                return RuntimeStatus::Unavailable(format!(
+                    "Container {} is not running",
+                    self.container_id
+                ));
+            }
+            Err(e) => return RuntimeStatus::Unavailable(e),
+        }
+        match remote.status().await {
+            RuntimeStatus::Unavailable(_) if !self.connected.initialized() => {
+                RuntimeStatus::Starting
+            }
+            status => status,
+        }
     }
 
     /// Executes an action by delegating to the internal RemoteRuntime.
     async fn execute(&self, action: &str, args: Value) -> Result<String, String> {
-        self.remote.execute(action, args).await
+        self.connect().await?;
+        if action == EXPOSE_PORT_TOOL {
+            let port = ports::port_arg(&args)?;
+            let exposed = self.expose_port(port).await?;
+            let mut output = format!("Port {} is reachable at {}", port, exposed.url);
+            if let Ok(listening) = self.listening_ports().await {
+                match listening.iter().find(|p| p.port == port) {
+                    None => output.push_str("\nNote: nothing is listening on this port yet."),
+                    Some(p) if p.loopback => output.push_str(
+                        "\nNote: the server only listens on localhost; bind it to 0.0.0.0.",
+                    ),
+                    Some(_) => {}
+                }
+            }
+            return Ok(output);
+        }
+        if let Some(tool) = self.host_tool(action) {
+            return tool.call(args).await;
+        }
+        self.remote().await?.execute(action, args).await
+    }
+
+    async fn execute_with_attachments(
+        &self,
+        action: &str,
+        args: Value,
+    ) -> Result<ToolOutput, String> {
+        if action == EXPOSE_PORT_TOOL {
+            return self.execute(action, args).await.map(ToolOutput::from);
+        }
+        self.connect().await?;
+        match self.host_tool(action) {
+            Some(tool) => tool.call_with_attachments(args).await,
+            None => {
+                self.remote()
+                    .await?
+                    .execute_with_attachments(action, args)
+                    .await
+            }
+        }
+    }
+
+    fn ports(&self) -> Option<PortRegistry> {
+        Some(self.ports.clone())
+    }
+
+    async fn read_file(&self, path: &Path) -> Option<String> {
+        self.connect().await.ok()?;
+        self.remote().await.ok()?.read_file(path).await
+    }
+
+    /// `docker cp` from the host into the container.
+    async fn copy_in(&self, host_path: &Path, runtime_path: &str) -> Result<(), String> {
+        self.connect().await?;
+        let host_path = host_path.to_string_lossy();
+        Self::docker_output(&["cp", &host_path, &self.container_path(runtime_path)]).await?;
+        Ok(())
+    }
+
+    /// `docker cp` from the container to the host.
+    async fn copy_out(&self, runtime_path: &str, host_path: &Path) -> Result<(), String> {
+        self.connect().await?;
+        let host_path = host_path.to_string_lossy();
+        Self::docker_output(&["cp", &self.container_path(runtime_path), &host_path]).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_args() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let builder = DockerRuntime::builder()
+            .image("openhands-agent-server-rs:latest")
+            .mount(temp_dir.path(), "/workspace")
+            .env("RUST_LOG", "debug")
+            .port(8080);
+        let (image, args) = builder.docker_args().unwrap();
+        assert_eq!(image, "openhands-agent-server-rs:latest");
+        let mount = format!(
+            "{}:/workspace",
+            temp_dir.path().canonicalize().unwrap().display()
+        );
+        assert_eq!(
+            args,
+            vec!["-v", &mount, "-e", "RUST_LOG=debug", "-p", "8080:8080"]
+        );
+
+        let error = |builder: DockerRuntimeBuilder| builder.docker_args().unwrap_err();
+        assert_eq!(
+            error(DockerRuntime::builder()),
+            "A Docker runtime needs an image"
+        );
+        let image = || DockerRuntime::builder().image("agent");
+        assert!(error(image().mount(temp_dir.path(), "workspace")).contains("not an absolute"));
+        assert!(
+            error(image().mount(temp_dir.path().join("missing"), "/w")).contains("Cannot mount")
+        );
+        assert!(error(image().env("A=B", "c")).contains("Invalid environment variable"));
+    }
+
+    #[tokio::test]
+    async fn test_container_starts_on_connect() {
+        // No docker call happens until the runtime connects.
+        let runtime = DockerRuntime::new("openhands-agent-server-rs:missing", Vec::new());
+        assert_eq!(runtime.status().await, RuntimeStatus::Starting);
+        assert!(runtime.tools().iter().any(|t| t.name() == EXPOSE_PORT_TOOL));
+        runtime.close().await.unwrap();
+        assert_eq!(runtime.status().await, RuntimeStatus::Closed);
+        assert!(runtime.connect().await.unwrap_err().contains("was closed"));
     }
 }
@@ -0,0 +1,475 @@
+//! Downloading URLs into the workspace, for the `download_file` tool.
+//!
+//! Content is written to `<path>.part` and renamed into place once complete (and verified,
+//! if a SHA-256 was given). A download that is cut off leaves the `.part` file behind, and
+//! the next request for the same path continues it with a `Range` request.
+//!
+//! The network policy is checked on every redirect hop. Loopback, link-local and private
+//! addresses (literal or resolved) are refused unless the host is listed in the policy's
+//! `hosts`, so an allowed host cannot redirect to cloud metadata or services on the server.
+
+use std::error::Error;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::models::{DownloadRequest, DownloadResponse};
+use crate::paths::confine;
+use crate::runtime::network::{NetworkPolicy, egress};
+
+/// Largest file `download_file` fetches, whatever the request's `max_bytes`.
+pub const MAX_DOWNLOAD_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Redirects followed before giving up, as reqwest's default policy.
+const MAX_REDIRECTS: usize = 10;
+
+/// Download `req.url` to `req.path` in `workspace_dir`, honouring the network policy.
+pub async fn download_file(workspace_dir: &Path, req: DownloadRequest) -> DownloadResponse {
+    download_with_policy(workspace_dir, req, &egress().policy).await
+}
+
+async fn download_with_policy(
+    workspace_dir: &Path,
+    req: DownloadRequest,
+    policy: &NetworkPolicy,
+) -> DownloadResponse {
+    let mut response = DownloadResponse {
+        url: req.url.clone(),
+        path: req.path.clone(),
+        success: false,
+        bytes: 0,
+        sha256: None,
+        resumed: false,
+        error: None,
+    };
+    match try_download(workspace_dir, &req, policy, &mut response).await {
+        Ok(()) => response.success = true,
+        Err(e) => response.error = Some(e),
+    }
+    response
+}
+
+/// Whether `ip` is on the host itself or a private network rather than the internet.
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // Shared address space (RFC 6598).
+                || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_internal(IpAddr::V4(ip)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+/// Why `url` may not be fetched under `policy`, checked for the request and each redirect.
+fn refusal(url: &reqwest::Url, policy: &NetworkPolicy) -> Option<String> {
+    let host = url.host_str().unwrap_or_default();
+    if !policy.allows_host(host) {
+        return Some(format!(
+            "Downloads from {} are not allowed by the network policy",
+            host
+        ));
+    }
+    let ip = host.trim_start_matches('[').trim_end_matches(']');
+    let ip = ip.parse::<IpAddr>().ok()?;
+    (is_internal(ip) && !policy.lists_host(host)).then(|| {
+        format!(
+            "Downloads from the internal address {} are not allowed",
+            host
+        )
+    })
+}
+
+/// Resolves host names, dropping internal addresses unless the host is listed.
+struct PublicResolver {
+    policy: NetworkPolicy,
+}
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        let listed = self.policy.lists_host(&host);
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| listed || !is_internal(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// A client that applies `policy` to every redirect hop and resolved address.
+fn client(policy: &NetworkPolicy) -> Result<reqwest::Client, String> {
+    let redirects = policy.clone();
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error("Too many redirects");
+            }
+            match refusal(attempt.url(), &redirects) {
+                Some(reason) => attempt.error(reason),
+                None => attempt.follow(),
+            }
+        }))
+        .dns_resolver(Arc::new(PublicResolver {
+            policy: policy.clone(),
+        }))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+fn partial_path(destination: &Path) -> PathBuf {
+    let mut partial = destination.as_os_str().to_os_string();
+    partial.push(".part");
+    PathBuf::from(partial)
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+async fn try_download(
+    workspace_dir: &Path,
+    req: &DownloadRequest,
+    policy: &NetworkPolicy,
+    response: &mut DownloadResponse,
+) -> Result<(), String> {
+    let url = reqwest::Url::parse(&req.url).map_err(|e| format!("Invalid URL: {}", e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Only http:// and https:// URLs can be downloaded".to_string());
+    }
+    if let Some(reason) = refusal(&url, policy) {
+        return Err(reason);
+    }
+    let max_bytes = req
+        .max_bytes
+        .map_or(MAX_DOWNLOAD_BYTES, |max| max.min(MAX_DOWNLOAD_BYTES));
+
+    let destination = confine(workspace_dir, &req.path)?;
+    if fs::symlink_metadata(&destination).await.is_ok() && !req.overwrite {
+        return Err(format!(
+            "File already exists: {}. Set overwrite to replace it.",
+            req.path
+        ));
+    }
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+    }
+    let partial = partial_path(&destination);
+    let mut offset = match fs::metadata(&partial).await {
+        Ok(metadata) if metadata.len() <= max_bytes => metadata.len(),
+        _ => 0,
+    };
+
+    let mut request = client(policy)?.get(url);
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+    }
+    // The causes say why a redirect or address was refused.
+    let mut http = request.send().await.map_err(|e| {
+        let mut message = format!("Failed to download {}: {}", req.url, e);
+        let mut source = e.source();
+        while let Some(cause) = source {
+            message.push_str(&format!(": {}", cause));
+            source = cause.source();
+        }
+        message
+    })?;
+    let status = http.status();
+    let continues = status == reqwest::StatusCode::PARTIAL_CONTENT
+        && http
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|range| range.starts_with(&format!("bytes {}-", offset)));
+    if offset > 0 && status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // The earlier download already got everything.
+    } else if !status.is_success() {
+        return Err(format!("Server returned {} for {}", status, req.url));
+    } else if !continues {
+        offset = 0;
+    }
+    if let Some(length) = http.content_length()
+        && status.is_success()
+        && offset + length > max_bytes
+    {
+        return Err(format!(
+            "File is larger than the {} byte limit ({} bytes)",
+            max_bytes,
+            offset + length
+        ));
+    }
+
+    // Hash what an earlier attempt wrote, then the rest as it arrives.
+    let mut hasher = Sha256::new();
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(&partial)
+        .await
+        .map_err(|e| format!("Failed to open {}: {}", partial.display(), e))?;
+    if offset == 0 {
+        file.set_len(0).await.map_err(|e| e.to_string())?;
+    } else {
+        response.resumed = true;
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf).await.map_err(|e| e.to_string())?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+    }
+
+    let mut written = offset;
+    if status.is_success() {
+        loop {
+            let chunk = match http.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(e) => {
+                    file.flush().await.ok();
+                    return Err(format!(
+                        "Download interrupted after {} bytes: {}. Request it again to resume.",
+                        written, e
+                    ));
+                }
+            };
+            written += chunk.len() as u64;
+            if written > max_bytes {
+                drop(file);
+                fs::remove_file(&partial).await.ok();
+                return Err(format!("File is larger than the {} byte limit", max_bytes));
+            }
+            hasher.update(&chunk);
+            file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+        }
+    }
+    file.flush().await.map_err(|e| e.to_string())?;
+    drop(file);
+
+    let actual = hex(&hasher.finalize());
+    if let Some(expected) = &req.sha256
+        && !expected.trim().eq_ignore_ascii_case(&actual)
+    {
+        fs::remove_file(&partial).await.ok();
+        return Err(format!(
+            "SHA-256 mismatch: expected {}, got {}. The download was discarded.",
+            expected.trim(),
+            actual
+        ));
+    }
+    fs::rename(&partial, &destination)
+        .await
+        .map_err(|e| format!("Failed to save {}: {}", req.path, e))?;
+    response.bytes = written;
+    response.sha256 = Some(actual);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    const BODY: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    /// Serve `BODY` to every request, honouring `Range: bytes=N-`.
+    async fn serve_body() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/data.bin", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = socket.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let start = request
+                    .lines()
+                    .find_map(|l| l.strip_prefix("range: bytes="))
+                    .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok());
+                let head = match start {
+                    Some(start) => format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\n",
+                        start,
+                        BODY.len() - 1,
+                        BODY.len(),
+                        BODY.len() - start
+                    ),
+                    None => format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n", BODY.len()),
+                };
+                let body = &BODY[start.unwrap_or(0)..];
+                socket
+                    .write_all(format!("{}Connection: close\r\n\r\n", head).as_bytes())
+                    .await
+                    .unwrap();
+                socket.write_all(body).await.unwrap();
+            }
+        });
+        url
+    }
+
+    /// The test servers listen on loopback, which has to be listed to be reachable.
+    fn local_policy() -> NetworkPolicy {
+        NetworkPolicy {
+            hosts: vec!["127.0.0.1".to_string()],
+            ..NetworkPolicy::default()
+        }
+    }
+
+    async fn download_file(workspace_dir: &Path, req: DownloadRequest) -> DownloadResponse {
+        download_with_policy(workspace_dir, req, &local_policy()).await
+    }
+
+    fn request(url: &str, path: &str) -> DownloadRequest {
+        DownloadRequest {
+            url: url.to_string(),
+            path: path.to_string(),
+            sha256: None,
+            max_bytes: None,
+            overwrite: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_file() {
+        let url = serve_body().await;
+        let dir = tempfile::tempdir().unwrap();
+        let digest = hex(&Sha256::digest(BODY));
+
+        let done = download_file(
+            dir.path(),
+            DownloadRequest {
+                sha256: Some(digest.to_uppercase()),
+                ..request(&url, "data/full.bin")
+            },
+        )
+        .await;
+        assert!(done.success, "{:?}", done.error);
+        assert_eq!(done.bytes, BODY.len() as u64);
+        assert_eq!(done.sha256.as_deref(), Some(digest.as_str()));
+        assert_eq!(
+            std::fs::read(dir.path().join("data/full.bin")).unwrap(),
+            BODY
+        );
+
+        let exists = download_file(dir.path(), request(&url, "data/full.bin")).await;
+        assert!(exists.error.unwrap().contains("already exists"));
+
+        // Continue from the first ten bytes of an interrupted download.
+        std::fs::write(dir.path().join("resumed.bin.part"), &BODY[..10]).unwrap();
+        let resumed = download_file(dir.path(), request(&url, "resumed.bin")).await;
+        assert!(resumed.success && resumed.resumed);
+        assert_eq!(std::fs::read(dir.path().join("resumed.bin")).unwrap(), BODY);
+        assert!(!dir.path().join("resumed.bin.part").exists());
+
+        let mismatch = download_file(
+            dir.path(),
+            DownloadRequest {
+                sha256: Some("00".repeat(32)),
+                ..request(&url, "bad.bin")
+            },
+        )
+        .await;
+        assert!(mismatch.error.unwrap().starts_with("SHA-256 mismatch"));
+        assert!(!dir.path().join("bad.bin").exists());
+        assert!(!dir.path().join("bad.bin.part").exists());
+
+        let capped = download_file(
+            dir.path(),
+            DownloadRequest {
+                max_bytes: Some(8),
+                ..request(&url, "big.bin")
+            },
+        )
+        .await;
+        assert!(capped.error.unwrap().contains("byte limit"));
+
+        let outside = download_file(dir.path(), request(&url, "../escape.bin")).await;
+        assert!(outside.error.unwrap().contains("outside the workspace"));
+        let ftp = download_file(dir.path(), request("ftp://example.com/x", "x")).await;
+        assert!(!ftp.success);
+    }
+
+    #[tokio::test]
+    async fn test_internal_addresses_need_listing() {
+        let url = serve_body().await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let unlisted = download_with_policy(
+            dir.path(),
+            request(&url, "a.bin"),
+            &NetworkPolicy::default(),
+        )
+        .await;
+        assert!(unlisted.error.unwrap().contains("internal address"));
+        let metadata = download_with_policy(
+            dir.path(),
+            request("http://169.254.169.254/latest/meta-data/", "b.bin"),
+            &NetworkPolicy::default(),
+        )
+        .await;
+        assert!(metadata.error.unwrap().contains("internal address"));
+
+        // A listed host cannot redirect to an unlisted internal one.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let redirect = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(
+                    b"HTTP/1.1 302 Found\r\nLocation: http://127.0.0.2/secret\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await
+                .unwrap();
+        });
+        let redirected = download_file(dir.path(), request(&redirect, "c.bin")).await;
+        let error = redirected.error.unwrap();
+        assert!(error.contains("internal address 127.0.0.2"), "{}", error);
+        assert!(!dir.path().join("c.bin").exists());
+    }
+
+    #[test]
+    fn test_is_internal() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "169.254.169.254",
+            "100.64.0.1",
+            "::1",
+            "fd00::1",
+            "::ffff:192.168.0.1",
+        ] {
+            assert!(is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["93.184.216.34", "2606:4700::1111"] {
+            assert!(!is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Dry runs: the agent plans without changing anything.
+//!
+//! [`DryRunRuntime`] wraps a conversation's runtime. While its [`DryRun`] is enabled, tool
+//! calls that are not read-only (see [`Tool::is_read_only`]) are recorded as
+//! [`ProposedAction`]s instead of executed, and the agent is told so. Read-only calls still
+//! run, so the agent can look around while it plans. The proposed actions can then be
+//! reviewed and executed in order with [`crate::session::Conversation::execute_plan`].
+
+use super::ports::PortRegistry;
+use super::{Runtime, RuntimeStatus};
+use crate::agent::tools::{Tool, ToolOutput};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A tool call intercepted by a dry run.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProposedAction {
+    pub tool_name: String,
+    pub arguments: Value,
+    /// The shell command it would run, for command and terminal tools.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Files it would change.
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+impl ProposedAction {
+    fn new(tool: &dyn Tool, arguments: Value) -> Self {
+        let tool_name = tool.name();
+        let command = match tool_name.as_str() {
+            "cmd" => arguments.get("command"),
+            "terminal" => arguments.get("keys"),
+            _ => None,
+        }
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+        let paths = tool
+            .affected_paths(&arguments)
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        Self {
+            tool_name,
+            arguments,
+            command,
+            paths,
+        }
+    }
+}
+
+/// Whether a conversation is in dry-run mode, and the actions proposed so far.
+#[derive(Clone, Default)]
+pub struct DryRun {
+    enabled: Arc<AtomicBool>,
+    proposed: Arc<Mutex<Vec<ProposedAction>>>,
+}
+
+impl DryRun {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    /// The actions proposed so far, in the order the agent made them.
+    pub fn proposed(&self) -> Vec<ProposedAction> {
+        self.proposed.lock().unwrap().clone()
+    }
+
+    /// Remove and return the proposed actions.
+    pub fn take(&self) -> Vec<ProposedAction> {
+        std::mem::take(&mut *self.proposed.lock().unwrap())
+    }
+
+    /// Put `actions` back at the front of the plan.
+    pub fn restore(&self, actions: Vec<ProposedAction>) {
+        let mut proposed = self.proposed.lock().unwrap();
+        proposed.splice(0..0, actions);
+    }
+
+    /// Record `action`, returning its 1-based position in the plan.
+    fn propose(&self, action: ProposedAction) -> usize {
+        let mut proposed = self.proposed.lock().unwrap();
+        proposed.push(action);
+        proposed.len()
+    }
+}
+
+/// A runtime that proposes instead of executing while its [`DryRun`] is enabled.
+pub struct DryRunRuntime {
+    inner: Box<dyn Runtime + Send + Sync>,
+    dry_run: DryRun,
+}
+
+impl DryRunRuntime {
+    pub fn new(inner: Box<dyn Runtime + Send + Sync>, dry_run: DryRun) -> Self {
+        Self { inner, dry_run }
+    }
+
+    /// The proposal message for `action`, if the call is intercepted.
+    fn intercept(&self, action: &str, args: &Value) -> Option<String> {
+        if !self.dry_run.is_enabled() {
+            return None;
+        }
+        let tool = self.inner.tools().iter().find(|t| t.name() == action)?;
+        if tool.is_read_only(args) {
+            return None;
+        }
+        let step = self
+            .dry_run
+            .propose(ProposedAction::new(tool.as_ref(), args.clone()));
+        Some(format!(
+            "[Dry run] Not executed. Recorded as step {} of the plan, which runs once a user \
+            approves it. Assume it succeeds and continue planning.",
+            step
+        ))
+    }
+}
+
+#[async_trait]
+impl Runtime for DryRunRuntime {
+    fn tools(&self) -> &[Box<dyn Tool>] {
+        self.inner.tools()
+    }
+
+    async fn connect(&self) -> Result<(), String> {
+        self.inner.connect().await
+    }
+
+    async fn close(&self) -> Result<(), String> {
+        self.inner.close().await
+    }
+
+    async fn status(&self) -> RuntimeStatus {
+        self.inner.status().await
+    }
+
+    async fn execute(&self, action: &str, args: Value) -> Result<String, String> {
+        match self.intercept(action, &args) {
+            Some(message) => Ok(message),
+            None => self.inner.execute(action, args).await,
+        }
+    }
+
+    async fn execute_with_attachments(
+        &self,
+        action: &str,
+        args: Value,
+    ) -> Result<ToolOutput, String> {
+        match self.intercept(action, &args) {
+            Some(message) => Ok(ToolOutput::from(message)),
+            None => self.inner.execute_with_attachments(action, args).await,
+        }
+    }
+
+    fn ports(&self) -> Option<PortRegistry> {
+        self.inner.ports()
+    }
+
+    async fn read_file(&self, path: &Path) -> Option<String> {
+        self.inner.read_file(path).await
+    }
+
+    async fn copy_in(&self, host_path: &Path, runtime_path: &str) -> Result<(), String> {
+        self.inner.copy_in(host_path, runtime_path).await
+    }
+
+    async fn copy_out(&self, runtime_path: &str, host_path: &Path) -> Result<(), String> {
+        self.inner.copy_out(runtime_path, host_path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::tools::default_tools;
+    use crate::runtime::LocalRuntime;
+    #[cfg(feature = "llm")]
+    use crate::{
+        events::Event,
+        llm::LLMConfig,
+        session::{ConversationManager, RuntimeKind},
+    };
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_dry_run_intercepts_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("a.txt"), "hello\n").unwrap();
+        let dry_run = DryRun::default();
+        let runtime = DryRunRuntime::new(
+            Box::new(LocalRuntime::new(default_tools(
+                temp_dir.path().to_path_buf(),
+            ))),
+            dry_run.clone(),
+        );
+        dry_run.set_enabled(true);
+
+        let view = json!({ "operation": "view", "path": temp_dir.path().join("a.txt") });
+        let output = runtime.execute("file_editor", view).await.unwrap();
+        assert!(output.contains("hello"));
+
+        let marker = temp_dir.path().join("marker");
+        let command = format!("touch {}", marker.display());
+        let output = runtime
+            .execute("cmd", json!({ "command": command }))
+            .await
+            .unwrap();
+        assert!(output.starts_with("[Dry run] Not executed. Recorded as step 1"));
+        assert!(!marker.exists());
+
+        let proposed = dry_run.proposed();
+        assert_eq!(proposed.len(), 1);
+        assert_eq!(proposed[0].tool_name, "cmd");
+        assert_eq!(proposed[0].command.as_deref(), Some(command.as_str()));
+
+        dry_run.set_enabled(false);
+        runtime
+            .execute("cmd", json!({ "command": command }))
+            .await
+            .unwrap();
+        assert!(marker.exists());
+        assert_eq!(dry_run.take().len(), 1);
+        assert!(dry_run.proposed().is_empty());
+    }
+
+    #[cfg(feature = "llm")]
+    #[tokio::test]
+    async fn test_execute_plan() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut manager = ConversationManager::new();
+        let conversation = manager.create_conversation_with(
+            "alice",
+            String::new(),
+            temp_dir.path().to_path_buf(),
+            LLMConfig::from_env(),
+            RuntimeKind::Local,
+        );
+        conversation.dry_run.set_enabled(true);
+        let runtime = conversation.runtime.read().await;
+        let file = temp_dir.path().join("notes.txt");
+        let create = json!({ "command": format!("printf 'one\\n' > {}", file.display()) });
+        runtime.execute("cmd", create).await.unwrap();
+        // Fails when executed: the command is missing.
+        runtime.execute("cmd", json!({})).await.unwrap();
+        runtime
+            .execute("cmd", json!({ "command": "echo never" }))
+            .await
+            .unwrap();
+        drop(runtime);
+        assert!(!file.exists());
+
+        let (events, remaining) = conversation.execute_plan().await;
+        assert!(!conversation.dry_run.is_enabled());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "one\n");
+        assert_eq!(events.len(), 4);
+        assert!(matches!(&events[0], Event::Action(a) if a.source == "plan"));
+        assert_eq!(remaining.len(), 2);
+        assert_eq!(remaining[0].arguments, json!({}));
+        assert_eq!(remaining[1].command.as_deref(), Some("echo never"));
+        assert_eq!(conversation.dry_run.proposed(), remaining);
+        assert_eq!(conversation.history.read().await.len(), 4);
+    }
+}
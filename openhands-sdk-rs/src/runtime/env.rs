@@ -0,0 +1,219 @@
+//! The environment of commands run by tools.
+//!
+//! Commands do not inherit the server's environment: only variables matching the
+//! [`EnvPolicy`] allowlist and none of its denylist are passed through, so the server's API
+//! keys stay out of the agent's shell. Secrets a conversation exposes to tools are added on
+//! top.
+//!
+//! Patterns are globs over variable names, e.g. `LC_*` or `*TOKEN*`.
+//!
+//! [`ToolEnv::command`] also applies the process-wide network [`Egress`] policy.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::sync::{Arc, RwLock};
+
+use glob::Pattern;
+use serde::Deserialize;
+
+use crate::runtime::network::{Egress, NetworkMode, egress};
+
+/// `[tool_env]` in the server configuration.
+#[derive(Deserialize, Debug, Clone)]
+pub struct EnvPolicy {
+    /// Variables passed through to commands.
+    #[serde(default = "default_allow")]
+    pub allow: Vec<String>,
+    /// Variables withheld even if allowed.
+    #[serde(default = "default_deny")]
+    pub deny: Vec<String>,
+}
+
+fn default_allow() -> Vec<String> {
+    [
+        "PATH", "HOME", "USER", "LOGNAME", "SHELL", "TERM", "LANG", "LANGUAGE", "LC_*", "TZ",
+        "TMPDIR", "HOSTNAME", "DISPLAY", "*_PROXY", "*_proxy",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn default_deny() -> Vec<String> {
+    ["*KEY*", "*TOKEN*", "*SECRET*", "*PASSWORD*", "*CREDENTIAL*"]
+        .map(String::from)
+        .to_vec()
+}
+
+impl Default for EnvPolicy {
+    fn default() -> Self {
+        Self {
+            allow: default_allow(),
+            deny: default_deny(),
+        }
+    }
+}
+
+fn matches_any(patterns: &[String], name: &str) -> bool {
+    patterns
+        .iter()
+        .any(|p| Pattern::new(p).is_ok_and(|p| p.matches(name)))
+}
+
+impl EnvPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        for pattern in self.allow.iter().chain(&self.deny) {
+            Pattern::new(pattern)
+                .map_err(|e| format!("Invalid tool_env pattern '{}': {}", pattern, e))?;
+        }
+        Ok(())
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        matches_any(&self.allow, name) && !matches_any(&self.deny, name)
+    }
+
+    /// The variables of `vars` that may be passed to commands.
+    pub fn filter(
+        &self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> BTreeMap<String, String> {
+        vars.into_iter()
+            .filter(|(name, _)| self.allows(name))
+            .collect()
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref POLICY: RwLock<Arc<EnvPolicy>> = RwLock::new(Arc::new(EnvPolicy::default()));
+    static ref EXPOSED: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+}
+
+/// Replace the process-wide policy used by [`ToolEnv::default`].
+pub fn set_policy(policy: EnvPolicy) {
+    *POLICY.write().unwrap() = Arc::new(policy);
+}
+
+/// Pass `name=value` to the commands of every [`ToolEnv`], whatever the policy, e.g. the
+/// display of a virtual screen the server started.
+pub fn expose_to_all(name: &str, value: &str) {
+    EXPOSED
+        .write()
+        .unwrap()
+        .insert(name.to_string(), value.to_string());
+}
+
+/// The environment for a set of tools: the filtered process environment plus exposed
+/// secrets, under the network policy. Clones share the exposed secrets, so secrets added
+/// later reach tools created earlier.
+#[derive(Clone)]
+pub struct ToolEnv {
+    policy: Arc<EnvPolicy>,
+    exposed: Arc<RwLock<BTreeMap<String, String>>>,
+    egress: Arc<Egress>,
+}
+
+impl Default for ToolEnv {
+    /// The process-wide policies, with no exposed secrets.
+    fn default() -> Self {
+        Self::new(POLICY.read().unwrap().clone())
+    }
+}
+
+impl ToolEnv {
+    pub fn new(policy: Arc<EnvPolicy>) -> Self {
+        Self {
+            policy,
+            exposed: Arc::default(),
+            egress: egress(),
+        }
+    }
+
+    pub fn with_egress(mut self, egress: Egress) -> Self {
+        self.egress = Arc::new(egress);
+        self
+    }
+
+    /// Pass `name=value` to commands, whatever the policy.
+    pub fn expose(&self, name: &str, value: &str) {
+        self.exposed
+            .write()
+            .unwrap()
+            .insert(name.to_string(), value.to_string());
+    }
+
+    /// The variables to run commands with; pass them after `env_clear()`. In `allowlist`
+    /// network mode they point HTTP clients at the egress proxy.
+    pub fn vars(&self) -> BTreeMap<String, String> {
+        let mut vars = self.policy.filter(std::env::vars());
+        vars.extend(self.egress.proxy_vars("127.0.0.1"));
+        vars.extend(EXPOSED.read().unwrap().clone());
+        vars.extend(self.exposed.read().unwrap().clone());
+        vars
+    }
+
+    /// A command running `program` with [`ToolEnv::vars`]. In `none` network mode it runs
+    /// in a network namespace of its own, with only a loopback interface.
+    pub fn command(&self, program: &str) -> Command {
+        let mut command = match self.egress.policy.mode {
+            NetworkMode::None => {
+                let mut command = Command::new("unshare");
+                command.args(["--net", "--map-current-user", "--", program]);
+                command
+            }
+            NetworkMode::Full | NetworkMode::Allowlist => Command::new(program),
+        };
+        command.env_clear().envs(self.vars());
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::network::NetworkPolicy;
+
+    #[test]
+    fn test_policy() {
+        let policy = EnvPolicy::default();
+        let vars = [
+            ("PATH", "/usr/bin"),
+            ("LC_ALL", "C.UTF-8"),
+            ("OPENAI_API_KEY", "sk-..."),
+            ("GITHUB_TOKEN", "ghp_..."),
+            ("DATABASE_URL", "postgres://..."),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let kept: Vec<String> = policy.filter(vars.clone()).into_keys().collect();
+        assert_eq!(kept, ["LC_ALL", "PATH"]);
+
+        let policy = EnvPolicy {
+            allow: vec!["*".to_string()],
+            deny: vec!["*_KEY".to_string()],
+        };
+        let kept: Vec<String> = policy.filter(vars).into_keys().collect();
+        assert_eq!(kept, ["DATABASE_URL", "GITHUB_TOKEN", "LC_ALL", "PATH"]);
+
+        let env = ToolEnv::new(Arc::new(EnvPolicy::default()));
+        env.clone().expose("NPM_TOKEN", "npm_secret");
+        assert_eq!(env.vars().get("NPM_TOKEN").unwrap(), "npm_secret");
+        assert!(!env.vars().contains_key("HTTPS_PROXY"));
+
+        let env = env.with_egress(Egress {
+            policy: NetworkPolicy {
+                mode: NetworkMode::Allowlist,
+                hosts: vec!["pypi.org".to_string()],
+            },
+            proxy_port: Some(3128),
+        });
+        assert_eq!(env.vars()["HTTPS_PROXY"], "http://127.0.0.1:3128");
+        assert_eq!(env.vars()["no_proxy"], "localhost,127.0.0.1,::1");
+        assert!(
+            EnvPolicy {
+                allow: vec!["[".to_string()],
+                deny: vec![],
+            }
+            .validate()
+            .is_err()
+        );
+    }
+}
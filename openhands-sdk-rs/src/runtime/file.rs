@@ -1,6 +1,10 @@
-use crate::models::{FileReadRequest, FileResponse, FileWriteRequest};
+use crate::models::{
+    FileReadRequest, FileResponse, FileTransferRequest, FileTransferResponse, FileWriteRequest,
+};
+use crate::paths::{confine, encode_path};
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
 
 pub struct FileService {
     pub workspace_dir: PathBuf,
@@ -33,15 +37,15 @@ impl FileService {
     pub fn write_file(&self, req: FileWriteRequest) -> FileResponse {
         let path = self.workspace_dir.join(&req.path);
 
-        if let Some(parent) = path.parent() {
-            if let Err(e) = fs::create_dir_all(parent) {
-                return FileResponse {
-                    path: req.path,
-                    content: None,
-                    success: false,
-                    error: Some(format!("Failed to create parent directory: {}", e)),
-                };
-            }
+        if let Some(parent) = path.parent()
+            && let Err(e) = fs::create_dir_all(parent)
+        {
+            return FileResponse {
+                path: req.path,
+                content: None,
+                success: false,
+                error: Some(format!("Failed to create parent directory: {}", e)),
+            };
         }
 
         match fs::write(&path, &req.content) {
@@ -59,4 +63,204 @@ impl FileService {
             },
         }
     }
+
+    pub fn move_path(&self, req: FileTransferRequest) -> FileTransferResponse {
+        move_path(&self.workspace_dir, req)
+    }
+
+    pub fn copy_path(&self, req: FileTransferRequest) -> FileTransferResponse {
+        copy_path(&self.workspace_dir, req)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Transfer {
+    Move,
+    Copy,
+}
+
+/// Move (rename) a file or directory within `workspace_dir`.
+pub fn move_path(workspace_dir: &Path, req: FileTransferRequest) -> FileTransferResponse {
+    transfer(workspace_dir, req, Transfer::Move)
+}
+
+/// Copy a file or directory within `workspace_dir`. Symlinks are copied as links.
+pub fn copy_path(workspace_dir: &Path, req: FileTransferRequest) -> FileTransferResponse {
+    transfer(workspace_dir, req, Transfer::Copy)
+}
+
+fn transfer(
+    workspace_dir: &Path,
+    req: FileTransferRequest,
+    kind: Transfer,
+) -> FileTransferResponse {
+    let mut response = FileTransferResponse {
+        source: req.source.clone(),
+        destination: req.destination.clone(),
+        success: false,
+        is_dir: false,
+        files: 0,
+        replaced: false,
+        error: None,
+    };
+    match try_transfer(workspace_dir, &req, kind, &mut response) {
+        Ok(()) => response.success = true,
+        Err(e) => response.error = Some(e),
+    }
+    response
+}
+
+fn try_transfer(
+    workspace_dir: &Path,
+    req: &FileTransferRequest,
+    kind: Transfer,
+    response: &mut FileTransferResponse,
+) -> Result<(), String> {
+    let source = confine(workspace_dir, &req.source)?;
+    let destination = confine(workspace_dir, &req.destination)?;
+    let metadata =
+        fs::symlink_metadata(&source).map_err(|_| format!("Source not found: {}", req.source))?;
+    response.is_dir = metadata.is_dir();
+    if source == destination {
+        return Err("Source and destination are the same path".to_string());
+    }
+    if response.is_dir && destination.starts_with(&source) {
+        return Err(format!(
+            "Cannot put directory {} inside itself",
+            encode_path(&source)
+        ));
+    }
+
+    if let Ok(existing) = fs::symlink_metadata(&destination) {
+        if !req.overwrite {
+            return Err(format!(
+                "Destination already exists: {}. Set overwrite to replace it.",
+                req.destination
+            ));
+        }
+        let removed = if existing.is_dir() {
+            fs::remove_dir_all(&destination)
+        } else {
+            fs::remove_file(&destination)
+        };
+        removed.map_err(|e| format!("Failed to replace {}: {}", req.destination, e))?;
+        response.replaced = true;
+    }
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+    }
+
+    response.files = count_files(&source);
+    let result = match kind {
+        Transfer::Copy => copy_recursive(&source, &destination),
+        Transfer::Move => match fs::rename(&source, &destination) {
+            // Across filesystems, e.g. into a mounted volume.
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                copy_recursive(&source, &destination).and_then(|()| {
+                    if response.is_dir {
+                        fs::remove_dir_all(&source)
+                    } else {
+                        fs::remove_file(&source)
+                    }
+                })
+            }
+            result => result,
+        },
+    };
+    result.map_err(|e| e.to_string())
+}
+
+fn count_files(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| !entry.file_type().is_dir())
+        .count() as u64
+}
+
+/// Copy `source` to `destination`, recreating symlinks as links rather than following them.
+/// Directories are merged into an existing `destination`.
+pub(crate) fn copy_recursive(source: &Path, destination: &Path) -> io::Result<()> {
+    let file_type = fs::symlink_metadata(source)?.file_type();
+    if file_type.is_symlink() {
+        copy_symlink(source, destination)
+    } else if file_type.is_dir() {
+        fs::create_dir_all(destination)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(source, destination).map(|_| ())
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(source: &Path, destination: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(source)?, destination)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(source: &Path, destination: &Path) -> io::Result<()> {
+    fs::copy(source, destination).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(source: &str, destination: &str, overwrite: bool) -> FileTransferRequest {
+        FileTransferRequest {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            overwrite,
+        }
+    }
+
+    #[test]
+    fn test_move_and_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = FileService::new(dir.path().to_path_buf());
+        let ws = dir.path();
+        fs::create_dir_all(ws.join("src/nested")).unwrap();
+        fs::write(ws.join("src/a.rs"), "a").unwrap();
+        fs::write(ws.join("src/nested/b.rs"), "b").unwrap();
+        fs::write(ws.join("notes.txt"), "notes").unwrap();
+
+        let copied = service.copy_path(request("src", "backup/src", false));
+        assert!(copied.success, "{:?}", copied.error);
+        assert!(copied.is_dir);
+        assert_eq!(copied.files, 2);
+        assert_eq!(
+            fs::read_to_string(ws.join("backup/src/nested/b.rs")).unwrap(),
+            "b"
+        );
+        assert!(ws.join("src/a.rs").exists());
+
+        let moved = service.move_path(request("notes.txt", "docs/notes.md", false));
+        assert!(moved.success);
+        assert!(!ws.join("notes.txt").exists());
+        assert_eq!(
+            fs::read_to_string(ws.join("docs/notes.md")).unwrap(),
+            "notes"
+        );
+
+        let refused = service.move_path(request("src/a.rs", "docs/notes.md", false));
+        assert!(!refused.success);
+        assert!(refused.error.unwrap().contains("already exists"));
+        assert!(ws.join("src/a.rs").exists());
+        let replaced = service.move_path(request("src/a.rs", "docs/notes.md", true));
+        assert!(replaced.success && replaced.replaced);
+        assert_eq!(fs::read_to_string(ws.join("docs/notes.md")).unwrap(), "a");
+
+        let into_itself = service.copy_path(request("src", "src/nested/copy", false));
+        assert!(into_itself.error.unwrap().contains("inside itself"));
+        let outside = service.copy_path(request("src", "../escaped", false));
+        assert!(outside.error.unwrap().contains("outside the workspace"));
+        assert!(!dir.path().parent().unwrap().join("escaped").exists());
+        let missing = service.move_path(request("gone.txt", "x.txt", false));
+        assert!(missing.error.unwrap().starts_with("Source not found"));
+    }
 }
@@ -1,7 +1,9 @@
-use crate::agent::tools::Tool;
+use crate::agent::tools::{Tool, ToolOutput};
 use crate::runtime::Runtime;
+use crate::runtime::file::copy_recursive;
 use async_trait::async_trait;
 use serde_json::Value;
+use std::path::{Path, PathBuf};
 
 /// A local runtime implementation that executes tools directly on the host machine
 /// (or within the same container if the agent itself is containerized).
@@ -9,13 +11,81 @@ use serde_json::Value;
 /// This runtime uses the provided tool implementations directly.
 pub struct LocalRuntime {
     tools: Vec<Box<dyn Tool>>,
+    /// Base of relative paths in `copy_in`/`copy_out`; the process cwd if unset.
+    working_dir: Option<PathBuf>,
 }
 
 impl LocalRuntime {
     /// Create a new LocalRuntime with the given set of tools.
     pub fn new(tools: Vec<Box<dyn Tool>>) -> Self {
-        Self { tools }
+        Self {
+            tools,
+            working_dir: None,
+        }
+    }
+
+    pub fn with_working_dir(mut self, working_dir: PathBuf) -> Self {
+        self.working_dir = Some(working_dir);
+        self
+    }
+
+    fn resolve(&self, path: &str) -> PathBuf {
+        match &self.working_dir {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        }
+    }
+}
+
+/// Copy `from` to `to` with `docker cp` semantics: onto an existing directory, `from` is
+/// copied inside it. Symlinks are copied as links.
+fn copy_path(from: &Path, to: &Path) -> Result<(), String> {
+    let to = match from.file_name() {
+        Some(name) if to.is_dir() => to.join(name),
+        _ => to.to_path_buf(),
+    };
+    let source = from
+        .canonicalize()
+        .map_err(|e| format!("{}: {}", from.display(), e))?;
+    if resolve_existing(&to).starts_with(&source) {
+        return Err(format!("Cannot copy {} inside itself", from.display()));
     }
+    copy_recursive(from, &to).map_err(|e| {
+        format!(
+            "Failed to copy {} to {}: {}",
+            from.display(),
+            to.display(),
+            e
+        )
+    })
+}
+
+/// Canonicalize the longest existing ancestor of `path` and re-append the rest, so a
+/// destination that does not exist yet still compares against the real source path.
+fn resolve_existing(path: &Path) -> PathBuf {
+    let mut missing = Vec::new();
+    let mut current = path;
+    loop {
+        if let Ok(resolved) = current.canonicalize() {
+            return missing
+                .iter()
+                .rev()
+                .fold(resolved, |acc, part| acc.join(part));
+        }
+        match (current.file_name(), current.parent()) {
+            (Some(name), Some(parent)) => {
+                missing.push(name.to_os_string());
+                current = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+async fn copy_blocking(from: PathBuf, to: PathBuf) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || copy_path(&from, &to))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
 #[async_trait]
@@ -31,4 +101,100 @@ impl Runtime for LocalRuntime {
             Err(format!("Tool {} not found", action))
         }
     }
+
+    async fn execute_with_attachments(
+        &self,
+        action: &str,
+        args: Value,
+    ) -> Result<ToolOutput, String> {
+        match self.tools.iter().find(|t| t.name() == action) {
+            Some(tool) => tool.call_with_attachments(args).await,
+            None => Err(format!("Tool {} not found", action)),
+        }
+    }
+
+    async fn read_file(&self, path: &Path) -> Option<String> {
+        std::fs::read_to_string(self.resolve(&path.to_string_lossy())).ok()
+    }
+
+    async fn copy_in(&self, host_path: &Path, runtime_path: &str) -> Result<(), String> {
+        copy_blocking(host_path.to_path_buf(), self.resolve(runtime_path)).await
+    }
+
+    async fn copy_out(&self, runtime_path: &str, host_path: &Path) -> Result<(), String> {
+        copy_blocking(self.resolve(runtime_path), host_path.to_path_buf()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_copy_in_and_out() {
+        let host = TempDir::new().unwrap();
+        let workspace = TempDir::new().unwrap();
+        std::fs::create_dir_all(host.path().join("inputs/nested")).unwrap();
+        std::fs::write(host.path().join("inputs/nested/data.csv"), "a,b").unwrap();
+        let runtime = LocalRuntime::new(Vec::new()).with_working_dir(workspace.path().into());
+
+        runtime
+            .copy_in(&host.path().join("inputs"), "seed")
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(workspace.path().join("seed/nested/data.csv")).unwrap(),
+            "a,b"
+        );
+        assert_eq!(
+            runtime
+                .read_file(Path::new("seed/nested/data.csv"))
+                .await
+                .as_deref(),
+            Some("a,b")
+        );
+        assert_eq!(runtime.read_file(Path::new("missing")).await, None);
+
+        // Onto an existing directory, the source lands inside it.
+        std::fs::create_dir(workspace.path().join("dist")).unwrap();
+        runtime
+            .copy_in(&host.path().join("inputs/nested/data.csv"), "dist")
+            .await
+            .unwrap();
+        runtime
+            .copy_out("dist", &host.path().join("artifacts"))
+            .await
+            .unwrap();
+        assert!(host.path().join("artifacts/data.csv").is_file());
+        assert!(runtime.copy_out("missing", host.path()).await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_copy_keeps_links_and_refuses_self_nesting() {
+        let host = TempDir::new().unwrap();
+        let workspace = TempDir::new().unwrap();
+        std::fs::create_dir(workspace.path().join("out")).unwrap();
+        std::os::unix::fs::symlink("/etc/passwd", workspace.path().join("out/passwd")).unwrap();
+        let runtime = LocalRuntime::new(Vec::new()).with_working_dir(workspace.path().into());
+
+        runtime
+            .copy_out("out", &host.path().join("artifacts"))
+            .await
+            .unwrap();
+        let copied = host.path().join("artifacts/passwd");
+        assert!(std::fs::symlink_metadata(&copied).unwrap().is_symlink());
+        assert_eq!(
+            std::fs::read_link(&copied).unwrap(),
+            Path::new("/etc/passwd")
+        );
+
+        let error = runtime
+            .copy_in(&workspace.path().join("out"), "out/nested")
+            .await
+            .unwrap_err();
+        assert!(error.contains("inside itself"), "{}", error);
+        assert!(!workspace.path().join("out/nested").exists());
+    }
 }
@@ -1,13 +1,43 @@
+pub mod baseline;
 pub mod bash;
+#[cfg(feature = "docker-runtime")]
+pub mod devcontainer;
+#[cfg(feature = "docker-runtime")]
 pub mod docker;
+#[cfg(feature = "http")]
+pub mod download;
+pub mod dry_run;
+pub mod env;
 pub mod file;
 pub mod local;
+pub mod network;
+#[cfg(feature = "docker-runtime")]
+pub mod pool;
+pub mod ports;
+#[cfg(feature = "remote-runtime")]
 pub mod remote;
+#[cfg(feature = "docker-runtime")]
+pub mod sandbox;
 
-use crate::agent::tools::Tool;
+use crate::agent::tools::{Tool, ToolOutput};
 use async_trait::async_trait;
-pub use docker::DockerRuntime;
+#[cfg(feature = "docker-runtime")]
+pub use docker::{DockerRuntime, DockerRuntimeBuilder};
+use ports::PortRegistry;
 use serde_json::Value;
+use std::path::Path;
+
+/// Whether a runtime can execute tools, from [`Runtime::status`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeStatus {
+    /// Started but not answering yet, e.g. a container whose agent server is booting.
+    Starting,
+    Ready,
+    /// Not answering, and why.
+    Unavailable(String),
+    /// Torn down by [`Runtime::close`].
+    Closed,
+}
 
 #[async_trait]
 /// Defines the runtime environment where the agent executes tools.
@@ -19,6 +49,23 @@ pub trait Runtime: Send + Sync {
     /// List available tools that the agent can call in this runtime.
     fn tools(&self) -> &[Box<dyn Tool>];
 
+    /// Wait until the runtime can execute tools, e.g. until a container's agent server
+    /// answers. Returns at once on a connected runtime; runtimes that are ready once
+    /// constructed keep the default.
+    async fn connect(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Release what the runtime holds, e.g. stop its container. Nothing is executed after.
+    async fn close(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Probe whether the runtime can execute tools right now.
+    async fn status(&self) -> RuntimeStatus {
+        RuntimeStatus::Ready
+    }
+
     /// Execute a tool action with the given arguments.
     ///
     /// # Arguments
@@ -29,7 +76,44 @@ pub trait Runtime: Send + Sync {
     /// * `Ok(String)` - The output of the tool execution.
     /// * `Err(String)` - An error message if execution fails.
     async fn execute(&self, action: &str, args: Value) -> Result<String, String>;
+
+    /// Like [`Runtime::execute`], keeping media the tool returned (see
+    /// [`Tool::call_with_attachments`]). Runtimes that cannot transfer media keep the default.
+    async fn execute_with_attachments(
+        &self,
+        action: &str,
+        args: Value,
+    ) -> Result<ToolOutput, String> {
+        self.execute(action, args).await.map(ToolOutput::from)
+    }
+
+    /// Ports forwarded out of the runtime's sandbox, if it has one.
+    fn ports(&self) -> Option<PortRegistry> {
+        None
+    }
+
+    /// The text of the file at `path` in the runtime, `None` if it is missing or cannot be
+    /// read. Relative paths are relative to the runtime's workspace. The agent reads files
+    /// through this to record the changes its tools make; runtimes that cannot read files
+    /// keep the default and no changes are recorded.
+    async fn read_file(&self, _path: &Path) -> Option<String> {
+        None
+    }
+
+    /// Copy a file or directory from the host into the runtime, like `docker cp`: a
+    /// directory copied onto an existing directory ends up inside it. Relative
+    /// `runtime_path`s are relative to the runtime's workspace.
+    async fn copy_in(&self, _host_path: &Path, _runtime_path: &str) -> Result<(), String> {
+        Err("This runtime does not support file transfer".to_string())
+    }
+
+    /// Copy a file or directory out of the runtime to the host, with the semantics of
+    /// [`Runtime::copy_in`].
+    async fn copy_out(&self, _runtime_path: &str, _host_path: &Path) -> Result<(), String> {
+        Err("This runtime does not support file transfer".to_string())
+    }
 }
 
 pub use local::LocalRuntime;
+#[cfg(feature = "remote-runtime")]
 pub use remote::RemoteRuntime;
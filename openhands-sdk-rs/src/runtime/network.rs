@@ -0,0 +1,396 @@
+//! Network egress policy for commands run by tools.
+//!
+//! - `full`: no restrictions.
+//! - `none`: no network. Local commands run in their own network namespace (`unshare`);
+//!   sandbox containers join an internal Docker network with no route out.
+//! - `allowlist`: HTTP(S) only, to the listed hosts, through an [`EgressProxy`] that
+//!   filters on the requested host. Sandbox containers are on the internal network, so the
+//!   proxy is their only way out; local commands are pointed at it with `HTTP(S)_PROXY`,
+//!   which well-behaved clients honour but cannot be enforced.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Docker network of sandboxes whose policy is not `full`.
+pub const INTERNAL_NETWORK: &str = "openhands-egress";
+
+/// Largest request head the proxy reads before giving up.
+const MAX_HEAD_LEN: usize = 16 * 1024;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkMode {
+    #[default]
+    Full,
+    None,
+    Allowlist,
+}
+
+/// `[network]` in the server configuration.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct NetworkPolicy {
+    #[serde(default)]
+    pub mode: NetworkMode,
+    /// Hosts reachable in `allowlist` mode. `*.example.com` matches the subdomains of
+    /// `example.com`.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+}
+
+impl NetworkPolicy {
+    pub fn allows_host(&self, host: &str) -> bool {
+        match self.mode {
+            NetworkMode::Full => true,
+            NetworkMode::None => false,
+            NetworkMode::Allowlist => self.lists_host(host),
+        }
+    }
+
+    /// Whether `host` matches an entry of `hosts`, whatever the mode.
+    pub fn lists_host(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.hosts.iter().any(|allowed| {
+            let allowed = allowed.to_ascii_lowercase();
+            match allowed.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.ends_with('.')),
+                None => host == allowed,
+            }
+        })
+    }
+}
+
+/// The process-wide network restrictions for commands.
+#[derive(Debug, Clone, Default)]
+pub struct Egress {
+    pub policy: NetworkPolicy,
+    /// Port of the [`EgressProxy`] in `allowlist` mode.
+    pub proxy_port: Option<u16>,
+}
+
+impl Egress {
+    /// Proxy variables for commands reaching the proxy at `proxy_host`; empty unless the
+    /// policy is `allowlist`.
+    pub fn proxy_vars(&self, proxy_host: &str) -> Vec<(String, String)> {
+        let Some(port) = self
+            .proxy_port
+            .filter(|_| self.policy.mode == NetworkMode::Allowlist)
+        else {
+            return Vec::new();
+        };
+        let url = format!("http://{}:{}", proxy_host, port);
+        let mut vars = Vec::new();
+        for name in ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"] {
+            vars.push((name.to_string(), url.clone()));
+            vars.push((name.to_lowercase(), url.clone()));
+        }
+        for name in ["NO_PROXY", "no_proxy"] {
+            vars.push((name.to_string(), "localhost,127.0.0.1,::1".to_string()));
+        }
+        vars
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref EGRESS: RwLock<Arc<Egress>> = RwLock::new(Arc::new(Egress::default()));
+}
+
+pub fn set_egress(egress: Egress) {
+    *EGRESS.write().unwrap() = Arc::new(egress);
+}
+
+pub fn egress() -> Arc<Egress> {
+    EGRESS.read().unwrap().clone()
+}
+
+/// An HTTP proxy forwarding only to hosts the policy allows: `CONNECT` tunnels for HTTPS and
+/// absolute-form requests for plain HTTP. Other requests are answered with `403`.
+///
+/// It accepts connections from loopback and private addresses only (the host and its
+/// sandbox containers).
+pub struct EgressProxy {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl EgressProxy {
+    pub async fn start(bind: IpAddr, policy: NetworkPolicy) -> Result<Self, String> {
+        let listener = TcpListener::bind((bind, 0))
+            .await
+            .map_err(|e| format!("Failed to start egress proxy: {}", e))?;
+        let addr = listener.local_addr().map_err(|e| e.to_string())?;
+        let policy = Arc::new(policy);
+        let task = tokio::spawn(async move {
+            loop {
+                let Ok((client, peer)) = listener.accept().await else {
+                    continue;
+                };
+                if !is_local(peer.ip()) {
+                    tracing::warn!("Egress proxy refused connection from {}", peer);
+                    continue;
+                }
+                let policy = policy.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle(client, &policy).await {
+                        tracing::debug!("Egress proxy connection failed: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(Self { addr, task })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+}
+
+impl Drop for EgressProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn is_local(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_private(),
+        IpAddr::V6(ip) => {
+            ip.is_loopback()
+                || ip
+                    .to_ipv4_mapped()
+                    .is_some_and(|ip| is_local(IpAddr::V4(ip)))
+        }
+    }
+}
+
+/// The request head (through the blank line) and whatever followed it in the same reads.
+async fn read_head(client: &mut TcpStream) -> Result<(String, Vec<u8>), String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buf.split_off(end + 4);
+            return Ok((String::from_utf8_lossy(&buf).into_owned(), rest));
+        }
+        if buf.len() > MAX_HEAD_LEN {
+            return Err("Request head too large".to_string());
+        }
+        let n = client.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Err("Connection closed before the request head".to_string());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// Host, port and origin-form path of an absolute `http://` URL.
+fn split_http_url(url: &str) -> Option<(String, u16, String)> {
+    let scheme = url.get(..7)?;
+    if !scheme.eq_ignore_ascii_case("http://") {
+        return None;
+    }
+    let rest = &url[7..];
+    let rest = rest.split_once('#').map_or(rest, |(rest, _)| rest);
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let (host, port) = host_port(authority, 80);
+    if host.is_empty() {
+        return None;
+    }
+    let path = match path {
+        "" => "/".to_string(),
+        p if p.starts_with('?') => format!("/{}", p),
+        p => p.to_string(),
+    };
+    Some((host.to_ascii_lowercase(), port, path))
+}
+
+/// Split `host:port`, with `default_port` if there is none.
+fn host_port(authority: &str, default_port: u16) -> (String, u16) {
+    if let Some((host, port)) = authority.rsplit_once(':')
+        && !host.ends_with(':')
+        && let Ok(port) = port.parse()
+    {
+        return (host.to_string(), port);
+    }
+    (authority.to_string(), default_port)
+}
+
+async fn respond(client: &mut TcpStream, status: &str, body: &str) -> Result<(), String> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    client
+        .write_all(response.as_bytes())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn handle(mut client: TcpStream, policy: &NetworkPolicy) -> Result<(), String> {
+    let (head, rest) = read_head(&mut client).await?;
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(m), Some(t), Some(v)) => (m, t, v),
+        _ => return respond(&mut client, "400 Bad Request", "Malformed request\n").await,
+    };
+
+    let (host, port, forward) = if method.eq_ignore_ascii_case("CONNECT") {
+        let (host, port) = host_port(target, 443);
+        (host, port, None)
+    } else {
+        let Some((host, port, path)) = split_http_url(target) else {
+            return respond(
+                &mut client,
+                "400 Bad Request",
+                "Only absolute http:// URLs and CONNECT are proxied\n",
+            )
+            .await;
+        };
+        // Origin-form request line, without hop-by-hop proxy headers.
+        let mut forward = format!("{} {} {}\r\n", method, path, version);
+        for line in lines.filter(|l| !l.is_empty()) {
+            let name = line
+                .split(':')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            if !name.starts_with("proxy-") {
+                forward.push_str(line);
+                forward.push_str("\r\n");
+            }
+        }
+        forward.push_str("\r\n");
+        (host, port, Some(forward))
+    };
+
+    if !policy.allows_host(&host) {
+        tracing::warn!("Blocked egress to {}:{}", host, port);
+        let body = format!("Egress to {} is not allowed by the network policy\n", host);
+        return respond(&mut client, "403 Forbidden", &body).await;
+    }
+    let mut upstream = match TcpStream::connect((host.as_str(), port)).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            let body = format!("Failed to connect to {}:{}: {}\n", host, port, e);
+            return respond(&mut client, "502 Bad Gateway", &body).await;
+        }
+    };
+    match forward {
+        None => client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await
+            .map_err(|e| e.to_string())?,
+        Some(forward) => upstream
+            .write_all(forward.as_bytes())
+            .await
+            .map_err(|e| e.to_string())?,
+    }
+    upstream.write_all(&rest).await.map_err(|e| e.to_string())?;
+    tokio::io::copy_bidirectional(&mut client, &mut upstream)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allows_host() {
+        let policy = NetworkPolicy {
+            mode: NetworkMode::Allowlist,
+            hosts: vec!["pypi.org".to_string(), "*.github.com".to_string()],
+        };
+        assert!(policy.allows_host("pypi.org"));
+        assert!(policy.allows_host("PyPI.org."));
+        assert!(policy.allows_host("api.github.com"));
+        assert!(!policy.allows_host("github.com"));
+        assert!(!policy.allows_host("evilgithub.com"));
+        assert!(!policy.allows_host("pypi.org.evil.com"));
+        assert!(
+            !NetworkPolicy {
+                mode: NetworkMode::None,
+                hosts: vec!["pypi.org".to_string()],
+            }
+            .allows_host("pypi.org")
+        );
+    }
+
+    #[test]
+    fn test_split_http_url() {
+        let split = |url| split_http_url(url).unwrap();
+        assert_eq!(split("http://PyPI.org"), ("pypi.org".into(), 80, "/".into()));
+        assert_eq!(
+            split("HTTP://localhost:8080/simple?q=1#top"),
+            ("localhost".into(), 8080, "/simple?q=1".into())
+        );
+        assert_eq!(
+            split("http://pypi.org@evil.com?x"),
+            ("evil.com".into(), 80, "/?x".into())
+        );
+        assert_eq!(split_http_url("https://pypi.org/"), None);
+        assert_eq!(split_http_url("/relative"), None);
+        assert_eq!(split_http_url("http:///path"), None);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_filters_hosts() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_port = upstream.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = upstream.accept().await.unwrap();
+            let (head, _) = read_head(&mut socket).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await
+                .unwrap();
+            head
+        });
+
+        let policy = NetworkPolicy {
+            mode: NetworkMode::Allowlist,
+            hosts: vec!["127.0.0.1".to_string()],
+        };
+        let proxy = EgressProxy::start("127.0.0.1".parse().unwrap(), policy)
+            .await
+            .unwrap();
+        let port = proxy.port();
+        let request = |target: String| async move {
+            let mut socket = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            let request = format!(
+                "GET {} HTTP/1.1\r\nHost: x\r\nProxy-Connection: keep-alive\r\n\r\n",
+                target
+            );
+            socket.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            socket.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let allowed = request(format!("http://127.0.0.1:{}/a?b=1", upstream_port)).await;
+        assert!(allowed.starts_with("HTTP/1.1 200 OK"));
+        assert!(allowed.ends_with("ok"));
+        let head = server.await.unwrap();
+        assert!(head.starts_with("GET /a?b=1 HTTP/1.1\r\n"));
+        assert!(!head.to_lowercase().contains("proxy-connection"));
+
+        let denied = request("http://example.com/".to_string()).await;
+        assert!(denied.starts_with("HTTP/1.1 403 Forbidden"));
+    }
+}
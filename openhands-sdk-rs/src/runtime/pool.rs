@@ -0,0 +1,293 @@
+//! A warm pool of sandbox containers, so conversations do not wait for `docker run` and the
+//! agent server to boot.
+//!
+//! [`SandboxPool::lease`] hands out an idle container as a [`DockerRuntime`] and starts a
+//! replacement in the background. Dropping the runtime returns the container: it is recycled
+//! (workspace wiped, container restarted to reset processes) and put back, or destroyed once
+//! it has served `max_uses` conversations or the pool is already full.
+//!
+//! A recycled container keeps what its user left outside the workspace (home directory,
+//! `/tmp`, installed packages), so it is only leased again to the same owner. Fresh
+//! containers go to anyone. Containers join the pool once their agent server answers.
+//!
+//! Configured with `SANDBOX_POOL_SIZE` (idle containers to keep, 0 disables the pool) and
+//! `SANDBOX_POOL_MAX_USES` (conversations per container, default 10).
+
+use std::collections::VecDeque;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::agent::tools::Tool;
+use crate::runtime::{DockerRuntime, RemoteRuntime, Runtime};
+
+const DEFAULT_MAX_USES: u32 = 10;
+
+/// A running agent server container owned by the pool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PooledContainer {
+    pub id: String,
+    pub api_url: String,
+    /// The user whose conversations the container served; `None` while fresh.
+    pub owner: Option<String>,
+    /// Conversations the container has served.
+    pub uses: u32,
+}
+
+pub struct SandboxPool {
+    image: String,
+    size: usize,
+    max_uses: u32,
+    idle: Mutex<VecDeque<PooledContainer>>,
+    /// Containers being started or recycled, counted towards `size`.
+    pending: AtomicUsize,
+}
+
+impl SandboxPool {
+    pub fn new(image: &str, size: usize, max_uses: u32) -> Arc<Self> {
+        Arc::new(Self {
+            image: image.to_string(),
+            size,
+            max_uses: max_uses.max(1),
+            idle: Mutex::new(VecDeque::new()),
+            pending: AtomicUsize::new(0),
+        })
+    }
+
+    /// A pool of `image` containers configured from the environment, or `None` when
+    /// `SANDBOX_POOL_SIZE` is unset or 0.
+    pub fn from_env(image: &str) -> Result<Option<Arc<Self>>, String> {
+        let size = match std::env::var("SANDBOX_POOL_SIZE") {
+            Ok(size) => size
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid SANDBOX_POOL_SIZE: {}", size))?,
+            Err(_) => 0,
+        };
+        let max_uses = match std::env::var("SANDBOX_POOL_MAX_USES") {
+            Ok(uses) => uses
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid SANDBOX_POOL_MAX_USES: {}", uses))?,
+            Err(_) => DEFAULT_MAX_USES,
+        };
+        Ok((size > 0).then(|| Self::new(image, size, max_uses)))
+    }
+
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    /// Number of idle containers the pool keeps.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of containers ready to be leased.
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    /// Start containers in the background until the pool is full.
+    pub fn fill(self: &Arc<Self>) {
+        loop {
+            let filled = self.idle_count() + self.pending.load(Ordering::SeqCst);
+            if filled >= self.size {
+                return;
+            }
+            self.pending.fetch_add(1, Ordering::SeqCst);
+            let pool = self.clone();
+            std::thread::spawn(move || {
+                match start(&pool.image) {
+                    Ok(container) => pool.idle.lock().unwrap().push_back(container),
+                    Err(e) => tracing::warn!("Failed to start pooled sandbox: {}", e),
+                }
+                pool.pending.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    }
+
+    /// A runtime for `owner` on an idle container, or on a new one started on connect if
+    /// none is ready. The container goes back to the pool when the runtime is closed or
+    /// dropped.
+    pub fn lease(self: &Arc<Self>, owner: &str, tools: Vec<Box<dyn Tool>>) -> DockerRuntime {
+        let idle = self.take_idle(owner);
+        self.fill();
+
+        let uses = idle.as_ref().map_or(0, |container| container.uses);
+        let mut runtime = match idle {
+            Some(container) => {
+                DockerRuntime::attach(container.id, &self.image, container.api_url, tools)
+            }
+            // Started on connect, and pooled afterwards like the others.
+            None => DockerRuntime::pending(&self.image, Vec::new(), tools),
+        };
+        *runtime.lease.get_mut().unwrap() = Some((self.clone(), owner.to_string(), uses + 1));
+        runtime
+    }
+
+    /// An idle container `owner` may use: one it used before, otherwise a fresh one.
+    fn take_idle(&self, owner: &str) -> Option<PooledContainer> {
+        let mut idle = self.idle.lock().unwrap();
+        let index = idle
+            .iter()
+            .position(|c| c.owner.as_deref() == Some(owner))
+            .or_else(|| idle.iter().position(|c| c.owner.is_none()))?;
+        idle.remove(index)
+    }
+
+    /// Whether a returned container is worth recycling rather than destroying.
+    fn should_recycle(&self, container: &PooledContainer) -> bool {
+        container.uses < self.max_uses
+            && self.idle_count() + self.pending.load(Ordering::SeqCst) < self.size
+    }
+
+    /// Take back a leased container, recycling or destroying it in the background.
+    pub fn release(self: &Arc<Self>, container: PooledContainer) {
+        if !self.should_recycle(&container) {
+            std::thread::spawn(move || destroy(&container.id));
+            return;
+        }
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        let pool = self.clone();
+        std::thread::spawn(move || {
+            match recycle(&container.id, &container.api_url) {
+                Ok(api_url) => pool.idle.lock().unwrap().push_back(PooledContainer {
+                    api_url,
+                    ..container
+                }),
+                Err(e) => {
+                    tracing::warn!("Failed to recycle sandbox {}: {}", container.id, e);
+                    destroy(&container.id);
+                }
+            }
+            pool.pending.fetch_sub(1, Ordering::SeqCst);
+            pool.fill();
+        });
+    }
+
+    /// Destroy all idle containers, e.g. on shutdown.
+    pub fn drain(&self) {
+        let idle: Vec<PooledContainer> = self.idle.lock().unwrap().drain(..).collect();
+        for container in idle {
+            destroy(&container.id);
+        }
+    }
+}
+
+/// Start a fresh container and wait for its agent server.
+fn start(image: &str) -> Result<PooledContainer, String> {
+    let id = DockerRuntime::container_name();
+    let api_url = DockerRuntime::run_container(&id, image, &[])?;
+    if let Err(e) = wait_ready(&api_url) {
+        destroy(&id);
+        return Err(e);
+    }
+    Ok(PooledContainer {
+        id,
+        api_url,
+        owner: None,
+        uses: 0,
+    })
+}
+
+/// Reset a container for the next conversation: wipe the workspace (dotfiles included) and
+/// restart it, which stops anything the previous conversation left running. Returns the
+/// container's API URL, which changes with its address on the internal network.
+fn recycle(container_id: &str, api_url: &str) -> Result<String, String> {
+    let wiped = Command::new("docker")
+        .args([
+            "exec",
+            container_id,
+            "sh",
+            "-c",
+            "rm -rf /workspace/* /workspace/.[!.]* /workspace/..?*",
+        ])
+        .status()
+        .map_err(|e| format!("Failed to run docker: {}", e))?;
+    if !wiped.success() {
+        return Err("Failed to wipe the workspace".to_string());
+    }
+
+    let restarted = Command::new("docker")
+        .args(["restart", container_id])
+        .output()
+        .map_err(|e| format!("Failed to run docker: {}", e))?;
+    if !restarted.status.success() {
+        return Err(String::from_utf8_lossy(&restarted.stderr)
+            .trim()
+            .to_string());
+    }
+
+    // Published ports survive the restart; the internal network may hand out a new address.
+    let api_url = if api_url.starts_with("http://localhost:") {
+        api_url.to_string()
+    } else {
+        DockerRuntime::internal_api_url(container_id)?
+    };
+    wait_ready(&api_url)?;
+    Ok(api_url)
+}
+
+/// Poll the agent server at `api_url` until it answers, from a pool thread.
+fn wait_ready(api_url: &str) -> Result<(), String> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+    runtime.block_on(RemoteRuntime::new(api_url.to_string(), Vec::new()).connect())
+}
+
+fn destroy(container_id: &str) {
+    let _ = Command::new("docker")
+        .args(["rm", "-f", container_id])
+        .output();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(uses: u32) -> PooledContainer {
+        PooledContainer {
+            id: format!("sandbox-{}", uses),
+            api_url: "http://localhost:3000".to_string(),
+            owner: None,
+            uses,
+        }
+    }
+
+    #[test]
+    fn test_should_recycle() {
+        let pool = SandboxPool::new("image", 2, 3);
+        assert!(pool.should_recycle(&container(1)));
+        // Worn out containers are destroyed.
+        assert!(!pool.should_recycle(&container(3)));
+
+        // As are containers the pool has no room for.
+        pool.idle.lock().unwrap().push_back(container(0));
+        pool.pending.fetch_add(1, Ordering::SeqCst);
+        assert!(!pool.should_recycle(&container(1)));
+        pool.pending.fetch_sub(1, Ordering::SeqCst);
+        assert!(pool.should_recycle(&container(1)));
+    }
+
+    #[test]
+    fn test_recycled_containers_stay_with_their_owner() {
+        let pool = SandboxPool::new("image", 3, 10);
+        let used = |id: &str, owner: &str| PooledContainer {
+            id: id.to_string(),
+            owner: Some(owner.to_string()),
+            ..container(1)
+        };
+        pool.idle.lock().unwrap().extend([
+            used("alice-1", "alice"),
+            container(0),
+            used("bob-1", "bob"),
+        ]);
+
+        assert_eq!(pool.take_idle("bob").unwrap().id, "bob-1");
+        // Without a container of their own, users get a fresh one, never another user's.
+        assert_eq!(pool.take_idle("carol").unwrap().id, "sandbox-0");
+        assert_eq!(pool.take_idle("carol"), None);
+        assert_eq!(pool.take_idle("alice").unwrap().id, "alice-1");
+    }
+}
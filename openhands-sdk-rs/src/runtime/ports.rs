@@ -0,0 +1,188 @@
+//! Reaching servers the agent starts inside a sandbox.
+//!
+//! A container's ports cannot be published after it has started, so exposed ports are
+//! forwarded by a TCP proxy in this process: it listens on a free host port and connects to
+//! the container's address on the bridge network. Servers must therefore listen on a
+//! non-loopback address (e.g. `0.0.0.0`) inside the container. Only ports the agent exposes
+//! with `expose_port` (or a devcontainer's `forwardPorts`) are forwarded. The proxy listens
+//! on `PREVIEW_BIND` (default `127.0.0.1`), and preview URLs use `PREVIEW_HOST` (default
+//! `localhost`) as host.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::agent::tools::Tool;
+
+pub const EXPOSE_PORT_TOOL: &str = "expose_port";
+
+/// `st` of a listening socket in `/proc/net/tcp`.
+const TCP_LISTEN: &str = "0A";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExposedPort {
+    pub container_port: u16,
+    pub host_port: u16,
+    pub url: String,
+}
+
+/// Ports exposed by a runtime, readable while the runtime itself is busy.
+pub type PortRegistry = Arc<Mutex<Vec<ExposedPort>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ListeningPort {
+    pub port: u16,
+    /// Bound to 127.0.0.1/::1 only, so not reachable from outside the container.
+    pub loopback: bool,
+}
+
+/// Listening TCP ports from the contents of `/proc/net/tcp` and/or `/proc/net/tcp6`.
+pub fn parse_listening_ports(proc_net_tcp: &str) -> Vec<ListeningPort> {
+    let mut ports: Vec<ListeningPort> = Vec::new();
+    for line in proc_net_tcp.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 || fields[3] != TCP_LISTEN {
+            continue;
+        }
+        let Some((address, port)) = fields[1].split_once(':') else {
+            continue;
+        };
+        let Ok(port) = u16::from_str_radix(port, 16) else {
+            continue;
+        };
+        let loopback = address == "0100007F" || address == "00000000000000000000000001000000";
+        match ports.iter_mut().find(|p| p.port == port) {
+            // Listening on any non-loopback address makes the port reachable.
+            Some(existing) => existing.loopback &= loopback,
+            None => ports.push(ListeningPort { port, loopback }),
+        }
+    }
+    ports.sort_by_key(|p| p.port);
+    ports
+}
+
+pub fn preview_url(host_port: u16) -> String {
+    let host = std::env::var("PREVIEW_HOST").unwrap_or_else(|_| "localhost".to_string());
+    format!("http://{}:{}", host, host_port)
+}
+
+/// Forward a free host port to `target` until the returned task is aborted.
+pub async fn forward(target: SocketAddr) -> Result<(u16, JoinHandle<()>), String> {
+    let bind = std::env::var("PREVIEW_BIND").unwrap_or_else(|_| "127.0.0.1".to_string());
+    let listener = TcpListener::bind((bind.as_str(), 0))
+        .await
+        .map_err(|e| format!("Failed to bind a host port: {}", e))?;
+    let host_port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let task = tokio::spawn(async move {
+        while let Ok((mut inbound, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                if let Ok(mut outbound) = TcpStream::connect(target).await {
+                    let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                }
+            });
+        }
+    });
+    Ok((host_port, task))
+}
+
+/// Lets the agent ask for a preview URL of a server it started. Runtimes that sandbox the
+/// agent handle the call themselves; on the host, ports are reachable as they are.
+pub struct ExposePortTool;
+
+#[async_trait]
+impl Tool for ExposePortTool {
+    fn name(&self) -> String {
+        EXPOSE_PORT_TOOL.to_string()
+    }
+
+    fn description(&self) -> String {
+        "Make a server you started reachable by the user and get its preview URL. \
+        The server must listen on 0.0.0.0, not only on localhost."
+            .to_string()
+    }
+
+    fn parameters(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "port": {
+                    "type": "integer",
+                    "description": "The port the server listens on"
+                }
+            },
+            "required": ["port"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<String, String> {
+        let port = port_arg(&args)?;
+        Ok(format!(
+            "Port {} is reachable at {}",
+            port,
+            preview_url(port)
+        ))
+    }
+}
+
+pub fn port_arg(args: &Value) -> Result<u16, String> {
+    args.get("port")
+        .and_then(|v| v.as_u64())
+        .and_then(|p| u16::try_from(p).ok())
+        .ok_or_else(|| "Missing or invalid 'port' argument".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_parse_listening_ports() {
+        let proc_net_tcp = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:1F40 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 1 1
+   1: 0100007F:0BB8 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 2 1
+   2: 0100007F:0BB8 0100007F:C350 01 00000000:00000000 00:00000000 00000000  1000        0 3 1
+   0: 00000000000000000000000000000000:1F40 00000000000000000000000000000000:0000 0A 0 0 0 0 0
+   1: 00000000000000000000000001000000:1538 00000000000000000000000000000000:0000 0A 0 0 0 0 0
+";
+        assert_eq!(
+            parse_listening_ports(proc_net_tcp),
+            vec![
+                ListeningPort {
+                    port: 3000,
+                    loopback: true
+                },
+                ListeningPort {
+                    port: 5432,
+                    loopback: true
+                },
+                ListeningPort {
+                    port: 8000,
+                    loopback: false
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forward() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = upstream.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = upstream.accept().await.unwrap();
+            socket.write_all(b"hello").await.unwrap();
+        });
+
+        let (host_port, task) = forward(target).await.unwrap();
+        let mut client = TcpStream::connect(("127.0.0.1", host_port)).await.unwrap();
+        let mut received = String::new();
+        client.read_to_string(&mut received).await.unwrap();
+        assert_eq!(received, "hello");
+        task.abort();
+    }
+}
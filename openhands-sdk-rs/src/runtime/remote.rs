@@ -1,20 +1,40 @@
-use crate::agent::tools::Tool;
-use crate::models::{
-    BashOutput, ExecuteBashRequest, FileReadRequest, FileResponse, FileWriteRequest,
-};
-use crate::runtime::Runtime;
+use crate::agent::tools::{Tool, ToolOutput};
+use crate::client::AgentServerClient;
+use crate::models::{ExecuteBashRequest, ServerInfo};
+use crate::runtime::{Runtime, RuntimeStatus};
 use async_trait::async_trait;
 use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+
+/// How long [`Runtime::connect`] waits for the server to answer.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
 
 /// A runtime that executes tools by sending requests to a remote agent server.
 pub struct RemoteRuntime {
-    pub base_url: String,
+    pub client: AgentServerClient,
     pub tools: Vec<Box<dyn Tool>>,
 }
 
 impl RemoteRuntime {
     pub fn new(base_url: String, tools: Vec<Box<dyn Tool>>) -> Self {
-        Self { base_url, tools }
+        Self {
+            client: AgentServerClient::new(&base_url),
+            tools,
+        }
+    }
+
+    /// The tool called `action` if it runs in this process (see [`Tool::runs_on_host`]).
+    fn host_tool(&self, action: &str) -> Option<&dyn Tool> {
+        self.tools
+            .iter()
+            .find(|t| t.name() == action && t.runs_on_host())
+            .map(|t| t.as_ref())
+    }
+
+    /// Fetch the server's version, runtime and tool inventory.
+    pub async fn server_info(&self) -> Result<ServerInfo, String> {
+        self.client.server_info().await
     }
 }
 
@@ -24,9 +44,32 @@ impl Runtime for RemoteRuntime {
         &self.tools
     }
 
-    async fn execute(&self, action: &str, args: Value) -> Result<String, String> {
-        let client = reqwest::Client::new();
+    /// Wait until the server answers `GET /health`.
+    async fn connect(&self) -> Result<(), String> {
+        let deadline = tokio::time::Instant::now() + CONNECT_TIMEOUT;
+        loop {
+            match self.client.health().await {
+                Ok(()) => return Ok(()),
+                Err(e) if tokio::time::Instant::now() >= deadline => {
+                    return Err(format!(
+                        "Agent server at {} did not answer: {}",
+                        self.client.base_url(),
+                        e
+                    ));
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(200)).await,
+            }
+        }
+    }
+
+    async fn status(&self) -> RuntimeStatus {
+        match self.client.health().await {
+            Ok(()) => RuntimeStatus::Ready,
+            Err(e) => RuntimeStatus::Unavailable(e),
+        }
+    }
 
+    async fn execute(&self, action: &str, args: Value) -> Result<String, String> {
         if action == "cmd" {
             let command = args["command"].as_str().ok_or("Missing command")?;
             let req = ExecuteBashRequest {
@@ -34,27 +77,14 @@ impl Runtime for RemoteRuntime {
                 cwd: None,
                 timeout: None,
             };
-            let res = client
-                .post(format!("{}/bash/execute_bash_command", self.base_url))
-                .json(&req)
-                .send()
-                .await
-                .map_err(|e| e.to_string())?;
-
-            if !res.status().is_success() {
-                let status = res.status();
-                let error_text = res.text().await.unwrap_or_default();
-                return Err(format!("Server returned error {}: {}", status, error_text));
-            }
-
-            let output: BashOutput = res.json().await.map_err(|e| e.to_string())?;
+            let output = self.client.execute_bash(&req).await?;
             let mut combined = String::new();
             if let Some(stdout_str) = output.stdout {
                 combined.push_str(&stdout_str);
             }
             if let Some(stderr_str) = output.stderr {
                 if !combined.is_empty() {
-                    combined.push_str("\n");
+                    combined.push('\n');
                 }
                 combined.push_str("Error output:\n");
                 combined.push_str(&stderr_str);
@@ -64,21 +94,7 @@ impl Runtime for RemoteRuntime {
 
         if action == "read_file" {
             let path = args["path"].as_str().ok_or("Missing path")?;
-            let req = FileReadRequest {
-                path: path.to_string(),
-            };
-            let res = client
-                .post(format!("{}/file/read", self.base_url))
-                .json(&req)
-                .send()
-                .await
-                .map_err(|e| e.to_string())?;
-
-            if !res.status().is_success() {
-                return Err(format!("Server returned error: {}", res.status()));
-            }
-
-            let output: FileResponse = res.json().await.map_err(|e| e.to_string())?;
+            let output = self.client.read_file(path).await?;
             if output.success {
                 return Ok(output.content.unwrap_or_default());
             } else {
@@ -89,22 +105,7 @@ impl Runtime for RemoteRuntime {
         if action == "write_file" {
             let path = args["path"].as_str().ok_or("Missing path")?;
             let content = args["content"].as_str().ok_or("Missing content")?;
-            let req = FileWriteRequest {
-                path: path.to_string(),
-                content: content.to_string(),
-            };
-            let res = client
-                .post(format!("{}/file/write", self.base_url))
-                .json(&req)
-                .send()
-                .await
-                .map_err(|e| e.to_string())?;
-
-            if !res.status().is_success() {
-                return Err(format!("Server returned error: {}", res.status()));
-            }
-
-            let output: FileResponse = res.json().await.map_err(|e| e.to_string())?;
+            let output = self.client.write_file(path, content).await?;
             if output.success {
                 return Ok(format!("File written to {}", path));
             } else {
@@ -112,9 +113,67 @@ impl Runtime for RemoteRuntime {
             }
         }
 
+        if let Some(tool) = self.host_tool(action) {
+            return tool.call(args).await;
+        }
+
         Err(format!(
             "Tool {} not yet supported via RemoteRuntime API",
             action
         ))
     }
+
+    /// Read through the server's `/file/read`.
+    async fn read_file(&self, path: &Path) -> Option<String> {
+        let response = self.client.read_file(&path.to_string_lossy()).await.ok()?;
+        response.content.filter(|_| response.success)
+    }
+
+    /// Media is only kept from tools run on the host; the agent server API returns text.
+    async fn execute_with_attachments(
+        &self,
+        action: &str,
+        args: Value,
+    ) -> Result<ToolOutput, String> {
+        match self.host_tool(action) {
+            Some(tool) => tool.call_with_attachments(args).await,
+            None => self.execute(action, args).await.map(ToolOutput::from),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_connect_waits_for_server() {
+        // A port nothing listens on yet.
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let runtime = RemoteRuntime::new(format!("http://{}", addr), Vec::new());
+        assert!(matches!(
+            runtime.status().await,
+            RuntimeStatus::Unavailable(_)
+        ));
+
+        let server = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 1024];
+                let _ = socket.read(&mut buf).await;
+                let response =
+                    "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nOK";
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        runtime.connect().await.unwrap();
+        assert_eq!(runtime.status().await, RuntimeStatus::Ready);
+        server.abort();
+    }
 }
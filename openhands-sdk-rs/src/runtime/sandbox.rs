@@ -0,0 +1,187 @@
+//! Sandbox images for `DockerRuntime`: the agent server binary layered on a base image of
+//! the user's choice.
+//!
+//! Images are tagged `openhands-sandbox:<hash>`, where the hash covers the generated
+//! Dockerfile and the binary, so an unchanged spec is only built once.
+//!
+//! The image `DockerRuntime` uses is chosen by [`sandbox_image`]:
+//! - `SANDBOX_IMAGE`: an existing image containing the agent server.
+//! - `SANDBOX_BASE_IMAGE`: a base image (Debian/Ubuntu based, as the binary links against
+//!   glibc) to build a sandbox from with the running binary, plus the packages in
+//!   `SANDBOX_PACKAGES` (space separated).
+//! - otherwise `openhands-agent-server-rs:latest`, built from the repository's Dockerfile.
+
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+pub const DEFAULT_SERVER_IMAGE: &str = "openhands-agent-server-rs:latest";
+
+/// Packages the agent's tools rely on.
+const REQUIRED_PACKAGES: &[&str] = &["bash", "ca-certificates", "curl", "git", "tmux"];
+const BINARY_NAME: &str = "openhands-agent-server-rs";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxImageSpec {
+    pub base_image: String,
+    /// Extra apt packages installed on top of the required ones.
+    pub packages: Vec<String>,
+    /// Agent server binary to copy into the image.
+    pub server_binary: PathBuf,
+}
+
+impl SandboxImageSpec {
+    pub fn new(base_image: &str, server_binary: PathBuf) -> Self {
+        Self {
+            base_image: base_image.to_string(),
+            packages: Vec::new(),
+            server_binary,
+        }
+    }
+
+    pub fn with_packages(mut self, packages: Vec<String>) -> Self {
+        self.packages = packages;
+        self
+    }
+
+    pub fn dockerfile(&self) -> String {
+        let mut packages: Vec<&str> = REQUIRED_PACKAGES.to_vec();
+        packages.extend(self.packages.iter().map(String::as_str));
+        packages.sort();
+        packages.dedup();
+        format!(
+            "FROM {base}\n\
+             USER root\n\
+             RUN apt-get update \\\n    \
+             && apt-get install -y --no-install-recommends {packages} \\\n    \
+             && rm -rf /var/lib/apt/lists/*\n\
+             COPY {BINARY_NAME} /usr/local/bin/{BINARY_NAME}\n\
+             RUN mkdir -p /workspace\n\
+             ENV WORKSPACE_DIR=/workspace\n\
+             WORKDIR /workspace\n\
+             EXPOSE 3000\n\
+             CMD [\"/usr/local/bin/{BINARY_NAME}\"]\n",
+            base = self.base_image,
+            packages = packages.join(" "),
+        )
+    }
+
+    /// `openhands-sandbox:<hash of the Dockerfile and binary>`.
+    pub fn tag(&self) -> Result<String, String> {
+        let binary = std::fs::read(&self.server_binary)
+            .map_err(|e| format!("Failed to read {}: {}", self.server_binary.display(), e))?;
+        let mut hasher = Sha256::new();
+        hasher.update(self.dockerfile().as_bytes());
+        hasher.update(&binary);
+        let hash: String = hasher
+            .finalize()
+            .iter()
+            .take(8)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Ok(format!("openhands-sandbox:{}", hash))
+    }
+
+    /// Build the image unless an image with the same tag exists, returning the tag.
+    pub fn build(&self) -> Result<String, String> {
+        let tag = self.tag()?;
+        if image_exists(&tag) {
+            return Ok(tag);
+        }
+
+        let context =
+            std::env::temp_dir().join(format!("openhands-sandbox-{}", uuid::Uuid::new_v4()));
+        let result = self.build_in(&context, &tag);
+        let _ = std::fs::remove_dir_all(&context);
+        result.map(|()| tag)
+    }
+
+    fn build_in(&self, context: &Path, tag: &str) -> Result<(), String> {
+        std::fs::create_dir_all(context).map_err(|e| e.to_string())?;
+        std::fs::copy(&self.server_binary, context.join(BINARY_NAME))
+            .map_err(|e| format!("Failed to copy {}: {}", self.server_binary.display(), e))?;
+        std::fs::write(context.join("Dockerfile"), self.dockerfile()).map_err(|e| e.to_string())?;
+
+        let status = Command::new("docker")
+            .args(["build", "-t", tag])
+            .arg(context)
+            .status()
+            .map_err(|e| format!("Failed to run docker build: {}", e))?;
+        if !status.success() {
+            return Err(format!("Failed to build {}", tag));
+        }
+        Ok(())
+    }
+}
+
+fn image_exists(tag: &str) -> bool {
+    Command::new("docker")
+        .args(["image", "inspect", tag])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// The agent server image for `DockerRuntime`, from the environment (see the module docs).
+/// A sandbox is built at most once per process; if that fails the default image is used.
+pub fn sandbox_image() -> String {
+    static IMAGE: OnceLock<String> = OnceLock::new();
+    IMAGE
+        .get_or_init(|| {
+            if let Ok(image) = std::env::var("SANDBOX_IMAGE") {
+                return image;
+            }
+            let Ok(base) = std::env::var("SANDBOX_BASE_IMAGE") else {
+                return DEFAULT_SERVER_IMAGE.to_string();
+            };
+            let packages = std::env::var("SANDBOX_PACKAGES")
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(str::to_string)
+                .collect();
+            let built = std::env::current_exe()
+                .map_err(|e| e.to_string())
+                .and_then(|binary| {
+                    SandboxImageSpec::new(&base, binary)
+                        .with_packages(packages)
+                        .build()
+                });
+            built.unwrap_or_else(|e| {
+                tracing::warn!("Failed to build sandbox from {}: {}", base, e);
+                DEFAULT_SERVER_IMAGE.to_string()
+            })
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sandbox_spec_tag_tracks_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let binary = temp_dir.path().join("server");
+        std::fs::write(&binary, "v1").unwrap();
+
+        let spec = SandboxImageSpec::new("python:3.12-bookworm", binary.clone())
+            .with_packages(vec!["nodejs".to_string(), "git".to_string()]);
+        let dockerfile = spec.dockerfile();
+        assert!(dockerfile.starts_with("FROM python:3.12-bookworm\n"));
+        assert!(dockerfile.contains(
+            "install -y --no-install-recommends bash ca-certificates curl git nodejs tmux"
+        ));
+
+        let tag = spec.tag().unwrap();
+        assert!(tag.starts_with("openhands-sandbox:"));
+        assert_eq!(spec.tag().unwrap(), tag);
+
+        std::fs::write(&binary, "v2").unwrap();
+        assert_ne!(spec.tag().unwrap(), tag);
+        let other_base = SandboxImageSpec::new("ubuntu:24.04", binary);
+        assert_ne!(other_base.tag().unwrap(), spec.tag().unwrap());
+    }
+}
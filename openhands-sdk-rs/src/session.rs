@@ -1,71 +1,667 @@
-use crate::agent::Agent;
-use crate::agent::tools::{CmdTool, FileReadTool, FileWriteTool, Tool};
-use crate::events::Event;
+use crate::activity::ConversationActivity;
+use crate::agent::format::OutputFormatter;
+use crate::agent::tools::{
+    FinishTool, RecallTool, RememberTool, ScratchpadTool, SemanticSearchTool, Tool,
+    default_tools_with_env, genai_tools, remove_scratchpad,
+};
+use crate::agent::{Agent, AgentConfig};
+use crate::events::{ActionEvent, Event, FileChange, MessageEvent, ObservationEvent};
+use crate::export::ConversationReport;
+use crate::injection::InjectionClassifier;
+use crate::journal::{FileMutationJournal, MutationRecord};
+use crate::llm::fixtures::MockLLM;
 use crate::llm::{LLM, LLMConfig};
-use crate::runtime::{DockerRuntime, LocalRuntime, Runtime};
+use crate::mcp::McpToolProvider;
+use crate::memory::MemoryStore;
+use crate::notifications::{Notifier, RunNotification};
+use crate::outcome::{FINISH_TOOL, TaskResult};
+use crate::plugins::PluginRegistry;
+use crate::redact::redactor;
+#[cfg(feature = "docker-runtime")]
+use crate::runtime::DockerRuntime;
+use crate::runtime::baseline::{Baseline, BaselineRuntime};
+#[cfg(feature = "docker-runtime")]
+use crate::runtime::devcontainer::DevContainerConfig;
+use crate::runtime::dry_run::{DryRun, DryRunRuntime, ProposedAction};
+use crate::runtime::env::ToolEnv;
+#[cfg(feature = "docker-runtime")]
+use crate::runtime::pool::SandboxPool;
+use crate::runtime::ports::PortRegistry;
+#[cfg(feature = "docker-runtime")]
+use crate::runtime::sandbox::sandbox_image;
+use crate::runtime::{LocalRuntime, Runtime, RuntimeStatus};
+use crate::security::{Confirmations, SecurityAnalyzer, SecurityMode};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct Conversation {
     pub id: String,
+    /// Id of the user the conversation belongs to.
+    pub owner: String,
+    pub workspace_dir: PathBuf,
     pub agent: Arc<Agent>,
     pub history: Arc<RwLock<Vec<Event>>>,
     pub runtime: Arc<RwLock<Box<dyn Runtime + Send + Sync>>>,
+    /// Ports forwarded out of the runtime, available while the runtime is busy.
+    pub ports: Option<PortRegistry>,
+    /// Environment of the commands run by the conversation's local tools.
+    pub tool_env: ToolEnv,
+    /// The security mode and the actions awaiting confirmation.
+    pub confirmations: Arc<Confirmations>,
+    /// The outcome the agent reported with `finish`, once it has.
+    pub result: Arc<std::sync::RwLock<Option<TaskResult>>>,
+    /// Whether tool calls that change something are only proposed; see [`crate::runtime::dry_run`].
+    pub dry_run: DryRun,
+    /// The files the conversation changed, as they were before; see [`Baseline::changes`].
+    pub baseline: Baseline,
+    /// The changes its file tools made, for undo; see [`Conversation::undo`].
+    pub journal: FileMutationJournal,
+    /// Whether a step is running and the tool call it waits on; pauses and stops steps.
+    pub activity: ConversationActivity,
+    /// Values added with [`Conversation::add_secret`], redacted until the conversation is
+    /// closed.
+    secrets: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+/// How long a `high` risk action waits for a human decision by default.
+pub const DEFAULT_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// The workspace's devcontainer if it has one, otherwise the sandbox image (leased from
+/// `pool` when there is one). A devcontainer that fails to start falls back to the sandbox
+/// image.
+#[cfg(feature = "docker-runtime")]
+fn docker_runtime(
+    owner: &str,
+    workspace_dir: &Path,
+    pool: Option<&Arc<SandboxPool>>,
+    tools: impl Fn() -> Vec<Box<dyn Tool>>,
+) -> DockerRuntime {
+    let server_image = sandbox_image();
+    if let Some(path) = DevContainerConfig::find(workspace_dir) {
+        let started = DevContainerConfig::load(workspace_dir, &path).and_then(|devcontainer| {
+            DockerRuntime::from_devcontainer(&devcontainer, &server_image, tools())
+        });
+        match started {
+            Ok(runtime) => return runtime,
+            Err(e) => tracing::warn!("Not using {}: {}", path.display(), e),
+        }
+    }
+    match pool {
+        Some(pool) => pool.lease(owner, tools()),
+        None => DockerRuntime::new(&server_image, tools()),
+    }
+}
+
+impl Conversation {
+    /// Redact `value` from the conversation's events, logs and prompts until it is closed,
+    /// and with `expose_to_tools` pass it to the commands of local tools as `name`.
+    pub fn add_secret(&self, name: &str, value: &str, expose_to_tools: bool) {
+        redactor().secrets().register(value);
+        self.secrets.lock().unwrap().push(value.to_string());
+        if expose_to_tools {
+            self.tool_env.expose(name, value);
+        }
+    }
+
+    /// Record the outcome reported by a step's `events`, which have been appended to
+    /// `history`. Steps that do not call `finish` keep the previous result.
+    pub fn record_result(&self, history: &[Event], events: &[Event]) {
+        let finished = events
+            .iter()
+            .any(|e| matches!(e, Event::Action(a) if a.tool_name == FINISH_TOOL));
+        if !finished {
+            return;
+        }
+        if let Some(result) = TaskResult::from_history(history) {
+            *self.result.write().unwrap() = Some(result);
+        }
+    }
+
+    /// The conversation so far, for a report; see [`crate::export`].
+    pub async fn report(&self) -> ConversationReport {
+        ConversationReport {
+            id: self.id.clone(),
+            events: self.history.read().await.clone(),
+            changes: self.baseline.changes(&self.workspace_dir),
+            result: self.task_result(),
+            exported_at: chrono::Utc::now(),
+        }
+    }
+
+    /// The outcome the agent last reported with `finish`.
+    pub fn task_result(&self) -> Option<TaskResult> {
+        self.result.read().unwrap().clone()
+    }
+
+    /// Leave dry-run mode and execute the proposed actions in order, adding an action and
+    /// an observation per step to the history. Stops at the first action that fails, which
+    /// stays in the plan with the ones after it; these are returned.
+    pub async fn execute_plan(&self) -> (Vec<Event>, Vec<ProposedAction>) {
+        self.dry_run.set_enabled(false);
+        let runtime = self.runtime.write().await;
+        let mut history = self.history.write().await;
+        let mut actions = self.dry_run.take().into_iter();
+        let mut events = Vec::new();
+        while let Some(proposed) = actions.next() {
+            let tool = runtime
+                .tools()
+                .iter()
+                .find(|t| t.name() == proposed.tool_name);
+            let paths = tool
+                .map(|t| t.affected_paths(&proposed.arguments))
+                .unwrap_or_default();
+            let untracked_changes =
+                tool.is_some_and(|t| t.has_untracked_changes(&proposed.arguments));
+            let before: Vec<Option<String>> = paths
+                .iter()
+                .map(|p| std::fs::read_to_string(p).ok())
+                .collect();
+
+            let action = ActionEvent {
+                source: "plan".to_string(),
+                tool_name: proposed.tool_name.clone(),
+                tool_call_id: format!("plan-{}", Uuid::new_v4()),
+                arguments: proposed.arguments.clone(),
+                thought: None,
+                security_risk: None,
+                reasoning: None,
+            };
+            events.push(Event::Action(action.clone()));
+            let result = runtime
+                .execute_with_attachments(&proposed.tool_name, proposed.arguments.clone())
+                .await;
+            let failed = result.is_err();
+            let (content, attachments) = match result {
+                Ok(output) => (output.content, output.attachments),
+                Err(e) => (format!("Error: {}", e), Vec::new()),
+            };
+            let file_changes = paths
+                .iter()
+                .zip(before)
+                .filter_map(|(path, before)| {
+                    let after = std::fs::read_to_string(path).ok();
+                    FileChange::capture(
+                        &path.to_string_lossy(),
+                        before.as_deref(),
+                        after.as_deref(),
+                    )
+                })
+                .collect();
+            events.push(Event::Observation(ObservationEvent {
+                source: "plan".to_string(),
+                tool_name: action.tool_name,
+                tool_call_id: action.tool_call_id,
+                content: redactor().redact(&content),
+                file_changes,
+                untracked_changes,
+                attachments,
+                injection: None,
+            }));
+
+            if failed {
+                let remaining: Vec<ProposedAction> =
+                    std::iter::once(proposed).chain(actions).collect();
+                self.dry_run.restore(remaining.clone());
+                history.extend(events.clone());
+                return (events, remaining);
+            }
+        }
+        history.extend(events.clone());
+        (events, Vec::new())
+    }
+
+    /// Undo the last `count` changes the conversation's file tools made, newest first, once
+    /// the agent is between steps. The agent is told which files were reverted by the
+    /// returned event, which has been added to the history.
+    pub async fn undo(&self, count: usize) -> Result<(Vec<MutationRecord>, Vec<Event>), String> {
+        let _runtime = self.runtime.write().await;
+        let undone = self.journal.undo(count)?;
+        let mut events = Vec::new();
+        if !undone.is_empty() {
+            let files: Vec<String> = undone
+                .iter()
+                .map(|m| {
+                    let path = m.path.strip_prefix(&self.workspace_dir).unwrap_or(&m.path);
+                    format!("- {} (by {})", path.display(), m.tool)
+                })
+                .collect();
+            let event = Event::Message(MessageEvent {
+                source: "user".to_string(),
+                content: format!(
+                    "I undid the last {} file change(s), newest first:\n{}",
+                    undone.len(),
+                    files.join("\n")
+                ),
+                file_refs: Vec::new(),
+            });
+            self.history.write().await.push(event.clone());
+            events.push(event);
+        }
+        Ok((undone, events))
+    }
+
+    /// Seed the runtime with a host file or directory; see [`Runtime::copy_in`].
+    pub async fn copy_in(&self, host_path: &Path, runtime_path: &str) -> Result<(), String> {
+        self.runtime
+            .read()
+            .await
+            .copy_in(host_path, runtime_path)
+            .await
+    }
+
+    /// Retrieve a file or directory (e.g. build artifacts) from the runtime.
+    pub async fn copy_out(&self, runtime_path: &str, host_path: &Path) -> Result<(), String> {
+        self.runtime
+            .read()
+            .await
+            .copy_out(runtime_path, host_path)
+            .await
+    }
+
+    /// Wait until the runtime can execute tools; see [`Runtime::connect`].
+    pub async fn connect(&self) -> Result<(), String> {
+        self.runtime.read().await.connect().await
+    }
+
+    /// Tear down the runtime, e.g. once the conversation is removed; see [`Runtime::close`].
+    /// The conversation's secrets are no longer redacted, unless something else registered
+    /// them too.
+    pub async fn close(&self) -> Result<(), String> {
+        let secrets = std::mem::take(&mut *self.secrets.lock().unwrap());
+        for secret in secrets {
+            redactor().secrets().unregister(&secret);
+        }
+        self.runtime.read().await.close().await
+    }
+
+    pub async fn runtime_status(&self) -> RuntimeStatus {
+        self.runtime.read().await.status().await
+    }
+}
+
+/// Where a conversation's tools execute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeKind {
+    /// On this machine, in the workspace directory.
+    Local,
+    /// In a sandbox container: the workspace's devcontainer or the sandbox image.
+    #[cfg(feature = "docker-runtime")]
+    Docker,
+}
+
+impl RuntimeKind {
+    /// `Docker` when `RUNTIME_ENV=docker`, otherwise `Local`. Without the `docker-runtime`
+    /// feature `RUNTIME_ENV=docker` is ignored with a warning.
+    pub fn from_env() -> Self {
+        if std::env::var("RUNTIME_ENV").unwrap_or_default() != "docker" {
+            return RuntimeKind::Local;
+        }
+        #[cfg(feature = "docker-runtime")]
+        return RuntimeKind::Docker;
+        #[cfg(not(feature = "docker-runtime"))]
+        {
+            tracing::warn!("RUNTIME_ENV=docker needs the docker-runtime feature; running locally");
+            RuntimeKind::Local
+        }
+    }
 }
 
 pub struct ConversationManager {
     conversations: HashMap<String, Conversation>,
+    #[cfg(feature = "docker-runtime")]
+    pool: Option<Arc<SandboxPool>>,
+    plugins: Option<Arc<PluginRegistry>>,
+    mcp: Option<Arc<McpToolProvider>>,
+    security_mode: SecurityMode,
+    confirmation_timeout: Duration,
+    security_analyzer: Option<Arc<dyn SecurityAnalyzer>>,
+    injection_classifier: Option<Arc<dyn InjectionClassifier>>,
+    output_formatter: Option<Arc<dyn OutputFormatter>>,
+    agent_config: AgentConfig,
+    llm_recordings: Option<PathBuf>,
+    mock_llm: Option<Arc<MockLLM>>,
+    notifier: Notifier,
+}
+
+impl Default for ConversationManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ConversationManager {
     pub fn new() -> Self {
         Self {
             conversations: HashMap::new(),
+            #[cfg(feature = "docker-runtime")]
+            pool: None,
+            plugins: None,
+            mcp: None,
+            security_mode: SecurityMode::default(),
+            confirmation_timeout: DEFAULT_CONFIRMATION_TIMEOUT,
+            security_analyzer: None,
+            injection_classifier: None,
+            output_formatter: None,
+            agent_config: AgentConfig::default(),
+            llm_recordings: None,
+            mock_llm: None,
+            notifier: Notifier::default(),
         }
     }
 
-    pub fn create_conversation(&mut self, system_message: String) -> Conversation {
+    /// Lease Docker runtimes from a warm pool instead of starting a container per
+    /// conversation.
+    #[cfg(feature = "docker-runtime")]
+    pub fn with_pool(mut self, pool: Arc<SandboxPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Give new conversations the healthy tools registered by plugins.
+    pub fn with_plugins(mut self, plugins: Arc<PluginRegistry>) -> Self {
+        self.plugins = Some(plugins);
+        self
+    }
+
+    /// Give new conversations the tools of external MCP servers.
+    pub fn with_mcp(mut self, mcp: Arc<McpToolProvider>) -> Self {
+        self.mcp = Some(mcp);
+        self
+    }
+
+    /// Start new conversations in `mode`, rejecting actions left unconfirmed for `timeout`.
+    pub fn with_security(mut self, mode: SecurityMode, timeout: Duration) -> Self {
+        self.security_mode = mode;
+        self.confirmation_timeout = timeout;
+        self
+    }
+
+    /// Post to `notifier` when an action of a new conversation awaits approval.
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Have `analyzer` review the actions of new conversations.
+    pub fn with_security_analyzer(mut self, analyzer: Arc<dyn SecurityAnalyzer>) -> Self {
+        self.security_analyzer = Some(analyzer);
+        self
+    }
+
+    /// Have `classifier` check the tool results of new conversations for prompt injection.
+    pub fn with_injection_classifier(mut self, classifier: Arc<dyn InjectionClassifier>) -> Self {
+        self.injection_classifier = Some(classifier);
+        self
+    }
+
+    /// Present tool results to the agents of new conversations with `formatter`.
+    pub fn with_output_formatter(mut self, formatter: Arc<dyn OutputFormatter>) -> Self {
+        self.output_formatter = Some(formatter);
+        self
+    }
+
+    /// Run the agents of new conversations as `config` says.
+    pub fn with_agent_config(mut self, config: AgentConfig) -> Self {
+        self.agent_config = config;
+        self
+    }
+
+    /// The configuration the agents of new conversations run with.
+    pub fn agent_config(&self) -> &AgentConfig {
+        &self.agent_config
+    }
+
+    /// Record the LLM exchanges of new conversations to `<dir>/<conversation id>.jsonl`; see
+    /// [`crate::llm::fixtures`].
+    pub fn with_llm_recording(mut self, dir: PathBuf) -> Self {
+        self.llm_recordings = Some(dir);
+        self
+    }
+
+    /// Answer the completions of new conversations with `mock`, in the order they are
+    /// requested across conversations, instead of calling a provider. For tests.
+    pub fn with_mock_llm(mut self, mock: Arc<MockLLM>) -> Self {
+        self.mock_llm = Some(mock);
+        self
+    }
+
+    /// Check that the provider of `llm` accepts the tools conversations get, so a schema
+    /// it would reject stops the server at startup instead of failing a conversation.
+    pub fn check_tool_schemas(&self, llm: &LLMConfig) -> Result<(), String> {
+        let tools = self.tools(
+            "schema-check",
+            &std::env::temp_dir(),
+            &ToolEnv::default(),
+            &FileMutationJournal::default(),
+        );
+        LLM::new(llm.clone()).check_tools(&genai_tools(&tools))
+    }
+
+    /// The names of the tools conversations get, e.g. to disable those a user may not use
+    /// through [`AgentConfig::disabled_tools`].
+    pub fn tool_names(&self) -> Vec<String> {
+        self.tools(
+            "tool-names",
+            &std::env::temp_dir(),
+            &ToolEnv::default(),
+            &FileMutationJournal::default(),
+        )
+        .iter()
+        .map(|tool| tool.name().to_string())
+        .collect()
+    }
+
+    /// The standard tools rooted at `workspace_dir`, `finish`, the scratchpad of
+    /// conversation `id`, `semantic_search` and the project's `remember` and `recall`, plus
+    /// MCP and plugin tools that do not shadow one of them.
+    fn tools(
+        &self,
+        id: &str,
+        workspace_dir: &Path,
+        env: &ToolEnv,
+        journal: &FileMutationJournal,
+    ) -> Vec<Box<dyn Tool>> {
+        let mut tools =
+            default_tools_with_env(workspace_dir.to_path_buf(), env.clone(), journal.clone());
+        tools.push(Box::new(FinishTool::new()));
+        tools.push(Box::new(ScratchpadTool::for_conversation(id)));
+        tools.push(Box::new(SemanticSearchTool::from_env(
+            workspace_dir.to_path_buf(),
+        )));
+        if let Ok(memory) = MemoryStore::shared(workspace_dir) {
+            tools.push(Box::new(RememberTool::new(memory.clone())));
+            tools.push(Box::new(RecallTool::new(memory)));
+        }
+        let mcp_tools = self.mcp.iter().flat_map(|mcp| mcp.tools());
+        let plugin_tools = self.plugins.iter().flat_map(|plugins| plugins.tools());
+        for tool in mcp_tools.chain(plugin_tools) {
+            if tools.iter().all(|t| t.name() != tool.name()) {
+                tools.push(tool);
+            }
+        }
+        tools
+    }
+
+    /// Create a conversation owned by `owner`, with tools rooted at `workspace_dir`. The LLM
+    /// and runtime are configured from the environment.
+    pub fn create_conversation(
+        &mut self,
+        owner: &str,
+        system_message: String,
+        workspace_dir: PathBuf,
+    ) -> Conversation {
+        self.create_conversation_with(
+            owner,
+            system_message,
+            workspace_dir,
+            LLMConfig::from_env(),
+            RuntimeKind::from_env(),
+        )
+    }
+
+    /// [`ConversationManager::create_conversation`] with an agent running as `config` says
+    /// instead of the manager's [`AgentConfig`].
+    pub fn create_conversation_configured(
+        &mut self,
+        owner: &str,
+        system_message: String,
+        workspace_dir: PathBuf,
+        config: AgentConfig,
+    ) -> Conversation {
+        self.build_conversation(
+            owner,
+            system_message,
+            workspace_dir,
+            LLMConfig::from_env(),
+            RuntimeKind::from_env(),
+            config,
+        )
+    }
+
+    /// Create a conversation with an explicit LLM configuration and runtime.
+    pub fn create_conversation_with(
+        &mut self,
+        owner: &str,
+        system_message: String,
+        workspace_dir: PathBuf,
+        llm: LLMConfig,
+        runtime: RuntimeKind,
+    ) -> Conversation {
+        let config = self.agent_config.clone();
+        self.build_conversation(owner, system_message, workspace_dir, llm, runtime, config)
+    }
+
+    fn build_conversation(
+        &mut self,
+        owner: &str,
+        system_message: String,
+        workspace_dir: PathBuf,
+        llm: LLMConfig,
+        runtime: RuntimeKind,
+        config: AgentConfig,
+    ) -> Conversation {
         let id = Uuid::new_v4().to_string();
 
-        let config = LLMConfig {
-            model: "gpt-5-nano".to_string(),
-            api_key: std::env::var("OPENAI_API_KEY").ok(),
-            reasoning_effort: Some("minimal".to_string()),
+        let mut llm = match &self.mock_llm {
+            Some(mock) => LLM::mock(mock.clone()),
+            None => LLM::new(llm),
+        };
+        if let Some(dir) = &self.llm_recordings {
+            llm = llm.with_recorder(dir.join(format!("{}.jsonl", id)));
+        }
+        let mut agent = Agent::new(llm, system_message).with_config(config);
+        if let Some(analyzer) = &self.security_analyzer {
+            agent = agent.with_security_analyzer(analyzer.clone());
+        }
+        if let Some(classifier) = &self.injection_classifier {
+            agent = agent.with_injection_classifier(classifier.clone());
+        }
+        if let Some(formatter) = &self.output_formatter {
+            agent = agent.with_output_formatter(formatter.clone());
+        }
+        match MemoryStore::shared(&workspace_dir) {
+            Ok(memory) => agent = agent.with_memory(memory),
+            Err(e) => tracing::warn!("Conversation {} runs without memory: {}", id, e),
+        }
+        let tool_env = ToolEnv::default();
+        let journal = FileMutationJournal::default();
+
+        let runtime: Box<dyn Runtime + Send + Sync> = match runtime {
+            #[cfg(feature = "docker-runtime")]
+            RuntimeKind::Docker => Box::new(docker_runtime(
+                owner,
+                &workspace_dir,
+                self.pool.as_ref(),
+                || self.tools(&id, &workspace_dir, &tool_env, &journal),
+            )),
+            RuntimeKind::Local => Box::new(
+                LocalRuntime::new(self.tools(&id, &workspace_dir, &tool_env, &journal))
+                    .with_working_dir(workspace_dir.clone()),
+            ),
         };
-        let llm = LLM::new(config);
-        let agent = Agent::new(llm, system_message);
-
-        let tools: Vec<Box<dyn Tool>> = vec![
-            Box::new(CmdTool),
-            Box::new(FileReadTool),
-            Box::new(FileWriteTool),
-        ];
-
-        let runtime: Box<dyn Runtime + Send + Sync> =
-            if std::env::var("RUNTIME_ENV").unwrap_or_default() == "docker" {
-                Box::new(DockerRuntime::new(
-                    "openhands-agent-server-rs:latest",
-                    tools,
-                ))
-            } else {
-                Box::new(LocalRuntime::new(tools))
-            };
 
+        let baseline = Baseline::default();
+        let runtime = Box::new(BaselineRuntime::new(runtime, baseline.clone()));
+        let dry_run = DryRun::default();
+        let runtime = Box::new(DryRunRuntime::new(runtime, dry_run.clone()));
+        let ports = runtime.ports();
         let conversation = Conversation {
             id: id.clone(),
+            owner: owner.to_string(),
+            workspace_dir,
             agent: Arc::new(agent),
             history: Arc::new(RwLock::new(Vec::new())),
             runtime: Arc::new(RwLock::new(runtime)),
+            ports,
+            tool_env,
+            confirmations: Arc::new(self.confirmations(&id)),
+            result: Arc::new(std::sync::RwLock::new(None)),
+            dry_run,
+            baseline,
+            journal,
+            activity: ConversationActivity::default(),
+            secrets: Arc::default(),
         };
 
         self.conversations.insert(id, conversation.clone());
         conversation
     }
 
+    /// Confirmations for the conversation `id`, announcing the actions that await approval.
+    fn confirmations(&self, id: &str) -> Confirmations {
+        let confirmations = Confirmations::new(self.security_mode, self.confirmation_timeout);
+        if self.notifier.is_empty() {
+            return confirmations;
+        }
+        let notifier = self.notifier.clone();
+        let run = id.to_string();
+        confirmations.on_pending(move |action| {
+            let notifier = notifier.clone();
+            let notification = RunNotification::AwaitingApproval {
+                run: run.clone(),
+                action: format!("`{}` {}", action.tool_name, action.arguments),
+            };
+            tokio::spawn(async move { notifier.notify(&notification).await });
+        })
+    }
+
     pub fn get_conversation(&self, id: &str) -> Option<&Conversation> {
         self.conversations.get(id)
     }
+
+    /// Look up a conversation on behalf of `owner`. Conversations of other users are
+    /// reported as missing rather than forbidden so their ids are not disclosed.
+    pub fn get_owned_conversation(&self, owner: &str, id: &str) -> Option<&Conversation> {
+        self.conversations.get(id).filter(|c| c.owner == owner)
+    }
+
+    /// Remove a conversation of `owner` and its scratchpad, returning it if it existed. Its
+    /// runtime stays up until [`Conversation::close`] or until the last clone is dropped.
+    pub fn remove_conversation(&mut self, owner: &str, id: &str) -> Option<Conversation> {
+        self.get_owned_conversation(owner, id)?;
+        remove_scratchpad(id);
+        self.conversations.remove(id)
+    }
+
+    /// Remove all conversations and close their runtimes, e.g. on shutdown.
+    pub async fn close_all(&mut self) {
+        for (id, conversation) in self.conversations.drain() {
+            remove_scratchpad(&id);
+            if let Err(e) = conversation.close().await {
+                tracing::warn!("Failed to close the runtime of {}: {}", id, e);
+            }
+        }
+    }
+
+    pub fn list_conversations(&self, owner: &str) -> Vec<&Conversation> {
+        self.conversations
+            .values()
+            .filter(|c| c.owner == owner)
+            .collect()
+    }
 }
@@ -0,0 +1,220 @@
+//! Environment setup hooks run in a workspace before the agent's first step.
+//!
+//! Commands run through the conversation's runtime, like the agent's own `cmd` calls, so a
+//! sandboxed conversation is set up inside its sandbox. Setup output is recorded as an
+//! [`ObservationEvent`] from `system`, so the agent sees what was installed (or what failed)
+//! without having to run it again.
+
+use crate::agent::tools::{CmdTool, WorkingDir};
+use crate::events::ObservationEvent;
+use crate::paths;
+use crate::runtime::env::ToolEnv;
+use crate::runtime::{LocalRuntime, Runtime};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// `source` of setup observations; the agent presents them as environment notes rather than
+/// tool results.
+pub const SETUP_SOURCE: &str = "system";
+pub const SETUP_TOOL_NAME: &str = "setup";
+
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+
+/// How `cmd` reports a failing command, followed by the exit code.
+const FAILURE_MARKER: &str = "\n[Command failed with exit code ";
+
+/// Exit code of `timeout` when the command ran out of time.
+const TIMED_OUT: &str = "124";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SetupConfig {
+    /// Shell commands, run in order in the workspace. The first failure stops the rest.
+    #[serde(default)]
+    pub commands: Vec<String>,
+    /// Dotenv-style files, relative to the workspace, loaded into the commands' environment.
+    #[serde(default)]
+    pub env_files: Vec<String>,
+    /// Per-command timeout in seconds (default 600).
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl SetupConfig {
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// `self` followed by `other`: commands and env files are appended, so per-request setup
+    /// runs after (and can override) server-wide setup.
+    pub fn extend(&self, other: &SetupConfig) -> SetupConfig {
+        SetupConfig {
+            commands: [self.commands.as_slice(), other.commands.as_slice()].concat(),
+            env_files: [self.env_files.as_slice(), other.env_files.as_slice()].concat(),
+            timeout_secs: other.timeout_secs.or(self.timeout_secs),
+        }
+    }
+
+    /// Run the commands in `workspace_dir` on this machine and describe the outcome.
+    pub async fn run(&self, workspace_dir: &Path) -> ObservationEvent {
+        let cmd =
+            CmdTool::new(ToolEnv::default()).with_cwd(WorkingDir::new(workspace_dir.to_path_buf()));
+        let runtime = LocalRuntime::new(vec![Box::new(cmd)]);
+        self.run_in(workspace_dir, &runtime).await
+    }
+
+    /// Run the commands with the `cmd` tool of `runtime`, whose workspace is
+    /// `workspace_dir`, under the env files' variables.
+    pub async fn run_in(&self, workspace_dir: &Path, runtime: &dyn Runtime) -> ObservationEvent {
+        let mut report = String::new();
+        let mut exports = String::new();
+        for file in &self.env_files {
+            let content = paths::confine(workspace_dir, file)
+                .and_then(|path| std::fs::read_to_string(path).map_err(|e| e.to_string()));
+            match content {
+                Ok(content) => {
+                    for (key, value) in parse_env_file(&content) {
+                        if is_env_name(&key) {
+                            exports.push_str(&format!("export {}={}\n", key, shell_quote(&value)));
+                        }
+                    }
+                }
+                Err(e) => report.push_str(&format!("Skipped env file {}: {}\n", file, e)),
+            }
+        }
+
+        let timeout = self.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS);
+        for command in &self.commands {
+            report.push_str(&format!("$ {}\n", command));
+            // A shell of its own, so a `cd` or `export` does not outlive the command.
+            let script = format!(
+                "timeout {} bash -c {}",
+                timeout,
+                shell_quote(&format!("{}{}", exports, command))
+            );
+            let output = runtime
+                .execute("cmd", serde_json::json!({ "command": script }))
+                .await;
+            let failure = match output {
+                Err(e) => Some(format!("Failed to run: {}", e)),
+                Ok(output) => {
+                    let (output, code) = match output.split_once(FAILURE_MARKER) {
+                        Some((output, rest)) => (output, rest.split(']').next()),
+                        None => (output.as_str(), None),
+                    };
+                    report.push_str(output);
+                    if !report.ends_with('\n') {
+                        report.push('\n');
+                    }
+                    code.map(|code| match code {
+                        TIMED_OUT => format!("Timed out after {}s", timeout),
+                        code => format!("Exited with code {}", code),
+                    })
+                }
+            };
+            if let Some(failure) = failure {
+                report.push_str(&format!(
+                    "{}; remaining setup commands were skipped.\n",
+                    failure
+                ));
+                break;
+            }
+        }
+
+        ObservationEvent {
+            source: SETUP_SOURCE.to_string(),
+            tool_name: SETUP_TOOL_NAME.to_string(),
+            tool_call_id: String::new(),
+            content: report,
+            file_changes: Vec::new(),
+            untracked_changes: true,
+            attachments: Vec::new(),
+            injection: None,
+        }
+    }
+}
+
+/// Whether `name` can be exported by a shell.
+fn is_env_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// `KEY=value` lines; blank lines, `#` comments and an `export ` prefix are allowed, and
+/// values may be quoted.
+pub fn parse_env_file(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_setup_runs_in_workspace_with_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path().join("workspace");
+        std::fs::create_dir(&workspace).unwrap();
+        std::fs::write(
+            workspace.join(".env"),
+            "# deps\nexport GREETING=\"hello there\"\nTARGET=world\n",
+        )
+        .unwrap();
+        std::fs::write(temp_dir.path().join("outside.env"), "TARGET=host\n").unwrap();
+        let setup = SetupConfig {
+            commands: vec!["echo $GREETING > out.txt".to_string()],
+            env_files: vec![".env".to_string()],
+            timeout_secs: None,
+        }
+        .extend(&SetupConfig {
+            commands: vec!["echo $TARGET; false".to_string(), "touch never".to_string()],
+            env_files: vec!["missing.env".to_string(), "../outside.env".to_string()],
+            timeout_secs: None,
+        });
+
+        let observation = setup.run(&workspace).await;
+        assert_eq!(observation.source, SETUP_SOURCE);
+        assert_eq!(
+            std::fs::read_to_string(workspace.join("out.txt")).unwrap(),
+            "hello there\n"
+        );
+        assert!(observation.content.contains("Skipped env file missing.env"));
+        // Env files are confined to the workspace.
+        assert!(
+            observation
+                .content
+                .contains("Skipped env file ../outside.env: Path '../outside.env' is outside")
+        );
+        assert!(
+            observation
+                .content
+                .contains("$ echo $TARGET; false\nworld\n")
+        );
+        assert!(
+            observation
+                .content
+                .contains("remaining setup commands were skipped")
+        );
+        assert!(!workspace.join("never").exists());
+    }
+}
@@ -0,0 +1,142 @@
+//! Cleaning up command output before an agent reads it.
+//!
+//! Tools like cargo and npm color their output with ANSI escape sequences and redraw
+//! progress bars and spinners with carriage returns. Both cost tokens and confuse models
+//! without telling them anything. [`clean`] strips the escape sequences and other control
+//! characters, and keeps only what a terminal would finally show of each redrawn line. The
+//! event store keeps commands' raw output; only what tools show the agent is cleaned.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::sync::{Arc, RwLock};
+
+/// `[shell_output]`: what [`clean`] does; both on by default.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ShellOutputConfig {
+    /// Remove ANSI escape sequences (colors, cursor movement, window titles) and control
+    /// characters other than newlines and tabs.
+    #[serde(default = "enabled")]
+    pub strip_ansi: bool,
+    /// Keep only what a terminal finally shows of lines redrawn with carriage returns or
+    /// backspaces, such as progress bars.
+    #[serde(default = "enabled")]
+    pub collapse_carriage_returns: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for ShellOutputConfig {
+    fn default() -> Self {
+        Self {
+            strip_ansi: true,
+            collapse_carriage_returns: true,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CONFIG: RwLock<Arc<ShellOutputConfig>> =
+        RwLock::new(Arc::new(ShellOutputConfig::default()));
+    /// CSI sequences (colors, cursor movement), OSC sequences (window titles, links), and
+    /// the other two-character escapes.
+    static ref ESCAPE: Regex = Regex::new(
+        r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[()][0-9A-Za-z]|\x1b[@-Z\\-_]"
+    )
+    .unwrap();
+}
+
+/// Set how [`clean`] treats output, process-wide.
+pub fn set_config(config: ShellOutputConfig) {
+    *CONFIG.write().unwrap() = Arc::new(config);
+}
+
+pub fn config() -> Arc<ShellOutputConfig> {
+    CONFIG.read().unwrap().clone()
+}
+
+/// `output` as the process-wide [`ShellOutputConfig`] says to show it to an agent.
+pub fn clean(output: &str) -> Cow<'_, str> {
+    clean_with(&config(), output)
+}
+
+pub fn clean_with<'a>(config: &ShellOutputConfig, output: &'a str) -> Cow<'a, str> {
+    let mut output = Cow::Borrowed(output);
+    if config.strip_ansi && output.contains('\x1b') {
+        output = Cow::Owned(ESCAPE.replace_all(&output, "").into_owned());
+    }
+    if config.collapse_carriage_returns && output.contains(['\r', '\x08']) {
+        output = Cow::Owned(
+            output
+                .split('\n')
+                .map(collapse_line)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+    }
+    if config.strip_ansi && output.chars().any(is_stray_control) {
+        output = Cow::Owned(output.chars().filter(|c| !is_stray_control(*c)).collect());
+    }
+    output
+}
+
+/// Control characters a model has no use for. Carriage returns and backspaces are left
+/// for [`collapse_line`].
+fn is_stray_control(c: char) -> bool {
+    c.is_control() && !matches!(c, '\n' | '\t' | '\r' | '\x08')
+}
+
+/// What a terminal shows of `line`: a carriage return goes back to the start of the line
+/// and a backspace one character back, and what follows overwrites what was there.
+fn collapse_line(line: &str) -> String {
+    // `\r\n` line endings are not redraws.
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let mut screen: Vec<char> = Vec::new();
+    let mut column: usize = 0;
+    for c in line.chars() {
+        match c {
+            '\r' => column = 0,
+            '\x08' => column = column.saturating_sub(1),
+            c => {
+                if column < screen.len() {
+                    screen[column] = c;
+                } else {
+                    screen.push(c);
+                }
+                column += 1;
+            }
+        }
+    }
+    screen.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean() {
+        let config = ShellOutputConfig::default();
+        let colored = "\x1b[1m\x1b[32m   Compiling\x1b[0m foo v0.1.0\r\n\x1b]0;title\x07done\x07\n";
+        assert_eq!(
+            clean_with(&config, colored),
+            "   Compiling foo v0.1.0\ndone\n"
+        );
+        let progress = "Downloading  10%\rDownloading  55%\rDownloading 100%\nok\r\n";
+        assert_eq!(clean_with(&config, progress), "Downloading 100%\nok\n");
+        // A shorter redraw leaves the end of the longer line, like a terminal.
+        assert_eq!(clean_with(&config, "abcdef\rxy"), "xycdef");
+        assert_eq!(clean_with(&config, "spin |\x08/\x08-"), "spin -");
+
+        // Output without escapes is not copied.
+        assert!(matches!(clean_with(&config, "plain\n"), Cow::Borrowed(_)));
+
+        let raw = ShellOutputConfig {
+            strip_ansi: false,
+            collapse_carriage_returns: false,
+        };
+        assert_eq!(clean_with(&raw, colored), colored);
+    }
+}
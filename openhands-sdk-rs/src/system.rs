@@ -1,7 +1,40 @@
+use crate::runtime::env::ToolEnv;
+use nvml_wrapper::Nvml;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use sysinfo::{Disks, Pid, System};
 
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Interpreters and compilers [`environment_info`] looks for, with their version flag.
+const TOOLCHAINS: &[(&str, &str)] = &[
+    ("python3", "--version"),
+    ("python", "--version"),
+    ("node", "--version"),
+    ("cargo", "--version"),
+    ("rustc", "--version"),
+    ("go", "version"),
+    ("gcc", "--version"),
+    ("clang", "--version"),
+    ("java", "-version"),
+    ("javac", "-version"),
+    ("ruby", "--version"),
+    ("php", "--version"),
+    ("dotnet", "--version"),
+];
+
+const PACKAGE_MANAGERS: &[&str] = &[
+    "apt-get", "dnf", "yum", "apk", "pacman", "brew", "pip", "pip3", "uv", "poetry", "conda",
+    "npm", "yarn", "pnpm", "bun", "mvn", "gradle", "gem", "composer",
+];
+
+/// How long a `--version` probe may take.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MemoryStats {
     pub rss: u64,
@@ -23,12 +56,41 @@ pub struct IoStats {
     pub write_bytes: u64,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GpuStats {
+    pub index: u32,
+    pub name: String,
+    pub utilization_percent: u32,
+    pub memory_total: u64,
+    pub memory_used: u64,
+}
+
+/// Limits of the cgroup the process runs in, i.e. the container's share of the host.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ContainerLimits {
+    pub memory_bytes: Option<u64>,
+    /// Number of CPUs worth of quota.
+    pub cpus: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Resources {
     pub cpu_percent: f32,
+    /// `percent` is relative to the container memory limit when there is one.
     pub memory: MemoryStats,
     pub disk: DiskStats,
     pub io: IoStats,
+    /// Empty when there is no NVIDIA GPU or driver.
+    pub gpus: Vec<GpuStats>,
+    pub container: Option<ContainerLimits>,
+    pub load_average: LoadAverage,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -38,22 +100,89 @@ pub struct SystemInfo {
     pub resources: Resources,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Toolchain {
+    pub name: String,
+    /// Where it was found on the tools' `PATH`; `None` if it is not installed.
+    pub path: Option<String>,
+    /// First line of its version output.
+    pub version: Option<String>,
+}
+
+/// What commands run by tools have to work with.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EnvironmentInfo {
+    pub os: String,
+    pub kernel: Option<String>,
+    pub arch: String,
+    pub cpus: usize,
+    pub cpu_brand: Option<String>,
+    pub memory_total: u64,
+    pub memory_available: u64,
+    pub container: Option<ContainerLimits>,
+    pub toolchains: Vec<Toolchain>,
+    /// Package managers found on the tools' `PATH`.
+    pub package_managers: Vec<String>,
+}
+
 lazy_static::lazy_static! {
-    static ref START_TIME: f64 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+    static ref START_TIME: f64 = now_secs();
+    static ref LAST_ACTIVITY: Mutex<f64> = Mutex::new(*START_TIME);
 }
 
-pub async fn get_system_info() -> SystemInfo {
-    let current_time = SystemTime::now()
+fn now_secs() -> f64 {
+    SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
-        .as_secs_f64();
+        .as_secs_f64()
+}
+
+/// Mark the process as busy (a request was handled or a tool ran), resetting [`idle_time`].
+pub fn record_activity() {
+    let now = now_secs();
+    let mut last = LAST_ACTIVITY.lock().unwrap();
+    *last = last.max(now);
+}
+
+/// Seconds since the last [`record_activity`], or since start if there was none.
+pub fn idle_time() -> f64 {
+    (now_secs() - *LAST_ACTIVITY.lock().unwrap()).max(0.0)
+}
+
+/// Work in progress, e.g. requests being handled and agent steps running.
+static BUSY: AtomicUsize = AtomicUsize::new(0);
+
+/// Marks the process as busy until dropped; see [`busy`].
+pub struct Busy(());
+
+impl Drop for Busy {
+    fn drop(&mut self) {
+        record_activity();
+        BUSY.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Mark the process as busy for as long as the returned guard lives, however long that is,
+/// e.g. while an agent step waits on a slow LLM call.
+pub fn busy() -> Busy {
+    BUSY.fetch_add(1, Ordering::SeqCst);
+    record_activity();
+    Busy(())
+}
 
-    let uptime = current_time - *START_TIME;
-    let idle_time = uptime;
+/// Whether any [`Busy`] guard is alive.
+pub fn is_busy() -> bool {
+    BUSY.load(Ordering::SeqCst) > 0
+}
+
+pub async fn get_system_info() -> SystemInfo {
+    let uptime = now_secs() - *START_TIME;
+    let idle_time = idle_time();
 
     let mut sys = System::new_all();
 
     let disks = Disks::new_with_refreshed_list();
+    let load = System::load_average();
 
     let pid = Pid::from_u32(std::process::id());
 
@@ -61,13 +190,23 @@ pub async fn get_system_info() -> SystemInfo {
     tokio::time::sleep(Duration::from_millis(100)).await;
     sys.refresh_pids(&[pid]);
 
+    let container = read_cgroup_limits(Path::new(CGROUP_ROOT), sys.total_memory());
+    let memory_limit = container
+        .as_ref()
+        .and_then(|c| c.memory_bytes)
+        .unwrap_or(sys.total_memory());
+
     let (cpu_percent, memory_stats, io_stats) = if let Some(process) = sys.process(pid) {
         let cpu = process.cpu_usage();
 
         let mem = MemoryStats {
             rss: process.memory(),
             vms: process.virtual_memory(),
-            percent: 0.0,
+            percent: if memory_limit > 0 {
+                (process.memory() as f64 / memory_limit as f64 * 100.0) as f32
+            } else {
+                0.0
+            },
         };
 
         let disk_usage = process.disk_usage();
@@ -119,6 +258,240 @@ pub async fn get_system_info() -> SystemInfo {
             memory: memory_stats,
             disk: disk_stats,
             io: io_stats,
+            gpus: gpu_stats(),
+            container,
+            load_average: LoadAverage {
+                one: load.one,
+                five: load.five,
+                fifteen: load.fifteen,
+            },
         },
     }
 }
+
+/// The executable `program` on the search path `path` (a `PATH` value).
+pub fn find_program(program: &str, path: &str) -> Option<PathBuf> {
+    std::env::split_paths(path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// First non-empty line `program flag` prints, on stdout or (like `java -version`) stderr.
+async fn probe_version(env: &ToolEnv, program: &str, flag: &str) -> Option<String> {
+    let mut command = tokio::process::Command::from(env.command(program));
+    command.arg(flag).stdin(Stdio::null()).kill_on_drop(true);
+    let output = tokio::time::timeout(VERSION_TIMEOUT, command.output())
+        .await
+        .ok()?
+        .ok()?;
+    [output.stdout, output.stderr].iter().find_map(|stream| {
+        String::from_utf8_lossy(stream)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(str::to_string)
+    })
+}
+
+/// OS, hardware, toolchains and package managers as seen by commands run with `env`.
+pub async fn environment_info(env: &ToolEnv) -> EnvironmentInfo {
+    let mut sys = System::new();
+    sys.refresh_cpu();
+    sys.refresh_memory();
+    let path = env.vars().get("PATH").cloned().unwrap_or_default();
+
+    let mut probes = tokio::task::JoinSet::new();
+    for (index, (name, flag)) in TOOLCHAINS.iter().enumerate() {
+        let found = find_program(name, &path);
+        let env = env.clone();
+        probes.spawn(async move {
+            let version = match &found {
+                Some(_) => probe_version(&env, name, flag).await,
+                None => None,
+            };
+            let toolchain = Toolchain {
+                name: name.to_string(),
+                path: found.map(|p| p.display().to_string()),
+                version,
+            };
+            (index, toolchain)
+        });
+    }
+    let mut toolchains: Vec<(usize, Toolchain)> = probes.join_all().await;
+    toolchains.sort_by_key(|(index, _)| *index);
+
+    EnvironmentInfo {
+        os: System::long_os_version().unwrap_or_else(|| std::env::consts::OS.to_string()),
+        kernel: System::kernel_version(),
+        arch: std::env::consts::ARCH.to_string(),
+        cpus: sys.cpus().len(),
+        cpu_brand: sys
+            .cpus()
+            .first()
+            .map(|cpu| cpu.brand().trim().to_string())
+            .filter(|brand| !brand.is_empty()),
+        memory_total: sys.total_memory(),
+        memory_available: sys.available_memory(),
+        container: read_cgroup_limits(Path::new(CGROUP_ROOT), sys.total_memory()),
+        toolchains: toolchains.into_iter().map(|(_, t)| t).collect(),
+        package_managers: PACKAGE_MANAGERS
+            .iter()
+            .filter(|name| find_program(name, &path).is_some())
+            .map(|name| name.to_string())
+            .collect(),
+    }
+}
+
+/// Stats for every NVIDIA GPU, via NVML. NVML is loaded once; hosts without the driver
+/// report no GPUs.
+fn gpu_stats() -> Vec<GpuStats> {
+    static NVML: OnceLock<Option<Nvml>> = OnceLock::new();
+    let Some(nvml) = NVML.get_or_init(|| Nvml::init().ok()) else {
+        return Vec::new();
+    };
+    let count = nvml.device_count().unwrap_or(0);
+    (0..count)
+        .filter_map(|index| {
+            let device = nvml.device_by_index(index).ok()?;
+            let memory = device.memory_info().ok()?;
+            Some(GpuStats {
+                index,
+                name: device.name().unwrap_or_default(),
+                utilization_percent: device.utilization_rates().map_or(0, |u| u.gpu),
+                memory_total: memory.total,
+                memory_used: memory.used,
+            })
+        })
+        .collect()
+}
+
+/// Memory and CPU limits from cgroup v2 (`memory.max`, `cpu.max`) or v1
+/// (`memory/memory.limit_in_bytes`, `cpu/cpu.cfs_quota_us`). `None` when neither is
+/// limited. A memory limit at or above the host's memory is no limit.
+pub fn read_cgroup_limits(root: &Path, host_memory: u64) -> Option<ContainerLimits> {
+    let read = |file: &str| std::fs::read_to_string(root.join(file)).ok();
+
+    let memory_bytes = read("memory.max")
+        .or_else(|| read("memory/memory.limit_in_bytes"))
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|&limit| host_memory == 0 || limit < host_memory);
+
+    let cpus = match read("cpu.max") {
+        Some(v) => {
+            let mut fields = v.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some(quota), Some(period)) => cpu_quota(quota, period),
+                _ => None,
+            }
+        }
+        None => match (read("cpu/cpu.cfs_quota_us"), read("cpu/cpu.cfs_period_us")) {
+            (Some(quota), Some(period)) => cpu_quota(quota.trim(), period.trim()),
+            _ => None,
+        },
+    };
+
+    if memory_bytes.is_none() && cpus.is_none() {
+        return None;
+    }
+    Some(ContainerLimits { memory_bytes, cpus })
+}
+
+/// `max` (v2) and `-1` (v1) mean no quota; both fail to parse as `u64`.
+fn cpu_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota = quota.parse::<u64>().ok()?;
+    let period = period.parse::<u64>().ok().filter(|&p| p > 0)?;
+    Some(quota as f64 / period as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_activity_resets_idle_time() {
+        record_activity();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(idle_time() >= 0.05);
+        record_activity();
+        assert!(idle_time() < 0.05);
+
+        let info = get_system_info().await;
+        assert!(info.idle_time <= info.uptime);
+    }
+
+    #[test]
+    fn test_read_cgroup_limits() {
+        let gib = 1 << 30;
+        let v2 = tempfile::TempDir::new().unwrap();
+        std::fs::write(v2.path().join("memory.max"), "1073741824\n").unwrap();
+        std::fs::write(v2.path().join("cpu.max"), "150000 100000\n").unwrap();
+        assert_eq!(
+            read_cgroup_limits(v2.path(), 16 * gib),
+            Some(ContainerLimits {
+                memory_bytes: Some(gib),
+                cpus: Some(1.5),
+            })
+        );
+
+        std::fs::write(v2.path().join("memory.max"), "max\n").unwrap();
+        std::fs::write(v2.path().join("cpu.max"), "max 100000\n").unwrap();
+        assert_eq!(read_cgroup_limits(v2.path(), 16 * gib), None);
+
+        let v1 = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(v1.path().join("memory")).unwrap();
+        std::fs::create_dir(v1.path().join("cpu")).unwrap();
+        std::fs::write(
+            v1.path().join("memory/memory.limit_in_bytes"),
+            "9223372036854771712\n",
+        )
+        .unwrap();
+        std::fs::write(v1.path().join("cpu/cpu.cfs_quota_us"), "200000\n").unwrap();
+        std::fs::write(v1.path().join("cpu/cpu.cfs_period_us"), "100000\n").unwrap();
+        assert_eq!(
+            read_cgroup_limits(v1.path(), 16 * gib),
+            Some(ContainerLimits {
+                memory_bytes: None,
+                cpus: Some(2.0),
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_environment_info() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let tool = dir.path().join("mytool");
+        std::fs::write(&tool, "#!/bin/sh\necho mytool 1.2.3\n").unwrap();
+        std::fs::write(dir.path().join("notes"), "").unwrap();
+        let path = std::env::join_paths(["/nonexistent", dir.path().to_str().unwrap()])
+            .unwrap()
+            .into_string()
+            .unwrap();
+        assert_eq!(find_program("mytool", &path), None);
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(find_program("mytool", &path), Some(tool));
+        assert_eq!(find_program("notes", &path), None);
+
+        let info = environment_info(&ToolEnv::default()).await;
+        assert_eq!(info.arch, std::env::consts::ARCH);
+        assert!(info.cpus > 0 && info.memory_total > 0);
+        assert_eq!(info.toolchains.len(), TOOLCHAINS.len());
+        // The tests run under cargo.
+        let cargo = info.toolchains.iter().find(|t| t.name == "cargo").unwrap();
+        assert!(cargo.version.as_deref().unwrap().starts_with("cargo "));
+    }
+}
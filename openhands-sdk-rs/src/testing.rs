@@ -0,0 +1,274 @@
+//! Test doubles for exercising agents end to end without a provider or a sandbox.
+//!
+//! [`TestWorkspace`] is a temporary workspace, empty or filled with a fixture tree that is
+//! the same on every run for a given seed. [`ScriptedRuntime`] answers tool calls with
+//! canned outputs and records them. Together with [`MockLLM`](crate::llm::fixtures::MockLLM)
+//! they let a test run a whole conversation hermetically.
+
+use crate::agent::tools::Tool;
+use crate::runtime::Runtime;
+use async_trait::async_trait;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::{Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Mutex;
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+/// Words the generated fixture files are made of.
+const WORDS: &[&str] = &[
+    "alpha", "bravo", "cache", "delta", "event", "field", "graph", "index", "limit", "merge",
+    "node", "parse", "queue", "range", "state", "token", "value", "write",
+];
+
+/// A temporary workspace, removed when dropped.
+pub struct TestWorkspace {
+    dir: TempDir,
+}
+
+impl TestWorkspace {
+    /// An empty workspace.
+    pub fn new() -> Self {
+        Self {
+            dir: TempDir::new().expect("Failed to create a temporary workspace"),
+        }
+    }
+
+    /// A small Rust project (`Cargo.toml`, `README.md` and `files` modules under `src/`)
+    /// whose names and contents only depend on `seed`.
+    pub fn seeded(seed: u64, files: usize) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut workspace = Self::new()
+            .with_file(
+                "Cargo.toml",
+                "[package]\nname = \"fixture\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+            )
+            .with_file("README.md", &format!("# Fixture {}\n", seed));
+        let mut modules = Vec::new();
+        for i in 0..files {
+            let module = format!("{}_{}", WORDS[rng.random_range(0..WORDS.len())], i);
+            let mut content = String::new();
+            for _ in 0..rng.random_range(1..=4) {
+                let name = WORDS[rng.random_range(0..WORDS.len())];
+                let value: u32 = rng.random_range(0..1000);
+                content.push_str(&format!(
+                    "pub fn {}_{}() -> u32 {{\n    {}\n}}\n\n",
+                    name, value, value
+                ));
+            }
+            workspace = workspace.with_file(&format!("src/{}.rs", module), content.trim_end());
+            modules.push(format!("pub mod {};", module));
+        }
+        workspace.with_file("src/lib.rs", &(modules.join("\n") + "\n"))
+    }
+
+    /// Add (or replace) the file at `path`, relative to the workspace, creating its
+    /// directories.
+    pub fn with_file(self, path: &str, content: &str) -> Self {
+        let path = self.dir.path().join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("Failed to create fixture directory");
+        }
+        std::fs::write(&path, content).expect("Failed to write fixture file");
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// The content of the file at `path`, relative to the workspace, or `None` if it is
+    /// missing.
+    pub fn read(&self, path: &str) -> Option<String> {
+        std::fs::read_to_string(self.dir.path().join(path)).ok()
+    }
+
+    /// The workspace's files, relative and sorted.
+    pub fn files(&self) -> Vec<String> {
+        let mut files: Vec<String> = WalkDir::new(self.dir.path())
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| {
+                let relative = entry.path().strip_prefix(self.dir.path()).ok()?;
+                Some(relative.to_string_lossy().to_string())
+            })
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+impl Default for TestWorkspace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A tool of a [`ScriptedRuntime`]; it only has a name, calls go to the runtime.
+struct ScriptedTool {
+    name: String,
+}
+
+#[async_trait]
+impl Tool for ScriptedTool {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn description(&self) -> String {
+        format!("Scripted {} tool", self.name)
+    }
+
+    fn parameters(&self) -> Value {
+        json!({ "type": "object" })
+    }
+
+    async fn call(&self, _args: Value) -> Result<String, String> {
+        Err(format!("{} only runs in a ScriptedRuntime", self.name))
+    }
+}
+
+/// A runtime answering tool calls with scripted outputs, in order per tool.
+#[derive(Default)]
+pub struct ScriptedRuntime {
+    tools: Vec<Box<dyn Tool>>,
+    outputs: Mutex<HashMap<String, VecDeque<Result<String, String>>>>,
+    calls: Mutex<Vec<(String, Value)>>,
+}
+
+impl ScriptedRuntime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the next call of `tool` without a scripted output yet with `output`, an
+    /// error for a failing call.
+    pub fn with_output(mut self, tool: &str, output: Result<&str, &str>) -> Self {
+        if self.tools.iter().all(|t| t.name() != tool) {
+            self.tools.push(Box::new(ScriptedTool {
+                name: tool.to_string(),
+            }));
+        }
+        self.outputs
+            .get_mut()
+            .unwrap()
+            .entry(tool.to_string())
+            .or_default()
+            .push_back(output.map(str::to_string).map_err(str::to_string));
+        self
+    }
+
+    /// The calls made so far, with their arguments.
+    pub fn calls(&self) -> Vec<(String, Value)> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Scripted outputs no call has used.
+    pub fn remaining(&self) -> usize {
+        self.outputs
+            .lock()
+            .unwrap()
+            .values()
+            .map(VecDeque::len)
+            .sum()
+    }
+}
+
+#[async_trait]
+impl Runtime for ScriptedRuntime {
+    fn tools(&self) -> &[Box<dyn Tool>] {
+        &self.tools
+    }
+
+    async fn execute(&self, action: &str, args: Value) -> Result<String, String> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push((action.to_string(), args.clone()));
+        self.outputs
+            .lock()
+            .unwrap()
+            .get_mut(action)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or_else(|| {
+                Err(format!(
+                    "ScriptedRuntime has no output left for {} (called with {})",
+                    action, args
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "llm")]
+    use crate::{
+        agent::Agent,
+        events::{Event, MessageEvent},
+        llm::fixtures::MockLLM,
+        llm::{LLM, LLMResponse, TokenUsage},
+    };
+    #[cfg(feature = "llm")]
+    use genai::chat::ToolCall;
+    #[cfg(feature = "llm")]
+    use std::sync::Arc;
+
+    #[test]
+    fn test_seeded_workspace_is_deterministic() {
+        let a = TestWorkspace::seeded(7, 3);
+        let b = TestWorkspace::seeded(7, 3);
+        assert_eq!(a.files(), b.files());
+        assert_eq!(a.files().len(), 6);
+        for file in a.files() {
+            assert_eq!(a.read(&file), b.read(&file));
+        }
+        assert_ne!(
+            TestWorkspace::seeded(8, 3).read("src/lib.rs"),
+            a.read("src/lib.rs")
+        );
+    }
+
+    #[cfg(feature = "llm")]
+    #[tokio::test]
+    async fn test_agent_with_scripted_runtime() {
+        let usage = TokenUsage::default();
+        let llm = LLM::mock(Arc::new(MockLLM::new(vec![
+            LLMResponse {
+                content: String::new(),
+                tool_calls: vec![ToolCall {
+                    call_id: "call_1".to_string(),
+                    fn_name: "cmd".to_string(),
+                    fn_arguments: json!({ "command": "cargo test" }),
+                }],
+                usage,
+                reasoning: None,
+            },
+            LLMResponse {
+                content: "The tests pass.".to_string(),
+                tool_calls: Vec::new(),
+                usage,
+                reasoning: None,
+            },
+        ])));
+        let agent = Agent::new(llm, String::new());
+        let mut runtime = ScriptedRuntime::new().with_output("cmd", Ok("test result: ok"));
+        let history = vec![Event::Message(MessageEvent {
+            source: "user".to_string(),
+            content: "Run the tests".to_string(),
+            file_refs: Vec::new(),
+        })];
+
+        let reply = agent.step(&history, &mut runtime).await.unwrap();
+        assert!(matches!(reply, Event::Message(m) if m.content == "The tests pass."));
+        assert_eq!(
+            runtime.calls(),
+            vec![("cmd".to_string(), json!({ "command": "cargo test" }))]
+        );
+        assert_eq!(runtime.remaining(), 0);
+        assert!(runtime.execute("cmd", json!({})).await.is_err());
+    }
+}